@@ -0,0 +1,291 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+#[cfg(feature = "wd-bench-alloc")]
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::error::Error;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use simple_error::SimpleError;
+use structopt::StructOpt;
+
+use crate::config::{Config, FileConfig};
+use crate::parser::{self, util};
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+/// Number of baseline differences to print before summarizing
+const MAX_CHANGES_SHOWN: usize = 20;
+
+/// Wraps the system allocator to count allocations, so `bench` can report
+/// them; only installed with the `wd-bench-alloc` feature, since it slows down
+/// every allocation
+#[cfg(feature = "wd-bench-alloc")]
+pub struct CountingAllocator;
+
+#[cfg(feature = "wd-bench-alloc")]
+unsafe impl GlobalAlloc for CountingAllocator {
+  unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+    ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+    System.alloc(layout)
+  }
+
+  unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+    System.dealloc(ptr, layout)
+  }
+
+  unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+    ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+    System.realloc(ptr, layout, new_size)
+  }
+}
+
+/// Runs the parser chain over a sample of log lines, reporting throughput and
+/// which parsers handled them
+#[derive(Debug, StructOpt)]
+#[structopt(name = "woodchipper bench", rename_all = "kebab-case")]
+pub struct BenchConfig {
+  /// A file of sample log lines
+  #[structopt(
+    name = "file",
+    parse(from_os_str),
+    raw(required_unless = r#""input""#, conflicts_with = r#""input""#)
+  )]
+  pub file: Option<PathBuf>,
+
+  /// A file of sample log lines, as an alternative to `FILE`
+  #[structopt(long, parse(from_os_str))]
+  pub input: Option<PathBuf>,
+
+  /// Number of times to parse the whole input
+  #[structopt(long, default_value = "1")]
+  pub iterations: usize,
+
+  /// Writes the format detected for each line to a file, for later use with
+  /// `--baseline`
+  #[structopt(long, parse(from_os_str))]
+  pub save_baseline: Option<PathBuf>,
+
+  /// Compares the format detected for each line against a file written by
+  /// `--save-baseline`, failing if any have changed
+  #[structopt(long, parse(from_os_str))]
+  pub baseline: Option<PathBuf>,
+
+  #[structopt(flatten)]
+  pub config: Config
+}
+
+/// Per-parser results from walking the parser chain
+#[derive(Debug, Default)]
+struct ParserStats {
+  hits: usize,
+  time: Duration
+}
+
+/// Walks the parser chain for each line, timing every parser that's tried and
+/// noting which one handled the line
+///
+/// Unlike normal parsing, pinned parsers and priority prefixes are ignored, so
+/// each parser's cost can be seen in isolation.
+fn chain_stats(
+  config: &Arc<Config>, lines: &[String], iterations: usize
+) -> Vec<(String, ParserStats)> {
+  let chain = parser::active_parsers(config);
+  let mut stats: Vec<(String, ParserStats)> = chain.iter()
+    .map(|(name, _)| (name.to_string(), ParserStats::default()))
+    .collect();
+
+  for iteration in 0..iterations {
+    for line in lines {
+      let line = util::strip_ansi(line);
+
+      for (i, (_, parser_fn)) in chain.iter().enumerate() {
+        let start = Instant::now();
+        let result = parser_fn(Arc::clone(config), &line, None);
+        stats[i].1.time += start.elapsed();
+
+        if let Ok(Some(_)) = result {
+          // hits are the same every time, so only count them once
+          if iteration == 0 {
+            stats[i].1.hits += 1;
+          }
+
+          break;
+        }
+      }
+    }
+  }
+
+  stats
+}
+
+/// The format detected for each line, e.g. `json`, or `none` if it was dropped
+fn detect_kinds(config: &Arc<Config>, lines: &[String]) -> Vec<String> {
+  lines.iter()
+    .map(|line| match parser::parse(Arc::clone(config), line, None) {
+      Ok(Some(message)) => message.kind.to_string().to_lowercase(),
+      Ok(None) => "none".to_string(),
+      Err(_) => "error".to_string()
+    })
+    .collect()
+}
+
+/// Finds lines whose detected format differs from the baseline, as
+/// `(line number, expected, actual)`
+fn compare_baseline(
+  expected: &[&str], actual: &[String]
+) -> Vec<(usize, String, String)> {
+  let len = expected.len().max(actual.len());
+
+  (0..len)
+    .filter_map(|i| {
+      let e = expected.get(i).copied().unwrap_or("missing");
+      let a = actual.get(i).map(String::as_str).unwrap_or("missing");
+
+      if e == a {
+        None
+      } else {
+        Some((i + 1, e.to_string(), a.to_string()))
+      }
+    })
+    .collect()
+}
+
+fn percent(part: f64, total: f64) -> f64 {
+  if total > 0.0 {
+    part / total * 100.0
+  } else {
+    0.0
+  }
+}
+
+pub fn run(bench: BenchConfig) -> Result<(), Box<dyn Error>> {
+  let BenchConfig { file, input, iterations, save_baseline, baseline, mut config } = bench;
+  let input = file.or(input).ok_or_else(|| SimpleError::new("no input file given"))?;
+  if config.config_file.is_none() {
+    config.config_file = FileConfig::load_default()?;
+  }
+
+  let config = Arc::new(config);
+
+  let contents = fs::read_to_string(&input).map_err(|e| SimpleError::new(
+    format!("error reading {}: {}", input.display(), e)
+  ))?;
+  let lines: Vec<String> = contents.lines().map(String::from).collect();
+  let iterations = iterations.max(1);
+
+  // full parses, including enrichment, for overall throughput
+  let allocations = ALLOCATIONS.load(Ordering::Relaxed);
+  let start = Instant::now();
+  for _ in 0..iterations {
+    for line in &lines {
+      parser::parse(Arc::clone(&config), line, None).ok();
+    }
+  }
+
+  let elapsed = start.elapsed().as_secs_f64();
+  let allocations = ALLOCATIONS.load(Ordering::Relaxed) - allocations;
+  let total = (lines.len() * iterations) as f64;
+
+  println!("lines:        {} x {} iterations", lines.len(), iterations);
+  println!("elapsed:      {:.3}s", elapsed);
+  println!("throughput:   {:.0} lines/sec", if elapsed > 0.0 { total / elapsed } else { 0.0 });
+  if cfg!(feature = "wd-bench-alloc") {
+    let per_line = if total > 0.0 { allocations as f64 / total } else { 0.0 };
+    println!("allocations:  {:.1} per line", per_line);
+  } else {
+    println!("allocations:  not counted, build with --features wd-bench-alloc");
+  }
+  println!();
+
+  let stats = chain_stats(&config, &lines, iterations);
+  let chain_time: f64 = stats.iter().map(|(_, s)| s.time.as_secs_f64()).sum();
+
+  println!("{:<10} {:>10} {:>9} {:>12} {:>7}", "parser", "hits", "hit rate", "time", "time %");
+  for (name, s) in &stats {
+    println!(
+      "{:<10} {:>10} {:>8.1}% {:>11.3}ms {:>6.1}%",
+      name, s.hits,
+      percent(s.hits as f64, lines.len() as f64),
+      s.time.as_secs_f64() * 1000.0,
+      percent(s.time.as_secs_f64(), chain_time)
+    );
+  }
+
+  let unparsed = lines.len() - stats.iter().map(|(_, s)| s.hits).sum::<usize>();
+  if unparsed > 0 {
+    println!("{:<10} {:>10} {:>8.1}%", "(none)", unparsed, percent(unparsed as f64, lines.len() as f64));
+  }
+
+  if save_baseline.is_none() && baseline.is_none() {
+    return Ok(());
+  }
+
+  let kinds = detect_kinds(&config, &lines);
+
+  if let Some(path) = &save_baseline {
+    let mut contents = kinds.join("\n");
+    contents.push('\n');
+    fs::write(path, contents)?;
+    println!("\nwrote baseline to {}", path.display());
+  }
+
+  if let Some(path) = &baseline {
+    let contents = fs::read_to_string(path)?;
+    let expected: Vec<&str> = contents.lines().collect();
+    let changes = compare_baseline(&expected, &kinds);
+
+    if !changes.is_empty() {
+      eprintln!();
+      for (number, e, a) in changes.iter().take(MAX_CHANGES_SHOWN) {
+        eprintln!("line {}: {} -> {}", number, e, a);
+      }
+
+      if changes.len() > MAX_CHANGES_SHOWN {
+        eprintln!("... and {} more", changes.len() - MAX_CHANGES_SHOWN);
+      }
+
+      bail!(format!("{} lines changed format since the baseline", changes.len()));
+    }
+
+    println!("\nall lines match the baseline");
+  }
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use spectral::prelude::*;
+
+  #[test]
+  fn test_compare_baseline() {
+    let actual = vec!["json".to_string(), "plain".to_string(), "logrus".to_string()];
+
+    assert_that!(compare_baseline(&["json", "plain", "logrus"], &actual)).is_empty();
+    assert_that!(compare_baseline(&["json", "klog"], &actual)).is_equal_to(vec![
+      (2, "klog".to_string(), "plain".to_string()),
+      (3, "missing".to_string(), "logrus".to_string())
+    ]);
+  }
+
+  #[test]
+  fn test_input() {
+    let parse = |args: &[&str]| BenchConfig::from_iter_safe(args);
+
+    let bench = parse(&["bench", "a.log", "--iterations", "2"]).unwrap();
+    assert_that!(bench.file).is_equal_to(Some(PathBuf::from("a.log")));
+    assert_that!(bench.iterations).is_equal_to(2);
+
+    let bench = parse(&["bench", "--input", "a.log"]).unwrap();
+    assert_that!(bench.input).is_equal_to(Some(PathBuf::from("a.log")));
+
+    assert_that!(parse(&["bench"]).is_err()).is_true();
+    assert_that!(parse(&["bench", "a.log", "--input", "b.log"]).is_err()).is_true();
+  }
+}
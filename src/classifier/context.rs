@@ -8,6 +8,7 @@ use super::util::clean_path;
 
 static FILE_FIELD: &str = "file";
 static CALLER_FIELD: &str = "caller";
+static LOGGER_FIELD: &str = "logger";
 
 fn context_chunk(context: String) -> Chunk {
   Chunk {
@@ -41,6 +42,10 @@ pub fn classify_context(
     fields.insert(CALLER_FIELD.to_string());
 
     ret.push(context_chunk(caller.to_string()));
+  } else if let Some(logger) = meta.get(LOGGER_FIELD).and_then(|c| c.as_str()) {
+    fields.insert(LOGGER_FIELD.to_string());
+
+    ret.push(context_chunk(logger.to_string()));
   }
 
   ret
@@ -2,9 +2,8 @@
 
 use std::collections::HashSet;
 
-use chrono::Local;
-
 use crate::parser::Message;
+use crate::timezone;
 use super::types::*;
 
 pub fn classify_timestamp(message: &Message, _fields: &mut HashSet<String>) -> Vec<Chunk> {
@@ -23,7 +22,9 @@ pub fn classify_timestamp(message: &Message, _fields: &mut HashSet<String>) -> V
   let formatted_date;
   let formatted_time;
   if let Some(timestamp) = maybe_timestamp {
-    let local = timestamp.with_timezone(&Local);
+    timezone::note_timestamp(timestamp);
+
+    let local = timezone::to_display(timestamp);
     formatted_date = local.format("%Y-%m-%d").to_string();
     formatted_time = local.format("%H:%M:%S").to_string();
   } else {
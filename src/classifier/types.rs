@@ -14,9 +14,19 @@ pub enum ChunkKind {
 
   Date,
   Time,
+
+  /// The time elapsed since the previously displayed message
+  Delta,
+
   Text,
   Context,
 
+  /// The number of consecutive repeats collapsed into a message
+  Repeat,
+
+  /// The number of lines hidden from a collapsed multi-line message
+  Collapsed,
+
   /// A chunk containing an arbitrary key/value pair
   Field,
 
@@ -1,5 +1,10 @@
 // (C) Copyright 2019 Hewlett Packard Enterprise Development LP
 
+use std::env;
+use std::io::{self, Write};
+use std::str::FromStr;
+use std::sync::RwLock;
+
 use simple_error::{SimpleError, SimpleResult};
 
 #[cfg(target_env = "musl")]
@@ -37,21 +42,140 @@ fn clip_xclip(text: String) -> SimpleResult<()> {
   }
 }
 
-pub fn clip(text: String) -> SimpleResult<()> {
-  if !clipboard_enabled() {
-    return Ok(());
+/// How text is copied to the clipboard, i.e. `--clipboard`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ClipboardMode {
+  /// OSC 52 over SSH, otherwise the system clipboard, falling back to OSC 52
+  Auto,
+
+  /// The system clipboard only
+  System,
+
+  /// OSC 52 escapes only, written to the terminal
+  Osc52
+}
+
+impl FromStr for ClipboardMode {
+  type Err = SimpleError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "auto" => Ok(ClipboardMode::Auto),
+      "system" => Ok(ClipboardMode::System),
+      "osc52" => Ok(ClipboardMode::Osc52),
+      _ => bail!("invalid clipboard mode: {}", s)
+    }
+  }
+}
+
+lazy_static! {
+  static ref MODE: RwLock<ClipboardMode> = RwLock::new(ClipboardMode::Auto);
+}
+
+/// Sets how text is copied, i.e. from `--clipboard`
+///
+/// Like the display timezone, this is global as copying happens far from the
+/// config.
+pub fn set_clipboard_mode(mode: ClipboardMode) {
+  if let Ok(mut current) = MODE.write() {
+    *current = mode;
+  }
+}
+
+fn mode() -> ClipboardMode {
+  MODE.read().map(|m| *m).unwrap_or(ClipboardMode::Auto)
+}
+
+static BASE64: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+fn base64(data: &[u8]) -> String {
+  let mut ret = String::with_capacity((data.len() + 2) / 3 * 4);
+
+  for chunk in data.chunks(3) {
+    let b = [chunk[0], *chunk.get(1).unwrap_or(&0), *chunk.get(2).unwrap_or(&0)];
+    let n = (b[0] as usize) << 16 | (b[1] as usize) << 8 | b[2] as usize;
+
+    for i in 0..4 {
+      if i <= chunk.len() {
+        ret.push(BASE64[n >> (18 - 6 * i) & 0x3f] as char);
+      } else {
+        ret.push('=');
+      }
+    }
+  }
+
+  ret
+}
+
+/// Copies text with an OSC 52 escape, which terminals (including over SSH)
+/// may pass to the local clipboard
+///
+/// Not all terminals support this, and there's no way to tell if it worked.
+/// Inside tmux, the escape is wrapped so tmux passes it through.
+fn clip_osc52(text: String) -> SimpleResult<()> {
+  let escape = format!("\x1b]52;c;{}\x07", base64(text.as_bytes()));
+  let escape = if env::var_os("TMUX").is_some() {
+    format!("\x1bPtmux;{}\x1b\\", escape.replace('\x1b', "\x1b\x1b"))
+  } else {
+    escape
+  };
+
+  let mut stdout = io::stdout();
+  stdout.write_all(escape.as_bytes())
+    .and_then(|_| stdout.flush())
+    .map_err(SimpleError::from)
+}
+
+fn clip_system(text: String) -> SimpleResult<()> {
+  if !cfg!(feature = "wd-clipboard") {
+    bail!("built without clipboard support");
   }
 
   #[cfg(target_env = "musl")]
   let clip_fn = clip_xclip;
- 
+
   #[cfg(not(target_env = "musl"))]
   let clip_fn = clip_all;
 
   clip_fn(text)
 }
 
-#[inline]
+/// If true, this is probably an SSH session, so the system clipboard (if any)
+/// isn't the user's
+fn is_ssh() -> bool {
+  env::var_os("SSH_TTY").is_some() || env::var_os("SSH_CONNECTION").is_some()
+}
+
+pub fn clip(text: String) -> SimpleResult<()> {
+  if !clipboard_enabled() {
+    return Ok(());
+  }
+
+  match mode() {
+    ClipboardMode::System => clip_system(text),
+    ClipboardMode::Osc52 => clip_osc52(text),
+    ClipboardMode::Auto if is_ssh() => clip_osc52(text),
+    ClipboardMode::Auto => clip_system(text.clone()).or_else(|_| clip_osc52(text))
+  }
+}
+
+/// If true, text can be copied, either to the system clipboard or with OSC 52
 pub fn clipboard_enabled() -> bool {
-  cfg!(feature = "wd-clipboard")
+  cfg!(feature = "wd-clipboard") || mode() != ClipboardMode::System
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use spectral::prelude::*;
+
+  #[test]
+  fn test_base64() {
+    assert_that!(base64(b"")).is_equal_to(String::new());
+    assert_that!(base64(b"f")).is_equal_to("Zg==".to_string());
+    assert_that!(base64(b"fo")).is_equal_to("Zm8=".to_string());
+    assert_that!(base64(b"foo")).is_equal_to("Zm9v".to_string());
+    assert_that!(base64(b"hello, world")).is_equal_to("aGVsbG8sIHdvcmxk".to_string());
+  }
 }
@@ -1,59 +1,85 @@
 // (C) Copyright 2019 Hewlett Packard Enterprise Development LP
 
+use std::collections::{BTreeMap, HashMap};
+use std::env;
 use std::error::Error;
 use std::fmt;
-use std::fs::File;
+use std::fs::{self, File};
 use std::io::BufReader;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::sync::Arc;
 
 use atty::{self, Stream};
+use chrono::{FixedOffset, NaiveDateTime, TimeZone};
 use regex::Regex;
 use serde::Deserialize;
 use serde::de::{self, Visitor, Deserializer};
 use shellexpand;
 use simple_error::SimpleError;
 use structopt::StructOpt;
+use structopt::clap::{ArgMatches, ErrorKind};
 
-use crate::style::StyleConfig;
+use crate::style::{Palette, StyleConfig, StyleRule};
+use crate::timezone::{DisplayZone, RelativeTime};
+use crate::parser::{self, LogLevel, Parser, parse_size_bytes};
+use crate::parser::classify::ClassifyRule;
+use crate::parser::protobuf::Descriptors;
 use crate::reader;
+use crate::reader::filter::{DropRule, SampleRate};
+use crate::reader::limit::LimitRule;
+use crate::reader::redact::RedactConfig;
+use crate::reader::trigger::TriggerRule;
+use crate::reader::sink::Sink;
+use crate::reader::split::PathTemplate;
+use crate::clip::ClipboardMode;
 use crate::renderer;
+use crate::renderer::glyphs::GlyphMode;
+use crate::renderer::interactive::keymap::Keymap;
+use crate::renderer::layout::{Breakpoints, Density, WidthLayout};
+use crate::renderer::markdown::MarkdownFormat;
+use crate::renderer::projection::Projection;
+use crate::renderer::template::Template;
+use crate::renderer::registry::{self, RendererInfo};
+use crate::subcommand;
 
-#[derive(Debug)]
+/// A renderer selected by name from the registry, or `auto`
+#[derive(Debug, Clone, PartialEq)]
 pub enum RendererType {
   Auto,
-  Plain,
-  Raw,
-  Json,
-  Styled,
-  Interactive
+  Named(String)
+}
+
+fn named_renderer(name: &str) -> RendererInfo {
+  // names are checked when parsed, but may be constructed directly
+  registry::find(name)
+    .or_else(|| registry::find("plain"))
+    .expect("the plain renderer is always registered")
 }
 
-fn get_auto_renderer(config: Arc<Config>) -> renderer::Renderer {
+fn get_auto_renderer(config: &Config) -> RendererInfo {
   // probably best not to infinitely loop
-  let preferred = match config.preferred_renderer {
-    RendererType::Auto => renderer::interactive_renderer,
-    _ => {
-      config.preferred_renderer.get_renderer(Arc::clone(&config))
-    }
+  let preferred = match &config.preferred_renderer {
+    RendererType::Auto => named_renderer("interactive"),
+    RendererType::Named(name) => named_renderer(name)
   };
 
-  if atty::is(Stream::Stdout) {
+  let capabilities = preferred.capabilities;
+  if atty::is(Stream::Stdout) || !(capabilities.styled || capabilities.needs_tty) {
     preferred
+  } else if config.color == ColorMode::Always {
+    // e.g. for piping into `less -R`
+    named_renderer("styled")
   } else {
-    renderer::plain_renderer
+    named_renderer("plain")
   }
 }
 
 impl RendererType {
-  pub fn get_renderer(&self, config: Arc<Config>) -> renderer::Renderer {
-    match *self {
+  pub fn get_renderer(&self, config: &Config) -> RendererInfo {
+    match self {
       RendererType::Auto => get_auto_renderer(config),
-      RendererType::Plain => renderer::plain_renderer,
-      RendererType::Raw => renderer::raw_renderer,
-      RendererType::Json => renderer::json_renderer,
-      RendererType::Styled => renderer::styled_renderer,
-      RendererType::Interactive => renderer::interactive_renderer,
+      RendererType::Named(name) => named_renderer(name)
     }
   }
 }
@@ -64,17 +90,28 @@ impl FromStr for RendererType {
   fn from_str(s: &str) -> Result<Self, Self::Err> {
     match s {
       "auto" => Ok(RendererType::Auto),
-      "plain" => Ok(RendererType::Plain),
-      "raw" => Ok(RendererType::Raw),
-      "json" => Ok(RendererType::Json),
-      "styled" => Ok(RendererType::Styled),
-      "interactive" => Ok(RendererType::Interactive),
-      _ => bail!(format!("invalid renderer type: {}", s))
+      name if registry::find(name).is_some() => Ok(RendererType::Named(name.to_string())),
+      _ => bail!(format!(
+        "invalid renderer type: {} (expected one of: auto, {})",
+        s, registry::names().join(", ")
+      ))
     }
   } 
 }
 
 fn get_auto_reader(config: Arc<Config>) -> reader::Reader {
+  if config.open.is_some() {
+    return reader::read_session;
+  }
+
+  if !config.exec.is_empty() {
+    return reader::read_exec;
+  }
+
+  if config.listen.is_some() {
+    return reader::read_listen;
+  }
+
   // TODO: is it possible to tell if stdin has some input?
   // TODO: consider detecting if k8s based on args and kubernetes::is_selector?
   if !atty::is(Stream::Stdin) {
@@ -99,8 +136,13 @@ pub enum ReaderType {
   Stdin,
   Hack,
   Kubernetes,
-  Null
-  //Subprocess
+  Msgpack,
+  Protobuf,
+  Null,
+  Exec,
+  Session,
+  File,
+  Listen
 }
 
 impl ReaderType {
@@ -110,8 +152,13 @@ impl ReaderType {
       ReaderType::Stdin => reader::read_stdin,
       ReaderType::Hack => reader::read_stdin_hack,
       ReaderType::Kubernetes => reader::read_kubernetes_selector,
-      ReaderType::Null => reader::read_null
-      //ReaderType::Subprocess => ...
+      ReaderType::Msgpack => reader::read_msgpack,
+      ReaderType::Protobuf => reader::read_protobuf,
+      ReaderType::Null => reader::read_null,
+      ReaderType::Exec => reader::read_exec,
+      ReaderType::Session => reader::read_session,
+      ReaderType::File => reader::read_file,
+      ReaderType::Listen => reader::read_listen
     }
   }
 }
@@ -125,7 +172,13 @@ impl FromStr for ReaderType {
       "stdin" => Ok(ReaderType::Stdin),
       "hack" => Ok(ReaderType::Hack),
       "kubernetes" | "k8s" => Ok(ReaderType::Kubernetes),
+      "msgpack" => Ok(ReaderType::Msgpack),
+      "protobuf" | "proto" => Ok(ReaderType::Protobuf),
       "null" => Ok(ReaderType::Null),
+      "exec" | "subprocess" => Ok(ReaderType::Exec),
+      "session" => Ok(ReaderType::Session),
+      "file" => Ok(ReaderType::File),
+      "listen" => Ok(ReaderType::Listen),
       _ => bail!(format!("invalid reader type: {}", s))
     }
   }
@@ -145,6 +198,10 @@ pub struct KubernetesConfig {
   #[structopt(long, short = "n", env = "WD_NAMESPACE")]
   pub namespace: Option<String>,
 
+  /// The kubeconfig context to use, if not the current context
+  #[structopt(long, env = "WD_CONTEXT")]
+  pub context: Option<String>,
+
   /// Local kubernetes proxy port
   /// 
   /// A kubernetes API proxy will be spawned on this port over the loopback
@@ -179,7 +236,7 @@ impl<'de> Visitor<'de> for RegexFromStr {
   }
 }
 
-fn de_regex<'de, D>(deserializer: D) -> Result<Regex, D::Error>
+pub fn de_regex<'de, D>(deserializer: D) -> Result<Regex, D::Error>
 where
   D: Deserializer<'de>
 {
@@ -227,86 +284,1928 @@ impl FromStr for RegexConfig {
   }
 }
 
-#[derive(Debug, StructOpt)]
-#[structopt(
-  name = "woodchipper",
-  rename_all = "kebab-case",
-  raw(setting = "structopt::clap::AppSettings::ColoredHelp")
-)]
-pub struct Config {
-  /// Renderer to use, one of: auto, plain, json, styled, interactive
-  /// 
-  /// If auto, will is determined by terminal and whether or not output will be
-  /// redirected. Automatic preference may be overridden with
-  /// --preferred-renderer, otherwise --renderer will force use of the given
-  /// renderer even if unsupported.
-  #[structopt(long, short, default_value = "auto", env = "WD_RENDERER")]
-  pub renderer: RendererType,
+/// How ANSI escape sequences in input are handled
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AnsiMode {
+  /// Sequences are removed entirely
+  Strip,
 
-  /// Preferred renderer, one of: plain, json, styled, interactive
-  ///
-  /// When --renderer=auto, this controls the preferred default renderer if no
-  /// conditions exist that would otherwise select a different renderer.
-  /// 
-  /// For example, if you dislike the interactive renderer but still wish to
-  /// automatically fall back to plaintext output when piped, use
-  /// --preferred-renderer=styled.
-  #[structopt(long, default_value = "interactive", env = "WD_PREFERRED_RENDERER")]
-  pub preferred_renderer: RendererType,
+  /// Sequences are removed for parsing, but plaintext messages are shown with
+  /// their original colors by the plain renderer
+  Keep
+}
 
-  /// Reader to use, one of: auto, stdin, hack, kubernetes
-  ///
-  /// If auto, reader will be determined selected based on OS and renderer.
-  ///{n}{n}
-  /// - `stdin` reads from standard input{n}
-  /// - `hack` reads from /dev/stdin to allow the interactive renderer to work{n}
-  /// - `kubernetes` continuously follows Kubernetes pods{n}
-  /// - `auto` selects `hack` on unix, unless some Kubernetes flag is set
-  #[structopt(long, short = "i", default_value = "auto", env = "WD_READER")]
-  pub reader: ReaderType,
+impl FromStr for AnsiMode {
+  type Err = Box<dyn Error>;
 
-  /// Kubernetes selector or subprocess args from which to capture log output.
-  /// If unset, assumes logs will be read from standard input.
-  pub app: Vec<String>,
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "strip" => Ok(AnsiMode::Strip),
+      "keep" => Ok(AnsiMode::Keep),
+      _ => bail!(format!("invalid ansi mode: {}", s))
+    }
+  }
+}
 
-  /// Fallback width for the styled renderer if no tty is detected
-  ///
-  /// Note that the plaintext renderer is recommended in most cases where
-  /// terminal size is unavailable.
-  #[structopt(
-    long,
-    short = "w",
-    default_value = "120",
-    env = "WD_FALLBACK_WIDTH"
-  )]
-  pub fallback_width: usize,
+/// When to page the output of the styled and plain renderers
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PagingMode {
+  /// Output is paged if stdout is a terminal
+  Auto,
+  Always,
+  Never
+}
 
-  /// Styled output configuration
-  ///
-  /// Must contain one of the following: `default`, `base16:<path to .yaml>`
-  #[structopt(long, short = "s", default_value = "default", env = "WD_STYLE")]
-  pub style: StyleConfig,
+impl PagingMode {
+  pub fn enabled(self) -> bool {
+    match self {
+      PagingMode::Auto => atty::is(Stream::Stdout),
+      PagingMode::Always => true,
+      PagingMode::Never => false
+    }
+  }
+}
 
-  /// A path to a regexes config file, which may contain custom parsing regexes
-  /// for application-specific log formats.
-  #[structopt(long, env = "WD_REGEXES")]
-  pub regexes: Option<RegexConfig>,
+impl FromStr for PagingMode {
+  type Err = Box<dyn Error>;
 
-  /// If set, attempts to ensure messages are displayed in semantic order by
-  /// placing them in a priority queue for a short period before being written
-  /// to the renderer. By default, messages are held for one second; this can be
-  /// overridden with `--buffer-ms`.
-  ///
-  /// Particularly useful when paired with readers that ingest from multiple
-  /// sources at once, like the Kubernetes reader.
-  #[structopt(long, short = "o")]
-  pub ordered: bool,
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "auto" => Ok(PagingMode::Auto),
+      "always" => Ok(PagingMode::Always),
+      "never" => Ok(PagingMode::Never),
+      _ => bail!(format!("invalid paging mode: {}", s))
+    }
+  }
+}
 
-  /// Sets the length of time, in milliseconds, that messages should be buffered
-  /// to attempt to reorder them. If set, implies `--ordered`.
-  #[structopt(long)]
-  pub buffer_ms: Option<u64>,
+/// When the styled and interactive renderers link URLs and fields
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HyperlinkMode {
+  /// Links are used if colors are, and the terminal is known to support them
+  Auto,
+  Always,
+  Never
+}
 
-  #[structopt(flatten)]
-  pub kubernetes: KubernetesConfig
+impl HyperlinkMode {
+  pub fn enabled(self, color: ColorMode) -> bool {
+    match self {
+      HyperlinkMode::Auto => color.enabled() && renderer::hyperlink::supported(),
+      HyperlinkMode::Always => true,
+      HyperlinkMode::Never => false
+    }
+  }
+}
+
+impl FromStr for HyperlinkMode {
+  type Err = Box<dyn Error>;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "auto" => Ok(HyperlinkMode::Auto),
+      "always" => Ok(HyperlinkMode::Always),
+      "never" => Ok(HyperlinkMode::Never),
+      _ => bail!(format!("invalid hyperlink mode: {}", s))
+    }
+  }
+}
+
+/// The format of messages written by `--tee` and `--split`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TeeFormat {
+  /// As shown by the plain renderer
+  Plain,
+
+  /// As written by the json renderer
+  Json
+}
+
+impl FromStr for TeeFormat {
+  type Err = Box<dyn Error>;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "plain" => Ok(TeeFormat::Plain),
+      "json" => Ok(TeeFormat::Json),
+      _ => bail!(format!("invalid tee format: {}", s))
+    }
+  }
+}
+
+/// A size in bytes, e.g. `10M` or `512KiB`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ByteSize(pub u64);
+
+impl FromStr for ByteSize {
+  type Err = SimpleError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    if let Ok(bytes) = s.parse() {
+      return Ok(ByteSize(bytes));
+    }
+
+    match parse_size_bytes(s) {
+      Some(bytes) => Ok(ByteSize(bytes as u64)),
+      None => bail!("invalid size, expected e.g. 10M or 512KiB: {}", s)
+    }
+  }
+}
+
+/// When to use colors in output
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorMode {
+  /// Colors are used if stdout is a terminal, unless `NO_COLOR` is set
+  Auto,
+  Always,
+  Never
+}
+
+impl ColorMode {
+  /// If false, colors must not be used in any output
+  pub fn allowed(self) -> bool {
+    match self {
+      ColorMode::Always => true,
+      ColorMode::Never => false,
+      ColorMode::Auto => match env::var_os("NO_COLOR") {
+        Some(value) => value.is_empty(),
+        None => true
+      }
+    }
+  }
+
+  /// If true, styled output should be colored
+  pub fn enabled(self) -> bool {
+    match self {
+      ColorMode::Auto => self.allowed() && atty::is(Stream::Stdout),
+      mode => mode.allowed()
+    }
+  }
+}
+
+impl FromStr for ColorMode {
+  type Err = Box<dyn Error>;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "auto" => Ok(ColorMode::Auto),
+      "always" => Ok(ColorMode::Always),
+      "never" => Ok(ColorMode::Never),
+      _ => bail!(format!("invalid color mode: {}", s))
+    }
+  }
+}
+
+/// The number of colors the terminal can display
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColorDepth {
+  /// Detected from the environment, see `detect()`
+  Auto,
+
+  /// The 16 standard ANSI colors
+  Ansi16,
+
+  /// The xterm 256-color palette
+  Ansi256,
+
+  /// 24-bit RGB colors
+  Truecolor
+}
+
+impl ColorDepth {
+  /// Resolves `Auto` using `COLORTERM` and `TERM`, as terminfo databases
+  /// rarely advertise truecolor support
+  pub fn detect(self) -> ColorDepth {
+    if self != ColorDepth::Auto {
+      return self;
+    }
+
+    let colorterm = env::var("COLORTERM").unwrap_or_default().to_lowercase();
+    if colorterm == "truecolor" || colorterm == "24bit" {
+      return ColorDepth::Truecolor;
+    }
+
+    let term = env::var("TERM").unwrap_or_default().to_lowercase();
+    if term.contains("truecolor") || term.contains("direct") {
+      ColorDepth::Truecolor
+    } else if term.contains("256color") {
+      ColorDepth::Ansi256
+    } else {
+      ColorDepth::Ansi16
+    }
+  }
+}
+
+impl FromStr for ColorDepth {
+  type Err = Box<dyn Error>;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "auto" => Ok(ColorDepth::Auto),
+      "16" => Ok(ColorDepth::Ansi16),
+      "256" => Ok(ColorDepth::Ansi256),
+      "truecolor" | "24bit" => Ok(ColorDepth::Truecolor),
+      _ => bail!(format!("invalid color depth: {}", s))
+    }
+  }
+}
+
+fn parse_level(s: &str) -> Result<LogLevel, SimpleError> {
+  s.parse().map_err(|_| SimpleError::new(format!(
+    "invalid log level: {}, expected one of: debug, info, warn, error, fatal", s
+  )))
+}
+
+fn get_named_parser(name: &str) -> Result<Parser, SimpleError> {
+  parser::get_parser(name).ok_or_else(|| SimpleError::new(format!(
+    "invalid parser: {}, expected one of: {}",
+    name, parser::parser_names().join(", ")
+  )))
+}
+
+/// A parser forced for all sources, or sources matching a regex
+#[derive(Debug)]
+pub struct ParserPin {
+  pub source: Option<Regex>,
+  pub parser: Parser
+}
+
+impl FromStr for ParserPin {
+  type Err = SimpleError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s.rfind('=') {
+      Some(index) => Ok(ParserPin {
+        source: Some(Regex::new(&s[..index]).map_err(SimpleError::from)?),
+        parser: get_named_parser(&s[index + 1..])?
+      }),
+      None => Ok(ParserPin {
+        source: None,
+        parser: get_named_parser(s)?
+      })
+    }
+  }
+}
+
+/// An ordered subset of parsers to try, by name
+#[derive(Debug)]
+pub struct ParserChain {
+  pub parsers: Vec<(String, Parser)>
+}
+
+impl FromStr for ParserChain {
+  type Err = SimpleError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let parsers = s.split(',')
+      .map(str::trim)
+      .filter(|name| !name.is_empty())
+      .map(|name| get_named_parser(name).map(|parser| (name.to_string(), parser)))
+      .collect::<Result<Vec<_>, SimpleError>>()?;
+
+    Ok(ParserChain { parsers })
+  }
+}
+
+/// A timezone used to interpret timestamps that don't include an offset
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Zone {
+  Local,
+  Utc,
+  Fixed(FixedOffset)
+}
+
+impl Zone {
+  /// Converts a naive datetime in this zone into UTC
+  pub fn to_utc(self, naive: &NaiveDateTime) -> Option<chrono::DateTime<chrono::Utc>> {
+    let utc = match self {
+      Zone::Local => chrono::Local.from_local_datetime(naive).earliest()?.naive_utc(),
+      Zone::Utc => *naive,
+      Zone::Fixed(offset) => offset.from_local_datetime(naive).earliest()?.naive_utc()
+    };
+
+    Some(chrono::Utc.from_utc_datetime(&utc))
+  }
+}
+
+impl FromStr for Zone {
+  type Err = SimpleError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    lazy_static! {
+      static ref OFFSET_RE: Regex = Regex::new(
+        r"^(?:UTC)?([+-])(\d{1,2})(?::?(\d{2}))?$"
+      ).unwrap();
+    }
+
+    match s.to_lowercase().as_str() {
+      "local" => return Ok(Zone::Local),
+      "utc" | "z" => return Ok(Zone::Utc),
+      _ => ()
+    };
+
+    let caps = OFFSET_RE.captures(s).ok_or_else(|| SimpleError::new(
+      format!("invalid timezone, expected local, utc, or an offset: {}", s)
+    ))?;
+
+    let hours: i32 = caps[2].parse().map_err(SimpleError::from)?;
+    let minutes: i32 = caps.get(3).map_or(Ok(0), |m| m.as_str().parse())
+      .map_err(SimpleError::from)?;
+
+    let seconds = (hours * 3600 + minutes * 60) * if &caps[1] == "-" { -1 } else { 1 };
+    FixedOffset::east_opt(seconds)
+      .map(Zone::Fixed)
+      .ok_or_else(|| SimpleError::new(format!("invalid timezone offset: {}", s)))
+  }
+}
+
+impl<'de> Deserialize<'de> for Zone {
+  fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+  where
+    D: Deserializer<'de>
+  {
+    let s = String::deserialize(deserializer)?;
+    s.parse().map_err(de::Error::custom)
+  }
+}
+
+/// Timestamp parsing options, consulted by all parsers
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct TimestampConfig {
+  /// Additional Chrono format strings to try when parsing timestamps
+  pub formats: Vec<String>,
+
+  /// The timezone for timestamps without an offset; if unset, each parser
+  /// uses its own default (usually UTC)
+  pub timezone: Option<Zone>,
+
+  /// The year for timestamp formats that omit one; if unset, the current year
+  /// is used
+  pub year: Option<i32>
+}
+
+/// How arrays are flattened in nested JSON
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ArrayStyle {
+  /// `key.0`, using the configured separator
+  Index,
+
+  /// `key[0]`
+  Bracket,
+
+  /// arrays are left as JSON
+  Keep
+}
+
+/// Nested JSON flattening options
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct FlattenConfig {
+  /// The number of levels of nested objects to flatten into separate fields;
+  /// anything deeper is kept as JSON. 0 disables flattening.
+  pub depth: usize,
+
+  /// Separator between flattened keys
+  pub separator: String,
+
+  pub arrays: ArrayStyle
+}
+
+impl Default for FlattenConfig {
+  fn default() -> Self {
+    FlattenConfig {
+      depth: 0,
+      separator: ".".to_string(),
+      arrays: ArrayStyle::Keep
+    }
+  }
+}
+
+/// User-defined level names, mapped onto log levels
+///
+/// Configured as a list of synonyms per level, e.g.:
+///
+/// ```yaml
+/// levels:
+///   warning: [WRN, 30]
+///   info: [notice]
+/// ```
+#[derive(Debug, Default)]
+pub struct LevelSynonyms {
+  /// Lowercase synonyms and their levels
+  pub synonyms: HashMap<String, LogLevel>,
+
+  /// Matches any synonym as a whole word, for plaintext messages
+  pub pattern: Option<Regex>
+}
+
+impl LevelSynonyms {
+  /// Finds the level for a (case insensitive) synonym
+  pub fn get(&self, name: &str) -> Option<LogLevel> {
+    if self.synonyms.is_empty() {
+      return None;
+    }
+
+    self.synonyms.get(&name.to_lowercase()).cloned()
+  }
+
+  /// Finds the level for the first synonym appearing in some text
+  pub fn find(&self, text: &str) -> Option<LogLevel> {
+    let mat = self.pattern.as_ref()?.find(text)?;
+    self.get(mat.as_str())
+  }
+}
+
+impl<'de> Deserialize<'de> for LevelSynonyms {
+  fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+  where
+    D: Deserializer<'de>
+  {
+    let raw: HashMap<String, Vec<serde_yaml::Value>> = HashMap::deserialize(deserializer)?;
+
+    let mut synonyms = HashMap::new();
+    for (level, names) in raw {
+      let level: LogLevel = level.parse()
+        .map_err(|_| de::Error::custom(format!("invalid log level: {}", level)))?;
+
+      for name in names {
+        let name = match name {
+          serde_yaml::Value::String(s) => s,
+          serde_yaml::Value::Number(n) => n.to_string(),
+          other => return Err(de::Error::custom(
+            format!("invalid level synonym: {:?}", other)
+          ))
+        };
+
+        synonyms.insert(name.to_lowercase(), level);
+      }
+    }
+
+    let pattern = if synonyms.is_empty() {
+      None
+    } else {
+      let alternatives: Vec<String> = synonyms.keys().map(|s| regex::escape(s)).collect();
+      let pattern = format!(r"(?i)\b(?:{})\b", alternatives.join("|"));
+      Some(Regex::new(&pattern).map_err(de::Error::custom)?)
+    };
+
+    Ok(LevelSynonyms { synonyms, pattern })
+  }
+}
+
+/// Which fields the styled and interactive renderers display, e.g.:
+///
+/// ```yaml
+/// fields:
+///   show: [pod, trace_id]
+///   hide: [logger]
+///   max_width: 40
+///   max_widths:
+///     request_body: 120
+/// ```
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct FieldSelection {
+  /// If set, only these fields are displayed, in this order
+  pub show: Vec<String>,
+
+  /// Fields that are never displayed
+  pub hide: Vec<String>,
+
+  /// Field values longer than this are truncated, unless the field has its own
+  /// width in `max_widths`
+  pub max_width: Option<usize>,
+
+  /// Maximum widths for particular fields
+  pub max_widths: HashMap<String, usize>
+}
+
+impl FieldSelection {
+  /// If true, some field values may be truncated
+  pub fn truncates(&self) -> bool {
+    self.max_width.is_some() || !self.max_widths.is_empty()
+  }
+
+  /// The maximum display width of a field's value, if any
+  pub fn max_width_for(&self, key: &str) -> Option<usize> {
+    self.max_widths.get(key).copied().or(self.max_width)
+  }
+}
+
+/// A maximum field width from `--max-field-width`, either a default like `40`
+/// or for a particular field, like `request_body=120`
+#[derive(Debug, Clone, PartialEq)]
+pub struct FieldWidth {
+  pub field: Option<String>,
+  pub width: usize
+}
+
+impl FromStr for FieldWidth {
+  type Err = SimpleError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let (field, width) = match s.rfind('=') {
+      Some(i) => (Some(s[..i].trim().to_string()), &s[i + 1..]),
+      None => (None, s)
+    };
+
+    match width.trim().parse() {
+      Ok(width) => Ok(FieldWidth { field, width }),
+      Err(_) => bail!("invalid field width, expected e.g. 40 or field=40: {}", s)
+    }
+  }
+}
+
+#[derive(Default, Deserialize)]
+#[serde(default)]
+struct RuleSections {
+  style: Vec<StyleRule>,
+  drop: Vec<DropRule>,
+  limit: Vec<LimitRule>,
+  trigger: Vec<TriggerRule>,
+  classify: Vec<ClassifyRule>
+}
+
+/// The config file's `rules`: either a list of style rules, or `style`,
+/// `drop`, `limit`, `trigger`, and `classify` sections, e.g.:
+///
+/// ```yaml
+/// rules:
+///   style:
+///     - field: status
+///       gte: 500
+///       style: red
+///   drop:
+///     - message: '^GET /healthz'
+///   limit:
+///     - message: 'connection refused'
+///       max: 5
+/// ```
+#[derive(Debug, Default)]
+pub struct Rules {
+  pub style: Vec<StyleRule>,
+  pub drop: Vec<DropRule>,
+  pub limit: Vec<LimitRule>,
+  pub trigger: Vec<TriggerRule>,
+  pub classify: Vec<ClassifyRule>
+}
+
+impl<'de> Deserialize<'de> for Rules {
+  fn deserialize<D>(deserializer: D) -> Result<Rules, D::Error>
+  where
+    D: Deserializer<'de>
+  {
+    let value = serde_yaml::Value::deserialize(deserializer)?;
+    if value.is_sequence() {
+      let style = serde_yaml::from_value(value).map_err(de::Error::custom)?;
+      return Ok(Rules { style, ..Default::default() });
+    }
+
+    let sections: RuleSections = serde_yaml::from_value(value).map_err(de::Error::custom)?;
+    Ok(Rules {
+      style: sections.style,
+      drop: sections.drop,
+      limit: sections.limit,
+      trigger: sections.trigger,
+      classify: sections.classify
+    })
+  }
+}
+
+/// Values for command-line flags by name, e.g. `renderer: styled`; lists set a
+/// flag several times, `true` sets a switch, and `app` holds the positional
+/// selector or command
+pub type FlagValues = BTreeMap<String, serde_yaml::Value>;
+
+/// Flags that can't come from the config file, since they choose it
+static FILE_ONLY_FLAGS: &[&str] = &["config", "profile", "no-project-config"];
+
+/// The name of a project's config file, found in the working directory or the
+/// nearest one above it, e.g. at the root of a repository
+pub static PROJECT_CONFIG: &str = ".woodchipper.yaml";
+
+/// A project config file in TOML; in the same directory, a `.woodchipper.yaml`
+/// takes precedence
+pub static PROJECT_TOML: &str = ".woodchipper.toml";
+
+/// Sections whose lists a project's config file adds to, rather than replaces
+static COMBINED_SECTIONS: &[&str] = &["rules", "redact"];
+
+/// Flags a project's config file can't set, since they run commands or write
+/// files, and a shared repository isn't necessarily trusted; this includes
+/// `app`, which `woodchipper exec` would run
+static UNTRUSTED_FLAGS: &[&str] = &[
+  "app", "exec", "run", "pager", "kubectl", "reader", "tee", "sink", "split", "listen"
+];
+
+/// Converts flag values to command-line arguments, skipping flags `matches`
+/// already has; returns the flags and any positional `app` arguments
+fn flag_args(
+  values: &FlagValues, matches: &ArgMatches
+) -> Result<(Vec<String>, Vec<String>), SimpleError> {
+  let mut flags = Vec::new();
+  let mut app = Vec::new();
+
+  for (name, value) in values {
+    if FILE_ONLY_FLAGS.contains(&name.as_str()) {
+      bail!("{} can't be set in the config file", name);
+    }
+
+    if matches.occurrences_of(name) > 0 {
+      continue;
+    }
+
+    let scalars = match value {
+      serde_yaml::Value::Sequence(values) => values.clone(),
+      value => vec![value.clone()]
+    };
+
+    for scalar in scalars {
+      let scalar = match scalar {
+        serde_yaml::Value::Null | serde_yaml::Value::Bool(false) => continue,
+        serde_yaml::Value::Bool(true) if name != "app" => {
+          flags.push(format!("--{}", name));
+          continue;
+        },
+        serde_yaml::Value::String(s) => s,
+        serde_yaml::Value::Bool(b) => b.to_string(),
+        serde_yaml::Value::Number(n) => n.to_string(),
+        _ => bail!("invalid value for {} in the config file, expected text or a list", name)
+      };
+
+      if name == "app" {
+        app.push(scalar);
+      } else {
+        flags.push(format!("--{}={}", name, scalar));
+      }
+    }
+  }
+
+  Ok((flags, app))
+}
+
+/// The prefix of environment variables setting flags, e.g.
+/// `WOODCHIPPER_RENDERER=plain` for `--renderer=plain`
+static ENV_PREFIX: &str = "WOODCHIPPER_";
+
+/// The older prefix of environment variables setting flags, an alias of
+/// `ENV_PREFIX`
+static OLD_ENV_PREFIX: &str = "WD_";
+
+/// Older environment variables not named after their flags
+static OLD_ENV_NAMES: &[(&str, &str)] = &[
+  ("WD_K8S_PORT", "port"),
+  ("WD_K8S_POLL_INTERVAL", "poll-interval")
+];
+
+/// Returns true if `name` is the name of a flag
+fn is_flag(name: &str) -> bool {
+  let args = ["woodchipper".to_string(), format!("--{}", name)];
+  match Config::clap().get_matches_from_safe(&args) {
+    Err(e) => e.kind != ErrorKind::UnknownArgument,
+    Ok(_) => true
+  }
+}
+
+/// Parses arguments that didn't all come from the command line, describing
+/// errors as coming from `source`
+fn parse_args(args: &[String], source: &str) -> Result<ArgMatches<'static>, SimpleError> {
+  // the command line was already checked, so its usage isn't helpful
+  let app = Config::clap().setting(structopt::clap::AppSettings::ColorNever);
+  app.get_matches_from_safe(args).map_err(|e| {
+    let message = e.message.lines().next().unwrap_or_default().to_string();
+    SimpleError::new(format!("invalid {}: {}", source, message.trim_start_matches("error: ")))
+  })
+}
+
+/// Adds flags before command-line arguments, and positional `app` arguments
+/// after them
+fn with_flags(args: &[String], flags: Vec<String>, app: Vec<String>) -> Vec<String> {
+  let mut merged: Vec<String> = args.iter().take(1).cloned().collect();
+  merged.extend(flags);
+  merged.extend(args.iter().skip(1).cloned());
+  if !app.is_empty() {
+    merged.push("--".to_string());
+    merged.extend(app);
+  }
+
+  merged
+}
+
+/// Converts `WOODCHIPPER_*` and older `WD_*` environment variables to flags,
+/// skipping flags `matches` already has; returns the flags and the names of
+/// those set
+///
+/// Switches take e.g. `true` or `false`, and flags that may be repeated take
+/// one value per line. If both are set, `WOODCHIPPER_*` variables win.
+/// Variables that aren't flags, e.g. a wrapper's `WOODCHIPPER_HOME` or
+/// `WD_TRIGGER`, are skipped, with a warning for `WOODCHIPPER_*` ones.
+fn env_flags(
+  vars: impl IntoIterator<Item = (String, String)>, matches: &ArgMatches
+) -> Result<(Vec<String>, Vec<String>), SimpleError> {
+  let mut old = BTreeMap::new();
+  let mut current = BTreeMap::new();
+  for (key, value) in vars {
+    if let Some(rest) = key.strip_prefix(ENV_PREFIX) {
+      let name = rest.to_lowercase().replace('_', "-");
+      if is_flag(&name) {
+        current.insert(name, (key, value));
+      } else {
+        eprintln!("warning: ignoring environment variable {}, there's no --{} flag", key, name);
+      }
+    } else if let Some(rest) = key.strip_prefix(OLD_ENV_PREFIX) {
+      let name = match OLD_ENV_NAMES.iter().find(|(old_key, _)| *old_key == key) {
+        Some((_, name)) => name.to_string(),
+        None => rest.to_lowercase().replace('_', "-")
+      };
+
+      if is_flag(&name) {
+        old.insert(name, (key, value));
+      }
+    }
+  }
+
+  old.extend(current);
+
+  let mut flags = Vec::new();
+  let mut names = Vec::new();
+  for (name, (key, value)) in old {
+    if matches.occurrences_of(&name) > 0 {
+      continue;
+    }
+
+    let source = format!("environment variable {}", key);
+    let program = "woodchipper".to_string();
+    // options with defaults parse without a value too, but still have one
+    let alone = parse_args(&[program.clone(), format!("--{}", name)], &source);
+    let switch = matches!(alone, Ok(matches) if matches.value_of_os(&name).is_none());
+
+    let values: Vec<String> = if switch {
+      match value.trim().to_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => vec![format!("--{}", name)],
+        "" | "0" | "false" | "no" | "off" => Vec::new(),
+        _ => bail!("invalid {}: expected true or false", source)
+      }
+    } else {
+      value.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| format!("--{}={}", name, line))
+        .collect()
+    };
+
+    // check each variable alone, so errors can name it
+    let args: Vec<String> = std::iter::once(program).chain(values.iter().cloned()).collect();
+    parse_args(&args, &source)?;
+
+    flags.extend(values);
+    names.push(name);
+  }
+
+  Ok((flags, names))
+}
+
+/// Options loaded from the config file
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct FileConfig {
+  pub timestamps: TimestampConfig,
+  pub flatten: FlattenConfig,
+  pub levels: LevelSynonyms,
+  pub fields: FieldSelection,
+
+  /// User-defined themes, selected with `--style <name>`
+  pub themes: HashMap<String, Palette>,
+
+  /// Conditional styles, e.g. to color messages with `status >= 500` red, and
+  /// rules for dropping, rate-limiting, and acting on messages
+  pub rules: Rules,
+
+  /// Secrets and personal data to mask before messages are shown or saved
+  pub redact: RedactConfig,
+
+  /// Columns to hide at particular terminal widths, overriding
+  /// `--breakpoints`
+  pub layouts: Vec<WidthLayout>,
+
+  /// Link templates by field name, e.g.
+  /// `trace_id: https://tracing.example.com/trace/{trace_id}`, used when
+  /// `--hyperlinks` are enabled
+  pub links: HashMap<String, String>,
+
+  /// Keys for the interactive viewer
+  pub keys: Keymap,
+
+  /// Values for flags not set on the command line, e.g. `renderer: styled`
+  pub defaults: FlagValues,
+
+  /// Named sets of flags selected with `--profile`, applied over `defaults`,
+  /// e.g. a Kubernetes selector and namespace with a set of filters
+  pub profiles: BTreeMap<String, FlagValues>
+}
+
+impl FileConfig {
+  /// The default config file path: `$XDG_CONFIG_HOME/woodchipper/config.yaml`,
+  /// or `~/.config/woodchipper/config.yaml`
+  pub fn default_path() -> PathBuf {
+    let base = match env::var("XDG_CONFIG_HOME") {
+      Ok(dir) if !dir.is_empty() => PathBuf::from(dir),
+      _ => PathBuf::from(shellexpand::tilde("~/.config").to_string())
+    };
+
+    base.join("woodchipper").join("config.yaml")
+  }
+
+  pub fn load(path: &Path) -> Result<Self, SimpleError> {
+    FileConfig::from_value(path, FileConfig::read(path)?)
+  }
+
+  fn read(path: &Path) -> Result<serde_yaml::Value, SimpleError> {
+    if path.extension().map_or(false, |ext| ext == "toml") {
+      let contents = fs::read_to_string(path).map_err(SimpleError::from)?;
+
+      return crate::toml::parse(&contents).map_err(|e| SimpleError::new(
+        format!("error loading config {}: {}", path.display(), e)
+      ));
+    }
+
+    let file = File::open(path).map_err(SimpleError::from)?;
+    let reader = BufReader::new(file);
+
+    serde_yaml::from_reader(reader).map_err(|e| SimpleError::new(
+      format!("error loading config {}: {}", path.display(), e)
+    ))
+  }
+
+  fn from_value(path: &Path, value: serde_yaml::Value) -> Result<Self, SimpleError> {
+    serde_yaml::from_value(value).map_err(|e| SimpleError::new(
+      format!("error loading config {}: {}", path.display(), e)
+    ))
+  }
+
+  /// Finds a project config file in `dir` or the nearest directory above it
+  pub fn find_project(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+      .flat_map(|dir| vec![dir.join(PROJECT_CONFIG), dir.join(PROJECT_TOML)])
+      .find(|path| path.is_file())
+  }
+
+  /// Loads a project's config file merged over the user's, if any
+  ///
+  /// Mappings are merged key by key, with the project's values replacing the
+  /// user's, except that lists of rules and redactions are combined.
+  pub fn load_project(user: Option<&Path>, project: &Path) -> Result<Self, SimpleError> {
+    let value = normalize_rules(FileConfig::read(project)?);
+    check_project(&value).map_err(|e| SimpleError::new(
+      format!("error loading config {}: {}", project.display(), e)
+    ))?;
+
+    let value = match user {
+      Some(user) => merge_files(normalize_rules(FileConfig::read(user)?), value),
+      None => value
+    };
+
+    FileConfig::from_value(project, value)
+  }
+
+  /// The config file at the default path, if any, or a `config.toml` in its
+  /// place
+  fn find_default() -> Option<PathBuf> {
+    let path = FileConfig::default_path();
+    let toml = path.with_extension("toml");
+
+    Some(path).filter(|path| path.exists()).or_else(|| Some(toml).filter(|path| path.exists()))
+  }
+
+  /// Loads the config file from the default path, if it exists
+  pub fn load_default() -> Result<Option<Self>, SimpleError> {
+    match FileConfig::find_default() {
+      Some(path) => FileConfig::load(&path).map(Some),
+      None => Ok(None)
+    }
+  }
+}
+
+/// Rewrites `rules` given as a list of style rules as a `style` section, so
+/// files using either form can be merged
+fn normalize_rules(mut value: serde_yaml::Value) -> serde_yaml::Value {
+  if let serde_yaml::Value::Mapping(mapping) = &mut value {
+    let key = serde_yaml::Value::from("rules");
+    if let Some(rules) = mapping.get_mut(&key).filter(|rules| rules.is_sequence()) {
+      let mut sections = serde_yaml::Mapping::new();
+      sections.insert(serde_yaml::Value::from("style"), rules.clone());
+      *rules = serde_yaml::Value::Mapping(sections);
+    }
+  }
+
+  value
+}
+
+/// Merges `over` into `base`: mappings key by key, and lists only if
+/// `combine` is set; otherwise `over` replaces `base`
+fn merge_values(
+  base: serde_yaml::Value, over: serde_yaml::Value, combine: bool
+) -> serde_yaml::Value {
+  use serde_yaml::Value;
+
+  match (base, over) {
+    (Value::Mapping(mut base), Value::Mapping(over)) => {
+      for (key, value) in over {
+        let merged = match base.remove(&key) {
+          Some(old) => merge_values(old, value, combine),
+          None => value
+        };
+
+        base.insert(key, merged);
+      }
+
+      Value::Mapping(base)
+    },
+    (Value::Sequence(mut base), Value::Sequence(over)) if combine => {
+      base.extend(over);
+      Value::Sequence(base)
+    },
+    (_, over) => over
+  }
+}
+
+/// Merges a project's config file over the user's
+fn merge_files(user: serde_yaml::Value, project: serde_yaml::Value) -> serde_yaml::Value {
+  use serde_yaml::Value;
+
+  let (mut user, project) = match (user, project) {
+    (Value::Mapping(user), Value::Mapping(project)) => (user, project),
+    (user, Value::Null) => return user,
+    (_, project) => return project
+  };
+
+  for (key, value) in project {
+    let combine = matches!(key.as_str(), Some(key) if COMBINED_SECTIONS.contains(&key));
+    let merged = match user.remove(&key) {
+      Some(old) => merge_values(old, value, combine),
+      None => value
+    };
+
+    user.insert(key, merged);
+  }
+
+  Value::Mapping(user)
+}
+
+/// Checks a project's config file doesn't run commands or write files
+fn check_project(value: &serde_yaml::Value) -> Result<(), SimpleError> {
+  let triggers = value.get("rules").and_then(|rules| rules.get("trigger"));
+  if let Some(triggers) = triggers.and_then(|triggers| triggers.as_sequence()) {
+    if triggers.iter().any(|trigger| trigger.get("run").is_some()) {
+      bail!("a project config can't set trigger commands");
+    }
+  }
+
+  let profiles = value.get("profiles")
+    .and_then(|profiles| profiles.as_mapping())
+    .into_iter()
+    .flat_map(|profiles| profiles.iter().map(|(_, profile)| profile));
+
+  for values in value.get("defaults").into_iter().chain(profiles) {
+    let names = values.as_mapping().into_iter().flat_map(|values| values.iter());
+    for name in names.filter_map(|(name, _)| name.as_str()) {
+      let flag = name.replace('_', "-");
+      if UNTRUSTED_FLAGS.contains(&flag.as_str()) {
+        bail!("a project config can't set {}", flag);
+      }
+    }
+  }
+
+  Ok(())
+}
+
+impl FromStr for FileConfig {
+  type Err = SimpleError;
+
+  fn from_str(path: &str) -> Result<Self, Self::Err> {
+    let expanded_path = shellexpand::full(path).map_err(SimpleError::from)?;
+    FileConfig::load(Path::new(&expanded_path.to_string()))
+  }
+}
+
+#[derive(Debug, StructOpt)]
+#[structopt(
+  name = "woodchipper",
+  rename_all = "kebab-case",
+  raw(setting = "structopt::clap::AppSettings::ColoredHelp"),
+  raw(after_help = "crate::subcommand::help()")
+)]
+pub struct Config {
+  /// Renderer to use, one of: auto, plain, json, styled, interactive, html,
+  /// csv, normalized, stats, markdown, parquet
+  /// 
+  /// If auto, will is determined by terminal and whether or not output will be
+  /// redirected. Automatic preference may be overridden with
+  /// --preferred-renderer, otherwise --renderer will force use of the given
+  /// renderer even if unsupported.
+  #[structopt(long, short, default_value = "auto", env = "WD_RENDERER")]
+  pub renderer: RendererType,
+
+  /// Preferred renderer, any renderer accepted by --renderer
+  ///
+  /// When --renderer=auto, this controls the preferred default renderer if no
+  /// conditions exist that would otherwise select a different renderer. Styled
+  /// and interactive renderers fall back to plain output when piped; others,
+  /// e.g. json, are always used.
+  /// 
+  /// For example, if you dislike the interactive renderer but still wish to
+  /// automatically fall back to plaintext output when piped, use
+  /// --preferred-renderer=styled.
+  #[structopt(long, default_value = "interactive", env = "WD_PREFERRED_RENDERER")]
+  pub preferred_renderer: RendererType,
+
+  /// Reader to use, one of: auto, stdin, hack, kubernetes, msgpack, protobuf,
+  /// exec, session, file, listen
+  ///
+  /// If auto, reader will be determined selected based on OS and renderer.
+  ///{n}{n}
+  /// - `stdin` reads from standard input{n}
+  /// - `hack` reads from /dev/stdin to allow the interactive renderer to work{n}
+  /// - `kubernetes` continuously follows Kubernetes pods{n}
+  /// - `msgpack` reads a stream of msgpack records (e.g. from fluentd){n}
+  /// - `protobuf` reads length-delimited protobuf messages, see
+  ///   `--proto-descriptors`{n}
+  /// - `exec` runs `--exec` commands (or the positional args) and reads their
+  ///   output{n}
+  /// - `session` reads a snapshot saved with `:snapshot`, see `--open`{n}
+  /// - `file` follows the files given as positional args{n}
+  /// - `listen` reads lines sent over TCP or UDP, see `--listen`{n}
+  /// - `auto` selects `session`, `exec`, or `listen` if `--open`, `--exec`,
+  ///   or `--listen` is set, otherwise `hack` on unix unless some Kubernetes
+  ///   flag is set
+  ///
+  /// Subcommands listed below select a reader too.
+  #[structopt(long, short = "i", default_value = "auto", env = "WD_READER")]
+  pub reader: ReaderType,
+
+  /// Kubernetes selector, subprocess args, or files from which to capture log
+  /// output. If unset, assumes logs will be read from standard input.
+  pub app: Vec<String>,
+
+  /// Fallback width for the styled renderer if no tty is detected
+  ///
+  /// Note that the plaintext renderer is recommended in most cases where
+  /// terminal size is unavailable.
+  #[structopt(
+    long,
+    short = "w",
+    default_value = "120",
+    env = "WD_FALLBACK_WIDTH"
+  )]
+  pub fallback_width: usize,
+
+  /// Styled output configuration
+  ///
+  /// Must contain one of the following: `default`, `dark`, `light`,
+  /// `solarized`, `dracula`, `base16:<path to .yaml>`, or the name of a theme
+  /// in the config file
+  ///
+  /// Themes may also be cycled in the interactive renderer with `T`, which is
+  /// remembered for later sessions unless this is set.
+  #[structopt(long, short = "s", default_value = "default", env = "WD_STYLE")]
+  pub style: StyleConfig,
+
+  /// When to use colors, one of: auto, always, never
+  ///
+  /// If auto, colors are used when writing to a terminal, unless the
+  /// `NO_COLOR` environment variable is set. With always, the styled renderer
+  /// is used instead of the plain renderer when output is redirected, e.g. to
+  /// `less -R`.
+  #[structopt(long, default_value = "auto", env = "WD_COLOR")]
+  pub color: ColorMode,
+
+  /// When to page output, one of: auto, always, never
+  ///
+  /// If auto, the styled and plain renderers send their output through a
+  /// pager when writing to a terminal, so the start of long logs isn't lost.
+  #[structopt(long, default_value = "auto", env = "WD_PAGING")]
+  pub paging: PagingMode,
+
+  /// When to make URLs and linked fields clickable, one of: auto, always,
+  /// never
+  ///
+  /// Links use OSC 8 escapes, which many terminals support. If auto, they're
+  /// used with colors in terminals known to support them. Fields are linked
+  /// using templates from `links` in the config file.
+  #[structopt(long, default_value = "auto", env = "WD_HYPERLINKS")]
+  pub hyperlinks: HyperlinkMode,
+
+  /// The pager command to use. If unset, uses `$PAGER`, or `less`.
+  ///
+  /// If `$LESS` is unset, it defaults to `FRX`, so `less` keeps colors and
+  /// exits right away when the output fits on one screen.
+  #[structopt(long, env = "WD_PAGER")]
+  pub pager: Option<String>,
+
+  /// The timezone in which timestamps are displayed, e.g. `local`, `UTC`,
+  /// `+05:30`, or a tz database name like `Europe/Berlin`
+  ///
+  /// Messages are still ordered by their UTC timestamps. The normalized
+  /// renderer always uses UTC.
+  #[structopt(long, default_value = "local", env = "WD_TIMEZONE")]
+  pub timezone: DisplayZone,
+
+  /// If set, show timestamps as offsets rather than dates and times, one of:
+  /// now, first
+  ///
+  /// With now, timestamps are shown relative to the current time, e.g.
+  /// `-00:02:13`; with first, relative to the first message. This may also be
+  /// toggled in the interactive renderer with `t`.
+  #[structopt(long, env = "WD_RELATIVE_TIME")]
+  pub relative_time: Option<RelativeTime>,
+
+  /// If set, adds a column showing the time elapsed since the previously
+  /// displayed message, e.g. `+3ms` or `+12.4s`
+  ///
+  /// In the interactive renderer, this is the previous message that passes all
+  /// filters.
+  #[structopt(long)]
+  pub delta: bool,
+
+  /// If set, runs of repeated messages are collapsed into one, annotated with
+  /// the number of repeats, e.g. `×12`. Messages repeat if they have the same
+  /// level, source, and text, regardless of their timestamps.
+  ///
+  /// This may also be toggled in the interactive renderer with `r`.
+  #[structopt(long)]
+  pub collapse_repeats: bool,
+
+  /// An expression messages must match to be shown, e.g. `--filter 'level >=
+  /// warn && fields.status >= 500 && message =~ "timeout"'`; may be repeated,
+  /// and messages must match them all
+  ///
+  /// Expressions are the same as the interactive renderer's `expr` filters.
+  /// Messages are filtered before any renderer sees them, or they're written
+  /// to `--sink`, `--split`, or `--tee`, so unlike interactive filters, these
+  /// can't be removed later.
+  #[structopt(long, raw(number_of_values = "1"))]
+  pub filter: Vec<String>,
+
+  /// Only show messages matching this regex, like `grep`; may be repeated to
+  /// show messages matching any of them
+  ///
+  /// Patterns are matched against both the original line and the parsed
+  /// message text. As with `--filter`, messages are dropped before they're
+  /// rendered or written anywhere.
+  #[structopt(long = "grep", short = "g", raw(number_of_values = "1"))]
+  pub grep: Vec<Regex>,
+
+  /// Hide messages matching this regex, like `grep -v`; may be repeated
+  #[structopt(long = "grep-exclude", short = "G", raw(number_of_values = "1"))]
+  pub grep_exclude: Vec<Regex>,
+
+  /// Only show messages at or above this level, e.g. `warn`
+  ///
+  /// Messages without a level are hidden. Like `--grep` and `--filter`,
+  /// messages are dropped before they're rendered or written anywhere.
+  #[structopt(long, short = "l", parse(try_from_str = "parse_level"), env = "WD_MIN_LEVEL")]
+  pub min_level: Option<LogLevel>,
+
+  /// Keeps only a random fraction of messages, e.g. `0.1`, `1/10`, or `10%`,
+  /// to keep very chatty streams readable
+  ///
+  /// Sampling applies after `--filter`, `--grep`, and the like. The
+  /// interactive renderer shows how many messages were sampled out.
+  #[structopt(long, env = "WD_SAMPLE")]
+  pub sample: Option<SampleRate>,
+
+  /// With `--sample`, always keeps messages at or above this level, e.g.
+  /// `warn`
+  #[structopt(long, parse(try_from_str = "parse_level"), env = "WD_SAMPLE_KEEP")]
+  pub sample_keep: Option<LogLevel>,
+
+  /// Only show messages at or after this time, e.g. `14:32`,
+  /// `2023-05-01T14:32:10Z`, `-1h30m`, or `15m` for 15 minutes ago
+  ///
+  /// Times without an offset are in the `--timezone`, and times without a
+  /// date are today. Messages without a timestamp are kept. Kubernetes logs
+  /// are only requested from this time on.
+  #[structopt(long, env = "WD_SINCE", raw(allow_hyphen_values = "true"))]
+  pub since: Option<String>,
+
+  /// Only show messages at or before this time, written as with `--since`
+  #[structopt(long, env = "WD_UNTIL", raw(allow_hyphen_values = "true"))]
+  pub until: Option<String>,
+
+  /// Watches each source's message rate and error ratio, adding an "anomaly"
+  /// message when either deviates sharply from the source's recent baseline
+  ///
+  /// Sources are counted in 10 second buckets, each compared with the minute
+  /// before it. The interactive renderer's histogram (`h`) underlines
+  /// anomalies.
+  #[structopt(long)]
+  pub anomalies: bool,
+
+  /// An expression that triggers `--notify`, `--bell`, or `--run` when a
+  /// message matches it, e.g. `--on 'level >= error'`; may be repeated
+  ///
+  /// Each trigger fires at most once every 10 seconds. More triggers, with
+  /// their own actions, may be set in the config file.
+  #[structopt(long, raw(number_of_values = "1"))]
+  pub on: Vec<String>,
+
+  /// When an `--on` trigger fires, shows a desktop notification (with
+  /// `notify-send`, or `osascript` on macOS)
+  #[structopt(long)]
+  pub notify: bool,
+
+  /// When an `--on` trigger fires, rings the terminal bell
+  #[structopt(long)]
+  pub bell: bool,
+
+  /// When an `--on` trigger fires, runs this shell command with the message
+  /// as JSON on its stdin
+  #[structopt(long)]
+  pub run: Option<String>,
+
+  /// The name of a filter saved in the interactive renderer with
+  /// `:save <name>` to apply on startup. More filters may be added, or this
+  /// one removed with `p`, as usual.
+  #[structopt(long)]
+  pub filter_preset: Option<String>,
+
+  /// If set, quoted strings, numbers, UUIDs, and embedded JSON in messages
+  /// aren't highlighted by the styled and interactive renderers.
+  #[structopt(long)]
+  pub no_highlight: bool,
+
+  /// If set, all messages are also written to this file, e.g. to record an
+  /// interactive session
+  #[structopt(long, parse(from_os_str), env = "WD_TEE")]
+  pub tee: Option<PathBuf>,
+
+  /// The format of messages written by `--tee`, one of: plain, json
+  #[structopt(long, default_value = "plain", env = "WD_TEE_FORMAT")]
+  pub tee_format: TeeFormat,
+
+  /// If set, the `--tee` file is rotated once it grows larger than this, e.g.
+  /// `10M`; rotated files are named like `out.log.1`, `out.log.2`, etc
+  #[structopt(long, env = "WD_TEE_MAX_SIZE")]
+  pub tee_max_size: Option<ByteSize>,
+
+  /// The number of rotated `--tee` files to keep
+  #[structopt(long, default_value = "5", env = "WD_TEE_KEEP")]
+  pub tee_keep: usize,
+
+  /// If set, rotated `--tee` files are compressed with gzip
+  #[structopt(long)]
+  pub tee_gzip: bool,
+
+  /// If set, all messages are also written to a database for later querying,
+  /// e.g. `sqlite:session.db`
+  ///
+  /// `sqlite:` databases are written with the `sqlite3` command, which must be
+  /// installed; `sql:session.sql` writes a SQL script that may be loaded
+  /// later instead.
+  #[structopt(long, env = "WD_SINK")]
+  pub sink: Option<Sink>,
+
+  /// If set, each source's messages are also written to their own file, named
+  /// by this template, e.g. `logs/{namespace}_{pod}.log`
+  ///
+  /// Placeholders may be any message field, `source`, or the `namespace`,
+  /// `pod`, and `container` of Kubernetes sources.
+  #[structopt(long, env = "WD_SPLIT")]
+  pub split: Option<PathTemplate>,
+
+  /// The format of messages written by `--split`, one of: plain, json
+  #[structopt(long, default_value = "plain", env = "WD_SPLIT_FORMAT")]
+  pub split_format: TeeFormat,
+
+  /// Terminal widths below which only high, medium, and normal importance
+  /// columns are shown, e.g. `60,80,100`
+  ///
+  /// By default, file and logger context is dropped below 100 columns, then
+  /// the date, fields, and source below 80, leaving only the level and message
+  /// below 60. For explicit column sets per width, see `layouts` in the config
+  /// file.
+  #[structopt(long, default_value = "60,80,100", env = "WD_BREAKPOINTS")]
+  pub breakpoints: Breakpoints,
+
+  /// How many columns the styled and interactive renderers show, one of:
+  /// detailed, compact
+  ///
+  /// compact shows only the time, level, and message at any width. This may
+  /// also be toggled in the interactive renderer with `D`, which is remembered
+  /// for later sessions unless this is set.
+  #[structopt(long, default_value = "detailed", env = "WD_DENSITY")]
+  pub density: Density,
+
+  /// How the interactive renderer copies to the clipboard, one of: auto,
+  /// system, osc52
+  ///
+  /// osc52 asks the terminal to set the clipboard with an escape sequence,
+  /// which works over SSH if the terminal supports it. auto uses osc52 in SSH
+  /// sessions, and otherwise the system clipboard, falling back to osc52.
+  #[structopt(long, default_value = "auto", env = "WD_CLIPBOARD")]
+  pub clipboard: ClipboardMode,
+
+  /// How the styled and interactive renderers show levels and sources, one
+  /// of: text, ascii, unicode, nerd
+  ///
+  /// To save space, ascii shows levels as a single letter, e.g. `W`, and
+  /// unicode as a symbol, e.g. `▲`, with symbols marking pods, containers,
+  /// and files. nerd uses icons from a Nerd Font instead. If the locale isn't
+  /// UTF-8, unicode and nerd fall back to ascii.
+  #[structopt(long, default_value = "text", env = "WD_GLYPHS")]
+  pub glyphs: GlyphMode,
+
+  /// If set, each message's fields are shown on their own lines beneath it,
+  /// rather than inline. This may also be toggled in the interactive renderer
+  /// with `x`.
+  #[structopt(long, short = "x")]
+  pub expand_fields: bool,
+
+  /// If set, the interactive renderer shows each message's record number, its
+  /// position in the order messages arrived, in a gutter; numbers don't change
+  /// when filtering, and copies and `:write` include them. This may also be
+  /// toggled with `#`.
+  #[structopt(long)]
+  pub record_numbers: bool,
+
+  /// If set, the interactive renderer draws over the terminal's main screen
+  /// rather than switching to the alternate screen, so earlier output stays in
+  /// the scrollback and tmux copy mode still works. The final view is left on
+  /// screen after quitting, rather than vanishing.
+  #[structopt(long)]
+  pub inline: bool,
+
+  /// The number of colors to use, one of: auto, 16, 256, truecolor
+  ///
+  /// If auto, truecolor is used if `COLORTERM` is `truecolor` or `24bit`, and
+  /// 256 colors are used if `TERM` contains `256color`. Theme colors are
+  /// approximated if the terminal can't display them.
+  #[structopt(long, default_value = "auto", env = "WD_COLOR_DEPTH")]
+  pub color_depth: ColorDepth,
+
+  /// A path to a regexes config file, which may contain custom parsing regexes
+  /// for application-specific log formats.
+  #[structopt(long, env = "WD_REGEXES")]
+  pub regexes: Option<RegexConfig>,
+
+  /// The format written by the markdown renderer, one of: table, code
+  ///
+  /// Tables have one column per `--columns` entry; code blocks contain messages
+  /// as shown by the plain renderer.
+  #[structopt(long, default_value = "table", env = "WD_MARKDOWN_FORMAT")]
+  pub markdown_format: MarkdownFormat,
+
+  /// The number of fields, besides timestamp, level, message, kind, and
+  /// source, given their own typed columns by the parquet renderer
+  ///
+  /// The most frequent fields are chosen; all fields are also included as a
+  /// JSON object in the `fields` column.
+  #[structopt(long, default_value = "16", env = "WD_PARQUET_FIELDS")]
+  pub parquet_fields: usize,
+
+  /// A comma-separated list of columns for the csv and markdown renderers, e.g.
+  /// `timestamp,level,pod,message`. Columns may be `timestamp`, `level`,
+  /// `message`, `kind`, `source`, `raw`, or any field name; nested fields may
+  /// be selected with dots, e.g. `http.status`. Defaults to
+  /// `timestamp,level,message`.
+  #[structopt(long, raw(use_delimiter = "true"), env = "WD_COLUMNS")]
+  pub columns: Vec<String>,
+
+  /// A line layout for the plain and styled renderers, e.g.
+  /// `{timestamp:%H:%M:%S} {level:>5} [{fields.pod}] {message}`
+  ///
+  /// Placeholders may include a width and alignment (`{level:>5}`), a maximum
+  /// width (`{pod:.12}`), or a strftime format for timestamps. Styles may be
+  /// set with `|`, e.g. `{message|bold,red}`.
+  #[structopt(long, env = "WD_FORMAT")]
+  pub format: Option<Template>,
+
+  /// A jq-style selection of parts of each record for the json and plain
+  /// renderers, e.g. `.fields.request_id, .message`
+  ///
+  /// The json renderer writes an object with one key per term, and the plain
+  /// renderer writes the values separated by tabs. Terms may be renamed, e.g.
+  /// `id: .fields.request_id`, and select array elements, e.g. `.tags[0]`.
+  /// Takes precedence over `--format`.
+  #[structopt(long, env = "WD_PROJECT")]
+  pub project: Option<Projection>,
+
+  /// A comma-separated list of fields to display in the styled and interactive
+  /// renderers, in order, e.g. `pod,trace_id`. Other fields are hidden.
+  #[structopt(long, raw(use_delimiter = "true"), env = "WD_FIELDS")]
+  pub fields: Vec<String>,
+
+  /// A comma-separated list of fields to hide in the styled and interactive
+  /// renderers, e.g. `logger,caller`
+  #[structopt(long, raw(use_delimiter = "true"), env = "WD_HIDE_FIELDS")]
+  pub hide_fields: Vec<String>,
+
+  /// Truncates field values longer than this in the styled and interactive
+  /// renderers, e.g. `40`, or a comma-separated list with widths for
+  /// particular fields, e.g. `40,request_body=120`
+  ///
+  /// The selected message in the interactive renderer is shown in full.
+  #[structopt(long, raw(use_delimiter = "true"), env = "WD_MAX_FIELD_WIDTH")]
+  pub max_field_width: Vec<FieldWidth>,
+
+  /// If set, attempts to ensure messages are displayed in semantic order by
+  /// placing them in a priority queue for a short period before being written
+  /// to the renderer. By default, messages are held for one second; this can be
+  /// overridden with `--buffer-ms`.
+  ///
+  /// Particularly useful when paired with readers that ingest from multiple
+  /// sources at once, like the Kubernetes reader.
+  #[structopt(long, short = "o")]
+  pub ordered: bool,
+
+  /// Sets the length of time, in milliseconds, that messages should be buffered
+  /// to attempt to reorder them. If set, implies `--ordered`.
+  #[structopt(long)]
+  pub buffer_ms: Option<u64>,
+
+  /// If set, disables folding of continuation lines (e.g. Java stack traces)
+  /// into the message that preceded them.
+  #[structopt(long)]
+  pub no_multiline: bool,
+
+  /// Forces a parser rather than detecting formats automatically, e.g.
+  /// `--parser json`. Prefix with a regex and `=` to force the parser only for
+  /// matching sources in multi-source sessions, e.g. `--parser 'api-.*=json'`.
+  /// May be repeated.
+  ///
+  /// Lines the forced parser can't handle are shown as plaintext.
+  #[structopt(name = "parser", long = "parser", number_of_values = 1)]
+  pub parser_pins: Vec<ParserPin>,
+
+  /// A comma-separated list of parsers to try, in order, e.g. `json,plain`.
+  /// Parsers not listed are disabled. Defaults to all parsers:
+  /// json, auditd, logrus, klog, envoy, spring, dotnet, zap, gc, regex, plain
+  #[structopt(long, env = "WD_PARSERS")]
+  pub parsers: Option<ParserChain>,
+
+  /// If set, JSON objects embedded in plaintext messages, e.g.
+  /// `request completed {"status": 500}`, are not extracted into fields.
+  #[structopt(long)]
+  pub no_embedded_json: bool,
+
+  /// If set, `key=value` and `key: value` pairs in plaintext messages are
+  /// extracted into fields.
+  #[structopt(long)]
+  pub extract_fields: bool,
+
+  /// If set, fields containing durations or sizes, e.g. `dur="1.5s"`, are
+  /// replaced with numeric fields in milliseconds or bytes, e.g. `dur_ms=1500`.
+  #[structopt(long)]
+  pub normalize_units: bool,
+
+  /// How ANSI escape sequences (e.g. colors) in input are handled, one of:
+  /// strip, keep
+  ///
+  /// Sequences are always removed before parsing. With `keep`, plaintext
+  /// messages are shown with their original colors in the plain renderer,
+  /// unless colors are disabled with `--color=never` or `NO_COLOR`.
+  #[structopt(long, default_value = "strip", env = "WD_ANSI")]
+  pub ansi: AnsiMode,
+
+  /// A path to a YAML config file. If unset, uses
+  /// `~/.config/woodchipper/config.yaml` if it exists.
+  #[structopt(name = "config", long = "config", env = "WD_CONFIG")]
+  pub config_file: Option<FileConfig>,
+
+  /// A profile from the config file to apply, e.g. `--profile prod-api`
+  ///
+  /// Profiles are named sets of flags, e.g. a Kubernetes selector, filters,
+  /// and a renderer. Flags on the command line take precedence over the
+  /// profile, which takes precedence over the config file's `defaults`.
+  #[structopt(long, env = "WD_PROFILE")]
+  pub profile: Option<String>,
+
+  /// If set, a `.woodchipper.yaml` (or `.toml`) in the working directory or
+  /// above it isn't merged over the config file
+  ///
+  /// Project config files let a repository share its parsers and rules; they
+  /// can't run commands or write files.
+  #[structopt(long)]
+  pub no_project_config: bool,
+
+  /// A path to a compiled `FileDescriptorSet` used by the protobuf reader,
+  /// e.g. from `protoc --include_imports --descriptor_set_out=logs.pb`
+  #[structopt(long, env = "WD_PROTO_DESCRIPTORS")]
+  pub proto_descriptors: Option<Descriptors>,
+
+  /// The full name of the message type read by the protobuf reader, e.g.
+  /// `mypackage.LogRecord`
+  #[structopt(long, env = "WD_PROTO_MESSAGE")]
+  pub proto_message: Option<String>,
+
+  /// A shell command to run and read logs from, e.g. `--exec './server'`;
+  /// may be repeated to run several commands, each shown as its own source
+  ///
+  /// Commands are run with `sh -c`, and can be restarted, stopped, or sent a
+  /// signal from the interactive renderer's source list (`a`).
+  #[structopt(long, raw(number_of_values = "1"))]
+  pub exec: Vec<String>,
+
+  /// A session snapshot to reopen, saved in the interactive renderer with
+  /// `:snapshot <path>`; also `woodchipper open <path>`
+  ///
+  /// Records are shown as they were, along with the filters, hidden sources,
+  /// bookmarks, and selection.
+  #[structopt(long, parse(from_os_str))]
+  pub open: Option<PathBuf>,
+
+  /// An address to listen on for logs, e.g. `0.0.0.0:5140`; also
+  /// `woodchipper listen [address]`
+  ///
+  /// Lines sent over TCP, or in UDP datagrams to the same port (e.g. by
+  /// syslog), are read, with each sending host shown as its own source.
+  #[structopt(long, env = "WD_LISTEN")]
+  pub listen: Option<String>,
+
+  /// If set, files read with `woodchipper tail` are read to their end, then
+  /// reading stops, rather than following them for new lines
+  #[structopt(long)]
+  pub no_follow: bool,
+
+  #[structopt(flatten)]
+  pub kubernetes: KubernetesConfig
+}
+
+impl Config {
+  /// Parses command-line arguments, exiting on errors or `--help`, then
+  /// applies `WOODCHIPPER_*` variables from `vars`, and the config file's
+  /// `defaults` and `--profile`, to any flags that weren't given
+  pub fn load(
+    args: Vec<String>, vars: impl IntoIterator<Item = (String, String)>
+  ) -> Result<Config, Box<dyn Error>> {
+    let (subcommand, args) = subcommand::rewrite_args(args);
+    let mut config = Config::load_args(args, vars)?;
+    if let Some(subcommand) = subcommand {
+      subcommand.finish(&mut config)?;
+    }
+
+    Ok(config)
+  }
+
+  fn load_args(
+    args: Vec<String>, vars: impl IntoIterator<Item = (String, String)>
+  ) -> Result<Config, Box<dyn Error>> {
+    let matches = Config::clap().get_matches_from(&args);
+    let (flags, env_names) = env_flags(vars, &matches)?;
+    let args = with_flags(&args, flags, Vec::new());
+    let matches = parse_args(&args, "environment variables")?;
+
+    let mut config = Config::from_clap(&matches);
+    if config.config_file.is_none() {
+      config.config_file = FileConfig::load_default()?;
+    }
+
+    let project = if config.no_project_config {
+      None
+    } else {
+      env::current_dir().ok().and_then(|dir| FileConfig::find_project(&dir))
+    };
+
+    if let Some(project) = project {
+      let user = match matches.value_of_os("config") {
+        Some(path) => Some(PathBuf::from(shellexpand::full(&path.to_string_lossy())?.as_ref())),
+        None => FileConfig::find_default()
+      };
+
+      config.config_file = Some(FileConfig::load_project(user.as_deref(), &project)?);
+    }
+
+    let file = config.file();
+    let profile = match &config.profile {
+      Some(name) => match file.profiles.get(name) {
+        Some(profile) => Some(profile),
+        None if file.profiles.is_empty() => bail!("unknown profile {}, none are configured", name),
+        None => bail!(
+          "unknown profile {}, expected one of: {}",
+          name, file.profiles.keys().cloned().collect::<Vec<String>>().join(", ")
+        )
+      },
+      None => None
+    };
+
+    // profile values replace defaults, however their names are spelled
+    let mut values = FlagValues::new();
+    for (name, value) in file.defaults.iter().chain(profile.into_iter().flatten()) {
+      values.insert(name.replace('_', "-"), value.clone());
+    }
+
+    // including switches the environment turned off
+    for name in &env_names {
+      values.remove(name);
+    }
+
+    let (flags, app) = flag_args(&values, &matches)?;
+    if flags.is_empty() && app.is_empty() {
+      return Ok(config);
+    }
+
+    let matches = parse_args(&with_flags(&args, flags, app), "flags in the config file")?;
+
+    let mut merged = Config::from_clap(&matches);
+    if merged.config_file.is_none() {
+      merged.config_file = config.config_file.take();
+    }
+
+    Ok(merged)
+  }
+
+  /// Finds the parser forced for the given source, if any; pins for specific
+  /// sources take precedence over global pins
+  pub fn pinned_parser(&self, source: Option<&String>) -> Option<Parser> {
+    let specific = source.and_then(|source| {
+      self.parser_pins.iter().find(|pin| match &pin.source {
+        Some(re) => re.is_match(source),
+        None => false
+      })
+    });
+
+    specific
+      .or_else(|| self.parser_pins.iter().find(|pin| pin.source.is_none()))
+      .map(|pin| pin.parser)
+  }
+
+  /// The fields to display, from flags if set or otherwise the config file
+  pub fn field_selection(&self) -> FieldSelection {
+    let file = &self.file().fields;
+
+    let mut max_width = file.max_width;
+    let mut max_widths = file.max_widths.clone();
+    for width in &self.max_field_width {
+      match &width.field {
+        Some(field) => { max_widths.insert(field.clone(), width.width); },
+        None => max_width = Some(width.width)
+      }
+    }
+
+    FieldSelection {
+      show: if self.fields.is_empty() { file.show.clone() } else { self.fields.clone() },
+      hide: if self.hide_fields.is_empty() { file.hide.clone() } else { self.hide_fields.clone() },
+      max_width,
+      max_widths
+    }
+  }
+
+  /// Finishes a style parsed from a `--style` value: loads user-defined themes
+  /// and rules from the config file, and adapts it to `--no-highlight`,
+  /// `--color`, and `--color-depth`
+  pub fn prepare_style(&self, mut style: StyleConfig) -> Result<StyleConfig, Box<dyn Error>> {
+    style.resolve(&self.file().themes)?;
+    style.rules = self.file().rules.style.clone();
+
+    if self.no_highlight {
+      style.disable_syntax();
+    }
+
+    if self.color.enabled() {
+      style.downsample(self.color_depth.detect());
+      Ok(style)
+    } else {
+      Ok(StyleConfig { name: style.name, ..StyleConfig::monochrome() })
+    }
+  }
+
+  /// Loads a theme by name, as if given to `--style`
+  pub fn load_style(&self, name: &str) -> Result<StyleConfig, Box<dyn Error>> {
+    self.prepare_style(name.parse()?)
+  }
+
+  /// Options from the config file, or defaults if there is none
+  pub fn file(&self) -> &FileConfig {
+    lazy_static! {
+      static ref DEFAULT: FileConfig = FileConfig::default();
+    }
+
+    self.config_file.as_ref().unwrap_or(&DEFAULT)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::fs;
+
+  use spectral::prelude::*;
+
+  #[test]
+  fn test_profiles() {
+    let path = env::temp_dir().join(format!("woodchipper-config-{}.yaml", std::process::id()));
+    fs::write(&path, concat!(
+      "defaults:\n",
+      "  renderer: plain\n",
+      "  min_level: info\n",
+      "  ordered: true\n",
+      "profiles:\n",
+      "  prod-api:\n",
+      "    app: [app=api]\n",
+      "    namespace: prod\n",
+      "    min-level: warn\n",
+      "    since: -1h\n",
+      "    filter: ['fields.status >= 500', 'pod =~ \"^api\"']\n",
+      "  bad:\n",
+      "    config: other.yaml\n"
+    )).unwrap();
+
+    let load_with = |args: &[&str], vars: &[(&str, &str)]| {
+      let mut argv = vec!["woodchipper", "--no-project-config", "--config", path.to_str().unwrap()];
+      argv.extend(args);
+      Config::load(
+        argv.into_iter().map(String::from).collect(),
+        vars.iter().map(|(key, value)| (key.to_string(), value.to_string()))
+      )
+    };
+    let load = |args: &[&str]| load_with(args, &[]);
+
+    let config = load(&[]).unwrap();
+    assert_that!(config.renderer).is_equal_to(RendererType::Named("plain".to_string()));
+    assert_that!(config.min_level).is_equal_to(Some(LogLevel::Info));
+    assert_that!(config.ordered).is_true();
+    assert_that!(config.config_file.is_some()).is_true();
+
+    let config = load(&["--profile", "prod-api"]).unwrap();
+    assert_that!(config.app).is_equal_to(vec!["app=api".to_string()]);
+    assert_that!(config.kubernetes.namespace).is_equal_to(Some("prod".to_string()));
+    assert_that!(config.min_level).is_equal_to(Some(LogLevel::Warning));
+    assert_that!(config.since).is_equal_to(Some("-1h".to_string()));
+    assert_that!(config.filter).has_length(2);
+
+    // flags on the command line replace the profile's
+    let config = load(&["--profile", "prod-api", "-l", "error", "--filter", "x", "app=web"]);
+    let config = config.unwrap();
+    assert_that!(config.app).is_equal_to(vec!["app=web".to_string()]);
+    assert_that!(config.min_level).is_equal_to(Some(LogLevel::Error));
+    assert_that!(config.filter).is_equal_to(vec!["x".to_string()]);
+    assert_that!(config.renderer).is_equal_to(RendererType::Named("plain".to_string()));
+
+    assert_that!(load(&["--profile", "bad"]).is_err()).is_true();
+    assert_that!(load(&["--profile", "missing"]).is_err()).is_true();
+
+    // the same file in TOML loads the same config
+    let toml = path.with_extension("toml");
+    fs::write(&toml, concat!(
+      "[defaults]\n",
+      "renderer = \"plain\"\n",
+      "min_level = \"info\"\n",
+      "ordered = true\n",
+      "\n",
+      "[profiles.prod-api]\n",
+      "app = [\"app=api\"]\n",
+      "namespace = \"prod\"\n",
+      "min-level = \"warn\"\n",
+      "since = \"-1h\"\n",
+      "filter = ['fields.status >= 500', 'pod =~ \"^api\"']\n"
+    )).unwrap();
+
+    let argv = vec!["woodchipper", "--no-project-config", "--config", toml.to_str().unwrap()];
+    let argv = argv.into_iter().chain(vec!["--profile", "prod-api"]).map(String::from);
+    let config = Config::load(argv.collect(), Vec::new()).unwrap();
+    assert_that!(config.renderer).is_equal_to(RendererType::Named("plain".to_string()));
+    assert_that!(config.app).is_equal_to(vec!["app=api".to_string()]);
+    assert_that!(config.min_level).is_equal_to(Some(LogLevel::Warning));
+    assert_that!(config.filter).has_length(2);
+
+    fs::write(&toml, "[defaults\n").unwrap();
+    let error = FileConfig::load(&toml).unwrap_err();
+    assert_that!(error.as_str().ends_with("line 1: expected ']', found '\n'")).is_true();
+    fs::remove_file(&toml).unwrap();
+
+    // environment variables fall between the command line and the config file
+    let config = load_with(&["-r", "json"], &[
+      ("WOODCHIPPER_PROFILE", "prod-api"),
+      ("WOODCHIPPER_RENDERER", "styled"),
+      ("WOODCHIPPER_MIN_LEVEL", "debug"),
+      ("WOODCHIPPER_FILTER", "a\nb\n"),
+      ("WOODCHIPPER_ORDERED", "false"),
+      ("WOODCHIPPER_NO_HIGHLIGHT", "1"),
+      ("WOODCHIPPER_CONTEXT", "staging")
+    ]).unwrap();
+    assert_that!(config.renderer).is_equal_to(RendererType::Named("json".to_string()));
+    assert_that!(config.min_level).is_equal_to(Some(LogLevel::Debug));
+    assert_that!(config.filter).is_equal_to(vec!["a".to_string(), "b".to_string()]);
+    assert_that!(config.ordered).is_false();
+    assert_that!(config.no_highlight).is_true();
+    assert_that!(config.kubernetes.context).is_equal_to(Some("staging".to_string()));
+    assert_that!(config.kubernetes.namespace).is_equal_to(Some("prod".to_string()));
+    assert_that!(config.since).is_equal_to(Some("-1h".to_string()));
+
+    // `--config` wins over the variable rather than repeating the flag
+    let config = load_with(&[], &[("WOODCHIPPER_CONFIG", "missing.yaml")]).unwrap();
+    assert_that!(config.renderer).is_equal_to(RendererType::Named("plain".to_string()));
+
+    // older `WD_` variables are aliases, also winning over the config file
+    let config = load_with(&[], &[
+      ("WD_RENDERER", "json"),
+      ("WD_K8S_PORT", "8080"),
+      ("WD_MIN_LEVEL", "error"),
+      ("WOODCHIPPER_MIN_LEVEL", "debug"),
+      ("WD_TRIGGER", "not-a-flag")
+    ]).unwrap();
+    assert_that!(config.renderer).is_equal_to(RendererType::Named("json".to_string()));
+    assert_that!(config.kubernetes.port).is_equal_to(Some(8080));
+    assert_that!(config.min_level).is_equal_to(Some(LogLevel::Debug));
+
+    // other programs' variables may share the prefix
+    let config = load_with(&[], &[("WOODCHIPPER_HOME", "/x")]).unwrap();
+    assert_that!(config.renderer).is_equal_to(RendererType::Named("plain".to_string()));
+
+    for vars in &[
+      [("WOODCHIPPER_RENDERER", "bogus")],
+      [("WOODCHIPPER_ORDERED", "maybe")],
+      [("WOODCHIPPER_MIN_LEVEL", "warn\nerror")]
+    ] {
+      assert_that!(load_with(&[], vars).is_err()).is_true();
+    }
+
+    fs::remove_file(&path).ok();
+  }
+
+  #[test]
+  fn test_project_config() {
+    let root = env::temp_dir().join(format!("woodchipper-project-{}", std::process::id()));
+    let nested = root.join("src").join("api");
+    fs::create_dir_all(&nested).unwrap();
+
+    let user = root.join("user.yaml");
+    fs::write(&user, concat!(
+      "rules:\n",
+      "  - field: status\n",
+      "    gte: 500\n",
+      "    style: red\n",
+      "fields:\n",
+      "  show: [pod]\n",
+      "  max_width: 40\n",
+      "defaults:\n",
+      "  renderer: plain\n",
+      "  ordered: true\n"
+    )).unwrap();
+
+    let project = root.join(PROJECT_CONFIG);
+    fs::write(&project, concat!(
+      "rules:\n",
+      "  style:\n",
+      "    - message: deprecated\n",
+      "      style: yellow\n",
+      "  drop:\n",
+      "    - message: '^GET /healthz'\n",
+      "fields:\n",
+      "  show: [request_id]\n",
+      "defaults:\n",
+      "  renderer: styled\n"
+    )).unwrap();
+
+    assert_that!(FileConfig::find_project(&nested)).is_equal_to(Some(project.clone()));
+
+    // a TOML project config is merged the same way, and the nearest wins
+    let toml = nested.join(PROJECT_TOML);
+    fs::write(&toml, concat!(
+      "[[rules.drop]]\n",
+      "message = '^GET /metrics'\n",
+      "\n",
+      "[fields]\n",
+      "show = [\"trace_id\"]\n"
+    )).unwrap();
+    assert_that!(FileConfig::find_project(&nested)).is_equal_to(Some(toml.clone()));
+
+    let file = FileConfig::load_project(Some(&user), &toml).unwrap();
+    assert_that!(file.rules.drop).has_length(1);
+    assert_that!(file.fields.show).is_equal_to(vec!["trace_id".to_string()]);
+    assert_that!(file.fields.max_width).is_equal_to(Some(40));
+
+    fs::write(&toml, "[defaults]\nsink = 'out.db'\n").unwrap();
+    assert_that!(FileConfig::load_project(None, &toml).is_err()).is_true();
+
+    // YAML takes precedence in the same directory
+    let yaml = nested.join(PROJECT_CONFIG);
+    fs::write(&yaml, "").unwrap();
+    assert_that!(FileConfig::find_project(&nested)).is_equal_to(Some(yaml.clone()));
+    fs::remove_file(&yaml).unwrap();
+    fs::remove_file(&toml).unwrap();
+
+    let file = FileConfig::load_project(Some(&user), &project).unwrap();
+    assert_that!(file.rules.style).has_length(2);
+    assert_that!(file.rules.drop).has_length(1);
+    assert_that!(file.fields.show).is_equal_to(vec!["request_id".to_string()]);
+    assert_that!(file.fields.max_width).is_equal_to(Some(40));
+    assert_that!(file.defaults.get("renderer")).is_equal_to(Some(&"styled".into()));
+    assert_that!(file.defaults.get("ordered")).is_equal_to(Some(&true.into()));
+
+    for untrusted in &[
+      "rules:\n  trigger:\n    - level: error\n      run: 'curl example.com'\n",
+      "defaults:\n  exec: ./server\n",
+      "defaults:\n  app: [sh, -c, 'echo PWNED > pwned']\n",
+      "profiles:\n  dev:\n    tee: out.log\n",
+      "profiles:\n  dev:\n    app: [make]\n"
+    ] {
+      fs::write(&project, untrusted).unwrap();
+      assert_that!(FileConfig::load_project(Some(&user), &project).is_err()).is_true();
+    }
+
+    fs::remove_dir_all(&root).ok();
+  }
 }
@@ -0,0 +1,383 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+use std::cmp::Ordering;
+use std::collections::BTreeSet;
+use std::str::FromStr;
+
+use pest::Parser;
+use pest::iterators::Pair;
+use regex::Regex;
+use serde_json::Value;
+use simple_error::{SimpleError, SimpleResult};
+
+use crate::parser::{LogLevel, Message};
+use crate::style::{value_number, value_string};
+
+#[derive(Parser)]
+#[grammar = "expression.pest"]
+struct ExpressionParser;
+
+/// Fields that every message may have, offered first when completing
+pub static BUILTIN_FIELDS: &[&str] = &[
+  "timestamp", "level", "message", "kind", "source", "pod", "container"
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operator {
+  Equals,
+  NotEquals,
+  Matches,
+  NotMatches,
+  Greater,
+  GreaterOrEqual,
+  Less,
+  LessOrEqual
+}
+
+impl FromStr for Operator {
+  type Err = SimpleError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    Ok(match s {
+      "==" => Operator::Equals,
+      "!=" => Operator::NotEquals,
+      "=~" => Operator::Matches,
+      "!~" => Operator::NotMatches,
+      ">" => Operator::Greater,
+      ">=" => Operator::GreaterOrEqual,
+      "<" => Operator::Less,
+      "<=" => Operator::LessOrEqual,
+      _ => bail!("invalid operator: {}", s)
+    })
+  }
+}
+
+impl Operator {
+  /// Applies the operator to the result of comparing a field to its operand
+  fn test(self, ordering: Ordering) -> bool {
+    match self {
+      Operator::Equals | Operator::Matches => ordering == Ordering::Equal,
+      Operator::NotEquals | Operator::NotMatches => ordering != Ordering::Equal,
+      Operator::Greater => ordering == Ordering::Greater,
+      Operator::GreaterOrEqual => ordering != Ordering::Less,
+      Operator::Less => ordering == Ordering::Less,
+      Operator::LessOrEqual => ordering != Ordering::Greater
+    }
+  }
+}
+
+/// The right hand side of a comparison, interpreted when parsed
+#[derive(Debug, Clone)]
+pub enum Operand {
+  Text(String),
+  Number(f64, String),
+  Level(LogLevel),
+  Regex(Regex)
+}
+
+/// A parsed filter expression, e.g.
+/// `level >= warn && pod =~ "api-.*" && fields.status == 500`
+#[derive(Debug, Clone)]
+pub enum Expression {
+  Or(Vec<Expression>),
+  And(Vec<Expression>),
+  Not(Box<Expression>),
+
+  /// A bare field name, true if the field is set and isn't `false`
+  Exists(String),
+
+  Compare(String, Operator, Operand)
+}
+
+/// Looks up a field for an expression
+///
+/// Names are as accepted by `Message::field()`, optionally prefixed with
+/// `fields.`. If a message has no `pod` or `container` field, they're taken
+/// from a `pod/container` source, as from `kubectl`.
+pub fn lookup(message: &Message, name: &str) -> Option<Value> {
+  let name = name.strip_prefix("fields.").unwrap_or(name);
+
+  match message.field(name) {
+    Some(Value::Null) | None => (),
+    value => return value
+  }
+
+  let source = message.reader_metadata.as_ref()?.source.as_ref()?;
+  let mut parts = source.splitn(2, '/');
+  let value = match name {
+    "pod" => parts.next(),
+    "container" => parts.nth(1),
+    _ => None
+  };
+
+  value.map(|v| Value::String(v.to_string()))
+}
+
+fn compare(message: &Message, name: &str, op: Operator, operand: &Operand) -> bool {
+  // levels compare by severity; plain and internal messages have none
+  if let Operand::Level(level) = operand {
+    return match message.level {
+      Some(LogLevel::Plain) | Some(LogLevel::Int) | None => false,
+      Some(actual) => op.test(actual.cmp(level))
+    };
+  }
+
+  let value = match lookup(message, name) {
+    Some(value) => value,
+    None => return false
+  };
+
+  let ordering = match operand {
+    Operand::Regex(re) => if re.is_match(&value_string(&value)) {
+      Ordering::Equal
+    } else {
+      Ordering::Less
+    },
+    Operand::Number(number, text) => match value_number(&value) {
+      Some(actual) => match actual.partial_cmp(number) {
+        Some(ordering) => ordering,
+        None => return false
+      },
+      None => value_string(&value).as_str().cmp(text.as_str())
+    },
+    Operand::Text(text) => value_string(&value).as_str().cmp(text.as_str()),
+    Operand::Level(_) => unreachable!()
+  };
+
+  op.test(ordering)
+}
+
+impl Expression {
+  pub fn matches(&self, message: &Message) -> bool {
+    match self {
+      Expression::Or(exprs) => exprs.iter().any(|e| e.matches(message)),
+      Expression::And(exprs) => exprs.iter().all(|e| e.matches(message)),
+      Expression::Not(expr) => !expr.matches(message),
+      Expression::Exists(name) => match lookup(message, name) {
+        Some(Value::Bool(false)) | None => false,
+        Some(_) => true
+      },
+      Expression::Compare(name, op, operand) => compare(message, name, *op, operand)
+    }
+  }
+}
+
+/// Unescapes a double-quoted string
+///
+/// Only quotes and backslashes are unescaped so regexes like `"\d+"` don't
+/// need double escaping.
+fn unescape(s: &str) -> String {
+  let mut ret = String::with_capacity(s.len());
+  let mut chars = s.chars();
+  while let Some(c) = chars.next() {
+    match (c, chars.clone().next()) {
+      ('\\', Some(next)) if next == '"' || next == '\\' => {
+        ret.push(next);
+        chars.next();
+      },
+      (c, _) => ret.push(c)
+    }
+  }
+
+  ret
+}
+
+fn parse_operand(name: &str, op: Operator, pair: Pair<Rule>) -> SimpleResult<Operand> {
+  let text = match pair.as_rule() {
+    Rule::string => unescape(pair.as_str()),
+    _ => pair.as_str().to_string()
+  };
+
+  if op == Operator::Matches || op == Operator::NotMatches {
+    return Regex::new(&text)
+      .map(Operand::Regex)
+      .map_err(SimpleError::from);
+  }
+
+  if name == "level" {
+    return match LogLevel::from_str(&text.to_lowercase()) {
+      Ok(level) => Ok(Operand::Level(level)),
+      Err(()) => bail!("invalid level: {}", text)
+    };
+  }
+
+  Ok(match text.parse() {
+    Ok(number) if pair.as_rule() == Rule::bare_string => Operand::Number(number, text),
+    _ => Operand::Text(text)
+  })
+}
+
+fn parse_pair(pair: Pair<Rule>) -> SimpleResult<Expression> {
+  Ok(match pair.as_rule() {
+    Rule::or | Rule::and => {
+      let rule = pair.as_rule();
+      let mut exprs = pair.into_inner()
+        .map(parse_pair)
+        .collect::<SimpleResult<Vec<_>>>()?;
+
+      if exprs.len() == 1 {
+        exprs.remove(0)
+      } else if rule == Rule::or {
+        Expression::Or(exprs)
+      } else {
+        Expression::And(exprs)
+      }
+    },
+    Rule::not => {
+      let inner = pair.into_inner().next().ok_or_else(|| SimpleError::new("expected expression"))?;
+      Expression::Not(Box::new(parse_pair(inner)?))
+    },
+    Rule::comparison => {
+      let mut inner = pair.into_inner();
+      let name = match inner.next() {
+        Some(field) => field.as_str().to_string(),
+        None => bail!("expected field")
+      };
+
+      match (inner.next(), inner.next()) {
+        (Some(op), Some(value)) => {
+          let op: Operator = op.as_str().parse()?;
+          let operand = parse_operand(&name, op, value)?;
+          Expression::Compare(name, op, operand)
+        },
+        _ => Expression::Exists(name)
+      }
+    },
+    rule => bail!("unexpected {:?}", rule)
+  })
+}
+
+/// Parses a filter expression
+pub fn parse(input: &str) -> SimpleResult<Expression> {
+  let mut pairs = ExpressionParser::parse(Rule::expression, input)
+    .map_err(SimpleError::from)?;
+
+  let or = pairs.next()
+    .and_then(|expression| expression.into_inner().next())
+    .ok_or_else(|| SimpleError::new("empty expression"))?;
+
+  parse_pair(or)
+}
+
+/// Finds the partial field name at the end of `input`, if any, returning its
+/// byte offset
+///
+/// Values following an operator aren't field names, so aren't returned.
+pub fn partial_field(input: &str) -> Option<usize> {
+  let is_field_char = |c: char| c.is_ascii_alphanumeric() || "_-.@".contains(c);
+
+  let start = input.trim_end_matches(is_field_char).len();
+  let before = input[..start].trim_end();
+  if before.ends_with(|c| "=~<>\"'".contains(c)) {
+    return None;
+  }
+
+  Some(start)
+}
+
+/// Known field names for completion: builtin fields, then the (possibly
+/// nested) metadata fields of `messages`, sorted
+pub fn known_fields<'a, I>(messages: I) -> Vec<String>
+where
+  I: Iterator<Item = &'a Message>
+{
+  let mut fields = BTreeSet::new();
+  for message in messages {
+    for (key, value) in &message.metadata {
+      if let Value::Object(map) = value {
+        for nested in map.keys() {
+          fields.insert(format!("{}.{}", key, nested));
+        }
+      }
+
+      fields.insert(key.clone());
+    }
+  }
+
+  BUILTIN_FIELDS.iter()
+    .map(|f| f.to_string())
+    .chain(fields.into_iter().filter(|f| !BUILTIN_FIELDS.contains(&f.as_str())))
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::collections::HashMap;
+
+  use chrono::Utc;
+  use serde_json::json;
+  use spectral::prelude::*;
+
+  use crate::parser::{MessageKind, ReaderMetadata};
+
+  fn message(level: LogLevel, metadata: HashMap<String, Value>) -> Message {
+    Message {
+      kind: MessageKind::Json,
+      timestamp: Some(Utc::now()),
+      level: Some(level),
+      raw: String::new(),
+      text: Some("request failed".to_string()),
+      metadata,
+      reader_metadata: Some(ReaderMetadata {
+        timestamp: None,
+        source: Some("api-7d9f8/envoy".to_string())
+      }),
+      mapped_fields: HashMap::new()
+    }
+  }
+
+  fn matches(expr: &str, message: &Message) -> bool {
+    parse(expr).unwrap().matches(message)
+  }
+
+  #[test]
+  fn test_matches() {
+    let m = message(LogLevel::Error, hashmap!{
+      "status".to_string() => json!(500),
+      "http".to_string() => json!({ "method": "GET" }),
+      "retry".to_string() => json!(false)
+    });
+
+    assert_that!(matches(r#"level >= warn && pod =~ "api-.*" && fields.status == 500"#, &m)).is_true();
+    assert_that!(matches("level<warn", &m)).is_false();
+    assert_that!(matches("status >= 400 && status < 600", &m)).is_true();
+    assert_that!(matches(r#"status == "500""#, &m)).is_true();
+    assert_that!(matches("http.method == GET && container == envoy", &m)).is_true();
+    assert_that!(matches(r#"message =~ "^request \w+""#, &m)).is_true();
+    assert_that!(matches("message !~ failed || missing", &m)).is_false();
+    assert_that!(matches("!(retry) && status", &m)).is_true();
+    assert_that!(matches("missing == 1", &m)).is_false();
+  }
+
+  #[test]
+  fn test_parse_errors() {
+    assert_that!(parse("level >= loud")).is_err();
+    assert_that!(parse("status =~ \"(\"")).is_err();
+    assert_that!(parse("status == 500 &&")).is_err();
+    assert_that!(parse("")).is_err();
+  }
+
+  #[test]
+  fn test_partial_field() {
+    assert_that!(partial_field("level >= warn && po")).is_equal_to(Some(17));
+    assert_that!(partial_field("!(htt")).is_equal_to(Some(2));
+    assert_that!(partial_field("pod =~ ap")).is_none();
+    assert_that!(partial_field("")).is_equal_to(Some(0));
+  }
+
+  #[test]
+  fn test_known_fields() {
+    let m = message(LogLevel::Info, hashmap!{
+      "status".to_string() => json!(200),
+      "http".to_string() => json!({ "method": "GET" }),
+      "level".to_string() => json!("info")
+    });
+
+    assert_that!(known_fields(vec![&m].into_iter())).is_equal_to(
+      ["timestamp", "level", "message", "kind", "source", "pod", "container", "http", "http.method", "status"]
+        .iter().map(|s| s.to_string()).collect::<Vec<_>>()
+    );
+  }
+}
@@ -3,8 +3,10 @@
 use std::marker::Sized;
 
 use regex::Regex;
+use serde::{Deserialize, Serialize};
 use simple_error::{SimpleError, SimpleResult};
 
+use crate::expression::{self, Expression};
 use crate::parser::Message;
 
 pub trait Filter {
@@ -13,6 +15,21 @@ pub trait Filter {
   /// Determines if the filter is inverted
   fn inverted(&self) -> bool;
 
+  /// The mode used to parse this filter's query
+  fn mode(&self) -> FilterMode;
+
+  /// The query this filter was created from
+  fn query(&self) -> &str;
+
+  /// A short description of the filter, e.g. `!error` for an inverted filter
+  fn describe(&self) -> String {
+    if self.inverted() {
+      format!("!{}", self.query())
+    } else {
+      self.query().to_string()
+    }
+  }
+
   /// Determines if the given message matches the filter without checking if the
   /// filter is inverted or not
   fn filter_pass(&self, message: &Message) -> bool;
@@ -30,17 +47,21 @@ pub trait Filter {
   }
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum FilterMode {
   Text,
-  Regex
+  Regex,
+  #[serde(alias = "expr")]
+  Expression
 }
 
 impl FilterMode {
   pub fn parse(self, filter: &str, inverted: bool) -> SimpleResult<Box<dyn Filter>> {
     Ok(match self {
       FilterMode::Text => Box::new(FullTextFilter::new(filter, inverted)?),
-      FilterMode::Regex => Box::new(RegexFilter::new(filter, inverted)?)
+      FilterMode::Regex => Box::new(RegexFilter::new(filter, inverted)?),
+      FilterMode::Expression => Box::new(ExpressionFilter::new(filter, inverted)?)
     })
   }
 
@@ -50,14 +71,16 @@ impl FilterMode {
     // will probably need to be smarter if more modes are added
     match self {
       FilterMode::Text => FilterMode::Regex,
-      FilterMode::Regex => FilterMode::Text
+      FilterMode::Regex => FilterMode::Expression,
+      FilterMode::Expression => FilterMode::Text
     }
   }
 
   pub fn name(self) -> &'static str {
     match self {
       FilterMode::Text => "text",
-      FilterMode::Regex => "regex"
+      FilterMode::Regex => "regex",
+      FilterMode::Expression => "expr"
     }
   }
 }
@@ -108,6 +131,14 @@ impl Filter for FullTextFilter {
   fn inverted(&self) -> bool {
     self.inverted
   }
+
+  fn mode(&self) -> FilterMode {
+    FilterMode::Text
+  }
+
+  fn query(&self) -> &str {
+    &self.query
+  }
 }
 
 pub struct RegexFilter {
@@ -155,4 +186,45 @@ impl Filter for RegexFilter {
   fn inverted(&self) -> bool {
     self.inverted
   }
+
+  fn mode(&self) -> FilterMode {
+    FilterMode::Regex
+  }
+
+  fn query(&self) -> &str {
+    self.re.as_str()
+  }
+}
+
+/// Filters on parsed fields with an expression, e.g. `level >= warn && status == 500`
+pub struct ExpressionFilter {
+  query: String,
+  expression: Expression,
+  inverted: bool
+}
+
+impl Filter for ExpressionFilter {
+  fn new(expr: &str, inverted: bool) -> SimpleResult<Self> {
+    expression::parse(expr).map(|expression| ExpressionFilter {
+      query: expr.to_string(),
+      expression,
+      inverted
+    })
+  }
+
+  fn filter_pass(&self, message: &Message) -> bool {
+    self.expression.matches(message)
+  }
+
+  fn inverted(&self) -> bool {
+    self.inverted
+  }
+
+  fn mode(&self) -> FilterMode {
+    FilterMode::Expression
+  }
+
+  fn query(&self) -> &str {
+    &self.query
+  }
 }
@@ -0,0 +1,166 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+use std::collections::BTreeMap;
+use std::fs::{self, File};
+use std::io::BufReader;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+use simple_error::{SimpleError, SimpleResult};
+
+use crate::config::FileConfig;
+use crate::filter::{Filter, FilterMode};
+use crate::renderer::layout::Density;
+
+/// The most filters and searches remembered, each
+const MAX_HISTORY: usize = 100;
+
+/// A filter or search as entered, which can be parsed again later
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SavedFilter {
+  pub mode: FilterMode,
+  pub query: String,
+
+  #[serde(default)]
+  pub inverted: bool
+}
+
+impl SavedFilter {
+  pub fn new(mode: FilterMode, query: &str, inverted: bool) -> Self {
+    SavedFilter { mode, query: query.to_string(), inverted }
+  }
+
+  pub fn of(filter: &dyn Filter) -> Self {
+    SavedFilter::new(filter.mode(), filter.query(), filter.inverted())
+  }
+
+  pub fn parse(&self) -> SimpleResult<Box<dyn Filter>> {
+    self.mode.parse(&self.query, self.inverted)
+  }
+}
+
+/// Recent filters and searches, filters saved by name, and appearance chosen
+/// in the interactive renderer, persisted between sessions in `history.yaml`
+/// beside the config file
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct History {
+  /// Recent filters, oldest first
+  #[serde(default)]
+  pub filters: Vec<SavedFilter>,
+
+  /// Recent searches, oldest first
+  #[serde(default)]
+  pub searches: Vec<SavedFilter>,
+
+  /// Filters saved with `:save <name>`, applied with `:preset <name>` or
+  /// `--filter-preset <name>`
+  #[serde(default)]
+  pub presets: BTreeMap<String, SavedFilter>,
+
+  /// The theme last chosen with `T`, used unless `--style` is set
+  #[serde(default)]
+  pub theme: Option<String>,
+
+  /// The density last chosen with `D`, used unless `--density` is set
+  #[serde(default)]
+  pub density: Option<Density>
+}
+
+/// Appends `item` to a history list, moving it to the end if it's already
+/// present and forgetting the oldest items past `MAX_HISTORY`
+fn remember(list: &mut Vec<SavedFilter>, item: SavedFilter) {
+  list.retain(|i| *i != item);
+  list.push(item);
+
+  if list.len() > MAX_HISTORY {
+    list.drain(..list.len() - MAX_HISTORY);
+  }
+}
+
+impl History {
+  /// The history file path, e.g. `~/.config/woodchipper/history.yaml`
+  pub fn default_path() -> PathBuf {
+    FileConfig::default_path().with_file_name("history.yaml")
+  }
+
+  /// Loads history from the default path, if it exists
+  pub fn load() -> SimpleResult<History> {
+    let path = History::default_path();
+    if !path.exists() {
+      return Ok(History::default());
+    }
+
+    let file = File::open(&path).map_err(SimpleError::from)?;
+    serde_yaml::from_reader(BufReader::new(file)).map_err(|e| SimpleError::new(
+      format!("error loading history {}: {}", path.display(), e)
+    ))
+  }
+
+  pub fn save(&self) -> SimpleResult<()> {
+    let path = History::default_path();
+    if let Some(dir) = path.parent() {
+      fs::create_dir_all(dir).map_err(SimpleError::from)?;
+    }
+
+    let file = File::create(&path).map_err(|e| SimpleError::new(
+      format!("could not write history {}: {}", path.display(), e)
+    ))?;
+
+    serde_yaml::to_writer(file, self).map_err(SimpleError::from)
+  }
+
+  pub fn remember_filter(&mut self, filter: SavedFilter) {
+    remember(&mut self.filters, filter);
+  }
+
+  pub fn remember_search(&mut self, search: SavedFilter) {
+    remember(&mut self.searches, search);
+  }
+
+  pub fn preset(&self, name: &str) -> SimpleResult<&SavedFilter> {
+    match self.presets.get(name) {
+      Some(preset) => Ok(preset),
+      None if self.presets.is_empty() => bail!("unknown filter preset: {}, none are saved", name),
+      None => bail!(
+        "unknown filter preset: {}, expected one of: {}",
+        name, self.presets.keys().cloned().collect::<Vec<String>>().join(", ")
+      )
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use spectral::prelude::*;
+
+  #[test]
+  fn test_remember() {
+    let filter = |query: &str| SavedFilter::new(FilterMode::Regex, query, false);
+
+    let mut history = History::default();
+    history.remember_filter(filter("a"));
+    history.remember_filter(filter("b"));
+    history.remember_filter(filter("a"));
+    assert_that!(history.filters).is_equal_to(vec![filter("b"), filter("a")]);
+
+    for i in 0..MAX_HISTORY {
+      history.remember_filter(filter(&i.to_string()));
+    }
+
+    assert_that!(history.filters).has_length(MAX_HISTORY);
+    assert_that!(history.filters[0]).is_equal_to(filter("0"));
+  }
+
+  #[test]
+  fn test_preset() {
+    let yaml = "presets:\n  errors:\n    mode: expr\n    query: level >= error\n";
+    let history: History = serde_yaml::from_str(yaml).unwrap();
+
+    assert_that!(history.preset("errors")).is_ok_containing(
+      &SavedFilter::new(FilterMode::Expression, "level >= error", false)
+    );
+    assert_that!(history.preset("warnings")).is_err();
+  }
+}
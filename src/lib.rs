@@ -0,0 +1,237 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+//! woodchipper as a library, e.g. to add output formats: register a
+//! `RendererInfo` for each with `register()`, then call `run()` from your own
+//! `main()` so `--renderer` can select them.
+
+extern crate atty;
+extern crate chrono;
+#[cfg(not(target_os = "linux"))] extern crate clipboard;
+extern crate crossterm;
+#[macro_use] extern crate lazy_static;
+#[macro_use] extern crate maplit;
+extern crate pest;
+#[macro_use] extern crate pest_derive;
+extern crate rand;
+extern crate regex;
+extern crate shellexpand;
+#[macro_use] extern crate simple_error;
+extern crate structopt;
+extern crate subprocess;
+
+use std::collections::HashMap;
+use std::env;
+use std::error::Error;
+use std::process;
+use std::sync::Arc;
+use std::sync::mpsc::channel;
+use std::time::Duration;
+
+use structopt::StructOpt;
+
+mod bench;
+mod config;
+mod clip;
+mod expression;
+mod filter;
+mod history;
+mod session;
+mod subcommand;
+mod style;
+mod reader;
+mod parser;
+mod classifier;
+mod renderer;
+mod timezone;
+mod toml;
+
+pub use config::Config;
+pub use parser::{LogLevel, Message};
+pub use renderer::{LogEntry, MessageEntry};
+pub use renderer::registry::{
+  Capabilities, Renderer, RendererFactory, RendererInfo, RendererThread, register
+};
+
+#[cfg(feature = "wd-bench-alloc")]
+#[global_allocator]
+static ALLOCATOR: bench::CountingAllocator = bench::CountingAllocator;
+
+/// Runs woodchipper with the process's arguments and environment, as the
+/// `woodchipper` binary does
+pub fn run() -> Result<(), Box<dyn Error>> {
+  let mut args: Vec<String> = env::args().collect();
+  if let Some(i) = subcommand::first_positional(&args).filter(|i| args[*i] == "bench") {
+    args.remove(i);
+    return bench::run(bench::BenchConfig::from_iter(args));
+  }
+
+  let mut config = Config::load(args, env::vars())?;
+
+  // appearance chosen in the interactive renderer applies unless set
+  // explicitly; a broken history file is reported there instead
+  let saved = history::History::load().unwrap_or_default();
+
+  // user-defined themes can only be found once the config file is loaded
+  let style = std::mem::replace(&mut config.style, style::StyleConfig::default());
+  config.style = match &saved.theme {
+    Some(name) if style.name == "default" => {
+      config.load_style(name).or_else(|_| config.prepare_style(style))?
+    },
+    _ => config.prepare_style(style)?
+  };
+
+  if config.density == renderer::layout::Density::Detailed {
+    config.density = saved.density.unwrap_or(config.density);
+  }
+
+  renderer::layout::set_layout(renderer::layout::Layout {
+    breakpoints: config.breakpoints,
+    layouts: config.config_file.as_ref().map(|f| f.layouts.clone()).unwrap_or_default(),
+    density: config.density
+  });
+
+  let mut links = HashMap::new();
+  if let Some(file) = &config.config_file {
+    for (field, template) in &file.links {
+      let template = template.parse()
+        .map_err(|e| format!("invalid link template for {}: {}", field, e))?;
+
+      links.insert(field.clone(), template);
+    }
+  }
+
+  renderer::hyperlink::set_hyperlinks(renderer::hyperlink::Hyperlinks {
+    enabled: config.hyperlinks.enabled(config.color),
+    links
+  });
+
+  renderer::glyphs::set_glyphs(config.glyphs.resolve());
+  clip::set_clipboard_mode(config.clipboard);
+
+  timezone::set_display_zone(config.timezone.clone());
+  timezone::set_relative_time(config.relative_time);
+
+  // check the preset exists now, rather than once the interactive view is up
+  if let Some(name) = &config.filter_preset {
+    history::History::load()?.preset(name)?.parse()?;
+  }
+
+  // and that filters and triggers parse
+  reader::filter::parse_filters(&config)?;
+  reader::filter::time_window(&config)?;
+  reader::trigger::check_triggers(&config)?;
+
+  // likewise for the session
+  if let Some(path) = &config.open {
+    session::SessionReader::open(path)?;
+  }
+
+  let config = Arc::new(config);
+
+  let renderer_impl = config.renderer.get_renderer(&config);
+  let reader_impl = config.reader.get_reader(Arc::clone(&config));
+
+  if reader_impl == reader::read_null {
+    eprintln!(
+      "{}\n\n{}\n\n{}",
+      "error: no reader was detected, select a reader or pipe in some input",
+      Config::clap().get_matches().usage(),
+      "For more information, see --help"
+    );
+
+    process::exit(1);
+  }
+
+  let (entry_tx, entry_rx) = channel();
+  let renderer = renderer_impl.spawn(Arc::clone(&config), entry_rx);
+
+  // kick off the reader thread and hope it goes on to do great things
+  // due to blocking IO limitations we can't ever expect to actually get a
+  // result out of it, and will have to let the OS handle cleanup for us
+  let (exit_req_tx, exit_req_rx) = channel();
+  let (exit_resp_tx, exit_resp_rx) = channel();
+
+  // wrapping readers are chained from the renderer backwards, so messages
+  // flow reader -> read_multiline -> read_ordered -> read_redact
+  // -> read_anomaly -> read_filter -> read_trigger -> read_limit -> read_sink -> read_split
+  // -> read_tee -> renderer
+  let mut reader_tx = entry_tx;
+
+  if config.tee.is_some() {
+    let (tee_tx, tee_rx) = channel();
+    reader::read_tee(Arc::clone(&config), tee_rx, reader_tx);
+    reader_tx = tee_tx;
+  }
+
+  if config.split.is_some() {
+    let (split_tx, split_rx) = channel();
+    reader::read_split(Arc::clone(&config), split_rx, reader_tx);
+    reader_tx = split_tx;
+  }
+
+  if config.sink.is_some() {
+    let (sink_tx, sink_rx) = channel();
+    reader::read_sink(Arc::clone(&config), sink_rx, reader_tx);
+    reader_tx = sink_tx;
+  }
+
+  if !config.file().rules.limit.is_empty() {
+    let (limit_tx, limit_rx) = channel();
+    reader::read_limit(Arc::clone(&config), limit_rx, reader_tx);
+    reader_tx = limit_tx;
+  }
+
+  if reader::trigger::enabled(&config) {
+    let (trigger_tx, trigger_rx) = channel();
+    reader::read_trigger(Arc::clone(&config), trigger_rx, reader_tx);
+    reader_tx = trigger_tx;
+  }
+
+  if reader::filter::enabled(&config) {
+    let (filter_tx, filter_rx) = channel();
+    reader::read_filter(Arc::clone(&config), filter_rx, reader_tx);
+    reader_tx = filter_tx;
+  }
+
+  if config.anomalies {
+    let (anomaly_tx, anomaly_rx) = channel();
+    reader::read_anomaly(Arc::clone(&config), anomaly_rx, reader_tx);
+    reader_tx = anomaly_tx;
+  }
+
+  if !config.file().redact.is_empty() {
+    let (redact_tx, redact_rx) = channel();
+    reader::read_redact(Arc::clone(&config), redact_rx, reader_tx);
+    reader_tx = redact_tx;
+  }
+
+  if config.ordered || config.buffer_ms.is_some() {
+    // if --ordered or --buffer-ms, wrap the reader in read_ordered
+    let (ord_tx, ord_rx) = channel();
+    reader::read_ordered(Arc::clone(&config), ord_rx, reader_tx);
+    reader_tx = ord_tx;
+  }
+
+  // sessions hold messages as they were shown, so are already folded
+  if !config.no_multiline && config.open.is_none() {
+    let (multi_tx, multi_rx) = channel();
+    reader::read_multiline(Arc::clone(&config), multi_rx, reader_tx);
+    reader_tx = multi_tx;
+  }
+
+  reader_impl(
+    Arc::clone(&config),
+    reader_tx,
+    exit_req_rx, exit_resp_tx
+  );
+
+  renderer.join().expect("renderer thread did not exit cleanly");
+
+  // attempt to tell the reader to quit (though it'll probably be ignored)
+  exit_req_tx.send(()).ok();
+
+  // and wait at most 1s for an exit confirmation
+  exit_resp_rx.recv_timeout(Duration::from_millis(1000)).ok();
+
+  Ok(())
+}
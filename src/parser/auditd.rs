@@ -0,0 +1,280 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::Arc;
+
+use chrono::prelude::*;
+use regex::Regex;
+use serde_json::Value;
+
+use crate::config::{Config, Zone};
+use super::timestamp::localize;
+use super::types::{LogLevel, Message, MessageKind, ReaderMetadata};
+
+/// Fields the kernel hex-encodes when their values contain spaces, quotes, or
+/// control characters
+static ENCODED_FIELDS: &[&str] = &[
+  "proctitle", "cmd", "comm", "exe", "cwd", "name", "path", "data", "key",
+  "acct", "old", "new", "dir", "file", "ocomm", "watch", "vm", "old-vm",
+  "new-vm", "root_dir", "sw", "grp", "id"
+];
+
+/// The enriched section of `log_format = ENRICHED` records (uppercase fields,
+/// e.g. `UID="root"`) follows this separator
+const ENRICHED_SEPARATOR: char = '\x1d';
+
+/// Decodes a hex-encoded value, e.g. `2F62696E2F6C73` -> `/bin/ls`
+///
+/// NUL separators (as in `proctitle` argument lists) become spaces.
+fn decode_hex(s: &str) -> Option<String> {
+  if s.is_empty() || !s.chars().all(|c| c.is_ascii_hexdigit()) {
+    return None;
+  }
+
+  // an odd trailing digit has no pair, so `get` fails and so does the decode
+  let bytes: Vec<u8> = (0..s.len()).step_by(2)
+    .map(|i| s.get(i..i + 2).and_then(|pair| u8::from_str_radix(pair, 16).ok()))
+    .collect::<Option<_>>()?;
+
+  let decoded = String::from_utf8(bytes).ok()?;
+  Some(decoded.trim_end_matches('\0').replace('\0', " "))
+}
+
+fn to_value(key: &str, raw: &str) -> Value {
+  let quoted = raw.len() >= 2
+    && ((raw.starts_with('"') && raw.ends_with('"'))
+      || (raw.starts_with('\'') && raw.ends_with('\'')));
+
+  if quoted {
+    return Value::String(raw[1..raw.len() - 1].to_string());
+  }
+
+  if ENCODED_FIELDS.contains(&key) {
+    if let Some(decoded) = decode_hex(raw) {
+      return Value::String(decoded);
+    }
+  }
+
+  // syscall arguments (`a0`, `a1`, ...) are hex even when they look decimal
+  let is_arg = key.starts_with('a') && key[1..].chars().all(|c| c.is_ascii_digit());
+  if !is_arg {
+    if let Ok(i) = raw.parse::<i64>() {
+      return Value::from(i);
+    }
+  }
+
+  Value::String(raw.to_string())
+}
+
+/// Parses `key=value` pairs into `metadata`, recursing into the single-quoted
+/// `msg='...'` payload of userspace records
+fn parse_pairs(s: &str, metadata: &mut HashMap<String, Value>) {
+  lazy_static! {
+    static ref RE: Regex = Regex::new(
+      r#"(?P<key>[A-Za-z_][\w-]*)=(?P<value>"[^"]*"|'[^']*'|\S*)"#
+    ).unwrap();
+  }
+
+  for caps in RE.captures_iter(s) {
+    let key = &caps["key"];
+    let value = &caps["value"];
+
+    if key == "msg" && value.len() >= 2 && value.starts_with('\'') && value.ends_with('\'') {
+      parse_pairs(&value[1..value.len() - 1], metadata);
+      continue;
+    }
+
+    metadata.insert(key.to_string(), to_value(key, value));
+  }
+}
+
+/// Parses the `audit(...)` event time, either as epoch seconds or as
+/// interpreted by `ausearch -i`, e.g. `11/25/2020 21:26:18.409`
+fn parse_audit_time(config: &Config, s: &str) -> Option<DateTime<Utc>> {
+  lazy_static! {
+    static ref EPOCH_RE: Regex = Regex::new(r"^(\d+)(?:\.(\d{1,9}))?$").unwrap();
+  }
+
+  if let Some(caps) = EPOCH_RE.captures(s) {
+    let secs = caps[1].parse::<i64>().ok()?;
+
+    // the fraction is usually milliseconds, but scale whatever is there
+    let nanos = match caps.get(2) {
+      Some(frac) => {
+        let digits = frac.as_str();
+        digits.parse::<u32>().ok()? * 10u32.pow(9 - digits.len() as u32)
+      },
+      None => 0
+    };
+
+    return Utc.timestamp_opt(secs, nanos).single();
+  }
+
+  let naive = NaiveDateTime::parse_from_str(s, "%m/%d/%Y %H:%M:%S%.f").ok()?;
+  localize(config, &naive, Zone::Local)
+}
+
+/// Picks a log level; records are informational unless they report a failure
+fn level_for_result(metadata: &HashMap<String, Value>) -> LogLevel {
+  let failed = match (metadata.get("success"), metadata.get("res")) {
+    (Some(Value::String(s)), _) => s == "no",
+    (_, Some(Value::String(s))) => s == "failed",
+    (_, Some(Value::Number(n))) => n.as_i64() == Some(0),
+    _ => false
+  };
+
+  if failed {
+    LogLevel::Warning
+  } else {
+    LogLevel::Info
+  }
+}
+
+/// Parses Linux audit records from `audit.log`, e.g.:
+///
+/// ```text
+/// type=SYSCALL msg=audit(1614679164.123:456): arch=c000003e syscall=59 success=yes comm="ls" exe="/usr/bin/ls"
+/// ```
+///
+/// The record type and serial number are kept as `type` and `serial` fields,
+/// along with the record's `key=value` pairs. Hex-encoded values (e.g.
+/// `proctitle`) are decoded, and the `msg='...'` payloads of userspace records
+/// are expanded into their own fields.
+pub fn parse_auditd(
+  config: Arc<Config>, line: &str, meta: Option<ReaderMetadata>
+) -> Result<Option<Message>, Box<dyn Error>> {
+  lazy_static! {
+    static ref RE: Regex = Regex::new(concat!(
+      r"^(?:node=(?P<node>\S+) )?type=(?P<type>\S+) ",
+      r"msg=audit\((?P<time>[^)]+):(?P<serial>\d+)\):\s*(?P<rest>.*)$"
+    )).unwrap();
+  }
+
+  if !line.starts_with("type=") && !line.starts_with("node=") {
+    return Ok(None);
+  }
+
+  let caps = match RE.captures(line) {
+    Some(caps) => caps,
+    None => return Ok(None)
+  };
+
+  let mut metadata = HashMap::new();
+  let mut rest = caps["rest"].splitn(2, ENRICHED_SEPARATOR);
+  if let Some(fields) = rest.next() {
+    parse_pairs(fields, &mut metadata);
+  }
+
+  if let Some(enriched) = rest.next() {
+    parse_pairs(enriched, &mut metadata);
+  }
+
+  let kind = caps["type"].to_string();
+  metadata.insert("type".to_string(), Value::String(kind.clone()));
+  if let Ok(serial) = caps["serial"].parse::<u64>() {
+    metadata.insert("serial".to_string(), Value::from(serial));
+  }
+
+  if let Some(node) = caps.name("node") {
+    metadata.insert("node".to_string(), Value::String(node.as_str().to_string()));
+  }
+
+  let timestamp = parse_audit_time(&config, &caps["time"])
+    .or_else(|| meta.as_ref().and_then(|m| m.timestamp));
+
+  Ok(Some(Message {
+    kind: MessageKind::Auditd,
+    raw: line.to_string(),
+    level: Some(level_for_result(&metadata)),
+    text: Some(kind),
+    reader_metadata: meta,
+    mapped_fields: HashMap::new(),
+    timestamp, metadata
+  }))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use serde_json::json;
+  use simple_error::{SimpleResult, SimpleError};
+  use spectral::prelude::*;
+  use structopt::StructOpt;
+
+  fn parse_to_value(line: &str) -> SimpleResult<Value> {
+    let config = Arc::new(Config::from_iter_safe(vec![""]).unwrap());
+    let parsed = parse_auditd(config, line, None)
+      .map_err(|e| SimpleError::new(format!("{:?}", e)))?;
+
+    serde_json::to_value(parsed).map_err(SimpleError::from)
+  }
+
+  #[test]
+  fn test_not_auditd() {
+    assert_that!(parse_to_value("hello world")).is_ok_containing(json!(null));
+    assert_that!(parse_to_value("type=foo bar")).is_ok_containing(json!(null));
+  }
+
+  #[test]
+  fn test_syscall() {
+    let line = concat!(
+      r#"type=SYSCALL msg=audit(1614679164.123:456): arch=c000003e syscall=59 "#,
+      r#"success=no exit=-13 a0=55d1 items=2 pid=1234 comm="ls" "#,
+      "exe=2F746D702F6D7920657865 key=(null)\x1dARCH=x86_64 UID=\"root\""
+    );
+
+    assert_that!(parse_to_value(line)).is_ok_containing(json!({
+      "kind": "auditd",
+      "raw": line,
+      "timestamp": "2021-03-02T09:59:24.123Z",
+      "level": "warning",
+      "text": "SYSCALL",
+      "metadata": {
+        "type": "SYSCALL",
+        "serial": 456,
+        "arch": "c000003e",
+        "syscall": 59,
+        "success": "no",
+        "exit": -13,
+        "a0": "55d1",
+        "items": 2,
+        "pid": 1234,
+        "comm": "ls",
+        "exe": "/tmp/my exe",
+        "key": "(null)",
+        "ARCH": "x86_64",
+        "UID": "root"
+      }
+    }));
+  }
+
+  #[test]
+  fn test_user_message() {
+    let line = concat!(
+      r#"node=web1 type=USER_LOGIN msg=audit(1614679164.5:7): pid=9 "#,
+      r#"msg='op=login acct="jane" exe="/usr/sbin/sshd" res=success'"#
+    );
+
+    assert_that!(parse_to_value(line)).is_ok_containing(json!({
+      "kind": "auditd",
+      "raw": line,
+      "timestamp": "2021-03-02T09:59:24.500Z",
+      "level": "info",
+      "text": "USER_LOGIN",
+      "metadata": {
+        "node": "web1",
+        "type": "USER_LOGIN",
+        "serial": 7,
+        "pid": 9,
+        "op": "login",
+        "acct": "jane",
+        "exe": "/usr/sbin/sshd",
+        "res": "success"
+      }
+    }));
+
+    assert_that!(decode_hex("2F62696E2F6C73002D6C61")).is_equal_to(Some("/bin/ls -la".to_string()));
+  }
+}
@@ -0,0 +1,160 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+use std::error::Error;
+
+use serde_json::{Map, Value};
+
+use crate::config::Config;
+use super::json::parse_document;
+use super::types::{LogLevel, MappingField, Message, MessageKind, ReaderMetadata};
+
+/// Fields bunyan includes in every record
+static BUNYAN_FIELDS: &[&str] = &["v", "level", "name", "hostname", "pid", "time", "msg"];
+
+/// Maps bunyan's numeric log levels, also used by pino
+pub fn level_for_number(level: u64) -> Option<LogLevel> {
+  match level {
+    0..=20 => Some(LogLevel::Debug),
+    21..=30 => Some(LogLevel::Info),
+    31..=40 => Some(LogLevel::Warning),
+    41..=50 => Some(LogLevel::Error),
+    _ => Some(LogLevel::Fatal)
+  }
+}
+
+/// Determines if a JSON document looks like a bunyan record
+pub fn is_bunyan_document(doc: &Map<String, Value>) -> bool {
+  BUNYAN_FIELDS.iter().all(|field| doc.contains_key(*field))
+    && doc.get("v").map(Value::is_number).unwrap_or(false)
+    && doc.get("level").map(Value::is_number).unwrap_or(false)
+}
+
+/// Maps a record with bunyan-like conventions (shared by pino) into a Message
+///
+/// The record's `name` is normalized to `logger` so it's shown as context, and
+/// any `err.stack` is appended to the message text so it renders as a block
+/// rather than one very long field.
+pub fn parse_node_document(
+  config: &Config,
+  line: &str,
+  kind: MessageKind,
+  mut doc: Map<String, Value>,
+  meta: Option<ReaderMetadata>
+) -> Result<Option<Message>, Box<dyn Error>> {
+  let level = doc.remove("level")
+    .and_then(|l| l.as_u64())
+    .and_then(|l| config.file().levels.get(&l.to_string()).or_else(|| level_for_number(l)));
+
+  if let Some(name) = doc.remove("name") {
+    doc.insert("logger".to_string(), name);
+  }
+
+  let stack = match doc.get_mut("err") {
+    Some(Value::Object(err)) => err.remove("stack"),
+    _ => None
+  };
+
+  let message = parse_document(config, line, kind, doc, meta)?;
+
+  Ok(message.map(|mut message| {
+    message.level = level;
+    message.mapped_fields.insert("level".to_string(), MappingField::Level);
+
+    if let Some(Value::String(stack)) = stack {
+      message.text = Some(match message.text.take() {
+        Some(text) => format!("{}\n{}", text, stack),
+        None => stack
+      });
+    }
+
+    message
+  }))
+}
+
+/// Maps a bunyan record into a Message
+pub fn parse_bunyan_document(
+  config: &Config,
+  line: &str,
+  mut doc: Map<String, Value>,
+  meta: Option<ReaderMetadata>
+) -> Result<Option<Message>, Box<dyn Error>> {
+  // the format version is just noise
+  doc.remove("v");
+
+  parse_node_document(config, line, MessageKind::Bunyan, doc, meta)
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::Arc;
+
+  use serde_json::json;
+  use simple_error::{SimpleResult, SimpleError};
+  use spectral::prelude::*;
+  use structopt::StructOpt;
+
+  use crate::config::Config;
+  use crate::parser::json::parse_json;
+
+  fn parse_to_value(line: &str) -> SimpleResult<serde_json::Value> {
+    let config = Arc::new(Config::from_iter_safe(vec![""]).unwrap());
+    let parsed = parse_json(config, line, None)
+      .map_err(|e| SimpleError::new(format!("{:?}", e)))?;
+
+    serde_json::to_value(parsed).map_err(SimpleError::from)
+  }
+
+  #[test]
+  fn test_bunyan() {
+    let line = concat!(
+      r#"{"name":"myapp","hostname":"banana.local","pid":40161,"level":30,"#,
+      r#""msg":"hi","time":"2013-01-04T18:46:23.851Z","v":0}"#
+    );
+
+    assert_that!(parse_to_value(line)).is_ok_containing(json!({
+      "kind": "bunyan",
+      "raw": line,
+      "timestamp": "2013-01-04T18:46:23.851Z",
+      "level": "info",
+      "text": "hi",
+      "mapped_fields": {
+        "time": "timestamp",
+        "level": "level",
+        "msg": "text"
+      },
+      "metadata": {
+        "logger": "myapp",
+        "hostname": "banana.local",
+        "pid": 40161
+      }
+    }));
+  }
+
+  #[test]
+  fn test_bunyan_stack() {
+    let line = concat!(
+      r#"{"name":"myapp","hostname":"banana.local","pid":40161,"level":50,"#,
+      r#""err":{"message":"boom","name":"Error","stack":"Error: boom\n    at foo (foo.js:1:1)"},"#,
+      r#""msg":"request failed","time":"2013-01-04T18:46:23.851Z","v":0}"#
+    );
+
+    assert_that!(parse_to_value(line)).is_ok_containing(json!({
+      "kind": "bunyan",
+      "raw": line,
+      "timestamp": "2013-01-04T18:46:23.851Z",
+      "level": "error",
+      "text": "request failed\nError: boom\n    at foo (foo.js:1:1)",
+      "mapped_fields": {
+        "time": "timestamp",
+        "level": "level",
+        "msg": "text"
+      },
+      "metadata": {
+        "logger": "myapp",
+        "hostname": "banana.local",
+        "pid": 40161,
+        "err": {"message": "boom", "name": "Error"}
+      }
+    }));
+  }
+}
@@ -0,0 +1,162 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+use std::collections::BTreeMap;
+
+use regex::Regex;
+use serde::Deserialize;
+use serde_json::Value;
+
+use crate::config::Config;
+use crate::style::{RuleConditions, de_opt_level, de_opt_regex};
+use super::types::{LogLevel, Message, MessageKind};
+
+/// A rule from the config file's `rules.classify` section, adjusting messages
+/// matching all of its conditions as they're parsed, e.g.:
+///
+/// ```yaml
+/// rules:
+///   classify:
+///     - field: logger
+///       matches: '^sqlalchemy'
+///       tags: [db]
+///     - message: 'deprecated'
+///       level: warn
+///       fields: {category: deprecation}
+/// ```
+///
+/// Every matching rule applies, in order, before any filters, triggers, or
+/// styles, so they see the adjusted message.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ClassifyRule {
+  #[serde(flatten)]
+  pub conditions: RuleConditions,
+
+  /// A regex the message's source must match, e.g. a pod name
+  #[serde(default, deserialize_with = "de_opt_regex")]
+  pub source: Option<Regex>,
+
+  /// A level to give matching messages, replacing any they had
+  #[serde(default, deserialize_with = "de_opt_level")]
+  pub level: Option<LogLevel>,
+
+  /// Tags added to the message's `tags` field
+  #[serde(default)]
+  pub tags: Vec<String>,
+
+  /// Fields to set on the message, replacing any with the same name
+  #[serde(default)]
+  pub fields: BTreeMap<String, Value>
+}
+
+impl ClassifyRule {
+  fn matches(&self, message: &Message) -> bool {
+    if let Some(re) = &self.source {
+      match message.reader_metadata.as_ref().and_then(|m| m.source.as_ref()) {
+        Some(source) if re.is_match(source) => (),
+        _ => return false
+      }
+    }
+
+    self.conditions.matches(message)
+  }
+
+  fn apply(&self, message: &mut Message) {
+    if let Some(level) = self.level {
+      message.level = Some(level);
+    }
+
+    for (key, value) in &self.fields {
+      message.metadata.insert(key.clone(), value.clone());
+    }
+
+    if self.tags.is_empty() {
+      return;
+    }
+
+    let tags = message.metadata.entry("tags".to_string())
+      .or_insert_with(|| Value::Array(Vec::new()));
+
+    // keep an existing non-list `tags` field as the first tag
+    if !tags.is_array() {
+      *tags = Value::Array(vec![tags.take()]);
+    }
+
+    if let Value::Array(values) = tags {
+      for tag in &self.tags {
+        let tag = Value::String(tag.clone());
+        if !values.contains(&tag) {
+          values.push(tag);
+        }
+      }
+    }
+  }
+}
+
+/// Applies the config file's classify rules
+pub fn apply_classify_rules(config: &Config, message: &mut Message) {
+  if message.kind == MessageKind::Internal {
+    return;
+  }
+
+  for rule in &config.file().rules.classify {
+    if rule.matches(message) {
+      rule.apply(message);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::sync::Arc;
+
+  use serde_json::json;
+  use spectral::prelude::*;
+  use structopt::StructOpt;
+
+  use crate::config::FileConfig;
+  use crate::parser::{parse, ReaderMetadata};
+
+  #[test]
+  fn test_classify() {
+    let mut config = Config::from_iter_safe(vec![""]).unwrap();
+    config.config_file = Some(serde_yaml::from_str::<FileConfig>(concat!(
+      "rules:\n",
+      "  classify:\n",
+      "    - field: logger\n",
+      "      matches: '^sqlalchemy'\n",
+      "      tags: [db]\n",
+      "    - message: deprecated\n",
+      "      level: warn\n",
+      "      tags: [db, deprecation]\n",
+      "      fields: {team: storage}\n",
+      "    - source: '^batch-'\n",
+      "      level: debug\n"
+    )).unwrap());
+
+    let config = Arc::new(config);
+    let parse = |line: &str, source: &str| {
+      let meta = ReaderMetadata { timestamp: None, source: Some(source.to_string()) };
+      parse(Arc::clone(&config), line, Some(meta)).unwrap().unwrap()
+    };
+
+    let db = parse(
+      r#"{"level": "info", "logger": "sqlalchemy.engine", "msg": "deprecated API"}"#, "api"
+    );
+    assert_that!(db.level).is_equal_to(Some(LogLevel::Warning));
+    assert_that!(db.metadata["tags"]).is_equal_to(json!(["db", "deprecation"]));
+    assert_that!(db.metadata["team"]).is_equal_to(json!("storage"));
+
+    let other = parse(r#"{"level": "info", "logger": "app", "tags": "gc", "msg": "ok"}"#, "api");
+    assert_that!(other.level).is_equal_to(Some(LogLevel::Info));
+    assert_that!(other.metadata["tags"]).is_equal_to(json!("gc"));
+
+    let batch = parse(r#"{"level": "error", "msg": "retrying"}"#, "batch-7");
+    assert_that!(batch.level).is_equal_to(Some(LogLevel::Debug));
+
+    assert_that!(serde_yaml::from_str::<FileConfig>(
+      "rules:\n  classify:\n    - level: loud\n"
+    ).is_err()).is_true();
+  }
+}
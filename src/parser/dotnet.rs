@@ -0,0 +1,243 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::Arc;
+
+use chrono::prelude::*;
+use regex::Regex;
+use serde_json::Value;
+
+use crate::config::{Config, Zone};
+use super::timestamp::localize;
+use super::types::{LogLevel, Message, MessageKind, ReaderMetadata};
+
+/// Maps both Serilog's three-letter level codes and the four-letter codes used
+/// by Microsoft.Extensions.Logging's console formatter
+fn map_dotnet_level(config: &Config, level: &str) -> Option<LogLevel> {
+  config.file().levels.get(level).or(match level {
+    "VRB" | "DBG" | "trce" | "dbug" => Some(LogLevel::Debug),
+    "INF" | "info" => Some(LogLevel::Info),
+    "WRN" | "warn" => Some(LogLevel::Warning),
+    "ERR" | "fail" => Some(LogLevel::Error),
+    "FTL" | "crit" => Some(LogLevel::Fatal),
+    _ => None
+  })
+}
+
+/// Parses a Serilog timestamp, either a full `yyyy-MM-dd HH:mm:ss.fff zzz` or
+/// the console sink's time-only `HH:mm:ss`
+///
+/// Time-only timestamps are assumed to be from the same (local) day as the
+/// reader timestamp, if any, or today otherwise.
+fn parse_serilog_datetime(
+  config: &Config, s: &str, meta: &Option<ReaderMetadata>
+) -> Option<DateTime<Utc>> {
+  if let Ok(dt) = DateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S%.f %:z") {
+    return Some(dt.with_timezone(&Utc));
+  }
+
+  let time = NaiveTime::parse_from_str(s, "%H:%M:%S%.f").ok()?;
+  let date = meta.as_ref()
+    .and_then(|m| m.timestamp)
+    .map(|t| t.with_timezone(&Local))
+    .unwrap_or_else(Local::now)
+    .date_naive();
+
+  localize(config, &date.and_time(time), Zone::Local)
+}
+
+/// Parses Serilog's default console and file output templates, e.g.:
+///
+/// ```text
+/// [12:00:00 INF] message
+/// 2020-05-01 12:00:00.123 +00:00 [INF] message
+/// ```
+fn parse_serilog(
+  config: &Config, line: &str, meta: Option<ReaderMetadata>
+) -> Option<Message> {
+  lazy_static! {
+    static ref RE: Regex = Regex::new(concat!(
+      r"^(?:\[(?P<time>\d{2}:\d{2}:\d{2}(?:\.\d+)?) (?P<console_level>[A-Z]{3})\]|",
+      r"(?P<datetime>\d{4}-\d{2}-\d{2} \d{2}:\d{2}:\d{2}(?:\.\d+)? [+-]\d{2}:\d{2}) ",
+      r"\[(?P<file_level>[A-Z]{3})\]) (?P<text>.*)$"
+    )).unwrap();
+  }
+
+  let caps = RE.captures(line)?;
+  let level = caps.name("console_level")
+    .or_else(|| caps.name("file_level"))
+    .and_then(|l| map_dotnet_level(config, l.as_str()));
+
+  // other formats might conceivably share the syntax, so insist on a known
+  // level
+  level?;
+
+  let timestamp = caps.name("time")
+    .or_else(|| caps.name("datetime"))
+    .and_then(|t| parse_serilog_datetime(config, t.as_str(), &meta))
+    .or_else(|| meta.as_ref().and_then(|m| m.timestamp));
+
+  Some(Message {
+    kind: MessageKind::Dotnet,
+    raw: line.to_string(),
+    text: caps.name("text").map(|t| t.as_str().to_string()),
+    metadata: HashMap::new(),
+    mapped_fields: HashMap::new(),
+    reader_metadata: meta,
+
+    timestamp, level
+  })
+}
+
+/// Parses the header line of Microsoft.Extensions.Logging's console formatter,
+/// e.g.:
+///
+/// ```text
+/// info: Microsoft.Hosting.Lifetime[0]
+///       Now listening on: http://localhost:5000
+/// ```
+///
+/// The message itself is on the following indented lines, which are folded
+/// in by `read_multiline()`.
+fn parse_mel(config: &Config, line: &str, meta: Option<ReaderMetadata>) -> Option<Message> {
+  lazy_static! {
+    static ref RE: Regex = Regex::new(
+      r"^(?P<level>trce|dbug|info|warn|fail|crit): (?P<category>[^\[\s]+)\[(?P<event_id>\d+)\]\s*(?P<text>.*)$"
+    ).unwrap();
+  }
+
+  let caps = RE.captures(line)?;
+
+  let mut metadata = HashMap::new();
+  if let Some(category) = caps.name("category") {
+    metadata.insert(
+      "category".to_string(),
+      Value::String(category.as_str().to_string())
+    );
+  }
+
+  if let Some(id) = caps.name("event_id").and_then(|i| i.as_str().parse::<u64>().ok()) {
+    metadata.insert("event_id".to_string(), Value::Number(id.into()));
+  }
+
+  let text = caps.name("text")
+    .map(|t| t.as_str().trim())
+    .filter(|t| !t.is_empty())
+    .map(String::from);
+
+  Some(Message {
+    kind: MessageKind::Dotnet,
+    raw: line.to_string(),
+    timestamp: meta.as_ref().and_then(|m| m.timestamp),
+    level: caps.name("level").and_then(|l| map_dotnet_level(config, l.as_str())),
+    mapped_fields: HashMap::new(),
+    reader_metadata: meta,
+
+    text, metadata
+  })
+}
+
+/// Parses common .NET console log output, from either Serilog or
+/// Microsoft.Extensions.Logging
+pub fn parse_dotnet(
+  config: Arc<Config>, line: &str, meta: Option<ReaderMetadata>
+) -> Result<Option<Message>, Box<dyn Error>> {
+  if let Some(message) = parse_serilog(&config, line, meta.clone()) {
+    return Ok(Some(message));
+  }
+
+  Ok(parse_mel(&config, line, meta))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use serde_json::json;
+  use simple_error::{SimpleResult, SimpleError};
+  use spectral::prelude::*;
+  use structopt::StructOpt;
+
+  use crate::config::FileConfig;
+
+  fn parse_to_value(line: &str, meta: Option<ReaderMetadata>) -> SimpleResult<Value> {
+    let config = Arc::new(Config::from_iter_safe(vec![""]).unwrap());
+    let parsed = parse_dotnet(config, line, meta)
+      .map_err(|e| SimpleError::new(format!("{:?}", e)))?;
+
+    serde_json::to_value(parsed).map_err(SimpleError::from)
+  }
+
+  #[test]
+  fn test_serilog_console() {
+    let reader_time = Local.from_local_datetime(
+      &NaiveDate::from_ymd_opt(2020, 5, 1).unwrap().and_hms_opt(18, 0, 0).unwrap()
+    ).unwrap().with_timezone(&Utc);
+
+    let meta = ReaderMetadata {
+      timestamp: Some(reader_time),
+      source: None
+    };
+
+    let expected_time = Local.from_local_datetime(
+      &NaiveDate::from_ymd_opt(2020, 5, 1).unwrap().and_hms_opt(12, 0, 3).unwrap()
+    ).unwrap().with_timezone(&Utc);
+
+    let line = "[12:00:03 WRN] Disk space is low";
+    assert_that!(parse_to_value(line, Some(meta))).is_ok_containing(json!({
+      "kind": "dotnet",
+      "raw": line,
+      "timestamp": expected_time,
+      "level": "warning",
+      "text": "Disk space is low",
+      "reader_metadata": {
+        "timestamp": reader_time,
+        "source": null
+      }
+    }));
+  }
+
+  #[test]
+  fn test_serilog_file() {
+    let line = "2020-05-01 12:00:00.123 +02:00 [FTL] Application terminated";
+    assert_that!(parse_to_value(line, None)).is_ok_containing(json!({
+      "kind": "dotnet",
+      "raw": line,
+      "timestamp": "2020-05-01T10:00:00.123Z",
+      "level": "fatal",
+      "text": "Application terminated"
+    }));
+  }
+
+  #[test]
+  fn test_mel() {
+    let line = "fail: Microsoft.AspNetCore.Server.Kestrel[13]";
+    assert_that!(parse_to_value(line, None)).is_ok_containing(json!({
+      "kind": "dotnet",
+      "raw": line,
+      "level": "error",
+      "metadata": {
+        "category": "Microsoft.AspNetCore.Server.Kestrel",
+        "event_id": 13
+      }
+    }));
+  }
+
+  #[test]
+  fn test_not_dotnet() {
+    assert_that!(parse_to_value("[12:00:00 ABC] hello", None)).is_ok_containing(json!(null));
+    assert_that!(parse_to_value("info: hello world", None)).is_ok_containing(json!(null));
+  }
+
+  #[test]
+  fn test_level_synonyms() {
+    let mut config = Config::from_iter_safe(vec![""]).unwrap();
+    config.config_file = Some(serde_yaml::from_str::<FileConfig>(
+      "levels: {debug: [INF]}"
+    ).unwrap());
+
+    assert_that!(map_dotnet_level(&config, "INF")).is_equal_to(Some(LogLevel::Debug));
+    assert_that!(map_dotnet_level(&config, "fail")).is_equal_to(Some(LogLevel::Error));
+  }
+}
@@ -0,0 +1,168 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+use serde_json::{Map, Value};
+
+use crate::config::Config;
+use super::json::flatten_value;
+use super::types::{Message, MessageKind};
+
+/// Finds the end of the balanced JSON object starting at `start`, if any
+fn find_object_end(s: &str, start: usize) -> Option<usize> {
+  let mut depth = 0;
+  let mut in_string = false;
+  let mut escaped = false;
+
+  for (i, c) in s[start..].char_indices() {
+    if in_string {
+      match c {
+        _ if escaped => escaped = false,
+        '\\' => escaped = true,
+        '"' => in_string = false,
+        _ => ()
+      }
+
+      continue;
+    }
+
+    match c {
+      '"' => in_string = true,
+      '{' => depth += 1,
+      '}' => {
+        depth -= 1;
+        if depth == 0 {
+          return Some(start + i + 1);
+        }
+      },
+      _ => ()
+    }
+  }
+
+  None
+}
+
+/// Finds all JSON objects embedded in a string, returning each parsed object
+/// with its byte range
+pub fn find_objects(s: &str) -> Vec<(Map<String, Value>, usize, usize)> {
+  let mut ret = Vec::new();
+  let mut pos = 0;
+
+  while let Some(offset) = s[pos..].find('{') {
+    let start = pos + offset;
+
+    let parsed = find_object_end(s, start).and_then(|end| {
+      serde_json::from_str::<Map<String, Value>>(&s[start..end])
+        .ok()
+        .map(|object| (object, end))
+    });
+
+    match parsed {
+      Some((object, end)) => {
+        ret.push((object, start, end));
+        pos = end;
+      },
+      None => pos = start + 1
+    }
+  }
+
+  ret
+}
+
+/// Lifts JSON objects embedded in plaintext messages into metadata, e.g.
+/// `request completed {"status": 500}`, leaving the surrounding text as the
+/// message
+pub fn extract_embedded_json(config: &Config, message: &mut Message) {
+  if message.kind != MessageKind::Plain || config.no_embedded_json {
+    return;
+  }
+
+  let text = match &message.text {
+    Some(text) if text.contains('{') => text,
+    _ => return
+  };
+
+  let objects = find_objects(text);
+  if objects.is_empty() {
+    return;
+  }
+
+  let mut remaining: Vec<&str> = Vec::new();
+  let mut pos = 0;
+  for (object, start, end) in &objects {
+    remaining.push(text[pos..*start].trim());
+    pos = *end;
+
+    let flatten = &config.file().flatten;
+    for (key, value) in object {
+      if !message.metadata.contains_key(key) {
+        flatten_value(flatten, key.clone(), value, 0, &mut message.metadata);
+      }
+    }
+  }
+  remaining.push(text[pos..].trim());
+
+  let remaining = remaining.into_iter()
+    .filter(|s| !s.is_empty())
+    .collect::<Vec<&str>>()
+    .join(" ");
+
+  message.text = if remaining.is_empty() { None } else { Some(remaining) };
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::collections::HashMap;
+
+  use serde_json::json;
+  use spectral::prelude::*;
+  use structopt::StructOpt;
+
+  fn plain(text: &str) -> Message {
+    Message {
+      kind: MessageKind::Plain,
+      timestamp: None,
+      level: None,
+      raw: text.to_string(),
+      text: Some(text.to_string()),
+      metadata: HashMap::new(),
+      reader_metadata: None,
+      mapped_fields: HashMap::new()
+    }
+  }
+
+  fn extract(text: &str) -> Value {
+    let config = Config::from_iter_safe(vec![""]).unwrap();
+    let mut message = plain(text);
+    extract_embedded_json(&config, &mut message);
+
+    json!({"text": message.text, "metadata": message.metadata})
+  }
+
+  #[test]
+  fn test_trailing_object() {
+    assert_that!(extract(r#"request completed {"status":500,"dur_ms":123}"#))
+      .is_equal_to(json!({
+        "text": "request completed",
+        "metadata": {"status": 500, "dur_ms": 123}
+      }));
+  }
+
+  #[test]
+  fn test_nested_objects() {
+    assert_that!(extract(r#"got {"a": {"b": "}"}} and {"c": [1]} done"#))
+      .is_equal_to(json!({
+        "text": "got and done",
+        "metadata": {"a": {"b": "}"}, "c": [1]}
+      }));
+  }
+
+  #[test]
+  fn test_not_json() {
+    assert_that!(extract("template {name} rendered {"))
+      .is_equal_to(json!({
+        "text": "template {name} rendered {",
+        "metadata": {}
+      }));
+  }
+}
@@ -0,0 +1,331 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::Arc;
+
+use regex::{Captures, Regex};
+use serde_json::{Map, Value};
+
+use crate::config::Config;
+use super::json::{get_value, parse_rfc3339};
+use super::types::{LogLevel, MappingField, Message, MessageKind, ReaderMetadata};
+
+/// Fields that should all be present in an Envoy JSON access log
+///
+/// These are the keys used in Envoy's documented JSON format example (and by
+/// Istio's default JSON access log format).
+static ENVOY_JSON_FIELDS: &[&str] = &[
+  "start_time", "method", "response_code", "response_flags"
+];
+
+static REQUEST_ID_FIELDS: &[&str] = &["request_id", "x_request_id"];
+
+/// Named capture groups copied verbatim into message metadata
+static STRING_GROUPS: &[&str] = &[
+  "method", "path", "protocol", "response_flags", "x_forwarded_for",
+  "user_agent", "request_id", "authority", "upstream_host"
+];
+
+/// Named capture groups copied into message metadata as numbers
+static NUMBER_GROUPS: &[&str] = &[
+  "response_code", "bytes_received", "bytes_sent", "duration",
+  "upstream_service_time"
+];
+
+/// Picks a log level based on the response code; Envoy itself doesn't include
+/// one in access logs
+fn level_for_code(code: Option<u64>) -> Option<LogLevel> {
+  match code {
+    Some(c) if c >= 500 => Some(LogLevel::Error),
+    Some(0) => Some(LogLevel::Error),
+    Some(c) if c >= 400 => Some(LogLevel::Warning),
+    Some(_) => Some(LogLevel::Info),
+    None => None
+  }
+}
+
+fn format_text(
+  method: Option<&str>, path: Option<&str>, code: Option<u64>
+) -> Option<String> {
+  let request = match (method, path) {
+    (Some(method), Some(path)) => format!("{} {}", method, path),
+    (Some(s), None) | (None, Some(s)) => s.to_string(),
+    (None, None) => return None
+  };
+
+  match code {
+    Some(code) => Some(format!("{} {}", request, code)),
+    None => Some(request)
+  }
+}
+
+/// Envoy uses `-` to denote a missing value
+fn present(s: &str) -> Option<&str> {
+  if s.is_empty() || s == "-" {
+    None
+  } else {
+    Some(s)
+  }
+}
+
+/// Determines if a JSON value is set, i.e. not null or `-`
+fn present_value(value: &Value) -> bool {
+  match value {
+    Value::Null => false,
+    Value::String(s) => present(s).is_some(),
+    _ => true
+  }
+}
+
+fn group<'a>(caps: &Captures<'a>, name: &str) -> Option<&'a str> {
+  caps.name(name).map(|m| m.as_str()).and_then(present)
+}
+
+/// Parses Envoy's default access log format:
+///
+/// ```text
+/// [%START_TIME%] "%REQ(:METHOD)% %REQ(X-ENVOY-ORIGINAL-PATH?:PATH)% %PROTOCOL%"
+/// %RESPONSE_CODE% %RESPONSE_FLAGS% %BYTES_RECEIVED% %BYTES_SENT% %DURATION%
+/// %RESP(X-ENVOY-UPSTREAM-SERVICE-TIME)% "%REQ(X-FORWARDED-FOR)%"
+/// "%REQ(USER-AGENT)%" "%REQ(X-REQUEST-ID)%" "%REQ(:AUTHORITY)%"
+/// "%UPSTREAM_HOST%"
+/// ```
+fn parse_envoy_text(
+  line: &str, meta: Option<ReaderMetadata>
+) -> Option<Message> {
+  lazy_static! {
+    static ref RE: Regex = Regex::new(concat!(
+      r#"^\[(?P<start_time>[^\]]+)\] "#,
+      r#""(?P<method>\S+) (?P<path>\S+) (?P<protocol>[^"]+)" "#,
+      r#"(?P<response_code>\d+) (?P<response_flags>\S+) "#,
+      r#"(?P<bytes_received>\d+) (?P<bytes_sent>\d+) "#,
+      r#"(?P<duration>\d+) (?P<upstream_service_time>\S+) "#,
+      r#""(?P<x_forwarded_for>[^"]*)" "(?P<user_agent>[^"]*)" "#,
+      r#""(?P<request_id>[^"]*)" "(?P<authority>[^"]*)" "#,
+      r#""(?P<upstream_host>[^"]*)""#
+    )).unwrap();
+  }
+
+  let caps = RE.captures(line)?;
+
+  let mut metadata = HashMap::new();
+  for name in STRING_GROUPS {
+    if let Some(value) = group(&caps, name) {
+      metadata.insert(name.to_string(), Value::String(value.to_string()));
+    }
+  }
+
+  for name in NUMBER_GROUPS {
+    if let Some(value) = group(&caps, name).and_then(|v| v.parse::<u64>().ok()) {
+      metadata.insert(name.to_string(), Value::Number(value.into()));
+    }
+  }
+
+  let code = group(&caps, "response_code").and_then(|c| c.parse().ok());
+  let timestamp = group(&caps, "start_time").and_then(parse_rfc3339)
+    .or_else(|| meta.as_ref().and_then(|m| m.timestamp));
+
+  Some(Message {
+    kind: MessageKind::Envoy,
+    raw: line.to_string(),
+    reader_metadata: meta,
+    text: format_text(group(&caps, "method"), group(&caps, "path"), code),
+    level: level_for_code(code),
+    mapped_fields: HashMap::new(),
+    timestamp, metadata
+  })
+}
+
+/// Determines if a JSON document looks like an Envoy JSON access log
+pub fn is_envoy_document(doc: &Map<String, Value>) -> bool {
+  ENVOY_JSON_FIELDS.iter().all(|field| doc.contains_key(*field))
+}
+
+/// Maps an Envoy JSON access log document into a Message
+///
+/// Field names are normalized to match the text format, so request ids (for
+/// example) can be filtered identically regardless of the access log format.
+pub fn parse_envoy_document(
+  line: &str, doc: Map<String, Value>, meta: Option<ReaderMetadata>
+) -> Message {
+  let mut mapped_fields = HashMap::new();
+
+  let timestamp = doc.get("start_time")
+    .and_then(|v| v.as_str())
+    .and_then(parse_rfc3339);
+  if timestamp.is_some() {
+    mapped_fields.insert("start_time".to_string(), MappingField::Timestamp);
+  }
+
+  let code = doc.get("response_code").and_then(|v| v.as_u64());
+  let text = format_text(
+    doc.get("method").and_then(|v| v.as_str()).and_then(present),
+    doc.get("path").and_then(|v| v.as_str()).and_then(present),
+    code
+  );
+
+  let request_id = get_value(&doc, REQUEST_ID_FIELDS)
+    .map(|(key, value)| (key.to_string(), value.clone()));
+
+  let mut metadata: HashMap<String, Value> = doc.into_iter()
+    .filter(|(k, _)| !mapped_fields.contains_key(k))
+    .filter(|(_, v)| present_value(v))
+    .collect();
+
+  // normalize the request id key so it matches the text format
+  if let Some((key, value)) = request_id {
+    metadata.remove(&key);
+    if present_value(&value) {
+      metadata.insert("request_id".to_string(), value);
+    }
+  }
+
+  Message {
+    kind: MessageKind::Envoy,
+    raw: line.to_string(),
+    timestamp: timestamp.or_else(|| meta.as_ref().and_then(|m| m.timestamp)),
+    reader_metadata: meta,
+    level: level_for_code(code),
+    text, metadata, mapped_fields
+  }
+}
+
+/// Parses Envoy access logs in the default text format
+///
+/// JSON access logs are handed off from the JSON parser via
+/// `parse_envoy_document()`.
+pub fn parse_envoy(
+  _config: Arc<Config>, line: &str, meta: Option<ReaderMetadata>
+) -> Result<Option<Message>, Box<dyn Error>> {
+  if !line.starts_with('[') {
+    return Ok(None);
+  }
+
+  Ok(parse_envoy_text(line, meta))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use serde_json::json;
+  use simple_error::{SimpleResult, SimpleError};
+  use spectral::prelude::*;
+
+  use crate::parser::json::parse_json;
+  use structopt::StructOpt;
+
+  fn config() -> Arc<Config> {
+    Arc::new(Config::from_iter_safe(vec![""]).unwrap())
+  }
+
+  fn parse_to_value(line: &str) -> SimpleResult<Value> {
+    let parsed = parse_envoy(config(), line, None)
+      .map_err(|e| SimpleError::new(format!("{:?}", e)))?;
+
+    serde_json::to_value(parsed).map_err(SimpleError::from)
+  }
+
+  #[test]
+  fn test_not_envoy() {
+    assert_that!(parse_to_value("hello world")).is_ok_containing(json!(null));
+    assert_that!(parse_to_value("[foo] bar")).is_ok_containing(json!(null));
+  }
+
+  #[test]
+  fn test_envoy_docs() {
+    let line = concat!(
+      r#"[2016-04-15T20:17:00.310Z] "POST /api/v1/locations HTTP/2" 204 - "#,
+      r#"154 0 226 100 "10.0.35.28" "nsq2http" "#,
+      r#""cc21d9b0-cf5c-432b-8c7e-98aeb7988cd2" "locations" "#,
+      r#""tcp://10.0.2.1:80""#
+    );
+
+    assert_that!(parse_to_value(line)).is_ok_containing(json!({
+      "kind": "envoy",
+      "raw": line,
+      "timestamp": "2016-04-15T20:17:00.310Z",
+      "level": "info",
+      "text": "POST /api/v1/locations 204",
+      "metadata": {
+        "method": "POST",
+        "path": "/api/v1/locations",
+        "protocol": "HTTP/2",
+        "response_code": 204,
+        "bytes_received": 154,
+        "bytes_sent": 0,
+        "duration": 226,
+        "upstream_service_time": 100,
+        "x_forwarded_for": "10.0.35.28",
+        "user_agent": "nsq2http",
+        "request_id": "cc21d9b0-cf5c-432b-8c7e-98aeb7988cd2",
+        "authority": "locations",
+        "upstream_host": "tcp://10.0.2.1:80"
+      }
+    }));
+  }
+
+  #[test]
+  fn test_envoy_flags() {
+    let line = concat!(
+      r#"[2020-11-25T21:26:18.409Z] "GET /status HTTP/1.1" 503 UH 0 91 0 - "#,
+      r#""-" "curl/7.73.0" "84961386-6d84-929d-98bd-c5aee93b5c88" "#,
+      r#""httpbin:8000" "-""#
+    );
+
+    assert_that!(parse_to_value(line)).is_ok_containing(json!({
+      "kind": "envoy",
+      "raw": line,
+      "timestamp": "2020-11-25T21:26:18.409Z",
+      "level": "error",
+      "text": "GET /status 503",
+      "metadata": {
+        "method": "GET",
+        "path": "/status",
+        "protocol": "HTTP/1.1",
+        "response_code": 503,
+        "response_flags": "UH",
+        "bytes_received": 0,
+        "bytes_sent": 91,
+        "duration": 0,
+        "user_agent": "curl/7.73.0",
+        "request_id": "84961386-6d84-929d-98bd-c5aee93b5c88",
+        "authority": "httpbin:8000"
+      }
+    }));
+  }
+
+  #[test]
+  fn test_envoy_json() {
+    let line = concat!(
+      r#"{"start_time":"2020-11-25T21:26:18.409Z","method":"GET","#,
+      r#""path":"/status","protocol":"HTTP/1.1","response_code":418,"#,
+      r#""response_flags":"-","duration":4,"#,
+      r#""x_request_id":"84961386-6d84-929d-98bd-c5aee93b5c88","#,
+      r#""upstream_host":"10.44.1.27:80"}"#
+    );
+
+    let parsed = parse_json(config(), line, None)
+      .map_err(|e| SimpleError::new(format!("{:?}", e)))
+      .and_then(|m| serde_json::to_value(m).map_err(SimpleError::from));
+
+    assert_that!(parsed).is_ok_containing(json!({
+      "kind": "envoy",
+      "raw": line,
+      "timestamp": "2020-11-25T21:26:18.409Z",
+      "level": "warning",
+      "text": "GET /status 418",
+      "mapped_fields": {"start_time": "timestamp"},
+      "metadata": {
+        "method": "GET",
+        "path": "/status",
+        "protocol": "HTTP/1.1",
+        "response_code": 418,
+        "duration": 4,
+        "request_id": "84961386-6d84-929d-98bd-c5aee93b5c88",
+        "upstream_host": "10.44.1.27:80"
+      }
+    }));
+  }
+}
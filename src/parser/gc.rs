@@ -0,0 +1,258 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::Arc;
+
+use chrono::prelude::*;
+use regex::Regex;
+use serde_json::Value;
+
+use crate::config::Config;
+use super::timestamp::{parse_epoch, parse_timestamp};
+use super::types::{LogLevel, Message, MessageKind, ReaderMetadata};
+use super::units::{parse_duration_ms, parse_size_bytes, to_number};
+
+/// Levels used by JVM unified logging
+fn map_gc_level(config: &Config, level: &str) -> Option<LogLevel> {
+  config.file().levels.get(level).or(match level {
+    "trace" | "debug" => Some(LogLevel::Debug),
+    "info" => Some(LogLevel::Info),
+    "warning" => Some(LogLevel::Warning),
+    "error" => Some(LogLevel::Error),
+    _ => None
+  })
+}
+
+/// Parses the `time` and `utctime` decorations, which omit the colon in the UTC
+/// offset, falling back to other common formats
+fn parse_gc_datetime(config: &Config, s: &str) -> Option<DateTime<Utc>> {
+  DateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S%.f%z")
+    .ok()
+    .map(|dt| dt.with_timezone(&Utc))
+    .or_else(|| parse_timestamp(config, s))
+}
+
+/// Extracts pause details from a GC event, e.g.:
+///
+/// ```text
+/// Pause Young (Normal) (G1 Evacuation Pause) 12M->4M(128M) 3.456ms
+/// ```
+fn extract_pause(event: &str, metadata: &mut HashMap<String, Value>) {
+  lazy_static! {
+    static ref RE: Regex = Regex::new(concat!(
+      r"^Pause (?P<type>[^(]+?)(?P<causes>(?: \([^)]*\))*)",
+      r"(?: (?P<before>\d+[KMGT])->(?P<after>\d+[KMGT])\((?P<total>\d+[KMGT])\))?",
+      r" (?P<pause>\d+(?:\.\d+)?ms)$"
+    )).unwrap();
+    static ref CAUSE_RE: Regex = Regex::new(r"\(([^)]*)\)").unwrap();
+  }
+
+  let caps = match RE.captures(event) {
+    Some(caps) => caps,
+    None => return
+  };
+
+  metadata.insert("pause_type".to_string(), Value::String(caps["type"].to_string()));
+
+  // the most specific cause comes last, e.g. `(Normal) (G1 Evacuation Pause)`
+  if let Some(cause) = CAUSE_RE.captures_iter(&caps["causes"]).last() {
+    metadata.insert("pause_cause".to_string(), Value::String(cause[1].to_string()));
+  }
+
+  for (group, key) in &[
+    ("before", "heap_before_bytes"),
+    ("after", "heap_after_bytes"),
+    ("total", "heap_total_bytes")
+  ] {
+    let bytes = caps.name(group).and_then(|m| parse_size_bytes(m.as_str()));
+    if let Some(value) = bytes.and_then(to_number) {
+      metadata.insert(key.to_string(), value);
+    }
+  }
+
+  if let Some(value) = parse_duration_ms(&caps["pause"]).and_then(to_number) {
+    metadata.insert("pause_ms".to_string(), value);
+  }
+}
+
+/// Parses JVM unified logging (`-Xlog:gc*`), e.g.:
+///
+/// ```text
+/// [0.123s][info][gc] GC(3) Pause Young (Normal) (G1 Evacuation Pause) 12M->4M(128M) 3.456ms
+/// [2020-11-25T21:26:18.409+0000][12345][info][gc,heap] GC(3) Eden regions: 3->0(5)
+/// ```
+///
+/// Decorations may appear in any combination; at least a level, tags, and
+/// either a time or uptime are required. GC pauses are broken out into
+/// `pause_type`, `pause_cause`, `heap_*_bytes` and `pause_ms` fields.
+pub fn parse_gc(
+  config: Arc<Config>, line: &str, meta: Option<ReaderMetadata>
+) -> Result<Option<Message>, Box<dyn Error>> {
+  lazy_static! {
+    static ref DECORATIONS_RE: Regex = Regex::new(r"^((?:\[[^\[\]]*\])+) ?(.*)$").unwrap();
+    static ref DECORATION_RE: Regex = Regex::new(r"\[([^\[\]]*)\]").unwrap();
+    static ref UPTIME_RE: Regex = Regex::new(r"^\d+(?:\.\d+)?(?:s|ms|ns)$").unwrap();
+    static ref TAGS_RE: Regex = Regex::new(r"^[a-z0-9]+(?:,[a-z0-9]+)*$").unwrap();
+    static ref GC_ID_RE: Regex = Regex::new(r"^GC\((\d+)\) (.*)$").unwrap();
+  }
+
+  if !line.starts_with('[') {
+    return Ok(None);
+  }
+
+  let caps = match DECORATIONS_RE.captures(line) {
+    Some(caps) => caps,
+    None => return Ok(None)
+  };
+
+  let mut metadata = HashMap::new();
+  let mut timestamp = None;
+  let mut level = None;
+  let mut tags = None;
+  let mut has_time = false;
+  let mut ids = Vec::new();
+
+  for decoration in DECORATION_RE.captures_iter(&caps[1]) {
+    let decoration = decoration[1].trim();
+
+    if decoration.chars().all(|c| c.is_ascii_digit()) && !decoration.is_empty() {
+      // pid and tid decorations are bare numbers, in that order; checked first
+      // so numeric level synonyms don't claim them
+      ids.push(decoration.to_string());
+    } else if let Some(l) = map_gc_level(&config, decoration) {
+      level = Some(l);
+    } else if UPTIME_RE.is_match(decoration) {
+      has_time = true;
+
+      // `timemillis` and `timenanos` are wall clock times, the rest are uptimes
+      let ms = parse_duration_ms(decoration);
+      match ms.map(|ms| (ms, parse_epoch(ms))) {
+        Some((ms, Some(epoch))) if ms >= 1e11 => timestamp = Some(epoch),
+        Some((ms, _)) => if let Some(value) = to_number(ms) {
+          metadata.insert("uptime_ms".to_string(), value);
+        },
+        None => ()
+      }
+    } else if TAGS_RE.is_match(decoration) {
+      tags = Some(decoration.to_string());
+    } else if let Some(dt) = parse_gc_datetime(&config, decoration) {
+      has_time = true;
+      timestamp = Some(dt);
+    } else {
+      return Ok(None);
+    }
+  }
+
+  let tags = match (level, tags, has_time) {
+    (Some(_), Some(tags), true) => tags,
+    _ => return Ok(None)
+  };
+
+  for (key, id) in ["pid", "tid"].iter().zip(ids) {
+    if let Ok(id) = id.parse::<u64>() {
+      metadata.insert(key.to_string(), Value::from(id));
+    }
+  }
+
+  metadata.insert("tags".to_string(), Value::String(tags));
+
+  let text = caps[2].trim();
+  if let Some(gc) = GC_ID_RE.captures(text) {
+    if let Ok(id) = gc[1].parse::<u64>() {
+      metadata.insert("gc_id".to_string(), Value::from(id));
+    }
+
+    extract_pause(&gc[2], &mut metadata);
+  }
+
+  Ok(Some(Message {
+    kind: MessageKind::Gc,
+    raw: line.to_string(),
+    timestamp: timestamp.or_else(|| meta.as_ref().and_then(|m| m.timestamp)),
+    text: Some(text.to_string()).filter(|t| !t.is_empty()),
+    reader_metadata: meta,
+    mapped_fields: HashMap::new(),
+    level, metadata
+  }))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use serde_json::json;
+  use simple_error::{SimpleResult, SimpleError};
+  use spectral::prelude::*;
+  use structopt::StructOpt;
+
+  use crate::config::FileConfig;
+
+  fn parse_to_value(line: &str) -> SimpleResult<Value> {
+    let config = Arc::new(Config::from_iter_safe(vec![""]).unwrap());
+    let parsed = parse_gc(config, line, None)
+      .map_err(|e| SimpleError::new(format!("{:?}", e)))?;
+
+    serde_json::to_value(parsed).map_err(SimpleError::from)
+  }
+
+  #[test]
+  fn test_not_gc() {
+    assert_that!(parse_to_value("hello world")).is_ok_containing(json!(null));
+    assert_that!(parse_to_value("[foo] bar")).is_ok_containing(json!(null));
+    assert_that!(parse_to_value("[info][gc] no time")).is_ok_containing(json!(null));
+  }
+
+  #[test]
+  fn test_pause() {
+    let line = "[0.123s][info][gc] GC(3) Pause Young (Normal) (G1 Evacuation Pause) 12M->4M(128M) 3.456ms";
+
+    assert_that!(parse_to_value(line)).is_ok_containing(json!({
+      "kind": "gc",
+      "raw": line,
+      "level": "info",
+      "text": "GC(3) Pause Young (Normal) (G1 Evacuation Pause) 12M->4M(128M) 3.456ms",
+      "metadata": {
+        "uptime_ms": 123,
+        "tags": "gc",
+        "gc_id": 3,
+        "pause_type": "Young",
+        "pause_cause": "G1 Evacuation Pause",
+        "heap_before_bytes": 12_582_912,
+        "heap_after_bytes": 4_194_304,
+        "heap_total_bytes": 134_217_728,
+        "pause_ms": 3.456
+      }
+    }));
+  }
+
+  #[test]
+  fn test_decorations() {
+    let line = "[2020-11-25T21:26:18.409+0000][12345][41][info ][gc,heap     ] GC(3) Eden regions: 3->0(5)";
+
+    assert_that!(parse_to_value(line)).is_ok_containing(json!({
+      "kind": "gc",
+      "raw": line,
+      "timestamp": "2020-11-25T21:26:18.409Z",
+      "level": "info",
+      "text": "GC(3) Eden regions: 3->0(5)",
+      "metadata": {
+        "pid": 12345,
+        "tid": 41,
+        "tags": "gc,heap",
+        "gc_id": 3
+      }
+    }));
+  }
+
+  #[test]
+  fn test_level_synonyms() {
+    let mut config = Config::from_iter_safe(vec![""]).unwrap();
+    config.config_file = Some(serde_yaml::from_str::<FileConfig>(
+      "levels: {debug: [info]}"
+    ).unwrap());
+
+    assert_that!(map_gc_level(&config, "info")).is_equal_to(Some(LogLevel::Debug));
+    assert_that!(map_gc_level(&config, "warning")).is_equal_to(Some(LogLevel::Warning));
+  }
+}
@@ -6,13 +6,16 @@ use std::sync::Arc;
 
 use chrono::prelude::*;
 use regex::Regex;
+use serde::de::DeserializeOwned;
 use serde_json::{self, Value, Map};
 
-use crate::config::Config;
+use crate::config::{ArrayStyle, Config, FlattenConfig};
 use super::types::{
-  LogLevel, MappingField, Message, MessageKind, ReaderMetadata
+  MappingField, Message, MessageKind, ReaderMetadata
 };
-use super::util::normalize_datetime;
+use super::timestamp::{parse_epoch, parse_timestamp};
+use super::util::{normalize_datetime, parse_level};
+use super::{bunyan, envoy, otel, pino};
 
 static TIMESTAMP_FIELDS: &[&str] = &["timestamp", "@timestamp", "time", "ts"];
 static LEVEL_FIELDS: &[&str] = &["level"];
@@ -77,23 +80,64 @@ pub fn parse_rfc3339(s: &str) -> Option<DateTime<Utc>> {
 
 /// Extract the timestamp from any supported field in the message, returning
 /// both the field and the parsed NaiveDateTime
-pub fn get_timestamp(msg: &Map<String, Value>) -> Option<(&str, DateTime<Utc>)> {
+///
+/// Numeric timestamps are treated as epoch seconds, millis, or nanos depending
+/// on their magnitude.
+pub fn get_timestamp<'a>(
+  config: &Config, msg: &Map<String, Value>
+) -> Option<(&'a str, DateTime<Utc>)> {
   if let Some((k, v)) = get_value(&msg, TIMESTAMP_FIELDS) {
-    let v_str = if let Some(v) = v.as_str() {
-      v
-    } else {
-      return None;
+    let timestamp = match v {
+      Value::String(s) => parse_timestamp(config, s),
+      Value::Number(n) => n.as_f64().and_then(parse_epoch),
+      _ => None
     };
 
-    parse_rfc3339(v_str)
-      .or_else(|| parse_rfc2822(v_str))
-      .and_then(|dt| Some((k, dt)))
+    timestamp.map(|dt| (k, dt))
   } else {
     None
   }
 }
 
+/// Flattens nested objects (and optionally arrays) into separate fields per
+/// the flattening config
+pub fn flatten_value(
+  config: &FlattenConfig,
+  key: String,
+  value: &Value,
+  depth: usize,
+  out: &mut HashMap<String, Value>
+) {
+  if depth >= config.depth {
+    out.insert(key, value.clone());
+    return;
+  }
+
+  match value {
+    Value::Object(map) if !map.is_empty() => {
+      for (k, v) in map {
+        let key = format!("{}{}{}", key, config.separator, k);
+        flatten_value(config, key, v, depth + 1, out);
+      }
+    },
+    Value::Array(values) if !values.is_empty() && config.arrays != ArrayStyle::Keep => {
+      for (i, v) in values.iter().enumerate() {
+        let key = match config.arrays {
+          ArrayStyle::Bracket => format!("{}[{}]", key, i),
+          _ => format!("{}{}{}", key, config.separator, i)
+        };
+
+        flatten_value(config, key, v, depth + 1, out);
+      }
+    },
+    _ => {
+      out.insert(key, value.clone());
+    }
+  }
+}
+
 pub fn parse_document(
+  config: &Config,
   line: &str,
   kind: MessageKind,
   doc: Map<String, Value>,
@@ -101,7 +145,7 @@ pub fn parse_document(
 ) -> Result<Option<Message>, Box<dyn Error>> {
   let mut mapped_fields = HashMap::new();
 
-  let timestamp = if let Some((key, timestamp)) = get_timestamp(&doc) {
+  let timestamp = if let Some((key, timestamp)) = get_timestamp(config, &doc) {
     mapped_fields.insert(String::from(key), MappingField::Timestamp);
     Some(timestamp)
   } else {
@@ -109,7 +153,13 @@ pub fn parse_document(
   };
 
   let level = if let Some((key, value)) = get_value(&doc, LEVEL_FIELDS) {
-    if let Some(level) = value.as_str().and_then(|s| s.parse::<LogLevel>().ok()) {
+    let level = match value {
+      Value::String(s) => parse_level(config, s),
+      Value::Number(n) => config.file().levels.get(&n.to_string()),
+      _ => None
+    };
+
+    if let Some(level) = level {
       mapped_fields.insert(String::from(key), MappingField::Level);
       Some(level)
     } else {
@@ -137,10 +187,11 @@ pub fn parse_document(
   };
 
   // clone remaining fields into the message metadata
-  let metadata: HashMap<String, Value> = doc.iter()
-    .filter(|(k, _v)| !mapped_fields.contains_key(k.as_str()))
-    .map(|(k, v)| (k.to_string(), v.to_owned()))
-    .collect();
+  let flatten = &config.file().flatten;
+  let mut metadata: HashMap<String, Value> = HashMap::new();
+  for (k, v) in doc.iter().filter(|(k, _v)| !mapped_fields.contains_key(k.as_str())) {
+    flatten_value(flatten, k.to_string(), v, 0, &mut metadata);
+  }
 
   let message = Message {
     kind,
@@ -152,16 +203,187 @@ pub fn parse_document(
   Ok(Some(message))
 }
 
+/// Parses the next JSON value from the start of `s`, returning it along with
+/// the remaining input
+fn next_value<T: DeserializeOwned>(s: &str) -> Option<(T, &str)> {
+  let mut stream = serde_json::Deserializer::from_str(s).into_iter::<T>();
+  match stream.next() {
+    Some(Ok(value)) => Some((value, &s[stream.byte_offset()..])),
+    _ => None
+  }
+}
+
+/// Recovers the leading fields of a JSON object that failed to parse, e.g.
+/// because the line was truncated
+///
+/// Returns None if nothing could be recovered, or if the object is actually
+/// complete and just followed by other text (which is better handled as
+/// plaintext with embedded JSON).
+fn salvage_fields(line: &str) -> Option<Map<String, Value>> {
+  let mut fields = Map::new();
+  let mut rest = line.strip_prefix('{')?.trim_start();
+
+  while let Some((key, after)) = next_value::<String>(rest) {
+    let after = match after.trim_start().strip_prefix(':') {
+      Some(after) => after.trim_start(),
+      None => break
+    };
+
+    let (value, after) = match next_value::<Value>(after) {
+      Some(pair) => pair,
+      None => break
+    };
+
+    // only trust values followed by a delimiter; a number at the very end of
+    // a truncated line may itself be truncated
+    let after = after.trim_start();
+    if let Some(after) = after.strip_prefix(',') {
+      fields.insert(key, value);
+      rest = after.trim_start();
+    } else if let Some(after) = after.strip_prefix('}') {
+      if !after.trim().is_empty() {
+        return None;
+      }
+
+      fields.insert(key, value);
+      break;
+    } else {
+      break;
+    }
+  }
+
+  if fields.is_empty() {
+    None
+  } else {
+    Some(fields)
+  }
+}
+
+/// Parses JSON logs
+///
+/// Lines that look like JSON objects but fail to parse keep whatever leading
+/// fields could be recovered, plus a `parse_error` field describing the
+/// problem.
 pub fn parse_json(
-  _config: Arc<Config>, line: &str, meta: Option<ReaderMetadata>
+  config: Arc<Config>, line: &str, meta: Option<ReaderMetadata>
 ) -> Result<Option<Message>, Box<dyn Error>> {
   // skip anything that doesn't at least vaguely look like json
-  if !line.starts_with('{') || !line.ends_with('}') {
+  if !line.starts_with('{') {
     return Ok(None);
   }
 
   match serde_json::from_str(line) {
-    Ok(message) => parse_document(line, MessageKind::Json, message, meta),
-    Err(_) => Ok(None)
+    Ok(doc) => parse_json_document(&config, line, doc, meta),
+    Err(e) => match salvage_fields(line) {
+      Some(mut doc) => {
+        doc.insert("parse_error".to_string(), Value::String(e.to_string()));
+        parse_json_document(&config, line, doc, meta)
+      },
+      None => Ok(None)
+    }
+  }
+}
+
+/// Maps an already-decoded JSON document into a Message, handing off to
+/// format-specific mappers where the document is recognized
+pub fn parse_json_document(
+  config: &Config,
+  line: &str,
+  doc: Map<String, Value>,
+  meta: Option<ReaderMetadata>
+) -> Result<Option<Message>, Box<dyn Error>> {
+  if envoy::is_envoy_document(&doc) {
+    Ok(Some(envoy::parse_envoy_document(line, doc, meta)))
+  } else if otel::is_otel_document(&doc) {
+    Ok(Some(otel::parse_otel_document(config, line, doc, meta)))
+  } else if pino::is_pino_document(&doc) {
+    pino::parse_pino_document(config, line, doc, meta)
+  } else if bunyan::is_bunyan_document(&doc) {
+    bunyan::parse_bunyan_document(config, line, doc, meta)
+  } else {
+    parse_document(config, line, MessageKind::Json, doc, meta)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use serde_json::json;
+  use spectral::prelude::*;
+  use structopt::StructOpt;
+
+  fn flatten(config: &str, value: Value) -> Value {
+    let config: FlattenConfig = serde_yaml::from_str(config).unwrap();
+
+    let mut out = HashMap::new();
+    for (k, v) in value.as_object().unwrap() {
+      flatten_value(&config, k.to_string(), v, 0, &mut out);
+    }
+
+    serde_json::to_value(out).unwrap()
+  }
+
+  #[test]
+  fn test_flatten_disabled() {
+    let doc = json!({"a": {"b": 1}, "c": [1, 2]});
+    assert_that!(flatten("{}", doc.clone())).is_equal_to(doc);
+  }
+
+  #[test]
+  fn test_flatten_depth() {
+    let doc = json!({"a": {"b": {"c": 1}, "d": 2}, "e": [1, 2]});
+
+    assert_that!(flatten("depth: 1", doc.clone())).is_equal_to(json!({
+      "a.b": {"c": 1},
+      "a.d": 2,
+      "e": [1, 2]
+    }));
+
+    assert_that!(flatten("{depth: 2, separator: _}", doc)).is_equal_to(json!({
+      "a_b_c": 1,
+      "a_d": 2,
+      "e": [1, 2]
+    }));
+  }
+
+  #[test]
+  fn test_flatten_arrays() {
+    let doc = json!({"a": [{"b": 1}, 2]});
+
+    assert_that!(flatten("{depth: 2, arrays: index}", doc.clone())).is_equal_to(json!({
+      "a.0.b": 1,
+      "a.1": 2
+    }));
+
+    assert_that!(flatten("{depth: 1, arrays: bracket}", doc)).is_equal_to(json!({
+      "a[0]": {"b": 1},
+      "a[1]": 2
+    }));
+  }
+
+  #[test]
+  fn test_salvage() {
+    let config = Arc::new(Config::from_iter_safe(vec![""]).unwrap());
+    let parse = |line: &str| {
+      serde_json::to_value(parse_json(Arc::clone(&config), line, None).unwrap()).unwrap()
+    };
+
+    let line = r#"{"level":"error","msg":"disk full","count":12,"path":"/va"#;
+    assert_that!(parse(line)).is_equal_to(json!({
+      "kind": "json",
+      "raw": line,
+      "level": "error",
+      "text": "disk full",
+      "mapped_fields": {"level": "level", "msg": "text"},
+      "metadata": {
+        "count": 12,
+        "parse_error": "EOF while parsing a string at line 1 column 57"
+      }
+    }));
+
+    // complete objects followed by text are left for the plain parser
+    assert_that!(parse(r#"{"a": 1} trailing text"#)).is_equal_to(json!(null));
+    assert_that!(parse("{not json}")).is_equal_to(json!(null));
   }
 }
@@ -5,20 +5,48 @@ use std::error::Error;
 use std::sync::Arc;
 
 use chrono::prelude::*;
+use chrono::Duration;
 use regex::Regex;
 use serde_json::Value;
 
-use crate::config::Config;
+use crate::config::{Config, Zone};
+use super::timestamp::localize;
 use super::types::{LogLevel, Message, MessageKind, ReaderMetadata};
 
-fn map_klog_level(level: &str) -> Option<LogLevel> {
-  match level {
+fn map_klog_level(config: &Config, level: &str) -> Option<LogLevel> {
+  config.file().levels.get(level).or(match level {
     "D" => Some(LogLevel::Debug), // not technically supported by klog
     "I" => Some(LogLevel::Info),
     "W" => Some(LogLevel::Warning),
     "E" => Some(LogLevel::Error),
     "F" => Some(LogLevel::Fatal),
     _ => None
+  })
+}
+
+/// Parses a klog timestamp with the year prepended
+fn parse_klog_datetime(config: &Config, s: &str) -> Option<DateTime<Utc>> {
+  NaiveDateTime::parse_from_str(s, "%Y %m%d %H:%M:%S%.f")
+    .ok()
+    .and_then(|naive| localize(config, &naive, Zone::Utc))
+}
+
+/// Fills in a year for klog timestamps, which omit it
+///
+/// The current year is used unless that would put the message more than a day
+/// in the future, in which case it's presumably from the end of last year.
+fn infer_year(
+  config: &Config, timestamp_str: &str, reference: DateTime<Utc>
+) -> Option<DateTime<Utc>> {
+  let parse = |year: i32| parse_klog_datetime(
+    config, &format!("{} {}", year, timestamp_str)
+  );
+
+  let timestamp = parse(reference.year())?;
+  if timestamp > reference + Duration::days(1) {
+    parse(reference.year() - 1)
+  } else {
+    Some(timestamp)
   }
 }
 
@@ -26,53 +54,49 @@ fn map_klog_level(level: &str) -> Option<LogLevel> {
 //
 // based on the format description at:
 // https://github.com/kubernetes/klog/blob/master/klog.go#L592-L602
+//
+// klog is a fork of glog, so this also handles glog output from non-Kubernetes
+// binaries, including the year-prefixed timestamps newer glog versions write
+// by default (ex: I20230501 12:00:00.123456 1234 file.cc:42] message)
 pub fn parse_klog(
-  _config: Arc<Config>, line: &str, meta: Option<ReaderMetadata>
+  config: Arc<Config>, line: &str, meta: Option<ReaderMetadata>
 ) -> Result<Option<Message>, Box<dyn Error>> {
   lazy_static! {
-    static ref RE: Regex = Regex::new(
-      r"^([A-Z])(\d{4} \d{2}:\d{2}:[\d\.]+)\s+(\d+) ([\S.]+:\d+)] (.+)$"
-    ).unwrap();
+    static ref RE: Regex = Regex::new(concat!(
+      r"^(?P<level>[A-Z])(?P<year>\d{4})?(?P<datetime>\d{4} \d{2}:\d{2}:[\d\.]+)\s+",
+      r"(?P<thread>\d+) (?P<context>[\S.]+:\d+)] (?P<text>.+)$"
+    )).unwrap();
   }
 
   if let Some(caps) = RE.captures(line) {
-    // naughty unwrapping, but we have a constant number of groups
-    let level = map_klog_level(caps.get(1).unwrap().as_str());
-
-    // chrono needs a year but klog omits this, so fill in the current year
-    let current_year = Utc::now().year();
-    let timestamp_str = format!(
-      "{} {}",
-      current_year,
-      caps.get(2).unwrap().as_str()
-    );
-    
+    // naughty unwrapping, but these groups aren't optional
+    let level = map_klog_level(&config, caps.name("level").unwrap().as_str());
+    let datetime = caps.name("datetime").unwrap().as_str();
+
     // ex: 0607 19:28:33.579841
-    let reader_timestamp = if let Some(meta) = &meta {
-      if let Some(timestamp) = &meta.timestamp {
-        Some(*timestamp)
-      } else {
-        None
-      }
-    } else { None };
+    let reader_timestamp = meta.as_ref().and_then(|m| m.timestamp);
 
-    let timestamp = Utc.datetime_from_str(
-      &timestamp_str,
-      "%Y %m%d %H:%M:%S%.f"
-    ).ok().or(reader_timestamp);
+    let timestamp = match caps.name("year") {
+      Some(year) => parse_klog_datetime(
+        &config, &format!("{} {}", year.as_str(), datetime)
+      ),
+      None => infer_year(
+        &config, datetime, reader_timestamp.unwrap_or_else(Utc::now)
+      )
+    }.or(reader_timestamp);
 
-    let text = caps.get(5).unwrap().as_str();
+    let text = caps.name("text").unwrap().as_str();
 
     let mut metadata = HashMap::new();
 
-    let maybe_thread_id = caps.get(3)
+    let maybe_thread_id = caps.name("thread")
       .map(|c| c.as_str())
       .and_then(|s| s.parse::<isize>().ok());
     if let Some(thread_id) = maybe_thread_id {
       metadata.insert("threadId".to_string(), Value::Number(thread_id.into()));
     }
 
-    if let Some(context) = caps.get(4).map(|c| c.as_str()) {
+    if let Some(context) = caps.name("context").map(|c| c.as_str()) {
       metadata.insert(
         "caller".to_string(),
         Value::String(context.to_string())
@@ -93,3 +117,61 @@ pub fn parse_klog(
 
   Ok(None)
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use serde_json::json;
+  use simple_error::{SimpleResult, SimpleError};
+  use spectral::prelude::*;
+  use structopt::StructOpt;
+
+  use crate::config::FileConfig;
+
+  fn parse_to_value(line: &str) -> SimpleResult<Value> {
+    let config = Arc::new(Config::from_iter_safe(vec![""]).unwrap());
+    let parsed = parse_klog(config, line, None)
+      .map_err(|e| SimpleError::new(format!("{:?}", e)))?;
+
+    serde_json::to_value(parsed).map_err(SimpleError::from)
+  }
+
+  #[test]
+  fn test_glog_year() {
+    let line = "E20230501 12:00:00.123456  1234 server.cc:42] connection reset";
+    assert_that!(parse_to_value(line)).is_ok_containing(json!({
+      "kind": "klog",
+      "raw": line,
+      "timestamp": "2023-05-01T12:00:00.123456Z",
+      "level": "error",
+      "text": "connection reset",
+      "metadata": {
+        "threadId": 1234,
+        "caller": "server.cc:42"
+      }
+    }));
+  }
+
+  #[test]
+  fn test_infer_year() {
+    let config = Config::from_iter_safe(vec![""]).unwrap();
+    let reference = Utc.with_ymd_and_hms(2021, 1, 2, 0, 0, 0).unwrap();
+
+    assert_that!(infer_year(&config, "0101 23:00:00.000000", reference))
+      .is_equal_to(Some(Utc.with_ymd_and_hms(2021, 1, 1, 23, 0, 0).unwrap()));
+    assert_that!(infer_year(&config, "1231 23:00:00.000000", reference))
+      .is_equal_to(Some(Utc.with_ymd_and_hms(2020, 12, 31, 23, 0, 0).unwrap()));
+  }
+
+  #[test]
+  fn test_level_synonyms() {
+    let mut config = Config::from_iter_safe(vec![""]).unwrap();
+    config.config_file = Some(serde_yaml::from_str::<FileConfig>(
+      "levels: {error: [W]}"
+    ).unwrap());
+
+    assert_that!(map_klog_level(&config, "W")).is_equal_to(Some(LogLevel::Error));
+    assert_that!(map_klog_level(&config, "I")).is_equal_to(Some(LogLevel::Info));
+  }
+}
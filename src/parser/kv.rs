@@ -0,0 +1,123 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+use regex::Regex;
+use serde_json::{Number, Value};
+
+use crate::config::Config;
+use super::types::{Message, MessageKind};
+
+/// Converts a raw value into a JSON value, unquoting strings and detecting
+/// numbers and booleans
+fn to_value(raw: &str) -> Value {
+  if raw.len() >= 2 && raw.starts_with('"') && raw.ends_with('"') {
+    let inner = &raw[1..raw.len() - 1];
+
+    return Value::String(
+      serde_json::from_str::<String>(raw).unwrap_or_else(|_| inner.to_string())
+    );
+  }
+
+  if raw.len() >= 2 && raw.starts_with('\'') && raw.ends_with('\'') {
+    return Value::String(raw[1..raw.len() - 1].to_string());
+  }
+
+  match raw {
+    "true" => return Value::Bool(true),
+    "false" => return Value::Bool(false),
+    _ => ()
+  };
+
+  if let Ok(i) = raw.parse::<i64>() {
+    return Value::Number(i.into());
+  }
+
+  if let Some(n) = raw.parse::<f64>().ok().and_then(Number::from_f64) {
+    return Value::Number(n);
+  }
+
+  Value::String(raw.to_string())
+}
+
+/// Lifts `key=value` and `key: value` pairs in plaintext messages into
+/// metadata, e.g. `request done status=200 user="jane doe"`
+///
+/// The message text is left as-is. Only runs if `--extract-fields` is set.
+pub fn extract_key_values(config: &Config, message: &mut Message) {
+  lazy_static! {
+    static ref RE: Regex = Regex::new(concat!(
+      r#"(?:^|[\s,;(\[])(?P<key>[A-Za-z_][\w.-]*)(?:=|: )"#,
+      r#"(?P<value>"(?:[^"\\]|\\.)*"|'[^']*'|[^\s,;)\]"']+)"#
+    )).unwrap();
+  }
+
+  if message.kind != MessageKind::Plain || !config.extract_fields {
+    return;
+  }
+
+  let text = match &message.text {
+    Some(text) => text,
+    None => return
+  };
+
+  for caps in RE.captures_iter(text) {
+    let key = &caps["key"];
+    if !message.metadata.contains_key(key) {
+      message.metadata.insert(key.to_string(), to_value(&caps["value"]));
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::collections::HashMap;
+
+  use serde_json::json;
+  use spectral::prelude::*;
+  use structopt::StructOpt;
+
+  fn extract(text: &str) -> Value {
+    let config = Config::from_iter_safe(vec!["", "--extract-fields"]).unwrap();
+    let mut message = Message {
+      kind: MessageKind::Plain,
+      timestamp: None,
+      level: None,
+      raw: text.to_string(),
+      text: Some(text.to_string()),
+      metadata: HashMap::new(),
+      reader_metadata: None,
+      mapped_fields: HashMap::new()
+    };
+
+    extract_key_values(&config, &mut message);
+    serde_json::to_value(message.metadata).unwrap()
+  }
+
+  #[test]
+  fn test_key_values() {
+    assert_that!(extract(r#"request done status=200 duration=1.5 ok=true user="jane \"j\" doe""#))
+      .is_equal_to(json!({
+        "status": 200,
+        "duration": 1.5,
+        "ok": true,
+        "user": "jane \"j\" doe"
+      }));
+  }
+
+  #[test]
+  fn test_colon_pairs() {
+    assert_that!(extract("login failed (user: alice, attempts: 3) ip='10.0.0.1'"))
+      .is_equal_to(json!({
+        "user": "alice",
+        "attempts": 3,
+        "ip": "10.0.0.1"
+      }));
+  }
+
+  #[test]
+  fn test_no_pairs() {
+    assert_that!(extract("a == b, see http://example.com/?a=b"))
+      .is_equal_to(json!({}));
+  }
+}
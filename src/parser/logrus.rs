@@ -4,12 +4,14 @@ use std::error::Error;
 use std::sync::Arc;
 
 use pest::Parser;
+use regex::Regex;
 use serde_json::{self, Value, Map};
 use simple_error::SimpleError;
 
 use crate::config::Config;
 use super::types::{Message, MessageKind, ReaderMetadata};
 use super::json::parse_document;
+use super::util::strip_ansi;
 
 #[derive(Parser)]
 #[grammar = "parser/logrus.pest"]
@@ -39,7 +41,9 @@ pub fn logrus_to_document(
     for inner in pair.into_inner() {
       match inner.as_rule() {
         Rule::key => key = Some(inner.as_str().to_string()),
-        Rule::string | Rule::bare_string | Rule::object => {
+        // quoted strings use go's escaping, which is close enough to json's
+        Rule::string => value = Some(Value::String(unescape(inner.as_str()))),
+        Rule::bare_string | Rule::object => {
           let s = inner.as_str();
 
           value = if s == "true" {
@@ -49,7 +53,7 @@ pub fn logrus_to_document(
           } else if let Ok(int) = s.parse::<i64>() {
             Some(Value::Number(int.into()))
           } else {
-            Some(Value::String(inner.as_str().to_string()))
+            Some(Value::String(s.to_string()))
           };
         },
         Rule::EOI => (),
@@ -65,18 +69,87 @@ pub fn logrus_to_document(
   Ok(doc)
 }
 
+/// Unescapes a quoted logrus value
+///
+/// Logrus quotes values with go's `strconv.Quote()`; the common escapes are
+/// shared with JSON, so anything that fails to parse is left as-is.
+fn unescape(s: &str) -> String {
+  if !s.contains('\\') {
+    return s.to_string();
+  }
+
+  serde_json::from_str::<String>(&format!("\"{}\"", s))
+    .unwrap_or_else(|_| s.to_string())
+}
+
+/// Maps the truncated level names used in logrus's TTY output
+fn map_tty_level(level: &str) -> Option<&'static str> {
+  match level {
+    "TRAC" => Some("trace"),
+    "DEBU" => Some("debug"),
+    "INFO" => Some("info"),
+    "WARN" => Some("warning"),
+    "ERRO" => Some("error"),
+    "FATA" => Some("fatal"),
+    "PANI" => Some("panic"),
+    _ => None
+  }
+}
+
+/// Converts logrus's TTY-formatted output into a JSON document
+///
+/// When attached to a terminal, logrus instead formats messages as:
+///     WARN[0000] The group's number increased tremendously!  number=122 omg=true
+///
+/// ... with ANSI colors, and with a full timestamp in place of the elapsed
+/// seconds if `FullTimestamp` is set. The message is padded and followed by at
+/// least two spaces before any fields.
+fn logrus_tty_to_document(line: &str) -> Option<Map<String, Value>> {
+  lazy_static! {
+    static ref RE: Regex = Regex::new(
+      r"^(?P<level>[A-Z]{4})\[(?P<time>[^\]]+)\] (?P<msg>.*?)(?:\s{2,}(?P<fields>[\w.-]+=.*))?$"
+    ).unwrap();
+  }
+
+  let line = strip_ansi(line);
+  let caps = RE.captures(&line)?;
+  let level = map_tty_level(caps.name("level")?.as_str())?;
+
+  let mut doc = match caps.name("fields") {
+    Some(fields) => logrus_to_document(fields.as_str()).ok()?,
+    None => Map::new()
+  };
+
+  doc.insert("level".to_string(), Value::String(level.to_string()));
+  doc.insert(
+    "msg".to_string(),
+    Value::String(caps.name("msg")?.as_str().trim_end().to_string())
+  );
+
+  // elapsed seconds aren't useful as a timestamp
+  let time = caps.name("time")?.as_str();
+  if time.parse::<u64>().is_err() {
+    doc.insert("time".to_string(), Value::String(time.to_string()));
+  }
+
+  Some(doc)
+}
+
 pub fn parse_logrus(
-  _config: Arc<Config>, line: &str, meta: Option<ReaderMetadata>
+  config: Arc<Config>, line: &str, meta: Option<ReaderMetadata>
 ) -> Result<Option<Message>, Box<dyn Error>> {
   match logrus_to_document(line) {
     Ok(doc) => {
       if doc.is_empty() {
         Ok(None)
       } else {
-        parse_document(line, MessageKind::Logrus, doc, meta)
+        parse_document(&config, line, MessageKind::Logrus, doc, meta)
       }
     },
-    Err(_) => Ok(None)
+    Err(_) => match logrus_tty_to_document(line) {
+      Some(doc) => parse_document(&config, line, MessageKind::Logrus, doc, meta),
+      None => Ok(None)
+    }
   }
 }
 
@@ -154,9 +227,12 @@ mod tests {
       "foo": "hello 'world'"
     }));
 
-    // TODO: need to unescape to make escaped strings reasonable
     assert_that!(parse(r#"foo="hello \"world\"""#)).is_ok_containing(json!({
-      "foo": "hello \\\"world\\\""
+      "foo": "hello \"world\""
+    }));
+
+    assert_that!(parse(r#"foo="line\nbreak""#)).is_ok_containing(json!({
+      "foo": "line\nbreak"
     }));
 
   }
@@ -228,4 +304,36 @@ mod tests {
       }
     }))
   }
+  #[test]
+  fn test_tty() {
+    let line = concat!(
+      "\x1b[33mWARN\x1b[0m[0000] The group's number increased tremendously!",
+      "  \x1b[33mnumber\x1b[0m=122 \x1b[33momg\x1b[0m=true"
+    );
+
+    assert_that!(parse_message(line)).is_ok_containing(json!({
+      "kind": "logrus",
+      "raw": line,
+      "level": "warning",
+      "text": "The group's number increased tremendously!",
+      "mapped_fields": {"level": "level", "msg": "text"},
+      "metadata": {
+        "number": 122,
+        "omg": true
+      }
+    }));
+
+    let line = "INFO[2015-03-26T01:27:38-04:00] A walrus appears                              animal=walrus";
+    assert_that!(parse_message(line)).is_ok_containing(json!({
+      "kind": "logrus",
+      "raw": line,
+      "timestamp": "2015-03-26T05:27:38Z",
+      "level": "info",
+      "text": "A walrus appears",
+      "mapped_fields": {"level": "level", "msg": "text", "time": "timestamp"},
+      "metadata": {
+        "animal": "walrus"
+      }
+    }));
+  }
 }
@@ -1,31 +1,99 @@
 // (C) Copyright 2019 Hewlett Packard Enterprise Development LP
 
+mod auditd;
+mod dotnet;
+mod embedded;
+mod bunyan;
+pub mod classify;
+mod envoy;
+mod gc;
 mod json;
 mod klog;
+mod kv;
 mod logrus;
+pub mod msgpack;
+mod otel;
+mod pino;
 mod plain;
+mod priority;
+pub mod protobuf;
 mod regex;
+mod spring;
+mod timestamp;
 mod types;
+mod units;
+mod zap;
 pub mod util;
 
 use std::error::Error;
 use std::sync::Arc;
 
-use crate::config::Config;
-pub use types::{LogLevel, Message, MessageKind, ReaderMetadata, Parser};
+use serde_json::{Map, Value};
 
-static PARSERS: &[Parser] = &[
-  json::parse_json,
-  logrus::parse_logrus,
-  klog::parse_klog,
-  regex::parse_regex,
-  plain::parse_plain
+use crate::config::{AnsiMode, Config};
+pub use types::{LogLevel, Message, MessageKind, ReaderMetadata, Parser, Enricher};
+pub use json::flatten_value;
+pub use units::parse_size_bytes;
+
+/// All parsers by name, in their default order
+static PARSERS: &[(&str, Parser)] = &[
+  ("json", json::parse_json),
+  // audit records would otherwise be mistaken for logrus key/value pairs
+  ("auditd", auditd::parse_auditd),
+  ("logrus", logrus::parse_logrus),
+  ("klog", klog::parse_klog),
+  ("envoy", envoy::parse_envoy),
+  ("spring", spring::parse_spring),
+  ("dotnet", dotnet::parse_dotnet),
+  ("zap", zap::parse_zap),
+  ("gc", gc::parse_gc),
+  ("regex", regex::parse_regex),
+  ("plain", plain::parse_plain)
 ];
 
-pub fn parse(
+/// Passes run over every parsed message, e.g. to extract fields from plain text
+static ENRICHERS: &[Enricher] = &[
+  embedded::extract_embedded_json,
+  kv::extract_key_values,
+  units::normalize_units,
+  classify::apply_classify_rules
+];
+
+/// Finds a parser by name
+pub fn get_parser(name: &str) -> Option<Parser> {
+  PARSERS.iter().find(|(n, _)| *n == name).map(|(_, p)| *p)
+}
+
+/// Names of all parsers, in their default order
+pub fn parser_names() -> Vec<&'static str> {
+  PARSERS.iter().map(|(n, _)| *n).collect()
+}
+
+/// The parsers to try for lines without a pinned parser, in order, along with
+/// their names
+pub fn active_parsers(config: &Config) -> Vec<(&str, Parser)> {
+  match &config.parsers {
+    Some(chain) => chain.parsers.iter().map(|(n, p)| (n.as_str(), *p)).collect(),
+    None => PARSERS.to_vec()
+  }
+}
+
+fn parse_chain(
   config: Arc<Config>, line: &str, meta: Option<ReaderMetadata>
 ) -> Result<Option<Message>, Box<dyn Error>> {
-  for parser_fn in PARSERS {
+  let source = meta.as_ref().and_then(|m| m.source.as_ref());
+
+  if let Some(pinned) = config.pinned_parser(source) {
+    let result = pinned(Arc::clone(&config), line, meta.clone());
+
+    // show lines the pinned parser can't handle rather than dropping them
+    return match result {
+      Ok(None) => plain::parse_plain(config, line, meta),
+      _ => result
+    };
+  }
+
+  for (_, parser_fn) in active_parsers(&config) {
     let result = parser_fn(Arc::clone(&config), line, meta.clone());
 
     match result {
@@ -36,3 +104,146 @@ pub fn parse(
 
   Ok(None)
 }
+
+pub fn parse(
+  config: Arc<Config>, line: &str, meta: Option<ReaderMetadata>
+) -> Result<Option<Message>, Box<dyn Error>> {
+  // colors would otherwise break format detection, so always parse without
+  // them; if they should be kept, the original line stays in `raw`
+  let stripped = util::strip_ansi(line);
+  let mut message = parse_uncolored(Arc::clone(&config), &stripped, meta)?;
+
+  if let Some(message) = &mut message {
+    if config.ansi == AnsiMode::Keep && stripped.len() != line.len() {
+      message.raw = line.to_string();
+    }
+
+    enrich(&config, message);
+  }
+
+  Ok(message)
+}
+
+/// Parses a line with the default config, e.g. to build messages for tests
+#[cfg(test)]
+pub fn test_message(line: &str, source: Option<&str>) -> Message {
+  use structopt::StructOpt;
+
+  let config = Arc::new(Config::from_iter_safe(vec![""]).unwrap());
+  let meta = source.map(|source| ReaderMetadata {
+    timestamp: None,
+    source: Some(source.to_string())
+  });
+
+  parse(config, line, meta).unwrap().unwrap()
+}
+
+/// Parses a structured record that was decoded from a binary stream rather
+/// than read as a line
+///
+/// The record is mapped as if it were a line of JSON, and its raw text is the
+/// equivalent JSON.
+pub fn parse_record(
+  config: Arc<Config>, doc: Map<String, Value>, meta: Option<ReaderMetadata>
+) -> Result<Option<Message>, Box<dyn Error>> {
+  let line = serde_json::to_string(&doc)?;
+  let mut message = json::parse_json_document(&config, &line, doc, meta)?;
+
+  if let Some(message) = &mut message {
+    if message.timestamp.is_none() {
+      message.timestamp = message.reader_metadata.as_ref().and_then(|m| m.timestamp);
+    }
+
+    enrich(&config, message);
+  }
+
+  Ok(message)
+}
+
+fn enrich(config: &Config, message: &mut Message) {
+  for enricher in ENRICHERS {
+    enricher(config, message);
+  }
+}
+
+fn parse_uncolored(
+  config: Arc<Config>, line: &str, meta: Option<ReaderMetadata>
+) -> Result<Option<Message>, Box<dyn Error>> {
+  // a `<N>` priority prefix can precede any other format, so strip it and
+  // parse the remainder as usual
+  if let Some((level, rest)) = priority::strip_priority(line) {
+    let message = parse_chain(config, rest, meta)?.map(|mut message| {
+      // the priority is authoritative unless the message declared its own
+      // level (plain messages only guess)
+      if message.level.is_none() || message.kind == MessageKind::Plain {
+        message.level = Some(level);
+      }
+
+      message.raw = line.to_string();
+      message
+    });
+
+    return Ok(message);
+  }
+
+  parse_chain(config, line, meta)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use spectral::prelude::*;
+  use structopt::StructOpt;
+
+  fn parse_kind(args: &[&str], line: &str, source: Option<&str>) -> Option<MessageKind> {
+    let mut argv = vec![""];
+    argv.extend_from_slice(args);
+
+    let config = Arc::new(Config::from_iter_safe(argv).unwrap());
+    let meta = source.map(|s| ReaderMetadata {
+      timestamp: None,
+      source: Some(s.to_string())
+    });
+
+    parse(config, line, meta).unwrap().map(|m| m.kind)
+  }
+
+  #[test]
+  fn test_default_chain() {
+    assert_that!(parse_kind(&[], r#"{"msg": "hello"}"#, None))
+      .is_equal_to(Some(MessageKind::Json));
+  }
+
+  #[test]
+  fn test_pinned_parser() {
+    let line = r#"{"msg": "hello"}"#;
+
+    assert_that!(parse_kind(&["--parser", "plain"], line, None))
+      .is_equal_to(Some(MessageKind::Plain));
+
+    // pins may only apply to some sources
+    let args = ["--parser", "^web-=plain"];
+    assert_that!(parse_kind(&args, line, Some("web-1")))
+      .is_equal_to(Some(MessageKind::Plain));
+    assert_that!(parse_kind(&args, line, Some("api-1")))
+      .is_equal_to(Some(MessageKind::Json));
+
+    // lines the pinned parser can't handle fall back to plaintext
+    assert_that!(parse_kind(&["--parser", "json"], "hello", None))
+      .is_equal_to(Some(MessageKind::Plain));
+  }
+
+  #[test]
+  fn test_parser_chain() {
+    let line = r#"time="2019-07-10T14:14:13Z" level=debug msg="hello world""#;
+
+    assert_that!(parse_kind(&["--parsers", "json,plain"], line, None))
+      .is_equal_to(Some(MessageKind::Plain));
+
+    // lines no parser handles are dropped
+    assert_that!(parse_kind(&["--parsers", "json"], line, None)).is_none();
+
+    assert_that!(Config::from_iter_safe(vec!["", "--parsers", "json,nope"])).is_err();
+  }
+}
@@ -0,0 +1,301 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+use std::io::{self, Read};
+
+use chrono::prelude::*;
+use serde_json::{Map, Number, Value};
+
+/// Deepest nesting of arrays and maps accepted before giving up on the stream
+const MAX_DEPTH: usize = 64;
+
+/// msgpack extension type used by fluentd's `EventTime`
+const EXT_EVENT_TIME: i8 = 0;
+
+/// msgpack's standard timestamp extension type
+const EXT_TIMESTAMP: i8 = -1;
+
+fn invalid(message: &str) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidData, message.to_string())
+}
+
+fn read_bytes<R: Read>(r: &mut R, len: usize) -> io::Result<Vec<u8>> {
+  let mut buf = Vec::new();
+  r.take(len as u64).read_to_end(&mut buf)?;
+  if buf.len() < len {
+    return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "truncated msgpack value"));
+  }
+
+  Ok(buf)
+}
+
+fn read_uint<R: Read>(r: &mut R, len: usize) -> io::Result<u64> {
+  let bytes = read_bytes(r, len)?;
+  Ok(bytes.iter().fold(0u64, |acc, b| (acc << 8) | u64::from(*b)))
+}
+
+fn read_int<R: Read>(r: &mut R, len: usize) -> io::Result<i64> {
+  let value = read_uint(r, len)?;
+  let shift = 64 - len * 8;
+
+  // sign extend from the encoded width
+  Ok(((value << shift) as i64) >> shift)
+}
+
+fn read_str<R: Read>(r: &mut R, len: usize) -> io::Result<Value> {
+  let bytes = read_bytes(r, len)?;
+  Ok(Value::String(String::from_utf8_lossy(&bytes).into_owned()))
+}
+
+fn float(f: f64) -> Value {
+  Number::from_f64(f).map(Value::Number).unwrap_or(Value::Null)
+}
+
+fn timestamp(secs: i64, nanos: u32) -> Value {
+  match Utc.timestamp_opt(secs, nanos).single() {
+    Some(dt) => Value::String(dt.to_rfc3339_opts(SecondsFormat::AutoSi, true)),
+    None => Value::Null
+  }
+}
+
+/// Converts an extension value to JSON
+///
+/// Only timestamps are understood (as RFC 3339 strings); anything else
+/// becomes `null`.
+fn read_ext<R: Read>(r: &mut R, len: usize) -> io::Result<Value> {
+  let ext_type = read_int(r, 1)? as i8;
+  let data = read_bytes(r, len)?;
+  let mut data = data.as_slice();
+
+  Ok(match (ext_type, len) {
+    (EXT_EVENT_TIME, 8) => {
+      let secs = read_uint(&mut data, 4)?;
+      let nanos = read_uint(&mut data, 4)?;
+      timestamp(secs as i64, nanos as u32)
+    },
+    (EXT_TIMESTAMP, 4) => timestamp(read_uint(&mut data, 4)? as i64, 0),
+    (EXT_TIMESTAMP, 8) => {
+      let value = read_uint(&mut data, 8)?;
+      timestamp((value & 0x3_ffff_ffff) as i64, (value >> 34) as u32)
+    },
+    (EXT_TIMESTAMP, 12) => {
+      let nanos = read_uint(&mut data, 4)?;
+      let secs = read_int(&mut data, 8)?;
+      timestamp(secs, nanos as u32)
+    },
+    _ => Value::Null
+  })
+}
+
+fn read_array<R: Read>(r: &mut R, len: usize, depth: usize) -> io::Result<Value> {
+  let mut values = Vec::new();
+  for _ in 0..len {
+    values.push(read_nested(r, depth + 1)?);
+  }
+
+  Ok(Value::Array(values))
+}
+
+fn read_map<R: Read>(r: &mut R, len: usize, depth: usize) -> io::Result<Value> {
+  let mut map = Map::new();
+  for _ in 0..len {
+    let key = match read_nested(r, depth + 1)? {
+      Value::String(s) => s,
+      other => other.to_string()
+    };
+
+    map.insert(key, read_nested(r, depth + 1)?);
+  }
+
+  Ok(Value::Object(map))
+}
+
+fn read_nested<R: Read>(r: &mut R, depth: usize) -> io::Result<Value> {
+  if depth > MAX_DEPTH {
+    return Err(invalid("msgpack value is nested too deeply"));
+  }
+
+  let mut marker = [0u8; 1];
+  r.read_exact(&mut marker)?;
+
+  read_marker(r, marker[0], depth)
+}
+
+fn read_marker<R: Read>(r: &mut R, marker: u8, depth: usize) -> io::Result<Value> {
+  Ok(match marker {
+    0x00..=0x7f => Value::Number(u64::from(marker).into()),
+    0x80..=0x8f => return read_map(r, (marker & 0x0f) as usize, depth),
+    0x90..=0x9f => return read_array(r, (marker & 0x0f) as usize, depth),
+    0xa0..=0xbf => return read_str(r, (marker & 0x1f) as usize),
+    0xc0 => Value::Null,
+    0xc2 => Value::Bool(false),
+    0xc3 => Value::Bool(true),
+
+    // bin, treated like str since loggers commonly use it for text
+    0xc4 => { let len = read_uint(r, 1)?; return read_str(r, len as usize) },
+    0xc5 => { let len = read_uint(r, 2)?; return read_str(r, len as usize) },
+    0xc6 => { let len = read_uint(r, 4)?; return read_str(r, len as usize) },
+
+    0xc7 => { let len = read_uint(r, 1)?; return read_ext(r, len as usize) },
+    0xc8 => { let len = read_uint(r, 2)?; return read_ext(r, len as usize) },
+    0xc9 => { let len = read_uint(r, 4)?; return read_ext(r, len as usize) },
+
+    0xca => float(f64::from(f32::from_bits(read_uint(r, 4)? as u32))),
+    0xcb => float(f64::from_bits(read_uint(r, 8)?)),
+
+    0xcc => Value::Number(read_uint(r, 1)?.into()),
+    0xcd => Value::Number(read_uint(r, 2)?.into()),
+    0xce => Value::Number(read_uint(r, 4)?.into()),
+    0xcf => Value::Number(read_uint(r, 8)?.into()),
+
+    0xd0 => Value::Number(read_int(r, 1)?.into()),
+    0xd1 => Value::Number(read_int(r, 2)?.into()),
+    0xd2 => Value::Number(read_int(r, 4)?.into()),
+    0xd3 => Value::Number(read_int(r, 8)?.into()),
+
+    0xd4 => return read_ext(r, 1),
+    0xd5 => return read_ext(r, 2),
+    0xd6 => return read_ext(r, 4),
+    0xd7 => return read_ext(r, 8),
+    0xd8 => return read_ext(r, 16),
+
+    0xd9 => { let len = read_uint(r, 1)?; return read_str(r, len as usize) },
+    0xda => { let len = read_uint(r, 2)?; return read_str(r, len as usize) },
+    0xdb => { let len = read_uint(r, 4)?; return read_str(r, len as usize) },
+
+    0xdc => { let len = read_uint(r, 2)?; return read_array(r, len as usize, depth) },
+    0xdd => { let len = read_uint(r, 4)?; return read_array(r, len as usize, depth) },
+    0xde => { let len = read_uint(r, 2)?; return read_map(r, len as usize, depth) },
+    0xdf => { let len = read_uint(r, 4)?; return read_map(r, len as usize, depth) },
+
+    0xe0..=0xff => Value::Number(i64::from(marker as i8).into()),
+
+    0xc1 => return Err(invalid("invalid msgpack marker 0xc1"))
+  })
+}
+
+/// Reads the next msgpack value from a stream, converted to JSON
+///
+/// Returns `Ok(None)` if the stream ended cleanly between values.
+pub fn read_value<R: Read>(r: &mut R) -> io::Result<Option<Value>> {
+  let mut marker = [0u8; 1];
+  loop {
+    match r.read(&mut marker) {
+      Ok(0) => return Ok(None),
+      Ok(_) => break,
+      Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+      Err(e) => return Err(e)
+    }
+  }
+
+  read_marker(r, marker[0], 0).map(Some)
+}
+
+/// A single log record from a msgpack stream
+#[derive(Debug, PartialEq)]
+pub struct Record {
+  pub doc: Map<String, Value>,
+
+  /// Event time, for records sent as fluentd `[time, record]` entries
+  pub time: Option<DateTime<Utc>>,
+
+  /// fluentd tag, if any
+  pub tag: Option<String>
+}
+
+fn event_time(value: &Value) -> Option<DateTime<Utc>> {
+  match value {
+    Value::Number(n) => n.as_i64().and_then(|secs| Utc.timestamp_opt(secs, 0).single()),
+    Value::String(s) => DateTime::parse_from_rfc3339(s).ok().map(|dt| dt.with_timezone(&Utc)),
+    _ => None
+  }
+}
+
+fn entry(tag: Option<&String>, time: &Value, doc: &Value) -> Option<Record> {
+  Some(Record {
+    doc: doc.as_object()?.clone(),
+    time: event_time(time),
+    tag: tag.cloned()
+  })
+}
+
+/// Splits a decoded value into log records
+///
+/// Plain maps are records themselves. fluentd's forward protocol entries are
+/// also accepted, i.e. `[time, record]`, `[tag, time, record]` (message mode)
+/// and `[tag, [[time, record], ...]]` (forward mode). Anything else is ignored.
+pub fn records(value: Value) -> Vec<Record> {
+  match value {
+    Value::Object(doc) => vec![Record { doc, time: None, tag: None }],
+    Value::Array(items) => match items.as_slice() {
+      [time, doc @ Value::Object(_)] => entry(None, time, doc).into_iter().collect(),
+      [Value::String(tag), time, doc @ Value::Object(_), ..] => {
+        entry(Some(tag), time, doc).into_iter().collect()
+      },
+      [Value::String(tag), Value::Array(entries), ..] => entries.iter()
+        .filter_map(|e| match e.as_array().map(|a| a.as_slice()) {
+          Some([time, doc]) => entry(Some(tag), time, doc),
+          _ => None
+        })
+        .collect(),
+      _ => Vec::new()
+    },
+    _ => Vec::new()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use serde_json::json;
+  use spectral::prelude::*;
+
+  fn decode(bytes: &[u8]) -> Vec<Value> {
+    let mut cursor = bytes;
+    let mut values = Vec::new();
+    while let Some(value) = read_value(&mut cursor).unwrap() {
+      values.push(value);
+    }
+
+    values
+  }
+
+  #[test]
+  fn test_decode() {
+    // {"msg": "hi", "n": -1, "ok": true}, [1, 300, 1.5]
+    let bytes = &[
+      0x83, 0xa3, b'm', b's', b'g', 0xa2, b'h', b'i', 0xa1, b'n', 0xff,
+      0xa2, b'o', b'k', 0xc3,
+      0x93, 0x01, 0xcd, 0x01, 0x2c, 0xcb, 0x3f, 0xf8, 0, 0, 0, 0, 0, 0
+    ];
+
+    assert_that!(decode(bytes)).is_equal_to(vec![
+      json!({"msg": "hi", "n": -1, "ok": true}),
+      json!([1, 300, 1.5])
+    ]);
+
+    let mut truncated: &[u8] = &[0x82, 0xa1, b'a'];
+    assert_that!(read_value(&mut truncated)).is_err();
+  }
+
+  #[test]
+  fn test_fluentd_records() {
+    // ["app", EventTime(2020-11-25T21:22:50.5Z), {"log": "hello"}]
+    let bytes = &[
+      0x93, 0xa3, b'a', b'p', b'p',
+      0xd7, 0x00, 0x5f, 0xbe, 0xcb, 0x2a, 0x1d, 0xcd, 0x65, 0x00,
+      0x81, 0xa3, b'l', b'o', b'g', 0xa5, b'h', b'e', b'l', b'l', b'o'
+    ];
+
+    let value = decode(bytes).pop().unwrap();
+    assert_that!(records(value)).is_equal_to(vec![Record {
+      doc: json!({"log": "hello"}).as_object().unwrap().clone(),
+      time: Some("2020-11-25T21:22:50.5Z".parse().unwrap()),
+      tag: Some("app".to_string())
+    }]);
+
+    let forward = json!(["app", [[1606339578, {"a": 1}], [1606339579, {"b": 2}]]]);
+    assert_that!(records(forward)).has_length(2);
+    assert_that!(records(json!("not a record"))).has_length(0);
+  }
+}
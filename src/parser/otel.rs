@@ -0,0 +1,246 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+use std::collections::HashMap;
+
+use chrono::prelude::*;
+use serde_json::{Map, Value};
+
+use crate::config::Config;
+use super::types::{LogLevel, MappingField, Message, MessageKind, ReaderMetadata};
+use super::util::parse_level;
+
+static TIMESTAMP_FIELDS: &[&str] = &["timeUnixNano", "observedTimeUnixNano"];
+
+/// Metadata keys for trace context, shared with other formats that carry it
+pub static TRACE_ID_FIELD: &str = "trace_id";
+pub static SPAN_ID_FIELD: &str = "span_id";
+
+/// Determines if a JSON document looks like an OTLP-JSON LogRecord
+pub fn is_otel_document(doc: &Map<String, Value>) -> bool {
+  (doc.contains_key("body") || doc.contains_key("severityNumber"))
+    && TIMESTAMP_FIELDS.iter().any(|f| doc.contains_key(*f))
+}
+
+/// Maps OpenTelemetry severity numbers onto log levels
+///
+/// See: https://opentelemetry.io/docs/specs/otel/logs/data-model/#field-severitynumber
+fn level_for_severity(severity: u64) -> Option<LogLevel> {
+  match severity {
+    1..=8 => Some(LogLevel::Debug),
+    9..=12 => Some(LogLevel::Info),
+    13..=16 => Some(LogLevel::Warning),
+    17..=20 => Some(LogLevel::Error),
+    21..=24 => Some(LogLevel::Fatal),
+    _ => None
+  }
+}
+
+/// Parses a nanosecond epoch timestamp; OTLP-JSON encodes 64 bit integers as
+/// strings, but some producers don't
+fn parse_unix_nanos(value: &Value) -> Option<DateTime<Utc>> {
+  let nanos = match value {
+    Value::String(s) => s.parse::<i64>().ok()?,
+    Value::Number(n) => n.as_i64()?,
+    _ => return None
+  };
+
+  if nanos == 0 {
+    // zero means unset
+    return None;
+  }
+
+  Some(Utc.timestamp_nanos(nanos))
+}
+
+/// Converts an OTLP `AnyValue` into a plain JSON value
+fn any_value(value: &Value) -> Value {
+  let obj = match value.as_object() {
+    Some(obj) => obj,
+    None => return value.clone()
+  };
+
+  if let Some(Value::String(i)) = obj.get("intValue") {
+    return i.parse::<i64>()
+      .map(|i| Value::Number(i.into()))
+      .unwrap_or_else(|_| Value::String(i.clone()));
+  }
+
+  if let Some(values) = obj.get("arrayValue").and_then(|a| a.get("values")) {
+    return match values.as_array() {
+      Some(values) => Value::Array(values.iter().map(any_value).collect()),
+      None => Value::Array(Vec::new())
+    };
+  }
+
+  if let Some(values) = obj.get("kvlistValue").and_then(|a| a.get("values")) {
+    return Value::Object(key_values(values));
+  }
+
+  for key in &["stringValue", "boolValue", "intValue", "doubleValue", "bytesValue"] {
+    if let Some(v) = obj.get(*key) {
+      return v.clone();
+    }
+  }
+
+  Value::Null
+}
+
+/// Converts an OTLP `KeyValue` array into a JSON object
+fn key_values(values: &Value) -> Map<String, Value> {
+  let mut map = Map::new();
+
+  if let Some(values) = values.as_array() {
+    for kv in values {
+      if let Some(key) = kv.get("key").and_then(|k| k.as_str()) {
+        let value = kv.get("value").map(any_value).unwrap_or(Value::Null);
+        map.insert(key.to_string(), value);
+      }
+    }
+  }
+
+  map
+}
+
+/// Maps an OTLP-JSON LogRecord into a Message
+///
+/// Attributes are flattened into metadata, and trace and span ids are
+/// promoted to the `trace_id` and `span_id` fields.
+pub fn parse_otel_document(
+  config: &Config,
+  line: &str, mut doc: Map<String, Value>, meta: Option<ReaderMetadata>
+) -> Message {
+  let mut mapped_fields = HashMap::new();
+
+  let mut timestamp = None;
+  for field in TIMESTAMP_FIELDS {
+    if let Some(value) = doc.remove(*field) {
+      if timestamp.is_none() {
+        timestamp = parse_unix_nanos(&value);
+        if timestamp.is_some() {
+          mapped_fields.insert(field.to_string(), MappingField::Timestamp);
+        }
+      }
+    }
+  }
+
+  let severity_text = doc.remove("severityText");
+  let level = doc.remove("severityNumber")
+    .and_then(|s| s.as_u64())
+    .and_then(level_for_severity)
+    .or_else(|| {
+      severity_text.as_ref()
+        .and_then(|s| s.as_str())
+        .and_then(|s| parse_level(config, s))
+    });
+
+  if level.is_some() {
+    mapped_fields.insert("severityNumber".to_string(), MappingField::Level);
+  }
+
+  let text = match doc.remove("body").map(|b| any_value(&b)) {
+    Some(Value::String(s)) => Some(s),
+    Some(Value::Null) | None => None,
+    Some(other) => Some(other.to_string())
+  };
+
+  if text.is_some() {
+    mapped_fields.insert("body".to_string(), MappingField::Text);
+  }
+
+  let mut metadata: HashMap<String, Value> = HashMap::new();
+  if let Some(attributes) = doc.remove("attributes") {
+    metadata.extend(key_values(&attributes));
+  }
+
+  for (from, to) in &[("traceId", TRACE_ID_FIELD), ("spanId", SPAN_ID_FIELD)] {
+    match doc.remove(*from) {
+      Some(Value::String(id)) if !id.is_empty() => {
+        metadata.insert(to.to_string(), Value::String(id));
+      },
+      _ => ()
+    }
+  }
+
+  // bookkeeping fields aren't interesting unless something was dropped
+  if doc.get("droppedAttributesCount").and_then(|c| c.as_u64()) == Some(0) {
+    doc.remove("droppedAttributesCount");
+  }
+
+  for (key, value) in doc {
+    metadata.entry(key).or_insert(value);
+  }
+
+  Message {
+    kind: MessageKind::Otel,
+    raw: line.to_string(),
+    timestamp: timestamp.or_else(|| meta.as_ref().and_then(|m| m.timestamp)),
+    reader_metadata: meta,
+    level, text, metadata, mapped_fields
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::sync::Arc;
+
+  use serde_json::json;
+  use simple_error::{SimpleResult, SimpleError};
+  use spectral::prelude::*;
+  use structopt::StructOpt;
+
+  use crate::config::Config;
+  use crate::parser::json::parse_json;
+
+  fn parse_to_value(line: &str) -> SimpleResult<Value> {
+    let config = Arc::new(Config::from_iter_safe(vec![""]).unwrap());
+    let parsed = parse_json(config, line, None)
+      .map_err(|e| SimpleError::new(format!("{:?}", e)))?;
+
+    serde_json::to_value(parsed).map_err(SimpleError::from)
+  }
+
+  #[test]
+  fn test_otel() {
+    let line = concat!(
+      r#"{"timeUnixNano":"1544712660300000000","observedTimeUnixNano":"1544712660300000000","#,
+      r#""severityNumber":10,"severityText":"Information","traceId":"5b8efff798038103d269b633813fc60c","#,
+      r#""spanId":"eee19b7ec3c1b174","body":{"stringValue":"Example log record"},"#,
+      r#""attributes":[{"key":"string.attribute","value":{"stringValue":"some string"}},"#,
+      r#"{"key":"int.attribute","value":{"intValue":"10"}},"#,
+      r#"{"key":"array.attribute","value":{"arrayValue":{"values":[{"boolValue":true}]}}},"#,
+      r#"{"key":"map.attribute","value":{"kvlistValue":{"values":[{"key":"some.map.key","value":{"stringValue":"some value"}}]}}}],"#,
+      r#""droppedAttributesCount":0}"#
+    );
+
+    assert_that!(parse_to_value(line)).is_ok_containing(json!({
+      "kind": "otel",
+      "raw": line,
+      "timestamp": "2018-12-13T14:51:00.300Z",
+      "level": "info",
+      "text": "Example log record",
+      "mapped_fields": {
+        "timeUnixNano": "timestamp",
+        "severityNumber": "level",
+        "body": "text"
+      },
+      "metadata": {
+        "string.attribute": "some string",
+        "int.attribute": 10,
+        "array.attribute": [true],
+        "map.attribute": {"some.map.key": "some value"},
+        "trace_id": "5b8efff798038103d269b633813fc60c",
+        "span_id": "eee19b7ec3c1b174"
+      }
+    }));
+  }
+
+  #[test]
+  fn test_severity() {
+    assert_that!(level_for_severity(1)).is_equal_to(Some(LogLevel::Debug));
+    assert_that!(level_for_severity(13)).is_equal_to(Some(LogLevel::Warning));
+    assert_that!(level_for_severity(24)).is_equal_to(Some(LogLevel::Fatal));
+    assert_that!(level_for_severity(0)).is_equal_to(None);
+  }
+}
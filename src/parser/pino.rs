@@ -0,0 +1,103 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+use std::error::Error;
+
+use serde_json::{Map, Value};
+
+use crate::config::Config;
+use super::bunyan::parse_node_document;
+use super::timestamp::parse_epoch;
+use super::types::{MappingField, Message, MessageKind, ReaderMetadata};
+
+/// Determines if a JSON document looks like a pino record: bunyan-like, but
+/// with an epoch `time`, usually in milliseconds
+pub fn is_pino_document(doc: &Map<String, Value>) -> bool {
+  doc.get("level").map(Value::is_number).unwrap_or(false)
+    && doc.get("time").map(Value::is_number).unwrap_or(false)
+}
+
+/// Maps a pino record into a Message
+pub fn parse_pino_document(
+  config: &Config,
+  line: &str,
+  mut doc: Map<String, Value>,
+  meta: Option<ReaderMetadata>
+) -> Result<Option<Message>, Box<dyn Error>> {
+  // older versions of pino included a bunyan-style format version
+  doc.remove("v");
+
+  // pino writes milliseconds by default, but `timestamp` options may change it
+  let timestamp = doc.get("time").and_then(Value::as_f64).and_then(parse_epoch);
+  if timestamp.is_some() {
+    doc.remove("time");
+  }
+
+  let message = parse_node_document(config, line, MessageKind::Pino, doc, meta)?;
+
+  Ok(message.map(|mut message| {
+    if timestamp.is_some() {
+      message.timestamp = timestamp;
+      message.mapped_fields.insert("time".to_string(), MappingField::Timestamp);
+    }
+
+    message
+  }))
+}
+
+#[cfg(test)]
+mod tests {
+  use std::sync::Arc;
+
+  use serde_json::json;
+  use simple_error::{SimpleResult, SimpleError};
+  use spectral::prelude::*;
+  use structopt::StructOpt;
+
+  use crate::config::Config;
+  use crate::parser::json::parse_json;
+
+  fn parse_to_value(line: &str) -> SimpleResult<serde_json::Value> {
+    let config = Arc::new(Config::from_iter_safe(vec![""]).unwrap());
+    let parsed = parse_json(config, line, None)
+      .map_err(|e| SimpleError::new(format!("{:?}", e)))?;
+
+    serde_json::to_value(parsed).map_err(SimpleError::from)
+  }
+
+  #[test]
+  fn test_pino() {
+    let line = concat!(
+      r#"{"level":40,"time":1531171074631,"pid":657,"hostname":"Davids-MBP-3.fritz.box","#,
+      r#""msg":"hello world","v":1}"#
+    );
+
+    assert_that!(parse_to_value(line)).is_ok_containing(json!({
+      "kind": "pino",
+      "raw": line,
+      "timestamp": "2018-07-09T21:17:54.631Z",
+      "level": "warning",
+      "text": "hello world",
+      "mapped_fields": {
+        "time": "timestamp",
+        "level": "level",
+        "msg": "text"
+      },
+      "metadata": {
+        "hostname": "Davids-MBP-3.fritz.box",
+        "pid": 657
+      }
+    }));
+  }
+
+  #[test]
+  fn test_pino_epoch_units() {
+    let timestamp = |line: &str| parse_to_value(line).unwrap()["timestamp"].clone();
+
+    assert_that!(timestamp(r#"{"level":30,"time":1600000000,"msg":"x"}"#))
+      .is_equal_to(json!("2020-09-13T12:26:40Z"));
+    assert_that!(timestamp(r#"{"level":30,"time":1600000000000,"msg":"x"}"#))
+      .is_equal_to(json!("2020-09-13T12:26:40Z"));
+    assert_that!(timestamp(r#"{"level":30,"time":1600000000.5,"msg":"x"}"#))
+      .is_equal_to(json!("2020-09-13T12:26:40.500Z"));
+  }
+}
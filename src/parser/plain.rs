@@ -8,6 +8,7 @@ use chrono::prelude::*;
 use regex::RegexSet;
 
 use crate::config::Config;
+use super::timestamp::find_timestamp;
 use super::types::{LogLevel, Message, MessageKind, ReaderMetadata};
 
 fn get_log_level(line: &str) -> Option<LogLevel> {
@@ -50,14 +51,22 @@ fn get_meta_timestamp(meta: &Option<ReaderMetadata>) -> Option<DateTime<Utc>> {
 }
 
 pub fn parse_plain(
-  _config: Arc<Config>, line: &str, meta: Option<ReaderMetadata>
+  config: Arc<Config>, line: &str, meta: Option<ReaderMetadata>
 ) -> Result<Option<Message>, Box<dyn Error>> {
+  // a leading timestamp is redundant with the timestamp column, so remove it
+  // from the text; anything elsewhere in the line is left alone
+  let (timestamp, text) = match find_timestamp(&config, line) {
+    Some((timestamp, 0, end)) => (Some(timestamp), line[end..].trim_start()),
+    Some((timestamp, _, _)) => (Some(timestamp), line),
+    None => (get_meta_timestamp(&meta), line)
+  };
+
   Ok(Some(Message {
     kind: MessageKind::Plain,
-    timestamp: get_meta_timestamp(&meta),
-    level: get_log_level(line),
+    timestamp,
+    level: config.file().levels.find(line).or_else(|| get_log_level(line)),
     raw: line.to_string(),
-    text: Some(String::from(line)),
+    text: Some(String::from(text)),
     metadata: HashMap::new(),
     reader_metadata: meta,
     mapped_fields: HashMap::new()
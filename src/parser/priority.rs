@@ -0,0 +1,75 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+use regex::Regex;
+
+use super::types::LogLevel;
+
+/// Maps a syslog-style numeric priority to a log level
+///
+/// Priorities greater than 7 also encode a facility (`facility * 8 + severity`)
+/// which is discarded here.
+fn map_priority(priority: u16) -> LogLevel {
+  match priority % 8 {
+    0 ..= 2 => LogLevel::Fatal, // emerg, alert, crit
+    3 => LogLevel::Error,
+    4 => LogLevel::Warning,
+    5 | 6 => LogLevel::Info, // notice, info
+    _ => LogLevel::Debug
+  }
+}
+
+/// Splits a `<N>` priority prefix (as written by `sd-daemon(3)` conventions and
+/// `dmesg -r`) off of a line, returning the mapped level and the remainder of
+/// the line
+pub fn strip_priority(line: &str) -> Option<(LogLevel, &str)> {
+  lazy_static! {
+    static ref RE: Regex = Regex::new(r"^<(\d{1,3})>").unwrap();
+  }
+
+  // cheap check before bothering with the regex
+  if !line.starts_with('<') {
+    return None;
+  }
+
+  let caps = RE.captures(line)?;
+  let prefix = caps.get(0)?;
+  let priority = caps.get(1)?.as_str().parse::<u16>().ok()?;
+
+  Some((map_priority(priority), &line[prefix.end()..]))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use spectral::prelude::*;
+
+  #[test]
+  fn test_strip_priority() {
+    assert_that!(strip_priority("<3>oh no"))
+      .is_some()
+      .is_equal_to((LogLevel::Error, "oh no"));
+
+    assert_that!(strip_priority("<6>[    0.000000] Linux version 5.4.0"))
+      .is_some()
+      .is_equal_to((LogLevel::Info, "[    0.000000] Linux version 5.4.0"));
+
+    assert_that!(strip_priority("<7>")).is_some().is_equal_to((LogLevel::Debug, ""));
+  }
+
+  #[test]
+  fn test_strip_priority_facility() {
+    // facility 4 (auth), severity 2 (crit)
+    assert_that!(strip_priority("<34>su: BAD SU"))
+      .is_some()
+      .is_equal_to((LogLevel::Fatal, "su: BAD SU"));
+  }
+
+  #[test]
+  fn test_strip_priority_invalid() {
+    assert_that!(strip_priority("hello")).is_none();
+    assert_that!(strip_priority("<html>")).is_none();
+    assert_that!(strip_priority("<1234>foo")).is_none();
+    assert_that!(strip_priority(" <3>foo")).is_none();
+  }
+}
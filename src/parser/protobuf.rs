@@ -0,0 +1,599 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{self, Read};
+use std::str::FromStr;
+
+use chrono::prelude::*;
+use serde_json::{Map, Number, Value};
+use simple_error::{SimpleError, SimpleResult};
+
+/// Largest message accepted from a stream, to avoid huge allocations when a
+/// stream is corrupt or isn't protobuf at all
+const MAX_MESSAGE_LEN: u64 = 64 * 1024 * 1024;
+
+const LABEL_REPEATED: u64 = 3;
+
+static TIMESTAMP_TYPE: &str = ".google.protobuf.Timestamp";
+
+/// A raw field value, as encoded on the wire
+#[derive(Debug, Clone, Copy)]
+enum Wire<'a> {
+  Varint(u64),
+  Fixed64(u64),
+  Bytes(&'a [u8]),
+  Fixed32(u32)
+}
+
+fn err<T>(message: &str) -> SimpleResult<T> {
+  Err(SimpleError::new(message))
+}
+
+fn read_varint(buf: &mut &[u8]) -> SimpleResult<u64> {
+  let mut value = 0u64;
+  for i in 0..10 {
+    let (byte, rest) = match buf.split_first() {
+      Some((byte, rest)) => (*byte, rest),
+      None => return err("truncated varint")
+    };
+
+    *buf = rest;
+    value |= u64::from(byte & 0x7f) << (i * 7);
+    if byte & 0x80 == 0 {
+      return Ok(value);
+    }
+  }
+
+  err("varint is too long")
+}
+
+fn read_fixed(buf: &mut &[u8], len: usize) -> SimpleResult<u64> {
+  if buf.len() < len {
+    return err("truncated fixed-width field");
+  }
+
+  let (bytes, rest) = buf.split_at(len);
+  *buf = rest;
+
+  // little endian
+  Ok(bytes.iter().rev().fold(0u64, |acc, b| (acc << 8) | u64::from(*b)))
+}
+
+/// Splits an encoded message into its fields, in wire order
+fn fields(mut buf: &[u8]) -> SimpleResult<Vec<(u64, Wire<'_>)>> {
+  let mut fields = Vec::new();
+  while !buf.is_empty() {
+    let key = read_varint(&mut buf)?;
+    let value = match key & 0x7 {
+      0 => Wire::Varint(read_varint(&mut buf)?),
+      1 => Wire::Fixed64(read_fixed(&mut buf, 8)?),
+      2 => {
+        let len = read_varint(&mut buf)? as usize;
+        if buf.len() < len {
+          return err("truncated length-delimited field");
+        }
+
+        let (bytes, rest) = buf.split_at(len);
+        buf = rest;
+        Wire::Bytes(bytes)
+      },
+      5 => Wire::Fixed32(read_fixed(&mut buf, 4)? as u32),
+      3 | 4 => return err("groups are not supported"),
+      _ => return err("invalid wire type")
+    };
+
+    fields.push((key >> 3, value));
+  }
+
+  Ok(fields)
+}
+
+fn string(value: Wire) -> Option<String> {
+  match value {
+    Wire::Bytes(b) => Some(String::from_utf8_lossy(b).into_owned()),
+    _ => None
+  }
+}
+
+fn varint(value: Wire) -> Option<u64> {
+  match value {
+    Wire::Varint(v) => Some(v),
+    _ => None
+  }
+}
+
+/// A field of a message type, from a `FieldDescriptorProto`
+#[derive(Debug, Clone, Default)]
+struct FieldType {
+  name: String,
+  number: u64,
+  repeated: bool,
+  kind: u64,
+  type_name: Option<String>
+}
+
+/// A message type, from a `DescriptorProto`
+#[derive(Debug, Default)]
+struct MessageType {
+  fields: HashMap<u64, FieldType>,
+  map_entry: bool
+}
+
+/// Message and enum types loaded from a compiled `FileDescriptorSet`, e.g.
+/// from `protoc --include_imports --descriptor_set_out=...`
+#[derive(Debug, Default)]
+pub struct Descriptors {
+  messages: HashMap<String, MessageType>,
+  enums: HashMap<String, HashMap<i64, String>>
+}
+
+impl Descriptors {
+  pub fn from_bytes(buf: &[u8]) -> SimpleResult<Descriptors> {
+    let mut descriptors = Descriptors::default();
+
+    for (number, file) in fields(buf)? {
+      if let (1, Wire::Bytes(file)) = (number, file) {
+        descriptors.load_file(file)?;
+      }
+    }
+
+    Ok(descriptors)
+  }
+
+  fn load_file(&mut self, buf: &[u8]) -> SimpleResult<()> {
+    let fields = fields(buf)?;
+
+    let package = fields.iter()
+      .find(|(n, _)| *n == 2)
+      .and_then(|(_, v)| string(*v))
+      .map(|p| format!(".{}", p))
+      .unwrap_or_default();
+
+    for (number, value) in fields {
+      match (number, value) {
+        (4, Wire::Bytes(message)) => self.load_message(&package, message)?,
+        (5, Wire::Bytes(e)) => self.load_enum(&package, e)?,
+        _ => ()
+      }
+    }
+
+    Ok(())
+  }
+
+  fn load_message(&mut self, scope: &str, buf: &[u8]) -> SimpleResult<()> {
+    let fields = fields(buf)?;
+
+    let name = fields.iter()
+      .find(|(n, _)| *n == 1)
+      .and_then(|(_, v)| string(*v))
+      .ok_or_else(|| SimpleError::new("message type is missing a name"))?;
+    let full_name = format!("{}.{}", scope, name);
+
+    let mut message = MessageType::default();
+    for (number, value) in fields {
+      match (number, value) {
+        (2, Wire::Bytes(field)) => {
+          let field = load_field(field)?;
+          message.fields.insert(field.number, field);
+        },
+        (3, Wire::Bytes(nested)) => self.load_message(&full_name, nested)?,
+        (4, Wire::Bytes(e)) => self.load_enum(&full_name, e)?,
+        (7, Wire::Bytes(options)) => {
+          // MessageOptions.map_entry
+          message.map_entry = self::fields(options)?.iter()
+            .any(|(n, v)| *n == 7 && varint(*v) == Some(1));
+        },
+        _ => ()
+      }
+    }
+
+    self.messages.insert(full_name, message);
+    Ok(())
+  }
+
+  fn load_enum(&mut self, scope: &str, buf: &[u8]) -> SimpleResult<()> {
+    let mut name = None;
+    let mut values = HashMap::new();
+
+    for (number, value) in fields(buf)? {
+      match (number, value) {
+        (1, value) => name = string(value),
+        (2, Wire::Bytes(value)) => {
+          let mut value_name = None;
+          let mut value_number = 0;
+          for (n, v) in fields(value)? {
+            match n {
+              1 => value_name = string(v),
+              2 => value_number = varint(v).unwrap_or(0) as i32 as i64,
+              _ => ()
+            }
+          }
+
+          if let Some(value_name) = value_name {
+            values.insert(value_number, value_name);
+          }
+        },
+        _ => ()
+      }
+    }
+
+    match name {
+      Some(name) => {
+        self.enums.insert(format!("{}.{}", scope, name), values);
+        Ok(())
+      },
+      None => err("enum type is missing a name")
+    }
+  }
+
+  /// Determines if a message type is known, by its full name (e.g.
+  /// `mypackage.LogRecord`)
+  pub fn has_message(&self, name: &str) -> bool {
+    self.messages.contains_key(&qualify(name))
+  }
+
+  /// Decodes a message of the given type into a JSON object
+  ///
+  /// Fields are keyed by their names in the `.proto`. Enums are decoded to
+  /// their value names, `bytes` fields to hex, and `google.protobuf.Timestamp`
+  /// messages to RFC 3339 strings. Fields missing from the descriptor are kept
+  /// under their field numbers.
+  pub fn decode(&self, name: &str, buf: &[u8]) -> SimpleResult<Map<String, Value>> {
+    self.decode_message(&qualify(name), buf, 0)
+  }
+
+  fn decode_message(
+    &self, name: &str, buf: &[u8], depth: usize
+  ) -> SimpleResult<Map<String, Value>> {
+    if depth > 64 {
+      return err("message is nested too deeply");
+    }
+
+    let message = self.messages.get(name)
+      .ok_or_else(|| SimpleError::new(format!("unknown message type: {}", name)))?;
+
+    let mut out = Map::new();
+    for (number, wire) in fields(buf)? {
+      let field = match message.fields.get(&number) {
+        Some(field) => field,
+        None => {
+          out.insert(number.to_string(), unknown(wire));
+          continue;
+        }
+      };
+
+      let values = match (wire, packable(field.kind)) {
+        // packed repeated scalars
+        (Wire::Bytes(mut packed), true) => {
+          let mut values = Vec::new();
+          while !packed.is_empty() {
+            let wire = match field.kind {
+              1 | 6 | 16 => Wire::Fixed64(read_fixed(&mut packed, 8)?),
+              2 | 7 | 15 => Wire::Fixed32(read_fixed(&mut packed, 4)? as u32),
+              _ => Wire::Varint(read_varint(&mut packed)?)
+            };
+
+            values.push(self.decode_value(field, wire, depth)?);
+          }
+
+          values
+        },
+        _ => vec![self.decode_value(field, wire, depth)?]
+      };
+
+      if field.repeated {
+        let entry = out.entry(field.name.clone()).or_insert_with(|| Value::Array(Vec::new()));
+        if let Value::Array(items) = entry {
+          items.extend(values);
+        }
+      } else if let Some(value) = values.into_iter().last() {
+        // last value wins for singular fields, as in any protobuf decoder
+        out.insert(field.name.clone(), value);
+      }
+    }
+
+    // map fields are encoded as repeated key/value entry messages
+    for field in message.fields.values() {
+      let is_map = field.type_name.as_ref()
+        .and_then(|t| self.messages.get(t))
+        .map(|m| m.map_entry)
+        .unwrap_or(false);
+
+      if !is_map {
+        continue;
+      }
+
+      if let Some(Value::Array(entries)) = out.remove(&field.name) {
+        let map: Map<String, Value> = entries.into_iter()
+          .filter_map(|entry| match entry {
+            Value::Object(mut entry) => {
+              let key = match entry.remove("key")? {
+                Value::String(s) => s,
+                other => other.to_string()
+              };
+
+              Some((key, entry.remove("value").unwrap_or(Value::Null)))
+            },
+            _ => None
+          })
+          .collect();
+
+        out.insert(field.name.clone(), Value::Object(map));
+      }
+    }
+
+    Ok(out)
+  }
+
+  fn decode_value(&self, field: &FieldType, wire: Wire, depth: usize) -> SimpleResult<Value> {
+    let value = match (field.kind, wire) {
+      (1, Wire::Fixed64(v)) => float(f64::from_bits(v)),
+      (2, Wire::Fixed32(v)) => float(f64::from(f32::from_bits(v))),
+      (3, Wire::Varint(v)) | (16, Wire::Fixed64(v)) => Value::from(v as i64),
+      (5, Wire::Varint(v)) => Value::from(v as i32),
+      (15, Wire::Fixed32(v)) => Value::from(v as i32),
+      (4, Wire::Varint(v)) | (6, Wire::Fixed64(v)) => Value::from(v),
+      (13, Wire::Varint(v)) => Value::from(v as u32),
+      (7, Wire::Fixed32(v)) => Value::from(v),
+      (17, Wire::Varint(v)) => Value::from(zigzag(v) as i32),
+      (18, Wire::Varint(v)) => Value::from(zigzag(v)),
+      (8, Wire::Varint(v)) => Value::Bool(v != 0),
+      (9, Wire::Bytes(b)) => Value::String(String::from_utf8_lossy(b).into_owned()),
+      (12, Wire::Bytes(b)) => Value::String(hex(b)),
+      (14, Wire::Varint(v)) => {
+        let number = v as i32 as i64;
+        field.type_name.as_ref()
+          .and_then(|t| self.enums.get(t))
+          .and_then(|values| values.get(&number))
+          .map(|name| Value::String(name.clone()))
+          .unwrap_or_else(|| Value::from(number))
+      },
+      (11, Wire::Bytes(b)) => match field.type_name.as_ref() {
+        Some(t) if t == TIMESTAMP_TYPE => timestamp(b)?,
+        Some(t) => Value::Object(self.decode_message(t, b, depth + 1)?),
+        None => unknown(wire)
+      },
+      _ => return Err(SimpleError::new(format!(
+        "field {} has an unexpected wire type", field.name
+      )))
+    };
+
+    Ok(value)
+  }
+}
+
+impl FromStr for Descriptors {
+  type Err = SimpleError;
+
+  fn from_str(path: &str) -> Result<Self, Self::Err> {
+    let expanded_path = shellexpand::full(path).map_err(SimpleError::from)?;
+    let buf = fs::read(expanded_path.to_string()).map_err(SimpleError::from)?;
+
+    Descriptors::from_bytes(&buf).map_err(|e| SimpleError::new(
+      format!("error loading descriptors {}: {}", path, e)
+    ))
+  }
+}
+
+fn load_field(buf: &[u8]) -> SimpleResult<FieldType> {
+  let mut field = FieldType::default();
+  for (number, value) in fields(buf)? {
+    match number {
+      1 => field.name = string(value).unwrap_or_default(),
+      3 => field.number = varint(value).unwrap_or(0),
+      4 => field.repeated = varint(value) == Some(LABEL_REPEATED),
+      5 => field.kind = varint(value).unwrap_or(0),
+      6 => field.type_name = string(value),
+      _ => ()
+    }
+  }
+
+  Ok(field)
+}
+
+/// Adds the leading `.` used by fully-qualified names in descriptors
+fn qualify(name: &str) -> String {
+  if name.starts_with('.') {
+    name.to_string()
+  } else {
+    format!(".{}", name)
+  }
+}
+
+/// Determines if a scalar type may use the packed repeated encoding
+fn packable(kind: u64) -> bool {
+  // string, group, message and bytes
+  !matches!(kind, 9..=12)
+}
+
+fn zigzag(v: u64) -> i64 {
+  ((v >> 1) as i64) ^ -((v & 1) as i64)
+}
+
+fn float(f: f64) -> Value {
+  Number::from_f64(f).map(Value::Number).unwrap_or(Value::Null)
+}
+
+fn hex(bytes: &[u8]) -> String {
+  bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn timestamp(buf: &[u8]) -> SimpleResult<Value> {
+  let mut secs = 0;
+  let mut nanos = 0;
+  for (number, value) in fields(buf)? {
+    match number {
+      1 => secs = varint(value).unwrap_or(0) as i64,
+      2 => nanos = varint(value).unwrap_or(0) as u32,
+      _ => ()
+    }
+  }
+
+  Ok(match Utc.timestamp_opt(secs, nanos).single() {
+    Some(dt) => Value::String(dt.to_rfc3339_opts(SecondsFormat::AutoSi, true)),
+    None => Value::Null
+  })
+}
+
+/// Best-effort conversion of a field that isn't in the descriptor
+fn unknown(wire: Wire) -> Value {
+  match wire {
+    Wire::Varint(v) | Wire::Fixed64(v) => Value::from(v),
+    Wire::Fixed32(v) => Value::from(v),
+    Wire::Bytes(b) => match std::str::from_utf8(b) {
+      Ok(s) => Value::String(s.to_string()),
+      Err(_) => Value::String(hex(b))
+    }
+  }
+}
+
+/// Reads the next varint-length-delimited message from a stream, as written
+/// by e.g. Java's `writeDelimitedTo()`
+///
+/// Returns `Ok(None)` if the stream ended cleanly between messages.
+pub fn read_delimited<R: Read>(r: &mut R) -> io::Result<Option<Vec<u8>>> {
+  let mut len = 0u64;
+  let mut byte = [0u8; 1];
+  for i in 0..10 {
+    if r.read(&mut byte)? == 0 {
+      if i == 0 {
+        return Ok(None);
+      }
+
+      return Err(io::ErrorKind::UnexpectedEof.into());
+    }
+
+    len |= u64::from(byte[0] & 0x7f) << (i * 7);
+    if byte[0] & 0x80 == 0 {
+      break;
+    }
+  }
+
+  if len > MAX_MESSAGE_LEN {
+    return Err(io::Error::new(
+      io::ErrorKind::InvalidData,
+      format!("message length {} is too large", len)
+    ));
+  }
+
+  let mut buf = Vec::new();
+  r.take(len).read_to_end(&mut buf)?;
+  if (buf.len() as u64) < len {
+    return Err(io::ErrorKind::UnexpectedEof.into());
+  }
+
+  Ok(Some(buf))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use serde_json::json;
+  use spectral::prelude::*;
+
+  fn key(number: u64, wire_type: u64) -> u8 {
+    ((number << 3) | wire_type) as u8
+  }
+
+  fn bytes_field(number: u64, value: &[u8]) -> Vec<u8> {
+    let mut out = vec![key(number, 2)];
+    let mut len = value.len();
+    while len >= 0x80 {
+      out.push((len as u8 & 0x7f) | 0x80);
+      len >>= 7;
+    }
+
+    out.push(len as u8);
+    out.extend_from_slice(value);
+    out
+  }
+
+  fn varint_field(number: u64, value: u8) -> Vec<u8> {
+    vec![key(number, 0), value]
+  }
+
+  fn field_descriptor(name: &str, number: u8, label: u8, kind: u8, type_name: Option<&str>) -> Vec<u8> {
+    let mut out = bytes_field(1, name.as_bytes());
+    out.extend(varint_field(3, number));
+    out.extend(varint_field(4, label));
+    out.extend(varint_field(5, kind));
+    if let Some(type_name) = type_name {
+      out.extend(bytes_field(6, type_name.as_bytes()));
+    }
+
+    out
+  }
+
+  /// package demo; enum Level { INFO = 0; WARN = 1; }
+  /// message Log { string msg = 1; Level level = 2; repeated int32 codes = 3;
+  ///   google.protobuf.Timestamp time = 4; map<string, string> labels = 5; }
+  fn descriptors() -> Descriptors {
+    let mut message = bytes_field(1, b"Log");
+    message.extend(bytes_field(2, &field_descriptor("msg", 1, 1, 9, None)));
+    message.extend(bytes_field(2, &field_descriptor("level", 2, 1, 14, Some(".demo.Level"))));
+    message.extend(bytes_field(2, &field_descriptor("codes", 3, 3, 5, None)));
+    message.extend(bytes_field(2, &field_descriptor(
+      "time", 4, 1, 11, Some(".google.protobuf.Timestamp")
+    )));
+    message.extend(bytes_field(2, &field_descriptor(
+      "labels", 5, 3, 11, Some(".demo.Log.LabelsEntry")
+    )));
+
+    let mut entry = bytes_field(1, b"LabelsEntry");
+    entry.extend(bytes_field(2, &field_descriptor("key", 1, 1, 9, None)));
+    entry.extend(bytes_field(2, &field_descriptor("value", 2, 1, 9, None)));
+    entry.extend(bytes_field(7, &varint_field(7, 1)));
+    message.extend(bytes_field(3, &entry));
+
+    let mut warn = bytes_field(1, b"WARN");
+    warn.extend(varint_field(2, 1));
+    let mut level = bytes_field(1, b"Level");
+    level.extend(bytes_field(2, &bytes_field(1, b"INFO")));
+    level.extend(bytes_field(2, &warn));
+
+    let mut file = bytes_field(1, b"demo.proto");
+    file.extend(bytes_field(2, b"demo"));
+    file.extend(bytes_field(4, &message));
+    file.extend(bytes_field(5, &level));
+
+    Descriptors::from_bytes(&bytes_field(1, &file)).unwrap()
+  }
+
+  #[test]
+  fn test_decode() {
+    let descriptors = descriptors();
+    assert_that!(descriptors.has_message("demo.Log")).is_true();
+    assert_that!(descriptors.has_message("demo.Missing")).is_false();
+
+    let mut message = bytes_field(1, b"hello");
+    message.extend(varint_field(2, 1));
+    message.extend(bytes_field(3, &[1, 2, 3]));
+    message.extend(bytes_field(4, &[key(1, 0), 0xd2, 0x09]));
+    message.extend(varint_field(9, 42));
+
+    let mut label = bytes_field(1, b"app");
+    label.extend(bytes_field(2, b"api"));
+    message.extend(bytes_field(5, &label));
+
+    assert_that!(descriptors.decode("demo.Log", &message).map(Value::Object))
+      .is_ok_containing(json!({
+        "msg": "hello",
+        "level": "WARN",
+        "codes": [1, 2, 3],
+        "time": "1970-01-01T00:20:34Z",
+        "labels": {"app": "api"},
+        "9": 42
+      }));
+  }
+
+  #[test]
+  fn test_read_delimited() {
+    let mut stream: &[u8] = &[2, 0x08, 0x01, 0, 3, 0xff];
+    assert_that!(read_delimited(&mut stream).unwrap()).is_equal_to(Some(vec![0x08, 0x01]));
+    assert_that!(read_delimited(&mut stream).unwrap()).is_equal_to(Some(vec![]));
+    assert_that!(read_delimited(&mut stream)).is_err();
+    assert_that!(read_delimited(&mut stream).unwrap()).is_equal_to(None);
+  }
+}
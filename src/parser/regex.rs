@@ -8,9 +8,10 @@ use std::sync::Arc;
 use chrono::prelude::*;
 use serde_json::Value;
 
-use crate::config::{Config, RegexMapping};
-use super::types::{LogLevel, Message, MessageKind, ReaderMetadata};
-use super::util::normalize_datetime;
+use crate::config::{Config, RegexMapping, Zone};
+use super::timestamp::{localize, parse_timestamp};
+use super::types::{Message, MessageKind, ReaderMetadata};
+use super::util::{normalize_datetime, parse_level};
 
 #[cfg(test)] use spectral::prelude::*;
 
@@ -29,7 +30,7 @@ fn parse_rfc3339(s: &str) -> Option<DateTime<Utc>> {
 }
 
 fn parse_format(
-  s: &str, fmt: &str, prepend: &Option<String>
+  config: &Config, s: &str, fmt: &str, prepend: &Option<String>
 ) -> Option<DateTime<Utc>> {
   let datetime = if let Some(prepend) = prepend {
     format!(
@@ -41,20 +42,27 @@ fn parse_format(
     String::from(s)
   };
 
-  Utc.datetime_from_str(&datetime, fmt).ok()
+  if let Ok(dt) = DateTime::parse_from_str(&datetime, fmt) {
+    return Some(dt.with_timezone(&Utc));
+  }
+
+  NaiveDateTime::parse_from_str(&datetime, fmt)
+    .ok()
+    .and_then(|naive| localize(config, &naive, Zone::Utc))
 }
 
 fn parse_datetime(
-  fmt: &str, datetime: &str, prepend: &Option<String>
+  config: &Config, fmt: &str, datetime: &str, prepend: &Option<String>
 ) -> Option<DateTime<Utc>> {
   match fmt {
     "rfc2822" => parse_rfc2822(datetime),
     "rfc3339" => parse_rfc3339(datetime),
-    _ => parse_format(datetime, fmt, prepend)
+    _ => parse_format(config, datetime, fmt, prepend)
   }
 }
 
 fn parse_mapping(
+  config: &Config,
   line: &str, mapping: &RegexMapping, meta: &Option<ReaderMetadata>
 ) -> Result<Option<Message>, Box<dyn Error>> {
   let caps = match mapping.pattern.captures(line) {
@@ -67,13 +75,21 @@ fn parse_mapping(
   );
 
   let timestamp = if let Some(datetime) = caps.name("datetime") {
-    if let Some(format) = &mapping.datetime {
-      group_names.remove("datetime");
-
-      parse_datetime(&format, datetime.as_str(), &mapping.datetime_prepend)
+    let timestamp = if let Some(format) = &mapping.datetime {
+      parse_datetime(
+        config, &format, datetime.as_str(), &mapping.datetime_prepend
+      )
     } else {
-      None
+      // without an explicit format, fall back to auto-detection
+      parse_timestamp(config, datetime.as_str())
+    };
+
+    // keep undetectable datetimes around as metadata
+    if mapping.datetime.is_some() || timestamp.is_some() {
+      group_names.remove("datetime");
     }
+
+    timestamp
   } else {
     None
   };
@@ -89,10 +105,7 @@ fn parse_mapping(
   let level = if let Some(level) = caps.name("level") {
     group_names.remove("level");
 
-    match level.as_str().parse::<LogLevel>() {
-      Ok(l) => Some(l),
-      Err(_) => None
-    }
+    parse_level(config, level.as_str())
   } else {
     None
   };
@@ -127,7 +140,7 @@ pub fn parse_regex(
 ) -> Result<Option<Message>, Box<dyn Error>> {
   if let Some(regexes) = &config.regexes {
     for mapping in &regexes.mappings {
-      match parse_mapping(line, mapping, &meta) {
+      match parse_mapping(&config, line, mapping, &meta) {
         Ok(Some(message)) => return Ok(Some(message)),
         Ok(None) => continue,
         Err(e) => return Err(e)
@@ -145,6 +158,7 @@ mod tests {
   use regex::Regex;
   use serde_json::json;
   use simple_error::{SimpleResult, SimpleError};
+  use structopt::StructOpt;
 
   fn mapping(pattern: &str, datetime: &str) -> RegexMapping {
     RegexMapping {
@@ -157,7 +171,8 @@ mod tests {
   fn parse_to_value(
     line: &str, mapping: &RegexMapping, meta: &Option<ReaderMetadata>
   ) -> SimpleResult<Value> {
-    let parsed = parse_mapping(line, mapping, meta)
+    let config = Config::from_iter_safe(vec![""]).unwrap();
+    let parsed = parse_mapping(&config, line, mapping, meta)
       .map_err(|e| SimpleError::new(format!("{:?}", e)))?;
 
     serde_json::to_value(parsed).map_err(SimpleError::from)
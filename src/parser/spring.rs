@@ -0,0 +1,165 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::Arc;
+
+use chrono::prelude::*;
+use regex::Regex;
+use serde_json::Value;
+
+use crate::config::{Config, Zone};
+use super::timestamp::localize;
+use super::types::{Message, MessageKind, ReaderMetadata};
+use super::util::parse_level;
+
+/// Parses Spring Boot timestamps, with or without a UTC offset
+///
+/// Spring Boot 2.x logs in local time without any offset, while 3.x formats
+/// as ISO-8601 with an offset.
+fn parse_spring_datetime(config: &Config, s: &str) -> Option<DateTime<Utc>> {
+  if let Ok(dt) = DateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S%.f%:z") {
+    return Some(dt.with_timezone(&Utc));
+  }
+
+  let naive = NaiveDateTime::parse_from_str(
+    &s.replacen('T', " ", 1), "%Y-%m-%d %H:%M:%S%.f"
+  ).ok()?;
+
+  localize(config, &naive, Zone::Local)
+}
+
+/// Parses logs in Spring Boot's default console format, e.g.:
+///
+/// ```text
+/// 2023-05-01 12:00:00.123  INFO 1234 --- [  main] c.e.MyClass : message
+/// ```
+///
+/// Spring Boot 3.2+ also includes the application name in its own set of
+/// brackets before the thread name. Exceptions are logged on the following
+/// lines.
+pub fn parse_spring(
+  config: Arc<Config>, line: &str, meta: Option<ReaderMetadata>
+) -> Result<Option<Message>, Box<dyn Error>> {
+  lazy_static! {
+    static ref RE: Regex = Regex::new(concat!(
+      r"^(?P<datetime>\d{4}-\d{2}-\d{2}[ T]\d{2}:\d{2}:\d{2}\.\d+(?:Z|[+-]\d{2}:\d{2})?)\s+",
+      r"(?P<level>[A-Z]+)\s+(?P<pid>\d+) --- ",
+      r"(?:\[(?P<app>[^\]]*)\] )?\[\s*(?P<thread>[^\]]*)\] ",
+      r"(?P<logger>\S+)\s+: (?P<text>.*)$"
+    )).unwrap();
+  }
+
+  // skip anything that doesn't at least vaguely look like spring
+  if !line.contains(" --- ") {
+    return Ok(None);
+  }
+
+  let caps = match RE.captures(line) {
+    Some(caps) => caps,
+    None => return Ok(None)
+  };
+
+  let timestamp = caps.name("datetime")
+    .and_then(|d| parse_spring_datetime(&config, d.as_str()))
+    .or_else(|| meta.as_ref().and_then(|m| m.timestamp));
+
+  let level = caps.name("level").and_then(|l| parse_level(&config, l.as_str()));
+
+  let mut metadata = HashMap::new();
+  if let Some(pid) = caps.name("pid").and_then(|p| p.as_str().parse::<u64>().ok()) {
+    metadata.insert("pid".to_string(), Value::Number(pid.into()));
+  }
+
+  for name in &["app", "thread", "logger"] {
+    if let Some(value) = caps.name(name).map(|v| v.as_str().trim()) {
+      if !value.is_empty() {
+        metadata.insert(name.to_string(), Value::String(value.to_string()));
+      }
+    }
+  }
+
+  Ok(Some(Message {
+    kind: MessageKind::Spring,
+    reader_metadata: meta,
+    raw: line.to_string(),
+    text: caps.name("text").map(|t| t.as_str().trim_end().to_string()),
+    mapped_fields: HashMap::new(),
+
+    timestamp, level, metadata
+  }))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use serde_json::json;
+  use simple_error::{SimpleResult, SimpleError};
+  use spectral::prelude::*;
+  use structopt::StructOpt;
+
+  fn parse_to_value(line: &str) -> SimpleResult<Value> {
+    let config = Arc::new(Config::from_iter_safe(vec![""]).unwrap());
+    let parsed = parse_spring(config, line, None)
+      .map_err(|e| SimpleError::new(format!("{:?}", e)))?;
+
+    serde_json::to_value(parsed).map_err(SimpleError::from)
+  }
+
+  #[test]
+  fn test_spring_2() {
+    let line = concat!(
+      "2019-03-05 10:57:51.112  INFO 45469 --- [           main] ",
+      "org.apache.catalina.core.StandardEngine  : Starting Servlet Engine"
+    );
+
+    let naive = NaiveDateTime::parse_from_str(
+      "2019-03-05 10:57:51.112", "%Y-%m-%d %H:%M:%S%.f"
+    ).unwrap();
+    let expected_time = Local.from_local_datetime(&naive).unwrap()
+      .with_timezone(&Utc);
+
+    assert_that!(parse_to_value(line)).is_ok_containing(json!({
+      "kind": "spring",
+      "raw": line,
+      "timestamp": expected_time,
+      "level": "info",
+      "text": "Starting Servlet Engine",
+      "metadata": {
+        "pid": 45469,
+        "thread": "main",
+        "logger": "org.apache.catalina.core.StandardEngine"
+      }
+    }));
+  }
+
+  #[test]
+  fn test_spring_3() {
+    let line = concat!(
+      "2023-11-23T10:15:30.123+01:00  WARN 12345 --- [myapp] [nio-8080-exec-1] ",
+      "o.s.w.s.DispatcherServlet : Completed initialization"
+    );
+
+    assert_that!(parse_to_value(line)).is_ok_containing(json!({
+      "kind": "spring",
+      "raw": line,
+      "timestamp": "2023-11-23T09:15:30.123Z",
+      "level": "warning",
+      "text": "Completed initialization",
+      "metadata": {
+        "pid": 12345,
+        "app": "myapp",
+        "thread": "nio-8080-exec-1",
+        "logger": "o.s.w.s.DispatcherServlet"
+      }
+    }));
+  }
+
+  #[test]
+  fn test_not_spring() {
+    assert_that!(parse_to_value("hello --- world")).is_ok_containing(json!(null));
+    assert_that!(parse_to_value("2019-03-05 10:57:51.112 hello"))
+      .is_ok_containing(json!(null));
+  }
+}
@@ -0,0 +1,257 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+use chrono::prelude::*;
+use regex::Regex;
+
+use crate::config::{Config, Zone};
+use super::json::{parse_rfc2822, parse_rfc3339};
+
+/// Built-in formats that include a UTC offset
+static OFFSET_FORMATS: &[&str] = &[
+  "%d/%b/%Y:%H:%M:%S %z", // common log format, e.g. 10/Oct/2000:13:55:36 -0700
+  "%d/%b/%Y:%H:%M:%S%.f %z",
+  "%Y-%m-%d %H:%M:%S%.f %z",
+  "%Y-%m-%d %H:%M:%S%.f%:z",
+];
+
+/// Built-in formats without an offset
+static NAIVE_FORMATS: &[&str] = &[
+  "%Y-%m-%d %H:%M:%S%.f",
+  "%Y-%m-%dT%H:%M:%S%.f",
+  "%d/%b/%Y:%H:%M:%S",
+  "%d/%b/%Y %H:%M:%S",
+  "%d/%b/%Y:%H:%M:%S%.f",
+];
+
+/// Converts a naive timestamp to UTC, using the configured timezone if set, or
+/// `default` otherwise
+pub fn localize(
+  config: &Config, naive: &NaiveDateTime, default: Zone
+) -> Option<DateTime<Utc>> {
+  config.file().timestamps.timezone.unwrap_or(default).to_utc(naive)
+}
+
+/// Parses a numeric epoch timestamp, guessing between seconds, milliseconds,
+/// microseconds, and nanoseconds based on its magnitude
+///
+/// Anything between 1973 and 5138 (in seconds) is considered valid; smaller
+/// values are more likely to be durations or counters.
+pub fn parse_epoch(value: f64) -> Option<DateTime<Utc>> {
+  // units per second
+  let scale = if value < 1e8 {
+    return None;
+  } else if value < 1e11 {
+    1.0
+  } else if value < 1e14 {
+    1e3
+  } else if value < 1e17 {
+    1e6
+  } else {
+    1e9
+  };
+
+  // split off whole seconds first, as scaling up to nanoseconds loses precision
+  let seconds = (value / scale).floor();
+  let nanos = ((value - seconds * scale) * (1e9 / scale)).round().min(999_999_999.0);
+  if seconds >= i64::MAX as f64 {
+    return None;
+  }
+
+  Utc.timestamp_opt(seconds as i64, nanos as u32).single()
+}
+
+/// Determines if a format includes a year (or is a complete timestamp already)
+fn has_year(format: &str) -> bool {
+  ["%Y", "%y", "%G", "%g", "%C", "%s", "%+", "%c", "%D", "%F", "%x"].iter()
+    .any(|spec| format.contains(spec))
+}
+
+/// Parses a timestamp using a user-specified Chrono format string
+fn parse_custom(config: &Config, s: &str, format: &str) -> Option<DateTime<Utc>> {
+  let (s, format) = if has_year(format) {
+    (s.to_string(), format.to_string())
+  } else {
+    let year = config.file().timestamps.year.unwrap_or_else(|| Utc::now().year());
+    (format!("{} {}", year, s), format!("%Y {}", format))
+  };
+
+  if let Ok(dt) = DateTime::parse_from_str(&s, &format) {
+    return Some(dt.with_timezone(&Utc));
+  }
+
+  NaiveDateTime::parse_from_str(&s, &format)
+    .ok()
+    .and_then(|naive| localize(config, &naive, Zone::Utc))
+}
+
+/// Attempts to parse a timestamp string in any known format, including
+/// user-specified formats from the config file
+pub fn parse_timestamp(config: &Config, s: &str) -> Option<DateTime<Utc>> {
+  lazy_static! {
+    static ref EPOCH_RE: Regex = Regex::new(r"^\d{9,19}(?:\.\d+)?$").unwrap();
+  }
+
+  let s = s.trim();
+  if let Some(dt) = parse_rfc3339(s).or_else(|| parse_rfc2822(s)) {
+    return Some(dt);
+  }
+
+  for format in &config.file().timestamps.formats {
+    if let Some(dt) = parse_custom(config, s, format) {
+      return Some(dt);
+    }
+  }
+
+  if EPOCH_RE.is_match(s) {
+    return s.parse::<f64>().ok().and_then(parse_epoch);
+  }
+
+  for format in OFFSET_FORMATS {
+    if let Ok(dt) = DateTime::parse_from_str(s, format) {
+      return Some(dt.with_timezone(&Utc));
+    }
+  }
+
+  for format in NAIVE_FORMATS {
+    if let Ok(naive) = NaiveDateTime::parse_from_str(s, format) {
+      return localize(config, &naive, Zone::Utc);
+    }
+  }
+
+  None
+}
+
+/// Searches a plaintext line for a timestamp, returning it and the byte range
+/// it occupied
+///
+/// Timestamps are only detected at the start of a line, or in brackets (as in
+/// the common log format).
+pub fn find_timestamp(
+  config: &Config, line: &str
+) -> Option<(DateTime<Utc>, usize, usize)> {
+  lazy_static! {
+    static ref LEADING_RE: Regex = Regex::new(concat!(
+      r"^\[?(\d{4}-\d{2}-\d{2}[T ]\d{2}:\d{2}:\d{2}(?:[.,]\d+)?",
+      r"(?:Z| ?[+-]\d{2}:?\d{2})?)\]?"
+    )).unwrap();
+
+    static ref BRACKETED_RE: Regex = Regex::new(
+      r"\[(\d{2}/\w{3}/\d{4}[: ]\d{2}:\d{2}:\d{2}(?:\.\d+)?(?: [+-]\d{4})?)\]"
+    ).unwrap();
+  }
+
+  for re in &[&*LEADING_RE, &*BRACKETED_RE] {
+    if let Some(caps) = re.captures(line) {
+      let whole = caps.get(0).unwrap();
+      let datetime = caps[1].replacen(',', ".", 1);
+
+      if let Some(dt) = parse_timestamp(config, &datetime) {
+        return Some((dt, whole.start(), whole.end()));
+      }
+    }
+  }
+
+  // user formats may contain spaces, so try progressively shorter prefixes
+  let formats = &config.file().timestamps.formats;
+  if !formats.is_empty() {
+    let ends: Vec<usize> = line.match_indices(' ')
+      .map(|(i, _)| i)
+      .chain(std::iter::once(line.len()))
+      .take(6)
+      .collect();
+
+    for end in ends.into_iter().rev() {
+      for format in formats {
+        if let Some(dt) = parse_custom(config, &line[..end], format) {
+          return Some((dt, 0, end));
+        }
+      }
+    }
+  }
+
+  None
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use spectral::prelude::*;
+  use structopt::StructOpt;
+
+  use crate::config::FileConfig;
+
+  fn config(yaml: &str) -> Config {
+    let mut config = Config::from_iter_safe(vec![""]).unwrap();
+    config.config_file = Some(serde_yaml::from_str::<FileConfig>(yaml).unwrap());
+    config
+  }
+
+  fn utc(s: &str) -> Option<DateTime<Utc>> {
+    Some(s.parse::<DateTime<Utc>>().unwrap())
+  }
+
+  #[test]
+  fn test_epoch() {
+    let expected = utc("2020-05-01T12:00:00Z");
+
+    assert_that!(parse_epoch(1588334400.0)).is_equal_to(expected);
+    assert_that!(parse_epoch(1588334400000.0)).is_equal_to(expected);
+    assert_that!(parse_epoch(1588334400000000.0)).is_equal_to(expected);
+    assert_that!(parse_epoch(1588334400000000000.0)).is_equal_to(expected);
+    assert_that!(parse_epoch(1234.0)).is_none();
+
+    let config = config("{}");
+    assert_that!(parse_timestamp(&config, "1588334400.5"))
+      .is_equal_to(utc("2020-05-01T12:00:00.5Z"));
+  }
+
+  #[test]
+  fn test_builtin_formats() {
+    let config = config("{}");
+
+    assert_that!(parse_timestamp(&config, "10/Oct/2000:13:55:36 -0700"))
+      .is_equal_to(utc("2000-10-10T20:55:36Z"));
+    assert_that!(parse_timestamp(&config, "2020-05-01 12:00:00.25"))
+      .is_equal_to(utc("2020-05-01T12:00:00.25Z"));
+    assert_that!(parse_timestamp(&config, "yesterday")).is_none();
+  }
+
+  #[test]
+  fn test_custom_formats() {
+    let config = config(concat!(
+      "timestamps:\n",
+      "  formats: ['%d.%m.%Y %H:%M:%S', '%b %e %H:%M:%S']\n",
+      "  timezone: '+02:00'\n",
+      "  year: 2019\n"
+    ));
+
+    assert_that!(parse_timestamp(&config, "01.05.2020 12:00:00"))
+      .is_equal_to(utc("2020-05-01T10:00:00Z"));
+    assert_that!(parse_timestamp(&config, "May  1 12:00:00"))
+      .is_equal_to(utc("2019-05-01T10:00:00Z"));
+
+    // the configured timezone also applies to built-in naive formats
+    assert_that!(parse_timestamp(&config, "2020-05-01 12:00:00"))
+      .is_equal_to(utc("2020-05-01T10:00:00Z"));
+  }
+
+  #[test]
+  fn test_find_timestamp() {
+    let config = config("timestamps: {formats: ['%b %e %H:%M:%S']}");
+
+    let line = "2020-05-01 12:00:00,123 hello";
+    assert_that!(find_timestamp(&config, line))
+      .is_equal_to(Some((utc("2020-05-01T12:00:00.123Z").unwrap(), 0, 23)));
+
+    let line = r#"127.0.0.1 - - [10/Oct/2000:13:55:36 -0700] "GET / HTTP/1.0" 200"#;
+    assert_that!(find_timestamp(&config, line))
+      .is_equal_to(Some((utc("2000-10-10T20:55:36Z").unwrap(), 14, 42)));
+
+    let line = "May  1 12:00:00 myhost sshd[123]: hello";
+    let (_, start, end) = find_timestamp(&config, line).unwrap();
+    assert_that!(line[start..end].to_string()).is_equal_to("May  1 12:00:00".to_string());
+
+    assert_that!(find_timestamp(&config, "no timestamp here")).is_none();
+  }
+}
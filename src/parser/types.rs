@@ -21,6 +21,9 @@ pub type Parser = fn(
   config: Arc<Config>, line: &str, meta: Option<ReaderMetadata>
 ) -> Result<Option<Message>, Box<dyn Error>>;
 
+/// Adds to or adjusts a parsed message, e.g. to lift fields out of its text
+pub type Enricher = fn(config: &Config, message: &mut Message);
+
 #[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum MessageKind {
@@ -28,6 +31,15 @@ pub enum MessageKind {
   Plain,
   Logrus,
   Klog,
+  Envoy,
+  Spring,
+  Dotnet,
+  Zap,
+  Bunyan,
+  Pino,
+  Otel,
+  Gc,
+  Auditd,
   Regex,
   Internal
 }
@@ -61,7 +73,7 @@ impl FromStr for LogLevel {
 
   fn from_str(s: &str) -> Result<LogLevel, ()> {
     match s.to_lowercase().as_str() {
-      "debug" | "dbg" | "d" => Ok(LogLevel::Debug),
+      "trace" | "debug" | "dbg" | "d" => Ok(LogLevel::Debug),
       "info" | "i" => Ok(LogLevel::Info),
       "warning" | "warn" | "w" => Ok(LogLevel::Warning),
       "error" | "err" | "e" => Ok(LogLevel::Error),
@@ -115,7 +127,7 @@ pub struct Message {
   pub text: Option<String>,
 
   /// Additional fields e.g. in json messages
-  #[serde(skip_serializing_if = "is_empty")]
+  #[serde(default, skip_serializing_if = "is_empty")]
   pub metadata: HashMap<String, Value>,
 
   /// Metadata from readers (filename, k8s pod, external timestamp, etc)
@@ -123,6 +135,39 @@ pub struct Message {
   pub reader_metadata: Option<ReaderMetadata>,
 
   /// Mappings of original field names to their destination fields
-  #[serde(skip_serializing_if = "is_empty")]
+  #[serde(default, skip_serializing_if = "is_empty")]
   pub mapped_fields: HashMap<String, MappingField>
 }
+
+impl Message {
+  /// Looks up a value by name, e.g. for selecting output columns
+  ///
+  /// `timestamp`, `level`, `message` (or `text`), `kind`, `source` and `raw`
+  /// refer to the message itself; other names are metadata fields. Nested
+  /// fields may be selected with dots, e.g. `http.status`.
+  pub fn field(&self, name: &str) -> Option<Value> {
+    match name {
+      "timestamp" => self.timestamp.map(|t| Value::String(t.to_rfc3339())),
+      "level" => self.level.map(|l| Value::String(l.to_string().to_lowercase())),
+      "message" | "text" => self.text.clone().map(Value::String),
+      "kind" => Some(Value::String(self.kind.to_string().to_lowercase())),
+      "source" => self.reader_metadata.as_ref()
+        .and_then(|m| m.source.clone())
+        .map(Value::String),
+      "raw" => Some(Value::String(self.raw.clone())),
+      _ => {
+        if let Some(value) = self.metadata.get(name) {
+          return Some(value.clone());
+        }
+
+        let mut parts = name.split('.');
+        let mut value = self.metadata.get(parts.next()?)?;
+        for part in parts {
+          value = value.get(part)?;
+        }
+
+        Some(value.clone())
+      }
+    }
+  }
+}
@@ -0,0 +1,181 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+use regex::Regex;
+use serde_json::{Number, Value};
+
+use crate::config::Config;
+use super::types::Message;
+
+/// Parses a duration like `12ms`, `1.5s`, or `2m30s` into milliseconds
+pub fn parse_duration_ms(s: &str) -> Option<f64> {
+  lazy_static! {
+    static ref FULL_RE: Regex = Regex::new(
+      r"^(?:\d+(?:\.\d+)?(?:ns|us|µs|ms|s|m|h|d))+$"
+    ).unwrap();
+    static ref PART_RE: Regex = Regex::new(
+      r"(\d+(?:\.\d+)?)(ns|us|µs|ms|s|m|h|d)"
+    ).unwrap();
+  }
+
+  if !FULL_RE.is_match(s) {
+    return None;
+  }
+
+  let mut total = 0.0;
+  for caps in PART_RE.captures_iter(s) {
+    let value: f64 = caps[1].parse().ok()?;
+    let scale = match &caps[2] {
+      "ns" => 1e-6,
+      "us" | "µs" => 1e-3,
+      "ms" => 1.0,
+      "s" => 1e3,
+      "m" => 60e3,
+      "h" => 3600e3,
+      _ => 86400e3
+    };
+
+    total += value * scale;
+  }
+
+  Some(total)
+}
+
+/// Parses a size like `512KiB` or `1.5 MB` into bytes; SI units are powers of
+/// 1000, IEC units (and bare `K`, `M`, etc) are powers of 1024
+pub fn parse_size_bytes(s: &str) -> Option<f64> {
+  lazy_static! {
+    static ref RE: Regex = Regex::new(
+      r"^(\d+(?:\.\d+)?) ?([KMGTP]i?B|[KMGTP]|B)$"
+    ).unwrap();
+  }
+
+  let caps = RE.captures(s)?;
+  let value: f64 = caps[1].parse().ok()?;
+  let unit = &caps[2];
+
+  let power = match unit.chars().next()? {
+    'B' => 0,
+    'K' => 1,
+    'M' => 2,
+    'G' => 3,
+    'T' => 4,
+    _ => 5
+  };
+
+  let base: f64 = if unit.len() == 2 { 1000.0 } else { 1024.0 };
+  Some(value * base.powi(power))
+}
+
+/// Converts a number to JSON, as an integer if it has no fractional part
+pub fn to_number(value: f64) -> Option<Value> {
+  if value.fract() == 0.0 && value.abs() < 9e15 {
+    Some(Value::Number((value as i64).into()))
+  } else {
+    Number::from_f64(value).map(Value::Number)
+  }
+}
+
+/// Returns `key` with `suffix` appended, unless it's already there
+fn with_suffix(key: &str, suffix: &str) -> String {
+  if key.ends_with(suffix) {
+    key.to_string()
+  } else {
+    format!("{}{}", key, suffix)
+  }
+}
+
+/// Replaces duration and size fields, e.g. `dur: "1.5s"`, with numeric fields
+/// in canonical units, e.g. `dur_ms: 1500`
+///
+/// Only runs if `--normalize-units` is set.
+pub fn normalize_units(config: &Config, message: &mut Message) {
+  if !config.normalize_units {
+    return;
+  }
+
+  let mut normalized = Vec::new();
+  for (key, value) in &message.metadata {
+    let s = match value {
+      Value::String(s) => s,
+      _ => continue
+    };
+
+    let field = if let Some(ms) = parse_duration_ms(s) {
+      to_number(ms).map(|v| (with_suffix(key, "_ms"), v))
+    } else if let Some(bytes) = parse_size_bytes(s) {
+      to_number(bytes.round()).map(|v| (with_suffix(key, "_bytes"), v))
+    } else {
+      None
+    };
+
+    if let Some(field) = field {
+      normalized.push((key.clone(), field));
+    }
+  }
+
+  for (old_key, (new_key, value)) in normalized {
+    message.metadata.remove(&old_key);
+    message.metadata.insert(new_key, value);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::collections::HashMap;
+
+  use serde_json::json;
+  use spectral::prelude::*;
+  use structopt::StructOpt;
+
+  use crate::parser::MessageKind;
+
+  #[test]
+  fn test_durations() {
+    assert_that!(parse_duration_ms("12ms")).is_equal_to(Some(12.0));
+    assert_that!(parse_duration_ms("1.5s")).is_equal_to(Some(1500.0));
+    assert_that!(parse_duration_ms("2m30s")).is_equal_to(Some(150000.0));
+    assert_that!(parse_duration_ms("250us")).is_equal_to(Some(0.25));
+    assert_that!(parse_duration_ms("12")).is_none();
+    assert_that!(parse_duration_ms("ms")).is_none();
+    assert_that!(parse_duration_ms("10 messages")).is_none();
+  }
+
+  #[test]
+  fn test_sizes() {
+    assert_that!(parse_size_bytes("512KiB")).is_equal_to(Some(524288.0));
+    assert_that!(parse_size_bytes("1.5 MB")).is_equal_to(Some(1500000.0));
+    assert_that!(parse_size_bytes("2G")).is_equal_to(Some(2147483648.0));
+    assert_that!(parse_size_bytes("100B")).is_equal_to(Some(100.0));
+    assert_that!(parse_size_bytes("KB")).is_none();
+  }
+
+  #[test]
+  fn test_normalize() {
+    let config = Config::from_iter_safe(vec!["", "--normalize-units"]).unwrap();
+    let mut message = Message {
+      kind: MessageKind::Json,
+      timestamp: None,
+      level: None,
+      raw: String::new(),
+      text: None,
+      metadata: serde_json::from_value(json!({
+        "dur": "1.5s",
+        "latency_ms": "12ms",
+        "size": "1KiB",
+        "name": "10s-timer"
+      })).unwrap(),
+      reader_metadata: None,
+      mapped_fields: HashMap::new()
+    };
+
+    normalize_units(&config, &mut message);
+    assert_that!(serde_json::to_value(message.metadata).unwrap()).is_equal_to(json!({
+      "dur_ms": 1500,
+      "latency_ms": 12,
+      "size_bytes": 1024,
+      "name": "10s-timer"
+    }));
+  }
+}
@@ -1,6 +1,17 @@
 // (C) Copyright 2019 Hewlett Packard Enterprise Development LP
 
+use std::borrow::Cow;
+
 use chrono::prelude::*;
+use regex::Regex;
+
+use crate::config::Config;
+use super::types::LogLevel;
+
+/// Parses a log level name, checking user-defined synonyms first
+pub fn parse_level(config: &Config, s: &str) -> Option<LogLevel> {
+  config.file().levels.get(s).or_else(|| s.parse().ok())
+}
 
 /// Convert a datetime to UTC if an offset is available
 pub fn normalize_datetime(
@@ -15,3 +26,55 @@ pub fn normalize_datetime(
   // if we can't convert, just assume utc
   Utc.from_utc_datetime(datetime)
 }
+
+/// Removes ANSI escape sequences (colors, cursor movement, etc) from a string
+pub fn strip_ansi(s: &str) -> Cow<'_, str> {
+  lazy_static! {
+    static ref RE: Regex = Regex::new(
+      r"\x1b(?:\[[0-9;?]*[ -/]*[@-~]|\][^\x07\x1b]*(?:\x07|\x1b\\)|[@-Z\\-_])"
+    ).unwrap();
+  }
+
+  if s.contains('\x1b') {
+    RE.replace_all(s, "")
+  } else {
+    Cow::Borrowed(s)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use spectral::prelude::*;
+  use structopt::StructOpt;
+
+  use crate::config::FileConfig;
+
+  #[test]
+  fn test_parse_level() {
+    let mut config = Config::from_iter_safe(vec![""]).unwrap();
+    config.config_file = Some(serde_yaml::from_str::<FileConfig>(
+      "levels: {warning: [WRN, W, 30], info: [notice]}"
+    ).unwrap());
+
+    assert_that!(parse_level(&config, "wrn")).is_equal_to(Some(LogLevel::Warning));
+    assert_that!(parse_level(&config, "30")).is_equal_to(Some(LogLevel::Warning));
+    assert_that!(parse_level(&config, "NOTICE")).is_equal_to(Some(LogLevel::Info));
+    assert_that!(parse_level(&config, "error")).is_equal_to(Some(LogLevel::Error));
+    assert_that!(parse_level(&config, "bogus")).is_equal_to(None);
+
+    let levels = &config.file().levels;
+    assert_that!(levels.find("12:00 WRN disk nearly full")).is_equal_to(Some(LogLevel::Warning));
+    assert_that!(levels.find("WRNG not a synonym")).is_equal_to(None);
+  }
+
+  #[test]
+  fn test_strip_ansi() {
+    assert_that!(strip_ansi("\x1b[1;31mERROR\x1b[0m oops").to_string())
+      .is_equal_to("ERROR oops".to_string());
+    assert_that!(strip_ansi("\x1b]8;;http://example.com\x1b\\link\x1b]8;;\x1b\\").to_string())
+      .is_equal_to("link".to_string());
+    assert_that!(strip_ansi("\x1b[2Kplain").to_string()).is_equal_to("plain".to_string());
+  }
+}
@@ -0,0 +1,187 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::sync::Arc;
+
+use chrono::prelude::*;
+use regex::Regex;
+use serde_json::{Map, Value};
+
+use crate::config::Config;
+use super::json::parse_rfc3339;
+use super::types::{LogLevel, Message, MessageKind, ReaderMetadata};
+
+fn map_zap_level(config: &Config, level: &str) -> Option<LogLevel> {
+  config.file().levels.get(level).or_else(|| match level.to_lowercase().as_str() {
+    "dpanic" => Some(LogLevel::Error),
+    "debug" | "info" | "warn" | "error" | "panic" | "fatal" => level.parse().ok(),
+    _ => None
+  })
+}
+
+/// Parses zap's `ISO8601TimeEncoder` format, which omits the colon in the UTC
+/// offset, falling back to RFC 3339
+fn parse_zap_datetime(s: &str) -> Option<DateTime<Utc>> {
+  DateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S%.f%z")
+    .ok()
+    .map(|dt| dt.with_timezone(&Utc))
+    .or_else(|| parse_rfc3339(s))
+}
+
+/// Determines if a field looks like a zap caller, e.g. `server/main.go:42`
+fn is_caller(s: &str) -> bool {
+  lazy_static! {
+    static ref RE: Regex = Regex::new(r"^\S+:\d+$").unwrap();
+  }
+
+  RE.is_match(s)
+}
+
+/// Parses zap's console encoding, e.g.:
+///
+/// ```text
+/// 2020-05-01T12:00:00.123Z  INFO  server/main.go:42  listening  {"port": 8080}
+/// ```
+///
+/// Fields are tab-separated: timestamp, level, an optional logger name, an
+/// optional caller, the message, and finally any context fields as JSON.
+pub fn parse_zap(
+  config: Arc<Config>, line: &str, meta: Option<ReaderMetadata>
+) -> Result<Option<Message>, Box<dyn Error>> {
+  let parts: Vec<&str> = line.split('\t').collect();
+  if parts.len() < 3 {
+    return Ok(None);
+  }
+
+  let timestamp = match parse_zap_datetime(parts[0]) {
+    Some(timestamp) => timestamp,
+    None => return Ok(None)
+  };
+
+  let level = match map_zap_level(&config, parts[1]) {
+    Some(level) => level,
+    None => return Ok(None)
+  };
+
+  let mut rest = &parts[2..];
+  let mut metadata = HashMap::new();
+
+  // context fields are encoded as a trailing json object
+  if rest.len() > 1 {
+    let last = rest[rest.len() - 1];
+    if last.starts_with('{') && last.ends_with('}') {
+      if let Ok(fields) = serde_json::from_str::<Map<String, Value>>(last) {
+        metadata.extend(fields);
+        rest = &rest[..rest.len() - 1];
+      }
+    }
+  }
+
+  // the message is now last, anything before it is the logger and/or caller
+  let (text, names) = match rest.split_last() {
+    Some((text, names)) => (text, names),
+    None => return Ok(None)
+  };
+
+  match names {
+    [] => (),
+    [caller] if is_caller(caller) => {
+      metadata.insert("caller".to_string(), Value::String(caller.to_string()));
+    },
+    [logger] => {
+      metadata.insert("logger".to_string(), Value::String(logger.to_string()));
+    },
+    [logger, caller, ..] => {
+      metadata.insert("logger".to_string(), Value::String(logger.to_string()));
+      metadata.insert("caller".to_string(), Value::String(caller.to_string()));
+    }
+  }
+
+  Ok(Some(Message {
+    kind: MessageKind::Zap,
+    raw: line.to_string(),
+    timestamp: Some(timestamp),
+    level: Some(level),
+    text: Some(text.trim().to_string()).filter(|t| !t.is_empty()),
+    reader_metadata: meta,
+    mapped_fields: HashMap::new(),
+    metadata
+  }))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use serde_json::json;
+  use simple_error::{SimpleResult, SimpleError};
+  use spectral::prelude::*;
+  use structopt::StructOpt;
+
+  use crate::config::FileConfig;
+
+  fn parse_to_value(line: &str) -> SimpleResult<Value> {
+    let config = Arc::new(Config::from_iter_safe(vec![""]).unwrap());
+    let parsed = parse_zap(config, line, None)
+      .map_err(|e| SimpleError::new(format!("{:?}", e)))?;
+
+    serde_json::to_value(parsed).map_err(SimpleError::from)
+  }
+
+  #[test]
+  fn test_zap_fields() {
+    let line = concat!(
+      "2020-05-01T12:00:00.123+0200\tINFO\tserver/main.go:42\tlistening\t",
+      r#"{"port": 8080, "tls": false}"#
+    );
+
+    assert_that!(parse_to_value(line)).is_ok_containing(json!({
+      "kind": "zap",
+      "raw": line,
+      "timestamp": "2020-05-01T10:00:00.123Z",
+      "level": "info",
+      "text": "listening",
+      "metadata": {
+        "caller": "server/main.go:42",
+        "port": 8080,
+        "tls": false
+      }
+    }));
+  }
+
+  #[test]
+  fn test_zap_logger() {
+    let line = "2020-05-01T12:00:00.123Z\tdpanic\tstore\tstore/db.go:7\tconnection lost";
+
+    assert_that!(parse_to_value(line)).is_ok_containing(json!({
+      "kind": "zap",
+      "raw": line,
+      "timestamp": "2020-05-01T12:00:00.123Z",
+      "level": "error",
+      "text": "connection lost",
+      "metadata": {
+        "logger": "store",
+        "caller": "store/db.go:7"
+      }
+    }));
+  }
+
+  #[test]
+  fn test_not_zap() {
+    assert_that!(parse_to_value("hello\tworld\tfoo")).is_ok_containing(json!(null));
+    assert_that!(parse_to_value("2020-05-01T12:00:00Z\tNOPE\tfoo"))
+      .is_ok_containing(json!(null));
+  }
+
+  #[test]
+  fn test_level_synonyms() {
+    let mut config = Config::from_iter_safe(vec![""]).unwrap();
+    config.config_file = Some(serde_yaml::from_str::<FileConfig>(
+      "levels: {fatal: [dpanic]}"
+    ).unwrap());
+
+    assert_that!(map_zap_level(&config, "dpanic")).is_equal_to(Some(LogLevel::Fatal));
+    assert_that!(map_zap_level(&config, "warn")).is_equal_to(Some(LogLevel::Warning));
+  }
+}
@@ -0,0 +1,271 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Arc;
+use std::sync::mpsc::{Receiver, Sender};
+use std::thread::{self, JoinHandle};
+
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use simple_error::SimpleResult;
+
+use crate::classifier::classify;
+use crate::config::Config;
+use crate::parser::{LogLevel, Message, MessageKind};
+use crate::renderer::{LogEntry, MessageEntry};
+
+/// The length of each bucket sources are counted in, in seconds
+static BUCKET_SECONDS: i64 = 10;
+
+/// The number of past buckets making up a source's baseline
+static BASELINE_BUCKETS: usize = 6;
+
+/// The number of past buckets needed before a source is judged at all
+static WARMUP_BUCKETS: usize = 3;
+
+/// A bucket is a volume anomaly at this many times its baseline...
+static VOLUME_FACTOR: f64 = 3.0;
+
+/// ... and at least this many messages
+static MIN_VOLUME: usize = 20;
+
+/// A bucket is an error anomaly with at least this many errors...
+static MIN_ERRORS: usize = 5;
+
+/// ... making up at least this much more of its messages than its baseline
+static ERROR_RATIO_INCREASE: f64 = 0.2;
+
+/// The metadata field marking anomaly records, holding their source
+pub static ANOMALY_FIELD: &str = "anomaly";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct Bucket {
+  count: usize,
+  errors: usize
+}
+
+/// A source's counts: its current bucket, and those before it
+#[derive(Debug, Default)]
+struct SourceHistory {
+  /// The start of the current bucket, in seconds
+  start: i64,
+  current: Bucket,
+  past: VecDeque<Bucket>,
+
+  /// If set, anomalies already reported for the current bucket
+  volume_flagged: bool,
+  errors_flagged: bool
+}
+
+impl SourceHistory {
+  /// Moves on to the bucket starting at `start`, counting any skipped buckets
+  /// as empty
+  fn advance(&mut self, start: i64) {
+    let skipped = ((start - self.start) / BUCKET_SECONDS).max(0) as usize;
+    for i in 0..skipped.min(BASELINE_BUCKETS) {
+      let bucket = if i == 0 { self.current } else { Bucket::default() };
+      self.past.push_back(bucket);
+      if self.past.len() > BASELINE_BUCKETS {
+        self.past.pop_front();
+      }
+    }
+
+    self.start = start;
+    self.current = Bucket::default();
+    self.volume_flagged = false;
+    self.errors_flagged = false;
+  }
+
+  /// The mean count and error ratio of past buckets
+  fn baseline(&self) -> (f64, f64) {
+    let count: usize = self.past.iter().map(|b| b.count).sum();
+    let errors: usize = self.past.iter().map(|b| b.errors).sum();
+    let ratio = if count == 0 { 0.0 } else { errors as f64 / count as f64 };
+
+    (count as f64 / self.past.len() as f64, ratio)
+  }
+}
+
+/// Tracks message rates and error ratios per source, spotting buckets that
+/// deviate sharply from their source's recent baseline
+#[derive(Default)]
+struct Detector {
+  sources: HashMap<Option<String>, SourceHistory>
+}
+
+impl Detector {
+  /// Counts a message at `timestamp`, returning a description of any anomaly
+  /// it completes
+  fn observe(&mut self, message: &Message, timestamp: DateTime<Utc>) -> Vec<String> {
+    let source = message.reader_metadata.as_ref().and_then(|m| m.source.clone());
+    let name = source.clone().unwrap_or_else(|| "input".to_string());
+    let start = timestamp.timestamp().div_euclid(BUCKET_SECONDS) * BUCKET_SECONDS;
+
+    let history = self.sources.entry(source).or_insert_with(|| SourceHistory {
+      start,
+      ..Default::default()
+    });
+
+    // messages slightly out of order still count towards the current bucket
+    if start > history.start {
+      history.advance(start);
+    }
+
+    history.current.count += 1;
+    if matches!(message.level, Some(LogLevel::Error) | Some(LogLevel::Fatal)) {
+      history.current.errors += 1;
+    }
+
+    if history.past.len() < WARMUP_BUCKETS {
+      return Vec::new();
+    }
+
+    let (rate, ratio) = history.baseline();
+    let current = history.current;
+    let mut anomalies = Vec::new();
+
+    let volume_threshold = (rate * VOLUME_FACTOR).max(MIN_VOLUME as f64);
+    if !history.volume_flagged && current.count as f64 >= volume_threshold {
+      history.volume_flagged = true;
+      anomalies.push(format!(
+        "anomaly: {} is logging {:.1}x its recent rate ({} messages in {}s, usually ~{:.0})",
+        name, current.count as f64 / rate.max(1.0), current.count, BUCKET_SECONDS, rate
+      ));
+    }
+
+    let current_ratio = current.errors as f64 / current.count as f64;
+    if !history.errors_flagged
+      && current.errors >= MIN_ERRORS
+      && current_ratio >= ratio + ERROR_RATIO_INCREASE
+    {
+      history.errors_flagged = true;
+      anomalies.push(format!(
+        "anomaly: {} errors are up to {:.0}% of messages ({} of {} in {}s, usually {:.0}%)",
+        name, current_ratio * 100.0, current.errors, current.count, BUCKET_SECONDS,
+        ratio * 100.0
+      ));
+    }
+
+    anomalies
+  }
+}
+
+/// An internal message reporting an anomaly at a time, marked so the
+/// interactive histogram can highlight it
+fn anomaly_entry(text: &str, source: Option<&String>, timestamp: DateTime<Utc>) -> LogEntry {
+  let mut entry = MessageEntry::internal(text);
+  entry.message.timestamp = Some(timestamp);
+  entry.message.metadata.insert(
+    ANOMALY_FIELD.to_string(),
+    source.map_or(Value::Bool(true), |s| Value::String(s.clone()))
+  );
+  entry.chunks = classify(&entry.message);
+
+  LogEntry { message: Some(entry), ..Default::default() }
+}
+
+/// If true, a message is an anomaly record from `--anomalies`
+pub fn is_anomaly(message: &Message) -> bool {
+  message.kind == MessageKind::Internal && message.metadata.contains_key(ANOMALY_FIELD)
+}
+
+/// A wrapping reader that passes every entry along, adding internal
+/// "anomaly" messages when a source's volume or error rate spikes
+pub fn read_anomaly(
+  _config: Arc<Config>,
+  rx: Receiver<LogEntry>,
+  tx: Sender<LogEntry>,
+) -> JoinHandle<SimpleResult<()>> {
+  thread::Builder::new().name("read_anomaly".to_string()).spawn(move || {
+    let mut detector = Detector::default();
+
+    for entry in rx {
+      let mut anomalies = Vec::new();
+      if let Some(message) = &entry.message {
+        let message = &message.message;
+        if message.kind != MessageKind::Internal {
+          // replayed logs are judged by when they happened, not when read
+          let timestamp = message.timestamp.unwrap_or_else(Utc::now);
+          let source = message.reader_metadata.as_ref().and_then(|m| m.source.as_ref());
+
+          for text in detector.observe(message, timestamp) {
+            anomalies.push(anomaly_entry(&text, source, timestamp));
+          }
+        }
+      }
+
+      if tx.send(entry).is_err() {
+        break;
+      }
+
+      for anomaly in anomalies {
+        tx.send(anomaly).ok();
+      }
+    }
+
+    Ok(())
+  }).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use chrono::TimeZone;
+  use spectral::prelude::*;
+  use structopt::StructOpt;
+
+  use crate::parser::ReaderMetadata;
+
+  #[test]
+  fn test_detector() {
+    let config = Arc::new(Config::from_iter_safe(vec![""]).unwrap());
+    let message = |level: &str, source: &str| {
+      let line = format!(r#"{{"level": "{}", "msg": "request"}}"#, level);
+      let meta = ReaderMetadata { timestamp: None, source: Some(source.to_string()) };
+      LogEntry::message(Arc::clone(&config), &line, Some(meta)).unwrap().unwrap()
+        .message.unwrap().message
+    };
+
+    let info = message("info", "api");
+    let error = message("error", "api");
+    let other = message("info", "db");
+    let at = |seconds: i64| Utc.timestamp_opt(1_600_000_000 + seconds, 0).unwrap();
+
+    let mut detector = Detector::default();
+    let mut anomalies = Vec::new();
+
+    // a steady baseline of 10 messages per bucket, with 1 error
+    for bucket in 0..4 {
+      for i in 0..10 {
+        let message = if i == 0 { &error } else { &info };
+        anomalies.extend(detector.observe(message, at(bucket * 10 + i)));
+        anomalies.extend(detector.observe(&other, at(bucket * 10 + i)));
+      }
+    }
+    assert_that!(anomalies).is_empty();
+
+    // then a burst of errors from the api only
+    for i in 0..30 {
+      let message = if i % 2 == 0 { &error } else { &info };
+      anomalies.extend(detector.observe(message, at(40 + i / 10)));
+      if i % 3 == 0 {
+        anomalies.extend(detector.observe(&other, at(40 + i / 10)));
+      }
+    }
+
+    assert_that!(anomalies).is_equal_to(vec![
+      "anomaly: api errors are up to 56% of messages (5 of 9 in 10s, usually 10%)".to_string(),
+      "anomaly: api is logging 3.0x its recent rate (30 messages in 10s, usually ~10)".to_string()
+    ]);
+
+    // each is reported once per bucket
+    for i in 0..30 {
+      assert_that!(detector.observe(&error, at(45 + i / 10))).is_empty();
+    }
+
+    let entry = anomaly_entry("anomaly", Some(&"api".to_string()), at(40));
+    assert_that!(is_anomaly(&entry.message.unwrap().message)).is_true();
+    assert_that!(is_anomaly(&info)).is_false();
+  }
+}
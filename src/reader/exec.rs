@@ -0,0 +1,424 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, Sender, Receiver, RecvTimeoutError};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use simple_error::{SimpleError, SimpleResult};
+use subprocess::{ExitStatus, Popen, PopenConfig, Redirection};
+
+use crate::config::Config;
+use crate::renderer::{LogEntry, SourceState};
+use crate::parser::ReaderMetadata;
+
+/// Signals that can be sent to a subprocess by name, limited to those with
+/// the same number on Linux and macOS
+pub static SIGNALS: &[(&str, i32)] = &[
+  ("HUP", 1),
+  ("INT", 2),
+  ("QUIT", 3),
+  ("KILL", 9),
+  ("TERM", 15)
+];
+
+/// Parses a signal name or number, e.g. `HUP`, `SIGHUP`, or `1`
+pub fn parse_signal(s: &str) -> SimpleResult<i32> {
+  if let Ok(number) = s.parse::<i32>() {
+    return Ok(number);
+  }
+
+  let upper = s.to_uppercase();
+  let name = upper.trim_start_matches("SIG");
+  match SIGNALS.iter().find(|(n, _)| *n == name) {
+    Some((_, number)) => Ok(*number),
+    None => bail!(
+      "invalid signal: {} (expected one of: {})",
+      s, SIGNALS.iter().map(|(n, _)| *n).collect::<Vec<&str>>().join(", ")
+    )
+  }
+}
+
+/// A signal's name, e.g. `SIGTERM`, or its number if it has no known name
+pub fn signal_name(number: i32) -> String {
+  match SIGNALS.iter().find(|(_, n)| *n == number) {
+    Some((name, _)) => format!("SIG{}", name),
+    None => format!("signal {}", number)
+  }
+}
+
+/// A request to change a managed subprocess
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Control {
+  /// Stops the process if it's running, then starts it again
+  Restart,
+
+  /// Terminates the process, leaving it stopped until restarted
+  Stop,
+
+  /// Sends a signal to the running process
+  Signal(i32)
+}
+
+lazy_static! {
+  /// Control channels for each running subprocess manager, by source name
+  static ref CONTROLS: Mutex<HashMap<String, Sender<Control>>> = Mutex::new(HashMap::new());
+}
+
+/// Sends a control request to the subprocess managing `source`
+///
+/// This is global as the interactive renderer otherwise has no way to reach
+/// the reader.
+pub fn control(source: &str, control: Control) -> SimpleResult<()> {
+  let controls = CONTROLS.lock().map_err(|_| SimpleError::new("subprocess controls poisoned"))?;
+  match controls.get(source) {
+    Some(tx) => tx.send(control).map_err(|_| SimpleError::new(
+      format!("subprocess {} is no longer managed", source)
+    )),
+    None => bail!("source {} is not a managed subprocess, see --exec", source)
+  }
+}
+
+/// Names of every managed subprocess
+pub fn managed() -> Vec<String> {
+  let mut names: Vec<String> = CONTROLS.lock()
+    .map(|c| c.keys().cloned().collect())
+    .unwrap_or_default();
+
+  names.sort();
+  names
+}
+
+/// A command to run, and the source name its output is shown under
+#[derive(Debug, Clone, PartialEq)]
+struct Command {
+  name: String,
+  args: Vec<String>
+}
+
+/// Names a command after the basename of its program, e.g. `server` for
+/// `./bin/server --port 80`
+fn command_name(program: &str) -> String {
+  Path::new(program).file_name()
+    .map(|n| n.to_string_lossy().to_string())
+    .unwrap_or_else(|| program.to_string())
+}
+
+/// Gathers commands from `--exec` (run with `sh -c`) or failing that the
+/// positional arguments, giving each a unique source name
+fn commands(exec: &[String], app: &[String]) -> Vec<Command> {
+  let mut commands: Vec<Command> = if exec.is_empty() {
+    match app.first() {
+      Some(program) => vec![Command { name: command_name(program), args: app.to_vec() }],
+      None => vec![]
+    }
+  } else {
+    exec.iter().map(|command| Command {
+      name: command_name(command.split_whitespace().next().unwrap_or("exec")),
+      args: vec!["sh".to_string(), "-c".to_string(), command.clone()]
+    }).collect()
+  };
+
+  // the same program run twice gets numbered, e.g. server, server-2
+  let mut seen: HashMap<String, usize> = HashMap::new();
+  for command in commands.iter_mut() {
+    let count = seen.entry(command.name.clone()).or_insert(0);
+    *count += 1;
+
+    if *count > 1 {
+      command.name = format!("{}-{}", command.name, count);
+    }
+  }
+
+  commands
+}
+
+fn describe_exit(status: ExitStatus) -> (String, SourceState) {
+  match status {
+    ExitStatus::Exited(0) => ("exited".to_string(), SourceState::Ended),
+    ExitStatus::Exited(code) => (format!("exited with code {}", code), SourceState::Dead),
+    ExitStatus::Signaled(signal) => (
+      format!("killed by {}", signal_name(i32::from(signal))),
+      SourceState::Dead
+    ),
+    _ => ("exited".to_string(), SourceState::Dead)
+  }
+}
+
+/// Reads the merged stdout and stderr of a child into messages, returning a
+/// channel that disconnects once output ends
+fn read_output(
+  config: Arc<Config>, name: String, child: &mut Popen, tx: Sender<LogEntry>
+) -> Receiver<()> {
+  let (done_tx, done_rx) = channel::<()>();
+  let stdout = match child.stdout.take() {
+    Some(stdout) => stdout,
+    None => return done_rx
+  };
+
+  thread::spawn(move || {
+    let _done_tx = done_tx;
+    let reader = BufReader::new(stdout);
+
+    for line in reader.lines() {
+      let line = match line {
+        Ok(line) => line,
+        Err(_) => break
+      };
+
+      let meta = ReaderMetadata { timestamp: None, source: Some(name.clone()) };
+      match LogEntry::message(Arc::clone(&config), &line, Some(meta)) {
+        Ok(Some(entry)) => tx.send(entry).ok(),
+        _ => continue
+      };
+    }
+  });
+
+  done_rx
+}
+
+/// Terminates a child, killing it if it hasn't exited after a second
+fn stop(child: &mut Popen) {
+  child.terminate().ok();
+
+  if let Ok(Some(_)) = child.wait_timeout(Duration::from_secs(1)) {
+    return;
+  }
+
+  child.kill().ok();
+  child.wait().ok();
+}
+
+/// What happened to a running child
+enum Outcome {
+  Exited(ExitStatus),
+  Failed,
+  Stopped,
+  Restart,
+  Shutdown
+}
+
+/// Waits for a child to exit or be stopped, handling control requests
+fn supervise(
+  name: &str, child: &mut Popen, control_rx: &Receiver<Control>, tx: &Sender<LogEntry>
+) -> Outcome {
+  loop {
+    match control_rx.recv_timeout(Duration::from_millis(100)) {
+      Ok(Control::Restart) => {
+        stop(child);
+        return Outcome::Restart;
+      },
+      Ok(Control::Stop) => {
+        stop(child);
+        return Outcome::Stopped;
+      },
+      Ok(Control::Signal(signal)) => {
+        let text = match send_signal(child, signal) {
+          Ok(()) => format!("{}: sent {}", name, signal_name(signal)),
+          Err(e) => format!("{}: could not send {}: {}", name, signal_name(signal), e)
+        };
+
+        tx.send(LogEntry::internal(&text)).ok();
+      },
+      Err(RecvTimeoutError::Timeout) => {
+        if let Some(status) = child.poll() {
+          return Outcome::Exited(status);
+        }
+      },
+      Err(RecvTimeoutError::Disconnected) => {
+        stop(child);
+        return Outcome::Shutdown;
+      }
+    }
+  }
+}
+
+#[cfg(unix)]
+fn send_signal(child: &Popen, signal: i32) -> SimpleResult<()> {
+  use subprocess::unix::PopenExt;
+
+  child.send_signal(signal).map_err(SimpleError::from)
+}
+
+#[cfg(not(unix))]
+fn send_signal(_child: &Popen, _signal: i32) -> SimpleResult<()> {
+  bail!("signals are only supported on unix")
+}
+
+/// Runs a command until shut down, starting it again when asked
+///
+/// `running` counts processes still running; once every one is down, an EOF
+/// is sent so non-interactive renderers can finish.
+fn manage(
+  config: Arc<Config>, command: Command, running: Arc<AtomicUsize>,
+  control_rx: Receiver<Control>, tx: Sender<LogEntry>
+) {
+  let name = command.name.clone();
+
+  loop {
+    running.fetch_add(1, Ordering::SeqCst);
+
+    // stdin is closed right away so children can't steal the renderer's input
+    let child = Popen::create(&command.args, PopenConfig {
+      stdin: Redirection::Pipe,
+      stdout: Redirection::Pipe,
+      stderr: Redirection::Merge,
+
+      ..Default::default()
+    });
+
+    let outcome = match child {
+      Ok(mut child) => {
+        child.stdin.take();
+
+        let pid = child.pid().map(|p| p.to_string()).unwrap_or_else(|| "?".to_string());
+        tx.send(LogEntry::internal(&format!("{}: started (pid {})", name, pid))).ok();
+        tx.send(LogEntry::status(&name, SourceState::Streaming)).ok();
+
+        let done = read_output(Arc::clone(&config), name.clone(), &mut child, tx.clone());
+        let outcome = supervise(&name, &mut child, &control_rx, &tx);
+
+        // let the last lines through before saying what happened, unless
+        // something else (e.g. a background job) holds the output open
+        done.recv_timeout(Duration::from_millis(500)).ok();
+        outcome
+      },
+      Err(e) => {
+        tx.send(LogEntry::internal(&format!("{}: could not start: {}", name, e))).ok();
+        Outcome::Failed
+      }
+    };
+
+    match outcome {
+      Outcome::Exited(status) => {
+        let (text, state) = describe_exit(status);
+        tx.send(LogEntry::internal(&format!("{}: {}", name, text))).ok();
+        tx.send(LogEntry::status(&name, state)).ok();
+      },
+      Outcome::Failed => {
+        tx.send(LogEntry::status(&name, SourceState::Dead)).ok();
+      },
+      Outcome::Stopped => {
+        tx.send(LogEntry::internal(&format!("{}: stopped", name))).ok();
+        tx.send(LogEntry::status(&name, SourceState::Ended)).ok();
+      },
+      Outcome::Restart => {
+        tx.send(LogEntry::internal(&format!("{}: restarting", name))).ok();
+        running.fetch_sub(1, Ordering::SeqCst);
+        continue;
+      },
+      Outcome::Shutdown => return
+    }
+
+    if running.fetch_sub(1, Ordering::SeqCst) == 1 {
+      tx.send(LogEntry::eof()).ok();
+    }
+
+    // the process is down, so wait until asked to start it again
+    loop {
+      match control_rx.recv() {
+        Ok(Control::Restart) => break,
+        Ok(_) => {
+          tx.send(LogEntry::internal(&format!("{}: not running, restart it first", name))).ok();
+        },
+        Err(_) => return
+      }
+    }
+  }
+}
+
+/// Runs commands from `--exec`, or the positional arguments, as managed
+/// subprocesses whose output is read as logs
+///
+/// Each command is a separate source that can be restarted, stopped, or
+/// signaled from the interactive renderer via `control()`.
+pub fn read_exec(
+  config: Arc<Config>,
+  tx: Sender<LogEntry>,
+  exit_req_rx: Receiver<()>,
+  exit_resp_tx: Sender<()>
+) -> JoinHandle<SimpleResult<()>> {
+  thread::Builder::new().name("read_exec".to_string()).spawn(move || {
+    let commands = commands(&config.exec, &config.app);
+    if commands.is_empty() {
+      tx.send(LogEntry::internal("no command to run, see --exec")).ok();
+      tx.send(LogEntry::eof()).ok();
+      return Ok(());
+    }
+
+    let running = Arc::new(AtomicUsize::new(0));
+    let mut managers = Vec::new();
+    for command in commands {
+      let (control_tx, control_rx) = channel();
+      if let Ok(mut controls) = CONTROLS.lock() {
+        controls.insert(command.name.clone(), control_tx);
+      }
+
+      let config = Arc::clone(&config);
+      let running = Arc::clone(&running);
+      let tx = tx.clone();
+      managers.push(thread::spawn(move || manage(config, command, running, control_rx, tx)));
+    }
+
+    exit_req_rx.recv().ok();
+
+    // dropping the control channels tells each manager to stop its process
+    if let Ok(mut controls) = CONTROLS.lock() {
+      controls.clear();
+    }
+
+    for manager in managers {
+      manager.join().ok();
+    }
+
+    exit_resp_tx.send(()).ok();
+
+    Ok(())
+  }).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use spectral::prelude::*;
+
+  #[test]
+  fn test_parse_signal() {
+    assert_that!(parse_signal("HUP")).is_ok_containing(1);
+    assert_that!(parse_signal("sigterm")).is_ok_containing(15);
+    assert_that!(parse_signal("10")).is_ok_containing(10);
+    assert_that!(parse_signal("SIGWAT")).is_err();
+
+    assert_that!(signal_name(9)).is_equal_to("SIGKILL".to_string());
+    assert_that!(signal_name(10)).is_equal_to("signal 10".to_string());
+  }
+
+  #[test]
+  fn test_commands() {
+    let exec = vec![
+      "./bin/server --port 80".to_string(),
+      "worker".to_string(),
+      "/usr/bin/server --port 81".to_string()
+    ];
+
+    let names: Vec<String> = commands(&exec, &[]).into_iter().map(|c| c.name).collect();
+    assert_that!(names).is_equal_to(vec![
+      "server".to_string(), "worker".to_string(), "server-2".to_string()
+    ]);
+
+    let app = vec!["tail".to_string(), "-f".to_string(), "app.log".to_string()];
+    assert_that!(commands(&[], &app)).is_equal_to(vec![
+      Command { name: "tail".to_string(), args: app.clone() }
+    ]);
+
+    // --exec takes precedence over positional arguments
+    assert_that!(commands(&exec[1..2], &app)[0].args).is_equal_to(vec![
+      "sh".to_string(), "-c".to_string(), "worker".to_string()
+    ]);
+  }
+}
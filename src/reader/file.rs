@@ -0,0 +1,300 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use simple_error::SimpleResult;
+
+use crate::config::Config;
+use crate::parser::ReaderMetadata;
+use crate::renderer::{LogEntry, SourceState};
+
+/// How often followed files are checked for new lines, in milliseconds
+static POLL_MS: u64 = 250;
+
+/// An identity for a file, to spot its path being replaced, e.g. by log
+/// rotation; unavailable on other platforms, where only truncation is noticed
+#[cfg(unix)]
+fn file_id(metadata: &fs::Metadata) -> Option<u64> {
+  use std::os::unix::fs::MetadataExt;
+
+  Some(metadata.ino())
+}
+
+#[cfg(not(unix))]
+fn file_id(_metadata: &fs::Metadata) -> Option<u64> {
+  None
+}
+
+/// A file being read, reopened from the start if it's truncated or replaced
+struct Followed {
+  name: String,
+  path: PathBuf,
+  reader: Option<BufReader<File>>,
+  id: Option<u64>,
+
+  /// The number of bytes read from the open file
+  position: u64,
+
+  /// The start of a line whose newline hasn't been written yet
+  partial: Vec<u8>,
+
+  /// If set, the file couldn't be opened, which has already been reported
+  missing: bool
+}
+
+impl Followed {
+  fn new(name: String, path: PathBuf) -> Self {
+    Followed {
+      name,
+      path,
+      reader: None,
+      id: None,
+      position: 0,
+      partial: Vec::new(),
+      missing: false
+    }
+  }
+
+  fn open(&mut self) -> io::Result<()> {
+    let file = File::open(&self.path)?;
+    self.id = file.metadata().ok().and_then(|m| file_id(&m));
+    self.reader = Some(BufReader::new(file));
+    self.position = 0;
+    self.partial.clear();
+
+    Ok(())
+  }
+
+  /// Reads every complete line written since the last read; at the end of
+  /// input, also the incomplete line that ends it
+  fn read_lines(&mut self, end: bool) -> Vec<String> {
+    let mut lines = Vec::new();
+    let reader = match &mut self.reader {
+      Some(reader) => reader,
+      None => return lines
+    };
+
+    loop {
+      match reader.read_until(b'\n', &mut self.partial) {
+        Ok(0) | Err(_) => break,
+        Ok(read) => self.position += read as u64
+      }
+
+      if self.partial.ends_with(b"\n") {
+        lines.push(line(&self.partial));
+        self.partial.clear();
+      }
+    }
+
+    if end && !self.partial.is_empty() {
+      lines.push(line(&self.partial));
+      self.partial.clear();
+    }
+
+    lines
+  }
+
+  /// If the file at the path isn't the one being read anymore, says why
+  fn changed(&self) -> Option<&'static str> {
+    let metadata = fs::metadata(&self.path).ok()?;
+    if self.id.is_some() && file_id(&metadata) != self.id {
+      Some("replaced")
+    } else if metadata.len() < self.position {
+      Some("truncated")
+    } else {
+      None
+    }
+  }
+
+  /// Sends any new lines, opening the file first if needed; returns false if
+  /// the renderer has quit
+  fn poll(&mut self, config: &Arc<Config>, tx: &Sender<LogEntry>, follow: bool) -> bool {
+    if self.reader.is_some() && follow {
+      if let Some(why) = self.changed() {
+        // lines written before a rotation still belong to the old file
+        let rest = self.read_lines(true);
+        if !self.send(config, tx, &rest) {
+          return false;
+        }
+
+        tx.send(LogEntry::internal(
+          &format!("{}: {}, reading from the start", self.name, why)
+        )).ok();
+        self.reader = None;
+      }
+    }
+
+    if self.reader.is_none() {
+      match self.open() {
+        Ok(()) => {
+          self.missing = false;
+          tx.send(LogEntry::status(&self.name, SourceState::Streaming)).ok();
+        },
+        Err(e) => {
+          if !self.missing {
+            let waiting = if follow { ", waiting for it" } else { "" };
+            tx.send(LogEntry::internal(&format!(
+              "{}: could not open {}: {}{}", self.name, self.path.display(), e, waiting
+            ))).ok();
+            tx.send(LogEntry::status(&self.name, SourceState::Dead)).ok();
+          }
+
+          self.missing = true;
+        }
+      }
+    }
+
+    let lines = self.read_lines(!follow);
+    self.send(config, tx, &lines)
+  }
+
+  /// Sends lines as messages from this file; returns false if the renderer
+  /// has quit
+  fn send(&self, config: &Arc<Config>, tx: &Sender<LogEntry>, lines: &[String]) -> bool {
+    for line in lines.iter().filter(|line| !line.is_empty()) {
+      let meta = ReaderMetadata { timestamp: None, source: Some(self.name.clone()) };
+      if let Ok(Some(entry)) = LogEntry::message(Arc::clone(config), line, Some(meta)) {
+        if tx.send(entry).is_err() {
+          return false;
+        }
+      }
+    }
+
+    true
+  }
+}
+
+fn line(bytes: &[u8]) -> String {
+  let text = String::from_utf8_lossy(bytes);
+  text.trim_end_matches(&['\n', '\r'][..]).to_string()
+}
+
+/// Pairs files with source names, after their basenames, numbering any
+/// shared by several files, e.g. `app.log`, `app.log-2`
+fn source_names(paths: &[String]) -> Vec<(String, PathBuf)> {
+  let mut seen: HashMap<String, usize> = HashMap::new();
+
+  paths.iter().map(|path| {
+    let path = PathBuf::from(shellexpand::tilde(path).to_string());
+    let name = Path::new(&path).file_name()
+      .map(|n| n.to_string_lossy().to_string())
+      .unwrap_or_else(|| path.display().to_string());
+
+    let count = seen.entry(name.clone()).or_insert(0);
+    *count += 1;
+
+    match count {
+      1 => (name, path),
+      count => (format!("{}-{}", name, count), path)
+    }
+  }).collect()
+}
+
+/// Reads files given as positional arguments, e.g. with `woodchipper tail`,
+/// each as its own source
+///
+/// Files are followed for new lines like `tail -F`, including across log
+/// rotation, unless `--no-follow` is set.
+pub fn read_file(
+  config: Arc<Config>,
+  tx: Sender<LogEntry>,
+  exit_req_rx: Receiver<()>,
+  exit_resp_tx: Sender<()>
+) -> JoinHandle<SimpleResult<()>> {
+  thread::Builder::new().name("read_file".to_string()).spawn(move || {
+    let mut files: Vec<Followed> = source_names(&config.app).into_iter()
+      .map(|(name, path)| Followed::new(name, path))
+      .collect();
+
+    if files.is_empty() {
+      tx.send(LogEntry::internal("no files to read, see woodchipper tail")).ok();
+      tx.send(LogEntry::eof()).ok();
+      return Ok(());
+    }
+
+    let follow = !config.no_follow;
+    loop {
+      for file in files.iter_mut() {
+        if !file.poll(&config, &tx, follow) {
+          return Ok(());
+        }
+      }
+
+      if !follow {
+        for file in files.iter().filter(|file| file.reader.is_some()) {
+          tx.send(LogEntry::status(&file.name, SourceState::Ended)).ok();
+        }
+
+        tx.send(LogEntry::eof()).ok();
+        return Ok(());
+      }
+
+      match exit_req_rx.recv_timeout(Duration::from_millis(POLL_MS)) {
+        Err(RecvTimeoutError::Timeout) => continue,
+        _ => break
+      }
+    }
+
+    exit_resp_tx.send(()).ok();
+
+    Ok(())
+  }).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::env;
+  use std::io::Write;
+  use std::sync::mpsc::channel;
+
+  use spectral::prelude::*;
+  use structopt::StructOpt;
+
+  #[test]
+  fn test_follow_file() {
+    let config = Arc::new(Config::from_iter_safe(vec![""]).unwrap());
+    let path = env::temp_dir().join(format!("woodchipper-file-{}.log", std::process::id()));
+    let append = |text: &str| {
+      let mut file = fs::OpenOptions::new().create(true).append(true).open(&path).unwrap();
+      file.write_all(text.as_bytes()).unwrap();
+    };
+
+    let (tx, rx) = channel::<LogEntry>();
+    let texts = || rx.try_iter()
+      .filter_map(|entry| entry.message)
+      .filter_map(|entry| entry.message.text)
+      .collect::<Vec<String>>();
+
+    let names = source_names(&[path.display().to_string(), path.display().to_string()]);
+    let mut file = Followed::new(names[0].0.clone(), path.clone());
+    assert_that!(names[1].0.ends_with(".log-2")).is_true();
+
+    assert_that!(file.poll(&config, &tx, true)).is_true();
+    assert_that!(texts()[0].contains("could not open")).is_true();
+
+    append("first\nsecond\r\nthi");
+    file.poll(&config, &tx, true);
+    assert_that!(texts()).is_equal_to(vec!["first".to_string(), "second".to_string()]);
+
+    append("rd\n");
+    file.poll(&config, &tx, true);
+    assert_that!(texts()).is_equal_to(vec!["third".to_string()]);
+
+    fs::write(&path, "over\n").unwrap();
+    file.poll(&config, &tx, true);
+    let texts = texts();
+    assert_that!(texts[0].ends_with("truncated, reading from the start")).is_true();
+    assert_that!(texts[1..].to_vec()).is_equal_to(vec!["over".to_string()]);
+
+    fs::remove_file(&path).ok();
+  }
+}
@@ -0,0 +1,488 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{Receiver, Sender};
+use std::thread::{self, JoinHandle};
+
+use chrono::{DateTime, Utc};
+use rand::Rng;
+use regex::Regex;
+use serde::Deserialize;
+use simple_error::{SimpleError, SimpleResult};
+
+use crate::config::Config;
+use crate::filter::{Filter, FilterMode};
+use crate::parser::{LogLevel, Message, MessageKind};
+use crate::renderer::LogEntry;
+use crate::style::{RuleConditions, de_opt_level, de_opt_regex};
+use crate::timezone;
+
+lazy_static! {
+  /// When woodchipper started, which `--since` and `--until` are relative to
+  static ref STARTED: DateTime<Utc> = Utc::now();
+}
+
+/// The number of messages discarded by drop rules so far
+static DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+/// The number of messages discarded by `--sample` so far
+static SAMPLED_OUT: AtomicUsize = AtomicUsize::new(0);
+
+/// The number of messages discarded by the config file's drop rules so far
+pub fn dropped() -> usize {
+  DROPPED.load(Ordering::Relaxed)
+}
+
+/// The number of messages discarded by `--sample` so far
+pub fn sampled_out() -> usize {
+  SAMPLED_OUT.load(Ordering::Relaxed)
+}
+
+/// The fraction of messages kept by `--sample`, written `0.1`, `1/10`, or
+/// `10%`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SampleRate(pub f64);
+
+impl FromStr for SampleRate {
+  type Err = SimpleError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let s = s.trim();
+    let rate = if let Some(percent) = s.strip_suffix('%') {
+      percent.trim().parse::<f64>().ok().map(|p| p / 100.0)
+    } else if let Some(i) = s.find('/') {
+      match (s[..i].trim().parse::<f64>(), s[i + 1..].trim().parse::<f64>()) {
+        (Ok(n), Ok(d)) if d > 0.0 => Some(n / d),
+        _ => None
+      }
+    } else {
+      s.parse().ok()
+    };
+
+    match rate {
+      Some(rate) if rate > 0.0 && rate <= 1.0 => Ok(SampleRate(rate)),
+      _ => bail!("invalid sample rate, expected e.g. 0.1, 1/10, or 10%: {}", s)
+    }
+  }
+}
+
+/// A rule from the config file's `rules.drop` section, discarding messages
+/// matching all of its conditions before they're buffered or rendered, e.g.
+/// health checks and heartbeats
+#[derive(Debug, Clone, Deserialize)]
+pub struct DropRule {
+  #[serde(flatten)]
+  pub conditions: RuleConditions,
+
+  /// Messages with a level below this match, e.g. `info` for debug messages
+  #[serde(default, deserialize_with = "de_opt_level")]
+  pub below: Option<LogLevel>,
+
+  /// A regex the message's source must match, e.g. a pod name
+  #[serde(default, deserialize_with = "de_opt_regex")]
+  pub source: Option<Regex>
+}
+
+impl DropRule {
+  pub fn matches(&self, message: &Message) -> bool {
+    if let Some(below) = self.below {
+      match message.level {
+        Some(LogLevel::Plain) | Some(LogLevel::Int) | None => return false,
+        Some(level) if level >= below => return false,
+        Some(_) => ()
+      }
+    }
+
+    if let Some(re) = &self.source {
+      let source = message.reader_metadata.as_ref().and_then(|m| m.source.as_ref());
+      match source {
+        Some(source) if re.is_match(source) => (),
+        _ => return false
+      }
+    }
+
+    self.conditions.matches(message)
+  }
+}
+
+/// The times set with `--since` and `--until`
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TimeWindow {
+  pub since: Option<DateTime<Utc>>,
+  pub until: Option<DateTime<Utc>>
+}
+
+impl TimeWindow {
+  /// If true, a message is within the window; messages without a timestamp
+  /// always are
+  pub fn contains(&self, message: &Message) -> bool {
+    let timestamp = match message.timestamp {
+      Some(timestamp) => timestamp,
+      None => return true
+    };
+
+    self.since.map_or(true, |since| timestamp >= since)
+      && self.until.map_or(true, |until| timestamp <= until)
+  }
+}
+
+/// Resolves a `--since` or `--until` time relative to `reference`, with a bare
+/// duration like `15m` meaning that long before it
+fn parse_time(
+  input: &str, flag: &str, reference: DateTime<Utc>
+) -> SimpleResult<DateTime<Utc>> {
+  lazy_static! {
+    static ref AGO_RE: Regex = Regex::new(r"^\s*(?:\d+(?:ms|d|h|m|s))+\s*$").unwrap();
+  }
+
+  let input = match AGO_RE.is_match(input) {
+    true => format!("-{}", input.trim()),
+    false => input.to_string()
+  };
+
+  timezone::parse_target(&input, timezone::to_display(reference))
+    .map_err(|e| SimpleError::new(format!("invalid {}: {}", flag, e)))
+}
+
+fn resolve_window(config: &Config, reference: DateTime<Utc>) -> SimpleResult<TimeWindow> {
+  let since = match &config.since {
+    Some(since) => Some(parse_time(since, "--since", reference)?),
+    None => None
+  };
+
+  let until = match &config.until {
+    Some(until) => Some(parse_time(until, "--until", reference)?),
+    None => None
+  };
+
+  if let (Some(since), Some(until)) = (since, until) {
+    if since > until {
+      bail!("--since ({}) is after --until ({})", since.to_rfc3339(), until.to_rfc3339());
+    }
+  }
+
+  Ok(TimeWindow { since, until })
+}
+
+/// Resolves `--since` and `--until`, relative to when woodchipper started
+pub fn time_window(config: &Config) -> SimpleResult<TimeWindow> {
+  resolve_window(config, *STARTED)
+}
+
+/// If true, any of `--filter`, `--grep`, `--grep-exclude`, `--min-level`,
+/// `--since`, `--until`, `--sample`, or drop rules are set
+pub fn enabled(config: &Config) -> bool {
+  !config.filter.is_empty()
+    || !config.grep.is_empty()
+    || !config.grep_exclude.is_empty()
+    || config.min_level.is_some()
+    || config.since.is_some()
+    || config.until.is_some()
+    || config.sample.is_some()
+    || !config.file().rules.drop.is_empty()
+}
+
+/// Parses the expressions given with `--filter`
+pub fn parse_filters(config: &Config) -> SimpleResult<Vec<Box<dyn Filter>>> {
+  config.filter.iter()
+    .map(|query| FilterMode::Expression.parse(query, false).map_err(|e| SimpleError::new(
+      format!("invalid --filter {:?}: {}", query, e)
+    )))
+    .collect()
+}
+
+/// If true, a regex matches the original line or the parsed text
+fn grep_match(re: &Regex, message: &Message) -> bool {
+  re.is_match(&message.raw) || message.text.as_ref().map_or(false, |text| re.is_match(text))
+}
+
+/// If true, a message passes `--min-level`, `--since`, `--until`, `--grep`,
+/// `--grep-exclude`, and every `--filter`
+fn message_pass(
+  config: &Config, window: &TimeWindow, filters: &[Box<dyn Filter>], message: &Message
+) -> bool {
+  if let Some(min) = config.min_level {
+    match message.level {
+      Some(LogLevel::Int) => (),
+      Some(LogLevel::Plain) | None => return false,
+      Some(level) if level < min => return false,
+      Some(_) => ()
+    }
+  }
+
+  if !window.contains(message) {
+    return false;
+  }
+
+  if !config.grep.is_empty() && !config.grep.iter().any(|re| grep_match(re, message)) {
+    return false;
+  }
+
+  if config.grep_exclude.iter().any(|re| grep_match(re, message)) {
+    return false;
+  }
+
+  filters.iter().all(|f| f.filter(message))
+}
+
+/// If true, a message survives `--sample`: it's at or above `--sample-keep`,
+/// or it's randomly chosen to be kept
+fn sample_pass<R: Rng>(config: &Config, rng: &mut R, message: &Message) -> bool {
+  let rate = match config.sample {
+    Some(SampleRate(rate)) => rate,
+    None => return true
+  };
+
+  match (config.sample_keep, message.level) {
+    (Some(_), Some(LogLevel::Plain)) | (Some(_), Some(LogLevel::Int)) => (),
+    (Some(keep), Some(level)) if level >= keep => return true,
+    _ => ()
+  }
+
+  rng.gen::<f64>() < rate
+}
+
+/// If true, an entry should be passed along: it isn't a message, it's an
+/// internal message, or it passes every filter, no drop rule matches it, and
+/// it isn't sampled out
+fn pass<R: Rng>(
+  config: &Config, window: &TimeWindow, filters: &[Box<dyn Filter>], rng: &mut R,
+  entry: &LogEntry
+) -> bool {
+  let message = match &entry.message {
+    Some(message) if message.message.kind != MessageKind::Internal => &message.message,
+    _ => return true
+  };
+
+  if config.file().rules.drop.iter().any(|rule| rule.matches(message)) {
+    DROPPED.fetch_add(1, Ordering::Relaxed);
+    return false;
+  }
+
+  if !message_pass(config, window, filters, message) {
+    return false;
+  }
+
+  if !sample_pass(config, rng, message) {
+    SAMPLED_OUT.fetch_add(1, Ordering::Relaxed);
+    return false;
+  }
+
+  true
+}
+
+/// Drops messages matching a drop rule, below `--min-level`, outside
+/// `--since` and `--until`, not matching `--grep`, matching `--grep-exclude`,
+/// not matching every `--filter` expression, or sampled out by `--sample`
+/// before they're rendered
+pub fn read_filter(
+  config: Arc<Config>,
+  rx: Receiver<LogEntry>,
+  tx: Sender<LogEntry>,
+) -> JoinHandle<SimpleResult<()>> {
+  thread::Builder::new().name("read_filter".to_string()).spawn(move || {
+    // already checked at startup
+    let filters = parse_filters(&config)?;
+    let window = time_window(&config)?;
+    let mut rng = rand::thread_rng();
+
+    for entry in rx {
+      if pass(&config, &window, &filters, &mut rng, &entry) && tx.send(entry).is_err() {
+        break;
+      }
+    }
+
+    Ok(())
+  }).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::sync::mpsc::channel;
+
+  use rand::SeedableRng;
+  use rand::rngs::StdRng;
+  use spectral::prelude::*;
+  use structopt::StructOpt;
+
+  use crate::config::FileConfig;
+  use crate::parser::ReaderMetadata;
+
+  #[test]
+  fn test_read_filter() {
+    let config = Config::from_iter_safe(vec![
+      "", "--filter", "level >= warn", "--filter", "status >= 500"
+    ]).unwrap();
+    assert_that!(parse_filters(&config).is_ok()).is_true();
+
+    let (in_tx, in_rx) = channel();
+    let (out_tx, out_rx) = channel();
+    let handle = read_filter(Arc::new(config), in_rx, out_tx);
+
+    let config = Arc::new(Config::from_iter_safe(vec![""]).unwrap());
+    for line in &[
+      r#"{"level": "error", "status": 503, "msg": "kept"}"#,
+      r#"{"level": "info", "status": 503, "msg": "too quiet"}"#,
+      r#"{"level": "error", "status": 404, "msg": "not a server error"}"#
+    ] {
+      in_tx.send(LogEntry::message(Arc::clone(&config), line, None).unwrap().unwrap()).unwrap();
+    }
+
+    in_tx.send(LogEntry::internal("always kept")).unwrap();
+    in_tx.send(LogEntry::eof()).unwrap();
+    drop(in_tx);
+    handle.join().unwrap().unwrap();
+
+    let entries: Vec<LogEntry> = out_rx.iter().collect();
+    let texts: Vec<String> = entries.iter()
+      .filter_map(|e| e.message.as_ref()?.message.text.clone())
+      .collect();
+
+    assert_that!(texts).is_equal_to(vec!["kept".to_string(), "always kept".to_string()]);
+    assert_that!(entries.last().unwrap().eof.is_some()).is_true();
+
+    let config = Config::from_iter_safe(vec!["", "--filter", "level >="]).unwrap();
+    assert_that!(parse_filters(&config).is_err()).is_true();
+  }
+
+  #[test]
+  fn test_drop_rules() {
+    let rules: Vec<DropRule> = serde_yaml::from_str(concat!(
+      "- message: heartbeat\n",
+      "- field: path\n",
+      "  equals: /healthz\n",
+      "- below: info\n",
+      "  source: ^worker\n"
+    )).unwrap();
+
+    let parse_config = Arc::new(Config::from_iter_safe(vec![""]).unwrap());
+    let dropped = |line: &str, source: Option<&str>| {
+      let meta = source.map(|s| ReaderMetadata { timestamp: None, source: Some(s.to_string()) });
+      let entry = LogEntry::message(Arc::clone(&parse_config), line, meta).unwrap().unwrap();
+      let message = entry.message.unwrap().message;
+      rules.iter().any(|rule| rule.matches(&message))
+    };
+
+    assert_that!(dropped(r#"{"level": "info", "msg": "heartbeat ok"}"#, None)).is_true();
+    assert_that!(dropped(r#"{"level": "info", "msg": "hit", "path": "/healthz"}"#, None)).is_true();
+    assert_that!(dropped(r#"{"level": "info", "msg": "hit", "path": "/api"}"#, None)).is_false();
+    assert_that!(dropped(r#"{"level": "debug", "msg": "poll"}"#, Some("worker-1"))).is_true();
+    assert_that!(dropped(r#"{"level": "debug", "msg": "poll"}"#, Some("api-1"))).is_false();
+    assert_that!(dropped(r#"{"level": "warn", "msg": "poll"}"#, Some("worker-1"))).is_false();
+
+    assert_that!(serde_yaml::from_str::<Vec<DropRule>>("- below: loud\n").is_err()).is_true();
+
+    // rules may be a list of style rules, or sections of style and drop rules
+    let file: FileConfig = serde_yaml::from_str(concat!(
+      "rules:\n",
+      "  - message: slow\n",
+      "    style: red\n"
+    )).unwrap();
+    assert_that!(file.rules.style).has_length(1);
+    assert_that!(file.rules.drop).is_empty();
+
+    let file: FileConfig = serde_yaml::from_str(concat!(
+      "rules:\n",
+      "  style:\n",
+      "    - message: slow\n",
+      "      style: red\n",
+      "  drop:\n",
+      "    - message: heartbeat\n"
+    )).unwrap();
+    assert_that!(file.rules.style).has_length(1);
+    assert_that!(file.rules.drop).has_length(1);
+  }
+
+  #[test]
+  fn test_sample() {
+    assert_that!("0.1".parse::<SampleRate>().unwrap()).is_equal_to(SampleRate(0.1));
+    assert_that!("1/4".parse::<SampleRate>().unwrap()).is_equal_to(SampleRate(0.25));
+    assert_that!("50%".parse::<SampleRate>().unwrap()).is_equal_to(SampleRate(0.5));
+    for invalid in &["0", "1.5", "1/0", "lots", "-1/2"] {
+      assert_that!(invalid.parse::<SampleRate>().is_err()).is_true();
+    }
+
+    let config = Config::from_iter_safe(vec!["", "--sample", "1/4", "--sample-keep", "warn"])
+      .unwrap();
+    let parse_config = Arc::new(Config::from_iter_safe(vec![""]).unwrap());
+    let message = |line: &str| {
+      LogEntry::message(Arc::clone(&parse_config), line, None).unwrap().unwrap()
+        .message.unwrap().message
+    };
+
+    let debug = message(r#"{"level": "debug", "msg": "chatty"}"#);
+    let warn = message(r#"{"level": "warn", "msg": "important"}"#);
+
+    let mut rng = StdRng::seed_from_u64(1);
+    let kept = (0..1000).filter(|_| sample_pass(&config, &mut rng, &debug)).count();
+    assert_that!(kept).is_greater_than(150);
+    assert_that!(kept).is_less_than(350);
+    assert_that!((0..100).all(|_| sample_pass(&config, &mut rng, &warn))).is_true();
+  }
+
+  #[test]
+  fn test_time_window() {
+    let config = Config::from_iter_safe(vec![
+      "", "--since", "2023-05-01T14:30:00Z", "--until", "2023-05-01T14:45:00Z"
+    ]).unwrap();
+    assert_that!(enabled(&config)).is_true();
+
+    let window = time_window(&config).unwrap();
+    let parse_config = Arc::new(Config::from_iter_safe(vec![""]).unwrap());
+    let pass = |line: &str| {
+      let entry = LogEntry::message(Arc::clone(&parse_config), line, None).unwrap().unwrap();
+      message_pass(&config, &window, &[], &entry.message.unwrap().message)
+    };
+
+    assert_that!(pass(r#"{"time": "2023-05-01T14:29:59Z", "msg": "before"}"#)).is_false();
+    assert_that!(pass(r#"{"time": "2023-05-01T14:30:00Z", "msg": "start"}"#)).is_true();
+    assert_that!(pass(r#"{"time": "2023-05-01T14:45:00Z", "msg": "end"}"#)).is_true();
+    assert_that!(pass(r#"{"time": "2023-05-01T14:45:01Z", "msg": "after"}"#)).is_false();
+    assert_that!(pass(r#"{"msg": "no timestamp"}"#)).is_true();
+
+    // bare durations are that long ago
+    let reference: DateTime<Utc> = "2023-05-01T15:00:00Z".parse().unwrap();
+    let config = Config::from_iter_safe(vec!["", "--since", "1h30m", "--until", "-10m"]).unwrap();
+    assert_that!(resolve_window(&config, reference)).is_equal_to(Ok(TimeWindow {
+      since: Some("2023-05-01T13:30:00Z".parse().unwrap()),
+      until: Some("2023-05-01T14:50:00Z".parse().unwrap())
+    }));
+
+    let config = Config::from_iter_safe(vec!["", "--since", "-5m", "--until", "-1h"]).unwrap();
+    assert_that!(resolve_window(&config, reference).is_err()).is_true();
+
+    let config = Config::from_iter_safe(vec!["", "--since", "yesterday"]).unwrap();
+    assert_that!(time_window(&config).is_err()).is_true();
+  }
+
+  #[test]
+  fn test_grep() {
+    let config = Config::from_iter_safe(vec![
+      "", "-g", "timeout", "-g", "refused", "-G", "health", "-l", "warn"
+    ]).unwrap();
+    assert_that!(enabled(&config)).is_true();
+
+    let parse_config = Arc::new(Config::from_iter_safe(vec![""]).unwrap());
+    let pass = |line: &str| {
+      let entry = LogEntry::message(Arc::clone(&parse_config), line, None).unwrap().unwrap();
+      message_pass(&config, &TimeWindow::default(), &[], &entry.message.unwrap().message)
+    };
+
+    assert_that!(pass(r#"{"level": "error", "msg": "upstream timeout"}"#)).is_true();
+    assert_that!(pass(r#"{"level": "warn", "msg": "connection refused"}"#)).is_true();
+    assert_that!(pass(r#"{"level": "info", "msg": "upstream timeout"}"#)).is_false();
+    assert_that!(pass(r#"{"level": "error", "msg": "health check timeout"}"#)).is_false();
+    assert_that!(pass(r#"{"level": "error", "msg": "disk full"}"#)).is_false();
+
+    // patterns may match fields in the original line
+    assert_that!(pass(r#"{"level": "error", "msg": "failed", "err": "timeout"}"#)).is_true();
+
+    assert_that!(enabled(&Config::from_iter_safe(vec![""]).unwrap())).is_false();
+    assert_that!(Config::from_iter_safe(vec!["", "-g", "("]).is_err()).is_true();
+    assert_that!(Config::from_iter_safe(vec!["", "-l", "loud"]).is_err()).is_true();
+  }
+}
@@ -16,7 +16,8 @@ use simple_error::{SimpleError, SimpleResult};
 use subprocess::{Popen, PopenConfig, Redirection, Exec};
 
 use crate::config::Config;
-use crate::renderer::LogEntry;
+use crate::reader::filter;
+use crate::renderer::{LogEntry, SourceState};
 use crate::parser::ReaderMetadata;
 use crate::parser::util::normalize_datetime;
 
@@ -395,6 +396,9 @@ fn follow_log(
   thread::spawn(move || {
     let client = Client::new();
 
+    // already checked at startup
+    let window = filter::time_window(&config).unwrap_or_default();
+
     // a count of retry attempts
     // this value may be reset if the log successfully runs for long enough
     let mut retries = 0;
@@ -411,6 +415,8 @@ fn follow_log(
 
         break;
       } else if retries > 0 {
+        tx.send(LogEntry::status(&container.to_string(), SourceState::Reconnecting)).ok();
+
         // if this is the 2nd (or nth) try, wait a bit
         // maybe the pod wasn't ready?
         thread::sleep(Duration::from_millis(5000));
@@ -425,12 +431,21 @@ fn follow_log(
         "started watching container: {}", container
       ))).ok();
 
-      let query = vec![
-        ("follow", "true"),
-        ("container", &container.container),
-        ("timestamps", "true")
+      let mut query = vec![
+        ("follow", "true".to_string()),
+        ("container", container.container.clone()),
+        ("timestamps", "true".to_string())
       ];
 
+      // let the API server skip messages outside --since and --until
+      if let Some(since) = window.since {
+        query.push(("sinceTime", since.to_rfc3339_opts(SecondsFormat::Secs, true)));
+      }
+
+      if window.until.map_or(false, |until| until < Utc::now()) {
+        query[0].1 = "false".to_string();
+      }
+
       let maybe_response = client
         .get(&format!(
           "http://localhost:{port}/api/v1/namespaces/{namespace}/pods/{pod}/log",
@@ -461,6 +476,8 @@ fn follow_log(
         continue;
       }
 
+      tx.send(LogEntry::status(&container.to_string(), SourceState::Streaming)).ok();
+
       let reader = BufReader::new(response);
       for (i, line) in reader.lines().enumerate() {
         // skip bad lines
@@ -511,6 +528,8 @@ fn follow_log(
         break;
       }
     }
+
+    tx.send(LogEntry::status(&container.to_string(), SourceState::Dead)).ok();
   });
 }
 
@@ -525,13 +544,17 @@ fn spawn_kubectl(config: Arc<Config>) -> SimpleResult<(Popen, u16)> {
     thread_rng().gen_range(1000, 65535)
   };
 
-  let port_arg = &format!("--port={}", port);
-  let args = vec![
-    "kubectl",
-    "proxy",
-    &port_arg
+  let port_arg = format!("--port={}", port);
+  let mut args = vec![
+    "kubectl".to_string(),
+    "proxy".to_string(),
+    port_arg
   ];
 
+  if let Some(context) = &config.kubernetes.context {
+    args.push(format!("--context={}", context));
+  }
+
   let mut child = Popen::create(&args, PopenConfig {
     stdout: Redirection::Merge,
     stderr: Redirection::None,
@@ -549,18 +572,24 @@ fn spawn_kubectl(config: Arc<Config>) -> SimpleResult<(Popen, u16)> {
   }
 }
 
-fn kubectl_get_namespace() -> SimpleResult<String> {
+fn kubectl_get_namespace(context: Option<&String>) -> SimpleResult<String> {
   // kubectl _appears_ to helpfully rewrite the config output to show the
   // current context first... but since that may or may not be intended
   // behavior, we also pass --minify which removes all but the current context
+  let mut args = vec![
+    "config".to_string(),
+    "view".to_string(),
+    "--minify".to_string(),
+    "-o".to_string(),
+    "jsonpath={.contexts[0].context.namespace}".to_string()
+  ];
+
+  if let Some(context) = context {
+    args.push(format!("--context={}", context));
+  }
+
   let data = Exec::cmd("kubectl")
-    .args(&[
-      "config",
-      "view",
-      "--minify",
-      "-o",
-      "jsonpath={.contexts[0].context.namespace}"
-    ])
+    .args(&args)
     .stdout(Redirection::Pipe)
     .stderr(Redirection::Pipe)
     .capture()
@@ -591,7 +620,7 @@ pub fn read_kubernetes_selector(
     let namespace = if let Some(namespace) = &config.kubernetes.namespace {
       namespace.clone()
     } else {
-      kubectl_get_namespace()?
+      kubectl_get_namespace(config.kubernetes.context.as_ref())?
     };
 
     let (mut kubectl, port) = spawn_kubectl(Arc::clone(&config))?;
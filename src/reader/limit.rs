@@ -0,0 +1,242 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+use std::sync::Arc;
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+use simple_error::SimpleResult;
+
+use crate::config::Config;
+use crate::parser::{Message, MessageKind};
+use crate::renderer::LogEntry;
+use crate::style::RuleConditions;
+
+fn default_seconds() -> f64 {
+  1.0
+}
+
+/// A rule from the config file's `rules.limit` section, letting at most `max`
+/// matching messages through every `seconds`
+///
+/// Once a window ends, the number of messages suppressed during it is reported
+/// with a single internal message, so storms are still visible.
+#[derive(Debug, Clone, Deserialize)]
+pub struct LimitRule {
+  /// A name for the rule, used when reporting suppressed messages
+  pub name: Option<String>,
+
+  #[serde(flatten)]
+  pub conditions: RuleConditions,
+
+  /// The number of matching messages let through per window
+  pub max: usize,
+
+  /// The length of each window, in seconds
+  #[serde(default = "default_seconds")]
+  pub seconds: f64
+}
+
+impl LimitRule {
+  fn window(&self) -> Duration {
+    Duration::from_secs_f64(self.seconds.max(0.001))
+  }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Window {
+  start: Instant,
+  count: usize,
+  suppressed: usize
+}
+
+/// Tracks each rule's current window
+struct Limiter<'a> {
+  rules: &'a [LimitRule],
+  windows: Vec<Option<Window>>
+}
+
+impl<'a> Limiter<'a> {
+  fn new(rules: &'a [LimitRule]) -> Self {
+    Limiter {
+      rules,
+      windows: vec![None; rules.len()]
+    }
+  }
+
+  fn summary(&self, index: usize, suppressed: usize) -> String {
+    let rule = &self.rules[index];
+    let name = match &rule.name {
+      Some(name) => name.clone(),
+      None => format!("rate limit rule {}", index + 1)
+    };
+
+    format!(
+      "suppressed {} similar line{} ({}: at most {} per {}s)",
+      suppressed, if suppressed == 1 { "" } else { "s" }, name, rule.max, rule.seconds
+    )
+  }
+
+  /// Ends windows that have run their course by `now`, returning summaries
+  /// of any that suppressed messages
+  fn expire(&mut self, now: Instant) -> Vec<String> {
+    let mut summaries = Vec::new();
+    for index in 0..self.windows.len() {
+      let window = match self.windows[index] {
+        Some(window) if now.duration_since(window.start) >= self.rules[index].window() => window,
+        _ => continue
+      };
+
+      self.windows[index] = None;
+      if window.suppressed > 0 {
+        summaries.push(self.summary(index, window.suppressed));
+      }
+    }
+
+    summaries
+  }
+
+  /// If true, a message should be let through; the first rule it matches
+  /// counts it
+  fn pass(&mut self, message: &Message, now: Instant) -> bool {
+    let index = match self.rules.iter().position(|rule| rule.conditions.matches(message)) {
+      Some(index) => index,
+      None => return true
+    };
+
+    let max = self.rules[index].max;
+    let window = self.windows[index].get_or_insert(Window { start: now, count: 0, suppressed: 0 });
+    if window.count < max {
+      window.count += 1;
+      true
+    } else {
+      window.suppressed += 1;
+      false
+    }
+  }
+
+  /// How long until the next window with suppressed messages ends
+  fn timeout(&self, now: Instant) -> Option<Duration> {
+    self.windows.iter().zip(self.rules)
+      .filter_map(|(window, rule)| match window {
+        Some(window) if window.suppressed > 0 => {
+          Some((window.start + rule.window()).saturating_duration_since(now))
+        },
+        _ => None
+      })
+      .min()
+  }
+
+  /// Summaries of every window that suppressed messages, e.g. at the end of
+  /// input
+  fn finish(&mut self) -> Vec<String> {
+    let summaries = (0..self.windows.len())
+      .filter_map(|index| match self.windows[index] {
+        Some(window) if window.suppressed > 0 => Some(self.summary(index, window.suppressed)),
+        _ => None
+      })
+      .collect();
+
+    self.windows.iter_mut().for_each(|w| *w = None);
+    summaries
+  }
+}
+
+/// A wrapping reader that rate-limits messages matching the config file's
+/// limit rules, reporting how many were suppressed
+pub fn read_limit(
+  config: Arc<Config>,
+  rx: Receiver<LogEntry>,
+  tx: Sender<LogEntry>,
+) -> JoinHandle<SimpleResult<()>> {
+  thread::Builder::new().name("read_limit".to_string()).spawn(move || {
+    let mut limiter = Limiter::new(&config.file().rules.limit);
+
+    let report = |summaries: Vec<String>| {
+      for summary in summaries {
+        tx.send(LogEntry::internal(&summary)).ok();
+      }
+    };
+
+    loop {
+      let received = match limiter.timeout(Instant::now()) {
+        Some(timeout) => rx.recv_timeout(timeout),
+        None => rx.recv().map_err(|_| RecvTimeoutError::Disconnected)
+      };
+
+      match received {
+        Ok(entry) => {
+          report(limiter.expire(Instant::now()));
+
+          let pass = match &entry.message {
+            Some(message) if message.message.kind != MessageKind::Internal => {
+              limiter.pass(&message.message, Instant::now())
+            },
+            _ => true
+          };
+
+          if entry.eof.is_some() {
+            report(limiter.finish());
+          }
+
+          if pass && tx.send(entry).is_err() {
+            break;
+          }
+        },
+        Err(RecvTimeoutError::Timeout) => report(limiter.expire(Instant::now())),
+        Err(RecvTimeoutError::Disconnected) => {
+          report(limiter.finish());
+          break;
+        }
+      }
+    }
+
+    Ok(())
+  }).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use spectral::prelude::*;
+  use structopt::StructOpt;
+
+  #[test]
+  fn test_limit() {
+    let rules: Vec<LimitRule> = serde_yaml::from_str(concat!(
+      "- name: heartbeats\n",
+      "  message: heartbeat\n",
+      "  max: 2\n",
+      "  seconds: 10\n"
+    )).unwrap();
+
+    let config = Arc::new(Config::from_iter_safe(vec![""]).unwrap());
+    let message = |line: &str| {
+      LogEntry::message(Arc::clone(&config), line, None).unwrap().unwrap().message.unwrap().message
+    };
+
+    let heartbeat = message(r#"{"msg": "heartbeat"}"#);
+    let other = message(r#"{"msg": "request done"}"#);
+
+    let start = Instant::now();
+    let mut limiter = Limiter::new(&rules);
+    let passed: Vec<bool> = (0..5).map(|_| limiter.pass(&heartbeat, start)).collect();
+    assert_that!(passed).is_equal_to(vec![true, true, false, false, false]);
+    assert_that!(limiter.pass(&other, start)).is_true();
+
+    assert_that!(limiter.timeout(start)).is_equal_to(Some(Duration::from_secs(10)));
+    assert_that!(limiter.expire(start + Duration::from_secs(5))).is_empty();
+
+    let later = start + Duration::from_secs(10);
+    assert_that!(limiter.expire(later)).is_equal_to(vec![
+      "suppressed 3 similar lines (heartbeats: at most 2 per 10s)".to_string()
+    ]);
+
+    // a new window starts with the next match
+    assert_that!(limiter.pass(&heartbeat, later)).is_true();
+    assert_that!(limiter.timeout(later)).is_none();
+    assert_that!(limiter.finish()).is_empty();
+  }
+}
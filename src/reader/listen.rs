@@ -0,0 +1,183 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+use std::io::{BufRead, BufReader};
+use std::net::{SocketAddr, TcpListener, TcpStream, UdpSocket};
+use std::sync::Arc;
+use std::sync::mpsc::{Receiver, Sender};
+use std::thread::{self, JoinHandle};
+
+use simple_error::SimpleResult;
+
+use crate::config::Config;
+use crate::parser::ReaderMetadata;
+use crate::renderer::LogEntry;
+
+/// The address `woodchipper listen` binds to if none is given
+pub static DEFAULT_ADDRESS: &str = "127.0.0.1:5140";
+
+/// The largest UDP datagram read; longer ones are cut short
+static MAX_DATAGRAM: usize = 65_536;
+
+/// Sends a line from a sender, shown as a source named after its IP address
+/// so reconnecting doesn't start a new source; returns false if the renderer
+/// has quit
+fn send_line(config: &Arc<Config>, tx: &Sender<LogEntry>, peer: &SocketAddr, line: &str) -> bool {
+  let line = line.trim_end_matches(&['\n', '\r'][..]);
+  if line.is_empty() {
+    return true;
+  }
+
+  let meta = ReaderMetadata { timestamp: None, source: Some(peer.ip().to_string()) };
+  match LogEntry::message(Arc::clone(config), line, Some(meta)) {
+    Ok(Some(entry)) => tx.send(entry).is_ok(),
+    _ => true
+  }
+}
+
+/// Reads newline-delimited lines from a TCP connection until it closes
+fn read_stream(config: Arc<Config>, stream: TcpStream, tx: Sender<LogEntry>) {
+  let peer = match stream.peer_addr() {
+    Ok(peer) => peer,
+    Err(_) => return
+  };
+
+  tx.send(LogEntry::internal(&format!("{}: connected", peer))).ok();
+
+  let mut reader = BufReader::new(stream);
+  let mut buf = Vec::new();
+  loop {
+    buf.clear();
+    match reader.read_until(b'\n', &mut buf) {
+      Ok(0) | Err(_) => break,
+      Ok(_) => if !send_line(&config, &tx, &peer, &String::from_utf8_lossy(&buf)) {
+        return;
+      }
+    }
+  }
+
+  tx.send(LogEntry::internal(&format!("{}: disconnected", peer))).ok();
+}
+
+/// Reads datagrams, each holding one or more lines, e.g. from syslog
+fn read_datagrams(config: Arc<Config>, socket: UdpSocket, tx: Sender<LogEntry>) {
+  let mut buf = vec![0; MAX_DATAGRAM];
+  while let Ok((len, peer)) = socket.recv_from(&mut buf) {
+    for line in String::from_utf8_lossy(&buf[..len]).lines() {
+      if !send_line(&config, &tx, &peer, line) {
+        return;
+      }
+    }
+  }
+}
+
+/// Accepts connections and datagrams on already bound sockets, reading each
+/// connection on its own thread
+fn serve(
+  config: Arc<Config>, listener: TcpListener, socket: Option<UdpSocket>, tx: Sender<LogEntry>
+) {
+  if let Some(socket) = socket {
+    let config = Arc::clone(&config);
+    let tx = tx.clone();
+    thread::spawn(move || read_datagrams(config, socket, tx));
+  }
+
+  for stream in listener.incoming().flatten() {
+    let config = Arc::clone(&config);
+    let tx = tx.clone();
+    thread::spawn(move || read_stream(config, stream, tx));
+  }
+}
+
+/// Reads lines sent to `--listen` (or `woodchipper listen`) over TCP, or in
+/// UDP datagrams on the same port, with each sender shown as its own source
+///
+/// Like stdin, listening never ends on its own, so it's left to the OS to
+/// clean up when woodchipper exits.
+pub fn read_listen(
+  config: Arc<Config>,
+  tx: Sender<LogEntry>,
+  _exit_req_rx: Receiver<()>,
+  _exit_resp_tx: Sender<()>
+) -> JoinHandle<SimpleResult<()>> {
+  thread::Builder::new().name("read_listen".to_string()).spawn(move || {
+    let address = config.listen.clone().unwrap_or_else(|| DEFAULT_ADDRESS.to_string());
+
+    let listener = match TcpListener::bind(&address) {
+      Ok(listener) => listener,
+      Err(e) => {
+        tx.send(LogEntry::internal(&format!("could not listen on {}: {}", address, e))).ok();
+        tx.send(LogEntry::eof()).ok();
+        return Ok(());
+      }
+    };
+
+    // the same port as TCP, in case the address asked for any free port
+    let bound = listener.local_addr().map(|a| a.to_string()).unwrap_or(address);
+    let socket = match UdpSocket::bind(&bound) {
+      Ok(socket) => {
+        tx.send(LogEntry::internal(&format!("listening on {} (tcp and udp)", bound))).ok();
+        Some(socket)
+      },
+      Err(e) => {
+        tx.send(LogEntry::internal(
+          &format!("listening on {} (tcp only, udp failed: {})", bound, e)
+        )).ok();
+        None
+      }
+    };
+
+    serve(config, listener, socket, tx);
+
+    Ok(())
+  }).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::io::Write;
+  use std::sync::mpsc::channel;
+  use std::time::Duration;
+
+  use spectral::prelude::*;
+  use structopt::StructOpt;
+
+  #[test]
+  fn test_listen() {
+    let config = Arc::new(Config::from_iter_safe(vec![""]).unwrap());
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let socket = UdpSocket::bind(address).unwrap();
+
+    let (tx, rx) = channel::<LogEntry>();
+    thread::spawn(move || serve(config, listener, Some(socket), tx));
+
+    let mut stream = TcpStream::connect(address).unwrap();
+    stream.write_all(b"over tcp\r\n").unwrap();
+    drop(stream);
+
+    let client = UdpSocket::bind("127.0.0.1:0").unwrap();
+    client.send_to(b"<34>over udp\nand again", address).unwrap();
+
+    let mut messages = Vec::new();
+    while messages.len() < 3 {
+      let entry = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+      if let Some(entry) = entry.message {
+        let source = entry.message.reader_metadata.and_then(|m| m.source);
+        if source.is_some() {
+          messages.push((entry.message.text.unwrap_or_default(), source));
+        }
+      }
+    }
+
+    // with syslog priorities handled as usual by the parser
+    messages.sort();
+    let local = Some("127.0.0.1".to_string());
+    assert_that!(messages).is_equal_to(vec![
+      ("and again".to_string(), local.clone()),
+      ("over tcp".to_string(), local.clone()),
+      ("over udp".to_string(), local)
+    ]);
+  }
+}
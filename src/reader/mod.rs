@@ -4,12 +4,42 @@ pub mod types;
 pub mod stdin;
 pub mod stdin_hack;
 pub mod kubernetes;
+pub mod exec;
+pub mod session;
+pub mod file;
+pub mod listen;
 pub mod null;
+pub mod msgpack;
+pub mod protobuf;
 pub mod ordered;
+pub mod multiline;
+pub mod filter;
+pub mod limit;
+pub mod anomaly;
+pub mod redact;
+pub mod trigger;
+pub mod tee;
+pub mod split;
+pub mod sink;
 
 pub use types::Reader;
 pub use stdin::read_stdin;
 pub use stdin_hack::read_stdin_hack;
 pub use kubernetes::read_kubernetes_selector;
+pub use exec::read_exec;
+pub use session::read_session;
+pub use file::read_file;
+pub use listen::read_listen;
 pub use null::read_null;
+pub use msgpack::read_msgpack;
+pub use protobuf::read_protobuf;
 pub(crate) use ordered::read_ordered;
+pub(crate) use multiline::read_multiline;
+pub(crate) use filter::read_filter;
+pub(crate) use limit::read_limit;
+pub(crate) use anomaly::read_anomaly;
+pub(crate) use redact::read_redact;
+pub(crate) use trigger::read_trigger;
+pub(crate) use tee::read_tee;
+pub(crate) use split::read_split;
+pub(crate) use sink::read_sink;
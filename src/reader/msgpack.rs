@@ -0,0 +1,78 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::sync::Arc;
+use std::sync::mpsc::{Receiver, Sender};
+use std::thread::{self, JoinHandle};
+
+use simple_error::SimpleResult;
+
+use crate::config::Config;
+use crate::parser::ReaderMetadata;
+use crate::parser::msgpack::{read_value, records};
+use crate::renderer::LogEntry;
+
+/// Reads a stream of msgpack-encoded records from stdin
+///
+/// Values are decoded back-to-back with no line splitting, so each map (or
+/// fluentd forward protocol entry) becomes one message. As with the hack
+/// reader, /dev/stdin is preferred where available so the interactive renderer
+/// keeps working.
+pub fn read_msgpack(
+  config: Arc<Config>,
+  tx: Sender<LogEntry>,
+  _exit_req_rx: Receiver<()>,
+  _exit_resp_tx: Sender<()>
+) -> JoinHandle<SimpleResult<()>> {
+  thread::Builder::new().name("read_msgpack".to_string()).spawn(move || {
+    let input: Box<dyn Read> = match File::open("/dev/stdin") {
+      Ok(file) => Box::new(file),
+      Err(_) => Box::new(io::stdin())
+    };
+    let mut input = BufReader::new(input);
+
+    let mut empty = true;
+    loop {
+      let value = match read_value(&mut input) {
+        Ok(Some(value)) => value,
+        Ok(None) => break,
+        Err(e) => {
+          // there's no way to resynchronize a corrupt stream, so give up
+          tx.send(LogEntry::internal(&format!(
+            "error: could not decode msgpack input: {}", e
+          ))).ok();
+          break;
+        }
+      };
+
+      for record in records(value) {
+        empty = false;
+
+        let meta = match (record.time, record.tag) {
+          (None, None) => None,
+          (timestamp, source) => Some(ReaderMetadata { timestamp, source })
+        };
+
+        match LogEntry::record(Arc::clone(&config), record.doc, meta) {
+          Ok(Some(entry)) => if tx.send(entry).is_err() {
+            // assume receiver has quit and stop
+            return Ok(());
+          },
+          _ => continue
+        }
+      }
+    }
+
+    if empty {
+      tx.send(LogEntry::internal(
+        "warning: reached end of input without reading any messages"
+      )).ok();
+    }
+
+    // not much we can do if this fails
+    tx.send(LogEntry::eof()).ok();
+
+    Ok(())
+  }).unwrap()
+}
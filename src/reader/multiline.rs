@@ -0,0 +1,258 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::sync::mpsc::{Receiver, RecvTimeoutError, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use regex::{Regex, RegexSet};
+use simple_error::SimpleResult;
+
+use crate::classifier::classify;
+use crate::config::Config;
+use crate::parser::{Message, MessageKind};
+use crate::renderer::{LogEntry, MessageEntry};
+
+/// The length of time a message is held waiting for continuation lines
+const HOLD_MS: u64 = 100;
+
+/// A message waiting for continuation lines
+struct PendingEntry {
+  /// Time the last line was folded into this entry
+  received: Instant,
+
+  /// If true, lines have been folded in and chunks need to be recalculated
+  folded: bool,
+
+  entry: MessageEntry
+}
+
+impl PendingEntry {
+  fn new(entry: MessageEntry) -> Self {
+    PendingEntry {
+      received: Instant::now(),
+      folded: false,
+      entry
+    }
+  }
+
+  fn fold(&mut self, line: &Message) {
+    let message = &mut self.entry.message;
+
+    // some formats (e.g. Microsoft.Extensions.Logging) put the entire message
+    // on continuation lines
+    message.text = match message.text.take() {
+      Some(text) => Some(format!("{}\n{}", text, expand_tabs(&line.raw))),
+      None => Some(line.raw.trim().to_string())
+    };
+    message.raw = format!("{}\n{}", message.raw, line.raw);
+
+    self.received = Instant::now();
+    self.folded = true;
+  }
+
+  fn into_entry(self) -> MessageEntry {
+    let mut entry = self.entry;
+    if self.folded {
+      entry.chunks = classify(&entry.message);
+    }
+
+    entry
+  }
+}
+
+/// Replaces leading tabs (as used by Java stack frames) with spaces so they
+/// don't throw off width calculations
+fn expand_tabs(line: &str) -> String {
+  let trimmed = line.trim_start_matches('\t');
+  let tabs = line.len() - trimmed.len();
+
+  format!("{}{}", "    ".repeat(tabs), trimmed)
+}
+
+/// Determines if `line` should be folded into the preceding message, `prev`
+pub fn is_continuation(prev: &Message, line: &Message) -> bool {
+  lazy_static! {
+    // lines that always continue the previous message, e.g. java stack frames
+    static ref FRAME_RE: RegexSet = RegexSet::new([
+      r"^\s+at \S",
+      r"^\s*\.\.\. \d+ (more|common frames omitted)",
+      r"^Caused by: ",
+      r"^\s*--- End of (inner exception )?stack trace",
+    ]).unwrap();
+
+    // a java exception header, e.g. `java.lang.IllegalStateException: oops`
+    static ref EXCEPTION_RE: Regex = Regex::new(
+      r"^[\w$]+(\.[\w$]+)+(Exception|Error|Throwable)(: .*)?$"
+    ).unwrap();
+
+    static ref INDENT_RE: Regex = Regex::new(r"^\s+\S").unwrap();
+  }
+
+  if line.kind != MessageKind::Plain || prev.kind == MessageKind::Internal {
+    return false;
+  }
+
+  if FRAME_RE.is_match(&line.raw) {
+    return true;
+  }
+
+  // only fold less specific lines into messages a parser actually understood,
+  // plain text is often indented on purpose
+  prev.kind != MessageKind::Plain && (
+    EXCEPTION_RE.is_match(&line.raw) || INDENT_RE.is_match(&line.raw)
+  )
+}
+
+fn source_of(entry: &MessageEntry) -> Option<String> {
+  entry.message.reader_metadata.as_ref().and_then(|m| m.source.clone())
+}
+
+/// A wrapping reader that folds continuation lines, like stack traces, into
+/// the message that preceded them.
+///
+/// Messages are tracked per source, so interleaved multi-line messages from
+/// several Kubernetes pods are kept separate.
+pub fn read_multiline(
+  _config: Arc<Config>,
+  rx: Receiver<LogEntry>,
+  tx: Sender<LogEntry>,
+) -> JoinHandle<SimpleResult<()>> {
+  thread::Builder::new().name("read_multiline".to_string()).spawn(move || {
+    let hold = Duration::from_millis(HOLD_MS);
+    let mut pending: HashMap<Option<String>, PendingEntry> = HashMap::new();
+
+    let send = |pending: PendingEntry| {
+      tx.send(LogEntry {
+        message: Some(pending.into_entry()),
+        ..Default::default()
+      }).ok();
+    };
+
+    loop {
+      let timeout = pending.values()
+        .map(|p| hold.checked_sub(p.received.elapsed()).unwrap_or_default())
+        .min();
+
+      let received = match timeout {
+        Some(timeout) => rx.recv_timeout(timeout),
+        None => rx.recv().map_err(|_| RecvTimeoutError::Disconnected)
+      };
+
+      match received {
+        Ok(LogEntry { message: Some(entry), .. }) => {
+          if entry.message.kind == MessageKind::Internal {
+            tx.send(LogEntry { message: Some(entry), ..Default::default() }).ok();
+            continue;
+          }
+
+          let source = source_of(&entry);
+          if let Some(prev) = pending.get_mut(&source) {
+            if is_continuation(&prev.entry.message, &entry.message) {
+              prev.fold(&entry.message);
+              continue;
+            }
+          }
+
+          if let Some(prev) = pending.insert(source, PendingEntry::new(entry)) {
+            send(prev);
+          }
+        },
+        Ok(LogEntry { eof: Some(_), .. }) => {
+          pending.drain().for_each(|(_, p)| send(p));
+          tx.send(LogEntry::eof()).ok();
+          break;
+        },
+        Ok(entry) => {
+          tx.send(entry).ok();
+        },
+        Err(RecvTimeoutError::Timeout) => {
+          let expired: Vec<Option<String>> = pending.iter()
+            .filter(|(_, p)| p.received.elapsed() >= hold)
+            .map(|(source, _)| source.clone())
+            .collect();
+
+          for source in expired {
+            if let Some(p) = pending.remove(&source) {
+              send(p);
+            }
+          }
+        },
+        Err(RecvTimeoutError::Disconnected) => {
+          pending.drain().for_each(|(_, p)| send(p));
+          break;
+        }
+      }
+    }
+
+    Ok(())
+  }).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use spectral::prelude::*;
+
+  fn message(kind: MessageKind, raw: &str) -> Message {
+    Message {
+      kind,
+      timestamp: None,
+      level: None,
+      raw: raw.to_string(),
+      text: Some(raw.to_string()),
+      metadata: HashMap::new(),
+      reader_metadata: None,
+      mapped_fields: HashMap::new()
+    }
+  }
+
+  #[test]
+  fn test_java_frames() {
+    let prev = message(MessageKind::Plain, "Exception in thread \"main\"");
+
+    for line in &[
+      "\tat com.example.Foo.bar(Foo.java:10)",
+      "    at com.example.Foo.main(Foo.java:4)",
+      "\t... 12 more",
+      "Caused by: java.lang.NullPointerException",
+      "   --- End of inner exception stack trace ---",
+    ] {
+      let line = message(MessageKind::Plain, line);
+      assert_that!(is_continuation(&prev, &line)).is_true();
+    }
+  }
+
+  #[test]
+  fn test_structured_continuation() {
+    let spring = message(MessageKind::Spring, "2020-01-01 ... : failed");
+    let plain = message(MessageKind::Plain, "hello");
+
+    let header = message(MessageKind::Plain, "java.lang.IllegalStateException: oops");
+    assert_that!(is_continuation(&spring, &header)).is_true();
+    assert_that!(is_continuation(&plain, &header)).is_false();
+
+    let indented = message(MessageKind::Plain, "  details");
+    assert_that!(is_continuation(&spring, &indented)).is_true();
+    assert_that!(is_continuation(&plain, &indented)).is_false();
+  }
+
+  #[test]
+  fn test_not_continuation() {
+    let spring = message(MessageKind::Spring, "2020-01-01 ... : failed");
+
+    let next = message(MessageKind::Spring, "\tat com.example.Foo.bar(Foo.java:10)");
+    assert_that!(is_continuation(&spring, &next)).is_false();
+
+    let plain = message(MessageKind::Plain, "unrelated");
+    assert_that!(is_continuation(&spring, &plain)).is_false();
+  }
+
+  #[test]
+  fn test_expand_tabs() {
+    assert_that!(expand_tabs("\tat foo")).is_equal_to("    at foo".to_string());
+    assert_that!(expand_tabs("a\tb")).is_equal_to("a\tb".to_string());
+  }
+}
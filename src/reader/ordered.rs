@@ -109,7 +109,7 @@ pub fn read_ordered(
           if let MessageKind::Internal = message.message.kind {
             tx.send(LogEntry {
               message: Some(message),
-              eof: None
+              ..Default::default()
             }).ok();
           } else {
             heap.push(TimestampedEntry::new(message));
@@ -119,6 +119,8 @@ pub fn read_ordered(
           // quit and send immediately (buffered messages will be discarded)
           tx.send(LogEntry::eof()).ok();
           break 'outer;
+        } else if unbuffered_entry.status.is_some() {
+          tx.send(unbuffered_entry).ok();
         }
       }
 
@@ -132,7 +134,7 @@ pub fn read_ordered(
 
           tx.send(LogEntry {
             message: Some(real_entry),
-            eof: None
+            ..Default::default()
           }).ok();
         } else {
           break;
@@ -0,0 +1,96 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+use std::fs::File;
+use std::io::{self, BufReader, Read};
+use std::sync::Arc;
+use std::sync::mpsc::{Receiver, Sender};
+use std::thread::{self, JoinHandle};
+
+use simple_error::{SimpleError, SimpleResult};
+
+use crate::config::Config;
+use crate::parser::protobuf::read_delimited;
+use crate::renderer::LogEntry;
+
+/// Reads varint-length-delimited protobuf messages from stdin
+///
+/// Messages are decoded using the `--proto-descriptors` and `--proto-message`
+/// options, and each becomes a structured record.
+pub fn read_protobuf(
+  config: Arc<Config>,
+  tx: Sender<LogEntry>,
+  _exit_req_rx: Receiver<()>,
+  _exit_resp_tx: Sender<()>
+) -> JoinHandle<SimpleResult<()>> {
+  thread::Builder::new().name("read_protobuf".to_string()).spawn(move || {
+    let fail = |message: String| {
+      tx.send(LogEntry::internal(&message)).ok();
+      tx.send(LogEntry::eof()).ok();
+      Err(SimpleError::new(message))
+    };
+
+    let (descriptors, message_type) = match (&config.proto_descriptors, &config.proto_message) {
+      (Some(d), Some(m)) => (d, m),
+      _ => return fail(
+        "error: the protobuf reader requires --proto-descriptors and --proto-message".to_string()
+      )
+    };
+
+    if !descriptors.has_message(message_type) {
+      return fail(format!("error: unknown protobuf message type: {}", message_type));
+    }
+
+    let input: Box<dyn Read> = match File::open("/dev/stdin") {
+      Ok(file) => Box::new(file),
+      Err(_) => Box::new(io::stdin())
+    };
+    let mut input = BufReader::new(input);
+
+    let mut empty = true;
+    loop {
+      let buf = match read_delimited(&mut input) {
+        Ok(Some(buf)) => buf,
+        Ok(None) => break,
+        Err(e) => {
+          // lengths can't be trusted after a bad read, so give up
+          tx.send(LogEntry::internal(&format!(
+            "error: could not read protobuf input: {}", e
+          ))).ok();
+          break;
+        }
+      };
+
+      empty = false;
+
+      let entry = descriptors.decode(message_type, &buf)
+        .map_err(|e| e.to_string())
+        .and_then(|doc| {
+          LogEntry::record(Arc::clone(&config), doc, None).map_err(|e| e.to_string())
+        });
+
+      let entry = match entry {
+        Ok(Some(entry)) => entry,
+        Ok(None) => continue,
+        Err(e) => LogEntry::internal(&format!(
+          "warning: could not decode protobuf message: {}", e
+        ))
+      };
+
+      if tx.send(entry).is_err() {
+        // assume receiver has quit and stop
+        return Ok(());
+      }
+    }
+
+    if empty {
+      tx.send(LogEntry::internal(
+        "warning: reached end of input without reading any messages"
+      )).ok();
+    }
+
+    // not much we can do if this fails
+    tx.send(LogEntry::eof()).ok();
+
+    Ok(())
+  }).unwrap()
+}
@@ -0,0 +1,274 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+use std::borrow::Cow;
+use std::sync::Arc;
+use std::sync::mpsc::{Receiver, Sender};
+use std::thread::{self, JoinHandle};
+
+use regex::Regex;
+use serde::Deserialize;
+use serde::de::{self, Deserializer};
+use serde_json::Value;
+use simple_error::SimpleResult;
+
+use crate::config::Config;
+use crate::parser::{Message, MessageKind};
+use crate::renderer::LogEntry;
+
+/// The text masked values are replaced with, unless set
+static DEFAULT_MASK: &str = "[redacted]";
+
+/// Field values shorter than this aren't also masked in the original line, as
+/// they'd likely match unrelated text
+static MIN_RAW_MASK_LEN: usize = 4;
+
+/// Common kinds of sensitive values, masked wherever they appear
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RedactPreset {
+  /// Email addresses
+  Email,
+
+  /// Runs of 13 to 19 digits, optionally separated by spaces or dashes, like
+  /// credit card numbers
+  Card,
+
+  /// `Bearer` tokens, e.g. from `Authorization` headers
+  Bearer
+}
+
+impl RedactPreset {
+  fn regex(self) -> &'static Regex {
+    lazy_static! {
+      static ref EMAIL: Regex = Regex::new(
+        r"[A-Za-z0-9._%+-]+@[A-Za-z0-9-]+(\.[A-Za-z0-9-]+)*\.[A-Za-z]{2,}"
+      ).unwrap();
+      static ref CARD: Regex = Regex::new(r"\b\d(?:[ -]?\d){12,18}\b").unwrap();
+      static ref BEARER: Regex = Regex::new(r"(?i)\bbearer\s+[A-Za-z0-9._~+/=-]+").unwrap();
+    }
+
+    match self {
+      RedactPreset::Email => &EMAIL,
+      RedactPreset::Card => &CARD,
+      RedactPreset::Bearer => &BEARER
+    }
+  }
+}
+
+fn de_regexes<'de, D>(deserializer: D) -> Result<Vec<Regex>, D::Error>
+where
+  D: Deserializer<'de>
+{
+  Vec::<String>::deserialize(deserializer)?.iter()
+    .map(|s| Regex::new(s).map_err(|e| de::Error::custom(format!(
+      "invalid redaction regex {:?}: {}", s, e
+    ))))
+    .collect()
+}
+
+/// The config file's `redact` section, masking secrets and personal data
+/// before messages are filtered, rendered, or written anywhere, e.g.:
+///
+/// ```yaml
+/// redact:
+///   fields: ['(?i)^authorization$', '(?i)password|secret']
+///   patterns: ['sk_live_[A-Za-z0-9]+']
+///   presets: [email, card, bearer]
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct RedactConfig {
+  /// Regexes matching field names, at any depth, whose values are masked
+  /// entirely
+  #[serde(deserialize_with = "de_regexes")]
+  pub fields: Vec<Regex>,
+
+  /// Regexes masked wherever they match in message text and field values
+  #[serde(deserialize_with = "de_regexes")]
+  pub patterns: Vec<Regex>,
+
+  /// Built-in patterns to mask
+  pub presets: Vec<RedactPreset>,
+
+  /// The replacement for masked values, `[redacted]` by default
+  pub mask: Option<String>
+}
+
+impl RedactConfig {
+  pub fn is_empty(&self) -> bool {
+    self.fields.is_empty() && self.patterns.is_empty() && self.presets.is_empty()
+  }
+
+  fn mask(&self) -> &str {
+    self.mask.as_deref().unwrap_or(DEFAULT_MASK)
+  }
+
+  fn patterns(&self) -> impl Iterator<Item = &Regex> {
+    self.patterns.iter().chain(self.presets.iter().map(|p| p.regex()))
+  }
+
+  /// Masks every pattern in some text
+  fn mask_text<'a>(&self, text: &'a str) -> Cow<'a, str> {
+    let mut text = Cow::Borrowed(text);
+    for re in self.patterns() {
+      if let Cow::Owned(masked) = re.replace_all(&text, self.mask()) {
+        text = Cow::Owned(masked);
+      }
+    }
+
+    text
+  }
+
+  /// Masks a field value, collecting the text of any values masked by field
+  /// name so they can be masked in the original line too
+  fn mask_value(&self, key: &str, value: &mut Value, masked: &mut Vec<String>) {
+    if self.fields.iter().any(|re| re.is_match(key)) {
+      match value {
+        Value::Null => return,
+        Value::String(s) => masked.push(s.clone()),
+        Value::Number(n) => masked.push(n.to_string()),
+        _ => ()
+      }
+
+      *value = Value::String(self.mask().to_string());
+      return;
+    }
+
+    match value {
+      Value::String(s) => {
+        if let Cow::Owned(m) = self.mask_text(s) {
+          *s = m;
+        }
+      },
+      Value::Number(n) => {
+        let text = n.to_string();
+        if let Cow::Owned(m) = self.mask_text(&text) {
+          *value = Value::String(m);
+        }
+      },
+      Value::Array(values) => {
+        for v in values.iter_mut() {
+          self.mask_value(key, v, masked);
+        }
+      },
+      Value::Object(map) => {
+        for (k, v) in map.iter_mut() {
+          self.mask_value(k, v, masked);
+        }
+      },
+      Value::Bool(_) | Value::Null => ()
+    }
+  }
+
+  /// Masks a message's text, fields, and original line
+  pub fn redact(&self, message: &mut Message) {
+    let mut masked = Vec::new();
+    for (key, value) in message.metadata.iter_mut() {
+      self.mask_value(key, value, &mut masked);
+    }
+
+    if let Some(text) = &message.text {
+      if let Cow::Owned(m) = self.mask_text(text) {
+        message.text = Some(m);
+      }
+    }
+
+    let mut raw = match self.mask_text(&message.raw) {
+      Cow::Owned(m) => m,
+      Cow::Borrowed(_) => message.raw.clone()
+    };
+
+    // longest first, in case values contain one another
+    masked.sort_by_key(|s| std::cmp::Reverse(s.len()));
+    for value in masked.iter().filter(|v| v.chars().count() >= MIN_RAW_MASK_LEN) {
+      raw = raw.replace(value.as_str(), self.mask());
+    }
+
+    message.raw = raw;
+  }
+}
+
+/// A wrapping reader that masks values per the config file's `redact`
+/// section, before anything else sees them
+pub fn read_redact(
+  config: Arc<Config>,
+  rx: Receiver<LogEntry>,
+  tx: Sender<LogEntry>,
+) -> JoinHandle<SimpleResult<()>> {
+  thread::Builder::new().name("read_redact".to_string()).spawn(move || {
+    let redact = &config.file().redact;
+
+    for mut entry in rx {
+      if let Some(message) = &mut entry.message {
+        if message.message.kind != MessageKind::Internal {
+          redact.redact(&mut message.message);
+        }
+      }
+
+      if tx.send(entry).is_err() {
+        break;
+      }
+    }
+
+    Ok(())
+  }).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use serde_json::json;
+  use spectral::prelude::*;
+
+  use crate::parser::test_message;
+
+  fn redacted() -> Message {
+    let redact: RedactConfig = serde_yaml::from_str(concat!(
+      "fields: ['(?i)^authorization$', 'password']\n",
+      "patterns: ['sk_live_[a-z0-9]+']\n",
+      "presets: [email, card]\n",
+      "mask: '***'\n"
+    )).unwrap();
+
+    let mut message = test_message(concat!(
+      r#"{"msg": "charged jo@example.com card 4111 1111 1111 1111", "#,
+      r#""headers": {"Authorization": "Basic dXNlcjpwYXNz"}, "password": "hunter22", "#,
+      r#""key": "sk_live_abc123", "order": 42}"#
+    ), None);
+
+    redact.redact(&mut message);
+    message
+  }
+
+  #[test]
+  fn test_redact_fields() {
+    let message = redacted();
+    assert_that!(message.metadata["headers"]).is_equal_to(json!({"Authorization": "***"}));
+    assert_that!(message.metadata["password"]).is_equal_to(json!("***"));
+    assert_that!(message.metadata["order"]).is_equal_to(json!(42));
+  }
+
+  #[test]
+  fn test_redact_patterns() {
+    let message = redacted();
+    assert_that!(message.text).is_equal_to(Some("charged *** card ***".to_string()));
+    assert_that!(message.metadata["key"]).is_equal_to(json!("***"));
+  }
+
+  #[test]
+  fn test_redact_raw() {
+    let message = redacted();
+    for secret in &["jo@example.com", "4111", "dXNlcjpwYXNz", "hunter22", "sk_live"] {
+      assert_that!(message.raw.contains(secret)).is_false();
+    }
+
+    assert_that!(message.raw.contains("\"order\": 42")).is_true();
+  }
+
+  #[test]
+  fn test_invalid_config() {
+    assert_that!(serde_yaml::from_str::<RedactConfig>("patterns: ['(']\n").is_err()).is_true();
+    assert_that!(serde_yaml::from_str::<RedactConfig>("presets: [ssn]\n").is_err()).is_true();
+  }
+}
@@ -0,0 +1,53 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+use std::sync::Arc;
+use std::sync::mpsc::{Receiver, Sender};
+use std::thread::{self, JoinHandle};
+
+use simple_error::SimpleResult;
+
+use crate::config::Config;
+use crate::renderer::LogEntry;
+use crate::session::SessionReader;
+
+/// Reads the records saved in a session snapshot, from `woodchipper open`
+///
+/// Records are sent as they were parsed when the snapshot was taken; the
+/// interactive renderer restores the rest of the view itself.
+pub fn read_session(
+  config: Arc<Config>,
+  tx: Sender<LogEntry>,
+  _exit_req_rx: Receiver<()>,
+  _exit_resp_tx: Sender<()>
+) -> JoinHandle<SimpleResult<()>> {
+  thread::Builder::new().name("read_session".to_string()).spawn(move || {
+    let path = match &config.open {
+      Some(path) => path,
+      None => {
+        tx.send(LogEntry::internal("no session to open, see woodchipper open")).ok();
+        tx.send(LogEntry::eof()).ok();
+        return Ok(());
+      }
+    };
+
+    match SessionReader::open(path) {
+      Ok(reader) => for message in reader {
+        match message {
+          Ok(message) => if tx.send(LogEntry::parsed(message)).is_err() {
+            break;
+          },
+          Err(e) => {
+            tx.send(LogEntry::internal(&format!("skipped a bad session record: {}", e))).ok();
+          }
+        }
+      },
+      Err(e) => {
+        tx.send(LogEntry::internal(&e.to_string())).ok();
+      }
+    }
+
+    tx.send(LogEntry::eof()).ok();
+
+    Ok(())
+  }).unwrap()
+}
@@ -0,0 +1,347 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+use std::fs::OpenOptions;
+use std::io::{self, BufWriter, Write};
+use std::path::PathBuf;
+use std::process::{Child, Command, Stdio};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::mpsc::{Receiver, Sender, TryRecvError};
+use std::thread::{self, JoinHandle};
+
+use simple_error::{SimpleError, SimpleResult};
+
+use crate::config::Config;
+use crate::parser::{Message, MessageKind};
+use crate::renderer::{LogEntry, NormalizedRecord};
+
+static SCHEMA: &str = "CREATE TABLE IF NOT EXISTS records (
+  id INTEGER PRIMARY KEY,
+  timestamp TEXT,
+  level TEXT,
+  message TEXT,
+  kind TEXT NOT NULL,
+  source TEXT,
+  fields TEXT NOT NULL,
+  raw TEXT NOT NULL
+);
+CREATE INDEX IF NOT EXISTS records_timestamp ON records (timestamp);";
+
+/// A database that records are written to, i.e. `--sink`
+#[derive(Debug, Clone, PartialEq)]
+pub enum Sink {
+  /// A SQLite database, written with the `sqlite3` command
+  Sqlite(PathBuf),
+
+  /// A SQL script that may be loaded later, e.g. with
+  /// `sqlite3 session.db < session.sql`
+  Sql(PathBuf)
+}
+
+impl FromStr for Sink {
+  type Err = SimpleError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let (kind, path) = match s.find(':') {
+      Some(i) => (&s[..i], &s[i + 1..]),
+      None => bail!("invalid sink, expected e.g. sqlite:session.db: {}", s)
+    };
+
+    if path.is_empty() {
+      bail!("sink path must not be empty: {}", s);
+    }
+
+    match kind {
+      "sqlite" => Ok(Sink::Sqlite(PathBuf::from(path))),
+      "sql" => Ok(Sink::Sql(PathBuf::from(path))),
+      _ => bail!("invalid sink type, expected sqlite or sql: {}", kind)
+    }
+  }
+}
+
+/// Quotes a SQL string literal
+fn quote(s: &str) -> String {
+  format!("'{}'", s.replace('\0', "").replace('\'', "''"))
+}
+
+fn quote_opt(s: Option<&str>) -> String {
+  s.map(quote).unwrap_or_else(|| "NULL".to_string())
+}
+
+/// The statement inserting a message, with the same columns as the normalized
+/// renderer and its fields as a JSON object
+fn insert_statement(message: &Message) -> String {
+  let record = NormalizedRecord::new(message);
+  let level = record.level.map(|l| l.to_string().to_lowercase());
+  let fields = serde_json::to_string(&record.fields).unwrap_or_else(|_| "{}".to_string());
+
+  format!(
+    "INSERT INTO records (timestamp, level, message, kind, source, fields, raw) \
+     VALUES ({}, {}, {}, {}, {}, {}, {});",
+    quote_opt(record.timestamp.as_deref()),
+    quote_opt(level.as_deref()),
+    quote_opt(record.message.as_deref()),
+    quote(&record.kind.to_string().to_lowercase()),
+    quote_opt(record.source.as_deref()),
+    quote(&fields),
+    quote(&message.raw)
+  )
+}
+
+struct SinkWriter {
+  out: Box<dyn Write + Send>,
+  child: Option<Child>,
+  in_transaction: bool
+}
+
+impl SinkWriter {
+  /// Opens the sink, replacing any existing SQL script unless `append` is set
+  fn open(sink: &Sink, append: bool) -> io::Result<SinkWriter> {
+    let (out, child): (Box<dyn Write + Send>, _) = match sink {
+      Sink::Sqlite(path) => {
+        let mut child = Command::new("sqlite3")
+          .arg("-batch")
+          .arg(path)
+          .stdin(Stdio::piped())
+          .stdout(Stdio::null())
+          .spawn()?;
+
+        let stdin = child.stdin.take()
+          .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "sqlite3 has no stdin"))?;
+
+        (Box::new(BufWriter::new(stdin)), Some(child))
+      },
+      Sink::Sql(path) => {
+        let file = OpenOptions::new()
+          .write(true)
+          .create(true)
+          .append(append)
+          .truncate(!append)
+          .open(path)?;
+
+        (Box::new(BufWriter::new(file)), None)
+      }
+    };
+
+    let mut writer = SinkWriter { out, child, in_transaction: false };
+    writeln!(writer.out, "{}", SCHEMA)?;
+    writer.out.flush()?;
+
+    Ok(writer)
+  }
+
+  fn insert(&mut self, message: &Message) -> io::Result<()> {
+    if !self.in_transaction {
+      writeln!(self.out, "BEGIN;")?;
+      self.in_transaction = true;
+    }
+
+    writeln!(self.out, "{}", insert_statement(message))
+  }
+
+  /// Commits any pending inserts, so they're saved even if we exit abruptly
+  fn commit(&mut self) -> io::Result<()> {
+    if self.in_transaction {
+      writeln!(self.out, "COMMIT;")?;
+      self.in_transaction = false;
+    }
+
+    self.out.flush()
+  }
+
+  /// Commits any pending inserts, and waits for `sqlite3` to finish writing
+  fn close(mut self) {
+    self.commit().ok();
+    drop(self.out);
+
+    if let Some(mut child) = self.child.take() {
+      child.wait().ok();
+    }
+  }
+}
+
+/// A wrapping reader that writes all messages to the `--sink` database, while
+/// passing them through to the renderer
+///
+/// Inserts are committed whenever the input goes quiet, so bursts of messages
+/// are written in a single transaction.
+pub fn read_sink(
+  config: Arc<Config>,
+  rx: Receiver<LogEntry>,
+  tx: Sender<LogEntry>,
+) -> JoinHandle<SimpleResult<()>> {
+  thread::Builder::new().name("read_sink".to_string()).spawn(move || {
+    let open = |sink: &Sink, append: bool| match SinkWriter::open(sink, append) {
+      Ok(writer) => Some(writer),
+      Err(e) => {
+        let hint = match sink {
+          Sink::Sqlite(_) => " (is sqlite3 installed? try --sink sql:FILE instead)",
+          Sink::Sql(_) => ""
+        };
+
+        tx.send(LogEntry::internal(&format!(
+          "error: could not open sink: {}{}", e, hint
+        ))).ok();
+
+        None
+      }
+    };
+
+    let mut writer = config.sink.as_ref().and_then(|sink| open(sink, false));
+
+    // the writer is closed at each eof, and reopened if e.g. a command restarts
+    let mut sink = config.sink.as_ref().filter(|_| writer.is_some());
+
+    let mut next = rx.recv().ok();
+    while let Some(entry) = next {
+      if let (Some(s), Some(message)) = (sink, &entry.message) {
+        if message.message.kind != MessageKind::Internal {
+          if writer.is_none() {
+            writer = open(s, true);
+          }
+
+          let result = match &mut writer {
+            Some(w) => w.insert(&message.message),
+            None => Ok(())
+          };
+
+          if let Err(e) = result {
+            tx.send(LogEntry::internal(&format!(
+              "error: could not write to sink, disabling: {}", e
+            ))).ok();
+
+            writer = None;
+          }
+
+          if writer.is_none() {
+            sink = None;
+          }
+        }
+      }
+
+      // the renderer may exit as soon as it sees the eof, so `sqlite3` must be
+      // done with the database first
+      if entry.eof.is_some() {
+        if let Some(w) = writer.take() {
+          w.close();
+        }
+      }
+
+      if tx.send(entry).is_err() {
+        break;
+      }
+
+      next = match rx.try_recv() {
+        Ok(entry) => Some(entry),
+        Err(TryRecvError::Empty) => {
+          if let Some(w) = &mut writer {
+            w.commit().ok();
+          }
+
+          rx.recv().ok()
+        },
+        Err(TryRecvError::Disconnected) => None
+      };
+    }
+
+    if let Some(w) = writer {
+      w.close();
+    }
+
+    Ok(())
+  }).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::collections::HashMap;
+  use std::env;
+  use std::fs;
+  use std::sync::mpsc::channel;
+
+  use serde_json::json;
+  use spectral::prelude::*;
+  use structopt::StructOpt;
+
+  use crate::parser::{LogLevel, test_message};
+
+  #[test]
+  fn test_insert_statement() {
+    let message = Message {
+      kind: MessageKind::Json,
+      timestamp: Some("2020-11-25T21:22:50Z".parse().unwrap()),
+      level: Some(LogLevel::Error),
+      text: Some("can't connect".to_string()),
+      metadata: hashmap!{
+        "http".to_string() => json!({"status": 503})
+      },
+      reader_metadata: None,
+      mapped_fields: HashMap::new(),
+      raw: "{}".to_string()
+    };
+
+    assert_that!(insert_statement(&message)).is_equal_to(
+      "INSERT INTO records (timestamp, level, message, kind, source, fields, raw) \
+       VALUES ('2020-11-25T21:22:50Z', 'error', 'can''t connect', 'json', NULL, \
+       '{\"http.status\":503}', '{}');".to_string()
+    );
+  }
+
+  #[test]
+  fn test_parse_sink() {
+    assert_that!("sqlite:session.db".parse::<Sink>())
+      .is_ok().is_equal_to(Sink::Sqlite(PathBuf::from("session.db")));
+    assert_that!("sql:out.sql".parse::<Sink>())
+      .is_ok().is_equal_to(Sink::Sql(PathBuf::from("out.sql")));
+    assert_that!("session.db".parse::<Sink>()).is_err();
+    assert_that!("postgres:db".parse::<Sink>()).is_err();
+  }
+
+  #[test]
+  fn test_read_sink() {
+    // sqlite3 isn't needed to build woodchipper, so may not be installed
+    if Command::new("sqlite3").arg("-version").output().is_err() {
+      return;
+    }
+
+    let path = env::temp_dir().join(format!("woodchipper-sink-{}.db", std::process::id()));
+    fs::remove_file(&path).ok();
+
+    let sink = format!("sqlite:{}", path.display());
+    let config = Arc::new(Config::from_iter_safe(vec!["", "--sink", &sink]).unwrap());
+    let (in_tx, in_rx) = channel();
+    let (out_tx, out_rx) = channel();
+    let handle = read_sink(config, in_rx, out_tx);
+
+    let count = || {
+      let output = Command::new("sqlite3")
+        .arg(&path)
+        .arg("SELECT count(*) FROM records;")
+        .output()
+        .unwrap();
+
+      String::from_utf8_lossy(&output.stdout).trim().to_string()
+    };
+
+    let send = |line: &str| in_tx.send(LogEntry::parsed(test_message(line, None))).unwrap();
+
+    // the database is complete by the time the eof is passed on
+    send(r#"{"level": "info", "msg": "one"}"#);
+    send(r#"{"level": "error", "msg": "two"}"#);
+    in_tx.send(LogEntry::eof()).unwrap();
+    assert_that!(out_rx.iter().any(|entry| entry.eof.is_some())).is_true();
+    assert_that!(count()).is_equal_to("2".to_string());
+
+    // and reopened if more messages follow, e.g. from a restarted command
+    send(r#"{"level": "info", "msg": "three"}"#);
+    in_tx.send(LogEntry::eof()).unwrap();
+    assert_that!(out_rx.iter().any(|entry| entry.eof.is_some())).is_true();
+    assert_that!(count()).is_equal_to("3".to_string());
+
+    drop(in_tx);
+    handle.join().unwrap().unwrap();
+    fs::remove_file(&path).ok();
+  }
+}
@@ -0,0 +1,327 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::mpsc::{Receiver, Sender};
+use std::thread::{self, JoinHandle};
+
+use serde_json::Value;
+use simple_error::{SimpleError, SimpleResult};
+
+use crate::config::Config;
+use crate::parser::{Message, MessageKind};
+use crate::renderer::LogEntry;
+use crate::reader::tee::{RotatingFile, format_entry};
+
+/// Used in file names when a placeholder has no value
+static UNKNOWN: &str = "unknown";
+
+/// The most split files kept open at once, so templates with many values don't
+/// run out of file descriptors
+const MAX_OPEN_FILES: usize = 64;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+  Literal(String),
+  Field(String)
+}
+
+/// A template for per-source file names, e.g. `logs/{namespace}_{pod}.log`
+///
+/// Placeholders may be any message field, or:
+///  - `source`: the reader's source, e.g. a Kubernetes pod and container
+///  - `pod` and `container`: the parts of a Kubernetes source
+///  - `namespace`: the Kubernetes namespace, if given with `--namespace`
+///
+/// Path separators in values are replaced, so each source maps to a single
+/// file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PathTemplate {
+  segments: Vec<Segment>
+}
+
+impl FromStr for PathTemplate {
+  type Err = SimpleError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let mut segments = Vec::new();
+    let mut rest = s;
+
+    while let Some(start) = rest.find('{') {
+      let end = match rest[start..].find('}') {
+        Some(end) => start + end,
+        None => bail!("unclosed '{{' in split template: {}", s)
+      };
+
+      let name = rest[start + 1..end].trim();
+      if name.is_empty() {
+        bail!("empty placeholder in split template: {}", s);
+      }
+
+      if start > 0 {
+        segments.push(Segment::Literal(rest[..start].to_string()));
+      }
+
+      segments.push(Segment::Field(name.to_string()));
+      rest = &rest[end + 1..];
+    }
+
+    if !rest.is_empty() {
+      segments.push(Segment::Literal(rest.to_string()));
+    }
+
+    if !segments.iter().any(|s| matches!(s, Segment::Field(_))) {
+      bail!("split template must contain at least one placeholder: {}", s);
+    }
+
+    Ok(PathTemplate { segments })
+  }
+}
+
+/// Makes a value safe to use as (part of) a single file name
+fn sanitize(value: &str) -> String {
+  let value: String = value.chars()
+    .map(|c| match c {
+      '/' | '\\' | ':' | '\0' => '_',
+      c if c.is_control() => '_',
+      c => c
+    })
+    .collect();
+
+  match value.as_str() {
+    "" | "." | ".." => UNKNOWN.to_string(),
+    _ => value
+  }
+}
+
+fn field_value(config: &Config, message: &Message, name: &str) -> Option<String> {
+  let source = || message.reader_metadata.as_ref().and_then(|m| m.source.clone());
+
+  let value = match message.field(name) {
+    Some(Value::String(s)) => Some(s),
+    Some(Value::Null) | None => None,
+    Some(value) => Some(value.to_string())
+  };
+
+  value.or_else(|| match name {
+    "pod" => source().map(|s| s.split('/').next().unwrap_or_default().to_string()),
+    "container" => source().and_then(|s| s.split('/').nth(1).map(str::to_string)),
+    "namespace" => config.kubernetes.namespace.clone(),
+    _ => None
+  })
+}
+
+impl PathTemplate {
+  pub fn render(&self, config: &Config, message: &Message) -> PathBuf {
+    let path: String = self.segments.iter()
+      .map(|segment| match segment {
+        Segment::Literal(s) => s.clone(),
+        Segment::Field(name) => field_value(config, message, name)
+          .map(|v| sanitize(&v))
+          .unwrap_or_else(|| UNKNOWN.to_string())
+      })
+      .collect();
+
+    PathBuf::from(path)
+  }
+}
+
+/// Split files by path, closing the least recently used once too many are open
+/// and reopening them for appending when needed again
+struct SplitFiles {
+  max_open: usize,
+
+  /// open files, and when each was last used
+  open: HashMap<PathBuf, (RotatingFile, u64)>,
+
+  /// files that failed to open or write, so errors are shown once
+  failed: HashSet<PathBuf>,
+
+  clock: u64
+}
+
+impl SplitFiles {
+  fn new(max_open: usize) -> Self {
+    SplitFiles { max_open, open: HashMap::new(), failed: HashSet::new(), clock: 0 }
+  }
+
+  /// Finds or opens the file for `path`, returning `None` if it previously
+  /// failed; errors are only returned the first time
+  fn get(&mut self, path: &Path) -> io::Result<Option<&mut RotatingFile>> {
+    if self.failed.contains(path) {
+      return Ok(None);
+    }
+
+    if !self.open.contains_key(path) {
+      if self.open.len() >= self.max_open {
+        let oldest = self.open.iter()
+          .min_by_key(|(_, (_, used))| *used)
+          .map(|(path, _)| path.clone());
+
+        if let Some((mut file, _)) = oldest.and_then(|oldest| self.open.remove(&oldest)) {
+          file.flush().ok();
+        }
+      }
+
+      if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).ok();
+      }
+
+      match RotatingFile::open(path, None, 0, false) {
+        Ok(file) => self.open.insert(path.to_path_buf(), (file, 0)),
+        Err(e) => {
+          self.failed.insert(path.to_path_buf());
+          return Err(e);
+        }
+      };
+    }
+
+    self.clock += 1;
+    let clock = self.clock;
+    Ok(self.open.get_mut(path).map(|entry| {
+      entry.1 = clock;
+      &mut entry.0
+    }))
+  }
+
+  /// Stops writing to a file after an error
+  fn disable(&mut self, path: &Path) {
+    self.open.remove(path);
+    self.failed.insert(path.to_path_buf());
+  }
+
+  fn flush(&mut self) {
+    for (file, _) in self.open.values_mut() {
+      file.flush().ok();
+    }
+  }
+}
+
+/// A wrapping reader that writes each source's messages to its own file, named
+/// by `--split`, while passing them through to the renderer
+pub fn read_split(
+  config: Arc<Config>,
+  rx: Receiver<LogEntry>,
+  tx: Sender<LogEntry>,
+) -> JoinHandle<SimpleResult<()>> {
+  thread::Builder::new().name("read_split".to_string()).spawn(move || {
+    let mut files = SplitFiles::new(MAX_OPEN_FILES);
+
+    for entry in rx {
+      if let (Some(template), Some(message)) = (&config.split, &entry.message) {
+        if message.message.kind != MessageKind::Internal {
+          let path = template.render(&config, &message.message);
+
+          let result = match files.get(&path) {
+            Ok(Some(f)) => format_entry(config.split_format, message).iter()
+              .try_for_each(|line| f.write_line(line)),
+            Ok(None) => Ok(()),
+            Err(e) => {
+              tx.send(LogEntry::internal(&format!(
+                "error: could not open split file {}: {}", path.display(), e
+              ))).ok();
+
+              Ok(())
+            }
+          };
+
+          if let Err(e) = result {
+            tx.send(LogEntry::internal(&format!(
+              "error: could not write to split file {}, disabling: {}", path.display(), e
+            ))).ok();
+
+            files.disable(&path);
+          }
+        }
+      }
+
+      if entry.eof.is_some() {
+        files.flush();
+      }
+
+      if tx.send(entry).is_err() {
+        break;
+      }
+    }
+
+    files.flush();
+
+    Ok(())
+  }).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use spectral::prelude::*;
+  use structopt::StructOpt;
+
+  use crate::parser::test_message;
+
+  fn message(source: Option<&str>) -> Message {
+    test_message(r#"{"msg": "hello", "app": "api"}"#, source)
+  }
+
+  fn render(template: &str, source: Option<&str>) -> PathBuf {
+    let config = Config::from_iter_safe(vec!["", "--namespace", "prod"]).unwrap();
+    template.parse::<PathTemplate>().unwrap().render(&config, &message(source))
+  }
+
+  #[test]
+  fn test_render_source() {
+    assert_that!(render("logs/{namespace}_{pod}.log", Some("api-7d9f8/sidecar")))
+      .is_equal_to(PathBuf::from("logs/prod_api-7d9f8.log"));
+
+    // separators are replaced, so each source is a single file
+    assert_that!(render("{source}-{container}.log", Some("api-7d9f8/sidecar")))
+      .is_equal_to(PathBuf::from("api-7d9f8_sidecar-sidecar.log"));
+  }
+
+  #[test]
+  fn test_render_fields() {
+    assert_that!(render("{app}/{pod}.log", Some("api-7d9f8")))
+      .is_equal_to(PathBuf::from("api/api-7d9f8.log"));
+  }
+
+  #[test]
+  fn test_render_missing() {
+    assert_that!(render("{source}-{container}-{app}.log", None))
+      .is_equal_to(PathBuf::from("unknown-unknown-api.log"));
+    assert_that!(render("{missing}.log", Some("api")))
+      .is_equal_to(PathBuf::from("unknown.log"));
+  }
+
+  #[test]
+  fn test_parse_template() {
+    assert_that!("{pod".parse::<PathTemplate>()).is_err();
+    assert_that!("{}.log".parse::<PathTemplate>()).is_err();
+    assert_that!("out.log".parse::<PathTemplate>()).is_err();
+  }
+
+  #[test]
+  fn test_max_open_files() {
+    let dir = std::env::temp_dir().join(format!("woodchipper-split-{}", std::process::id()));
+    let mut files = SplitFiles::new(2);
+
+    for (i, name) in ["a", "b", "c", "a", "b"].iter().enumerate() {
+      let file = files.get(&dir.join(name)).unwrap().unwrap();
+      file.write_line(&i.to_string()).unwrap();
+      assert_that!(files.open.len()).is_less_than_or_equal_to(2);
+    }
+
+    files.flush();
+    assert_that!(fs::read_to_string(dir.join("a")).unwrap()).is_equal_to("0\n3\n".to_string());
+    assert_that!(fs::read_to_string(dir.join("b")).unwrap()).is_equal_to("1\n4\n".to_string());
+    assert_that!(fs::read_to_string(dir.join("c")).unwrap()).is_equal_to("2\n".to_string());
+
+    files.disable(&dir.join("a"));
+    assert_that!(files.get(&dir.join("a")).unwrap().is_none()).is_true();
+
+    fs::remove_dir_all(&dir).ok();
+  }
+}
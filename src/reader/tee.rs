@@ -0,0 +1,271 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::mpsc::{Receiver, Sender};
+use std::thread::{self, JoinHandle};
+
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use simple_error::SimpleResult;
+
+use crate::config::{Config, TeeFormat};
+use crate::parser::MessageKind;
+use crate::renderer::{LogEntry, MessageEntry, plain_render};
+
+/// A file that is rotated once it grows past some size
+pub(crate) struct RotatingFile {
+  path: PathBuf,
+  file: BufWriter<File>,
+
+  /// bytes written to the current file, including any from before we opened it
+  size: u64,
+
+  max_size: Option<u64>,
+
+  /// the number of rotated files to keep
+  keep: usize,
+
+  gzip: bool
+}
+
+fn open_append(path: &Path) -> io::Result<(BufWriter<File>, u64)> {
+  let file = OpenOptions::new().create(true).append(true).open(path)?;
+  let size = file.metadata()?.len();
+
+  Ok((BufWriter::new(file), size))
+}
+
+/// Compresses a file to `<path>.gz`, removing the original
+fn gzip(path: &Path) -> io::Result<()> {
+  let mut gz_path = path.as_os_str().to_owned();
+  gz_path.push(".gz");
+
+  let mut input = File::open(path)?;
+  let mut encoder = GzEncoder::new(File::create(gz_path)?, Compression::default());
+  io::copy(&mut input, &mut encoder)?;
+  encoder.finish()?;
+
+  fs::remove_file(path)
+}
+
+impl RotatingFile {
+  pub(crate) fn open(path: &Path, max_size: Option<u64>, keep: usize, gzip: bool) -> io::Result<Self> {
+    let (file, size) = open_append(path)?;
+
+    Ok(RotatingFile { path: path.to_path_buf(), file, size, max_size, keep, gzip })
+  }
+
+  /// The path of the `n`th rotated file, e.g. `out.log.2.gz`
+  fn rotated_path(&self, n: usize) -> PathBuf {
+    let mut path = self.path.as_os_str().to_owned();
+    path.push(format!(".{}", n));
+    if self.gzip {
+      path.push(".gz");
+    }
+
+    PathBuf::from(path)
+  }
+
+  fn rotate(&mut self) -> io::Result<()> {
+    self.file.flush()?;
+
+    if self.keep == 0 {
+      fs::remove_file(&self.path)?;
+    } else {
+      // shift older files up, dropping the oldest
+      for n in (1..self.keep).rev() {
+        let from = self.rotated_path(n);
+        if from.exists() {
+          fs::rename(from, self.rotated_path(n + 1))?;
+        }
+      }
+
+      let mut first = self.path.as_os_str().to_owned();
+      first.push(".1");
+      fs::rename(&self.path, &first)?;
+
+      if self.gzip {
+        gzip(Path::new(&first))?;
+      }
+    }
+
+    let (file, size) = open_append(&self.path)?;
+    self.file = file;
+    self.size = size;
+
+    Ok(())
+  }
+
+  pub(crate) fn write_line(&mut self, line: &str) -> io::Result<()> {
+    writeln!(self.file, "{}", line)?;
+    self.size += line.len() as u64 + 1;
+
+    match self.max_size {
+      Some(max_size) if self.size >= max_size => self.rotate(),
+      _ => Ok(())
+    }
+  }
+
+  pub(crate) fn flush(&mut self) -> io::Result<()> {
+    self.file.flush()
+  }
+}
+
+pub(crate) fn format_entry(format: TeeFormat, entry: &MessageEntry) -> Vec<String> {
+  match format {
+    TeeFormat::Plain => plain_render(entry),
+    TeeFormat::Json => match serde_json::to_string(&entry.message) {
+      Ok(s) => vec![s],
+      Err(_) => Vec::new()
+    }
+  }
+}
+
+/// A wrapping reader that writes all messages to the `--tee` file, while
+/// passing them through to the renderer
+pub fn read_tee(
+  config: Arc<Config>,
+  rx: Receiver<LogEntry>,
+  tx: Sender<LogEntry>,
+) -> JoinHandle<SimpleResult<()>> {
+  thread::Builder::new().name("read_tee".to_string()).spawn(move || {
+    let max_size = config.tee_max_size.map(|size| size.0);
+    let mut file = config.tee.as_ref().and_then(|path| {
+      match RotatingFile::open(path, max_size, config.tee_keep, config.tee_gzip) {
+        Ok(file) => Some(file),
+        Err(e) => {
+          tx.send(LogEntry::internal(&format!(
+            "error: could not open tee file {}: {}", path.display(), e
+          ))).ok();
+
+          None
+        }
+      }
+    });
+
+    for entry in rx {
+      if let (Some(f), Some(message)) = (&mut file, &entry.message) {
+        if message.message.kind != MessageKind::Internal {
+          let result = format_entry(config.tee_format, message).iter()
+            .try_for_each(|line| f.write_line(line));
+
+          if let Err(e) = result {
+            tx.send(LogEntry::internal(&format!(
+              "error: could not write to tee file, disabling: {}", e
+            ))).ok();
+
+            file = None;
+          }
+        }
+      }
+
+      if entry.eof.is_some() {
+        if let Some(f) = &mut file {
+          f.flush().ok();
+        }
+      }
+
+      if tx.send(entry).is_err() {
+        break;
+      }
+    }
+
+    if let Some(mut f) = file {
+      f.flush().ok();
+    }
+
+    Ok(())
+  }).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::io::Read;
+
+  use flate2::read::GzDecoder;
+  use spectral::prelude::*;
+
+  fn temp_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir()
+      .join(format!("woodchipper-tee-{}-{}", name, std::process::id()));
+    fs::remove_dir_all(&dir).ok();
+    fs::create_dir_all(&dir).unwrap();
+
+    dir
+  }
+
+  fn write_lines(file: &mut RotatingFile, lines: &[&str]) {
+    for line in lines {
+      file.write_line(line).unwrap();
+    }
+
+    file.flush().unwrap();
+  }
+
+  #[test]
+  fn test_rotate_gzip() {
+    let dir = temp_dir("gzip");
+    let path = dir.join("out.log");
+
+    let mut file = RotatingFile::open(&path, Some(10), 2, true).unwrap();
+    write_lines(&mut file, &["first line", "second line", "third line", "fourth"]);
+
+    let gunzip = |path: PathBuf| {
+      let mut s = String::new();
+      GzDecoder::new(File::open(path).unwrap()).read_to_string(&mut s).unwrap();
+      s
+    };
+
+    assert_that!(fs::read_to_string(&path).unwrap()).is_equal_to("fourth\n".to_string());
+    assert_that!(gunzip(dir.join("out.log.1.gz"))).is_equal_to("third line\n".to_string());
+    assert_that!(gunzip(dir.join("out.log.2.gz"))).is_equal_to("second line\n".to_string());
+    assert_that!(dir.join("out.log.3.gz").exists()).is_false();
+
+    fs::remove_dir_all(&dir).ok();
+  }
+
+  #[test]
+  fn test_rotate_keep() {
+    let dir = temp_dir("keep");
+    let path = dir.join("out.log");
+
+    let mut file = RotatingFile::open(&path, Some(10), 1, false).unwrap();
+    write_lines(&mut file, &["first line", "second line", "third"]);
+
+    assert_that!(fs::read_to_string(&path).unwrap()).is_equal_to("third\n".to_string());
+    assert_that!(fs::read_to_string(dir.join("out.log.1")).unwrap())
+      .is_equal_to("second line\n".to_string());
+    assert_that!(dir.join("out.log.2").exists()).is_false();
+
+    // without any kept, full files are just removed
+    let mut file = RotatingFile::open(&dir.join("none.log"), Some(10), 0, false).unwrap();
+    write_lines(&mut file, &["first line", "second"]);
+
+    assert_that!(fs::read_to_string(dir.join("none.log")).unwrap())
+      .is_equal_to("second\n".to_string());
+    assert_that!(dir.join("none.log.1").exists()).is_false();
+
+    fs::remove_dir_all(&dir).ok();
+  }
+
+  #[test]
+  fn test_reopen() {
+    let dir = temp_dir("reopen");
+    let path = dir.join("out.log");
+    fs::write(&path, "existing\n").unwrap();
+
+    // existing lines are kept, and count towards the size
+    let mut file = RotatingFile::open(&path, Some(15), 1, false).unwrap();
+    write_lines(&mut file, &["appended"]);
+    assert_that!(fs::read_to_string(&path).unwrap()).is_equal_to("".to_string());
+    assert_that!(fs::read_to_string(dir.join("out.log.1")).unwrap())
+      .is_equal_to("existing\nappended\n".to_string());
+
+    fs::remove_dir_all(&dir).ok();
+  }
+}
@@ -0,0 +1,354 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+use std::io::{self, Write};
+use std::process::{Command, Stdio};
+use std::sync::Arc;
+use std::sync::mpsc::{Receiver, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+use simple_error::{SimpleError, SimpleResult};
+
+use crate::config::Config;
+use crate::filter::{Filter, FilterMode};
+use crate::parser::{Message, MessageKind};
+use crate::renderer::LogEntry;
+use crate::style::RuleConditions;
+
+/// Seconds a trigger waits after firing before it may fire again, unless set
+static DEFAULT_COOLDOWN: f64 = 10.0;
+
+fn default_cooldown() -> f64 {
+  DEFAULT_COOLDOWN
+}
+
+/// A rule from the config file's `rules.trigger` section, acting when a
+/// message matches all of its conditions, e.g.:
+///
+/// ```yaml
+/// rules:
+///   trigger:
+///     - name: fatal
+///       expr: level >= fatal
+///       notify: true
+///       run: 'curl -d @- https://hooks.example.com/alert'
+/// ```
+#[derive(Debug, Clone, Deserialize)]
+pub struct TriggerRule {
+  /// A name for the rule, used as the notification title
+  pub name: Option<String>,
+
+  #[serde(flatten)]
+  pub conditions: RuleConditions,
+
+  /// An expression the message must also match, as with `--filter`
+  pub expr: Option<String>,
+
+  /// If true, shows a desktop notification
+  #[serde(default)]
+  pub notify: bool,
+
+  /// If true, rings the terminal bell
+  #[serde(default)]
+  pub bell: bool,
+
+  /// A shell command to run with the message as JSON on its stdin
+  pub run: Option<String>,
+
+  /// Seconds to wait after firing before firing again
+  #[serde(default = "default_cooldown")]
+  pub cooldown: f64
+}
+
+/// A trigger ready to match messages, from `--on` or the config file
+struct Trigger {
+  name: String,
+  conditions: Option<RuleConditions>,
+  expr: Option<Box<dyn Filter>>,
+  notify: bool,
+  bell: bool,
+  run: Option<String>,
+  cooldown: Duration,
+  fired: Option<Instant>
+}
+
+impl Trigger {
+  /// If true, a message matches and the trigger isn't cooling down; if so,
+  /// the trigger's cooldown starts
+  fn fire(&mut self, message: &Message, now: Instant) -> bool {
+    if let Some(fired) = self.fired {
+      if now.duration_since(fired) < self.cooldown {
+        return false;
+      }
+    }
+
+    let matched = self.conditions.as_ref().map_or(true, |c| c.matches(message))
+      && self.expr.as_ref().map_or(true, |f| f.filter(message));
+
+    if matched {
+      self.fired = Some(now);
+    }
+
+    matched
+  }
+}
+
+fn parse_expr(expr: &str, flag: &str) -> SimpleResult<Box<dyn Filter>> {
+  FilterMode::Expression.parse(expr, false).map_err(|e| SimpleError::new(
+    format!("invalid {} {:?}: {}", flag, expr, e)
+  ))
+}
+
+/// If true, any triggers are set with `--on` or in the config file
+pub fn enabled(config: &Config) -> bool {
+  !config.on.is_empty() || !config.file().rules.trigger.is_empty()
+}
+
+/// Checks the triggers from `--on` and the config file
+pub fn check_triggers(config: &Config) -> SimpleResult<()> {
+  if !config.on.is_empty() && !config.notify && !config.bell && config.run.is_none() {
+    bail!("--on needs an action: --notify, --bell, or --run");
+  }
+
+  parse_triggers(config).map(|_| ())
+}
+
+fn parse_triggers(config: &Config) -> SimpleResult<Vec<Trigger>> {
+  let mut triggers = Vec::new();
+  for expr in &config.on {
+    triggers.push(Trigger {
+      name: expr.clone(),
+      conditions: None,
+      expr: Some(parse_expr(expr, "--on")?),
+      notify: config.notify,
+      bell: config.bell,
+      run: config.run.clone(),
+      cooldown: Duration::from_secs_f64(DEFAULT_COOLDOWN),
+      fired: None
+    });
+  }
+
+  for (i, rule) in config.file().rules.trigger.iter().enumerate() {
+    let name = rule.name.clone().unwrap_or_else(|| format!("trigger {}", i + 1));
+    let expr = match &rule.expr {
+      Some(expr) => Some(parse_expr(expr, &format!("expr for {}", name))?),
+      None => None
+    };
+
+    triggers.push(Trigger {
+      name,
+      conditions: Some(rule.conditions.clone()),
+      expr,
+      notify: rule.notify,
+      bell: rule.bell,
+      run: rule.run.clone(),
+      cooldown: Duration::from_secs_f64(rule.cooldown.max(0.0)),
+      fired: None
+    });
+  }
+
+  Ok(triggers)
+}
+
+/// Quotes a string for AppleScript
+#[cfg_attr(not(target_os = "macos"), allow(dead_code))]
+fn applescript_quote(s: &str) -> String {
+  format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(target_os = "macos")]
+fn notify_command(title: &str, body: &str) -> Command {
+  let mut command = Command::new("osascript");
+  command.arg("-e").arg(format!(
+    "display notification {} with title {}", applescript_quote(body), applescript_quote(title)
+  ));
+
+  command
+}
+
+#[cfg(not(target_os = "macos"))]
+fn notify_command(title: &str, body: &str) -> Command {
+  let mut command = Command::new("notify-send");
+  command.arg("--").arg(title).arg(body);
+
+  command
+}
+
+/// Runs a command in the background, reporting failures as internal messages
+fn spawn(
+  name: &str, what: &str, mut command: Command, stdin: Option<String>, tx: &Sender<LogEntry>
+) {
+  let name = name.to_string();
+  let what = what.to_string();
+  let tx = tx.clone();
+
+  let report = move |text: String| {
+    tx.send(LogEntry::internal(&format!("trigger {}: {} {}", name, what, text))).ok();
+  };
+
+  thread::spawn(move || {
+    command
+      .stdin(if stdin.is_some() { Stdio::piped() } else { Stdio::null() })
+      .stdout(Stdio::null())
+      .stderr(Stdio::piped());
+
+    let mut child = match command.spawn() {
+      Ok(child) => child,
+      Err(e) => return report(format!("could not start: {}", e))
+    };
+
+    if let (Some(text), Some(mut pipe)) = (stdin, child.stdin.take()) {
+      // the command may not read its input at all
+      pipe.write_all(text.as_bytes()).ok();
+    }
+
+    match child.wait_with_output() {
+      Ok(output) if output.status.success() => (),
+      Ok(output) => {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        report(match stderr.trim().lines().last() {
+          Some(line) => format!("failed ({}): {}", output.status, line),
+          None => format!("failed ({})", output.status)
+        });
+      },
+      Err(e) => report(format!("failed: {}", e))
+    }
+  });
+}
+
+fn act(trigger: &Trigger, message: &Message, tx: &Sender<LogEntry>) {
+  if trigger.bell {
+    let mut stderr = io::stderr();
+    write!(stderr, "\x07").and_then(|_| stderr.flush()).ok();
+  }
+
+  if trigger.notify {
+    let body = message.text.as_deref().unwrap_or(&message.raw);
+    let title = format!("woodchipper: {}", trigger.name);
+    spawn(&trigger.name, "notification", notify_command(&title, body), None, tx);
+  }
+
+  if let Some(run) = &trigger.run {
+    let json = serde_json::to_string(message).unwrap_or_default();
+    let mut command = Command::new("sh");
+    command.arg("-c").arg(run).env("WD_TRIGGER", &trigger.name);
+    spawn(&trigger.name, "command", command, Some(json + "\n"), tx);
+  }
+}
+
+/// A wrapping reader that passes every entry along, acting on messages that
+/// match a trigger: notifying, ringing the bell, or running a command
+pub fn read_trigger(
+  config: Arc<Config>,
+  rx: Receiver<LogEntry>,
+  tx: Sender<LogEntry>,
+) -> JoinHandle<SimpleResult<()>> {
+  thread::Builder::new().name("read_trigger".to_string()).spawn(move || {
+    // already checked at startup
+    let mut triggers = parse_triggers(&config)?;
+
+    for entry in rx {
+      if let Some(message) = &entry.message {
+        if message.message.kind != MessageKind::Internal {
+          let now = Instant::now();
+          for trigger in triggers.iter_mut() {
+            if trigger.fire(&message.message, now) {
+              act(trigger, &message.message, &tx);
+            }
+          }
+        }
+      }
+
+      if tx.send(entry).is_err() {
+        break;
+      }
+    }
+
+    Ok(())
+  }).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use spectral::prelude::*;
+  use structopt::StructOpt;
+
+  use crate::config::FileConfig;
+  use crate::parser::test_message;
+
+  /// An `--on` trigger, with the default cooldown, then a config rule without
+  fn triggers() -> Vec<Trigger> {
+    let mut config = Config::from_iter_safe(vec!["", "--on", "level >= error", "--bell"]).unwrap();
+    config.config_file = Some(serde_yaml::from_str::<FileConfig>(concat!(
+      "rules:\n",
+      "  trigger:\n",
+      "    - message: deadlock\n",
+      "      cooldown: 0\n",
+      "      notify: true\n"
+    )).unwrap());
+    assert_that!(check_triggers(&config).is_ok()).is_true();
+
+    parse_triggers(&config).unwrap()
+  }
+
+  fn error() -> Message {
+    test_message(r#"{"level": "error", "msg": "deadlock detected"}"#, None)
+  }
+
+  fn info() -> Message {
+    test_message(r#"{"level": "info", "msg": "ok"}"#, None)
+  }
+
+  #[test]
+  fn test_parse_triggers() {
+    let triggers = triggers();
+    assert_that!(triggers).has_length(2);
+    assert_that!(triggers[0].name.as_str()).is_equal_to("level >= error");
+    assert_that!(triggers[1].name.as_str()).is_equal_to("trigger 1");
+  }
+
+  #[test]
+  fn test_fire() {
+    let mut triggers = triggers();
+    let start = Instant::now();
+
+    assert_that!(triggers[0].fire(&info(), start)).is_false();
+    assert_that!(triggers[0].fire(&error(), start)).is_true();
+    assert_that!(triggers[1].fire(&info(), start)).is_false();
+    assert_that!(triggers[1].fire(&error(), start)).is_true();
+  }
+
+  #[test]
+  fn test_cooldown() {
+    let mut triggers = triggers();
+    let start = Instant::now();
+    assert_that!(triggers[0].fire(&error(), start)).is_true();
+
+    // errors during the cooldown don't fire again
+    assert_that!(triggers[0].fire(&error(), start + Duration::from_secs(5))).is_false();
+    assert_that!(triggers[0].fire(&error(), start + Duration::from_secs(10))).is_true();
+
+    // unless it's disabled
+    assert_that!(triggers[1].fire(&error(), start)).is_true();
+    assert_that!(triggers[1].fire(&error(), start)).is_true();
+  }
+
+  #[test]
+  fn test_check_triggers() {
+    // a trigger needs an action
+    let config = Config::from_iter_safe(vec!["", "--on", "level >= error"]).unwrap();
+    assert_that!(check_triggers(&config).is_err()).is_true();
+
+    let config = Config::from_iter_safe(vec!["", "--on", "level >=", "--bell"]).unwrap();
+    assert_that!(check_triggers(&config).is_err()).is_true();
+  }
+
+  #[test]
+  fn test_applescript_quote() {
+    assert_that!(applescript_quote(r#"say "hi" \o/"#))
+      .is_equal_to(r#""say \"hi\" \\o/""#.to_string());
+  }
+}
@@ -1,15 +1,25 @@
 // (C) Copyright 2019 Hewlett Packard Enterprise Development LP
 
+use std::borrow::Cow;
 use std::collections::VecDeque;
 use std::cmp::max;
 
+use chrono::{DateTime, Duration, Utc};
+use serde_json::Value;
 use textwrap::{Wrapper, NoHyphenation};
 
+use crate::config::FieldSelection;
+use crate::parser::Message;
 use crate::style::StyleProfile;
 use crate::classifier::{
-  Chunk, ChunkKind, ChunkSlot, ChunkAlignment, ChunkWeight
+  Chunk, ChunkKind, ChunkSlot, ChunkAlignment, ChunkWeight, classify
 };
 use crate::renderer::MessageEntry;
+use crate::renderer::glyphs;
+use crate::renderer::highlight::highlight;
+use crate::renderer::hyperlink;
+use crate::renderer::layout;
+use crate::timezone;
 
 #[cfg(test)] use spectral::prelude::*;
 
@@ -346,6 +356,7 @@ pub fn fixed_width(kind: ChunkKind) -> Option<usize> {
   match kind {
     ChunkKind::Date => Some(10),
     ChunkKind::Time => Some(8),
+    ChunkKind::Delta => Some(7),
     ChunkKind::Level(_) => Some(7),
     _ => None
   }
@@ -379,6 +390,68 @@ where
   (left, center, right)
 }
 
+/// the key of a field chunk, without its trailing `=`
+fn field_key(chunk: &Chunk) -> Option<&str> {
+  chunk.children.iter()
+    .find(|c| c.kind == ChunkKind::FieldKey)
+    .and_then(|c| c.value.as_ref())
+    .map(|v| v.trim_end_matches('='))
+}
+
+/// removes and reorders fields per the user's selection
+pub fn select_fields(selection: &FieldSelection, entry: MessageEntry) -> MessageEntry {
+  if selection.show.is_empty() && selection.hide.is_empty() {
+    return entry;
+  }
+
+  let mut message = entry.message;
+  message.metadata.retain(|key, _| {
+    !selection.hide.contains(key)
+      && (selection.show.is_empty() || selection.show.contains(key))
+  });
+
+  let (mut fields, mut chunks): (Vec<Chunk>, Vec<Chunk>) = classify(&message)
+    .into_iter()
+    .partition(|c| c.kind == ChunkKind::Field);
+
+  if !selection.show.is_empty() {
+    fields.sort_by_key(|c| field_key(c)
+      .and_then(|key| selection.show.iter().position(|s| s == key)));
+  }
+
+  chunks.extend(fields);
+  MessageEntry { message, chunks }
+}
+
+/// truncates field values longer than their maximum width with an ellipsis
+///
+/// Only the displayed chunks are truncated; the message keeps full values.
+pub fn truncate_fields(selection: &FieldSelection, mut entry: MessageEntry) -> MessageEntry {
+  if !selection.truncates() {
+    return entry;
+  }
+
+  for chunk in entry.chunks.iter_mut().filter(|c| c.kind == ChunkKind::Field) {
+    let max = match field_key(chunk).and_then(|key| selection.max_width_for(key)) {
+      Some(max) => max,
+      None => continue
+    };
+
+    let values = chunk.children.iter_mut()
+      .filter(|c| c.kind == ChunkKind::FieldValue)
+      .filter_map(|c| c.value.as_mut());
+
+    for value in values {
+      if value.chars().count() > max {
+        *value = value.chars().take(max.saturating_sub(1)).collect();
+        value.push('…');
+      }
+    }
+  }
+
+  entry
+}
+
 /// renders a single chunk into one or more RenderedChunk
 /// these chunks are semantically intended to appear on one line, but may be
 /// wrapped later if necessary
@@ -386,7 +459,7 @@ where
 /// region (e.g. left/right/center) and is used to split long chunks into one
 /// or more RenderedChunks, in addition to any child chunks they may contain
 fn styled_render_chunk(
-  chunk: &Chunk,
+  chunk: &Chunk, message: &Message,
   profile: &StyleProfile, wrap_width: Option<usize>
 ) -> Vec<RenderedChunk> {
   let chunk_style = profile.get_style(&chunk.kind);
@@ -407,15 +480,33 @@ fn styled_render_chunk(
 
     for wrapped_line in wrapped {
       // TODO: decide if we should apply fixed width to all wrapped lines
-      let content = if let Some(fixed_width) = fixed_width(chunk.kind) {
+      let width = match chunk.kind {
+        ChunkKind::Level(_) => glyphs::level_width().or_else(|| fixed_width(chunk.kind)),
+        kind => fixed_width(kind)
+      };
+
+      let content = if let Some(fixed_width) = width {
         align(&wrapped_line, fixed_width, chunk.alignment)
       } else {
         wrapped_line
       };
 
       let length = content.chars().count();
+      let paint = |s: &str| if chunk.kind == ChunkKind::Text && profile.highlights_syntax() {
+        highlight(s, *chunk_style, profile)
+      } else {
+        chunk_style.paint(s).to_string()
+      };
+
+      let linkable = matches!(chunk.kind, ChunkKind::Text | ChunkKind::FieldValue);
+      let content = if linkable && hyperlink::enabled() {
+        hyperlink::linkify(&content, paint)
+      } else {
+        paint(&content)
+      };
+
       rendered_chunks.push(RenderedChunk {
-        content: chunk_style.paint(content).to_string(),
+        content,
         width: length,
         pad_left: chunk.pad_left,
         pad_right: chunk.pad_right,
@@ -429,11 +520,26 @@ fn styled_render_chunk(
     }
   }
 
-  rendered_chunks.extend(
-    chunk.children.iter()
-      .flat_map(|c| styled_render_chunk(c, profile, wrap_width))
-  );
+  let mut children: Vec<RenderedChunk> = chunk.children.iter()
+    .flat_map(|c| styled_render_chunk(c, message, profile, wrap_width))
+    .collect();
+
+  // link the value of fields with a configured template, unless it already
+  // contains a URL
+  let url = match chunk.kind {
+    ChunkKind::Field => field_key(chunk).and_then(|key| hyperlink::field_link(key, message)),
+    _ => None
+  };
+
+  if let Some(url) = url {
+    for child in children.iter_mut().filter(|c| c.kind == ChunkKind::FieldValue) {
+      if !child.content.contains("\x1b]8;") {
+        child.content = hyperlink::link(&url, &child.content);
+      }
+    }
+  }
 
+  rendered_chunks.extend(children);
   rendered_chunks
 }
 
@@ -442,11 +548,12 @@ fn styled_render_chunk(
 /// displayed on its own line (possibly with additional chunks on the side)
 fn styled_render_region(
   chunks: Vec<&Chunk>,
+  message: &Message,
   profile: &StyleProfile,
   wrap_width: Option<usize>
 ) -> Vec<RenderedChunk> {
   let rendered_chunks: Vec<RenderedChunk> = chunks.iter()
-    .flat_map(|c| styled_render_chunk(c, profile, wrap_width))
+    .flat_map(|c| styled_render_chunk(c, message, profile, wrap_width))
     .collect();
 
   if let Some(wrap_width) = wrap_width {
@@ -460,23 +567,254 @@ fn styled_render_region(
   }
 }
 
-/// filters chunks for only those with weight >= min
-fn prune(chunks: Vec<&Chunk>, min: i8) -> Vec<&Chunk> {
-  chunks.into_iter().filter(|c| c.weight >= min).collect()
+/// filters chunks for only those shown at the current width, per the layout
+fn prune(chunks: Vec<&Chunk>, wrap_width: Option<usize>) -> Vec<&Chunk> {
+  chunks.into_iter().filter(|c| layout::shows(c, wrap_width)).collect()
+}
+
+/// The value of a `--columns` entry for a message, as text
+pub fn column_value(message: &Message, column: &str) -> String {
+  if column == "timestamp" {
+    return message.timestamp
+      .map(|t| timezone::to_display(t).to_rfc3339())
+      .unwrap_or_default();
+  }
+
+  match message.field(column) {
+    Some(Value::String(s)) => s,
+    Some(Value::Null) | None => String::new(),
+    Some(value) => value.to_string()
+  }
+}
+
+/// Returns a message's timestamp, falling back to the time it was read
+pub fn message_timestamp(message: &Message) -> Option<DateTime<Utc>> {
+  message.timestamp
+    .or_else(|| message.reader_metadata.as_ref().and_then(|m| m.timestamp))
+}
+
+/// Formats an elapsed time compactly, e.g. `+3ms`, `+12.4s`, or `+2m05s`
+pub fn format_delta(delta: Duration) -> String {
+  let sign = if delta < Duration::zero() { '-' } else { '+' };
+  let ms = delta.num_milliseconds().abs();
+
+  if ms < 1000 {
+    format!("{}{}ms", sign, ms)
+  } else if ms < 60_000 {
+    format!("{}{:.1}s", sign, ms as f64 / 1000.0)
+  } else if ms < 3_600_000 {
+    format!("{}{}m{:02}s", sign, ms / 60_000, ms % 60_000 / 1000)
+  } else {
+    format!("{}{}h{:02}m", sign, ms / 3_600_000, ms % 3_600_000 / 60_000)
+  }
+}
+
+/// Adds a delta column to an entry showing the time elapsed since `previous`,
+/// the timestamp of the previously displayed message
+///
+/// The column is left blank if either timestamp is unknown.
+pub fn with_delta(entry: &MessageEntry, previous: Option<DateTime<Utc>>) -> MessageEntry {
+  let delta = match (message_timestamp(&entry.message), previous) {
+    (Some(current), Some(previous)) => format_delta(current - previous),
+    _ => String::new()
+  };
+
+  let mut chunks = entry.chunks.clone();
+  let position = chunks.iter()
+    .rposition(|c| c.kind == ChunkKind::Time)
+    .map(|i| i + 1)
+    .unwrap_or(0);
+
+  chunks.insert(position, Chunk {
+    kind: ChunkKind::Delta,
+    slot: ChunkSlot::Left,
+
+    alignment: ChunkAlignment::Right,
+    weight: ChunkWeight::Medium.value(),
+    pad_right: true,
+
+    value: Some(delta),
+
+    ..Default::default()
+  });
+
+  MessageEntry { message: entry.message.clone(), chunks }
+}
+
+/// If true, `b` repeats `a`, ignoring timestamps: both have the same level,
+/// source, and text, or the same fields if neither has any text
+pub fn is_repeat(a: &Message, b: &Message) -> bool {
+  let source = |m: &Message| m.reader_metadata.as_ref().and_then(|r| r.source.clone());
+
+  a.kind == b.kind && a.level == b.level && source(a) == source(b) && match (&a.text, &b.text) {
+    (None, None) => a.metadata == b.metadata,
+    (a, b) => a == b
+  }
+}
+
+/// Annotates an entry with the number of consecutive repeats collapsed into it,
+/// e.g. `×12`, shown after its text
+pub fn with_repeats(entry: &MessageEntry, count: usize) -> MessageEntry {
+  let mut chunks = entry.chunks.clone();
+  let position = chunks.iter()
+    .rposition(|c| c.kind == ChunkKind::Text)
+    .map(|i| i + 1)
+    .unwrap_or(chunks.len());
+
+  chunks.insert(position, Chunk {
+    kind: ChunkKind::Repeat,
+    slot: ChunkSlot::Center,
+
+    weight: ChunkWeight::High.value(),
+    pad_left: true,
+    pad_right: true,
+
+    value: Some(format!("×{}", count)),
+
+    ..Default::default()
+  });
+
+  MessageEntry { message: entry.message.clone(), chunks }
+}
+
+/// Collapses a multi-line message to its first line followed by the number of
+/// hidden lines, e.g. `▸ [+37 lines]`, or returns None if it has only one line
+pub fn collapse_lines(entry: &MessageEntry) -> Option<MessageEntry> {
+  let lines = entry.chunks.iter().filter(|c| c.kind == ChunkKind::Text).count();
+  if lines < 2 {
+    return None;
+  }
+
+  let first = entry.chunks.iter().position(|c| c.kind == ChunkKind::Text)?;
+
+  let mut chunks: Vec<Chunk> = entry.chunks.iter()
+    .enumerate()
+    .filter(|(i, c)| *i == first || c.kind != ChunkKind::Text)
+    .map(|(_, c)| c.clone())
+    .collect();
+
+  chunks[first].force_break_after = false;
+  chunks.insert(first + 1, Chunk {
+    kind: ChunkKind::Collapsed,
+    slot: ChunkSlot::Center,
+
+    weight: ChunkWeight::High.value(),
+    pad_left: true,
+    pad_right: true,
+
+    value: Some(format!("▸ [+{} lines]", lines - 1)),
+
+    ..Default::default()
+  });
+
+  Some(MessageEntry { message: entry.message.clone(), chunks })
+}
+
+/// Collapses runs of repeated messages for the streaming renderers
+///
+/// Each message is held until the next differing one arrives, so the last
+/// message isn't shown until then or until `finish()` is called.
+#[derive(Default)]
+pub struct RepeatCollapser {
+  pending: Option<(MessageEntry, usize)>
+}
+
+impl RepeatCollapser {
+  fn take(&mut self) -> Option<MessageEntry> {
+    self.pending.take().map(|(entry, count)| match count {
+      1 => entry,
+      count => with_repeats(&entry, count)
+    })
+  }
+
+  /// Adds an entry, returning the previous run if this entry ends it
+  pub fn push(&mut self, entry: MessageEntry) -> Option<MessageEntry> {
+    if let Some((pending, count)) = &mut self.pending {
+      if is_repeat(&pending.message, &entry.message) {
+        *count += 1;
+        return None;
+      }
+    }
+
+    let previous = self.take();
+    self.pending = Some((entry, 1));
+    previous
+  }
+
+  /// Returns the final run, if any
+  pub fn finish(&mut self) -> Option<MessageEntry> {
+    self.take()
+  }
+}
+
+/// Forces a line break after a chunk, or after its last child
+fn force_break_after(chunk: &mut Chunk) {
+  match chunk.children.last_mut() {
+    Some(child) => force_break_after(child),
+    None => chunk.force_break_after = true
+  }
 }
 
-fn prune_level(wrap_width: Option<usize>) -> ChunkWeight {
-  if let Some(width) = wrap_width {
-    if width < 60 {
-      return ChunkWeight::High;
-    } else if width < 80 {
-      return ChunkWeight::Medium;
-    } else if width < 100 {
-      return ChunkWeight::Normal;
+/// Moves an entry's fields onto their own lines beneath the message, with
+/// aligned keys, similar to `kubectl describe`
+pub fn expand_fields(entry: &MessageEntry) -> MessageEntry {
+  let key_width = entry.chunks.iter()
+    .filter(|c| c.kind == ChunkKind::Field)
+    .filter_map(field_key)
+    .map(|key| key.chars().count() + 1)
+    .max();
+
+  let key_width = match key_width {
+    Some(width) => width,
+    None => return entry.clone()
+  };
+
+  let mut chunks = entry.chunks.clone();
+
+  // start the first field on a new line
+  if let Some(first) = chunks.iter().position(|c| c.kind == ChunkKind::Field) {
+    if let Some(previous) = chunks[..first].iter().rposition(|c| matches!(c.slot, ChunkSlot::Center)) {
+      force_break_after(&mut chunks[previous]);
+    }
+  }
+
+  for chunk in chunks.iter_mut().filter(|c| c.kind == ChunkKind::Field) {
+    for child in chunk.children.iter_mut().filter(|c| c.kind == ChunkKind::FieldKey) {
+      if let Some(key) = &child.value {
+        let key = format!("{}:", key.trim_end_matches('='));
+        child.value = Some(format!("  {:<w$}", key, w = key_width + 1));
+      }
     }
+
+    force_break_after(chunk);
   }
 
-  ChunkWeight::Low
+  MessageEntry { message: entry.message.clone(), chunks }
+}
+
+/// Returns an entry's chunks, with its date and time replaced by a relative
+/// offset if relative timestamps are enabled
+///
+/// This happens at render time rather than during classification as offsets
+/// from now change, and relative display may be toggled interactively.
+pub fn display_chunks(entry: &MessageEntry) -> Cow<'_, [Chunk]> {
+  let relative = match message_timestamp(&entry.message).and_then(timezone::to_relative) {
+    Some(relative) => relative,
+    None => return Cow::Borrowed(&entry.chunks)
+  };
+
+  let chunks = entry.chunks.iter()
+    .filter(|chunk| chunk.kind != ChunkKind::Date)
+    .map(|chunk| match chunk.kind {
+      ChunkKind::Time => Chunk {
+        value: Some(relative.clone()),
+        ..chunk.clone()
+      },
+      _ => chunk.clone()
+    })
+    .collect();
+
+  Cow::Owned(chunks)
 }
 
 /// renders a MessageEntry into a list of strings wrapped to fit `width`
@@ -487,16 +825,15 @@ pub fn styled_render(
   // into buckets to prune fields based on weight
   // for now, just skip rendering the right column if wrapping is disabled
   // TODO: allow left and right columns to wrap as well?
-  let min_weight = prune_level(wrap_width).value();
-
-  let (left, center, right) = bucketize(entry.chunks.iter());
+  let chunks = glyphs::apply(&entry.message, display_chunks(entry));
+  let (left, center, right) = bucketize(chunks.iter());
   let right_is_empty = right.is_empty();
   let left_rendered = styled_render_region(
-    prune(left, min_weight), profile, None
+    prune(left, wrap_width), &entry.message, profile, None
   );
   let left_width = largest_chunk(&left_rendered);
   let right_rendered = styled_render_region(
-    prune(right, min_weight), profile, None
+    prune(right, wrap_width), &entry.message, profile, None
   );
   let right_width = largest_chunk(&right_rendered);
   
@@ -517,7 +854,7 @@ pub fn styled_render(
   };
 
   let center_rendered = styled_render_region(
-    prune(center, min_weight), profile, Some(center_width)
+    prune(center, wrap_width), &entry.message, profile, Some(center_width)
   );
 
   let left_spacer = RenderedChunk::spacer(left_width, profile);
@@ -664,17 +1001,32 @@ mod tests {
     }
   }
 
+  fn empty_message() -> Message {
+    Message {
+      kind: crate::parser::MessageKind::Plain,
+      timestamp: None,
+      level: None,
+      text: None,
+      metadata: std::collections::HashMap::new(),
+      reader_metadata: None,
+      mapped_fields: std::collections::HashMap::new(),
+      raw: String::new()
+    }
+  }
+
   fn get_tags(profile: &StyleProfile) -> Vec<RenderedChunk> {
+    let message = empty_message();
+
     vec![
-      styled_render_chunk(&get_field_chunk("foo=", "1"), &profile, None),
-      styled_render_chunk(&get_field_chunk("bar=", "2"), &profile, None),
-      styled_render_chunk(&get_field_chunk("baz=", "3"), &profile, None)
+      styled_render_chunk(&get_field_chunk("foo=", "1"), &message, &profile, None),
+      styled_render_chunk(&get_field_chunk("bar=", "2"), &message, &profile, None),
+      styled_render_chunk(&get_field_chunk("baz=", "3"), &message, &profile, None)
     ].into_iter().flatten().collect()
   }
 
   fn get_message(profile: &StyleProfile) -> Vec<RenderedChunk> {
     vec![
-      styled_render_chunk(&get_text_chunk("hello world"), &profile, None),
+      styled_render_chunk(&get_text_chunk("hello world"), &empty_message(), &profile, None),
       get_tags(&profile)
     ].into_iter().flatten().collect()
   }
@@ -780,4 +1132,172 @@ mod tests {
     assert_that!(measure_chunks(&get_message(&normal))).is_equal_to(29);
     assert_that!(measure_chunks(&get_message(&selected))).is_equal_to(29);
   }
+
+  #[test]
+  fn test_select_fields() {
+    use std::collections::HashMap;
+
+    use crate::parser::{Message, MessageKind};
+
+    let message = Message {
+      kind: MessageKind::Json,
+      timestamp: None,
+      level: None,
+      text: None,
+      metadata: hashmap!{
+        "pod".to_string() => serde_json::json!("api-7d9f8c"),
+        "trace_id".to_string() => serde_json::json!("abc"),
+        "logger".to_string() => serde_json::json!("http"),
+        "user".to_string() => serde_json::json!("jane")
+      },
+      reader_metadata: None,
+      mapped_fields: HashMap::new(),
+      raw: String::new()
+    };
+
+    let chunks = classify(&message);
+    let selection = FieldSelection {
+      show: vec!["trace_id".to_string(), "pod".to_string(), "logger".to_string()],
+      hide: vec!["logger".to_string()],
+      max_width: Some(6),
+      max_widths: hashmap!{ "trace_id".to_string() => 2 }
+    };
+
+    let entry = select_fields(&selection, MessageEntry { message, chunks });
+    let entry = truncate_fields(&selection, entry);
+    let fields: Vec<(&str, &str)> = entry.chunks.iter()
+      .filter(|c| c.kind == ChunkKind::Field)
+      .map(|c| (
+        field_key(c).unwrap(),
+        c.children[1].value.as_deref().unwrap()
+      ))
+      .collect();
+
+    assert_that!(fields).is_equal_to(vec![("trace_id", "a…"), ("pod", "api-7…")]);
+    assert_that!(entry.message.metadata.get("pod")).is_equal_to(Some(&serde_json::json!("api-7d9f8c")));
+    assert_that!(entry.chunks.iter().any(|c| c.kind == ChunkKind::Context)).is_false();
+  }
+
+  #[test]
+  fn test_with_delta() {
+    let delta = |ms| format_delta(Duration::milliseconds(ms));
+    assert_that!(delta(3)).is_equal_to("+3ms".to_string());
+    assert_that!(delta(12_400)).is_equal_to("+12.4s".to_string());
+    assert_that!(delta(125_000)).is_equal_to("+2m05s".to_string());
+    assert_that!(delta(-7_380_000)).is_equal_to("-2h03m".to_string());
+
+    let message = Message {
+      kind: crate::parser::MessageKind::Plain,
+      timestamp: Some("2020-11-25T21:22:53Z".parse().unwrap()),
+      level: None,
+      text: Some("hello".to_string()),
+      metadata: std::collections::HashMap::new(),
+      reader_metadata: None,
+      mapped_fields: std::collections::HashMap::new(),
+      raw: String::new()
+    };
+    let entry = MessageEntry { chunks: classify(&message), message };
+
+    let previous = Some("2020-11-25T21:22:50.5Z".parse().unwrap());
+    let chunks = with_delta(&entry, previous).chunks;
+    let kinds: Vec<ChunkKind> = chunks.iter().take(3).map(|c| c.kind).collect();
+    assert_that!(kinds).is_equal_to(vec![ChunkKind::Date, ChunkKind::Time, ChunkKind::Delta]);
+    assert_that!(chunks[2].value).is_equal_to(Some("+2.5s".to_string()));
+
+    let chunks = with_delta(&entry, None).chunks;
+    assert_that!(chunks[2].value).is_equal_to(Some(String::new()));
+  }
+
+  #[test]
+  fn test_collapse_repeats() {
+    let entry = |text: &str, second: u32| {
+      let message = Message {
+        kind: crate::parser::MessageKind::Plain,
+        timestamp: Some(format!("2020-11-25T21:22:{:02}Z", second).parse().unwrap()),
+        level: None,
+        text: Some(text.to_string()),
+        metadata: std::collections::HashMap::new(),
+        reader_metadata: None,
+        mapped_fields: std::collections::HashMap::new(),
+        raw: String::new()
+      };
+
+      MessageEntry { chunks: classify(&message), message }
+    };
+
+    let repeats = |entry: &MessageEntry| entry.chunks.iter()
+      .find(|c| c.kind == ChunkKind::Repeat)
+      .and_then(|c| c.value.clone());
+
+    let mut collapser = RepeatCollapser::default();
+    assert_that!(collapser.push(entry("retrying", 1))).is_none();
+    assert_that!(collapser.push(entry("retrying", 2))).is_none();
+    assert_that!(collapser.push(entry("retrying", 3))).is_none();
+
+    let collapsed = collapser.push(entry("connected", 4)).unwrap();
+    assert_that!(collapsed.message.text).is_equal_to(Some("retrying".to_string()));
+    assert_that!(repeats(&collapsed)).is_equal_to(Some("×3".to_string()));
+
+    let last = collapser.finish().unwrap();
+    assert_that!(last.message.text).is_equal_to(Some("connected".to_string()));
+    assert_that!(repeats(&last)).is_none();
+    assert_that!(collapser.finish()).is_none();
+  }
+
+  #[test]
+  fn test_expand_fields() {
+    let message = Message {
+      kind: crate::parser::MessageKind::Json,
+      timestamp: None,
+      level: None,
+      text: Some("request done".to_string()),
+      metadata: hashmap!{
+        "status".to_string() => serde_json::json!(200),
+        "duration_ms".to_string() => serde_json::json!(12)
+      },
+      reader_metadata: None,
+      mapped_fields: std::collections::HashMap::new(),
+      raw: String::new()
+    };
+    let entry = expand_fields(&MessageEntry { chunks: classify(&message), message });
+
+    let lines: Vec<String> = styled_render(&entry, &StyleProfile::plain(), Some(80))
+      .iter()
+      .map(|line| line.trim_end().to_string())
+      .collect();
+
+    assert_that!(lines).has_length(3);
+    assert_that!(lines[0].ends_with("request done")).is_true();
+    assert_that!(lines[1].ends_with("  status:      200")).is_true();
+    assert_that!(lines[2].ends_with("  duration_ms: 12")).is_true();
+  }
+
+  #[test]
+  fn test_collapse_lines() {
+    let entry = |text: &str| {
+      let message = Message {
+        kind: crate::parser::MessageKind::Plain,
+        timestamp: None,
+        level: None,
+        text: Some(text.to_string()),
+        metadata: std::collections::HashMap::new(),
+        reader_metadata: None,
+        mapped_fields: std::collections::HashMap::new(),
+        raw: String::new()
+      };
+
+      MessageEntry { chunks: classify(&message), message }
+    };
+
+    assert_that!(collapse_lines(&entry("one line"))).is_none();
+
+    let collapsed = collapse_lines(&entry("oops\n    at a\n    at b")).unwrap();
+    let lines: Vec<String> = styled_render(&collapsed, &StyleProfile::plain(), Some(80))
+      .iter()
+      .map(|line| line.trim_end().to_string())
+      .collect();
+
+    assert_that!(lines).has_length(1);
+    assert_that!(lines[0].ends_with("oops ▸ [+2 lines]")).is_true();
+  }
 }
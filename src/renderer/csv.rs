@@ -0,0 +1,102 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+use std::io::{self, Write};
+use std::sync::Arc;
+use std::sync::mpsc::Receiver;
+use std::thread::{self, JoinHandle};
+
+use crate::config::Config;
+use crate::parser::Message;
+use crate::renderer::types::*;
+use crate::renderer::common::column_value;
+
+/// Columns written if `--columns` is unset
+pub static DEFAULT_COLUMNS: &[&str] = &["timestamp", "level", "message"];
+
+/// Quotes a CSV value per RFC 4180, if needed
+fn quote(s: &str) -> String {
+  if s.contains(&[',', '"', '\n', '\r'][..]) {
+    format!("\"{}\"", s.replace('"', "\"\""))
+  } else {
+    s.to_string()
+  }
+}
+
+fn cell(message: &Message, column: &str) -> String {
+  quote(&column_value(message, column))
+}
+
+fn csv_row<S: AsRef<str>>(message: &Message, columns: &[S]) -> String {
+  columns.iter()
+    .map(|column| cell(message, column.as_ref()))
+    .collect::<Vec<_>>()
+    .join(",")
+}
+
+/// Writes messages as CSV, with one column per `--columns` entry and a header
+/// row
+pub fn csv_renderer(config: Arc<Config>, rx: Receiver<LogEntry>) -> JoinHandle<()> {
+  thread::Builder::new().name("csv_renderer".to_string()).spawn(move || {
+    let columns: Vec<String> = if config.columns.is_empty() {
+      DEFAULT_COLUMNS.iter().map(|c| c.to_string()).collect()
+    } else {
+      config.columns.clone()
+    };
+
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+
+    let header: Vec<String> = columns.iter().map(|c| quote(c)).collect();
+    if writeln!(out, "{}", header.join(",")).is_err() {
+      return;
+    }
+
+    for entry in rx {
+      if entry.eof.is_some() {
+        break;
+      }
+
+      if let Some(entry) = entry.message {
+        if writeln!(out, "{}", csv_row(&entry.message, &columns)).is_err() {
+          break;
+        }
+      }
+    }
+  }).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::collections::HashMap;
+
+  use serde_json::json;
+  use spectral::prelude::*;
+
+  use crate::parser::{LogLevel, MessageKind};
+  use crate::timezone;
+
+  #[test]
+  fn test_csv_row() {
+    let message = Message {
+      kind: MessageKind::Json,
+      timestamp: Some("2020-11-25T21:22:50Z".parse().unwrap()),
+      level: Some(LogLevel::Warning),
+      text: Some("said \"hi\", then left".to_string()),
+      metadata: hashmap!{
+        "pod".to_string() => json!("api-1"),
+        "http".to_string() => json!({"status": 503})
+      },
+      reader_metadata: None,
+      mapped_fields: HashMap::new(),
+      raw: String::new()
+    };
+
+    let timestamp = timezone::to_display(message.timestamp.unwrap()).to_rfc3339();
+    let columns = ["timestamp", "level", "pod", "message", "http.status", "missing"];
+    assert_that!(csv_row(&message, &columns)).is_equal_to(format!(
+      r#"{},warning,api-1,"said ""hi"", then left",503,"#, timestamp
+    ));
+  }
+}
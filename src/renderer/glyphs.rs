@@ -0,0 +1,207 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+use std::borrow::Cow;
+use std::env;
+use std::str::FromStr;
+use std::sync::RwLock;
+
+use simple_error::SimpleError;
+
+use crate::classifier::{Chunk, ChunkKind};
+use crate::parser::{LogLevel, Message};
+
+/// How the styled and interactive renderers show levels and sources, i.e.
+/// `--glyphs`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GlyphMode {
+  /// Full level names, e.g. `warning`
+  Text,
+
+  /// Single letter levels, e.g. `W`
+  Ascii,
+
+  /// Unicode symbols for levels and sources, e.g. `▲`
+  Unicode,
+
+  /// Icons from a Nerd Font (https://www.nerdfonts.com/) for levels and
+  /// sources
+  Nerd
+}
+
+impl FromStr for GlyphMode {
+  type Err = SimpleError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "text" => Ok(GlyphMode::Text),
+      "ascii" => Ok(GlyphMode::Ascii),
+      "unicode" => Ok(GlyphMode::Unicode),
+      "nerd" => Ok(GlyphMode::Nerd),
+      _ => bail!("invalid glyph mode: {}", s)
+    }
+  }
+}
+
+/// If true, the locale and terminal can probably show non-ASCII glyphs
+fn unicode_supported() -> bool {
+  let locale = ["LC_ALL", "LC_CTYPE", "LANG"].iter()
+    .filter_map(|name| env::var(name).ok())
+    .find(|value| !value.is_empty())
+    .unwrap_or_default()
+    .to_lowercase();
+
+  let term = env::var("TERM").unwrap_or_default();
+
+  (locale.contains("utf-8") || locale.contains("utf8"))
+    && term != "linux" && term != "dumb"
+}
+
+impl GlyphMode {
+  /// The mode to actually use, falling back to ASCII if glyphs probably can't
+  /// be shown
+  pub fn resolve(self) -> GlyphMode {
+    match self {
+      GlyphMode::Unicode | GlyphMode::Nerd if !unicode_supported() => GlyphMode::Ascii,
+      mode => mode
+    }
+  }
+
+  fn level(self, level: LogLevel) -> Option<&'static str> {
+    let glyph = match (self, level) {
+      (GlyphMode::Text, _) => return None,
+      (_, LogLevel::Plain) => " ",
+
+      (GlyphMode::Ascii, LogLevel::Debug) => "D",
+      (GlyphMode::Ascii, LogLevel::Info) => "I",
+      (GlyphMode::Ascii, LogLevel::Warning) => "W",
+      (GlyphMode::Ascii, LogLevel::Error) => "E",
+      (GlyphMode::Ascii, LogLevel::Fatal) => "F",
+      (GlyphMode::Ascii, LogLevel::Int) => "*",
+
+      (GlyphMode::Unicode, LogLevel::Debug) => "·",
+      (GlyphMode::Unicode, LogLevel::Info) => "●",
+      (GlyphMode::Unicode, LogLevel::Warning) => "▲",
+      (GlyphMode::Unicode, LogLevel::Error) => "✖",
+      (GlyphMode::Unicode, LogLevel::Fatal) => "☠",
+      (GlyphMode::Unicode, LogLevel::Int) => "»",
+
+      // nf-fa-bug, info_circle, warning, times_circle, bomb, cog
+      (GlyphMode::Nerd, LogLevel::Debug) => "\u{f188}",
+      (GlyphMode::Nerd, LogLevel::Info) => "\u{f05a}",
+      (GlyphMode::Nerd, LogLevel::Warning) => "\u{f071}",
+      (GlyphMode::Nerd, LogLevel::Error) => "\u{f057}",
+      (GlyphMode::Nerd, LogLevel::Fatal) => "\u{f1e2}",
+      (GlyphMode::Nerd, LogLevel::Int) => "\u{f013}"
+    };
+
+    Some(glyph)
+  }
+
+  /// Icons for a pod, container, and file
+  fn source_icons(self) -> Option<(&'static str, &'static str, &'static str)> {
+    match self {
+      GlyphMode::Text | GlyphMode::Ascii => None,
+      GlyphMode::Unicode => Some(("⎈", "▣", "≡")),
+
+      // nf-md-kubernetes, nf-md-docker, nf-fa-file_text_o
+      GlyphMode::Nerd => Some(("\u{f10fe}", "\u{f0868}", "\u{f0f6}"))
+    }
+  }
+
+  /// Labels a reader source with icons for its kind: a file path, a
+  /// Kubernetes `pod/container`, or a pod
+  fn source(self, source: &str) -> Option<String> {
+    let (pod, container, file) = self.source_icons()?;
+
+    let is_file = source.starts_with('/') || source.starts_with("./")
+      || source.starts_with('~') || source.ends_with(".log");
+
+    Some(match source.find('/') {
+      _ if is_file => format!("{} {}", file, source),
+      Some(i) => format!("{} {} {} {}", pod, &source[..i], container, &source[i + 1..]),
+      None => format!("{} {}", pod, source)
+    })
+  }
+}
+
+lazy_static! {
+  static ref GLYPHS: RwLock<GlyphMode> = RwLock::new(GlyphMode::Text);
+}
+
+/// Sets the glyph mode used by the styled and interactive renderers
+pub fn set_glyphs(mode: GlyphMode) {
+  if let Ok(mut current) = GLYPHS.write() {
+    *current = mode;
+  }
+}
+
+fn current() -> GlyphMode {
+  GLYPHS.read().map(|g| *g).unwrap_or(GlyphMode::Text)
+}
+
+/// The width of the level column, if glyphs replace level names
+pub fn level_width() -> Option<usize> {
+  match current() {
+    GlyphMode::Text => None,
+    _ => Some(1)
+  }
+}
+
+fn replace(mode: GlyphMode, message: &Message, chunks: &[Chunk]) -> Option<Vec<Chunk>> {
+  if mode == GlyphMode::Text {
+    return None;
+  }
+
+  let source = message.reader_metadata.as_ref().and_then(|m| m.source.as_deref());
+
+  let chunks = chunks.iter()
+    .map(|chunk| {
+      let value = match chunk.kind {
+        ChunkKind::Level(level) => mode.level(level).map(str::to_string),
+        ChunkKind::Context if chunk.value.as_deref() == source => {
+          source.and_then(|s| mode.source(s))
+        },
+        _ => None
+      };
+
+      match value {
+        Some(value) => Chunk { value: Some(value), ..chunk.clone() },
+        None => chunk.clone()
+      }
+    })
+    .collect();
+
+  Some(chunks)
+}
+
+/// Replaces level names and sources with glyphs, per the glyph mode
+pub fn apply<'a>(message: &Message, chunks: Cow<'a, [Chunk]>) -> Cow<'a, [Chunk]> {
+  match replace(current(), message, &chunks) {
+    Some(replaced) => Cow::Owned(replaced),
+    None => chunks
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use spectral::prelude::*;
+
+  #[test]
+  fn test_source() {
+    assert_that!(GlyphMode::Unicode.source("api-7d9f8c")).is_equal_to(Some("⎈ api-7d9f8c".to_string()));
+    assert_that!(GlyphMode::Unicode.source("api-7d9f8c/envoy"))
+      .is_equal_to(Some("⎈ api-7d9f8c ▣ envoy".to_string()));
+    assert_that!(GlyphMode::Unicode.source("/var/log/app.log"))
+      .is_equal_to(Some("≡ /var/log/app.log".to_string()));
+    assert_that!(GlyphMode::Ascii.source("api")).is_none();
+  }
+
+  #[test]
+  fn test_level() {
+    assert_that!(GlyphMode::Ascii.level(LogLevel::Warning)).is_equal_to(Some("W"));
+    assert_that!(GlyphMode::Unicode.level(LogLevel::Error)).is_equal_to(Some("✖"));
+    assert_that!(GlyphMode::Text.level(LogLevel::Error)).is_none();
+  }
+}
@@ -0,0 +1,165 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+
+use regex::{Captures, Regex};
+
+use crate::parser::LogLevel;
+
+lazy_static! {
+  static ref UUID: Regex = Regex::new(
+    r"\b[0-9a-fA-F]{8}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{12}\b"
+  ).unwrap();
+
+  static ref IP: Regex = Regex::new(r"\b\d{1,3}(?:\.\d{1,3}){3}(?::\d+)?\b").unwrap();
+
+  /// `0x` literals, and long runs of hex digits like hashes and IDs
+  static ref HEX: Regex = Regex::new(r"\b0x[0-9a-fA-F]+\b|\b[0-9a-fA-F]{8,}\b").unwrap();
+
+  static ref NUMBER: Regex = Regex::new(r"\d+(?:\.\d+)?").unwrap();
+
+  static ref PLACEHOLDER: Regex = Regex::new(r"<(?:uuid|ip|hex|num)>").unwrap();
+}
+
+/// Replaces the variable parts of a message with placeholders, so messages
+/// differing only in IDs, addresses, or numbers share a template, e.g.
+/// `request 42 from 10.0.0.7 took 1.5ms` becomes
+/// `request <num> from <ip> took <num>ms`
+pub fn canonicalize(text: &str) -> String {
+  let text = UUID.replace_all(text.trim(), "<uuid>");
+  let text = IP.replace_all(&text, "<ip>");
+  let text = HEX.replace_all(&text, |caps: &Captures| {
+    let hex = &caps[0];
+    if hex.starts_with("0x") || hex.chars().any(|c| c.is_ascii_digit()) {
+      if hex.chars().all(|c| c.is_ascii_digit()) { "<num>" } else { "<hex>" }
+    } else {
+      // a word that happens to be spelled with a-f, like "acceded"
+      hex
+    }.to_string()
+  });
+
+  NUMBER.replace_all(&text, "<num>").into_owned()
+}
+
+/// A regex matching the text of messages with a template, as an exact match
+pub fn template_regex(template: &str) -> String {
+  let mut regex = String::from(r"^\s*");
+  let mut last = 0;
+  for placeholder in PLACEHOLDER.find_iter(template) {
+    regex.push_str(&regex::escape(&template[last..placeholder.start()]));
+    regex.push_str(match placeholder.as_str() {
+      "<uuid>" => r"[0-9a-fA-F]{8}(?:-[0-9a-fA-F]{4}){3}-[0-9a-fA-F]{12}",
+      "<ip>" => r"\d{1,3}(?:\.\d{1,3}){3}(?::\d+)?",
+      "<hex>" => r"(?:0x)?[0-9a-fA-F]+",
+      _ => r"\d+(?:\.\d+)?"
+    });
+
+    last = placeholder.end();
+  }
+
+  regex.push_str(&regex::escape(&template[last..]));
+  regex.push_str(r"\s*$");
+  regex
+}
+
+/// Messages sharing a template
+#[derive(Debug, Clone, PartialEq)]
+pub struct TemplateGroup {
+  pub template: String,
+  pub count: usize,
+
+  /// The most severe level of any message in the group
+  pub level: Option<LogLevel>,
+
+  /// The latest message in the group, e.g. an index into a list of entries
+  pub example: usize
+}
+
+/// Counts messages by template
+#[derive(Debug, Default)]
+pub struct TemplateGroups {
+  groups: HashMap<String, TemplateGroup>
+}
+
+impl TemplateGroups {
+  /// Counts a message's text, with `example` identifying it
+  pub fn add(&mut self, text: &str, level: Option<LogLevel>, example: usize) {
+    let template = canonicalize(text);
+    let level = match level {
+      Some(LogLevel::Plain) | Some(LogLevel::Int) => None,
+      level => level
+    };
+
+    let group = self.groups.entry(template.clone()).or_insert_with(|| TemplateGroup {
+      template,
+      count: 0,
+      level: None,
+      example
+    });
+
+    group.count += 1;
+    group.level = group.level.max(level);
+    group.example = example;
+  }
+
+  /// Groups, most common first
+  pub fn sorted(&self) -> Vec<TemplateGroup> {
+    let mut groups: Vec<TemplateGroup> = self.groups.values().cloned().collect();
+    groups.sort_by(|a, b| (Reverse(a.count), &a.template).cmp(&(Reverse(b.count), &b.template)));
+    groups
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use spectral::prelude::*;
+
+  #[test]
+  fn test_canonicalize() {
+    assert_that!(canonicalize("request 42 from 10.0.0.7:8080 took 1.5ms"))
+      .is_equal_to("request <num> from <ip> took <num>ms".to_string());
+    assert_that!(canonicalize("user 0b8a1c2e-7d3f-4e5a-9b6c-1d2e3f4a5b6c not found"))
+      .is_equal_to("user <uuid> not found".to_string());
+    assert_that!(canonicalize("commit 3f9c2ab14e at 0xdeadbeef, feedface, id 123456789"))
+      .is_equal_to("commit <hex> at <hex>, feedface, id <num>".to_string());
+
+    for text in &[
+      "request 42 from 10.0.0.7:8080 took 1.5ms",
+      "user 0b8a1c2e-7d3f-4e5a-9b6c-1d2e3f4a5b6c not found (1+1)",
+      "commit 3f9c2ab14e at 0xdeadbeef"
+    ] {
+      let re = Regex::new(&template_regex(&canonicalize(text))).unwrap();
+      assert_that!(re.is_match(text)).is_true();
+    }
+
+    let re = Regex::new(&template_regex("request <num> done")).unwrap();
+    assert_that!(re.is_match("request 7 done")).is_true();
+    assert_that!(re.is_match("request 7 done twice")).is_false();
+  }
+
+  #[test]
+  fn test_groups() {
+    let mut groups = TemplateGroups::default();
+    groups.add("request 1 done", Some(LogLevel::Info), 0);
+    groups.add("connection lost", Some(LogLevel::Error), 1);
+    groups.add("request 2 done", Some(LogLevel::Warning), 2);
+
+    assert_that!(groups.sorted()).is_equal_to(vec![
+      TemplateGroup {
+        template: "request <num> done".to_string(),
+        count: 2,
+        level: Some(LogLevel::Warning),
+        example: 2
+      },
+      TemplateGroup {
+        template: "connection lost".to_string(),
+        count: 1,
+        level: Some(LogLevel::Error),
+        example: 1
+      }
+    ]);
+  }
+}
@@ -0,0 +1,297 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+use ansi_term::Style;
+use regex::{CaptureLocations, Regex};
+
+use crate::classifier::ChunkKind;
+use crate::parser::LogLevel;
+use crate::style::StyleProfile;
+
+lazy_static! {
+  static ref TOKEN: Regex = Regex::new(concat!(
+    r#"(?P<key>"(?:[^"\\]|\\.)*"\s*:)"#,
+    r#"|(?P<string>"(?:[^"\\]|\\.)*")"#,
+    r"|(?P<quoted>'[^'\n]*')",
+    r"|(?P<uuid>\b[0-9a-fA-F]{8}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{12}\b)",
+    r"|(?P<literal>\b(?:true|false|null)\b)",
+    r"|(?P<number>-?\b\d+(?:\.\d+)?(?:[eE][+-]?\d+)?(?:(?:ns|us|µs|ms|s|m|h|[kKMG]i?B|B)\b|%|\b))"
+  )).unwrap();
+}
+
+/// The kinds of tokens highlighted within message text
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Token {
+  Key,
+  String,
+  Uuid,
+  Number
+}
+
+/// Picks token colors from the profile, so highlighting follows the theme
+fn token_style(token: Token, base: Style, profile: &StyleProfile) -> Option<Style> {
+  let kind = match token {
+    Token::Key => ChunkKind::FieldKey,
+    Token::String => ChunkKind::Level(LogLevel::Info),
+    Token::Uuid => ChunkKind::Level(LogLevel::Int),
+    Token::Number => ChunkKind::Level(LogLevel::Debug)
+  };
+
+  profile.get_chunk_style(&kind).map(|style| Style {
+    background: base.background,
+    is_bold: base.is_bold || style.is_bold,
+    ..*style
+  })
+}
+
+/// `'quoted'` strings only count if they aren't apostrophes, e.g. in `don't`
+fn is_quoted(text: &str, start: usize, end: usize) -> bool {
+  let before = text[..start].chars().next_back();
+  let after = text[end..].chars().next();
+
+  !before.map_or(false, char::is_alphanumeric) && !after.map_or(false, char::is_alphanumeric)
+}
+
+/// Returns the token matched by `TOKEN`, based on which of its groups matched:
+/// key, string, quoted, uuid, literal, or number
+fn classify_token(
+  text: &str, locations: &CaptureLocations, depth: usize
+) -> Option<Token> {
+  let (start, end) = locations.get(0)?;
+  let group = (1..locations.len()).find(|i| locations.get(*i).is_some())?;
+
+  match group {
+    1 => Some(Token::Key),
+    2 => Some(Token::String),
+    3 if is_quoted(text, start, end) => Some(Token::String),
+    4 => Some(Token::Uuid),
+    // `true` and friends are only interesting inside JSON
+    5 if depth > 0 => Some(Token::Number),
+    6 => Some(Token::Number),
+    _ => None
+  }
+}
+
+/// Tracks how deeply nested in JSON brackets some plain text leaves us
+fn update_depth(depth: usize, text: &str) -> usize {
+  text.chars().fold(depth, |depth, c| match c {
+    '{' | '[' => depth + 1,
+    '}' | ']' => depth.saturating_sub(1),
+    _ => depth
+  })
+}
+
+/// Paints a line of message text, coloring quoted strings, JSON keys, numbers,
+/// and UUIDs distinctly
+///
+/// Tokens are detected per line, so strings that wrap aren't highlighted.
+pub fn highlight(text: &str, base: Style, profile: &StyleProfile) -> String {
+  let mut out = String::new();
+  let mut plain = String::new();
+  let mut depth = 0;
+  let mut pos = 0;
+
+  let mut locations = TOKEN.capture_locations();
+  while let Some(matched) = TOKEN.captures_read_at(&mut locations, text, pos) {
+    let token = classify_token(text, &locations, depth);
+    let style = token.and_then(|t| token_style(t, base, profile));
+
+    plain.push_str(&text[pos..matched.start()]);
+    depth = update_depth(depth, &text[pos..matched.start()]);
+
+    match style {
+      Some(style) => {
+        if !plain.is_empty() {
+          out.push_str(&base.paint(plain.split_off(0)).to_string());
+        }
+
+        out.push_str(&style.paint(matched.as_str()).to_string());
+        pos = matched.end();
+      },
+      None => {
+        // skip a single character, in case a shorter token starts inside
+        let next = matched.start() + text[matched.start()..].chars().next().unwrap().len_utf8();
+        plain.push_str(&text[matched.start()..next]);
+        depth = update_depth(depth, &text[matched.start()..next]);
+        pos = next;
+      }
+    }
+  }
+
+  plain.push_str(&text[pos..]);
+  if !plain.is_empty() {
+    out.push_str(&base.paint(plain).to_string());
+  }
+
+  out
+}
+
+/// A piece of already-styled text: an escape sequence, or a visible character
+enum Piece<'a> {
+  Escape(&'a str),
+  Char(&'a str)
+}
+
+/// Splits styled text into escape sequences (CSI, e.g. colors, and OSC, e.g.
+/// hyperlinks) and visible characters
+fn pieces(text: &str) -> Vec<Piece<'_>> {
+  let mut pieces = Vec::new();
+  let mut chars = text.char_indices().peekable();
+  while let Some((start, c)) = chars.next() {
+    if c != '\x1b' {
+      pieces.push(Piece::Char(&text[start..start + c.len_utf8()]));
+      continue;
+    }
+
+    let mut end = text.len();
+    match chars.next() {
+      Some((_, '[')) => {
+        for (i, c) in chars.by_ref() {
+          if ('@'..='~').contains(&c) {
+            end = i + 1;
+            break;
+          }
+        }
+      },
+      Some((_, ']')) => {
+        while let Some((i, c)) = chars.next() {
+          if c == '\x07' {
+            end = i + 1;
+            break;
+          } else if c == '\x1b' && chars.peek().map(|(_, c)| *c) == Some('\\') {
+            end = i + 2;
+            chars.next();
+            break;
+          }
+        }
+      },
+      Some((i, c)) => end = i + c.len_utf8(),
+      None => ()
+    }
+
+    pieces.push(Piece::Escape(&text[start..end]));
+  }
+
+  pieces
+}
+
+/// Paints matches of `pattern` in a line of already-styled text with
+/// `style`, e.g. to show search results
+///
+/// Styles in effect around each match are restored after it, so matches can
+/// span differently styled chunks.
+pub fn highlight_matches(line: &str, pattern: &Regex, style: Style) -> String {
+  let pieces = pieces(line);
+  let visible: String = pieces.iter()
+    .filter_map(|p| match p { Piece::Char(c) => Some(*c), _ => None })
+    .collect();
+
+  let mut matches = pattern.find_iter(&visible)
+    .filter(|m| !m.as_str().is_empty())
+    .map(|m| (m.start(), m.end()))
+    .peekable();
+
+  if matches.peek().is_none() {
+    return line.to_string();
+  }
+
+  let prefix = style.prefix().to_string();
+  let mut out = String::new();
+  let mut active: Vec<&str> = Vec::new();
+  let mut in_match = false;
+  let mut pos = 0;
+
+  for piece in pieces {
+    match piece {
+      Piece::Escape(escape) => {
+        out.push_str(escape);
+
+        if escape.starts_with("\x1b[") && escape.ends_with('m') {
+          if escape == "\x1b[0m" || escape == "\x1b[m" {
+            active.clear();
+          } else {
+            active.push(escape);
+          }
+
+          if in_match {
+            out.push_str(&prefix);
+          }
+        }
+      },
+      Piece::Char(c) => {
+        if !in_match && matches.peek().map_or(false, |(start, _)| *start == pos) {
+          out.push_str(&prefix);
+          in_match = true;
+        }
+
+        out.push_str(c);
+        pos += c.len();
+
+        if in_match && matches.peek().map_or(false, |(_, end)| *end == pos) {
+          matches.next();
+          in_match = false;
+
+          out.push_str("\x1b[0m");
+          active.iter().for_each(|escape| out.push_str(escape));
+        }
+      }
+    }
+  }
+
+  if in_match {
+    out.push_str("\x1b[0m");
+  }
+
+  out
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use ansi_term::Color;
+  use spectral::prelude::*;
+
+  #[test]
+  fn test_highlight() {
+    let profile = StyleProfile::default_normal();
+    let base = Style::new();
+
+    let line = highlight(r#"done in 12ms {"id": "x", "ok": true} don't 'quoted'"#, base, &profile);
+    let expected = [
+      base.paint("done in ").to_string(),
+      Color::Cyan.paint("12ms").to_string(),
+      base.paint(" {").to_string(),
+      Color::Cyan.dimmed().paint(r#""id":"#).to_string(),
+      base.paint(" ").to_string(),
+      Color::Green.paint(r#""x""#).to_string(),
+      base.paint(", ").to_string(),
+      Color::Cyan.dimmed().paint(r#""ok":"#).to_string(),
+      base.paint(" ").to_string(),
+      Color::Cyan.paint("true").to_string(),
+      base.paint("} don't ").to_string(),
+      Color::Green.paint("'quoted'").to_string()
+    ].concat();
+    assert_that!(line).is_equal_to(expected);
+
+    let uuid = "3f2b8c4e-1d2a-4b6f-9c1e-7a8b9c0d1e2f";
+    assert_that!(highlight(uuid, base, &profile))
+      .is_equal_to(Color::Purple.bold().paint(uuid).to_string());
+
+    // no colors to pick from, so nothing to highlight
+    assert_that!(highlight("took 5s", base, &StyleProfile::plain()))
+      .is_equal_to("took 5s".to_string());
+  }
+
+  #[test]
+  fn test_highlight_matches() {
+    let re = Regex::new("lo w").unwrap();
+    let line = format!("{} {}", Color::Red.paint("hello"), Color::Blue.paint("world"));
+
+    assert_that!(highlight_matches(&line, &re, Style::new().reverse())).is_equal_to(
+      "\x1b[31mhel\x1b[7mlo\x1b[0m\x1b[7m \x1b[34m\x1b[7mw\x1b[0m\x1b[34morld\x1b[0m".to_string()
+    );
+
+    assert_that!(highlight_matches("plain", &re, Style::new().reverse()))
+      .is_equal_to("plain".to_string());
+  }
+}
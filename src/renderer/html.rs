@@ -0,0 +1,245 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+use std::io::{self, Write};
+use std::sync::Arc;
+use std::sync::mpsc::Receiver;
+use std::thread::{self, JoinHandle};
+
+use crate::classifier::{Chunk, ChunkKind};
+use crate::config::Config;
+use crate::parser::LogLevel;
+use crate::renderer::types::*;
+use crate::renderer::common::{bucketize, display_chunks};
+
+static HEADER: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>woodchipper</title>
+<style>
+  body { background: #1d1f21; color: #c5c8c6; font: 13px/1.5 monospace; margin: 0; }
+  #filter { position: sticky; top: 0; background: #282a2e; padding: 8px; }
+  #filter input { width: 100%; box-sizing: border-box; font: inherit; padding: 4px;
+    background: #1d1f21; color: inherit; border: 1px solid #373b41; }
+  .entry { padding: 1px 8px; white-space: pre-wrap; word-break: break-word; }
+  .entry:hover { background: #282a2e; }
+  .date, .time, .delta, .context { color: #707880; }
+  .context { float: right; }
+  .key { color: #8abeb7; }
+  .repeat { color: #f0c674; font-weight: bold; }
+  .level { display: inline-block; min-width: 5ch; padding: 0 4px; border-radius: 3px;
+    text-align: center; color: #1d1f21; }
+  .level-debug { background: #8abeb7; }
+  .level-info { background: #b5bd68; }
+  .level-warning { background: #f0c674; }
+  .level-error { background: #de935f; }
+  .level-fatal { background: #cc6666; font-weight: bold; }
+  .level-int { background: #b294bb; }
+  .level-plain { background: none; color: inherit; }
+  details { display: inline; }
+  summary { display: inline; cursor: pointer; color: #707880; }
+  table { border-collapse: collapse; margin: 2px 0 2px 4ch; }
+  td { padding: 0 8px 0 0; vertical-align: top; }
+</style>
+</head>
+<body>
+<div id="filter"><input type="search" placeholder="filter" autofocus></div>
+<div id="log">
+"#;
+
+static FOOTER: &str = r#"</div>
+<script>
+  var input = document.querySelector('#filter input');
+  input.addEventListener('input', function () {
+    var query = input.value.toLowerCase();
+    document.querySelectorAll('.entry').forEach(function (entry) {
+      var match = entry.textContent.toLowerCase().indexOf(query) !== -1;
+      entry.style.display = match ? '' : 'none';
+    });
+  });
+</script>
+</body>
+</html>
+"#;
+
+fn escape(s: &str) -> String {
+  let mut escaped = String::with_capacity(s.len());
+  for c in s.chars() {
+    match c {
+      '&' => escaped.push_str("&amp;"),
+      '<' => escaped.push_str("&lt;"),
+      '>' => escaped.push_str("&gt;"),
+      '"' => escaped.push_str("&quot;"),
+      '\'' => escaped.push_str("&#39;"),
+      c => escaped.push(c)
+    }
+  }
+
+  escaped
+}
+
+fn level_class(level: LogLevel) -> &'static str {
+  match level {
+    LogLevel::Debug => "debug",
+    LogLevel::Info => "info",
+    LogLevel::Warning => "warning",
+    LogLevel::Error => "error",
+    LogLevel::Fatal => "fatal",
+    LogLevel::Plain => "plain",
+    LogLevel::Int => "int"
+  }
+}
+
+fn chunk_class(kind: ChunkKind) -> String {
+  match kind {
+    ChunkKind::Level(level) => format!("level level-{}", level_class(level)),
+    ChunkKind::Date => "date".to_string(),
+    ChunkKind::Time => "time".to_string(),
+    ChunkKind::Delta => "delta".to_string(),
+    ChunkKind::Repeat => "repeat".to_string(),
+    ChunkKind::Collapsed => "collapsed".to_string(),
+    ChunkKind::Text => "text".to_string(),
+    ChunkKind::Context => "context".to_string(),
+    ChunkKind::Field => "field".to_string(),
+    ChunkKind::FieldKey => "key".to_string(),
+    ChunkKind::FieldValue => "value".to_string(),
+    ChunkKind::Spacer | ChunkKind::Other => String::new()
+  }
+}
+
+fn render_chunk(chunk: &Chunk, out: &mut String) {
+  if chunk.pad_left {
+    out.push(' ');
+  }
+
+  out.push_str(&format!(r#"<span class="{}">"#, chunk_class(chunk.kind)));
+  if let Some(value) = &chunk.value {
+    out.push_str(&escape(value));
+  }
+
+  for child in &chunk.children {
+    render_chunk(child, out);
+  }
+
+  out.push_str("</span>");
+
+  if chunk.pad_right {
+    out.push(' ');
+  }
+}
+
+/// Renders a field chunk as a table row, using its key and value children
+fn render_field_row(chunk: &Chunk, out: &mut String) {
+  let part = |kind: ChunkKind| chunk.children.iter()
+    .find(|c| c.kind == kind)
+    .and_then(|c| c.value.as_ref())
+    .map(|v| escape(v))
+    .unwrap_or_default();
+
+  out.push_str(&format!(
+    r#"<tr><td class="key">{}</td><td>{}</td></tr>"#,
+    part(ChunkKind::FieldKey).trim_end_matches('='),
+    part(ChunkKind::FieldValue)
+  ));
+}
+
+/// Renders a message as a single HTML element
+///
+/// Fields are moved into a collapsible table following the message, rather than
+/// being shown inline as in other renderers.
+pub fn html_render(entry: &MessageEntry) -> String {
+  let level = entry.message.level.unwrap_or(LogLevel::Plain);
+  let chunks = display_chunks(entry);
+  let (left, center, right) = bucketize(chunks.iter());
+
+  let mut out = format!(r#"<div class="entry entry-{}">"#, level_class(level));
+  for chunk in right {
+    render_chunk(chunk, &mut out);
+  }
+
+  let mut fields = Vec::new();
+  for chunk in left.into_iter().chain(center) {
+    if chunk.kind == ChunkKind::Field {
+      fields.push(chunk);
+    } else {
+      render_chunk(chunk, &mut out);
+    }
+  }
+
+  if !fields.is_empty() {
+    out.push_str(&format!(
+      " <details><summary>[{} field{}]</summary><table>",
+      fields.len(),
+      if fields.len() == 1 { "" } else { "s" }
+    ));
+
+    for field in fields {
+      render_field_row(field, &mut out);
+    }
+
+    out.push_str("</table></details>");
+  }
+
+  out.push_str("</div>");
+  out
+}
+
+/// Writes a standalone HTML page, e.g. to attach to a ticket
+///
+/// Messages are written as they arrive, and the page is finished once input
+/// ends.
+pub fn html_renderer(_: Arc<Config>, rx: Receiver<LogEntry>) -> JoinHandle<()> {
+  thread::Builder::new().name("html_renderer".to_string()).spawn(move || {
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+
+    if out.write_all(HEADER.as_bytes()).is_err() {
+      return;
+    }
+
+    for entry in rx {
+      if entry.eof.is_some() {
+        break;
+      }
+
+      if let Some(message) = entry.message {
+        if writeln!(out, "{}", html_render(&message)).is_err() {
+          return;
+        }
+      }
+    }
+
+    out.write_all(FOOTER.as_bytes()).ok();
+  }).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use spectral::prelude::*;
+
+  use crate::classifier::classify;
+  use crate::parser::{Message, MessageKind};
+
+  #[test]
+  fn test_html_render() {
+    let message = Message {
+      kind: MessageKind::Json,
+      timestamp: None,
+      level: Some(LogLevel::Error),
+      text: Some("<b>oops</b>".to_string()),
+      metadata: hashmap!{ "status".to_string() => serde_json::json!(500) },
+      reader_metadata: None,
+      mapped_fields: std::collections::HashMap::new(),
+      raw: String::new()
+    };
+
+    let chunks = classify(&message);
+    let html = html_render(&MessageEntry { message, chunks });
+
+    assert_that!(html.starts_with(r#"<div class="entry entry-error">"#)).is_true();
+    assert_that!(html.contains("&lt;b&gt;oops&lt;/b&gt;")).is_true();
+    assert_that!(html.contains(r#"<tr><td class="key">status</td><td>500</td></tr>"#)).is_true();
+  }
+}
@@ -0,0 +1,147 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+use std::collections::HashMap;
+use std::env;
+use std::ops::Range;
+use std::sync::RwLock;
+
+use regex::Regex;
+
+use crate::parser::Message;
+use crate::renderer::template::Template;
+
+lazy_static! {
+  static ref URL: Regex = Regex::new(r#"\bhttps?://[^\s<>"'`]+"#).unwrap();
+
+  static ref HYPERLINKS: RwLock<Hyperlinks> = RwLock::new(Hyperlinks::default());
+}
+
+/// Trailing characters that are more likely punctuation than part of a URL
+static TRAILING: &[char] = &['.', ',', ';', ':', '!', '?', ')', ']', '}'];
+
+/// Terminal hyperlink settings for the styled and interactive renderers
+#[derive(Debug, Clone, Default)]
+pub struct Hyperlinks {
+  /// If false, nothing is linked
+  pub enabled: bool,
+
+  /// Link templates by field name, from the config file's `links`, e.g.
+  /// `trace_id: https://tracing.example.com/trace/{trace_id}`
+  pub links: HashMap<String, Template>
+}
+
+/// If true, the terminal probably supports OSC 8 hyperlinks
+///
+/// There's no way to query support, so this checks for terminals known to
+/// support them. Unsupported terminals should ignore the escapes, but some
+/// older ones print them as garbage.
+pub fn supported() -> bool {
+  let var = |name: &str| env::var(name).unwrap_or_default();
+
+  if var("TERM") == "dumb" {
+    return false;
+  }
+
+  let vte_version: u32 = var("VTE_VERSION").parse().unwrap_or(0);
+
+  ["WT_SESSION", "KONSOLE_VERSION", "KITTY_WINDOW_ID", "WEZTERM_EXECUTABLE"]
+    .iter()
+    .any(|name| env::var_os(name).is_some())
+    || vte_version >= 5000
+    || ["iTerm.app", "WezTerm", "vscode", "Hyper", "ghostty"].contains(&var("TERM_PROGRAM").as_str())
+    || ["xterm-kitty", "alacritty", "foot", "xterm-ghostty"].contains(&var("TERM").as_str())
+}
+
+/// Sets the hyperlink settings used by all renderers
+///
+/// Like the layout, this is global as `styled_render()` is called from many
+/// places that don't otherwise need the config.
+pub fn set_hyperlinks(hyperlinks: Hyperlinks) {
+  if let Ok(mut current) = HYPERLINKS.write() {
+    *current = hyperlinks;
+  }
+}
+
+/// If true, URLs and linked fields should be rendered as hyperlinks
+pub fn enabled() -> bool {
+  HYPERLINKS.read().map(|h| h.enabled).unwrap_or(false)
+}
+
+/// Wraps already-styled text in an OSC 8 hyperlink
+pub fn link(url: &str, text: &str) -> String {
+  // control characters would end the escape early
+  let url: String = url.chars().filter(|c| !c.is_control()).collect();
+
+  format!("\x1b]8;;{}\x1b\\{}\x1b]8;;\x1b\\", url, text)
+}
+
+/// Finds URLs in plain text, as byte ranges
+pub fn find_urls(text: &str) -> Vec<Range<usize>> {
+  URL.find_iter(text)
+    .map(|m| m.start()..m.start() + m.as_str().trim_end_matches(TRAILING).len())
+    .collect()
+}
+
+/// Renders plain text with each URL linked, styling URLs and the text between
+/// them with `paint`
+pub fn linkify<F>(text: &str, paint: F) -> String
+where
+  F: Fn(&str) -> String
+{
+  let urls = find_urls(text);
+  if urls.is_empty() {
+    return paint(text);
+  }
+
+  let mut ret = String::new();
+  let mut last = 0;
+  for range in urls {
+    if range.start > last {
+      ret.push_str(&paint(&text[last..range.start]));
+    }
+
+    let url = &text[range.clone()];
+    ret.push_str(&link(url, &paint(url)));
+    last = range.end;
+  }
+
+  if last < text.len() {
+    ret.push_str(&paint(&text[last..]));
+  }
+
+  ret
+}
+
+/// The link for a field, if a template is configured for it
+pub fn field_link(key: &str, message: &Message) -> Option<String> {
+  let hyperlinks = HYPERLINKS.read().ok()?;
+  if !hyperlinks.enabled {
+    return None;
+  }
+
+  hyperlinks.links.get(key).map(|template| template.render(message, None))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use spectral::prelude::*;
+
+  #[test]
+  fn test_find_urls() {
+    let text = "see https://example.com/a?b=c. (or http://x.io/y) not ftp://z";
+    let urls: Vec<&str> = find_urls(text).into_iter().map(|r| &text[r]).collect();
+
+    assert_that!(urls).is_equal_to(vec!["https://example.com/a?b=c", "http://x.io/y"]);
+  }
+
+  #[test]
+  fn test_linkify() {
+    let linked = linkify("go to http://x.io now", |s| s.to_uppercase());
+
+    assert_that!(linked).is_equal_to(
+      "GO TO \x1b]8;;http://x.io\x1b\\HTTP://X.IO\x1b]8;;\x1b\\ NOW".to_string()
+    );
+  }
+}
@@ -0,0 +1,51 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+use std::rc::Rc;
+
+use crate::renderer::layout;
+use crate::style;
+
+use super::state::RcState;
+use super::state::actions as state_actions;
+use super::history;
+
+pub mod actions {
+  use super::*;
+
+  /// Switches to a theme by name, remembering it for later sessions
+  pub fn set_theme(mut state: RcState, name: &str) -> RcState {
+    if !state.config.color.enabled() {
+      return state_actions::internal(state, "colors are disabled, see --color");
+    }
+
+    match state.config.load_style(name) {
+      Ok(style) => Rc::make_mut(&mut state).style = Rc::new(style),
+      Err(e) => {
+        return state_actions::internal(state, &format!("could not load theme {}: {}", name, e));
+      }
+    }
+
+    state.history.borrow_mut().theme = Some(name.to_string());
+
+    let state = history::actions::save(state);
+    state_actions::internal(state, &format!("theme: {}", name))
+  }
+
+  /// Switches to the next theme
+  pub fn cycle_theme(state: RcState) -> RcState {
+    let name = style::next_theme(&state.style.name, &state.config.file().themes);
+    set_theme(state, &name)
+  }
+
+  /// Switches between compact and detailed columns, remembering the choice for
+  /// later sessions
+  pub fn toggle_density(state: RcState) -> RcState {
+    let density = layout::density().toggle();
+    layout::set_density(density);
+
+    state.history.borrow_mut().density = Some(density);
+
+    let state = history::actions::save(state);
+    state_actions::internal(state, &format!("density: {}", density))
+  }
+}
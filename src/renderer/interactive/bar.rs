@@ -10,12 +10,48 @@ use super::log;
 use super::status_bar;
 use super::search_bar;
 use super::filter_bar;
+use super::goto_bar;
+use super::bookmarks_bar;
+use super::sources_bar;
+use super::facet_bar;
+use super::template_bar;
+use super::detail_bar;
+use super::command_bar;
+use super::help_bar;
+use super::range_bar;
+use super::histogram_bar;
 
 #[derive(Copy, Clone)]
 pub enum BarType {
   Status,
   Filter,
-  Search
+  Search,
+  Goto,
+  Command,
+
+  /// Picks a moment from the histogram strip
+  Histogram,
+
+  /// The bookmark list, which overlays the bottom of the log
+  Bookmarks,
+
+  /// The source list, which overlays the bottom of the log
+  Sources,
+
+  /// A field's distinct values, which overlay the bottom of the log
+  Facet,
+
+  /// Messages grouped by template, which overlay the bottom of the log
+  Templates,
+
+  /// Details of the selected message, which overlays the whole log
+  Detail,
+
+  /// Keybindings, which overlay the whole log
+  Help,
+
+  /// A summary of the marked range, which overlays the whole log
+  Range
 }
 
 #[derive(Clone)]
@@ -37,7 +73,17 @@ pub fn render(
   let renderer = match state.bar.active {
     BarType::Status => status_bar::render,
     BarType::Filter => filter_bar::render,
-    BarType::Search => search_bar::render
+    BarType::Search => search_bar::render,
+    BarType::Goto => goto_bar::render,
+    BarType::Command => command_bar::render,
+    BarType::Histogram => histogram_bar::render,
+    BarType::Bookmarks => bookmarks_bar::render,
+    BarType::Sources => sources_bar::render,
+    BarType::Facet => facet_bar::render,
+    BarType::Templates => template_bar::render,
+    BarType::Detail => detail_bar::render,
+    BarType::Help => help_bar::render,
+    BarType::Range => range_bar::render
   };
 
   renderer(state, terminal, cursor)
@@ -60,7 +106,21 @@ fn input_global(mut state: RcState, key: &KeyEvent) -> (RcState, InputAction) {
 }
 
 pub fn input(state: RcState, key: KeyEvent) -> (RcState, InputAction) {
-  let (state, action) = input_global(state, &key);
+  // overlays use the arrow keys to move their own cursors, and the status bar
+  // navigates with its (possibly remapped) keymap
+  let (state, action) = match state.bar.active {
+    BarType::Status | BarType::Bookmarks | BarType::Sources | BarType::Facet |
+    BarType::Templates | BarType::Detail | BarType::Help | BarType::Range => {
+      (state, InputAction::Unhandled)
+    },
+
+    // prompts with history browse it with up and down instead
+    BarType::Filter | BarType::Search if matches!(key, KeyEvent::Up | KeyEvent::Down) => {
+      (state, InputAction::Unhandled)
+    },
+    _ => input_global(state, &key)
+  };
+
   if action != InputAction::Unhandled {
     return (state, action);
   }
@@ -68,7 +128,17 @@ pub fn input(state: RcState, key: KeyEvent) -> (RcState, InputAction) {
   let handler = match state.bar.active {
     BarType::Status => status_bar::input,
     BarType::Filter => filter_bar::input,
-    BarType::Search => search_bar::input
+    BarType::Search => search_bar::input,
+    BarType::Goto => goto_bar::input,
+    BarType::Command => command_bar::input,
+    BarType::Histogram => histogram_bar::input,
+    BarType::Bookmarks => bookmarks_bar::input,
+    BarType::Sources => sources_bar::input,
+    BarType::Facet => facet_bar::input,
+    BarType::Templates => template_bar::input,
+    BarType::Detail => detail_bar::input,
+    BarType::Help => help_bar::input,
+    BarType::Range => range_bar::input
   };
 
   handler(state, &key)
@@ -0,0 +1,248 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::rc::Rc;
+
+use crossterm::{Terminal, TerminalCursor, KeyEvent, ClearType};
+
+use crate::renderer::plain::plain_render;
+
+use super::state::{RcState, RenderState};
+use super::state::actions as state_actions;
+use super::bar::{self, BarType};
+use super::log;
+use super::InputAction;
+
+/// Bookmarked messages, and the bookmark list overlay
+#[derive(Clone)]
+pub struct BookmarksBarState {
+  /// Bookmarked indexes into `entries`, so they survive filter changes
+  marks: BTreeSet<usize>,
+
+  /// The highlighted bookmark in the list, by position
+  cursor: usize
+}
+
+impl BookmarksBarState {
+  pub fn new() -> Self {
+    BookmarksBarState {
+      marks: BTreeSet::new(),
+      cursor: 0
+    }
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.marks.is_empty()
+  }
+
+  /// Bookmarked indexes into `entries`, in order
+  pub fn marks(&self) -> Vec<usize> {
+    self.marks.iter().cloned().collect()
+  }
+}
+
+/// The index of the selected message in `entries`
+fn selected_index(state: &RenderState) -> Option<usize> {
+  let selection = state.log.selection?;
+  state.filtered_entries.borrow().get(selection.rel_index).map(|f| f.index)
+}
+
+/// The position of an entry in `filtered_entries`, if it isn't filtered out
+fn filtered_position(state: &RenderState, index: usize) -> Option<usize> {
+  state.filtered_entries.borrow().binary_search_by_key(&index, |f| f.index).ok()
+}
+
+/// A one line summary of a bookmarked message
+fn describe(state: &RenderState, index: usize) -> String {
+  let entry = match state.entries.borrow().get(index) {
+    Some(entry) => Rc::clone(entry),
+    None => return String::new()
+  };
+
+  let line = plain_render(&entry).into_iter().next().unwrap_or_default();
+  let hidden = if filtered_position(state, index).is_none() { " (hidden)" } else { "" };
+
+  format!("{}{}", line.trim_end(), hidden)
+}
+
+/// The range of bookmarks shown in the list, keeping the cursor in view
+fn visible_range(state: &RenderState) -> (usize, usize) {
+  let rows = (state.height as usize / 2).max(1);
+  let len = state.bookmarks.marks.len();
+  let start = state.bookmarks.cursor.saturating_sub(rows - 1).min(len.saturating_sub(rows));
+
+  (start, (start + rows).min(len))
+}
+
+pub fn render(
+  state: RcState, terminal: &Terminal, cursor: &TerminalCursor
+) -> Result<RcState, Box<dyn Error>> {
+  let width = state.width as usize;
+  let normal = state.style.highlighted.get_base();
+  let selected = state.style.selected.get_base();
+
+  let (start, end) = visible_range(&state);
+  let marks: Vec<usize> = state.bookmarks.marks.iter().cloned().collect();
+  let top = state.height.saturating_sub(1 + (end - start) as u16);
+
+  for (row, i) in (start..end).enumerate() {
+    let line = format!(" {:>3} {}", i + 1, describe(&state, marks[i]));
+    let line: String = line.chars().take(width).collect();
+    let padding = " ".repeat(width.saturating_sub(line.chars().count()));
+
+    let style = if i == state.bookmarks.cursor { selected } else { normal };
+
+    cursor.goto(0, top + row as u16)?;
+    terminal.clear(ClearType::CurrentLine)?;
+    terminal.write(style.paint(format!("{}{}", line, padding)))?;
+  }
+
+  let help = "bookmarks | enter: go to | d: remove | esc: close";
+  let help: String = help.chars().take(width).collect();
+  let padding = " ".repeat(width.saturating_sub(help.len()));
+
+  cursor.hide()?;
+  cursor.goto(0, state.height - 1)?;
+  terminal.clear(ClearType::CurrentLine)?;
+  terminal.write(selected.paint(format!("{}{}", help, padding)))?;
+
+  Ok(state)
+}
+
+pub fn input(mut state: RcState, key: &KeyEvent) -> (RcState, InputAction) {
+  state = match key {
+    KeyEvent::Esc | KeyEvent::Char('q') | KeyEvent::Char('B') => {
+      bar::actions::set_active(state, BarType::Status)
+    },
+    KeyEvent::Up => actions::move_cursor(state, -1),
+    KeyEvent::Down => actions::move_cursor(state, 1),
+    KeyEvent::Char('\n') => {
+      let mark = state.bookmarks.marks.iter().nth(state.bookmarks.cursor).cloned();
+      let state = bar::actions::set_active(state, BarType::Status);
+
+      match mark {
+        Some(mark) => actions::jump_to(state, mark),
+        None => state
+      }
+    },
+    KeyEvent::Char('d') => {
+      let state = actions::remove_at_cursor(state);
+      if state.bookmarks.is_empty() {
+        bar::actions::set_active(state, BarType::Status)
+      } else {
+        state
+      }
+    },
+    _ => return (state, InputAction::Unhandled)
+  };
+
+  (state, InputAction::Rerender)
+}
+
+pub mod actions {
+  use super::*;
+
+  /// Opens the bookmark list, highlighting the bookmark nearest the selection
+  pub fn open(mut state: RcState) -> RcState {
+    if state.bookmarks.is_empty() {
+      return state_actions::internal(state, "no bookmarks, press b to add one");
+    }
+
+    let cursor = match selected_index(&state) {
+      Some(index) => state.bookmarks.marks.range(..index).count(),
+      None => state.bookmarks.marks.len() - 1
+    };
+
+    let state_mut = Rc::make_mut(&mut state);
+    state_mut.bookmarks.cursor = cursor.min(state_mut.bookmarks.marks.len() - 1);
+
+    bar::actions::set_active(state, BarType::Bookmarks)
+  }
+
+  pub fn move_cursor(mut state: RcState, amount: isize) -> RcState {
+    let state_mut = Rc::make_mut(&mut state);
+    let max = state_mut.bookmarks.marks.len().saturating_sub(1) as isize;
+    let cursor = (state_mut.bookmarks.cursor as isize + amount).max(0).min(max);
+    state_mut.bookmarks.cursor = cursor as usize;
+
+    state
+  }
+
+  /// Replaces every bookmark, e.g. when restoring a session
+  pub fn set_marks(mut state: RcState, marks: BTreeSet<usize>) -> RcState {
+    let state_mut = Rc::make_mut(&mut state);
+    state_mut.bookmarks.marks = marks;
+    state_mut.bookmarks.cursor = 0;
+
+    state
+  }
+
+  pub fn remove_at_cursor(mut state: RcState) -> RcState {
+    let state_mut = Rc::make_mut(&mut state);
+    let bookmarks = &mut state_mut.bookmarks;
+
+    if let Some(mark) = bookmarks.marks.iter().nth(bookmarks.cursor).cloned() {
+      bookmarks.marks.remove(&mark);
+    }
+
+    bookmarks.cursor = bookmarks.cursor.min(bookmarks.marks.len().saturating_sub(1));
+
+    state
+  }
+
+  /// Bookmarks the selected message, or removes its bookmark
+  pub fn toggle(mut state: RcState) -> RcState {
+    let index = match selected_index(&state) {
+      Some(index) => index,
+      None => return state_actions::internal(state, "no message is selected")
+    };
+
+    let state_mut = Rc::make_mut(&mut state);
+    let marks = &mut state_mut.bookmarks.marks;
+    let added = marks.insert(index);
+    if !added {
+      marks.remove(&index);
+    }
+
+    let count = marks.len();
+    state_actions::internal(state, &format!(
+      "{} bookmark ({} total)", if added { "added" } else { "removed" }, count
+    ))
+  }
+
+  /// Selects a bookmarked message, if it isn't filtered out
+  pub fn jump_to(state: RcState, mark: usize) -> RcState {
+    match filtered_position(&state, mark) {
+      Some(position) => log::actions::move_selection_to_index(state, position),
+      None => state_actions::internal(state, "bookmarked message is hidden by filters")
+    }
+  }
+
+  /// Selects the next (or previous) visible bookmark after the selection
+  pub fn jump_next(state: RcState, forward: bool) -> RcState {
+    if state.bookmarks.is_empty() {
+      return state_actions::internal(state, "no bookmarks, press b to add one");
+    }
+
+    let current = selected_index(&state);
+    let visible = |mark: &&usize| filtered_position(&state, **mark).is_some();
+
+    // with no selection the view is at the bottom, so nothing is further down
+    let mark = if forward {
+      current.and_then(|c| state.bookmarks.marks.range(c + 1..).find(visible).cloned())
+    } else {
+      let before = current.unwrap_or(usize::MAX);
+      state.bookmarks.marks.range(..before).rev().find(visible).cloned()
+    };
+
+    match mark {
+      Some(mark) => jump_to(state, mark),
+      None => state_actions::internal(state, if forward {
+        "no more bookmarks below"
+      } else {
+        "no more bookmarks above"
+      })
+    }
+  }
+}
@@ -0,0 +1,580 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+use std::error::Error;
+use std::fs::OpenOptions;
+use std::io::{BufWriter, Write};
+use std::rc::Rc;
+
+use crossterm::{Terminal, TerminalCursor, KeyEvent, ClearType};
+use shellexpand;
+use simple_error::SimpleResult;
+
+use crate::expression;
+use crate::filter::FilterMode;
+use crate::reader::exec;
+use crate::style::{self, StyleProfileKind, styler_base};
+
+use super::state::{RcState, RenderState};
+use super::state::actions as state_actions;
+use super::appearance;
+use super::bar::{self, BarType};
+use super::facet_bar;
+use super::goto_bar;
+use super::history;
+use super::log;
+use super::snapshot;
+use super::sources_bar;
+use super::text::{self, TextBuffer, TextInputAction};
+use super::InputAction;
+
+#[derive(Clone)]
+pub struct CommandBarState {
+  text: TextBuffer
+}
+
+impl CommandBarState {
+  pub fn new() -> Self {
+    let styler = styler_base(StyleProfileKind::Selected);
+
+    CommandBarState {
+      text: TextBuffer::new().with_styler(Some(styler))
+    }
+  }
+}
+
+/// How `:write` formats messages
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum WriteFormat {
+  Plain,
+  Raw,
+  Json
+}
+
+impl WriteFormat {
+  fn parse(s: &str) -> Option<WriteFormat> {
+    match s {
+      "plain" => Some(WriteFormat::Plain),
+      "raw" => Some(WriteFormat::Raw),
+      "json" => Some(WriteFormat::Json),
+      _ => None
+    }
+  }
+
+  /// Guesses a format from a path's extension, e.g. `.json`
+  fn for_path(path: &str) -> WriteFormat {
+    let path = path.to_lowercase();
+    if path.ends_with(".json") || path.ends_with(".jsonl") || path.ends_with(".ndjson") {
+      WriteFormat::Json
+    } else {
+      WriteFormat::Plain
+    }
+  }
+}
+
+/// Command names, for completion
+static COMMANDS: &[&str] = &[
+  "facet", "filter", "goto", "preset", "save", "snapshot", "source", "theme", "write"
+];
+
+/// What `:source` does to a source
+#[derive(Debug, PartialEq)]
+enum SourceCommand {
+  Hide(String),
+  Show(String),
+  Solo(String),
+
+  /// Shows every source
+  All,
+
+  /// Restarts a subprocess started with `--exec`
+  Restart(String),
+
+  /// Stops a subprocess started with `--exec`
+  Stop(String),
+
+  /// Sends a signal to a subprocess started with `--exec`
+  Signal {
+    signal: i32,
+    source: String
+  }
+}
+
+#[derive(Debug, PartialEq)]
+enum Command {
+  /// Writes the marked range, or every filtered message, to a file
+  Write {
+    path: String,
+    format: WriteFormat,
+    overwrite: bool
+  },
+
+  /// Saves buffered messages and the view to a session file
+  Snapshot {
+    path: String,
+    overwrite: bool
+  },
+
+  /// Saves the last filter as a preset
+  Save {
+    name: String
+  },
+
+  /// Applies a saved preset, or lists them
+  Preset {
+    name: Option<String>
+  },
+
+  /// Adds a filter, an expression unless another mode is given
+  Filter {
+    mode: FilterMode,
+    query: String,
+    inverted: bool
+  },
+
+  /// Lists a field's distinct values, to include or exclude them
+  Facet {
+    field: String
+  },
+
+  /// Switches to a theme, or lists them
+  Theme {
+    name: Option<String>
+  },
+
+  /// Hides, shows, or solos a source, or controls a subprocess
+  Source(SourceCommand),
+
+  /// Goes to a time or record number, as with `g`
+  Goto {
+    target: String
+  }
+}
+
+impl Command {
+  fn parse(input: &str) -> SimpleResult<Command> {
+    let mut words = input.split_whitespace();
+
+    let (name, args): (&str, Vec<&str>) = match words.next() {
+      Some(name) => (name, words.collect()),
+      None => bail!("no command given")
+    };
+
+    // everything after the name, for commands taking free text
+    let rest = input.trim_start()[name.len()..].trim();
+
+    match name {
+      "w" | "write" | "w!" | "write!" => {
+        let (format, path) = match args.as_slice() {
+          [path] => (WriteFormat::for_path(path), *path),
+          [format, path] => match WriteFormat::parse(format) {
+            Some(format) => (format, *path),
+            None => bail!("invalid format: {}, expected plain, raw, or json", format)
+          },
+          _ => bail!("usage: write [plain|raw|json] <path>")
+        };
+
+        Ok(Command::Write {
+          path: path.to_string(),
+          format,
+          overwrite: name.ends_with('!')
+        })
+      },
+      "snapshot" | "snapshot!" => match args.as_slice() {
+        [path] => Ok(Command::Snapshot { path: path.to_string(), overwrite: name.ends_with('!') }),
+        _ => bail!("usage: snapshot[!] <path>")
+      },
+      "save" => match args.as_slice() {
+        [name] => Ok(Command::Save { name: name.to_string() }),
+        _ => bail!("usage: save <name>")
+      },
+      "preset" => match args.as_slice() {
+        [] => Ok(Command::Preset { name: None }),
+        [name] => Ok(Command::Preset { name: Some(name.to_string()) }),
+        _ => bail!("usage: preset [name]")
+      },
+      "filter" | "filter!" => {
+        let mode = args.first().and_then(|word| match *word {
+          "text" => Some(FilterMode::Text),
+          "regex" => Some(FilterMode::Regex),
+          "expr" => Some(FilterMode::Expression),
+          _ => None
+        });
+
+        let (mode, query) = match mode {
+          Some(mode) if args.len() > 1 => (mode, rest[args[0].len()..].trim()),
+          _ => (FilterMode::Expression, rest)
+        };
+
+        if query.is_empty() {
+          bail!("usage: filter[!] [text|regex|expr] <query>");
+        }
+
+        Ok(Command::Filter { mode, query: query.to_string(), inverted: name.ends_with('!') })
+      },
+      "facet" => match args.as_slice() {
+        [field] => Ok(Command::Facet { field: field.to_string() }),
+        _ => bail!("usage: facet <field>")
+      },
+      "theme" => match args.as_slice() {
+        [] => Ok(Command::Theme { name: None }),
+        [name] => Ok(Command::Theme { name: Some(name.to_string()) }),
+        _ => bail!("usage: theme [name]")
+      },
+      "source" => {
+        let source = args.get(1..).map(|words| words.join(" ")).unwrap_or_default();
+        match (args.first(), source.is_empty()) {
+          (Some(&"hide"), false) => Ok(Command::Source(SourceCommand::Hide(source))),
+          (Some(&"show"), false) => Ok(Command::Source(SourceCommand::Show(source))),
+          (Some(&"solo"), false) => Ok(Command::Source(SourceCommand::Solo(source))),
+          (Some(&"all"), true) => Ok(Command::Source(SourceCommand::All)),
+          (Some(&"restart"), false) => Ok(Command::Source(SourceCommand::Restart(source))),
+          (Some(&"stop"), false) => Ok(Command::Source(SourceCommand::Stop(source))),
+          (Some(&"signal"), false) if args.len() > 2 => Ok(Command::Source(SourceCommand::Signal {
+            signal: exec::parse_signal(args[1])?,
+            source: args[2..].join(" ")
+          })),
+          _ => bail!(
+            "usage: source hide|show|solo|restart|stop <name>, source signal <signal> <name>, \
+            or source all"
+          )
+        }
+      },
+      "goto" | "g" if !rest.is_empty() => Ok(Command::Goto { target: rest.to_string() }),
+      "goto" | "g" => bail!("usage: goto <time or #record>"),
+      _ => bail!("unknown command: {}", name)
+    }
+  }
+}
+
+/// Splits input into the words before the one being completed, and that
+/// (possibly empty) partial word
+fn completion_context(input: &str) -> (Vec<&str>, &str) {
+  let mut words: Vec<&str> = input.split_whitespace().collect();
+  if input.is_empty() || input.ends_with(char::is_whitespace) {
+    (words, "")
+  } else {
+    let partial = words.pop().unwrap_or("");
+    (words, partial)
+  }
+}
+
+/// Possible completions of the word being entered
+fn candidates(state: &RenderState, input: &str) -> Vec<String> {
+  let (words, _) = completion_context(input);
+  let name = words.first().map(|name| name.trim_end_matches('!'));
+
+  let strings = |items: &[&str]| items.iter().map(|s| s.to_string()).collect();
+  match (name, words.len()) {
+    (None, _) => strings(COMMANDS),
+    (Some("w"), 1) | (Some("write"), 1) => strings(&["plain", "raw", "json"]),
+    (Some("filter"), 1) => strings(&["text", "regex", "expr"]),
+    (Some("facet"), 1) => {
+      let entries = state.entries.borrow();
+      expression::known_fields(entries.iter().map(|e| &e.message))
+    },
+    (Some("preset"), 1) => state.history.borrow().presets.keys().cloned().collect(),
+    (Some("theme"), 1) => style::theme_names(&state.config.file().themes),
+    (Some("source"), 1) => strings(&["hide", "show", "solo", "restart", "stop", "signal", "all"]),
+    (Some("source"), 2) if words[1] == "signal" => {
+      exec::SIGNALS.iter().map(|(name, _)| name.to_string()).collect()
+    },
+    (Some("source"), 2) if ["restart", "stop"].contains(&words[1]) => exec::managed(),
+    (Some("source"), 3) if words[1] == "signal" => exec::managed(),
+    (Some("source"), 2) if words[1] != "all" => sources_bar::source_names(state),
+    _ => Vec::new()
+  }
+}
+
+/// Completes the last word of `input` from `candidates`: fully if only one
+/// matches, otherwise as far as they agree
+///
+/// Returns the new input, and every matching candidate.
+fn complete(input: &str, candidates: &[String]) -> (String, Vec<String>) {
+  let (_, partial) = completion_context(input);
+  let matches: Vec<String> = candidates.iter()
+    .filter(|c| c.starts_with(partial))
+    .cloned()
+    .collect();
+
+  let base = &input[..input.len() - partial.len()];
+  match matches.as_slice() {
+    [] => (input.to_string(), matches),
+    [only] => (format!("{}{} ", base, only), matches),
+    [first, rest @ ..] => {
+      let common = rest.iter().fold(first.clone(), |common, c| {
+        common.chars().zip(c.chars())
+          .take_while(|(a, b)| a == b)
+          .map(|(a, _)| a)
+          .collect()
+      });
+
+      (format!("{}{}", base, common), matches)
+    }
+  }
+}
+
+/// Writes messages to a file, returning the number written
+fn write(
+  state: &RenderState, path: &str, format: WriteFormat, overwrite: bool
+) -> Result<usize, Box<dyn Error>> {
+  let path = shellexpand::tilde(path).to_string();
+
+  let file = OpenOptions::new()
+    .write(true)
+    .truncate(true)
+    .create(overwrite)
+    .create_new(!overwrite)
+    .open(&path)
+    .map_err(|e| format!("could not write {}: {}{}", path, e, if overwrite {
+      ""
+    } else {
+      " (use write! to overwrite)"
+    }))?;
+
+  let filtered_entries = state.filtered_entries.borrow();
+  let range = match (state.log.mark, log::selected_range(state)) {
+    (Some(_), Some(range)) => range,
+    _ => 0..=filtered_entries.len().saturating_sub(1)
+  };
+
+  let mut out = BufWriter::new(file);
+  let mut count = 0;
+  for filtered in range.filter_map(|i| filtered_entries.get(i)) {
+    let entry = match filtered.entry.upgrade() {
+      Some(entry) => entry,
+      None => continue
+    };
+
+    match format {
+      WriteFormat::Plain => for line in log::plain_lines(state, filtered.index, &entry) {
+        writeln!(out, "{}", line.trim_end())?;
+      },
+      WriteFormat::Raw => writeln!(out, "{}", entry.message.raw)?,
+      WriteFormat::Json => writeln!(out, "{}", serde_json::to_string(&entry.message)?)?
+    }
+
+    count += 1;
+  }
+
+  out.flush()?;
+
+  Ok(count)
+}
+
+pub fn render(
+  state: RcState, terminal: &Terminal, cursor: &TerminalCursor
+) -> Result<RcState, Box<dyn Error>> {
+  cursor.goto(0, state.height - 1)?;
+  terminal.clear(ClearType::CurrentLine)?;
+
+  let style = &state.style.selected.get_base();
+  terminal.write(style.paint(" ".repeat(state.width as usize)))?;
+  cursor.goto(0, state.height - 1)?;
+
+  terminal.write(style.paint(":").to_string())?;
+  text::render(
+    Rc::clone(&state), &state.command.text,
+    terminal, cursor,
+    1, state.height - 1
+  )?;
+
+  Ok(state)
+}
+
+fn handle_text_input(
+  mut state: RcState, key: &KeyEvent
+) -> (RcState, TextInputAction) {
+  let state_mut = Rc::make_mut(&mut state);
+
+  let text_state = state_mut.command.text.clone();
+  let (text_state, action) = text::input(text_state, key);
+  state_mut.command.text = text_state;
+
+  (state, action)
+}
+
+pub fn input(mut state: RcState, key: &KeyEvent) -> (RcState, InputAction) {
+  if matches!(key, KeyEvent::Char('\t')) {
+    return (actions::complete_input(state), InputAction::Rerender);
+  }
+
+  let (new_state, action) = handle_text_input(state, key);
+  state = new_state;
+
+  let input_action = match action {
+    TextInputAction::Action(a) | TextInputAction::Update(a) => a,
+    TextInputAction::Exit(a) => {
+      state = bar::actions::set_active(state, BarType::Status);
+      a
+    },
+    TextInputAction::Submit(a, input) => {
+      state = actions::clear_input(state);
+      state = bar::actions::set_active(state, BarType::Status);
+
+      match Command::parse(&input) {
+        Ok(command) => state = actions::run(state, command),
+        Err(e) => state = state_actions::internal(state, &e.to_string())
+      }
+
+      a
+    }
+  };
+
+  (state, input_action)
+}
+
+pub mod actions {
+  use super::*;
+
+  pub fn clear_input(mut state: RcState) -> RcState {
+    let state_mut = Rc::make_mut(&mut state);
+
+    state_mut.command.text = text::actions::clear_input(
+      state_mut.command.text.clone()
+    );
+
+    state
+  }
+
+  pub(super) fn run(state: RcState, command: Command) -> RcState {
+    match command {
+      Command::Write { path, format, overwrite } => {
+        match write(&state, &path, format, overwrite) {
+          Ok(count) => state_actions::internal(
+            state, &format!("wrote {} message{} to {}", count, if count == 1 { "" } else { "s" }, path)
+          ),
+          Err(e) => state_actions::internal(state, &e.to_string())
+        }
+      },
+      Command::Snapshot { path, overwrite } => snapshot::actions::save(state, &path, overwrite),
+      Command::Save { name } => history::actions::save_preset(state, &name),
+      Command::Preset { name: Some(name) } => history::actions::apply_preset(state, &name),
+      Command::Preset { name: None } => history::actions::list_presets(state),
+      Command::Filter { mode, query, inverted } => match mode.parse(&query, inverted) {
+        Ok(filter) => state_actions::add_filter(state, filter),
+        Err(e) => state_actions::internal(state, &e.to_string())
+      },
+      Command::Facet { field } => facet_bar::actions::open(state, &field),
+      Command::Theme { name: Some(name) } => appearance::actions::set_theme(state, &name),
+      Command::Theme { name: None } => {
+        let names = style::theme_names(&state.config.file().themes);
+        let text = format!("themes: {} (current: {})", names.join(", "), state.style.name);
+        state_actions::internal(state, &text)
+      },
+      Command::Source(command) => match command {
+        SourceCommand::Hide(source) => sources_bar::actions::hide_source(state, &source),
+        SourceCommand::Show(source) => sources_bar::actions::show_source(state, &source),
+        SourceCommand::Solo(source) => sources_bar::actions::solo_source(state, &source),
+        SourceCommand::All => sources_bar::actions::show_all(state),
+        SourceCommand::Restart(source) => {
+          sources_bar::actions::control(state, &source, exec::Control::Restart)
+        },
+        SourceCommand::Stop(source) => {
+          sources_bar::actions::control(state, &source, exec::Control::Stop)
+        },
+        SourceCommand::Signal { signal, source } => {
+          sources_bar::actions::control(state, &source, exec::Control::Signal(signal))
+        }
+      },
+      Command::Goto { target } => goto_bar::actions::go(state, &target)
+    }
+  }
+
+  /// Completes the word being entered, listing the options if there are
+  /// several
+  pub fn complete_input(mut state: RcState) -> RcState {
+    let input = state.command.text.input.clone();
+    let (completed, matches) = complete(&input, &candidates(&state, &input));
+
+    let state_mut = Rc::make_mut(&mut state);
+    state_mut.command.text = history::set_input(state_mut.command.text.clone(), &completed);
+
+    if matches.len() > 1 && completed == input {
+      state_actions::internal(state, &format!("completions: {}", matches.join(", ")))
+    } else {
+      state
+    }
+  }
+
+  /// Opens the prompt with some input already entered, e.g. a command name
+  pub fn open_with(mut state: RcState, input: &str) -> RcState {
+    let state_mut = Rc::make_mut(&mut state);
+    state_mut.command.text = history::set_input(state_mut.command.text.clone(), input);
+
+    bar::actions::set_active(state, BarType::Command)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use spectral::prelude::*;
+
+  #[test]
+  fn test_parse() {
+    assert_that!(Command::parse("write out.log")).is_equal_to(Ok(Command::Write {
+      path: "out.log".to_string(), format: WriteFormat::Plain, overwrite: false
+    }));
+    assert_that!(Command::parse("w! out.jsonl")).is_equal_to(Ok(Command::Write {
+      path: "out.jsonl".to_string(), format: WriteFormat::Json, overwrite: true
+    }));
+    assert_that!(Command::parse("w raw out.json")).is_equal_to(Ok(Command::Write {
+      path: "out.json".to_string(), format: WriteFormat::Raw, overwrite: false
+    }));
+    assert_that!(Command::parse("w yaml out")).is_err();
+    assert_that!(Command::parse("save errors")).is_equal_to(Ok(Command::Save {
+      name: "errors".to_string()
+    }));
+    assert_that!(Command::parse("preset")).is_equal_to(Ok(Command::Preset { name: None }));
+    assert_that!(Command::parse("preset a b")).is_err();
+    assert_that!(Command::parse("quit")).is_err();
+
+    assert_that!(Command::parse("filter level >= warn")).is_equal_to(Ok(Command::Filter {
+      mode: FilterMode::Expression, query: "level >= warn".to_string(), inverted: false
+    }));
+    assert_that!(Command::parse("filter! regex ^GET /health")).is_equal_to(Ok(Command::Filter {
+      mode: FilterMode::Regex, query: "^GET /health".to_string(), inverted: true
+    }));
+    assert_that!(Command::parse("filter")).is_err();
+    assert_that!(Command::parse("facet status")).is_equal_to(Ok(Command::Facet {
+      field: "status".to_string()
+    }));
+    assert_that!(Command::parse("facet")).is_err();
+    assert_that!(Command::parse("source hide api-canary")).is_equal_to(Ok(Command::Source(
+      SourceCommand::Hide("api-canary".to_string())
+    )));
+    assert_that!(Command::parse("source all")).is_equal_to(Ok(Command::Source(SourceCommand::All)));
+    assert_that!(Command::parse("source solo")).is_err();
+    assert_that!(Command::parse("source signal hup server")).is_equal_to(Ok(Command::Source(
+      SourceCommand::Signal { signal: 1, source: "server".to_string() }
+    )));
+    assert_that!(Command::parse("source signal server")).is_err();
+    assert_that!(Command::parse("source signal wat server")).is_err();
+    assert_that!(Command::parse("goto 14:32")).is_equal_to(Ok(Command::Goto {
+      target: "14:32".to_string()
+    }));
+  }
+
+  #[test]
+  fn test_complete() {
+    let strings = |items: &[&str]| items.iter().map(|s| s.to_string()).collect::<Vec<String>>();
+    let commands = strings(COMMANDS);
+
+    assert_that!(complete("th", &commands)).is_equal_to(
+      ("theme ".to_string(), strings(&["theme"]))
+    );
+    assert_that!(complete("s", &commands)).is_equal_to(
+      ("s".to_string(), strings(&["save", "snapshot", "source"]))
+    );
+
+    let sources = strings(&["api-canary", "api-main", "db"]);
+    assert_that!(complete("source hide a", &sources)).is_equal_to(
+      ("source hide api-".to_string(), strings(&["api-canary", "api-main"]))
+    );
+    assert_that!(complete("source hide x", &sources).0).is_equal_to("source hide x".to_string());
+
+    assert_that!(completion_context("source ")).is_equal_to((vec!["source"], ""));
+    assert_that!(completion_context("")).is_equal_to((vec![], ""));
+  }
+}
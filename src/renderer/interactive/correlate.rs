@@ -0,0 +1,236 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+use std::collections::BTreeSet;
+use std::rc::Rc;
+
+use serde_json::Value;
+
+use crate::expression;
+use crate::filter::{ExpressionFilter, Filter, FilterMode};
+use crate::parser::{Message, MessageKind};
+use crate::style::value_string;
+
+use super::facet_bar::quote;
+use super::state::{self, RcState};
+use super::state::actions as state_actions;
+use super::log;
+
+/// Field names, lowercased without dashes or underscores, that hold IDs
+/// shared by every message about one request, e.g. `trace_id`, `traceId`, or
+/// `x-request-id`
+static CORRELATION_NAMES: &[&str] = &[
+  "traceid", "xtraceid", "xb3traceid", "requestid", "xrequestid", "reqid",
+  "correlationid", "xcorrelationid"
+];
+
+/// If true, a field (or the last part of a nested one) holds a trace or
+/// request ID
+pub fn is_correlation_field(name: &str) -> bool {
+  let last = name.rsplit('.').next().unwrap_or(name);
+  let normalized: String = last.chars()
+    .filter(|c| *c != '-' && *c != '_')
+    .flat_map(char::to_lowercase)
+    .collect();
+
+  CORRELATION_NAMES.contains(&normalized.as_str())
+}
+
+/// The first trace or request ID on a message, as its field and value
+pub fn correlation_id(message: &Message) -> Option<(String, String)> {
+  expression::known_fields(std::iter::once(message)).into_iter()
+    .filter(|field| is_correlation_field(field))
+    .find_map(|field| match expression::lookup(message, &field) {
+      Some(Value::Null) | Some(Value::Object(_)) | Some(Value::Array(_)) | None => None,
+      Some(value) => match value_string(&value) {
+        value if value.is_empty() => None,
+        value => Some((field, value))
+      }
+    })
+}
+
+/// An expression matching messages with an ID in any of the given fields,
+/// e.g. `request_id == "f00" || x-request-id == "f00"`
+fn correlation_query(fields: &BTreeSet<String>, id: &str) -> String {
+  fields.iter()
+    .map(|field| format!("{} == {}", field, quote(id)))
+    .collect::<Vec<String>>()
+    .join(" || ")
+}
+
+/// The ID being followed, and the filter added for it
+#[derive(Clone, Default)]
+pub struct CorrelateState {
+  id: Option<String>,
+  query: Option<String>
+}
+
+impl CorrelateState {
+  pub fn new() -> Self {
+    CorrelateState::default()
+  }
+}
+
+pub mod actions {
+  use super::*;
+
+  /// Removes the filter for the followed ID, if any
+  fn remove_filter(mut state: RcState) -> RcState {
+    if let Some(old) = &state.correlate.query {
+      state.filters.borrow_mut()
+        .retain(|f| f.mode() != FilterMode::Expression || f.query() != old);
+    }
+
+    Rc::make_mut(&mut state).correlate = CorrelateState::new();
+    state
+  }
+
+  /// Keeps the message at `index` in `entries` selected after refiltering
+  fn reselect(state: RcState, index: Option<usize>) -> RcState {
+    let position = index.and_then(|index| {
+      state.filtered_entries.borrow().binary_search_by_key(&index, |f| f.index).ok()
+    });
+
+    match position {
+      Some(position) => log::actions::move_selection_to_index(state, position),
+      None => state
+    }
+  }
+
+  /// Filters the whole session to the selected message's trace or request
+  /// ID, across every source and correlation field; following the same ID
+  /// again stops
+  pub fn follow(mut state: RcState) -> RcState {
+    let selected = state.log.selection.and_then(|selection| {
+      let filtered_entries = state.filtered_entries.borrow();
+      let filtered = filtered_entries.get(selection.rel_index)?;
+      Some((filtered.index, filtered.entry.upgrade()?))
+    });
+
+    let (index, entry) = match selected {
+      Some(selected) => selected,
+      None => return state_actions::internal(state, "select a message to follow its request")
+    };
+
+    let (field, id) = match correlation_id(&entry.message) {
+      Some(found) => found,
+      None => return state_actions::internal(
+        state, "no trace or request id on the selected message"
+      )
+    };
+
+    if state.correlate.id.as_deref() == Some(id.as_str()) {
+      let state = reselect(state::refilter(remove_filter(state)), Some(index));
+      return state_actions::internal(state, &format!("stopped following {}", id));
+    }
+
+    let fields: BTreeSet<String> = {
+      let entries = state.entries.borrow();
+      let messages = entries.iter()
+        .map(|e| &e.message)
+        .filter(|m| m.kind != MessageKind::Internal);
+
+      expression::known_fields(messages).into_iter()
+        .filter(|field| is_correlation_field(field))
+        .collect()
+    };
+
+    let query = correlation_query(&fields, &id);
+    let filter = match ExpressionFilter::new(&query, false) {
+      Ok(filter) => filter,
+      Err(e) => return state_actions::internal(state, &format!("can't follow {}: {}", id, e))
+    };
+
+    state = remove_filter(state);
+    let state_mut = Rc::make_mut(&mut state);
+    state_mut.correlate = CorrelateState { id: Some(id.clone()), query: Some(query) };
+
+    let state = reselect(state_actions::add_filter(state, Box::new(filter)), Some(index));
+
+    let (count, sources) = {
+      let filtered_entries = state.filtered_entries.borrow();
+      let messages: Vec<_> = filtered_entries.iter()
+        .filter_map(|f| f.entry.upgrade())
+        .filter(|e| e.message.kind != MessageKind::Internal)
+        .collect();
+
+      let sources: BTreeSet<Option<String>> = messages.iter()
+        .map(|e| e.message.reader_metadata.as_ref().and_then(|m| m.source.clone()))
+        .collect();
+
+      (messages.len(), sources.len())
+    };
+
+    state_actions::internal(state, &format!(
+      "following {} {}: {} message{} from {} source{}, follow it again to stop",
+      field, id,
+      count, if count == 1 { "" } else { "s" },
+      sources, if sources == 1 { "" } else { "s" }
+    ))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::sync::Arc;
+
+  use serde_json::json;
+  use spectral::prelude::*;
+  use structopt::StructOpt;
+
+  use crate::config::Config;
+  use crate::parser::ReaderMetadata;
+  use crate::renderer::types::MessageEntry;
+  use crate::renderer::interactive::RenderState;
+
+  fn entry(source: &str, field: &str, id: &str) -> MessageEntry {
+    let mut entry = MessageEntry::internal(&format!("{} {}", source, id));
+    entry.message.kind = MessageKind::Json;
+    entry.message.metadata.insert(field.to_string(), json!(id));
+    entry.message.reader_metadata = Some(ReaderMetadata {
+      timestamp: None,
+      source: Some(source.to_string())
+    });
+
+    entry
+  }
+
+  fn shown(state: &RcState) -> Vec<String> {
+    state.filtered_entries.borrow().iter()
+      .filter_map(|f| f.entry.upgrade())
+      .filter(|e| e.message.kind != MessageKind::Internal)
+      .filter_map(|e| e.message.text.clone())
+      .collect()
+  }
+
+  #[test]
+  fn test_follow() {
+    for name in &["trace_id", "traceId", "x-request-id", "http.request_id", "correlationID"] {
+      assert_that!(is_correlation_field(name)).is_true();
+    }
+    assert_that!(is_correlation_field("id")).is_false();
+
+    let config = Arc::new(Config::from_iter_safe(vec![""]).unwrap());
+    let mut state = Rc::new(RenderState::new(config));
+    state = state_actions::add_entry(state, entry("gateway", "x-request-id", "r1"));
+    state = state_actions::add_entry(state, entry("api", "request_id", "r2"));
+    state = state_actions::add_entry(state, entry("api", "request_id", "r1"));
+    state = state_actions::add_entry(state, entry("db", "traceId", "r1"));
+
+    state = log::actions::move_selection_to_index(state, 2);
+    state = actions::follow(state);
+    assert_that!(shown(&state)).is_equal_to(vec![
+      "gateway r1".to_string(), "api r1".to_string(), "db r1".to_string()
+    ]);
+
+    // the followed message stays selected
+    let selected = state.log.selection
+      .map(|s| state.filtered_entries.borrow()[s.rel_index].index);
+    assert_that!(selected).is_equal_to(Some(2));
+
+    state = actions::follow(state);
+    assert_that!(shown(&state)).has_length(4);
+    assert_that!(state.filters.borrow().is_empty()).is_true();
+  }
+}
@@ -0,0 +1,295 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+use std::error::Error;
+use std::rc::Rc;
+
+use crossterm::{Terminal, TerminalCursor, KeyEvent, ClearType};
+use serde_json::Value;
+
+use crate::clip::{clip, clipboard_enabled};
+use crate::renderer::types::MessageEntry;
+use crate::timezone;
+
+use super::state::{RcState, RenderState};
+use super::state::actions as state_actions;
+use super::bar::{self, BarType};
+use super::InputAction;
+
+/// A full screen overlay showing every detail of the selected message
+#[derive(Clone)]
+pub struct DetailBarState {
+  entry: Option<Rc<MessageEntry>>,
+
+  /// The highlighted item, by position
+  cursor: usize
+}
+
+impl DetailBarState {
+  pub fn new() -> Self {
+    DetailBarState {
+      entry: None,
+      cursor: 0
+    }
+  }
+}
+
+/// Formats a field value, pretty-printing nested objects and arrays
+fn format_value(value: &Value) -> String {
+  match value {
+    Value::String(s) => s.clone(),
+    Value::Array(_) | Value::Object(_) => {
+      serde_json::to_string_pretty(value).unwrap_or_else(|_| value.to_string())
+    },
+    value => value.to_string()
+  }
+}
+
+/// The labelled items shown for a message: its text and metadata, each field
+/// sorted by name, and finally the raw line
+fn items(entry: &MessageEntry) -> Vec<(String, String)> {
+  let message = &entry.message;
+  let mut items = Vec::new();
+
+  if let Some(text) = &message.text {
+    items.push(("message".to_string(), text.clone()));
+  }
+
+  if let Some(timestamp) = message.timestamp {
+    items.push(("timestamp".to_string(), timezone::to_display(timestamp).to_rfc3339()));
+  }
+
+  if let Some(level) = message.level {
+    items.push(("level".to_string(), level.to_string().to_lowercase()));
+  }
+
+  items.push(("kind".to_string(), message.kind.to_string().to_lowercase()));
+
+  if let Some(source) = message.reader_metadata.as_ref().and_then(|m| m.source.as_ref()) {
+    items.push(("source".to_string(), source.clone()));
+  }
+
+  let mut fields: Vec<_> = message.metadata.iter().collect();
+  fields.sort_by(|a, b| a.0.cmp(b.0));
+  for (key, value) in fields {
+    items.push((format!("fields.{}", key), format_value(value)));
+  }
+
+  items.push(("raw".to_string(), message.raw.clone()));
+
+  items
+}
+
+/// Hard wraps text to a width, preserving line breaks
+fn wrap(text: &str, width: usize) -> Vec<String> {
+  let width = width.max(1);
+  let mut lines = Vec::new();
+
+  for line in text.lines() {
+    let chars: Vec<char> = line.chars().collect();
+    if chars.is_empty() {
+      lines.push(String::new());
+    }
+
+    for chunk in chars.chunks(width) {
+      lines.push(chunk.iter().collect());
+    }
+  }
+
+  if lines.is_empty() {
+    lines.push(String::new());
+  }
+
+  lines
+}
+
+/// Items laid out as lines
+struct Layout {
+  /// Each line, with the index of the item it belongs to
+  lines: Vec<(usize, String)>,
+
+  /// The range of lines used by each item
+  ranges: Vec<(usize, usize)>
+}
+
+/// Lays out items as lines, with values wrapped and indented beneath labels
+fn layout(items: &[(String, String)], width: usize) -> Layout {
+  let mut lines = Vec::new();
+  let mut ranges = Vec::new();
+
+  for (i, (label, value)) in items.iter().enumerate() {
+    let start = lines.len();
+    lines.push((i, format!("{}:", label)));
+
+    for line in wrap(value, width.saturating_sub(2)) {
+      lines.push((i, format!("  {}", line)));
+    }
+
+    ranges.push((start, lines.len()));
+  }
+
+  Layout { lines, ranges }
+}
+
+/// The first line shown, keeping the highlighted item in view
+fn scroll_top(state: &RenderState, ranges: &[(usize, usize)], rows: usize) -> usize {
+  let (start, end) = match ranges.get(state.detail.cursor) {
+    Some(range) => *range,
+    None => return 0
+  };
+
+  if end <= rows {
+    0
+  } else if end - start > rows {
+    start
+  } else {
+    end - rows
+  }
+}
+
+pub fn render(
+  state: RcState, terminal: &Terminal, cursor: &TerminalCursor
+) -> Result<RcState, Box<dyn Error>> {
+  let entry = match &state.detail.entry {
+    Some(entry) => Rc::clone(entry),
+    None => return Ok(state)
+  };
+
+  let width = state.width as usize;
+  let rows = state.height.saturating_sub(1) as usize;
+  let normal = state.style.normal.get_base();
+  let selected = state.style.selected.get_base();
+
+  let Layout { lines, ranges } = layout(&items(&entry), width);
+  let top = scroll_top(&state, &ranges, rows);
+
+  for row in 0..rows {
+    cursor.goto(0, row as u16)?;
+    terminal.clear(ClearType::CurrentLine)?;
+
+    if let Some((item, line)) = lines.get(top + row) {
+      let line: String = line.chars().take(width).collect();
+      if *item == state.detail.cursor {
+        let padding = " ".repeat(width.saturating_sub(line.chars().count()));
+        terminal.write(selected.paint(format!("{}{}", line, padding)))?;
+      } else {
+        terminal.write(normal.paint(line))?;
+      }
+    }
+  }
+
+  let mut help = "details | up/down: select".to_string();
+  if clipboard_enabled() {
+    help.push_str(" | c: copy value");
+  }
+
+  help.push_str(" | esc: close");
+
+  let help: String = help.chars().take(width).collect();
+  let padding = " ".repeat(width.saturating_sub(help.len()));
+
+  cursor.hide()?;
+  cursor.goto(0, state.height - 1)?;
+  terminal.clear(ClearType::CurrentLine)?;
+  terminal.write(selected.paint(format!("{}{}", help, padding)))?;
+
+  Ok(state)
+}
+
+pub fn input(mut state: RcState, key: &KeyEvent) -> (RcState, InputAction) {
+  state = match key {
+    KeyEvent::Esc | KeyEvent::Char('q') | KeyEvent::Char('\n') => actions::close(state),
+    KeyEvent::Up => actions::move_cursor(state, -1),
+    KeyEvent::Down => actions::move_cursor(state, 1),
+    KeyEvent::PageUp => actions::move_cursor(state, -10),
+    KeyEvent::PageDown => actions::move_cursor(state, 10),
+    KeyEvent::Home => actions::move_cursor(state, isize::MIN / 2),
+    KeyEvent::End => actions::move_cursor(state, isize::MAX / 2),
+    KeyEvent::Char('c') => actions::copy_value(state),
+    _ => return (state, InputAction::Unhandled)
+  };
+
+  (state, InputAction::Rerender)
+}
+
+pub mod actions {
+  use super::*;
+
+  /// Shows details of the selected message
+  pub fn open(mut state: RcState) -> RcState {
+    let entry = state.log.selection.and_then(|selection| {
+      state.filtered_entries.borrow().get(selection.rel_index)?.entry.upgrade()
+    });
+
+    if entry.is_none() {
+      return state_actions::internal(state, "no message is selected");
+    }
+
+    let state_mut = Rc::make_mut(&mut state);
+    state_mut.detail.entry = entry;
+    state_mut.detail.cursor = 0;
+
+    bar::actions::set_active(state, BarType::Detail)
+  }
+
+  pub fn close(mut state: RcState) -> RcState {
+    Rc::make_mut(&mut state).detail.entry = None;
+
+    bar::actions::set_active(state, BarType::Status)
+  }
+
+  pub fn move_cursor(mut state: RcState, amount: isize) -> RcState {
+    let len = state.detail.entry.as_ref().map_or(0, |e| items(e).len());
+
+    let state_mut = Rc::make_mut(&mut state);
+    let max = len.saturating_sub(1) as isize;
+    let cursor = (state_mut.detail.cursor as isize).saturating_add(amount).max(0).min(max);
+    state_mut.detail.cursor = cursor as usize;
+
+    state
+  }
+
+  /// Copies the highlighted item's value to the clipboard
+  pub fn copy_value(state: RcState) -> RcState {
+    if !clipboard_enabled() {
+      return state;
+    }
+
+    let item = state.detail.entry.as_ref()
+      .and_then(|entry| items(entry).into_iter().nth(state.detail.cursor));
+
+    let (label, value) = match item {
+      Some(item) => item,
+      None => return state
+    };
+
+    match clip(value) {
+      Ok(()) => state_actions::internal(state, &format!("copied {} to clipboard", label)),
+      Err(e) => state_actions::internal(
+        state, &format!("error writing to clipboard: {:?}", e)
+      )
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use spectral::prelude::*;
+
+  #[test]
+  fn test_layout() {
+    let items = vec![
+      ("message".to_string(), "hello world".to_string()),
+      ("fields.a".to_string(), "{\n  \"b\": 1\n}".to_string())
+    ];
+
+    let Layout { lines, ranges } = layout(&items, 8);
+    let lines: Vec<&str> = lines.iter().map(|(_, l)| l.as_str()).collect();
+
+    assert_that!(lines).is_equal_to(vec![
+      "message:", "  hello ", "  world", "fields.a:", "  {", "    \"b\":", "   1", "  }"
+    ]);
+    assert_that!(ranges).is_equal_to(vec![(0, 3), (3, 8)]);
+  }
+}
@@ -0,0 +1,382 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::error::Error;
+use std::rc::Rc;
+
+use crossterm::{Terminal, TerminalCursor, KeyEvent, ClearType};
+
+use crate::expression;
+use crate::filter::{ExpressionFilter, Filter, FilterMode};
+use crate::parser::MessageKind;
+use crate::style::value_string;
+
+use super::state::{self, RcState, RenderState};
+use super::state::actions as state_actions;
+use super::bar::{self, BarType};
+use super::InputAction;
+
+/// Values of a field to include or exclude, and the filter applying them
+#[derive(Clone, Default)]
+struct Facet {
+  included: BTreeSet<String>,
+  excluded: BTreeSet<String>,
+
+  /// The query of the filter added for this facet, if any
+  query: Option<String>
+}
+
+/// The facet list overlay, showing a field's distinct values
+#[derive(Clone)]
+pub struct FacetBarState {
+  /// The field being browsed
+  field: String,
+
+  /// Distinct values and their counts when opened, most common first
+  values: Rc<Vec<(String, usize)>>,
+
+  /// The number of messages counted without the field
+  missing: usize,
+
+  /// The highlighted value in the list, by position
+  cursor: usize,
+
+  /// Each field's toggled values, for fields with any
+  facets: BTreeMap<String, Facet>
+}
+
+impl FacetBarState {
+  pub fn new() -> Self {
+    FacetBarState {
+      field: String::new(),
+      values: Rc::new(Vec::new()),
+      missing: 0,
+      cursor: 0,
+      facets: BTreeMap::new()
+    }
+  }
+}
+
+/// Quotes a value for an expression
+pub fn quote(value: &str) -> String {
+  format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// An expression matching messages whose field has any included value and
+/// no excluded one, e.g. `status == "500" && !(pod == "api-1")`, or None if
+/// no values are toggled
+///
+/// Exclusions are negated equalities so messages without the field are kept.
+fn facet_query(
+  field: &str, included: &BTreeSet<String>, excluded: &BTreeSet<String>
+) -> Option<String> {
+  let any = |values: &BTreeSet<String>| values.iter()
+    .map(|value| format!("{} == {}", field, quote(value)))
+    .collect::<Vec<String>>()
+    .join(" || ");
+
+  let mut parts = Vec::new();
+  match included.len() {
+    0 => (),
+    1 => parts.push(any(included)),
+    _ => parts.push(format!("({})", any(included)))
+  }
+
+  if !excluded.is_empty() {
+    parts.push(format!("!({})", any(excluded)));
+  }
+
+  if parts.is_empty() {
+    None
+  } else {
+    Some(parts.join(" && "))
+  }
+}
+
+/// Counts the distinct values of a field over the current view, as if the
+/// filter with the query `skip` weren't applied
+///
+/// Returns values and counts, most common first, and the number of messages
+/// without the field.
+fn count(state: &RenderState, field: &str, skip: Option<&str>) -> (Vec<(String, usize)>, usize) {
+  let mut counts: HashMap<String, usize> = HashMap::new();
+  let mut missing = 0;
+
+  for entry in state.entries.borrow().iter() {
+    let message = &entry.message;
+    if message.kind == MessageKind::Internal || !state::visible(state, message) {
+      continue;
+    }
+
+    if !state::filter_pass_except(state, message, skip) {
+      continue;
+    }
+
+    match expression::lookup(message, field) {
+      Some(value) => *counts.entry(value_string(&value)).or_insert(0) += 1,
+      None => missing += 1
+    }
+  }
+
+  let mut values: Vec<(String, usize)> = counts.into_iter().collect();
+  values.sort_by(|(a, a_count), (b, b_count)| b_count.cmp(a_count).then_with(|| a.cmp(b)));
+
+  (values, missing)
+}
+
+/// The range of values shown in the list, keeping the cursor in view
+fn visible_range(state: &RenderState, len: usize) -> (usize, usize) {
+  let rows = (state.height as usize / 2).saturating_sub(1).max(1);
+  let start = state.facet.cursor.saturating_sub(rows - 1).min(len.saturating_sub(rows));
+
+  (start, (start + rows).min(len))
+}
+
+pub fn render(
+  state: RcState, terminal: &Terminal, cursor: &TerminalCursor
+) -> Result<RcState, Box<dyn Error>> {
+  let width = state.width as usize;
+  let normal = state.style.highlighted.get_base();
+  let selected = state.style.selected.get_base();
+
+  let facet = state.facet.facets.get(&state.facet.field).cloned().unwrap_or_default();
+  let values = &state.facet.values;
+  let total: usize = values.iter().map(|(_, count)| count).sum::<usize>() + state.facet.missing;
+  let count_width = values.first().map_or(1, |(_, count)| count.to_string().len());
+
+  let mut lines = vec![format!(
+    " {}: {} value{} in {} message{}{}",
+    state.facet.field,
+    values.len(), if values.len() == 1 { "" } else { "s" },
+    total, if total == 1 { "" } else { "s" },
+    if state.facet.missing > 0 {
+      format!(", {} without it", state.facet.missing)
+    } else {
+      String::new()
+    }
+  )];
+
+  let (start, end) = visible_range(&state, values.len());
+  for (value, count) in &values[start..end] {
+    let mark = if facet.included.contains(value) {
+      "+"
+    } else if facet.excluded.contains(value) {
+      "-"
+    } else {
+      " "
+    };
+
+    let percent = *count as f64 * 100.0 / total.max(1) as f64;
+    lines.push(format!(" [{}] {:>w$} {:>5.1}%  {}", mark, count, percent, value, w = count_width));
+  }
+
+  let top = state.height.saturating_sub(1 + lines.len() as u16);
+  for (row, line) in lines.iter().enumerate() {
+    let line: String = line.chars().take(width).collect();
+    let padding = " ".repeat(width.saturating_sub(line.chars().count()));
+
+    let style = if row > 0 && start + row - 1 == state.facet.cursor { selected } else { normal };
+
+    cursor.goto(0, top + row as u16)?;
+    terminal.clear(ClearType::CurrentLine)?;
+    terminal.write(style.paint(format!("{}{}", line, padding)))?;
+  }
+
+  let help = "facets | space: include | x: exclude | c: clear | esc: close";
+  let help: String = help.chars().take(width).collect();
+  let padding = " ".repeat(width.saturating_sub(help.len()));
+
+  cursor.hide()?;
+  cursor.goto(0, state.height - 1)?;
+  terminal.clear(ClearType::CurrentLine)?;
+  terminal.write(selected.paint(format!("{}{}", help, padding)))?;
+
+  Ok(state)
+}
+
+pub fn input(mut state: RcState, key: &KeyEvent) -> (RcState, InputAction) {
+  let page = (state.height as isize / 2 - 1).max(1);
+
+  state = match key {
+    KeyEvent::Esc | KeyEvent::Char('q') | KeyEvent::Char('\n') => {
+      bar::actions::set_active(state, BarType::Status)
+    },
+    KeyEvent::Up => actions::move_cursor(state, -1),
+    KeyEvent::Down => actions::move_cursor(state, 1),
+    KeyEvent::PageUp => actions::move_cursor(state, -page),
+    KeyEvent::PageDown => actions::move_cursor(state, page),
+    KeyEvent::Char(' ') | KeyEvent::Char('+') => actions::toggle_at_cursor(state, true),
+    KeyEvent::Char('x') | KeyEvent::Char('-') => actions::toggle_at_cursor(state, false),
+    KeyEvent::Char('c') => actions::clear(state),
+    _ => return (state, InputAction::Unhandled)
+  };
+
+  (state, InputAction::Rerender)
+}
+
+pub mod actions {
+  use super::*;
+
+  /// Lists the distinct values of a field over the current view
+  ///
+  /// If the field's values were toggled before, and its filter is still
+  /// applied, the toggles are kept and the counts ignore that filter, so
+  /// excluded values are still listed.
+  pub fn open(mut state: RcState, field: &str) -> RcState {
+    let applied = state.facet.facets.get(field)
+      .and_then(|facet| facet.query.clone())
+      .filter(|query| state.filters.borrow().iter().any(|f| f.query() == query));
+
+    let (values, missing) = count(&state, field, applied.as_deref());
+    if values.is_empty() {
+      return state_actions::internal(state, &format!("no messages in view have a {} field", field));
+    }
+
+    let state_mut = Rc::make_mut(&mut state);
+    if applied.is_none() {
+      state_mut.facet.facets.remove(field);
+    }
+
+    state_mut.facet.field = field.to_string();
+    state_mut.facet.values = Rc::new(values);
+    state_mut.facet.missing = missing;
+    state_mut.facet.cursor = 0;
+
+    bar::actions::set_active(state, BarType::Facet)
+  }
+
+  pub fn move_cursor(mut state: RcState, amount: isize) -> RcState {
+    let max = state.facet.values.len().saturating_sub(1) as isize;
+    let state_mut = Rc::make_mut(&mut state);
+    let cursor = (state_mut.facet.cursor as isize + amount).max(0).min(max);
+    state_mut.facet.cursor = cursor as usize;
+
+    state
+  }
+
+  /// Replaces the field's filter with one for its toggled values
+  fn apply(mut state: RcState, facet: Facet) -> RcState {
+    let field = state.facet.field.clone();
+    let query = facet_query(&field, &facet.included, &facet.excluded);
+
+    let filter = match &query {
+      Some(query) => match ExpressionFilter::new(query, false) {
+        Ok(filter) => Some(filter),
+        Err(e) => return state_actions::internal(state, &format!(
+          "can't filter on {}: {}", field, e
+        ))
+      },
+      None => None
+    };
+
+    if let Some(old) = &facet.query {
+      state.filters.borrow_mut()
+        .retain(|f| f.mode() != FilterMode::Expression || f.query() != old);
+    }
+
+    let state_mut = Rc::make_mut(&mut state);
+    state_mut.log.selection = None;
+    state_mut.facet.facets.insert(field, Facet { query, ..facet });
+
+    match filter {
+      Some(filter) => state_actions::add_filter(state, Box::new(filter)),
+      None => state::refilter(state)
+    }
+  }
+
+  /// Includes the highlighted value, or if `include` is false, excludes it;
+  /// toggling it again clears it
+  pub fn toggle_at_cursor(state: RcState, include: bool) -> RcState {
+    let value = match state.facet.values.get(state.facet.cursor) {
+      Some((value, _)) => value.clone(),
+      None => return state
+    };
+
+    let mut facet = state.facet.facets.get(&state.facet.field).cloned().unwrap_or_default();
+    let (toggled, other) = if include {
+      (&mut facet.included, &mut facet.excluded)
+    } else {
+      (&mut facet.excluded, &mut facet.included)
+    };
+
+    other.remove(&value);
+    if !toggled.remove(&value) {
+      toggled.insert(value);
+    }
+
+    apply(state, facet)
+  }
+
+  /// Clears every toggled value of the field, removing its filter
+  pub fn clear(state: RcState) -> RcState {
+    let facet = state.facet.facets.get(&state.facet.field).cloned().unwrap_or_default();
+    apply(state, Facet { query: facet.query, ..Default::default() })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::sync::Arc;
+
+  use serde_json::json;
+  use spectral::prelude::*;
+  use structopt::StructOpt;
+
+  use crate::config::Config;
+  use crate::renderer::types::MessageEntry;
+
+  fn set(items: &[&str]) -> BTreeSet<String> {
+    items.iter().map(|s| s.to_string()).collect()
+  }
+
+  #[test]
+  fn test_facet_query() {
+    assert_that!(facet_query("status", &set(&[]), &set(&[]))).is_none();
+    assert_that!(facet_query("status", &set(&["500"]), &set(&[])))
+      .is_equal_to(Some("status == \"500\"".to_string()));
+    assert_that!(facet_query("pod", &set(&["a", "b"]), &set(&["say \"hi\""]))).is_equal_to(Some(
+      "(pod == \"a\" || pod == \"b\") && !(pod == \"say \\\"hi\\\"\")".to_string()
+    ));
+  }
+
+  #[test]
+  fn test_toggle() {
+    let config = Arc::new(Config::from_iter_safe(vec![""]).unwrap());
+    let mut state = Rc::new(RenderState::new(config));
+    for status in &[200, 500, 200, 404, 200] {
+      let mut entry = MessageEntry::internal("request");
+      entry.message.kind = MessageKind::Json;
+      entry.message.level = None;
+      entry.message.metadata.insert("status".to_string(), json!(status));
+      state = state_actions::add_entry(state, entry);
+    }
+
+    let shown = |state: &RcState| state.filtered_entries.borrow().iter()
+      .filter_map(|f| f.entry.upgrade())
+      .filter(|e| e.message.kind != MessageKind::Internal)
+      .count();
+
+    state = actions::open(state, "status");
+    assert_that!(*state.facet.values).is_equal_to(vec![
+      ("200".to_string(), 3), ("404".to_string(), 1), ("500".to_string(), 1)
+    ]);
+
+    // exclude 200, then reopen: the counts ignore the facet's own filter
+    state = actions::toggle_at_cursor(state, false);
+    assert_that!(shown(&state)).is_equal_to(2);
+
+    state = actions::open(state, "status");
+    assert_that!(state.facet.values.len()).is_equal_to(3);
+
+    // including it instead replaces the filter rather than adding another
+    state = actions::toggle_at_cursor(state, true);
+    assert_that!(shown(&state)).is_equal_to(3);
+    assert_that!(state.filters.borrow().len()).is_equal_to(1);
+
+    state = actions::clear(state);
+    assert_that!(shown(&state)).is_equal_to(5);
+    assert_that!(state.filters.borrow().is_empty()).is_true();
+  }
+}
@@ -5,12 +5,15 @@ use std::rc::Rc;
 
 use crossterm::{Terminal, TerminalCursor, KeyEvent, ClearType};
 
+use crate::expression;
 use crate::filter::FilterMode;
+use crate::history::SavedFilter;
 use crate::style::{StyleProfileKind, styler_base, styler_error};
 
 use super::state::{self, RcState};
 use super::state::actions as state_actions;
 use super::bar::{self, BarType};
+use super::history;
 use super::text::{self, TextBuffer, TextInputAction};
 use super::InputAction;
 
@@ -18,7 +21,10 @@ use super::InputAction;
 pub struct FilterBarState {
   mode: FilterMode,
   text: TextBuffer,
-  inverted: bool
+  inverted: bool,
+
+  /// The position in the filter history while browsing, see `history::step()`
+  history: Option<usize>
 }
 
 impl FilterBarState {
@@ -28,7 +34,8 @@ impl FilterBarState {
     FilterBarState {
       mode: FilterMode::Regex,
       text: TextBuffer::new().with_styler(Some(styler)),
-      inverted: false
+      inverted: false,
+      history: None
     }
   }
 }
@@ -59,7 +66,7 @@ pub fn render(
   cursor.goto(0, state.height - 1)?;
   terminal.clear(ClearType::CurrentLine)?;
 
-  let style = &state.config.style.selected.get_base();
+  let style = &state.style.selected.get_base();
   terminal.write(style.paint(" ".repeat(state.width as usize)))?;
   cursor.goto(0, state.height - 1)?;
 
@@ -99,12 +106,29 @@ fn handle_text_input(
 }
 
 pub fn input(mut state: RcState, key: &KeyEvent) -> (RcState, InputAction) {
+  if let KeyEvent::Up | KeyEvent::Down = key {
+    state = actions::browse_history(state, matches!(key, KeyEvent::Up));
+    state = actions::update_highlight(state);
+    state = actions::update_style(state);
+
+    return (state, InputAction::Rerender);
+  }
+
+  if let (FilterMode::Expression, KeyEvent::Char('\t')) = (state.filter.mode, key) {
+    state = actions::complete_field(state);
+    state = actions::update_highlight(state);
+    state = actions::update_style(state);
+
+    return (state, InputAction::Rerender);
+  }
+
   let (new_state, action) = handle_text_input(state, key);
   state = new_state;
 
   let input_action = match action {
     TextInputAction::Action(a) => a,
     TextInputAction::Exit(a) => {
+      Rc::make_mut(&mut state).filter.history = None;
       state = actions::update_highlight(state);
       state = actions::update_style(state);
       state = bar::actions::set_active(state, BarType::Status);
@@ -113,6 +137,10 @@ pub fn input(mut state: RcState, key: &KeyEvent) -> (RcState, InputAction) {
     TextInputAction::Submit(a, input) => {
       match state.filter.mode.parse(&input, state.filter.inverted) {
         Ok(filter) => {
+          let saved = SavedFilter::new(state.filter.mode, &input, state.filter.inverted);
+          state = history::actions::remember_filter(state, saved);
+
+          Rc::make_mut(&mut state).filter.history = None;
           state = actions::clear_input(state);
           state = bar::actions::set_active(state, BarType::Status);
           state = actions::update_highlight(state);
@@ -202,6 +230,80 @@ pub mod actions {
     state
   }
 
+  /// Completes the field name before the cursor in an expression, listing the
+  /// candidates if there's more than one
+  pub fn complete_field(mut state: RcState) -> RcState {
+    let buffer = &state.filter.text;
+    let before: String = buffer.input.chars().take(buffer.position - 1).collect();
+    let start = match expression::partial_field(&before) {
+      Some(start) => start,
+      None => return state
+    };
+
+    let partial = &before[start..];
+    let candidates: Vec<String> = {
+      let entries = state.entries.borrow();
+      expression::known_fields(entries.iter().map(|e| &e.message))
+        .into_iter()
+        .filter(|f| f.starts_with(partial))
+        .collect()
+    };
+
+    let completion = match candidates.as_slice() {
+      [] => return state_actions::internal(state, "no fields to complete"),
+      [field] => format!("{} ", &field[partial.len()..]),
+      [first, rest @ ..] => {
+        let mut common = rest.iter().fold(first.len(), |len, f| {
+          first.bytes().zip(f.bytes()).take(len).take_while(|(a, b)| a == b).count()
+        });
+
+        while !first.is_char_boundary(common) {
+          common -= 1;
+        }
+
+        if common == partial.len() {
+          let shown: Vec<&str> = candidates.iter().take(20).map(String::as_str).collect();
+          let more = if candidates.len() > shown.len() { ", ..." } else { "" };
+
+          return state_actions::internal(
+            state, &format!("fields: {}{}", shown.join(", "), more)
+          );
+        }
+
+        first[partial.len()..common].to_string()
+      }
+    };
+
+    let state_mut = Rc::make_mut(&mut state);
+    for c in completion.chars() {
+      state_mut.filter.text = text::actions::push_input(state_mut.filter.text.clone(), c);
+    }
+
+    state
+  }
+
+  /// Replaces the input with an older or newer filter from the history
+  pub fn browse_history(mut state: RcState, older: bool) -> RcState {
+    let position = history::step(state.history.borrow().filters.len(), state.filter.history, older);
+    let item = history::item(&state.history.borrow().filters, position).cloned();
+
+    let state_mut = Rc::make_mut(&mut state);
+    state_mut.filter.history = position;
+
+    let text = state_mut.filter.text.clone();
+    state_mut.filter.text = match item {
+      Some(item) => {
+        state_mut.filter.mode = item.mode;
+        state_mut.filter.inverted = item.inverted;
+
+        history::set_input(text, &item.query)
+      },
+      None => text::actions::clear_input(text)
+    };
+
+    state
+  }
+
   pub fn next_mode(mut state: RcState) -> RcState {
     let state_mut = Rc::make_mut(&mut state);
     state_mut.filter.mode = state_mut.filter.mode.next();
@@ -0,0 +1,222 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+use std::error::Error;
+use std::rc::Rc;
+
+use chrono::prelude::*;
+use crossterm::{Terminal, TerminalCursor, KeyEvent, ClearType};
+use simple_error::SimpleResult;
+
+use crate::style::{StyleProfileKind, styler_base, styler_error};
+use crate::timezone;
+
+use super::state::{RcState, RenderState};
+use super::state::actions as state_actions;
+use super::bar::{self, BarType};
+use super::log;
+use super::text::{self, TextBuffer, TextInputAction};
+use super::InputAction;
+
+#[derive(Clone)]
+pub struct GotoBarState {
+  text: TextBuffer
+}
+
+impl GotoBarState {
+  pub fn new() -> Self {
+    let styler = styler_base(StyleProfileKind::Selected);
+
+    GotoBarState {
+      text: TextBuffer::new().with_styler(Some(styler))
+    }
+  }
+}
+
+/// The timestamp of a filtered entry, if it's still available
+fn timestamp_at(state: &RenderState, index: usize) -> Option<DateTime<Utc>> {
+  state.filtered_entries.borrow().get(index)?.entry.upgrade()?.message.timestamp
+}
+
+/// The timestamp offsets are relative to: the selected message's, or the last
+/// message's if none is selected
+fn reference(state: &RenderState) -> Option<DateTime<Utc>> {
+  match state.log.selection {
+    Some(selection) => timestamp_at(state, selection.rel_index),
+    None => {
+      let len = state.filtered_entries.borrow().len();
+      (0..len).rev().find_map(|i| timestamp_at(state, i))
+    }
+  }
+}
+
+/// Where to go: a moment, or a record by number, e.g. `#48210`
+#[derive(Debug, PartialEq)]
+enum Target {
+  Time(DateTime<Utc>),
+  Record(usize)
+}
+
+/// Resolves user input to a target, with times offset from now if no message
+/// has a timestamp
+fn parse_input(state: &RenderState, input: &str) -> SimpleResult<Target> {
+  if let Some(number) = input.trim().strip_prefix('#') {
+    return match number.parse::<usize>() {
+      Ok(number) if number > 0 => Ok(Target::Record(number)),
+      _ => bail!("invalid record number, expected e.g. #42: {}", input)
+    };
+  }
+
+  let reference = reference(state).unwrap_or_else(Utc::now);
+  timezone::parse_target(input, timezone::to_display(reference)).map(Target::Time)
+}
+
+/// Finds the filtered entry nearest in time to `target`
+///
+/// Messages aren't necessarily in order, so this checks all of them.
+fn nearest(state: &RenderState, target: DateTime<Utc>) -> Option<usize> {
+  let filtered_entries = state.filtered_entries.borrow();
+
+  filtered_entries.iter()
+    .enumerate()
+    .filter_map(|(i, filtered)| {
+      let timestamp = filtered.entry.upgrade()?.message.timestamp?;
+      Some((i, timestamp.signed_duration_since(target).num_milliseconds().abs()))
+    })
+    .min_by_key(|(_, distance)| *distance)
+    .map(|(i, _)| i)
+}
+
+pub fn render(
+  state: RcState, terminal: &Terminal, cursor: &TerminalCursor
+) -> Result<RcState, Box<dyn Error>> {
+  cursor.goto(0, state.height - 1)?;
+  terminal.clear(ClearType::CurrentLine)?;
+
+  let style = &state.style.selected.get_base();
+  terminal.write(style.paint(" ".repeat(state.width as usize)))?;
+  cursor.goto(0, state.height - 1)?;
+
+  terminal.write(style.paint("go to > ").to_string())?;
+  text::render(
+    Rc::clone(&state), &state.goto.text,
+    terminal, cursor,
+    8, state.height - 1
+  )?;
+
+  let right = "| e.g. 14:32, 2023-05-01T14:32:10Z, -10m, #42";
+  if let Some(col) = state.width.checked_sub(right.len() as u16) {
+    if col as usize > 8 + state.goto.text.input.len() {
+      cursor.goto(col, state.height - 1)?;
+      terminal.write(style.paint(right))?;
+    }
+  }
+
+  cursor.goto(8 + state.goto.text.position as u16 - 1, state.height - 1)?;
+
+  Ok(state)
+}
+
+fn handle_text_input(
+  mut state: RcState, key: &KeyEvent
+) -> (RcState, TextInputAction) {
+  let state_mut = Rc::make_mut(&mut state);
+
+  let text_state = state_mut.goto.text.clone();
+  let (text_state, action) = text::input(text_state, key);
+  state_mut.goto.text = text_state;
+
+  (state, action)
+}
+
+pub fn input(mut state: RcState, key: &KeyEvent) -> (RcState, InputAction) {
+  let (new_state, action) = handle_text_input(state, key);
+  state = new_state;
+
+  let input_action = match action {
+    TextInputAction::Action(a) => a,
+    TextInputAction::Exit(a) => {
+      state = actions::update_style(state);
+      state = bar::actions::set_active(state, BarType::Status);
+      a
+    },
+    TextInputAction::Submit(a, input) => {
+      if parse_input(&state, &input).is_ok() {
+        state = actions::clear_input(state);
+        state = actions::update_style(state);
+        state = bar::actions::set_active(state, BarType::Status);
+      }
+
+      state = actions::go(state, &input);
+
+      a
+    },
+    TextInputAction::Update(a) => {
+      state = actions::update_style(state);
+      a
+    }
+  };
+
+  (state, input_action)
+}
+
+pub mod actions {
+  use super::*;
+
+  pub fn update_style(mut state: RcState) -> RcState {
+    let state_mut = Rc::make_mut(&mut state);
+
+    let input = &state_mut.goto.text.input;
+    let styler = if input.is_empty() || parse_input(state_mut, input).is_ok() {
+      styler_base(StyleProfileKind::Selected)
+    } else {
+      styler_error(StyleProfileKind::Selected)
+    };
+
+    state_mut.goto.text.styler = Some(styler);
+
+    state
+  }
+
+  pub fn clear_input(mut state: RcState) -> RcState {
+    let state_mut = Rc::make_mut(&mut state);
+
+    state_mut.goto.text = text::actions::clear_input(
+      state_mut.goto.text.clone()
+    );
+
+    state
+  }
+
+  /// Goes to a time or record number entered by the user, e.g. with `:goto`
+  pub fn go(state: RcState, input: &str) -> RcState {
+    match parse_input(&state, input) {
+      Ok(Target::Time(time)) => jump_to(state, time),
+      Ok(Target::Record(number)) => jump_to_record(state, number),
+      Err(e) => state_actions::internal(state, &e.to_string())
+    }
+  }
+
+  /// Selects the message nearest in time to `target`
+  pub fn jump_to(state: RcState, target: DateTime<Utc>) -> RcState {
+    match nearest(&state, target) {
+      Some(index) => log::actions::move_selection_to_index(state, index),
+      None => state_actions::internal(state, "no messages have timestamps")
+    }
+  }
+
+  /// Selects a message by its record number, if it isn't filtered out
+  pub fn jump_to_record(state: RcState, number: usize) -> RcState {
+    if number > state.entries.borrow().len() {
+      return state_actions::internal(state, &format!("there's no record #{}", number));
+    }
+
+    let position = state.filtered_entries.borrow()
+      .binary_search_by_key(&(number - 1), |f| f.index)
+      .ok();
+
+    match position {
+      Some(position) => log::actions::move_selection_to_index(state, position),
+      None => state_actions::internal(state, &format!("record #{} is hidden by filters", number))
+    }
+  }
+}
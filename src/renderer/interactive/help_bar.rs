@@ -0,0 +1,142 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+use std::error::Error;
+use std::rc::Rc;
+
+use crossterm::{Terminal, TerminalCursor, KeyEvent, ClearType};
+
+use super::state::{RcState, RenderState};
+use super::bar::{self, BarType};
+use super::keymap::{Key, ACTIONS};
+use super::InputAction;
+
+/// A full screen overlay listing keybindings
+#[derive(Clone)]
+pub struct HelpBarState {
+  /// The first line shown
+  scroll: usize
+}
+
+impl HelpBarState {
+  pub fn new() -> Self {
+    HelpBarState { scroll: 0 }
+  }
+}
+
+/// Help categories, in order
+static CATEGORIES: &[&str] = &[
+  "navigation", "filtering and search", "selection and copying", "display", "panes", "other"
+];
+
+/// Keys that can't be remapped, by category, as (keys, description)
+static FIXED: &[(&str, &str, &str)] = &[
+  ("filtering and search", "C-r", "while filtering or searching: switch between modes"),
+  ("filtering and search", "C-e", "while filtering or searching: invert the match"),
+  ("filtering and search", "tab", "in an expression filter: complete a field name"),
+  ("other", "C-c", "copy the selected messages, or quit")
+];
+
+/// The help text for the effective keymap, as lines
+fn lines(state: &RenderState) -> Vec<String> {
+  let keymap = &state.config.file().keys;
+
+  let mut bindings: Vec<(&str, String, &str)> = Vec::new();
+  for info in ACTIONS {
+    let keys: Vec<String> = keymap.keys(info.action).iter().map(Key::to_string).collect();
+    let keys = if keys.is_empty() { "unbound".to_string() } else { keys.join(", ") };
+
+    bindings.push((info.category, keys, info.description));
+  }
+
+  for (category, keys, description) in FIXED {
+    bindings.push((category, keys.to_string(), description));
+  }
+
+  let width = bindings.iter().map(|(_, keys, _)| keys.len()).max().unwrap_or(0);
+
+  let mut lines = Vec::new();
+  for category in CATEGORIES {
+    if !lines.is_empty() {
+      lines.push(String::new());
+    }
+
+    lines.push(format!("{}:", category));
+    for (_, keys, description) in bindings.iter().filter(|(c, _, _)| c == category) {
+      lines.push(format!("  {:width$}  {}", keys, description, width = width));
+    }
+  }
+
+  lines
+}
+
+fn max_scroll(state: &RenderState) -> usize {
+  lines(state).len().saturating_sub(state.height.saturating_sub(1) as usize)
+}
+
+pub fn render(
+  state: RcState, terminal: &Terminal, cursor: &TerminalCursor
+) -> Result<RcState, Box<dyn Error>> {
+  let width = state.width as usize;
+  let rows = state.height.saturating_sub(1) as usize;
+  let normal = state.style.normal.get_base();
+  let selected = state.style.selected.get_base();
+
+  let lines = lines(&state);
+  for row in 0..rows {
+    cursor.goto(0, row as u16)?;
+    terminal.clear(ClearType::CurrentLine)?;
+
+    if let Some(line) = lines.get(state.help.scroll + row) {
+      let line: String = line.chars().take(width).collect();
+      terminal.write(normal.paint(line))?;
+    }
+  }
+
+  let help = "help | up/down: scroll | esc: close";
+  let help: String = help.chars().take(width).collect();
+  let padding = " ".repeat(width.saturating_sub(help.len()));
+
+  cursor.hide()?;
+  cursor.goto(0, state.height - 1)?;
+  terminal.clear(ClearType::CurrentLine)?;
+  terminal.write(selected.paint(format!("{}{}", help, padding)))?;
+
+  Ok(state)
+}
+
+pub fn input(mut state: RcState, key: &KeyEvent) -> (RcState, InputAction) {
+  let page = state.height as isize - 1;
+
+  state = match key {
+    KeyEvent::Esc | KeyEvent::Char('q') | KeyEvent::Char('?') => {
+      bar::actions::set_active(state, BarType::Status)
+    },
+    KeyEvent::Up => actions::scroll(state, -1),
+    KeyEvent::Down => actions::scroll(state, 1),
+    KeyEvent::PageUp => actions::scroll(state, -page),
+    KeyEvent::PageDown => actions::scroll(state, page),
+    _ => return (state, InputAction::Unhandled)
+  };
+
+  (state, InputAction::Rerender)
+}
+
+pub mod actions {
+  use super::*;
+
+  pub fn open(mut state: RcState) -> RcState {
+    Rc::make_mut(&mut state).help.scroll = 0;
+
+    bar::actions::set_active(state, BarType::Help)
+  }
+
+  pub fn scroll(mut state: RcState, amount: isize) -> RcState {
+    let max = max_scroll(&state) as isize;
+
+    let state_mut = Rc::make_mut(&mut state);
+    let scroll = (state_mut.help.scroll as isize + amount).max(0).min(max);
+    state_mut.help.scroll = scroll as usize;
+
+    state
+  }
+}
@@ -0,0 +1,554 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::rc::{Rc, Weak};
+
+use chrono::prelude::*;
+use crossterm::{Terminal, TerminalCursor, KeyEvent, ClearType};
+
+use crate::classifier::ChunkKind;
+use crate::parser::LogLevel;
+use crate::reader::anomaly;
+use crate::renderer::stats::spark_glyph;
+use crate::timezone;
+
+use super::state::{FilteredEntry, RcState, RenderState};
+use super::state::actions as state_actions;
+use super::bar::{self, BarType};
+use super::log;
+use super::zoom::{self, Window};
+use super::InputAction;
+
+/// Bucket sizes, in seconds, from which the smallest that fits is chosen
+static BUCKET_SECONDS: &[i64] = &[
+  1, 2, 5, 10, 15, 30,
+  60, 2 * 60, 5 * 60, 10 * 60, 15 * 60, 30 * 60,
+  3600, 2 * 3600, 3 * 3600, 6 * 3600, 12 * 3600, 86400
+];
+
+/// A strip above the log charting message volume over time, and a bar to pick
+/// a moment from it
+#[derive(Clone)]
+pub struct HistogramBarState {
+  /// If true, the strip is shown
+  pub visible: bool,
+
+  /// The highlighted bucket while picking
+  cursor: usize,
+
+  /// The bucket at the other end of a range to zoom into, while picking
+  anchor: Option<usize>,
+
+  cache: Rc<RefCell<HistogramCache>>
+}
+
+impl HistogramBarState {
+  pub fn new() -> Self {
+    HistogramBarState {
+      visible: false,
+      cursor: 0,
+      anchor: None,
+      cache: Rc::new(RefCell::new(HistogramCache::default()))
+    }
+  }
+
+  /// Forgets counted messages, e.g. after the filtered entries are rebuilt
+  pub fn invalidate(&self) {
+    *self.cache.borrow_mut() = HistogramCache::default();
+  }
+}
+
+/// The timestamps and levels of filtered entries, collected incrementally as
+/// entries arrive, and the last histogram built from them
+///
+/// The strip is redrawn far more often than entries arrive, so this keeps it
+/// from walking every entry on each redraw, e.g. while resizing.
+#[derive(Default)]
+struct HistogramCache {
+  /// The filtered entries collected from, since switching panes swaps them;
+  /// held weakly rather than by address, which could be reused once freed
+  source: Weak<RefCell<Vec<FilteredEntry>>>,
+  scanned: usize,
+  messages: Vec<(DateTime<Utc>, Option<LogLevel>)>,
+
+  /// The times of anomalies reported with `--anomalies`
+  anomalies: Vec<DateTime<Utc>>,
+
+  /// The last histogram built, with the number of messages and the width it
+  /// was built for
+  built: Option<(usize, usize, Rc<Histogram>)>
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+struct Bucket {
+  count: usize,
+  levels: BTreeMap<LogLevel, usize>
+}
+
+impl Bucket {
+  /// The most severe level making up at least a tenth of this bucket, used to
+  /// color it, so a lone error doesn't color an otherwise healthy bucket
+  fn level(&self) -> Option<LogLevel> {
+    self.levels.iter()
+      .rev()
+      .find(|(_, count)| **count * 10 >= self.count)
+      .map(|(level, _)| *level)
+  }
+}
+
+#[derive(Debug, PartialEq)]
+struct Histogram {
+  /// The start of the first bucket, in seconds
+  start: i64,
+
+  /// The width of each bucket, in seconds
+  seconds: i64,
+
+  buckets: Vec<Bucket>
+}
+
+impl Histogram {
+  /// Counts timestamped messages into at most `width` buckets
+  fn new(messages: &[(DateTime<Utc>, Option<LogLevel>)], width: usize) -> Option<Histogram> {
+    let min = messages.iter().map(|(t, _)| t.timestamp()).min()?;
+    let max = messages.iter().map(|(t, _)| t.timestamp()).max()?;
+    let width = width.max(1) as i64;
+
+    let needed = (max - min + 1 + width - 1) / width;
+    let seconds = BUCKET_SECONDS.iter()
+      .cloned()
+      .find(|s| *s >= needed && (max - min.div_euclid(*s) * s) / s < width)
+      .unwrap_or_else(|| (needed + 86399) / 86400 * 86400 + 86400);
+
+    let start = min.div_euclid(seconds) * seconds;
+    let mut buckets = vec![Bucket::default(); ((max - start) / seconds + 1) as usize];
+    for (timestamp, level) in messages {
+      let bucket = &mut buckets[((timestamp.timestamp() - start) / seconds) as usize];
+      bucket.count += 1;
+
+      if let Some(level) = level {
+        *bucket.levels.entry(*level).or_insert(0) += 1;
+      }
+    }
+
+    Some(Histogram { start, seconds, buckets })
+  }
+
+  /// The bucket containing a time
+  fn position(&self, time: DateTime<Utc>) -> usize {
+    let position = (time.timestamp() - self.start).max(0) / self.seconds;
+    (position as usize).min(self.buckets.len() - 1)
+  }
+
+  fn bucket_start(&self, position: usize) -> DateTime<Utc> {
+    Utc.timestamp_opt(self.start + position as i64 * self.seconds, 0).unwrap()
+  }
+
+  /// The time covered by the buckets from `a` to `b`, in either order
+  fn window(&self, a: usize, b: usize) -> Window {
+    let end = self.bucket_start(a.max(b) + 1) - chrono::Duration::nanoseconds(1);
+    Window::new(self.bucket_start(a.min(b)), end)
+  }
+
+  /// The next bucket after (or before) `position` that's a spike: a local peak
+  /// at least twice the average
+  fn next_spike(&self, position: usize, forward: bool) -> Option<usize> {
+    let total: usize = self.buckets.iter().map(|b| b.count).sum();
+    let average = total as f64 / self.buckets.len() as f64;
+
+    let count = |i: usize| self.buckets.get(i).map_or(0, |b| b.count);
+    let spike = |i: &usize| {
+      let c = count(*i);
+      c as f64 >= average * 2.0 && c >= count(i.wrapping_sub(1)) && c >= count(i + 1)
+    };
+
+    if forward {
+      (position + 1..self.buckets.len()).find(spike)
+    } else {
+      (0..position).rev().find(spike)
+    }
+  }
+}
+
+/// Formats a bucket size, e.g. `10s` or `5m`
+fn format_seconds(seconds: i64) -> String {
+  if seconds % 3600 == 0 {
+    format!("{}h", seconds / 3600)
+  } else if seconds % 60 == 0 {
+    format!("{}m", seconds / 60)
+  } else {
+    format!("{}s", seconds)
+  }
+}
+
+/// Builds a histogram of the filtered messages, ignoring internal messages
+fn histogram(state: &RenderState) -> Option<Rc<Histogram>> {
+  let mut cache = state.histogram.cache.borrow_mut();
+  let filtered_entries = state.filtered_entries.borrow();
+
+  let source = Rc::downgrade(&state.filtered_entries);
+  if !cache.source.ptr_eq(&source) || cache.scanned > filtered_entries.len() {
+    *cache = HistogramCache { source, ..Default::default() };
+  }
+
+  let cache = &mut *cache;
+  for filtered in &filtered_entries[cache.scanned..] {
+    let entry = match filtered.entry.upgrade() {
+      Some(entry) => entry,
+      None => continue
+    };
+
+    match (entry.message.level, entry.message.timestamp) {
+      (Some(LogLevel::Int), Some(timestamp)) if anomaly::is_anomaly(&entry.message) => {
+        cache.anomalies.push(timestamp)
+      },
+      (Some(LogLevel::Int), _) | (_, None) => (),
+      (level, Some(timestamp)) => cache.messages.push((timestamp, level))
+    }
+  }
+
+  cache.scanned = filtered_entries.len();
+
+  let width = state.width as usize;
+  match &cache.built {
+    Some((count, built, histogram)) if *count == cache.messages.len() && *built == width => {
+      Some(Rc::clone(histogram))
+    },
+    _ => {
+      let histogram = Rc::new(Histogram::new(&cache.messages, width)?);
+      cache.built = Some((cache.messages.len(), width, Rc::clone(&histogram)));
+      Some(histogram)
+    }
+  }
+}
+
+/// Buckets containing anomalies from `--anomalies`, once `histogram()` has
+/// collected them
+fn anomaly_positions(state: &RenderState, histogram: &Histogram) -> BTreeSet<usize> {
+  state.histogram.cache.borrow().anomalies.iter()
+    .map(|timestamp| histogram.position(*timestamp))
+    .collect()
+}
+
+/// The bucket containing the selected message, if it has a timestamp
+fn selected_position(state: &RenderState, histogram: &Histogram) -> Option<usize> {
+  let selection = state.log.selection?;
+  let entry = state.filtered_entries.borrow().get(selection.rel_index)?.entry.upgrade()?;
+
+  Some(histogram.position(entry.message.timestamp?))
+}
+
+/// Renders the strip at the top of the screen, if it's visible
+pub fn render_strip(
+  state: &RenderState, terminal: &Terminal, cursor: &TerminalCursor
+) -> Result<(), Box<dyn Error>> {
+  if !state.histogram.visible {
+    return Ok(());
+  }
+
+  cursor.goto(0, 0)?;
+  terminal.clear(ClearType::CurrentLine)?;
+
+  let histogram = match histogram(state) {
+    Some(histogram) => histogram,
+    None => return Ok(())
+  };
+
+  let picking = matches!(state.bar.active, BarType::Histogram);
+  let last = histogram.buckets.len() - 1;
+  let highlighted = if picking {
+    Some(state.histogram.cursor.min(last))
+  } else {
+    selected_position(state, &histogram)
+  };
+
+  // while picking a range, every bucket in it is highlighted
+  let range = match (picking, state.histogram.anchor, highlighted) {
+    (true, Some(anchor), Some(cursor)) => {
+      let anchor = anchor.min(last);
+      Some((anchor.min(cursor), anchor.max(cursor)))
+    },
+    (_, _, Some(position)) => Some((position, position)),
+    _ => None
+  };
+  let in_range = |i: usize| range.map_or(false, |(start, end)| i >= start && i <= end);
+
+  let anomalies = anomaly_positions(state, &histogram);
+  let max = histogram.buckets.iter().map(|b| b.count).max().unwrap_or(0);
+  let normal = &state.style.normal;
+  for (i, bucket) in histogram.buckets.iter().enumerate() {
+    let glyph = match spark_glyph(bucket.count, max) {
+      ' ' if in_range(i) => '▁',
+      glyph => glyph
+    };
+
+    let style = match bucket.level() {
+      Some(level) => normal.get_style(&ChunkKind::Level(level)),
+      None => normal.get_base()
+    };
+
+    let style = if in_range(i) { style.reverse() } else { *style };
+    let style = if anomalies.contains(&i) { style.underline() } else { style };
+    terminal.write(style.paint(glyph.to_string()))?;
+  }
+
+  Ok(())
+}
+
+pub fn render(
+  state: RcState, terminal: &Terminal, cursor: &TerminalCursor
+) -> Result<RcState, Box<dyn Error>> {
+  let width = state.width as usize;
+  let selected = state.style.selected.get_base();
+
+  let mut help = "histogram".to_string();
+  if let Some(histogram) = histogram(&state) {
+    let position = state.histogram.cursor.min(histogram.buckets.len() - 1);
+    let bucket = &histogram.buckets[position];
+    let start = timezone::to_display(histogram.bucket_start(position));
+
+    help.push_str(&format!(
+      " | {} +{}: {} message{}",
+      start.format("%Y-%m-%d %H:%M:%S"), format_seconds(histogram.seconds),
+      bucket.count, if bucket.count == 1 { "" } else { "s" }
+    ));
+
+    if let Some(level) = bucket.level() {
+      help.push_str(&format!(
+        " ({} {})", bucket.levels[&level], level.to_string().to_lowercase()
+      ));
+    }
+  }
+
+  if state.histogram.anchor.is_some() {
+    help.push_str(" | left/right: extend | w: zoom to range | space: unmark");
+  } else {
+    help.push_str(" | left/right: select | n/N: next/prev spike | space: mark range | w: zoom");
+  }
+
+  help.push_str(" | enter: go to | esc: close");
+
+  let help: String = help.chars().take(width).collect();
+  let padding = " ".repeat(width.saturating_sub(help.chars().count()));
+
+  cursor.hide()?;
+  cursor.goto(0, state.height - 1)?;
+  terminal.clear(ClearType::CurrentLine)?;
+  terminal.write(selected.paint(format!("{}{}", help, padding)))?;
+
+  Ok(state)
+}
+
+pub fn input(mut state: RcState, key: &KeyEvent) -> (RcState, InputAction) {
+  state = match key {
+    KeyEvent::Esc | KeyEvent::Char('q') => bar::actions::set_active(state, BarType::Status),
+    KeyEvent::Left => actions::move_cursor(state, -1),
+    KeyEvent::Right => actions::move_cursor(state, 1),
+    KeyEvent::Char('n') => actions::next_spike(state, true),
+    KeyEvent::Char('N') => actions::next_spike(state, false),
+    KeyEvent::Char(' ') => actions::toggle_anchor(state),
+    KeyEvent::Char('w') => actions::zoom_to_cursor(state),
+    KeyEvent::Char('\n') => {
+      let state = actions::jump_to_cursor(state);
+      bar::actions::set_active(state, BarType::Status)
+    },
+    _ => return (state, InputAction::Unhandled)
+  };
+
+  (state, InputAction::Rerender)
+}
+
+pub mod actions {
+  use super::*;
+
+  /// Shows or hides the strip
+  pub fn toggle(mut state: RcState) -> RcState {
+    let state_mut = Rc::make_mut(&mut state);
+    state_mut.histogram.visible = !state_mut.histogram.visible;
+
+    state
+  }
+
+  /// Shows the strip and starts picking a bucket, from the selected message's
+  /// or the latest
+  pub fn open(mut state: RcState) -> RcState {
+    let histogram = match histogram(&state) {
+      Some(histogram) => histogram,
+      None => return state_actions::internal(state, "no messages have timestamps")
+    };
+
+    let position = selected_position(&state, &histogram)
+      .unwrap_or(histogram.buckets.len() - 1);
+
+    let state_mut = Rc::make_mut(&mut state);
+    state_mut.histogram.visible = true;
+    state_mut.histogram.cursor = position;
+    state_mut.histogram.anchor = None;
+
+    bar::actions::set_active(state, BarType::Histogram)
+  }
+
+  pub fn move_cursor(mut state: RcState, amount: isize) -> RcState {
+    let len = histogram(&state).map_or(0, |h| h.buckets.len());
+
+    let state_mut = Rc::make_mut(&mut state);
+    let max = len.saturating_sub(1) as isize;
+    let cursor = (state_mut.histogram.cursor as isize + amount).max(0).min(max);
+    state_mut.histogram.cursor = cursor as usize;
+
+    state
+  }
+
+  pub fn next_spike(mut state: RcState, forward: bool) -> RcState {
+    let spike = histogram(&state).and_then(|h| h.next_spike(state.histogram.cursor, forward));
+
+    match spike {
+      Some(position) => {
+        Rc::make_mut(&mut state).histogram.cursor = position;
+        state
+      },
+      None => state_actions::internal(state, if forward {
+        "no more spikes to the right"
+      } else {
+        "no more spikes to the left"
+      })
+    }
+  }
+
+  /// Selects the first message in the highlighted bucket
+  pub fn jump_to_cursor(state: RcState) -> RcState {
+    let start = match histogram(&state) {
+      Some(histogram) => histogram.bucket_start(state.histogram.cursor),
+      None => return state
+    };
+
+    let index = state.filtered_entries.borrow().iter().position(|filtered| {
+      filtered.entry.upgrade()
+        .and_then(|e| e.message.timestamp)
+        .map_or(false, |timestamp| timestamp >= start)
+    });
+
+    match index {
+      Some(index) => log::actions::move_selection_to_index(state, index),
+      None => state
+    }
+  }
+
+  /// Marks the highlighted bucket as one end of a range to zoom into, or
+  /// clears the mark
+  pub fn toggle_anchor(mut state: RcState) -> RcState {
+    let state_mut = Rc::make_mut(&mut state);
+    state_mut.histogram.anchor = match state_mut.histogram.anchor {
+      Some(_) => None,
+      None => Some(state_mut.histogram.cursor)
+    };
+
+    state
+  }
+
+  /// Zooms into the marked range of buckets, or just the highlighted one,
+  /// and stops picking
+  pub fn zoom_to_cursor(mut state: RcState) -> RcState {
+    let window = match histogram(&state) {
+      Some(histogram) => {
+        let cursor = state.histogram.cursor.min(histogram.buckets.len() - 1);
+        let anchor = state.histogram.anchor.unwrap_or(cursor).min(histogram.buckets.len() - 1);
+        histogram.window(anchor, cursor)
+      },
+      None => return state
+    };
+
+    Rc::make_mut(&mut state).histogram.anchor = None;
+    let state = bar::actions::set_active(state, BarType::Status);
+    zoom::actions::zoom_to(state, window)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use spectral::prelude::*;
+
+  fn at(seconds: i64, level: LogLevel) -> (DateTime<Utc>, Option<LogLevel>) {
+    (Utc.timestamp_opt(1_600_000_000 + seconds, 0).unwrap(), Some(level))
+  }
+
+  #[test]
+  fn test_histogram() {
+    let messages = vec![
+      at(0, LogLevel::Info),
+      at(1, LogLevel::Info),
+      at(25, LogLevel::Error),
+      at(26, LogLevel::Info),
+      at(59, LogLevel::Info)
+    ];
+
+    let histogram = Histogram::new(&messages, 10).unwrap();
+    assert_that!(histogram.seconds).is_equal_to(10);
+
+    let counts: Vec<usize> = histogram.buckets.iter().map(|b| b.count).collect();
+    assert_that!(counts).is_equal_to(vec![2, 0, 2, 0, 0, 1]);
+    assert_that!(histogram.buckets[2].level()).is_equal_to(Some(LogLevel::Error));
+    assert_that!(histogram.position(messages[2].0)).is_equal_to(2);
+
+    // a range of buckets covers everything up to the start of the next
+    let window = histogram.window(2, 0);
+    assert_that!(window.start).is_equal_to(messages[0].0);
+    assert_that!(window.end).is_less_than(messages[3].0 + chrono::Duration::seconds(4));
+    assert_that!(window.end).is_greater_than(messages[3].0 + chrono::Duration::seconds(3));
+
+    assert_that!(Histogram::new(&[], 10)).is_none();
+  }
+
+  #[test]
+  fn test_cache() {
+    use std::sync::Arc;
+    use structopt::StructOpt;
+
+    use crate::config::Config;
+    use crate::parser::MessageKind;
+    use crate::renderer::types::MessageEntry;
+
+    let config = Arc::new(Config::from_iter_safe(vec![""]).unwrap());
+    let mut state = Rc::new(RenderState::new(config));
+    Rc::make_mut(&mut state).width = 10;
+
+    let add = |state: RcState, seconds: i64| {
+      let mut entry = MessageEntry::internal("request");
+      entry.message.kind = MessageKind::Json;
+      entry.message.level = Some(LogLevel::Info);
+      entry.message.timestamp = Some(at(seconds, LogLevel::Info).0);
+      state_actions::add_entry(state, entry)
+    };
+
+    state = add(state, 0);
+    state = add(state, 5);
+    let first = histogram(&state).unwrap();
+    assert_that!(Rc::ptr_eq(&first, &histogram(&state).unwrap())).is_true();
+
+    // new entries are counted without rescanning, and refiltering starts over
+    state = add(state, 9);
+    assert_that!(histogram(&state).unwrap().buckets.iter().map(|b| b.count).sum::<usize>())
+      .is_equal_to(3);
+
+    state = state_actions::set_min_level(state, Some(LogLevel::Error));
+    assert_that!(histogram(&state)).is_none();
+  }
+
+  #[test]
+  fn test_next_spike() {
+    let messages: Vec<_> = [0, 10, 10, 10, 10, 10, 20, 40, 40, 40, 40, 40]
+      .iter()
+      .map(|s| at(*s, LogLevel::Info))
+      .collect();
+
+    let histogram = Histogram::new(&messages, 5).unwrap();
+    assert_that!(histogram.next_spike(0, true)).is_equal_to(Some(1));
+    assert_that!(histogram.next_spike(1, true)).is_equal_to(Some(4));
+    assert_that!(histogram.next_spike(4, false)).is_equal_to(Some(1));
+    assert_that!(histogram.next_spike(4, true)).is_none();
+  }
+}
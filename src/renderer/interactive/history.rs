@@ -0,0 +1,134 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+use crate::history::{History, SavedFilter};
+
+use super::state::RcState;
+use super::state::actions as state_actions;
+use super::text::{self, TextBuffer};
+
+/// Moves through a history list of `len` items while browsing in a prompt
+///
+/// `current` counts back from the newest item, with None being the user's own
+/// (empty) input. Returns the new position.
+pub fn step(len: usize, current: Option<usize>, older: bool) -> Option<usize> {
+  match (current, older) {
+    (None, true) if len > 0 => Some(0),
+    (None, _) => None,
+    (Some(i), true) => Some((i + 1).min(len - 1)),
+    (Some(0), false) => None,
+    (Some(i), false) => Some(i - 1)
+  }
+}
+
+/// The item at a browsing position, as returned by `step()`
+pub fn item(list: &[SavedFilter], position: Option<usize>) -> Option<&SavedFilter> {
+  position.and_then(|i| list.len().checked_sub(i + 1)).map(|i| &list[i])
+}
+
+/// Replaces a prompt's input, moving the cursor to the end
+pub fn set_input(buffer: TextBuffer, input: &str) -> TextBuffer {
+  input.chars().fold(text::actions::clear_input(buffer), text::actions::push_input)
+}
+
+pub mod actions {
+  use super::*;
+
+  /// Loads history saved by previous sessions, and applies the filter preset
+  /// from `--filter-preset`, if any
+  pub fn load(mut state: RcState) -> RcState {
+    match History::load() {
+      Ok(history) => *state.history.borrow_mut() = history,
+      Err(e) => state = state_actions::internal(state, &e.to_string())
+    }
+
+    match state.config.filter_preset.clone() {
+      Some(name) => apply_preset(state, &name),
+      None => state
+    }
+  }
+
+  pub fn save(state: RcState) -> RcState {
+    let result = state.history.borrow().save();
+    match result {
+      Ok(()) => state,
+      Err(e) => state_actions::internal(state, &e.to_string())
+    }
+  }
+
+  pub fn remember_filter(state: RcState, filter: SavedFilter) -> RcState {
+    state.history.borrow_mut().remember_filter(filter);
+
+    save(state)
+  }
+
+  pub fn remember_search(state: RcState, search: SavedFilter) -> RcState {
+    state.history.borrow_mut().remember_search(search);
+
+    save(state)
+  }
+
+  /// Saves the last filter under `name`
+  pub fn save_preset(state: RcState, name: &str) -> RcState {
+    let filter = state.filters.borrow().last().map(|f| SavedFilter::of(f.as_ref()));
+    let filter = match filter {
+      Some(filter) => filter,
+      None => return state_actions::internal(state, "no filter to save, add one first")
+    };
+
+    let text = format!("saved filter {} as preset {}", filter.query, name);
+    state.history.borrow_mut().presets.insert(name.to_string(), filter);
+
+    let state = save(state);
+    state_actions::internal(state, &text)
+  }
+
+  /// Adds the filter saved as `name`
+  pub fn apply_preset(state: RcState, name: &str) -> RcState {
+    let filter = state.history.borrow().preset(name).and_then(|preset| preset.parse());
+    match filter {
+      Ok(filter) => state_actions::add_filter(state, filter),
+      Err(e) => state_actions::internal(state, &e.to_string())
+    }
+  }
+
+  /// Lists saved presets
+  pub fn list_presets(state: RcState) -> RcState {
+    let names: Vec<String> = state.history.borrow().presets.keys().cloned().collect();
+    if names.is_empty() {
+      state_actions::internal(state, "no filter presets, save one with :save <name>")
+    } else {
+      state_actions::internal(state, &format!("filter presets: {}", names.join(", ")))
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use spectral::prelude::*;
+
+  use crate::filter::FilterMode;
+
+  #[test]
+  fn test_step() {
+    let list = vec![
+      SavedFilter::new(FilterMode::Regex, "old", false),
+      SavedFilter::new(FilterMode::Regex, "new", false)
+    ];
+
+    let up = step(list.len(), None, true);
+    assert_that!(item(&list, up).map(|f| f.query.as_str())).is_equal_to(Some("new"));
+
+    let up = step(list.len(), up, true);
+    assert_that!(item(&list, up).map(|f| f.query.as_str())).is_equal_to(Some("old"));
+
+    // the oldest item stays put
+    let up = step(list.len(), up, true);
+    assert_that!(item(&list, up).map(|f| f.query.as_str())).is_equal_to(Some("old"));
+
+    let down = step(list.len(), step(list.len(), up, false), false);
+    assert_that!(down).is_none();
+    assert_that!(step(0, None, true)).is_none();
+  }
+}
@@ -0,0 +1,479 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::str::FromStr;
+
+use crossterm::KeyEvent;
+use serde::Deserialize;
+use serde::de::{self, Deserializer};
+use simple_error::{SimpleError, SimpleResult};
+
+/// A key that may be bound to an action
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Key {
+  Char(char),
+  Ctrl(char),
+  Alt(char),
+  Up,
+  Down,
+  Left,
+  Right,
+  Home,
+  End,
+  PageUp,
+  PageDown,
+  Enter,
+  Tab,
+  Esc,
+  F(u8)
+}
+
+impl Key {
+  pub fn from_event(event: &KeyEvent) -> Option<Key> {
+    Some(match event {
+      KeyEvent::Char('\n') => Key::Enter,
+      KeyEvent::Char('\t') => Key::Tab,
+      KeyEvent::Char(c) => Key::Char(*c),
+      KeyEvent::Ctrl(c) => Key::Ctrl(*c),
+      KeyEvent::Alt(c) => Key::Alt(*c),
+      KeyEvent::Up => Key::Up,
+      KeyEvent::Down => Key::Down,
+      KeyEvent::Left => Key::Left,
+      KeyEvent::Right => Key::Right,
+      KeyEvent::Home => Key::Home,
+      KeyEvent::End => Key::End,
+      KeyEvent::PageUp => Key::PageUp,
+      KeyEvent::PageDown => Key::PageDown,
+      KeyEvent::Esc => Key::Esc,
+      KeyEvent::F(n) => Key::F(*n),
+      _ => return None
+    })
+  }
+}
+
+impl FromStr for Key {
+  type Err = SimpleError;
+
+  /// Parses a key name, e.g. `j`, `shift-g`, `ctrl-d`, `alt-v`, or `pgdn`
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let mut chars = s.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+      return Ok(Key::Char(c));
+    }
+
+    let lower = s.to_lowercase();
+    let (modifier, name) = match lower.find('-') {
+      Some(i) if i > 0 && i + 1 < s.len() => (Some(&lower[..i]), &s[i + 1..]),
+      _ => (None, s)
+    };
+
+    let mut chars = name.chars();
+    if let (Some(modifier), Some(c), None) = (modifier, chars.next(), chars.next()) {
+      match modifier {
+        "ctrl" | "c" => return Ok(Key::Ctrl(c.to_ascii_lowercase())),
+        "alt" | "meta" | "m" | "a" => return Ok(Key::Alt(c)),
+        "shift" | "s" => return Ok(Key::Char(c.to_ascii_uppercase())),
+        _ => ()
+      }
+    }
+
+    Ok(match lower.as_str() {
+      "up" => Key::Up,
+      "down" => Key::Down,
+      "left" => Key::Left,
+      "right" => Key::Right,
+      "home" => Key::Home,
+      "end" => Key::End,
+      "pgup" | "pageup" | "page-up" => Key::PageUp,
+      "pgdn" | "pagedown" | "page-down" => Key::PageDown,
+      "enter" | "return" => Key::Enter,
+      "tab" => Key::Tab,
+      "esc" | "escape" => Key::Esc,
+      "space" => Key::Char(' '),
+      f if f.starts_with('f') => match f[1..].parse() {
+        Ok(n) if (1..=12).contains(&n) => Key::F(n),
+        _ => bail!("invalid key: {}", s)
+      },
+      _ => bail!("invalid key: {}", s)
+    })
+  }
+}
+
+impl fmt::Display for Key {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    match self {
+      Key::Char(' ') => write!(f, "space"),
+      Key::Char(c) if c.is_ascii_uppercase() => write!(f, "S-{}", c.to_ascii_lowercase()),
+      Key::Char(c) => write!(f, "{}", c),
+      Key::Ctrl(c) => write!(f, "C-{}", c),
+      Key::Alt(c) => write!(f, "M-{}", c),
+      Key::Up => write!(f, "up"),
+      Key::Down => write!(f, "down"),
+      Key::Left => write!(f, "left"),
+      Key::Right => write!(f, "right"),
+      Key::Home => write!(f, "home"),
+      Key::End => write!(f, "end"),
+      Key::PageUp => write!(f, "pgup"),
+      Key::PageDown => write!(f, "pgdn"),
+      Key::Enter => write!(f, "enter"),
+      Key::Tab => write!(f, "tab"),
+      Key::Esc => write!(f, "esc"),
+      Key::F(n) => write!(f, "f{}", n)
+    }
+  }
+}
+
+/// Something a key may do in the log view
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Action {
+  Up,
+  Down,
+  PageUp,
+  PageDown,
+  Top,
+  Bottom,
+  Follow,
+  Pause,
+  Replay,
+  GoTo,
+  Histogram,
+  Zoom,
+  ZoomOut,
+  Bookmark,
+  PrevBookmark,
+  NextBookmark,
+  NextError,
+  PrevError,
+  Bookmarks,
+  Back,
+  Filter,
+  PopFilter,
+  ClearFilters,
+  Preset,
+  AllLevels,
+  MinLevelDebug,
+  MinLevelInfo,
+  MinLevelWarning,
+  MinLevelError,
+  MinLevelFatal,
+  MoreContext,
+  LessContext,
+  Search,
+  NextMatch,
+  PrevMatch,
+  Sources,
+  Facets,
+  FollowId,
+  Templates,
+  Details,
+  Mark,
+  RangeSummary,
+  Copy,
+  CopyRaw,
+  CopyJson,
+  CopyScreen,
+  CopyTable,
+  CopyCode,
+  Command,
+  ToggleTime,
+  ToggleExpand,
+  ToggleRecordNumbers,
+  ToggleRepeats,
+  ToggleLines,
+  ToggleAllLines,
+  ToggleHistogram,
+  ToggleSummary,
+  CycleTheme,
+  ToggleDensity,
+  SplitSources,
+  SplitFilter,
+  NextPane,
+  MergePanes,
+  Help,
+  Quit
+}
+
+/// An action's name in the config file, help category, description, and
+/// default keys
+pub struct ActionInfo {
+  pub action: Action,
+  pub name: &'static str,
+  pub category: &'static str,
+  pub description: &'static str,
+  keys: &'static [&'static str]
+}
+
+macro_rules! actions {
+  ($($action:ident, $name:expr, $category:expr, $description:expr, [$($key:expr),*];)*) => {
+    &[$(ActionInfo {
+      action: Action::$action,
+      name: $name,
+      category: $category,
+      description: $description,
+      keys: &[$($key),*]
+    }),*]
+  }
+}
+
+/// Every action, in the order shown in help
+pub static ACTIONS: &[ActionInfo] = actions! {
+  Up, "up", "navigation", "select the previous message", ["up"];
+  Down, "down", "navigation", "select the next message", ["down"];
+  PageUp, "page-up", "navigation", "scroll up one screen", ["pgup"];
+  PageDown, "page-down", "navigation", "scroll down one screen", ["pgdn"];
+  Top, "top", "navigation", "go to the first message", ["home"];
+  Bottom, "bottom", "navigation", "go to the latest message", ["end"];
+  Follow, "follow", "navigation", "pause following new messages, or resume", ["F"];
+  Pause, "pause", "navigation", "freeze the display while buffering new messages, or jump to live", ["space"];
+  Replay, "replay", "navigation", "while paused: resume by replaying buffered messages quickly", ["R"];
+  GoTo, "go-to", "navigation", "go to the message nearest a time", ["g"];
+  Histogram, "histogram", "navigation", "pick a moment from the histogram", ["H"];
+  Zoom, "zoom", "navigation", "show only the time spanned by the marked range, zooming further if repeated", ["w"];
+  ZoomOut, "zoom-out", "navigation", "zoom back out to the previous time window", ["W"];
+  Bookmark, "bookmark", "navigation", "bookmark the selected message", ["b"];
+  PrevBookmark, "prev-bookmark", "navigation", "go to the previous bookmark", ["["];
+  NextBookmark, "next-bookmark", "navigation", "go to the next bookmark", ["]"];
+  NextError, "next-error", "navigation", "go to the next error or fatal message", ["e"];
+  PrevError, "prev-error", "navigation", "go to the previous error or fatal message", ["E"];
+  Bookmarks, "bookmarks", "navigation", "list bookmarks", ["B"];
+  Back, "back", "navigation", "clear the mark, then the selection, then quit", ["esc"];
+  Filter, "filter", "filtering and search", "add a filter", ["f", "|"];
+  PopFilter, "pop-filter", "filtering and search", "remove the last filter", ["p"];
+  ClearFilters, "clear-filters", "filtering and search", "remove all filters", ["P"];
+  Preset, "preset", "filtering and search", "add a filter saved with :save <name>", ["'"];
+  AllLevels, "all-levels", "filtering and search", "show all levels", ["0"];
+  MinLevelDebug, "min-level-debug", "filtering and search", "show debug and above", ["1"];
+  MinLevelInfo, "min-level-info", "filtering and search", "show info and above", ["2"];
+  MinLevelWarning, "min-level-warning", "filtering and search", "show warning and above", ["3"];
+  MinLevelError, "min-level-error", "filtering and search", "show error and above", ["4"];
+  MinLevelFatal, "min-level-fatal", "filtering and search", "show only fatal", ["5"];
+  MoreContext, "more-context", "filtering and search", "show one more message of context around matches", ["+"];
+  LessContext, "less-context", "filtering and search", "show one less message of context around matches", ["-"];
+  Search, "search", "filtering and search", "search", ["/", "ctrl-f"];
+  NextMatch, "next-match", "filtering and search", "go to the next match", ["n"];
+  PrevMatch, "prev-match", "filtering and search", "go to the previous match", ["N"];
+  Sources, "sources", "filtering and search", "list sources to hide, show, or solo", ["a"];
+  Facets, "facets", "filtering and search", "list a field's values with counts, to include or exclude them", ["u"];
+  Templates, "templates", "filtering and search", "group messages in view by template, ignoring IDs and numbers, most common first", ["U"];
+  FollowId, "follow-id", "filtering and search", "show only messages sharing the selected message's trace or request id", ["I"];
+  Details, "details", "selection and copying", "show details of the selected message", ["enter"];
+  Mark, "mark", "selection and copying", "mark one end of a range", ["v"];
+  RangeSummary, "range-summary", "selection and copying", "summarize the marked range: duration, counts, top messages, and rate", ["V"];
+  Copy, "copy", "selection and copying", "copy the selected messages as plain text", ["c"];
+  CopyRaw, "copy-raw", "selection and copying", "copy the selected messages' raw lines", ["y"];
+  CopyJson, "copy-json", "selection and copying", "copy the selected messages as json", ["j"];
+  CopyScreen, "copy-screen", "selection and copying", "copy the screen as plain text", ["C"];
+  CopyTable, "copy-table", "selection and copying", "copy as a markdown table", ["m"];
+  CopyCode, "copy-code", "selection and copying", "copy as a markdown code block", ["M"];
+  Command, "command", "selection and copying", "run a command, e.g. :write <path>", [":"];
+  ToggleTime, "toggle-time", "display", "cycle between absolute and relative timestamps", ["t"];
+  ToggleExpand, "toggle-expand", "display", "toggle showing fields on their own lines", ["x"];
+  ToggleRecordNumbers, "toggle-record-numbers", "display", "toggle record numbers, which don't change when filtering", ["#"];
+  ToggleRepeats, "toggle-repeats", "display", "toggle collapsing repeated messages", ["r"];
+  ToggleLines, "toggle-lines", "display", "expand or collapse the selected multi-line message", ["z"];
+  ToggleAllLines, "toggle-all-lines", "display", "expand or collapse all multi-line messages", ["Z"];
+  ToggleHistogram, "toggle-histogram", "display", "toggle the message volume histogram", ["h"];
+  ToggleSummary, "toggle-summary", "display", "toggle the source and level summary line", ["i"];
+  CycleTheme, "cycle-theme", "display", "switch to the next color theme", ["T"];
+  ToggleDensity, "toggle-density", "display", "switch between compact and detailed columns", ["D"];
+  SplitSources, "split-sources", "panes", "split the view into a pane per source", ["s"];
+  SplitFilter, "split-filter", "panes", "move the last filter into its own pane", ["S"];
+  NextPane, "next-pane", "panes", "focus the next pane", ["tab"];
+  MergePanes, "merge-panes", "panes", "merge panes back into one view", ["o"];
+  Help, "help", "other", "show this help", ["?"];
+  Quit, "quit", "other", "quit", ["q", "ctrl-q"];
+};
+
+/// Bindings applied over the defaults by `preset: vim`
+static VIM: &[(&str, &[&str])] = &[
+  ("down", &["j", "down"]),
+  ("up", &["k", "up"]),
+  ("page-down", &["ctrl-d", "ctrl-f", "pgdn"]),
+  ("page-up", &["ctrl-u", "ctrl-b", "pgup"]),
+  ("bottom", &["G", "end"]),
+  ("search", &["/"]),
+  ("copy-json", &["J"])
+];
+
+/// Bindings applied over the defaults by `preset: emacs`
+static EMACS: &[(&str, &[&str])] = &[
+  ("down", &["ctrl-n", "down"]),
+  ("up", &["ctrl-p", "up"]),
+  ("page-down", &["ctrl-v", "pgdn"]),
+  ("page-up", &["alt-v", "pgup"]),
+  ("top", &["alt-<", "home"]),
+  ("bottom", &["alt->", "end"]),
+  ("search", &["ctrl-s", "/"]),
+  ("back", &["esc", "ctrl-g"])
+];
+
+fn action_info(name: &str) -> SimpleResult<&'static ActionInfo> {
+  match ACTIONS.iter().find(|info| info.name == name) {
+    Some(info) => Ok(info),
+    None => bail!("unknown action: {}", name)
+  }
+}
+
+fn parse_keys<S: AsRef<str>>(names: &[S]) -> SimpleResult<Vec<Key>> {
+  names.iter().map(|name| name.as_ref().parse()).collect()
+}
+
+/// The keys bound to each action in the log view
+///
+/// Configured with an optional preset, `vim` or `emacs`, and lists of keys for
+/// any actions to rebind, e.g.:
+///
+/// ```yaml
+/// keys:
+///   preset: vim
+///   copy: [c, ctrl-y]
+///   toggle-repeats: []
+/// ```
+///
+/// Each list replaces the action's default keys. A key may only be bound to
+/// one action.
+#[derive(Debug, Clone)]
+pub struct Keymap {
+  bindings: BTreeMap<Action, Vec<Key>>,
+  actions: HashMap<Key, Action>
+}
+
+impl Keymap {
+  /// Builds a keymap from the defaults, a preset, and overrides, failing if
+  /// any key would be bound to two actions
+  pub fn new(preset: Option<&str>, overrides: &[(String, Vec<String>)]) -> SimpleResult<Keymap> {
+    let mut bindings = BTreeMap::new();
+    for info in ACTIONS {
+      bindings.insert(info.action, parse_keys(info.keys)?);
+    }
+
+    let preset: &[(&str, &[&str])] = match preset {
+      None | Some("default") => &[],
+      Some("vim") => VIM,
+      Some("emacs") => EMACS,
+      Some(other) => bail!("unknown key preset: {}, expected default, vim, or emacs", other)
+    };
+
+    for (name, keys) in preset {
+      bindings.insert(action_info(name)?.action, parse_keys(keys)?);
+    }
+
+    for (name, keys) in overrides {
+      bindings.insert(action_info(name)?.action, parse_keys(keys)?);
+    }
+
+    let mut actions = HashMap::new();
+    for (action, keys) in &bindings {
+      for key in keys {
+        if let Some(other) = actions.insert(*key, *action) {
+          if other != *action {
+            bail!(
+              "key {} is bound to both {} and {}, rebind one of them",
+              key, name_of(other), name_of(*action)
+            );
+          }
+        }
+      }
+    }
+
+    Ok(Keymap { bindings, actions })
+  }
+
+  /// The action bound to a key, if any
+  pub fn action(&self, event: &KeyEvent) -> Option<Action> {
+    self.actions.get(&Key::from_event(event)?).cloned()
+  }
+
+  /// The keys bound to an action, in preference order
+  pub fn keys(&self, action: Action) -> &[Key] {
+    self.bindings.get(&action).map(Vec::as_slice).unwrap_or(&[])
+  }
+
+  /// The name of an action's first key, if it's bound at all
+  pub fn key_name(&self, action: Action) -> Option<String> {
+    self.keys(action).first().map(Key::to_string)
+  }
+}
+
+fn name_of(action: Action) -> &'static str {
+  ACTIONS.iter().find(|info| info.action == action).map_or("?", |info| info.name)
+}
+
+impl Default for Keymap {
+  fn default() -> Self {
+    Keymap::new(None, &[]).expect("default keymap has conflicts")
+  }
+}
+
+impl<'de> Deserialize<'de> for Keymap {
+  fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+  where
+    D: Deserializer<'de>
+  {
+    #[derive(Deserialize)]
+    struct RawKeymap {
+      preset: Option<String>,
+
+      #[serde(flatten)]
+      bindings: HashMap<String, Vec<String>>
+    }
+
+    let raw = RawKeymap::deserialize(deserializer)?;
+
+    // sorted so errors are stable
+    let mut overrides: Vec<(String, Vec<String>)> = raw.bindings.into_iter().collect();
+    overrides.sort();
+
+    Keymap::new(raw.preset.as_deref(), &overrides)
+      .map_err(de::Error::custom)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use spectral::prelude::*;
+
+  #[test]
+  fn test_parse_key() {
+    assert_that!("j".parse::<Key>()).is_equal_to(Ok(Key::Char('j')));
+    assert_that!("shift-g".parse::<Key>()).is_equal_to(Ok(Key::Char('G')));
+    assert_that!("C-d".parse::<Key>()).is_equal_to(Ok(Key::Ctrl('d')));
+    assert_that!("alt->".parse::<Key>()).is_equal_to(Ok(Key::Alt('>')));
+    assert_that!("-".parse::<Key>()).is_equal_to(Ok(Key::Char('-')));
+    assert_that!("PgDn".parse::<Key>()).is_equal_to(Ok(Key::PageDown));
+    assert_that!("f5".parse::<Key>()).is_equal_to(Ok(Key::F(5)));
+    assert_that!("hyper-x".parse::<Key>()).is_err();
+
+    assert_that!(Key::Char('G').to_string()).is_equal_to("S-g".to_string());
+    assert_that!(Key::Ctrl('d').to_string()).is_equal_to("C-d".to_string());
+  }
+
+  #[test]
+  fn test_keymap() {
+    let keymap = Keymap::default();
+    assert_that!(keymap.action(&KeyEvent::Char('j'))).is_equal_to(Some(Action::CopyJson));
+    assert_that!(keymap.action(&KeyEvent::Char('\n'))).is_equal_to(Some(Action::Details));
+
+    let vim = Keymap::new(Some("vim"), &[]).unwrap();
+    assert_that!(vim.action(&KeyEvent::Char('j'))).is_equal_to(Some(Action::Down));
+    assert_that!(vim.action(&KeyEvent::Ctrl('u'))).is_equal_to(Some(Action::PageUp));
+    assert_that!(vim.keys(Action::CopyJson).to_vec()).is_equal_to(vec![Key::Char('J')]);
+
+    assert_that!(Keymap::new(Some("emacs"), &[])).is_ok();
+
+    let unbound = Keymap::new(None, &[("copy".to_string(), vec![])]).unwrap();
+    assert_that!(unbound.action(&KeyEvent::Char('c'))).is_equal_to(None);
+    assert_that!(unbound.key_name(Action::Copy)).is_equal_to(None);
+
+    let conflict = Keymap::new(None, &[("down".to_string(), vec!["j".to_string()])]);
+    assert_that!(conflict.map(|_| ()).map_err(|e| e.to_string())).is_equal_to(Err(
+      "key j is bound to both down and copy-json, rebind one of them".to_string()
+    ));
+
+    assert_that!(Keymap::new(None, &[("fly".to_string(), vec![])])).is_err();
+    assert_that!(Keymap::new(Some("nano"), &[])).is_err();
+  }
+}
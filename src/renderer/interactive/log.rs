@@ -1,17 +1,74 @@
 // (C) Copyright 2019 Hewlett Packard Enterprise Development LP
 
+use std::borrow::Cow;
 use std::cell::RefCell;
 use std::cmp::{min, max};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
 use std::error::Error;
+use std::ops::RangeInclusive;
 use std::rc::Rc;
 
+use ansi_term::Style;
 use crossterm::{Terminal, TerminalCursor, ClearType};
 
+use crate::classifier::ChunkKind;
+use crate::parser::{LogLevel, Message};
 use crate::renderer::types::*;
 use crate::renderer::common::*;
+use crate::renderer::highlight::highlight_matches;
+use crate::renderer::plain::plain_render;
 use crate::style::StyleProfile;
-use crate::renderer::interactive::state::{RenderState, RcState};
+use crate::timezone;
+use crate::renderer::interactive::state::{FilteredEntry, RenderState, RcState};
+use crate::renderer::interactive::state::actions as state_actions;
+
+/// The number of entries beyond those on screen whose layout is kept, so
+/// scrolling a little way doesn't lay them out again
+static LAYOUT_MARGIN: usize = 100;
+
+/// The width of the record number gutter, including its trailing space, or 0
+/// if it's hidden
+fn gutter_width(state: &RenderState) -> usize {
+  if state.record_numbers {
+    state.entries.borrow().len().to_string().len() + 1
+  } else {
+    0
+  }
+}
+
+/// The width available to messages, beside the gutter
+fn content_width(state: &RenderState) -> usize {
+  (state.width as usize).saturating_sub(gutter_width(state)).max(1)
+}
+
+/// Prefixes a message's lines with its record number, numbered from 1 in the
+/// order messages arrived
+fn with_gutter(lines: Vec<String>, number: usize, width: usize, style: Style) -> Vec<String> {
+  lines.into_iter().enumerate().map(|(i, line)| {
+    let gutter = if i == 0 {
+      format!("{:>width$} ", number, width = width - 1)
+    } else {
+      " ".repeat(width)
+    };
+
+    format!("{}{}", style.paint(gutter), line)
+  }).collect()
+}
+
+/// A message as plain text, prefixed with its record number if the gutter is
+/// shown, e.g. for copying or `:write`
+pub fn plain_lines(state: &RenderState, index: usize, entry: &MessageEntry) -> Vec<String> {
+  let lines = plain_render(entry);
+  if !state.record_numbers {
+    return lines;
+  }
+
+  lines.into_iter().enumerate().map(|(i, line)| if i == 0 {
+    format!("#{} {}", index + 1, line)
+  } else {
+    line
+  }).collect()
+}
 
 /// renders a message without displaying and returns its height
 /// this is mildly expensive and should be called sparingly
@@ -19,16 +76,60 @@ fn measure_entry(state: RcState, abs_index: usize) -> usize {
   let entry = &state.entries.borrow()[abs_index];
   styled_render(
     &entry,
-    &state.config.style.normal,
-    Some(state.width as usize)
+    &state.style.normal,
+    Some(content_width(&state))
   ).len()
 }
 
+/// Returns the filtered entry at `rel_index`, with its repeat count, truncated
+/// or expanded fields, and a delta column if enabled
+fn display_entry(
+  state: &RenderState, filtered_entries: &[FilteredEntry], rel_index: usize
+) -> Rc<MessageEntry> {
+  let filtered = &filtered_entries[rel_index];
+  let mut entry = filtered.entry.upgrade().unwrap();
+
+  if state.collapse_lines != state.toggled_lines.contains(&filtered.index) {
+    if let Some(collapsed) = collapse_lines(&entry) {
+      entry = Rc::new(collapsed);
+    }
+  }
+
+  if filtered.repeats > 1 {
+    entry = Rc::new(with_repeats(&entry, filtered.repeats));
+  }
+
+  // the selected message is shown in full
+  let selected = state.log.selection.map(|s| s.rel_index) == Some(rel_index);
+  if state.field_selection.truncates() && !selected {
+    entry = Rc::new(truncate_fields(&state.field_selection, (*entry).clone()));
+  }
+
+  if state.expand_fields {
+    entry = Rc::new(expand_fields(&entry));
+  }
+
+  if state.config.delta {
+    let previous = rel_index.checked_sub(1)
+      .and_then(|i| filtered_entries[i].entry.upgrade())
+      .and_then(|e| message_timestamp(&e.message));
+
+    entry = Rc::new(with_delta(&entry, previous));
+  }
+
+  entry
+}
+
 fn profile_for_message<'a>(
-  state: &'a RenderState, message: &MessageEntry, selected: bool
-) -> &'a  StyleProfile {
+  state: &'a RenderState, message: &MessageEntry, index: usize, selected: bool
+) -> Cow<'a, StyleProfile> {
   if selected {
-    return &state.config.style.selected;
+    return Cow::Borrowed(&state.style.selected);
+  }
+
+  // highlight the rest of a marked range
+  if state.log.mark.is_some() && selected_range(state).map_or(false, |r| r.contains(&index)) {
+    return Cow::Borrowed(&state.style.highlighted);
   }
 
   // TODO: also highlight messages during find
@@ -36,11 +137,70 @@ fn profile_for_message<'a>(
   // if the user is writing a filter, highlight matching messages
   if let Some(filter) = &state.highlight_filter {
     if filter.filter(&message.message) {
-      return &state.config.style.highlighted;
+      return Cow::Borrowed(&state.style.highlighted);
     }
   }
 
-  &state.config.style.normal
+  // context around filter matches is dimmed
+  let context = state.filtered_entries.borrow().get(index).map_or(false, |f| f.context);
+  if context {
+    return Cow::Owned(state.style.profile_for(&message.message).dimmed());
+  }
+
+  state.style.profile_for(&message.message)
+}
+
+/// Renders an entry for display, highlighting search matches within it
+fn render_entry(
+  state: &RenderState, entry: &MessageEntry, index: usize, selected: bool
+) -> Vec<String> {
+  let profile = profile_for_message(state, entry, index, selected);
+  let lines = styled_render(entry, &profile, Some(content_width(state)));
+
+  let lines = match state.search.pattern() {
+    Some(pattern) => lines.iter()
+      .map(|line| highlight_matches(line, pattern, Style::new().reverse()))
+      .collect(),
+    None => lines
+  };
+
+  let record = state.filtered_entries.borrow().get(index).map(|f| f.index);
+  match record {
+    Some(record) if state.record_numbers => with_gutter(
+      lines, record + 1, gutter_width(state), *profile.get_style(&ChunkKind::Context)
+    ),
+    _ => lines
+  }
+}
+
+/// What a displayed entry's lines depend on, besides state that only changes
+/// on input
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct LayoutKey {
+  /// the index into `entries`
+  index: usize,
+
+  repeats: usize,
+  context: bool,
+  selected: bool,
+  marked: bool,
+
+  /// the previous filtered entry, for the delta column
+  previous: Option<usize>
+}
+
+/// The lines of entries displayed recently, so a redraw only lays out entries
+/// that are new or have changed, e.g. as messages arrive or once a resize
+/// settles
+///
+/// Anything else that changes how entries look, like the theme or columns,
+/// only changes on input, which clears the cache (see `invalidate_layout`).
+#[derive(Debug, Default)]
+struct LayoutCache {
+  /// The terminal width the lines were laid out for
+  width: u16,
+
+  lines: HashMap<LayoutKey, Rc<Vec<String>>>
 }
 
 #[derive(Debug, Clone, Copy)]
@@ -84,7 +244,24 @@ pub struct LogState {
   /// bottom
   anchors: Rc<RefCell<BTreeMap<usize, Anchor>>>,
 
+  layout: Rc<RefCell<LayoutCache>>,
+
   pub selection: Option<Selection>,
+
+  /// An entry marked as the other end of a range from the selection, as an
+  /// index into `entries` so it survives filter changes
+  pub mark: Option<usize>,
+
+  /// The number of entries when following was paused by selecting a message,
+  /// to count new entries since; only meaningful while there's a selection
+  pub paused_at: usize,
+
+  /// The first screen row used by the log
+  pub top: u16,
+
+  /// The number of rows used by the log if it's one of several panes,
+  /// otherwise it fills the screen above the bar
+  pub rows: Option<u16>
 }
 
 impl LogState {
@@ -93,11 +270,150 @@ impl LogState {
       range_min: 0,
       range_max: 0,
       anchors: Rc::new(RefCell::new(BTreeMap::new())),
-      selection: None
+      layout: Rc::new(RefCell::new(LayoutCache::default())),
+      selection: None,
+      mark: None,
+      paused_at: 0,
+      top: 0,
+      rows: None
     }
   }
 }
 
+/// Forgets how entries were laid out in every pane, e.g. after input that may
+/// change how they look
+pub fn invalidate_layout(state: &RenderState) {
+  state.log.layout.borrow_mut().lines.clear();
+  for pane in &state.panes.panes {
+    pane.log.layout.borrow_mut().lines.clear();
+  }
+}
+
+/// The lines of the filtered entry at `rel_index`, reusing its last layout if
+/// nothing it depends on has changed
+fn entry_lines(
+  state: &RenderState, filtered_entries: &[FilteredEntry], rel_index: usize, selected: bool
+) -> Rc<Vec<String>> {
+  let filtered = &filtered_entries[rel_index];
+  let key = LayoutKey {
+    index: filtered.index,
+    repeats: filtered.repeats,
+    context: filtered.context,
+    selected,
+    marked: state.log.mark.is_some()
+      && selected_range(state).map_or(false, |r| r.contains(&rel_index)),
+    previous: match state.config.delta {
+      true => rel_index.checked_sub(1).map(|i| filtered_entries[i].index),
+      false => None
+    }
+  };
+
+  // relative timestamps change as time passes
+  let cached = timezone::relative_time().is_none();
+  if cached {
+    let mut cache = state.log.layout.borrow_mut();
+    if cache.width != state.width {
+      cache.width = state.width;
+      cache.lines.clear();
+    }
+
+    if let Some(lines) = cache.lines.get(&key) {
+      return Rc::clone(lines);
+    }
+  }
+
+  let entry = display_entry(state, filtered_entries, rel_index);
+  let lines = Rc::new(render_entry(state, &entry, rel_index, selected));
+  if cached {
+    state.log.layout.borrow_mut().lines.insert(key, Rc::clone(&lines));
+  }
+
+  lines
+}
+
+/// Forgets the layout of entries more than `LAYOUT_MARGIN` away from those on
+/// screen
+fn trim_layout(state: &RenderState) {
+  let filtered_entries = state.filtered_entries.borrow();
+  let last = match filtered_entries.len().checked_sub(1) {
+    Some(last) => last,
+    None => return state.log.layout.borrow_mut().lines.clear()
+  };
+
+  let low = filtered_entries[state.log.range_min.saturating_sub(LAYOUT_MARGIN).min(last)].index;
+  let high = filtered_entries[(state.log.range_max + LAYOUT_MARGIN).min(last)].index;
+  state.log.layout.borrow_mut().lines.retain(|key, _| key.index >= low && key.index <= high);
+}
+
+/// The log's height, including a row below it for the bar (or the next pane)
+fn log_height(state: &RenderState) -> u16 {
+  state.log.rows.map_or(state.height, |rows| rows + 1)
+}
+
+/// Clears rows `from..to` of a log starting at row `top`
+fn clear_rows(
+  terminal: &Terminal, cursor: &TerminalCursor, top: u16, from: u16, to: u16
+) -> Result<(), Box<dyn Error>> {
+  for y in from..to {
+    cursor.goto(0, top + y)?;
+    terminal.clear(ClearType::CurrentLine)?;
+  }
+
+  Ok(())
+}
+
+/// The filtered entries between the mark and the selection, inclusive, or
+/// just the selection if nothing is marked
+pub fn selected_range(state: &RenderState) -> Option<RangeInclusive<usize>> {
+  let selection = state.log.selection?.rel_index;
+
+  let mark = match state.log.mark {
+    Some(mark) => {
+      let filtered_entries = state.filtered_entries.borrow();
+      let position = filtered_entries.partition_point(|f| f.index < mark);
+      position.min(filtered_entries.len().saturating_sub(1))
+    },
+    None => selection
+  };
+
+  Some(min(mark, selection)..=max(mark, selection))
+}
+
+/// The number of displayed entries that arrived since following was paused,
+/// if it is
+pub fn new_since_paused(state: &RenderState) -> Option<usize> {
+  state.log.selection?;
+
+  let filtered_entries = state.filtered_entries.borrow();
+  let old = filtered_entries.partition_point(|f| f.index < state.log.paused_at);
+
+  Some(filtered_entries.len() - old)
+}
+
+/// If true, a message is at least as severe as `level`; plain and internal
+/// messages never are
+fn at_least(message: &Message, level: LogLevel) -> bool {
+  match message.level {
+    Some(LogLevel::Plain) | Some(LogLevel::Int) | None => false,
+    Some(l) => l >= level
+  }
+}
+
+/// Finds the nearest filtered entry at least as severe as `level`, after the
+/// selection if `forward`, otherwise before it (or the bottom)
+fn find_level(state: &RenderState, level: LogLevel, forward: bool) -> Option<usize> {
+  let filtered_entries = state.filtered_entries.borrow();
+  let is_match = |i: &usize| filtered_entries[*i].entry.upgrade()
+    .map_or(false, |e| at_least(&e.message, level));
+
+  match (state.log.selection, forward) {
+    (Some(selection), true) => (selection.rel_index + 1..filtered_entries.len()).find(is_match),
+    (Some(selection), false) => (0..selection.rel_index).rev().find(is_match),
+    (None, true) => None,
+    (None, false) => (0..filtered_entries.len()).rev().find(is_match)
+  }
+}
+
 fn render_int(
   state_mut: &mut RenderState, terminal: &Terminal, cursor: &TerminalCursor
 ) -> Result<(), Box<dyn Error>> {
@@ -115,11 +431,20 @@ fn render_int(
   let mut anchors = state_mut.log.anchors.borrow_mut();
   let filtered_entries = state_mut.filtered_entries.borrow();
 
+  let height = log_height(state_mut);
+  let top = state_mut.log.top;
+
   anchors.clear();
-  if filtered_entries.is_empty() || state_mut.height < 2 {
+  if filtered_entries.is_empty() || height < 2 {
     state_mut.log.range_min = 0;
     state_mut.log.range_max = 0;
-    terminal.clear(ClearType::All)?;
+
+    if state_mut.log.rows.is_some() {
+      clear_rows(terminal, cursor, top, 0, height - 1)?;
+    } else {
+      terminal.clear(ClearType::All)?;
+    }
+
     return Ok(());
   }
 
@@ -127,7 +452,7 @@ fn render_int(
   let start_index: usize;
   let mut start_y: u16;
   let start_height;
-  let end_y = state_mut.height - 1; // last valid y pos (inclusive)
+  let end_y = height - 1; // last valid y pos (inclusive)
 
   if let Some(selection) = state_mut.log.selection {
     start_selected = true;
@@ -149,12 +474,7 @@ fn render_int(
 
   // render the anchored entry first so we can decide if start_y is still
   // valid
-  let start_entry = &filtered_entries[start_index].entry.upgrade().unwrap();
-  let start_lines = styled_render(
-    start_entry,
-    profile_for_message(&state_mut, start_entry, start_selected),
-    Some(state_mut.width as usize)
-  );
+  let start_lines = entry_lines(state_mut, &filtered_entries, start_index, start_selected);
 
   // if the message height has changed (e.g. due to a resize),
   // update the position to keep it anchored
@@ -164,7 +484,7 @@ fn render_int(
     if diff != 0 {
       start_y = min(
         max(start_y as isize + diff, 0),
-        state_mut.height as isize - 1
+        height as isize - 1
       ) as u16;
 
       if let Some(old_selection) = state_mut.log.selection {
@@ -204,8 +524,8 @@ fn render_int(
   });
 
   // actually render that first entry (or as much of it as possible)
-  for line in start_lines {
-    cursor.goto(0, y_pos as u16)?;
+  for line in start_lines.iter() {
+    cursor.goto(0, top + y_pos)?;
     terminal.clear(ClearType::CurrentLine)?;
     terminal.write(line)?;
 
@@ -218,12 +538,7 @@ fn render_int(
   // now render as many entries below it as possible
   if y_pos < end_y {
     'outer_down: for i in {start_index + 1 .. filtered_entries.len()} {
-      let entry = &filtered_entries[i].entry.upgrade().unwrap();
-      let lines = styled_render(
-        entry,
-        profile_for_message(&state_mut, entry, false),
-        Some(state_mut.width as usize)
-      );
+      let lines = entry_lines(state_mut, &filtered_entries, i, false);
 
       state_mut.log.range_max = i;
       anchors.insert(i, Anchor {
@@ -231,8 +546,8 @@ fn render_int(
         height: Some(lines.len() as u16)
       });
 
-      for line in lines {
-        cursor.goto(0, y_pos)?;
+      for line in lines.iter() {
+        cursor.goto(0, top + y_pos)?;
         terminal.clear(ClearType::CurrentLine)?;
         terminal.write(line)?;
 
@@ -246,8 +561,7 @@ fn render_int(
 
   // clear any space at the bottom (unlikely, but possible)
   if y_pos < end_y {
-    cursor.goto(0, y_pos)?;
-    terminal.clear(ClearType::FromCursorDown)?;
+    clear_rows(terminal, cursor, top, y_pos, end_y)?;
   }
 
   // now reset y_pos and render upward
@@ -255,13 +569,8 @@ fn render_int(
     y_pos = start_y - 1;
 
     'outer_up: for i in {0..start_index}.rev() {
-      let entry = &filtered_entries[i].entry.upgrade().unwrap();
-      let lines = styled_render(
-        entry,
-        profile_for_message(&state_mut, entry, false),
-        Some(state_mut.width as usize)
-      );
-      
+      let lines = entry_lines(state_mut, &filtered_entries, i, false);
+
       state_mut.log.range_min = i;
 
       // y here is only used for anchoring purposes
@@ -275,7 +584,7 @@ fn render_int(
       });
 
       for line in lines.iter().rev() {
-        cursor.goto(0, y_pos as u16)?;
+        cursor.goto(0, top + y_pos)?;
         terminal.clear(ClearType::CurrentLine)?;
         terminal.write(line)?;
 
@@ -291,15 +600,11 @@ fn render_int(
 
     // attempt to clear out any remaining empty space at the top (case #1)
     if y_pos > 0 {
-      cursor.goto(0, y_pos)?;
-      terminal.clear(ClearType::CurrentLine)?;
-      terminal.clear(ClearType::FromCursorUp)?;
+      clear_rows(terminal, cursor, top, 0, y_pos + 1)?;
     }
   } else if start_y > 0 {
     // top clearing case #2
-    cursor.goto(0, start_y - 1)?;
-    terminal.clear(ClearType::CurrentLine)?;
-    terminal.clear(ClearType::FromCursorUp)?;
+    clear_rows(terminal, cursor, top, 0, start_y)?;
   }
 
   Ok(())
@@ -315,6 +620,8 @@ pub fn render(
     render_int(state_mut, terminal, cursor)?;
   }
 
+  trim_layout(&state);
+
   Ok(state)
 }
 
@@ -336,6 +643,11 @@ pub mod actions {
     // jump through hoops to return later
     let mut state = (*state).clone();
 
+    // selecting a message pauses following new entries
+    if state.log.selection.is_none() {
+      state.log.paused_at = state.entries.borrow().len();
+    }
+
     if filtered_entries.len() == 0 {
       // there's nothing to select
       return Rc::new(state);
@@ -365,7 +677,7 @@ pub mod actions {
       // selected message is off-screen and early/above
       state.log.selection = Some(Selection {
         rel_index: desired_index,
-        anchor: Anchor { offset: state.height - 1, height: None }
+        anchor: Anchor { offset: log_height(&state) - 1, height: None }
       });
     } else if desired_index > state.log.range_max {
       // selected message is off-screen and later/below
@@ -384,9 +696,10 @@ pub mod actions {
 
       // if the anchor is partially off-screen (i.e. too high up), nudge in the
       // right direction
-      let offset = if anchor.offset > state.height - 1 {
+      let height = log_height(&state);
+      let offset = if anchor.offset > height - 1 {
         // message extends upward
-        state.height - 1
+        height - 1
       } else if (anchor.offset as isize) - (anchor_height as isize) < 0 {
         // message extends downward
         anchor_height
@@ -441,7 +754,7 @@ pub mod actions {
         // visible to give the user some context
 
         // we can't move further than this
-        let max_height = state.height as isize - 2;
+        let max_height = log_height(&state) as isize - 2;
         let mut running_height = 0;
         let mut running_count = 0;
 
@@ -494,7 +807,7 @@ pub mod actions {
         // visible to give the user some context
 
         // we can't move further than this
-        let max_height = state.height as isize - 2;
+        let max_height = log_height(&state) as isize - 2;
         let mut running_height = 0;
         let mut running_count = 0;
 
@@ -528,9 +841,126 @@ pub mod actions {
     }
   }
 
+  /// Pauses following new entries by selecting the latest, or resumes
+  pub fn toggle_follow(state: RcState) -> RcState {
+    if state.log.selection.is_some() {
+      clear_selection(state)
+    } else {
+      move_selection(state, 1)
+    }
+  }
+
+  /// Selects the next (or previous) message at least as severe as `level`,
+  /// skipping over anything less severe
+  pub fn jump_to_level(state: RcState, level: LogLevel, forward: bool) -> RcState {
+    match find_level(&state, level, forward) {
+      Some(index) => move_selection_to_index(state, index),
+      None => state_actions::internal(state, &format!(
+        "no {} {} messages or worse",
+        if forward { "later" } else { "earlier" },
+        level.to_string().to_lowercase()
+      ))
+    }
+  }
+
+  /// Marks the selected message as one end of a range, or clears the mark
+  pub fn toggle_mark(mut state: RcState) -> RcState {
+    let index = state.log.selection.and_then(|selection| {
+      state.filtered_entries.borrow().get(selection.rel_index).map(|f| f.index)
+    });
+
+    let state_mut = Rc::make_mut(&mut state);
+    state_mut.log.mark = match state_mut.log.mark {
+      Some(_) => None,
+      None => index
+    };
+
+    state
+  }
+
   pub fn clear_selection(mut state: RcState) -> RcState {
     Rc::make_mut(&mut state).log.selection = None;
 
     state
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::sync::Arc;
+
+  use spectral::prelude::*;
+  use structopt::StructOpt;
+
+  use crate::config::Config;
+
+  #[test]
+  fn test_find_level() {
+    let config = Arc::new(Config::from_iter_safe(vec![""]).unwrap());
+    let mut state = Rc::new(RenderState::new(config));
+
+    let levels = [LogLevel::Error, LogLevel::Info, LogLevel::Fatal, LogLevel::Warning, LogLevel::Int];
+    for level in levels.iter() {
+      let mut entry = MessageEntry::internal("test");
+      entry.message.level = Some(*level);
+      state = state_actions::add_entry(state, entry);
+    }
+
+    // from the bottom, only earlier messages can be found
+    assert_that!(find_level(&state, LogLevel::Error, true)).is_none();
+    assert_that!(find_level(&state, LogLevel::Error, false)).is_equal_to(Some(2));
+    assert_that!(find_level(&state, LogLevel::Warning, false)).is_equal_to(Some(3));
+
+    state = actions::move_selection_to_index(state, 2);
+    assert_that!(find_level(&state, LogLevel::Error, false)).is_equal_to(Some(0));
+    assert_that!(find_level(&state, LogLevel::Error, true)).is_none();
+  }
+
+  #[test]
+  fn test_layout_cache() {
+    let config = Arc::new(Config::from_iter_safe(vec![""]).unwrap());
+    let mut state = Rc::new(RenderState::new(config));
+    Rc::make_mut(&mut state).width = 80;
+    for i in 0..250 {
+      state = state_actions::add_entry(state, MessageEntry::internal(&format!("message {}", i)));
+    }
+
+    let lines = |state: &RcState, index: usize, selected: bool| {
+      entry_lines(state, &state.filtered_entries.borrow(), index, selected)
+    };
+
+    let first = lines(&state, 1, false);
+    assert_that!(Rc::ptr_eq(&first, &lines(&state, 1, false))).is_true();
+    assert_that!(Rc::ptr_eq(&first, &lines(&state, 1, true))).is_false();
+
+    invalidate_layout(&state);
+    let second = lines(&state, 1, false);
+    assert_that!(Rc::ptr_eq(&first, &second)).is_false();
+    assert_that!(second).is_equal_to(first);
+
+    // a new width lays everything out again
+    Rc::make_mut(&mut state).width = 60;
+    assert_that!(Rc::ptr_eq(&second, &lines(&state, 1, false))).is_false();
+
+    // only entries near the screen are kept
+    lines(&state, 245, false);
+    let state_mut = Rc::make_mut(&mut state);
+    state_mut.log.range_min = 240;
+    state_mut.log.range_max = 249;
+    trim_layout(&state);
+
+    let kept: Vec<usize> = state.log.layout.borrow().lines.keys().map(|k| k.index).collect();
+    assert_that!(kept).is_equal_to(vec![245]);
+  }
+
+  #[test]
+  fn test_with_gutter() {
+    let lines = vec!["first".to_string(), "second".to_string()];
+    assert_that!(with_gutter(lines, 42, 5, Style::new())).is_equal_to(vec![
+      "  42 first".to_string(),
+      "     second".to_string()
+    ]);
+  }
+}
@@ -6,10 +6,12 @@ use std::sync::mpsc::Receiver;
 use std::thread::{self, JoinHandle};
 use std::time::{Duration, Instant};
 
-use crossterm::{Crossterm, Screen, TerminalInput, InputEvent};
+use crossterm::{ClearType, Crossterm, Screen, Terminal, TerminalCursor, TerminalInput, InputEvent};
 
 use crate::config::Config;
 use crate::renderer::types::*;
+use crate::renderer::common::select_fields;
+use crate::timezone::{self, RelativeTime};
 
 pub mod state;
 pub mod text;
@@ -18,13 +20,80 @@ pub mod bar;
 pub mod status_bar;
 pub mod filter_bar;
 pub mod search_bar;
+pub mod goto_bar;
+pub mod bookmarks_bar;
+pub mod sources_bar;
+pub mod detail_bar;
+pub mod command_bar;
+pub mod help_bar;
+pub mod keymap;
+pub mod pane;
+pub mod histogram_bar;
+pub mod summary;
+pub mod pause;
+pub mod history;
+pub mod appearance;
+pub mod range_bar;
+pub mod snapshot;
+pub mod facet_bar;
+pub mod zoom;
+pub mod correlate;
+pub mod template_bar;
 
 pub use state::RenderState;
 pub use state::RcState;
 
 lazy_static! {
-  /// The interval between full redraws even if no inputs occur
-  static ref REFRESH_INTERVAL: Duration = Duration::from_millis(500);
+  /// The interval between redraws while timestamps are relative to now
+  static ref TICK_INTERVAL: Duration = Duration::from_secs(1);
+
+  /// How long the terminal size must hold steady before laying out again
+  static ref RESIZE_DEBOUNCE: Duration = Duration::from_millis(100);
+}
+
+/// Debounces terminal resizes, so dragging a window's edge through many sizes
+/// lays the log out once it settles rather than at every size along the way
+#[derive(Default)]
+struct Resize {
+  /// A new size, and when it was first seen
+  pending: Option<((u16, u16), Instant)>
+}
+
+impl Resize {
+  /// Returns the size to lay out at if the terminal's `size` differs from the
+  /// `current` layout and has held for `RESIZE_DEBOUNCE`, or immediately if
+  /// nothing's been laid out yet
+  fn settle(
+    &mut self, size: (u16, u16), current: (u16, u16), now: Instant
+  ) -> Option<(u16, u16)> {
+    if size == current {
+      self.pending = None;
+      return None;
+    }
+
+    if current == (0, 0) {
+      return Some(size);
+    }
+
+    match self.pending {
+      Some((pending, since)) if pending == size => {
+        if now.duration_since(since) >= *RESIZE_DEBOUNCE {
+          self.pending = None;
+          Some(size)
+        } else {
+          None
+        }
+      },
+      _ => {
+        self.pending = Some((size, now));
+        None
+      }
+    }
+  }
+
+  fn is_pending(&self) -> bool {
+    self.pending.is_some()
+  }
 }
 
 #[derive(PartialEq)]
@@ -39,48 +108,98 @@ pub enum InputAction {
   Unhandled
 }
 
+/// Redraws the final view without any overlay or status bar, leaving it on
+/// the main screen with the cursor beneath it for whatever runs next
+fn leave_inline(rs: RcState, terminal: &Terminal, cursor: &TerminalCursor) {
+  let rs = bar::actions::set_active(rs, bar::BarType::Status);
+  let bottom = rs.height.saturating_sub(1);
+  pane::render(rs, terminal, cursor).ok();
+
+  cursor.goto(0, bottom).ok();
+  terminal.clear(ClearType::CurrentLine).ok();
+}
+
 pub fn interactive_renderer(config: Arc<Config>, rx: Receiver<LogEntry>) -> JoinHandle<()> {
   thread::Builder::new().name("interactive".to_string()).spawn(move || {
     let mut rs = Rc::new(RenderState::new(config));
+    rs = history::actions::load(rs);
+    rs = snapshot::actions::restore(rs);
 
-    let screen = Screen::default();
-    let alt = match screen.enable_alternate_modes(true) {
-      Ok(alternate) => alternate,
-      Err(e) => {
-        eprintln!("error opening alternate mode: {:?}", e);
-        return;
+    // inline, the main screen is put in raw mode itself; otherwise the
+    // alternate screen is, and the main screen is left alone
+    let inline = rs.config.inline;
+    let main = Screen::new(inline);
+    let alt = if inline {
+      None
+    } else {
+      match main.enable_alternate_modes(true) {
+        Ok(alternate) => Some(alternate),
+        Err(e) => {
+          eprintln!("error opening alternate mode: {:?}", e);
+          return;
+        }
       }
     };
 
-    let crossterm = Crossterm::from_screen(&alt.screen);
+    let screen = alt.as_ref().map_or(&main, |alt| &alt.screen);
+    let crossterm = Crossterm::from_screen(screen);
     let cursor = crossterm.cursor();
     let terminal = crossterm.terminal();
 
-    let input = TerminalInput::from_output(&alt.screen.stdout);
+    if inline {
+      // scroll whatever's on screen into the scrollback rather than drawing
+      // over it
+      let (_, height) = terminal.terminal_size();
+      terminal.write("\n".repeat(height as usize)).ok();
+    }
+
+    let input = TerminalInput::from_output(&screen.stdout);
 
     let mut stdin = input.read_async();
 
     let mut last_render: Option<Instant> = None;
-    let (mut last_width, mut last_height) = (0, 0);
+    let mut resize = Resize::default();
     'outer: loop {
       // whether or not we should redraw at the end of this iter
       let mut dirty = false;
 
       for entry in rx.try_iter() {
         if let Some(message) = entry.message {
-          rs = state::actions::add_entry(rs.clone(), message);
+          let message = select_fields(&rs.field_selection, message);
+          rs = snapshot::actions::receive(rs);
+          rs.summary.summary.borrow_mut().count(&message.message);
+          rs = pause::actions::receive(rs.clone(), message);
+          dirty = true;
+        }
+
+        if let Some(status) = entry.status {
+          // a source coming back, e.g. a restarted subprocess, means input
+          // hasn't ended after all
+          if rs.eof && status.state == SourceState::Streaming {
+            rs = state::actions::set_eof(rs.clone(), false);
+          }
+
+          rs = summary::actions::set_source(rs.clone(), &status.source, status.state);
           dirty = true;
         }
 
         if entry.eof.is_some() {
           rs = state::actions::set_eof(rs.clone(), true);
+          rs = snapshot::actions::finish(rs);
           dirty = true;
         }
       }
 
+      let (new_state, released) = pause::actions::tick(rs.clone());
+      rs = new_state;
+      dirty |= released;
+
       // handle as many input events as we can
       while let Some(event) = stdin.next() {
         if let InputEvent::Keyboard(key) = event {
+          // nearly anything can change how entries look, so lay them out again
+          log::invalidate_layout(&rs);
+
           let (new_state, action) = bar::input(rs.clone(), key);
           rs = new_state;
 
@@ -93,24 +212,37 @@ pub fn interactive_renderer(config: Arc<Config>, rx: Receiver<LogEntry>) -> Join
       }
 
       // TODO: is calling terminal_size() every loop expensive?
-      let (width, height) = terminal.terminal_size();
-      let resized = (width != last_width) || (height != last_height);
-      if resized {
-        let rs_mut = Rc::make_mut(&mut rs);
-        rs_mut.width = width;
-        rs_mut.height = height;
+      // crossterm doesn't seem to support resize events yet, so sizes are
+      // polled, and only laid out once they settle
+      let size = terminal.terminal_size();
+      let resized = match resize.settle(size, (rs.width, rs.height), Instant::now()) {
+        Some((width, height)) => {
+          let rs_mut = Rc::make_mut(&mut rs);
+          rs_mut.width = width;
+          rs_mut.height = height;
+          true
+        },
+        None => false
+      };
+
+      // the terminal reflows what's on screen as it's resized, so drawing at
+      // the old size mid-resize only adds garbage; once it settles, clear it
+      // all away and draw once at the new size
+      if resize.is_pending() {
+        thread::sleep(Duration::from_millis(25));
+        continue;
       }
 
-      last_width = width;
-      last_height = height;
+      if resized && last_render.is_some() {
+        terminal.clear(ClearType::All).ok();
+      }
 
-      // TODO: crossterm doesn't seem to support resize events yet
-      // until then, just rerender occasionally
-      // resizes won't be very smooth, but it will clean itself up
       // note that we also want to reduce unnecessary redraws as they can
       // clear a user's terminal selection
       let force_refresh = if let Some(last_render) = last_render {
-        resized && (last_render.elapsed() >= *REFRESH_INTERVAL)
+        let ticking = timezone::relative_time() == Some(RelativeTime::Now);
+
+        resized || (ticking && (last_render.elapsed() >= *TICK_INTERVAL))
       } else {
         // first render
         true
@@ -118,7 +250,7 @@ pub fn interactive_renderer(config: Arc<Config>, rx: Receiver<LogEntry>) -> Join
 
       if dirty || force_refresh {
         // TODO actually render
-        rs = log::render(rs.clone(), &terminal, &cursor).unwrap();
+        rs = pane::render(rs.clone(), &terminal, &cursor).unwrap();
         rs = bar::render(rs.clone(), &terminal, &cursor).unwrap();
 
         last_render = Some(Instant::now());
@@ -127,7 +259,42 @@ pub fn interactive_renderer(config: Arc<Config>, rx: Receiver<LogEntry>) -> Join
       thread::sleep(Duration::from_millis(25));
     }
 
+    if inline {
+      leave_inline(rs, &terminal, &cursor);
+    }
+
     // attempt to un-hide the cursor on the way out
     cursor.show().ok();
   }).unwrap()
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use spectral::prelude::*;
+
+  #[test]
+  fn test_resize() {
+    let start = Instant::now();
+    let at = |ms: u64| start + Duration::from_millis(ms);
+    let mut resize = Resize::default();
+
+    // the first layout isn't delayed
+    assert_that!(resize.settle((80, 24), (0, 0), at(0))).is_equal_to(Some((80, 24)));
+    assert_that!(resize.settle((80, 24), (80, 24), at(0))).is_none();
+
+    // sizes that keep changing aren't laid out until one holds
+    assert_that!(resize.settle((90, 24), (80, 24), at(10))).is_none();
+    assert_that!(resize.settle((100, 30), (80, 24), at(60))).is_none();
+    assert_that!(resize.settle((100, 30), (80, 24), at(150))).is_none();
+    assert_that!(resize.is_pending()).is_true();
+    assert_that!(resize.settle((100, 30), (80, 24), at(160))).is_equal_to(Some((100, 30)));
+    assert_that!(resize.is_pending()).is_false();
+
+    // and resizing back before settling cancels it
+    assert_that!(resize.settle((120, 30), (100, 30), at(200))).is_none();
+    assert_that!(resize.settle((100, 30), (100, 30), at(210))).is_none();
+    assert_that!(resize.is_pending()).is_false();
+  }
+}
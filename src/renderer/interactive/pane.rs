@@ -0,0 +1,317 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+use std::cell::RefCell;
+use std::error::Error;
+use std::rc::Rc;
+
+use crossterm::{Terminal, TerminalCursor, ClearType};
+
+use crate::filter::Filter;
+use crate::parser::{LogLevel, Message};
+
+use super::state::{self, FilteredEntry, RcState, RenderState};
+use super::state::actions as state_actions;
+use super::log::{self, LogState};
+use super::histogram_bar;
+use super::summary;
+
+/// The most panes the screen is split into
+const MAX_PANES: usize = 4;
+
+/// The fewest rows a pane needs, including its title
+const MIN_PANE_ROWS: u16 = 3;
+
+/// The messages shown in a pane, in addition to the usual filters
+#[derive(Clone)]
+enum PaneKind {
+  All,
+  Source(String),
+  Filter(Rc<Box<dyn Filter>>)
+}
+
+/// One of several independently scrollable views of the log
+#[derive(Clone)]
+pub struct Pane {
+  name: String,
+  kind: PaneKind,
+
+  /// This pane's scroll position and selection, while it isn't focused
+  pub log: LogState,
+
+  pub filtered_entries: Rc<RefCell<Vec<FilteredEntry>>>
+}
+
+impl Pane {
+  fn new(name: String, kind: PaneKind) -> Self {
+    Pane {
+      name,
+      kind,
+      log: LogState::new(),
+      filtered_entries: Rc::new(RefCell::new(Vec::new()))
+    }
+  }
+
+  /// If true, a message belongs in this pane; internal messages are shown in
+  /// every pane
+  pub fn pass(&self, message: &Message) -> bool {
+    if message.level == Some(LogLevel::Int) {
+      return true;
+    }
+
+    match &self.kind {
+      PaneKind::All => true,
+      PaneKind::Source(source) => {
+        message.reader_metadata.as_ref().and_then(|m| m.source.as_ref()) == Some(source)
+      },
+      PaneKind::Filter(filter) => filter.filter(message)
+    }
+  }
+}
+
+/// Panes splitting the log view, if any
+///
+/// The focused pane's log state lives in `RenderState::log`, and its filtered
+/// entries are shared with `RenderState::filtered_entries`, so everything else
+/// only needs to consider the focused pane.
+#[derive(Clone)]
+pub struct PaneState {
+  pub panes: Vec<Pane>,
+  pub focused: usize
+}
+
+impl PaneState {
+  pub fn new() -> Self {
+    PaneState {
+      panes: Vec::new(),
+      focused: 0
+    }
+  }
+
+  pub fn is_split(&self) -> bool {
+    !self.panes.is_empty()
+  }
+}
+
+/// Divides the rows above the bar between panes, as (top, rows) including
+/// each pane's title row, or None if there isn't room
+fn layout(available: u16, count: usize) -> Option<Vec<(u16, u16)>> {
+  let count = count as u16;
+  if count == 0 || available / count < MIN_PANE_ROWS {
+    return None;
+  }
+
+  let rows = available / count;
+  Some((0..count).map(|i| {
+    let top = i * rows;
+    if i == count - 1 {
+      (top, available - top)
+    } else {
+      (top, rows)
+    }
+  }).collect())
+}
+
+/// Renders a pane's title row, e.g. `[2] api/sidecar: 153 messages`
+fn render_title(
+  state: &RenderState, terminal: &Terminal, cursor: &TerminalCursor,
+  index: usize, row: u16
+) -> Result<(), Box<dyn Error>> {
+  let pane = &state.panes.panes[index];
+  let count = pane.filtered_entries.borrow().len();
+  let focused = index == state.panes.focused;
+
+  let title = format!(
+    " [{}] {}: {} message{}{}",
+    index + 1, pane.name, count, if count == 1 { "" } else { "s" },
+    if focused { " (focused)" } else { "" }
+  );
+
+  let width = state.width as usize;
+  let title: String = title.chars().take(width).collect();
+  let padding = " ".repeat(width.saturating_sub(title.chars().count()));
+
+  let style = if focused {
+    state.style.selected.get_base()
+  } else {
+    state.style.highlighted.get_base()
+  };
+
+  cursor.goto(0, row)?;
+  terminal.clear(ClearType::CurrentLine)?;
+  terminal.write(style.paint(format!("{}{}", title, padding)))?;
+
+  Ok(())
+}
+
+/// Renders the log, split into panes if requested
+pub fn render(
+  mut state: RcState, terminal: &Terminal, cursor: &TerminalCursor
+) -> Result<RcState, Box<dyn Error>> {
+  // the histogram strip takes the first row, and the summary the row above
+  // the bar
+  histogram_bar::render_strip(&state, terminal, cursor)?;
+  summary::render_line(&state, terminal, cursor)?;
+  let offset = if state.histogram.visible { 1 } else { 0 };
+  let summary_rows = if state.summary.visible { 1 } else { 0 };
+  let available = state.height.saturating_sub(1 + offset + summary_rows);
+
+  let layout = match layout(available, state.panes.panes.len()) {
+    Some(layout) => layout,
+    None => {
+      // not split, or too small to show every pane: only show the focused one
+      let state_mut = Rc::make_mut(&mut state);
+      state_mut.log.top = offset;
+      state_mut.log.rows = if offset + summary_rows > 0 { Some(available) } else { None };
+
+      return log::render(state, terminal, cursor);
+    }
+  };
+
+  for (i, (top, rows)) in layout.into_iter().enumerate() {
+    let top = top + offset;
+
+    render_title(&state, terminal, cursor, i, top)?;
+
+    if i == state.panes.focused {
+      let state_mut = Rc::make_mut(&mut state);
+      state_mut.log.top = top + 1;
+      state_mut.log.rows = Some(rows - 1);
+
+      state = log::render(state, terminal, cursor)?;
+    } else {
+      let pane = &state.panes.panes[i];
+
+      let mut pane_state = (*state).clone();
+      pane_state.log = pane.log.clone();
+      pane_state.log.top = top + 1;
+      pane_state.log.rows = Some(rows - 1);
+      pane_state.filtered_entries = Rc::clone(&pane.filtered_entries);
+
+      let pane_state = log::render(Rc::new(pane_state), terminal, cursor)?;
+      Rc::make_mut(&mut state).panes.panes[i].log = pane_state.log.clone();
+    }
+  }
+
+  Ok(state)
+}
+
+pub mod actions {
+  use super::*;
+
+  /// Replaces the panes, focusing the one at `focused`
+  fn set_panes(mut state: RcState, panes: Vec<Pane>, focused: usize) -> RcState {
+    let state_mut = Rc::make_mut(&mut state);
+    state_mut.filtered_entries = Rc::clone(&panes[focused].filtered_entries);
+    state_mut.log.selection = None;
+    state_mut.panes = PaneState { panes, focused };
+
+    state::refilter(state)
+  }
+
+  /// Splits the view into a pane per source
+  pub fn split_sources(state: RcState) -> RcState {
+    let mut sources: Vec<String> = Vec::new();
+    for entry in state.entries.borrow().iter() {
+      let source = entry.message.reader_metadata.as_ref().and_then(|m| m.source.as_ref());
+      if let Some(source) = source {
+        if !sources.contains(source) {
+          sources.push(source.clone());
+        }
+      }
+    }
+
+    if sources.len() < 2 {
+      return state_actions::internal(state, "there's only one source, nothing to split");
+    }
+
+    if sources.len() > MAX_PANES {
+      return state_actions::internal(state, &format!(
+        "too many sources to split ({}), at most {} fit", sources.len(), MAX_PANES
+      ));
+    }
+
+    let panes = sources.into_iter()
+      .map(|source| Pane::new(source.clone(), PaneKind::Source(source)))
+      .collect();
+
+    set_panes(state, panes, 0)
+  }
+
+  /// Moves the last filter into a new pane, keeping the current view beside it
+  pub fn split_filter(mut state: RcState) -> RcState {
+    if state.panes.panes.len() >= MAX_PANES {
+      return state_actions::internal(state, &format!("at most {} panes fit", MAX_PANES));
+    }
+
+    let filter = state.filters.borrow_mut().pop();
+    let filter = match filter {
+      Some(filter) => filter,
+      None => return state_actions::internal(state, "no filter to split off, add one first")
+    };
+
+    let state_mut = Rc::make_mut(&mut state);
+    let mut panes = state_mut.panes.panes.clone();
+    if panes.is_empty() {
+      panes.push(Pane::new("all".to_string(), PaneKind::All));
+    } else {
+      panes[state_mut.panes.focused].log = state_mut.log.clone();
+    }
+
+    panes.push(Pane::new(filter.describe(), PaneKind::Filter(Rc::new(filter))));
+
+    let focused = panes.len() - 1;
+    set_panes(state, panes, focused)
+  }
+
+  /// Focuses the next pane
+  pub fn focus_next(mut state: RcState) -> RcState {
+    if !state.panes.is_split() {
+      return state_actions::internal(state, "the view isn't split");
+    }
+
+    let state_mut = Rc::make_mut(&mut state);
+    let current = state_mut.panes.focused;
+    let next = (current + 1) % state_mut.panes.panes.len();
+
+    state_mut.panes.panes[current].log = state_mut.log.clone();
+    state_mut.log = state_mut.panes.panes[next].log.clone();
+    state_mut.filtered_entries = Rc::clone(&state_mut.panes.panes[next].filtered_entries);
+    state_mut.panes.focused = next;
+    state_mut.search.invalidate();
+    state_mut.histogram.invalidate();
+
+    state
+  }
+
+  /// Merges all panes back into one view
+  pub fn merge(mut state: RcState) -> RcState {
+    if !state.panes.is_split() {
+      return state_actions::internal(state, "the view isn't split");
+    }
+
+    let state_mut = Rc::make_mut(&mut state);
+    state_mut.panes = PaneState::new();
+    state_mut.filtered_entries = Rc::new(RefCell::new(Vec::new()));
+    state_mut.histogram.invalidate();
+    state_mut.log.selection = None;
+    state_mut.log.top = 0;
+    state_mut.log.rows = None;
+
+    state::refilter(state)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use spectral::prelude::*;
+
+  #[test]
+  fn test_layout() {
+    assert_that!(layout(20, 0)).is_none();
+    assert_that!(layout(20, 2)).is_equal_to(Some(vec![(0, 10), (10, 10)]));
+    assert_that!(layout(20, 3)).is_equal_to(Some(vec![(0, 6), (6, 6), (12, 8)]));
+    assert_that!(layout(8, 3)).is_none();
+  }
+}
@@ -0,0 +1,185 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+use std::time::Instant;
+
+use crate::renderer::types::MessageEntry;
+
+use super::state::RcState;
+use super::state::actions as state_actions;
+
+/// The slowest a backlog is replayed, in messages per second
+const MIN_REPLAY_RATE: f64 = 20.0;
+
+/// The longest a replay takes, in seconds; larger backlogs are replayed faster
+const MAX_REPLAY_SECONDS: f64 = 10.0;
+
+/// A replay of buffered messages in progress
+#[derive(Clone, Copy)]
+struct Replay {
+  started: Instant,
+
+  /// The number of messages to replay per second
+  rate: f64,
+
+  /// The number of messages released so far
+  released: usize
+}
+
+impl Replay {
+  fn new(backlog: usize) -> Self {
+    Replay {
+      started: Instant::now(),
+      rate: replay_rate(backlog),
+      released: 0
+    }
+  }
+}
+
+/// The rate at which to replay a backlog of `backlog` messages
+fn replay_rate(backlog: usize) -> f64 {
+  (backlog as f64 / MAX_REPLAY_SECONDS).max(MIN_REPLAY_RATE)
+}
+
+/// The number of messages a replay should have released after `elapsed`
+/// seconds
+fn replay_due(rate: f64, elapsed: f64) -> usize {
+  (rate * elapsed).ceil() as usize
+}
+
+/// Freezes the display while buffering incoming messages
+///
+/// While paused, or replaying, new messages are held in `pending` rather than
+/// added to the log.
+#[derive(Clone)]
+pub struct PauseState {
+  paused: bool,
+  replay: Option<Replay>,
+
+  pending: Rc<RefCell<VecDeque<MessageEntry>>>
+}
+
+impl PauseState {
+  pub fn new() -> Self {
+    PauseState {
+      paused: false,
+      replay: None,
+      pending: Rc::new(RefCell::new(VecDeque::new()))
+    }
+  }
+
+  pub fn is_paused(&self) -> bool {
+    self.paused
+  }
+
+  pub fn is_replaying(&self) -> bool {
+    self.replay.is_some()
+  }
+
+  /// The number of buffered messages not yet shown
+  pub fn pending(&self) -> usize {
+    self.pending.borrow().len()
+  }
+}
+
+pub mod actions {
+  use super::*;
+
+  /// Adds a newly received message to the log, or buffers it if paused or
+  /// replaying
+  pub fn receive(state: RcState, entry: MessageEntry) -> RcState {
+    if state.pause.paused || state.pause.replay.is_some() {
+      state.pause.pending.borrow_mut().push_back(entry);
+
+      state
+    } else {
+      state_actions::add_entry(state, entry)
+    }
+  }
+
+  /// Adds every buffered message to the log
+  fn flush(mut state: RcState) -> RcState {
+    let pending: Vec<MessageEntry> = state.pause.pending.borrow_mut().drain(..).collect();
+    for entry in pending {
+      state = state_actions::add_entry(state, entry);
+    }
+
+    state
+  }
+
+  /// Pauses, or resumes by jumping straight to the latest message
+  pub fn toggle(mut state: RcState) -> RcState {
+    let state_mut = Rc::make_mut(&mut state);
+    if state_mut.pause.paused || state_mut.pause.replay.is_some() {
+      state_mut.pause.paused = false;
+      state_mut.pause.replay = None;
+
+      flush(state)
+    } else {
+      state_mut.pause.paused = true;
+
+      state
+    }
+  }
+
+  /// Resumes by replaying buffered messages at an accelerated rate
+  pub fn replay(mut state: RcState) -> RcState {
+    if !state.pause.paused {
+      return state_actions::internal(state, "not paused, nothing to replay");
+    }
+
+    let backlog = state.pause.pending();
+    let state_mut = Rc::make_mut(&mut state);
+    state_mut.pause.paused = false;
+    state_mut.pause.replay = if backlog > 0 { Some(Replay::new(backlog)) } else { None };
+
+    state
+  }
+
+  /// Releases any buffered messages due during a replay, returning true if
+  /// any were shown
+  pub fn tick(mut state: RcState) -> (RcState, bool) {
+    let mut replay = match state.pause.replay {
+      Some(replay) => replay,
+      None => return (state, false)
+    };
+
+    let due = replay_due(replay.rate, replay.started.elapsed().as_secs_f64());
+    let mut released = false;
+    while replay.released < due {
+      let entry = state.pause.pending.borrow_mut().pop_front();
+      match entry {
+        Some(entry) => state = state_actions::add_entry(state, entry),
+        None => break
+      }
+
+      replay.released += 1;
+      released = true;
+    }
+
+    let done = state.pause.pending() == 0;
+    Rc::make_mut(&mut state).pause.replay = if done { None } else { Some(replay) };
+
+    (state, released || done)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use spectral::prelude::*;
+
+  #[test]
+  fn test_replay() {
+    // small backlogs are replayed at a readable rate, large ones in bounded time
+    assert_that!(replay_rate(10)).is_equal_to(MIN_REPLAY_RATE);
+    assert_that!(replay_rate(5000)).is_equal_to(500.0);
+
+    assert_that!(replay_due(20.0, 0.0)).is_equal_to(0);
+    assert_that!(replay_due(20.0, 0.01)).is_equal_to(1);
+    assert_that!(replay_due(20.0, 1.0)).is_equal_to(20);
+  }
+}
@@ -0,0 +1,210 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+use std::error::Error;
+use std::rc::Rc;
+
+use chrono::Utc;
+use crossterm::{Terminal, TerminalCursor, KeyEvent, ClearType};
+
+use crate::parser::{Message, MessageKind};
+use crate::renderer::common::{format_delta, message_timestamp};
+use crate::renderer::stats::Stats;
+use crate::timezone;
+
+use super::state::RcState;
+use super::state::actions as state_actions;
+use super::bar::{self, BarType};
+use super::log;
+use super::InputAction;
+
+/// A full screen overlay summarizing the marked range
+#[derive(Clone)]
+pub struct RangeBarState {
+  /// The summary, computed when opened
+  lines: Rc<Vec<String>>,
+
+  /// The first line shown
+  scroll: usize
+}
+
+impl RangeBarState {
+  pub fn new() -> Self {
+    RangeBarState {
+      lines: Rc::new(Vec::new()),
+      scroll: 0
+    }
+  }
+}
+
+/// Summarizes messages: the time they span, then counts per level and source,
+/// the most repeated messages, and the rate, as with `-r stats`
+fn summarize(messages: &[&Message], width: usize) -> Vec<String> {
+  let messages: Vec<&Message> = messages.iter()
+    .filter(|m| m.kind != MessageKind::Internal)
+    .cloned()
+    .collect();
+
+  let timestamps: Vec<_> = messages.iter().filter_map(|m| message_timestamp(m)).collect();
+  let first = timestamps.iter().min();
+  let last = timestamps.iter().max();
+
+  let mut lines = Vec::new();
+  let duration = match (first, last) {
+    (Some(first), Some(last)) => {
+      let format = "%Y-%m-%d %H:%M:%S%.3f";
+      lines.push(format!("from: {}", timezone::to_display(*first).format(format)));
+      lines.push(format!("to:   {}", timezone::to_display(*last).format(format)));
+      lines.push(format!("duration: {}", format_delta(*last - *first).trim_start_matches('+')));
+      lines.push(String::new());
+
+      (*last - *first).to_std().unwrap_or_default()
+    },
+    _ => {
+      lines.push("no timestamps in range, so no duration or rate".to_string());
+      lines.push(String::new());
+
+      Default::default()
+    }
+  };
+
+  let mut stats = Stats::default();
+  let now = Utc::now();
+  for message in &messages {
+    stats.add(message, now);
+  }
+
+  lines.extend(stats.render(duration, width));
+  lines
+}
+
+fn max_scroll(state: &RcState) -> usize {
+  state.range.lines.len().saturating_sub(state.height.saturating_sub(1) as usize)
+}
+
+pub fn render(
+  state: RcState, terminal: &Terminal, cursor: &TerminalCursor
+) -> Result<RcState, Box<dyn Error>> {
+  let width = state.width as usize;
+  let rows = state.height.saturating_sub(1) as usize;
+  let normal = state.style.normal.get_base();
+  let selected = state.style.selected.get_base();
+
+  for row in 0..rows {
+    cursor.goto(0, row as u16)?;
+    terminal.clear(ClearType::CurrentLine)?;
+
+    if let Some(line) = state.range.lines.get(state.range.scroll + row) {
+      let line: String = line.chars().take(width).collect();
+      terminal.write(normal.paint(line))?;
+    }
+  }
+
+  let help = "range summary | up/down: scroll | esc: close";
+  let help: String = help.chars().take(width).collect();
+  let padding = " ".repeat(width.saturating_sub(help.len()));
+
+  cursor.hide()?;
+  cursor.goto(0, state.height - 1)?;
+  terminal.clear(ClearType::CurrentLine)?;
+  terminal.write(selected.paint(format!("{}{}", help, padding)))?;
+
+  Ok(state)
+}
+
+pub fn input(mut state: RcState, key: &KeyEvent) -> (RcState, InputAction) {
+  let page = state.height as isize - 1;
+
+  state = match key {
+    KeyEvent::Esc | KeyEvent::Char('q') | KeyEvent::Char('\n') => {
+      bar::actions::set_active(state, BarType::Status)
+    },
+    KeyEvent::Up => actions::scroll(state, -1),
+    KeyEvent::Down => actions::scroll(state, 1),
+    KeyEvent::PageUp => actions::scroll(state, -page),
+    KeyEvent::PageDown => actions::scroll(state, page),
+    _ => return (state, InputAction::Unhandled)
+  };
+
+  (state, InputAction::Rerender)
+}
+
+pub mod actions {
+  use super::*;
+
+  /// Summarizes the messages between the mark and the selection
+  pub fn open(mut state: RcState) -> RcState {
+    let range = match (state.log.mark, log::selected_range(&state)) {
+      (Some(_), Some(range)) => range,
+      _ => return state_actions::internal(state, "mark a range first, then select its other end")
+    };
+
+    let entries: Vec<_> = {
+      let filtered_entries = state.filtered_entries.borrow();
+      range.filter_map(|i| filtered_entries.get(i)?.entry.upgrade()).collect()
+    };
+
+    let messages: Vec<&Message> = entries.iter().map(|e| &e.message).collect();
+    let lines = summarize(&messages, state.width as usize);
+
+    let state_mut = Rc::make_mut(&mut state);
+    state_mut.range.lines = Rc::new(lines);
+    state_mut.range.scroll = 0;
+
+    bar::actions::set_active(state, BarType::Range)
+  }
+
+  pub fn scroll(mut state: RcState, amount: isize) -> RcState {
+    let max = max_scroll(&state) as isize;
+
+    let state_mut = Rc::make_mut(&mut state);
+    let scroll = (state_mut.range.scroll as isize + amount).max(0).min(max);
+    state_mut.range.scroll = scroll as usize;
+
+    state
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::collections::HashMap;
+
+  use spectral::prelude::*;
+
+  use crate::parser::{LogLevel, ReaderMetadata};
+
+  fn message(second: u32, level: LogLevel, text: &str) -> Message {
+    Message {
+      kind: MessageKind::Json,
+      timestamp: Some(format!("2020-11-25T21:22:{:02}Z", second).parse().unwrap()),
+      level: Some(level),
+      raw: String::new(),
+      text: Some(text.to_string()),
+      metadata: HashMap::new(),
+      reader_metadata: Some(ReaderMetadata { timestamp: None, source: Some("api".to_string()) }),
+      mapped_fields: HashMap::new()
+    }
+  }
+
+  #[test]
+  fn test_summarize() {
+    let mut internal = message(59, LogLevel::Int, "ignored");
+    internal.kind = MessageKind::Internal;
+
+    let messages = [
+      message(10, LogLevel::Info, "request 1 done"),
+      message(40, LogLevel::Error, "timeout after 30s"),
+      message(20, LogLevel::Error, "timeout after 10s"),
+      internal
+    ];
+
+    let lines = summarize(&messages.iter().collect::<Vec<_>>(), 40);
+    assert_that!(lines[2]).is_equal_to("duration: 30.0s".to_string());
+    assert_that!(lines[4]).is_equal_to("records: 3 in 30.0s (0.1/s)".to_string());
+    assert_that!(lines).contains("         2 timeout after <num>s".to_string());
+
+    let lines = summarize(&[], 40);
+    assert_that!(lines[0]).starts_with("no timestamps in range");
+  }
+}
@@ -1,27 +1,47 @@
 // (C) Copyright 2019 Hewlett Packard Enterprise Development LP
 
+use std::cell::RefCell;
 use std::error::Error;
 use std::rc::Rc;
 
 use crossterm::{Terminal, TerminalCursor, KeyEvent, ClearType};
+use regex::{Regex, RegexBuilder};
 
 use crate::filter::{Filter, FilterMode};
+use crate::history::SavedFilter;
 use crate::style::{StyleProfileKind, styler_base, styler_error};
 
-use super::state::RcState;
+use super::state::{RcState, RenderState};
 use super::state::actions as state_actions;
 use super::bar::{self, BarType};
+use super::history;
 use super::status_bar;
 use super::log;
 use super::text::{self, TextBuffer, TextInputAction};
 use super::InputAction;
 
+/// Indexes of filtered entries matching the search, counted incrementally as
+/// entries arrive
+#[derive(Default)]
+struct MatchCache {
+  scanned: usize,
+  indexes: Vec<usize>
+}
+
 #[derive(Clone)]
 pub struct SearchBarState {
   mode: FilterMode,
   text: TextBuffer,
   inverted: bool,
-  filter: Option<Rc<Box<dyn Filter>>>
+  filter: Option<Rc<Box<dyn Filter>>>,
+
+  /// The pattern highlighted within matching messages, unless inverted
+  pattern: Option<Regex>,
+
+  matches: Rc<RefCell<MatchCache>>,
+
+  /// The position in the search history while browsing, see `history::step()`
+  history: Option<usize>
 }
 
 impl SearchBarState {
@@ -32,9 +52,68 @@ impl SearchBarState {
       mode: FilterMode::Regex,
       text: TextBuffer::new().with_styler(Some(styler)),
       inverted: false,
-      filter: None
+      filter: None,
+      pattern: None,
+      matches: Rc::new(RefCell::new(MatchCache::default())),
+      history: None
+    }
+  }
+
+  /// If true, a search is in effect, even if the search bar isn't open
+  pub fn is_active(&self) -> bool {
+    self.filter.is_some()
+  }
+
+  pub fn pattern(&self) -> Option<&Regex> {
+    self.pattern.as_ref()
+  }
+
+  /// Forgets counted matches, e.g. after the filtered entries are rebuilt
+  pub fn invalidate(&self) {
+    *self.matches.borrow_mut() = MatchCache::default();
+  }
+}
+
+/// Returns the position of the selection among matches, if it matches, and
+/// the total number of matches in the filtered entries
+pub fn match_position(state: &RenderState) -> Option<(Option<usize>, usize)> {
+  let filter = state.search.filter.as_ref()?;
+  let mut matches = state.search.matches.borrow_mut();
+
+  let filtered_entries = state.filtered_entries.borrow();
+  for i in matches.scanned..filtered_entries.len() {
+    if let Some(entry) = filtered_entries[i].entry.upgrade() {
+      if filter.filter(&entry.message) {
+        matches.indexes.push(i);
+      }
     }
   }
+
+  matches.scanned = filtered_entries.len();
+
+  let position = state.log.selection
+    .and_then(|s| matches.indexes.binary_search(&s.rel_index).ok())
+    .map(|i| i + 1);
+
+  Some((position, matches.indexes.len()))
+}
+
+/// Builds the pattern highlighted for a search, matching how its filter does
+fn search_pattern(mode: FilterMode, input: &str, inverted: bool) -> Option<Regex> {
+  if input.is_empty() || inverted {
+    return None;
+  }
+
+  match mode {
+    FilterMode::Regex => Regex::new(input).ok(),
+    FilterMode::Text => RegexBuilder::new(&regex::escape(input))
+      .case_insensitive(true)
+      .build()
+      .ok(),
+
+    // expressions match fields, so there's no text to highlight
+    FilterMode::Expression => None
+  }
 }
 
 fn format_right(state: &RcState) -> String {
@@ -63,7 +142,7 @@ pub fn render(
   cursor.goto(0, state.height - 1)?;
   terminal.clear(ClearType::CurrentLine)?;
 
-  let style = &state.config.style.selected.get_base();
+  let style = &state.style.selected.get_base();
   terminal.write(style.paint(" ".repeat(state.width as usize)))?;
 
   let (right_len, right) = status_bar::format_right(&state);
@@ -109,12 +188,23 @@ fn handle_text_input(
 }
 
 pub fn input(mut state: RcState, key: &KeyEvent) -> (RcState, InputAction) {
+  if let KeyEvent::Up | KeyEvent::Down = key {
+    state = actions::browse_history(state, matches!(key, KeyEvent::Up));
+    state = actions::update_filter(state);
+    state = actions::next_match(state, true);
+    state = actions::update_highlight(state);
+    state = actions::update_style(state);
+
+    return (state, InputAction::Rerender);
+  }
+
   let (new_state, action) = handle_text_input(state, key);
   state = new_state;
 
   let input_action = match action {
     TextInputAction::Action(a) => a,
     TextInputAction::Exit(a) => {
+      Rc::make_mut(&mut state).search.history = None;
       state = actions::update_filter(state);
       state = actions::update_highlight(state);
       state = actions::update_style(state);
@@ -122,7 +212,13 @@ pub fn input(mut state: RcState, key: &KeyEvent) -> (RcState, InputAction) {
 
       a
     },
-    TextInputAction::Submit(a, _) => {
+    TextInputAction::Submit(a, input) => {
+      if state.search.filter.is_some() {
+        let saved = SavedFilter::new(state.search.mode, &input, state.search.inverted);
+        state = history::actions::remember_search(state, saved);
+      }
+
+      Rc::make_mut(&mut state).search.history = None;
       state = actions::next_match(state, false);
 
       a
@@ -191,8 +287,12 @@ pub mod actions {
       None
     };
 
+    let pattern = search_pattern(state.search.mode, input, state.search.inverted);
+
     let state_mut = Rc::make_mut(&mut state);
     state_mut.search.filter = new_filter;
+    state_mut.search.pattern = pattern;
+    state_mut.search.matches = Rc::new(RefCell::new(MatchCache::default()));
 
     state
   }
@@ -329,6 +429,28 @@ pub mod actions {
     }
   }
 
+  /// Replaces the input with an older or newer search from the history
+  pub fn browse_history(mut state: RcState, older: bool) -> RcState {
+    let position = history::step(state.history.borrow().searches.len(), state.search.history, older);
+    let item = history::item(&state.history.borrow().searches, position).cloned();
+
+    let state_mut = Rc::make_mut(&mut state);
+    state_mut.search.history = position;
+
+    let text = state_mut.search.text.clone();
+    state_mut.search.text = match item {
+      Some(item) => {
+        state_mut.search.mode = item.mode;
+        state_mut.search.inverted = item.inverted;
+
+        history::set_input(text, &item.query)
+      },
+      None => text::actions::clear_input(text)
+    };
+
+    state
+  }
+
   pub fn next_mode(mut state: RcState) -> RcState {
     let state_mut = Rc::make_mut(&mut state);
     state_mut.search.mode = state_mut.search.mode.next();
@@ -0,0 +1,189 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+use std::collections::BTreeSet;
+use std::path::Path;
+use std::rc::Rc;
+
+use crate::history::SavedFilter;
+use crate::session::{self, SessionReader, SessionView};
+
+use super::state::{RcState, RenderState};
+use super::state::actions as state_actions;
+use super::bookmarks_bar;
+use super::log;
+use super::sources_bar;
+
+/// A session being reopened with `woodchipper open`, whose bookmarks and
+/// selection are restored once all of its records have arrived
+#[derive(Clone)]
+pub struct SnapshotState {
+  pending: Option<Rc<SessionView>>,
+
+  /// The index in `entries` of the session's first record
+  base: Option<usize>
+}
+
+impl SnapshotState {
+  pub fn new() -> Self {
+    SnapshotState {
+      pending: None,
+      base: None
+    }
+  }
+}
+
+/// The current view, with positions as indexes into `entries`
+fn view(state: &RenderState) -> SessionView {
+  let selection = state.log.selection.and_then(|selection| {
+    state.filtered_entries.borrow().get(selection.rel_index).map(|f| f.index)
+  });
+
+  SessionView {
+    filters: state.filters.borrow().iter().map(|f| SavedFilter::of(f.as_ref())).collect(),
+    min_level: state.min_level,
+    context: state.context,
+    hidden_sources: state.sources.hidden().iter().map(|s| s.to_string()).collect(),
+    bookmarks: state.bookmarks.marks(),
+    selection,
+    mark: state.log.mark,
+
+    ..Default::default()
+  }
+}
+
+pub mod actions {
+  use super::*;
+
+  /// Restores the filters and hidden sources of the session from `--open`, if
+  /// any, before its records arrive
+  pub fn restore(mut state: RcState) -> RcState {
+    let path = match &state.config.open {
+      Some(path) => path.clone(),
+      None => return state
+    };
+
+    let view = match SessionReader::open(&path) {
+      Ok(reader) => reader.view,
+      Err(e) => return state_actions::internal(state, &e.to_string())
+    };
+
+    for filter in &view.filters {
+      match filter.parse() {
+        Ok(filter) => state.filters.borrow_mut().push(filter),
+        Err(e) => state = state_actions::internal(state, &format!("skipped a filter: {}", e))
+      }
+    }
+
+    let state_mut = Rc::make_mut(&mut state);
+    state_mut.min_level = view.min_level;
+    state_mut.context = view.context;
+    state_mut.snapshot.pending = Some(Rc::new(view.clone()));
+
+    let hidden: BTreeSet<String> = view.hidden_sources.into_iter().collect();
+    sources_bar::actions::set_hidden(state, hidden)
+  }
+
+  /// Notes where the session's records start, before the first is added
+  pub fn receive(mut state: RcState) -> RcState {
+    if state.snapshot.pending.is_none() || state.snapshot.base.is_some() {
+      return state;
+    }
+
+    let base = state.entries.borrow().len();
+    Rc::make_mut(&mut state).snapshot.base = Some(base);
+
+    state
+  }
+
+  /// Restores the session's bookmarks and selection once its records have
+  /// all arrived
+  pub fn finish(mut state: RcState) -> RcState {
+    let view = match state.snapshot.pending.clone() {
+      Some(view) => view,
+      None => return state
+    };
+
+    let base = state.snapshot.base.unwrap_or(0);
+    let state_mut = Rc::make_mut(&mut state);
+    state_mut.snapshot.pending = None;
+    state_mut.log.mark = view.mark.map(|mark| base + mark);
+
+    let marks = view.bookmarks.iter().map(|mark| base + mark).collect();
+    let state = bookmarks_bar::actions::set_marks(state, marks);
+
+    let position = view.selection.and_then(|selection| {
+      state.filtered_entries.borrow().binary_search_by_key(&(base + selection), |f| f.index).ok()
+    });
+
+    let state = match position {
+      Some(position) => log::actions::move_selection_to_index(state, position),
+      None => state
+    };
+
+    let path = state.config.open.as_ref().map(|p| p.display().to_string()).unwrap_or_default();
+    state_actions::internal(state, &format!("opened session {} ({} records)", path, view.records))
+  }
+
+  /// Saves every buffered message and the current view to a file
+  pub fn save(state: RcState, path: &str, overwrite: bool) -> RcState {
+    let path = shellexpand::tilde(path).to_string();
+    let result = {
+      let entries = state.entries.borrow();
+      session::write(Path::new(&path), view(&state), entries.iter().map(|e| &e.message), overwrite)
+    };
+
+    match result {
+      Ok(count) => state_actions::internal(state, &format!(
+        "saved session of {} records to {}, reopen it with: woodchipper open {}", count, path, path
+      )),
+      Err(e) if !overwrite && Path::new(&path).exists() => {
+        state_actions::internal(state, &format!("{} (use snapshot! to overwrite)", e))
+      },
+      Err(e) => state_actions::internal(state, &e.to_string())
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::sync::Arc;
+
+  use spectral::prelude::*;
+  use structopt::StructOpt;
+
+  use crate::config::Config;
+  use crate::renderer::types::MessageEntry;
+
+  #[test]
+  fn test_finish() {
+    let config = Arc::new(Config::from_iter_safe(vec![""]).unwrap());
+    let mut state = Rc::new(RenderState::new(config));
+
+    // a message from before the session's records arrive shifts them along
+    state = state_actions::internal(state, "hello");
+
+    let view = SessionView {
+      records: 3, bookmarks: vec![0, 2], selection: Some(1), ..Default::default()
+    };
+    Rc::make_mut(&mut state).snapshot.pending = Some(Rc::new(view));
+
+    for text in &["a", "b", "c"] {
+      state = actions::receive(state);
+      state = state_actions::add_entry(state, MessageEntry::internal(text));
+    }
+
+    state = actions::finish(state);
+    assert_that!(state.bookmarks.marks()).is_equal_to(vec![1, 3]);
+
+    let selection = state.log.selection.map(|s| state.filtered_entries.borrow()[s.rel_index].index);
+    assert_that!(selection).is_equal_to(Some(2));
+
+    // and the view round trips, with positions as indexes into entries
+    let saved = super::view(&state);
+    assert_that!(saved.bookmarks).is_equal_to(vec![1, 3]);
+    assert_that!(saved.selection).is_equal_to(Some(2));
+    assert_that!(state.snapshot.pending.is_none()).is_true();
+  }
+}
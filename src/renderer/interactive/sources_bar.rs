@@ -0,0 +1,292 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::rc::Rc;
+
+use crossterm::{Terminal, TerminalCursor, KeyEvent, ClearType};
+
+use crate::parser::{LogLevel, Message};
+use crate::reader::exec::{self, Control};
+use crate::renderer::types::SourceState;
+
+use super::state::{self, RcState, RenderState};
+use super::state::actions as state_actions;
+use super::bar::{self, BarType};
+use super::InputAction;
+
+/// Sources whose messages are hidden, and the source list overlay
+#[derive(Clone)]
+pub struct SourcesBarState {
+  /// Hidden sources, by name
+  hidden: BTreeSet<String>,
+
+  /// The highlighted source in the list, by position
+  cursor: usize
+}
+
+impl SourcesBarState {
+  pub fn new() -> Self {
+    SourcesBarState {
+      hidden: BTreeSet::new(),
+      cursor: 0
+    }
+  }
+
+  /// If true, a message's source isn't hidden; internal messages and those
+  /// without a source are always shown
+  pub fn pass(&self, message: &Message) -> bool {
+    if message.level == Some(LogLevel::Int) || self.hidden.is_empty() {
+      return true;
+    }
+
+    match message.reader_metadata.as_ref().and_then(|m| m.source.as_ref()) {
+      Some(source) => !self.hidden.contains(source),
+      None => true
+    }
+  }
+
+  /// Names of hidden sources, if any
+  pub fn hidden(&self) -> Vec<&str> {
+    self.hidden.iter().map(|s| s.as_str()).collect()
+  }
+}
+
+/// Every source seen so far, in the order they were first seen
+fn sources(state: &RenderState) -> Vec<(String, SourceState)> {
+  state.summary.summary.borrow().sources().to_vec()
+}
+
+/// The names of every source seen so far
+pub fn source_names(state: &RenderState) -> Vec<String> {
+  sources(state).into_iter().map(|(source, _)| source).collect()
+}
+
+/// The sources to hide so only `source` is shown, or none if it's already
+/// shown alone
+fn solo(names: &[String], hidden: &BTreeSet<String>, source: &str) -> BTreeSet<String> {
+  let others: BTreeSet<String> = names.iter().filter(|n| *n != source).cloned().collect();
+  if *hidden == others {
+    BTreeSet::new()
+  } else {
+    others
+  }
+}
+
+/// The range of sources shown in the list, keeping the cursor in view
+fn visible_range(state: &RenderState, len: usize) -> (usize, usize) {
+  let rows = (state.height as usize / 2).max(1);
+  let start = state.sources.cursor.saturating_sub(rows - 1).min(len.saturating_sub(rows));
+
+  (start, (start + rows).min(len))
+}
+
+pub fn render(
+  state: RcState, terminal: &Terminal, cursor: &TerminalCursor
+) -> Result<RcState, Box<dyn Error>> {
+  let width = state.width as usize;
+  let normal = state.style.highlighted.get_base();
+  let selected = state.style.selected.get_base();
+
+  let sources = sources(&state);
+  let (start, end) = visible_range(&state, sources.len());
+  let top = state.height.saturating_sub(1 + (end - start) as u16);
+
+  for (row, (source, source_state)) in sources[start..end].iter().enumerate() {
+    let checked = if state.sources.hidden.contains(source) { " " } else { "x" };
+    let line = format!(" [{}] {} ({})", checked, source, source_state);
+    let line: String = line.chars().take(width).collect();
+    let padding = " ".repeat(width.saturating_sub(line.chars().count()));
+
+    let style = if start + row == state.sources.cursor { selected } else { normal };
+
+    cursor.goto(0, top + row as u16)?;
+    terminal.clear(ClearType::CurrentLine)?;
+    terminal.write(style.paint(format!("{}{}", line, padding)))?;
+  }
+
+  let help = if exec::managed().is_empty() {
+    "sources | space: show/hide | s: solo | a: show all | esc: close"
+  } else {
+    "sources | space: show/hide | s: solo | a: show all | r: restart | x: stop | esc: close"
+  };
+  let help: String = help.chars().take(width).collect();
+  let padding = " ".repeat(width.saturating_sub(help.len()));
+
+  cursor.hide()?;
+  cursor.goto(0, state.height - 1)?;
+  terminal.clear(ClearType::CurrentLine)?;
+  terminal.write(selected.paint(format!("{}{}", help, padding)))?;
+
+  Ok(state)
+}
+
+pub fn input(mut state: RcState, key: &KeyEvent) -> (RcState, InputAction) {
+  state = match key {
+    KeyEvent::Esc | KeyEvent::Char('q') | KeyEvent::Char('\n') => {
+      bar::actions::set_active(state, BarType::Status)
+    },
+    KeyEvent::Up => actions::move_cursor(state, -1),
+    KeyEvent::Down => actions::move_cursor(state, 1),
+    KeyEvent::Char(' ') => actions::toggle_at_cursor(state),
+    KeyEvent::Char('s') => actions::solo_at_cursor(state),
+    KeyEvent::Char('a') => actions::show_all(state),
+    KeyEvent::Char('r') => actions::control_at_cursor(state, Control::Restart),
+    KeyEvent::Char('x') => actions::control_at_cursor(state, Control::Stop),
+    _ => return (state, InputAction::Unhandled)
+  };
+
+  (state, InputAction::Rerender)
+}
+
+pub mod actions {
+  use super::*;
+
+  /// Opens the source list
+  pub fn open(mut state: RcState) -> RcState {
+    let len = sources(&state).len();
+    if len == 0 {
+      return state_actions::internal(state, "no sources yet");
+    }
+
+    let state_mut = Rc::make_mut(&mut state);
+    state_mut.sources.cursor = state_mut.sources.cursor.min(len - 1);
+
+    bar::actions::set_active(state, BarType::Sources)
+  }
+
+  pub fn move_cursor(mut state: RcState, amount: isize) -> RcState {
+    let max = sources(&state).len().saturating_sub(1) as isize;
+    let state_mut = Rc::make_mut(&mut state);
+    let cursor = (state_mut.sources.cursor as isize + amount).max(0).min(max);
+    state_mut.sources.cursor = cursor as usize;
+
+    state
+  }
+
+  /// Replaces the hidden sources, refiltering immediately
+  pub fn set_hidden(mut state: RcState, hidden: BTreeSet<String>) -> RcState {
+    let state_mut = Rc::make_mut(&mut state);
+    state_mut.log.selection = None;
+    state_mut.sources.hidden = hidden;
+
+    state::refilter(state)
+  }
+
+  /// Hides the highlighted source, or shows it again
+  pub fn toggle_at_cursor(state: RcState) -> RcState {
+    let source = match sources(&state).get(state.sources.cursor) {
+      Some((source, _)) => source.clone(),
+      None => return state
+    };
+
+    let mut hidden = state.sources.hidden.clone();
+    if !hidden.remove(&source) {
+      hidden.insert(source);
+    }
+
+    set_hidden(state, hidden)
+  }
+
+  /// Shows only the highlighted source, or shows everything again if it's
+  /// already shown alone
+  pub fn solo_at_cursor(state: RcState) -> RcState {
+    let names = source_names(&state);
+    let source = match names.get(state.sources.cursor) {
+      Some(source) => source.clone(),
+      None => return state
+    };
+
+    let hidden = solo(&names, &state.sources.hidden, &source);
+    set_hidden(state, hidden)
+  }
+
+  /// Checks a source named in a command exists
+  fn find(state: RcState, source: &str) -> Result<RcState, RcState> {
+    let names = source_names(&state);
+    if names.iter().any(|name| name == source) {
+      Ok(state)
+    } else if names.is_empty() {
+      Err(state_actions::internal(state, &format!("unknown source: {}, none are attached", source)))
+    } else {
+      Err(state_actions::internal(state, &format!(
+        "unknown source: {}, expected one of: {}", source, names.join(", ")
+      )))
+    }
+  }
+
+  pub fn hide_source(state: RcState, source: &str) -> RcState {
+    let state = match find(state, source) {
+      Ok(state) => state,
+      Err(state) => return state
+    };
+
+    let mut hidden = state.sources.hidden.clone();
+    hidden.insert(source.to_string());
+    set_hidden(state, hidden)
+  }
+
+  pub fn show_source(state: RcState, source: &str) -> RcState {
+    let state = match find(state, source) {
+      Ok(state) => state,
+      Err(state) => return state
+    };
+
+    let mut hidden = state.sources.hidden.clone();
+    hidden.remove(source);
+    set_hidden(state, hidden)
+  }
+
+  /// Shows only `source`
+  pub fn solo_source(state: RcState, source: &str) -> RcState {
+    let state = match find(state, source) {
+      Ok(state) => state,
+      Err(state) => return state
+    };
+
+    let hidden = source_names(&state).into_iter().filter(|name| name != source).collect();
+    set_hidden(state, hidden)
+  }
+
+  pub fn show_all(state: RcState) -> RcState {
+    set_hidden(state, BTreeSet::new())
+  }
+
+  /// Restarts, stops, or signals a subprocess started with `--exec`; the
+  /// reader reports what happened as it happens
+  pub fn control(state: RcState, source: &str, control: Control) -> RcState {
+    match exec::control(source, control) {
+      Ok(()) => state,
+      Err(e) => state_actions::internal(state, &e.to_string())
+    }
+  }
+
+  pub fn control_at_cursor(state: RcState, request: Control) -> RcState {
+    match source_names(&state).get(state.sources.cursor) {
+      Some(source) => control(state, source, request),
+      None => state
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use spectral::prelude::*;
+
+  #[test]
+  fn test_solo() {
+    let names: Vec<String> = vec!["api".into(), "sidecar".into(), "db".into()];
+    let set = |items: &[&str]| items.iter().map(|s| s.to_string()).collect::<BTreeSet<String>>();
+
+    let hidden = solo(&names, &BTreeSet::new(), "api");
+    assert_that!(hidden).is_equal_to(set(&["sidecar", "db"]));
+
+    // soloing the same source again shows everything
+    assert_that!(solo(&names, &hidden, "api")).is_equal_to(BTreeSet::new());
+
+    // soloing another source switches to it
+    assert_that!(solo(&names, &hidden, "db")).is_equal_to(set(&["api", "sidecar"]));
+  }
+}
@@ -1,21 +1,50 @@
 // (C) Copyright 2019 Hewlett Packard Enterprise Development LP
 
 use std::cell::RefCell;
+use std::collections::BTreeSet;
 use std::rc::{Rc, Weak};
 use std::sync::Arc;
 
-use crate::config::Config;
+use crate::config::{Config, FieldSelection};
 use crate::filter::Filter;
+use crate::history::History;
+use crate::style::StyleConfig;
+use crate::parser::{LogLevel, Message};
 use crate::renderer::types::*;
+use crate::renderer::common::is_repeat;
 
-use super::log::LogState;
+use super::log::{self, LogState};
 use super::bar::BarState;
 use super::filter_bar::FilterBarState;
 use super::search_bar::SearchBarState;
+use super::goto_bar::GotoBarState;
+use super::bookmarks_bar::BookmarksBarState;
+use super::detail_bar::DetailBarState;
+use super::command_bar::CommandBarState;
+use super::help_bar::HelpBarState;
+use super::range_bar::RangeBarState;
+use super::facet_bar::FacetBarState;
+use super::correlate::CorrelateState;
+use super::template_bar::TemplateBarState;
+use super::snapshot::SnapshotState;
+use super::zoom::ZoomState;
+use super::pane::PaneState;
+use super::histogram_bar::HistogramBarState;
+use super::summary::SummaryState;
+use super::pause::PauseState;
+use super::sources_bar::SourcesBarState;
 
 pub struct FilteredEntry {
   pub index: usize,
   pub entry: Weak<MessageEntry>,
+
+  /// The number of consecutive repeats collapsed into this entry, including
+  /// itself
+  pub repeats: usize,
+
+  /// If true, this entry didn't pass the filters but is shown as context
+  /// around one that did
+  pub context: bool
 }
 
 /// shared state between all components
@@ -28,6 +57,9 @@ pub struct FilteredEntry {
 pub struct RenderState {
   pub config: Arc<Config>,
 
+  /// The theme in use, initially from `--style`
+  pub style: Rc<StyleConfig>,
+
   pub width: u16,
   pub height: u16,
 
@@ -40,6 +72,10 @@ pub struct RenderState {
   /// A list of filters used to generated `filtered_entries` from `entries`
   pub filters: Rc<RefCell<Vec<Box<dyn Filter>>>>,
 
+  /// Recent filters and searches, and saved presets, shared with the history
+  /// file
+  pub history: Rc<RefCell<History>>,
+
   /// A Vec of entries filtered from the main list..
   ///
   /// This list contains the subset of entries requested by the user
@@ -54,10 +90,54 @@ pub struct RenderState {
   /// If true, input EoF has been reached
   pub eof: bool,
 
+  /// If true, fields are shown on their own lines beneath each message
+  pub expand_fields: bool,
+
+  /// If true, each message's record number, i.e. its index in `entries` plus
+  /// one, is shown in a gutter
+  pub record_numbers: bool,
+
+  /// If true, runs of repeated messages are collapsed into one
+  pub collapse_repeats: bool,
+
+  /// If true, multi-line messages are collapsed to their first line
+  pub collapse_lines: bool,
+
+  /// Indexes into `entries` of messages expanded or collapsed individually,
+  /// i.e. shown the opposite of `collapse_lines`
+  pub toggled_lines: BTreeSet<usize>,
+
+  /// If set, only messages at least this severe are shown, along with
+  /// internal messages
+  pub min_level: Option<LogLevel>,
+
+  /// The number of messages shown before and after each one passing the
+  /// filters, like `grep -C`
+  pub context: usize,
+
+  /// The fields to display, and their maximum widths
+  pub field_selection: FieldSelection,
+
   pub log: LogState,
+  pub panes: PaneState,
   pub bar: BarState,
   pub filter: FilterBarState,
-  pub search: SearchBarState
+  pub search: SearchBarState,
+  pub goto: GotoBarState,
+  pub bookmarks: BookmarksBarState,
+  pub sources: SourcesBarState,
+  pub detail: DetailBarState,
+  pub command: CommandBarState,
+  pub help: HelpBarState,
+  pub range: RangeBarState,
+  pub facet: FacetBarState,
+  pub histogram: HistogramBarState,
+  pub summary: SummaryState,
+  pub pause: PauseState,
+  pub snapshot: SnapshotState,
+  pub zoom: ZoomState,
+  pub correlate: CorrelateState,
+  pub templates: TemplateBarState
 }
 
 /// A RenderState wrapped in a Cow for perf reasons
@@ -69,6 +149,15 @@ pub type RcState = Rc<RenderState>;
 impl RenderState {
   pub fn new(config: Arc<Config>) -> Self {
     RenderState {
+      expand_fields: config.expand_fields,
+      record_numbers: config.record_numbers,
+      collapse_repeats: config.collapse_repeats,
+      collapse_lines: true,
+      toggled_lines: BTreeSet::new(),
+      min_level: None,
+      context: 0,
+      field_selection: config.field_selection(),
+      style: Rc::new(config.style.clone()),
       config,
 
       width: 0,
@@ -76,6 +165,7 @@ impl RenderState {
 
       entries: Rc::new(RefCell::new(Vec::new())),
       filters: Rc::new(RefCell::new(Vec::new())),
+      history: Rc::new(RefCell::new(History::default())),
       filtered_entries: Rc::new(RefCell::new(Vec::new())),
 
       highlight_filter: None,
@@ -83,21 +173,63 @@ impl RenderState {
       eof: false,
 
       log: LogState::new(),
+      panes: PaneState::new(),
       bar: BarState::new(),
       filter: FilterBarState::new(),
-      search: SearchBarState::new()
+      search: SearchBarState::new(),
+      goto: GotoBarState::new(),
+      bookmarks: BookmarksBarState::new(),
+      sources: SourcesBarState::new(),
+      detail: DetailBarState::new(),
+      command: CommandBarState::new(),
+      help: HelpBarState::new(),
+      range: RangeBarState::new(),
+      facet: FacetBarState::new(),
+      histogram: HistogramBarState::new(),
+      summary: SummaryState::new(),
+      pause: PauseState::new(),
+      snapshot: SnapshotState::new(),
+      zoom: ZoomState::new(),
+      correlate: CorrelateState::new(),
+      templates: TemplateBarState::new()
     }
   }
 }
 
+/// If true, a message passes a severity threshold
+///
+/// Messages without a level, e.g. plain text, are hidden, but internal
+/// messages are always shown.
+fn level_pass(min_level: Option<LogLevel>, message: &Message) -> bool {
+  match (min_level, message.level) {
+    (None, _) | (_, Some(LogLevel::Int)) => true,
+    (Some(_), Some(LogLevel::Plain)) | (Some(_), None) => false,
+    (Some(min), Some(level)) => level >= min
+  }
+}
+
 pub fn filter_pass(state: RcState, entry: &MessageEntry) -> bool {
+  filter_pass_except(&state, &entry.message, None)
+}
+
+/// If true, a message passes the severity threshold and every filter but any
+/// with the query `skip`, e.g. to count what that filter alone hides
+pub fn filter_pass_except(state: &RenderState, message: &Message, skip: Option<&str>) -> bool {
+  if !level_pass(state.min_level, message) {
+    return false;
+  }
+
   let filters = state.filters.borrow();
   if filters.is_empty() {
     return true;
   }
 
   for filter in filters.iter() {
-    if !filter.filter(&entry.message) {
+    if Some(filter.query()) == skip {
+      continue;
+    }
+
+    if !filter.filter(message) {
       return false;
     }
   }
@@ -105,6 +237,147 @@ pub fn filter_pass(state: RcState, entry: &MessageEntry) -> bool {
   true
 }
 
+/// If true, a message is in a shown source and inside the zoomed window, so
+/// it's considered for filtering at all
+pub fn visible(state: &RenderState, message: &Message) -> bool {
+  state.sources.pass(message) && state.zoom.pass(message)
+}
+
+/// Appends an entry that passed all filters, or is context for one that did,
+/// collapsing it into the last one if it's a repeat
+fn push_filtered(
+  state: &RenderState, filtered: &mut Vec<FilteredEntry>, index: usize,
+  entry: &Rc<MessageEntry>, context: bool
+) {
+  if state.collapse_repeats {
+    if let Some(last) = filtered.last_mut() {
+      let repeat = last.context == context && last.entry.upgrade()
+        .map_or(false, |e| is_repeat(&e.message, &entry.message));
+
+      if repeat {
+        last.repeats += 1;
+        return;
+      }
+    }
+  }
+
+  filtered.push(FilteredEntry {
+    index,
+    entry: Rc::downgrade(entry),
+    repeats: 1,
+    context
+  });
+}
+
+/// The most context messages shown around each match
+const MAX_CONTEXT: usize = 20;
+
+/// If true, context is shown around messages passing the filters
+fn context_active(state: &RenderState) -> bool {
+  state.context > 0 && (state.min_level.is_some() || !state.filters.borrow().is_empty())
+}
+
+/// Considers the entry at `index` for a filtered list, adding it if it passes
+/// the filters or is within `state.context` entries of one that does
+///
+/// Only entries for which `candidate` is true count toward the context, e.g.
+/// those belonging to a pane.
+fn push_candidate(
+  state: &RenderState, filtered: &mut Vec<FilteredEntry>, entries: &[Rc<MessageEntry>],
+  index: usize, pass: bool, candidate: &dyn Fn(&Message) -> bool
+) {
+  if !context_active(state) {
+    if pass {
+      push_filtered(state, filtered, index, &entries[index], false);
+    }
+
+    return;
+  }
+
+  if pass {
+    // entries since the last one shown lead up to this one
+    let shown = filtered.last().map_or(0, |f| f.index + 1);
+    let mut before: Vec<usize> = (shown..index).rev()
+      .filter(|i| candidate(&entries[*i].message))
+      .take(state.context)
+      .collect();
+    before.reverse();
+
+    for i in before {
+      push_filtered(state, filtered, i, &entries[i], true);
+    }
+
+    push_filtered(state, filtered, index, &entries[index], false);
+  } else {
+    // context after the last match, which leading context is always followed by
+    let trailing: Vec<&FilteredEntry> = filtered.iter().rev().take_while(|f| f.context).collect();
+    let count: usize = trailing.iter().map(|f| f.repeats).sum();
+
+    if trailing.len() < filtered.len() && count < state.context {
+      push_filtered(state, filtered, index, &entries[index], true);
+    }
+  }
+}
+
+/// Rebuilds `filtered_entries`, and each pane's, after the filters or
+/// collapsing change
+pub fn refilter(mut state: RcState) -> RcState {
+  if !state.panes.is_split() {
+    let mut filtered = Vec::new();
+    let entries = state.entries.borrow();
+    for (i, entry) in entries.iter().enumerate() {
+      if !visible(&state, &entry.message) {
+        continue;
+      }
+
+      let pass = filter_pass(Rc::clone(&state), entry);
+      push_candidate(&state, &mut filtered, &entries, i, pass, &|m| visible(&state, m));
+    }
+
+    drop(entries);
+
+    *state.filtered_entries.borrow_mut() = filtered;
+    state.search.invalidate();
+    state.histogram.invalidate();
+    log::invalidate_layout(&state);
+
+    return state;
+  }
+
+  let mut filtered: Vec<Vec<FilteredEntry>> = state.panes.panes.iter().map(|_| Vec::new()).collect();
+  let entries = state.entries.borrow();
+  for (i, entry) in entries.iter().enumerate() {
+    if !visible(&state, &entry.message) {
+      continue;
+    }
+
+    let pass = filter_pass(Rc::clone(&state), entry);
+
+    for (pane, filtered) in state.panes.panes.iter().zip(filtered.iter_mut()) {
+      if pane.pass(&entry.message) {
+        push_candidate(
+          &state, filtered, &entries, i, pass, &|m| pane.pass(m) && visible(&state, m)
+        );
+      }
+    }
+  }
+
+  drop(entries);
+
+  // the focused pane's entries are shared with `filtered_entries`
+  let state_mut = Rc::make_mut(&mut state);
+  for (pane, filtered) in state_mut.panes.panes.iter_mut().zip(filtered) {
+    *pane.filtered_entries.borrow_mut() = filtered;
+    pane.log.selection = None;
+  }
+
+  state.search.invalidate();
+  state.histogram.invalidate();
+  log::invalidate_layout(&state);
+
+  state
+}
+
 pub mod actions {
   use super::*;
 
@@ -115,16 +388,7 @@ pub mod actions {
     // TODO: figure out how to keep the selection while adjusting filters
     state_mut.log.selection = None;
 
-    *state.filtered_entries.borrow_mut() = state.entries.borrow().iter()
-      .enumerate()
-      .filter(|(_, e)| filter_pass(Rc::clone(&state), e))
-      .map(|(i, e)| FilteredEntry {
-        index: i,
-        entry: Rc::downgrade(e)
-      })
-      .collect();
-
-    state
+    refilter(state)
   }
 
   pub fn pop_filter(mut state: RcState) -> RcState {
@@ -133,30 +397,45 @@ pub mod actions {
 
     state.filters.borrow_mut().pop();
 
-    let new_filtered = if state.filters.borrow().is_empty() {
-      state.entries.borrow().iter()
-        .enumerate()
-        .filter(|(_, e)| filter_pass(Rc::clone(&state), e))
-        .map(|(i, e)| FilteredEntry {
-          index: i,
-          entry: Rc::downgrade(e)
-        })
-        .collect()
+    refilter(state)
+  }
+
+  /// Sets or clears the severity threshold
+  pub fn set_min_level(mut state: RcState, level: Option<LogLevel>) -> RcState {
+    let state_mut = Rc::make_mut(&mut state);
+    state_mut.log.selection = None;
+    state_mut.min_level = level;
+
+    refilter(state)
+  }
 
+  /// Changes the number of context messages shown around each match
+  pub fn adjust_context(mut state: RcState, amount: isize) -> RcState {
+    let context = (state.context as isize + amount).max(0).min(MAX_CONTEXT as isize) as usize;
+    if context == state.context {
+      return state;
+    }
+
+    let state_mut = Rc::make_mut(&mut state);
+    state_mut.log.selection = None;
+    state_mut.context = context;
+
+    let state = refilter(state);
+    if context_active(&state) {
+      state
     } else {
-      state.entries.borrow().iter()
-        .enumerate()
-        .filter(|(_, e)| filter_pass(Rc::clone(&state), e))
-        .map(|(i, e)| FilteredEntry {
-          index: i,
-          entry: Rc::downgrade(e)
-        })
-        .collect()
-    };
+      internal(state, &format!("{} messages of context will be shown once filtering", context))
+    }
+  }
 
-    *state.filtered_entries.borrow_mut() = new_filtered;
+  /// Removes all filters, showing every message again
+  pub fn clear_filters(mut state: RcState) -> RcState {
+    let state_mut = Rc::make_mut(&mut state);
+    state_mut.log.selection = None;
 
-    state
+    state.filters.borrow_mut().clear();
+
+    refilter(state)
   }
 
   /// updates the temp filter based on user input
@@ -179,14 +458,25 @@ pub mod actions {
       // this mut borrow needs to be dropped so we can return state
       let mut entries = state.entries.borrow_mut();
 
-      if filter_pass(Rc::clone(&state), &entry) {
-        entries.push(Rc::new(entry));
-        state.filtered_entries.borrow_mut().push(FilteredEntry {
-          index: entries.len() - 1,
-          entry: Rc::downgrade(&entries[entries.len() - 1]),
-        });
-      } else {
-        entries.push(Rc::new(entry));
+      let pass = filter_pass(Rc::clone(&state), &entry);
+      entries.push(Rc::new(entry));
+
+      let index = entries.len() - 1;
+      // messages from hidden sources, or outside the zoomed window, are kept
+      // for when they're shown again
+      let shown = visible(&state, &entries[index].message);
+      if shown && state.panes.is_split() {
+        for pane in state.panes.panes.iter().filter(|p| p.pass(&entries[index].message)) {
+          push_candidate(
+            &state, &mut pane.filtered_entries.borrow_mut(), &entries, index, pass,
+            &|m| pane.pass(m) && visible(&state, m)
+          );
+        }
+      } else if shown {
+        push_candidate(
+          &state, &mut state.filtered_entries.borrow_mut(), &entries, index, pass,
+          &|m| visible(&state, m)
+        );
       }
     }
 
@@ -198,9 +488,67 @@ pub mod actions {
   }
 
   pub fn set_eof(mut state: RcState, eof: bool) -> RcState {
+    if eof {
+      state.summary.summary.borrow_mut().end();
+    }
+
     let state_mut = Rc::make_mut(&mut state);
     state_mut.eof = eof;
 
     state
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use spectral::prelude::*;
+
+  #[test]
+  fn test_level_pass() {
+    let message = |level| Message {
+      level,
+      ..MessageEntry::internal("test").message
+    };
+
+    assert_that!(level_pass(None, &message(None))).is_true();
+    assert_that!(level_pass(Some(LogLevel::Warning), &message(Some(LogLevel::Error)))).is_true();
+    assert_that!(level_pass(Some(LogLevel::Warning), &message(Some(LogLevel::Info)))).is_false();
+    assert_that!(level_pass(Some(LogLevel::Warning), &message(Some(LogLevel::Plain)))).is_false();
+    assert_that!(level_pass(Some(LogLevel::Warning), &message(None))).is_false();
+    assert_that!(level_pass(Some(LogLevel::Fatal), &message(Some(LogLevel::Int)))).is_true();
+  }
+
+  #[test]
+  fn test_context() {
+    use structopt::StructOpt;
+
+    let config = Arc::new(Config::from_iter_safe(vec![""]).unwrap());
+    let mut state = Rc::new(RenderState::new(config));
+    Rc::make_mut(&mut state).min_level = Some(LogLevel::Error);
+    Rc::make_mut(&mut state).context = 1;
+
+    let levels = [
+      LogLevel::Info, LogLevel::Info, LogLevel::Error, LogLevel::Info,
+      LogLevel::Info, LogLevel::Info, LogLevel::Error
+    ];
+
+    for level in levels.iter() {
+      let mut entry = MessageEntry::internal("test");
+      entry.message.level = Some(*level);
+      state = actions::add_entry(state, entry);
+    }
+
+    let shown = |state: &RcState| -> Vec<(usize, bool)> {
+      state.filtered_entries.borrow().iter().map(|f| (f.index, f.context)).collect()
+    };
+
+    let expected = vec![(1, true), (2, false), (3, true), (5, true), (6, false)];
+    assert_that!(shown(&state)).is_equal_to(expected.clone());
+
+    // refiltering from scratch gives the same result
+    state = refilter(state);
+    assert_that!(shown(&state)).is_equal_to(expected);
+  }
+}
@@ -1,34 +1,152 @@
 // (C) Copyright 2019 Hewlett Packard Enterprise Development LP
 
 use std::error::Error;
+use std::rc::Rc;
 
 use crossterm::{Terminal, TerminalCursor, KeyEvent, ClearType};
 
 use crate::clip::{clip, clipboard_enabled};
+use crate::parser::LogLevel;
+use crate::reader;
 use crate::renderer::interactive::InputAction;
+use crate::renderer::interactive::appearance;
 use crate::renderer::interactive::bar::{self, BarType};
+use crate::renderer::interactive::bookmarks_bar;
+use crate::renderer::interactive::command_bar;
+use crate::renderer::interactive::correlate;
+use crate::renderer::interactive::template_bar;
+use crate::renderer::interactive::detail_bar;
+use crate::renderer::interactive::help_bar;
+use crate::renderer::interactive::histogram_bar;
+use crate::renderer::interactive::keymap::Action;
 use crate::renderer::interactive::log;
-use crate::renderer::interactive::state::RcState;
+use crate::renderer::interactive::pane;
+use crate::renderer::interactive::pause;
+use crate::renderer::interactive::range_bar;
+use crate::renderer::interactive::search_bar;
+use crate::renderer::interactive::sources_bar;
+use crate::renderer::interactive::state::{self, RcState};
 use crate::renderer::interactive::state::actions as state_actions;
-use crate::renderer::plain::plain_render;
+use crate::renderer::interactive::summary;
+use crate::renderer::interactive::zoom;
+use crate::renderer::markdown::{MarkdownFormat, markdown_code, markdown_table, table_columns};
+use crate::renderer::types::MessageEntry;
+use crate::timezone::{self, RelativeTime};
 
-fn format_left(state: &RcState) -> (usize, String) {
-  let mut buf = String::new();
-  buf.push_str("q: quit | f: filter | /: find");
+/// Adds a hint for some actions, e.g. `n/N: next/prev match`, unless any of
+/// them are unbound
+fn push_hint(
+  hints: &mut Vec<String>, state: &RcState, actions: &[Action], separator: &str, label: &str
+) {
+  let keymap = &state.config.file().keys;
+  let keys: Option<Vec<String>> = actions.iter().map(|a| keymap.key_name(*a)).collect();
+
+  if let Some(keys) = keys {
+    hints.push(format!("{}: {}", keys.join(separator), label));
+  }
+}
+
+/// Hints for the keys that apply right now, most useful first: the basics,
+/// then anything specific to the current state, then general toggles
+fn hints(state: &RcState) -> Vec<String> {
+  let mut hints = Vec::new();
+  push_hint(&mut hints, state, &[Action::Help], "", "help");
+  push_hint(&mut hints, state, &[Action::Quit], "", "quit");
+  push_hint(&mut hints, state, &[Action::Filter], "", "filter");
+  push_hint(&mut hints, state, &[Action::Search], "", "find");
 
-  if clipboard_enabled() {
-    if state.log.selection.is_some() {
-      buf.push_str(" | c: copy msg");
+  if state.pause.is_paused() {
+    push_hint(&mut hints, state, &[Action::Pause, Action::Replay], "/", "live/replay");
+  }
+
+  if state.search.is_active() {
+    push_hint(&mut hints, state, &[Action::NextMatch, Action::PrevMatch], "/", "next/prev match");
+  }
+
+  let copy = clipboard_enabled();
+  if state.log.selection.is_some() {
+    if copy {
+      push_hint(
+        &mut hints, state, &[Action::Copy, Action::CopyRaw, Action::CopyJson],
+        "/", "copy msg/raw/json"
+      );
+      push_hint(&mut hints, state, &[Action::Mark], "", "mark range");
     }
 
-    buf.push_str(" | S-c: copy screen");
+    push_hint(&mut hints, state, &[Action::Details], "", "details");
+    push_hint(&mut hints, state, &[Action::Follow], "", "follow");
+    push_hint(&mut hints, state, &[Action::Bookmark], "", "bookmark");
+  }
+
+  if state.log.mark.is_some() {
+    push_hint(&mut hints, state, &[Action::RangeSummary], "", "summarize range");
+    push_hint(&mut hints, state, &[Action::Zoom], "", "zoom to range");
+  }
+
+  if state.zoom.window().is_some() {
+    push_hint(&mut hints, state, &[Action::ZoomOut], "", "zoom out");
   }
 
   if !state.filters.borrow().is_empty() {
-    buf.push_str(" | p: pop filter");
+    push_hint(&mut hints, state, &[Action::PopFilter], "", "pop filter");
+    push_hint(&mut hints, state, &[Action::ClearFilters], "", "clear filters");
+    push_hint(&mut hints, state, &[Action::MoreContext, Action::LessContext], "/", "context");
+  }
+
+  if !state.bookmarks.is_empty() {
+    push_hint(
+      &mut hints, state, &[Action::PrevBookmark, Action::NextBookmark],
+      "/", "prev/next bookmark"
+    );
+    push_hint(&mut hints, state, &[Action::Bookmarks], "", "bookmarks");
+  }
+
+  if state.summary.summary.borrow().sources().len() > 1 {
+    push_hint(&mut hints, state, &[Action::Sources], "", "sources");
+  }
+
+  if state.panes.is_split() {
+    push_hint(&mut hints, state, &[Action::NextPane], "", "next pane");
+    push_hint(&mut hints, state, &[Action::MergePanes], "", "merge panes");
   }
 
-  (buf.len(), buf)
+  if copy {
+    push_hint(&mut hints, state, &[Action::CopyScreen], "", "copy screen");
+  }
+
+  push_hint(&mut hints, state, &[Action::GoTo], "", "go to");
+  push_hint(&mut hints, state, &[Action::Command], "", "command");
+  if !state.pause.is_paused() {
+    push_hint(&mut hints, state, &[Action::Pause], "", "pause");
+  }
+  push_hint(&mut hints, state, &[Action::MinLevelDebug, Action::MinLevelFatal], "-", "level");
+  push_hint(&mut hints, state, &[Action::ToggleTime], "", "time");
+  push_hint(&mut hints, state, &[Action::ToggleExpand], "", "expand");
+  push_hint(&mut hints, state, &[Action::ToggleRepeats], "", "repeats");
+  push_hint(&mut hints, state, &[Action::ToggleHistogram], "", "histogram");
+  push_hint(&mut hints, state, &[Action::ToggleSummary], "", "summary");
+
+  hints
+}
+
+/// Joins as many hints as fit in `width`, most useful first; the first (help)
+/// is always kept, even if it doesn't fit
+fn format_left(state: &RcState, width: usize) -> (usize, String) {
+  let mut buf = String::new();
+  let mut len = 0;
+  for hint in hints(state) {
+    let hint_len = hint.chars().count();
+    if buf.is_empty() {
+      buf.push_str(&hint);
+      len = hint_len;
+    } else if len + 3 + hint_len <= width {
+      buf.push_str(" | ");
+      buf.push_str(&hint);
+      len += 3 + hint_len;
+    }
+  }
+
+  (len, buf)
 }
 
 pub fn format_right(state: &RcState) -> (usize, String) {
@@ -54,45 +172,76 @@ pub fn format_right(state: &RcState) -> (usize, String) {
     format!("{}", len_filtered_entries)
   };
 
+  let matches = match search_bar::match_position(state) {
+    Some((Some(position), total)) => format!("match {} / {} | ", position, total),
+    Some((None, total)) => format!("{} match{} | ", total, if total == 1 { "" } else { "es" }),
+    None => String::new()
+  };
+
+  let paused = if state.pause.is_paused() {
+    format!(" (paused, +{} pending)", state.pause.pending())
+  } else if state.pause.is_replaying() {
+    format!(" (replaying, {} pending)", state.pause.pending())
+  } else {
+    match log::new_since_paused(state) {
+      Some(0) => " (paused)".to_string(),
+      Some(new) => format!(" (paused, {} new)", new),
+      None => String::new()
+    }
+  };
+
+  let zoom = match state.zoom.window() {
+    Some(window) => format!(" (zoomed to {})", window.describe()),
+    None => String::new()
+  };
+
+  let level = match state.min_level {
+    Some(level) => format!(" ({}+)", level.to_string().to_lowercase()),
+    None => String::new()
+  };
+
+  let context = if state.context > 0 && (len_filters > 0 || state.min_level.is_some()) {
+    format!(" (±{} context)", state.context)
+  } else {
+    String::new()
+  };
+
+  let dropped = match reader::filter::dropped() {
+    0 => String::new(),
+    dropped => format!(" ({} dropped)", dropped)
+  };
+
+  let sampled = match reader::filter::sampled_out() {
+    0 => String::new(),
+    sampled => format!(" ({} sampled out)", sampled)
+  };
+
   // this will need to change if any parts are styled in the future
-  let right = format!("{}{}{}", count, filters, eof);
-  (right.len(), right)
+  let right = format!(
+    "{}{}{}{}{}{}{}{}{}{}",
+    matches, count, paused, zoom, level, context, filters, dropped, sampled, eof
+  );
+  (right.chars().count(), right)
 }
 
 pub fn render(
   state: RcState, terminal: &Terminal, cursor: &TerminalCursor
 ) -> Result<RcState, Box<dyn Error>> {
-  let (left_len, left) = format_left(&state);
+  let width = state.width as usize;
   let (right_len, right) = format_right(&state);
 
-  let profile = &state.config.style.selected;
-  let style = profile.get_base();
-
-  // prioritize showing the right-side content as the left is just help text
-  let mut buf = String::new();
-
-  let combined_len = left_len + right_len;
-  if let Some(spacer) = state.width.checked_sub(combined_len as u16) {
-    // room for both sides
-    buf.push_str(&left);
-    for _ in 0..spacer {
-      buf.push(' ');
-    }
+  // the right side takes priority over all but the first hint, as the rest
+  // are just help text
+  let (left_len, left) = format_left(&state, width.saturating_sub(right_len + 1));
 
-    buf.push_str(&right);
-  } else if let Some(spacer) = state.width.checked_sub(right_len as u16) {
-    // only room for right
-    for _ in 0..spacer {
-      buf.push(' ');
-    }
+  let profile = &state.style.selected;
+  let style = profile.get_base();
 
-    buf.push_str(&right);
-  } else {
-    // just fill
-    for _ in 0..state.width {
-      buf.push(' ');
-    }
-  }
+  let spacer = width.saturating_sub(left_len + right_len).max(1);
+  let buf: String = format!("{}{}{}", left, " ".repeat(spacer), right)
+    .chars()
+    .take(width)
+    .collect();
 
   cursor.hide()?;
   cursor.goto(0, state.height - 1)?;
@@ -103,41 +252,127 @@ pub fn render(
 }
 
 pub fn input(mut state: RcState, key: &KeyEvent) -> (RcState, InputAction) {
-  state = match key {
-    KeyEvent::Esc => {
-      if state.log.selection.is_some() {
+  // ctrl-c always copies or quits, like an interrupt
+  if let KeyEvent::Ctrl('c') = key {
+    if state.log.selection.is_none() {
+      return (state, InputAction::Exit);
+    }
+
+    let state = actions::copy_selection(state, actions::CopyFormat::Plain);
+    return (state, InputAction::Rerender);
+  }
+
+  let action = match state.config.file().keys.action(key) {
+    Some(action) => action,
+    None => return (state, InputAction::Unhandled)
+  };
+
+  state = match action {
+    Action::Up => log::actions::move_selection(state, 1),
+    Action::Down => log::actions::move_selection(state, -1),
+    Action::PageUp => log::actions::move_selection_page_up(state),
+    Action::PageDown => log::actions::move_selection_page_down(state),
+    Action::Top => log::actions::move_selection_to_top(state),
+    Action::Bottom => log::actions::clear_selection(state),
+    Action::Back => {
+      if state.log.mark.is_some() {
+        log::actions::toggle_mark(state)
+      } else if state.log.selection.is_some() {
         log::actions::clear_selection(state)
       } else {
         return (state, InputAction::Exit)
       }
-    }
-    KeyEvent::Char(c) => match c {
-      'q' => return (state, InputAction::Exit),
-      '|' | 'f' => bar::actions::set_active(state, BarType::Filter),
-      '/' => bar::actions::set_active(state, BarType::Search),
-      'p' => {
-        if state.filters.borrow().is_empty() {
-          state_actions::internal(state, "no filters to remove")
-        } else {
-          state_actions::pop_filter(state)
-        }
-      },
-      'c' => actions::copy_selection(state),
-      'C' => actions::copy_view(state),
-      _ => return (state, InputAction::Unhandled)
     },
-    KeyEvent::Ctrl(c) => match c {
-      'c' => {
-        if state.log.selection.is_some() {
-          actions::copy_selection(state)
-        } else {
-          return (state, InputAction::Exit)
-        }
-      },
-      'f' => bar::actions::set_active(state, BarType::Search),
-      _ => return (state, InputAction::Unhandled)
-    }
-    _ => return (state, InputAction::Unhandled)
+    Action::Quit => return (state, InputAction::Exit),
+    Action::Help => help_bar::actions::open(state),
+    Action::Details => detail_bar::actions::open(state),
+    Action::RangeSummary => range_bar::actions::open(state),
+    Action::Filter => bar::actions::set_active(state, BarType::Filter),
+    Action::Search => bar::actions::set_active(state, BarType::Search),
+    Action::GoTo => bar::actions::set_active(state, BarType::Goto),
+    Action::Histogram => histogram_bar::actions::open(state),
+    Action::Zoom => zoom::actions::zoom_to_range(state),
+    Action::ZoomOut => zoom::actions::zoom_out(state),
+    Action::ToggleHistogram => histogram_bar::actions::toggle(state),
+    Action::ToggleSummary => summary::actions::toggle(state),
+    Action::CycleTheme => appearance::actions::cycle_theme(state),
+    Action::ToggleDensity => appearance::actions::toggle_density(state),
+    Action::Command => bar::actions::set_active(state, BarType::Command),
+    Action::Preset => command_bar::actions::open_with(state, "preset "),
+    Action::Follow => log::actions::toggle_follow(state),
+    Action::MoreContext => state_actions::adjust_context(state, 1),
+    Action::LessContext => state_actions::adjust_context(state, -1),
+    Action::Pause => pause::actions::toggle(state),
+    Action::Replay => pause::actions::replay(state),
+    Action::Bookmark => bookmarks_bar::actions::toggle(state),
+    Action::Bookmarks => bookmarks_bar::actions::open(state),
+    Action::Sources => sources_bar::actions::open(state),
+    Action::Facets => command_bar::actions::open_with(state, "facet "),
+    Action::FollowId => correlate::actions::follow(state),
+    Action::Templates => template_bar::actions::open(state),
+    Action::PrevBookmark => bookmarks_bar::actions::jump_next(state, false),
+    Action::NextBookmark => bookmarks_bar::actions::jump_next(state, true),
+    Action::NextError => log::actions::jump_to_level(state, LogLevel::Error, true),
+    Action::PrevError => log::actions::jump_to_level(state, LogLevel::Error, false),
+    Action::NextMatch | Action::PrevMatch if !state.search.is_active() => {
+      let hint = match state.config.file().keys.key_name(Action::Search) {
+        Some(key) => format!("nothing to find, press {} to search", key),
+        None => "nothing to find".to_string()
+      };
+
+      state_actions::internal(state, &hint)
+    },
+    Action::NextMatch => search_bar::actions::next_match(state, false),
+    Action::PrevMatch => search_bar::actions::prev_match(state),
+    Action::PopFilter => {
+      if state.filters.borrow().is_empty() {
+        state_actions::internal(state, "no filters to remove")
+      } else {
+        state_actions::pop_filter(state)
+      }
+    },
+    Action::ClearFilters => {
+      if state.filters.borrow().is_empty() {
+        state_actions::internal(state, "no filters to remove")
+      } else {
+        let count = state.filters.borrow().len();
+        let state = state_actions::clear_filters(state);
+
+        state_actions::internal(state, &format!(
+          "removed {} filter{}", count, if count == 1 { "" } else { "s" }
+        ))
+      }
+    },
+    Action::AllLevels => actions::toggle_min_level(state, None),
+    Action::MinLevelDebug => actions::toggle_min_level(state, Some(LogLevel::Debug)),
+    Action::MinLevelInfo => actions::toggle_min_level(state, Some(LogLevel::Info)),
+    Action::MinLevelWarning => actions::toggle_min_level(state, Some(LogLevel::Warning)),
+    Action::MinLevelError => actions::toggle_min_level(state, Some(LogLevel::Error)),
+    Action::MinLevelFatal => actions::toggle_min_level(state, Some(LogLevel::Fatal)),
+    Action::Copy => actions::copy_selection(state, actions::CopyFormat::Plain),
+    Action::CopyRaw => actions::copy_selection(state, actions::CopyFormat::Raw),
+    Action::CopyJson => actions::copy_selection(state, actions::CopyFormat::Json),
+    Action::Mark => {
+      let state = log::actions::toggle_mark(state);
+      match (state.log.selection, state.log.mark) {
+        (None, _) => state_actions::internal(state, "no message is selected"),
+        (Some(_), Some(_)) => state,
+        (Some(_), None) => state_actions::internal(state, "cleared the marked range")
+      }
+    },
+    Action::CopyScreen => actions::copy_view(state),
+    Action::CopyTable => actions::copy_markdown(state, MarkdownFormat::Table),
+    Action::CopyCode => actions::copy_markdown(state, MarkdownFormat::Code),
+    Action::ToggleTime => actions::toggle_relative_time(state),
+    Action::ToggleExpand => actions::toggle_expand_fields(state),
+    Action::ToggleRecordNumbers => actions::toggle_record_numbers(state),
+    Action::ToggleRepeats => actions::toggle_collapse_repeats(state),
+    Action::ToggleLines => actions::toggle_lines(state),
+    Action::ToggleAllLines => actions::toggle_all_lines(state),
+    Action::SplitSources => pane::actions::split_sources(state),
+    Action::SplitFilter => pane::actions::split_filter(state),
+    Action::NextPane => pane::actions::focus_next(state),
+    Action::MergePanes => pane::actions::merge(state)
   };
 
   (state, InputAction::Rerender)
@@ -146,27 +381,197 @@ pub fn input(mut state: RcState, key: &KeyEvent) -> (RcState, InputAction) {
 pub mod actions {
   use super::*;
 
-  pub fn copy_selection(state: RcState) -> RcState {
+  /// How selected messages are copied
+  #[derive(Debug, Clone, Copy, PartialEq)]
+  pub enum CopyFormat {
+    /// As displayed, without styling
+    Plain,
+
+    /// The original input lines
+    Raw,
+
+    /// As parsed, one JSON object per line
+    Json
+  }
+
+  /// Toggles showing fields on their own lines beneath each message
+  pub fn toggle_expand_fields(mut state: RcState) -> RcState {
+    let state_mut = Rc::make_mut(&mut state);
+    state_mut.expand_fields = !state_mut.expand_fields;
+
+    state
+  }
+
+  pub fn toggle_record_numbers(mut state: RcState) -> RcState {
+    let state_mut = Rc::make_mut(&mut state);
+    state_mut.record_numbers = !state_mut.record_numbers;
+
+    state
+  }
+
+  /// Shows only messages at least as severe as `level`; `None`, or repeating
+  /// the current level, shows all levels
+  pub fn toggle_min_level(state: RcState, level: Option<LogLevel>) -> RcState {
+    let level = if level == state.min_level { None } else { level };
+    let state = state_actions::set_min_level(state, level);
+    let text = match state.min_level {
+      Some(level) => format!("showing {} and above", level.to_string().to_lowercase()),
+      None => "showing all levels".to_string()
+    };
+
+    state_actions::internal(state, &text)
+  }
+
+  /// Collapses or expands runs of repeated messages
+  pub fn toggle_collapse_repeats(mut state: RcState) -> RcState {
+    let state_mut = Rc::make_mut(&mut state);
+    state_mut.collapse_repeats = !state_mut.collapse_repeats;
+    state_mut.log.selection = None;
+
+    state::refilter(state)
+  }
+
+  /// Expands or collapses the selected message, if it has several lines
+  pub fn toggle_lines(mut state: RcState) -> RcState {
+    let index = state.log.selection.and_then(|selection| {
+      state.filtered_entries.borrow().get(selection.rel_index).map(|f| f.index)
+    });
+
+    let index = match index {
+      Some(index) => index,
+      None => return state_actions::internal(state, "select a message to expand or collapse it")
+    };
+
+    let state_mut = Rc::make_mut(&mut state);
+    if !state_mut.toggled_lines.remove(&index) {
+      state_mut.toggled_lines.insert(index);
+    }
+
+    state
+  }
+
+  /// Expands or collapses every multi-line message
+  pub fn toggle_all_lines(mut state: RcState) -> RcState {
+    let state_mut = Rc::make_mut(&mut state);
+    state_mut.collapse_lines = !state_mut.collapse_lines;
+    state_mut.toggled_lines.clear();
+
+    let collapse = state_mut.collapse_lines;
+    state_actions::internal(state, if collapse {
+      "collapsing multi-line messages"
+    } else {
+      "expanding multi-line messages"
+    })
+  }
+
+  /// Cycles timestamps between absolute, relative to now, and relative to the
+  /// first message
+  pub fn toggle_relative_time(state: RcState) -> RcState {
+    let mode = RelativeTime::next(timezone::relative_time());
+    timezone::set_relative_time(mode);
+
+    state_actions::internal(state, match mode {
+      None => "showing absolute timestamps",
+      Some(RelativeTime::Now) => "showing timestamps relative to now",
+      Some(RelativeTime::First) => "showing timestamps relative to the first message"
+    })
+  }
+
+  /// The selected messages: the marked range, or just the selected message
+  /// The selected entries, with their indexes into `entries`
+  fn selected_entries(state: &RcState) -> Vec<(usize, Rc<MessageEntry>)> {
+    let range = match log::selected_range(state) {
+      Some(range) => range,
+      None => return Vec::new()
+    };
+
+    // TODO: handle unset weak ref
+    let filtered_entries = state.filtered_entries.borrow();
+    range.filter_map(|i| filtered_entries.get(i))
+      .filter_map(|e| e.entry.upgrade().map(|entry| (e.index, entry)))
+      .collect()
+  }
+
+  fn copy_text(state: RcState, text: String, description: &str) -> RcState {
+    match clip(text) {
+      Ok(()) => state_actions::internal(
+        state, &format!("copied {} to clipboard", description)
+      ),
+      Err(e) => state_actions::internal(
+        state, &format!("error writing to clipboard: {:?}", e)
+      )
+    }
+  }
+
+  fn describe_count(count: usize, format: &str) -> String {
+    match count {
+      1 => format!("message {}", format),
+      count => format!("{} messages {}", count, format)
+    }
+  }
+
+  /// Copies the selected messages in the given format
+  pub fn copy_selection(state: RcState, format: CopyFormat) -> RcState {
     if !clipboard_enabled() {
       return state;
     }
 
-    if let Some(selection) = state.log.selection {
-      let plain = {
-        let entry = &state.filtered_entries.borrow()[selection.rel_index];
-        plain_render(&entry.entry.upgrade().unwrap())
-      }.join("\n");
+    let entries = selected_entries(&state);
+    if entries.is_empty() {
+      return state_actions::internal(state, "no message is selected");
+    }
+
+    let lines: Vec<String> = match format {
+      CopyFormat::Plain => entries.iter()
+        .flat_map(|(i, e)| log::plain_lines(&state, *i, e))
+        .collect(),
+      CopyFormat::Raw => entries.iter().map(|(_, e)| e.message.raw.clone()).collect(),
+      CopyFormat::Json => entries.iter()
+        .filter_map(|(_, e)| serde_json::to_string(&e.message).ok())
+        .collect()
+    };
+
+    let description = describe_count(entries.len(), match format {
+      CopyFormat::Plain => "as plain text",
+      CopyFormat::Raw => "as raw lines",
+      CopyFormat::Json => "as json"
+    });
+
+    copy_text(state, lines.join("\n"), &description)
+  }
+
+  /// Copies the selected messages, or the current screen if none are
+  /// selected, as a Markdown table or code block
+  pub fn copy_markdown(state: RcState, format: MarkdownFormat) -> RcState {
+    if !clipboard_enabled() {
+      return state;
+    }
 
+    let mut entries = selected_entries(&state);
+    if entries.is_empty() {
       // TODO: handle unset weak ref
-      match clip(plain) {
-        Ok(()) => state_actions::internal(state, "copied message to clipboard"),
-        Err(e) => state_actions::internal(
-          state, &format!("error writing to clipboard: {:?}", e)
-        )
-      }
-    } else {
-      state_actions::internal(state, "no message is selected")
+      let filtered_entries = state.filtered_entries.borrow();
+      entries = (state.log.range_min..=state.log.range_max)
+        .filter_map(|i| filtered_entries.get(i))
+        .map(|e| (e.index, e.entry.upgrade().unwrap()))
+        .collect();
     }
+
+    let lines = match format {
+      MarkdownFormat::Table => {
+        let messages: Vec<_> = entries.iter().map(|(_, e)| &e.message).collect();
+        markdown_table(&messages, &table_columns(&state.config))
+      },
+      MarkdownFormat::Code => {
+        let lines: Vec<String> = entries.iter()
+          .flat_map(|(i, e)| log::plain_lines(&state, *i, e))
+          .collect();
+        markdown_code(&lines)
+      }
+    };
+
+    let description = describe_count(entries.len(), "as markdown");
+    copy_text(state, lines.join("\n"), &description)
   }
 
   pub fn copy_view(state: RcState) -> RcState {
@@ -180,7 +585,7 @@ pub mod actions {
       let entry = &state.filtered_entries.borrow()[i];
 
       // TODO: handle unset weak ref
-      for line in plain_render(&entry.entry.upgrade().unwrap()) {
+      for line in log::plain_lines(&state, entry.index, &entry.entry.upgrade().unwrap()) {
         buf.push_str(&line);
         buf.push('\n');
         lines += 1;
@@ -197,4 +602,32 @@ pub mod actions {
     }
 
   }
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::sync::Arc;
+
+  use spectral::prelude::*;
+  use structopt::StructOpt;
+
+  use crate::config::Config;
+  use crate::renderer::interactive::state::RenderState;
+
+  #[test]
+  fn test_hints_fit() {
+    let config = Arc::new(Config::from_iter_safe(vec![""]).unwrap());
+    let state = Rc::new(RenderState::new(config));
+    let all = hints(&state).join(" | ");
+    assert_that!(all.len()).is_greater_than(80);
+
+    let (len, left) = format_left(&state, 60);
+    assert_that!(len).is_less_than_or_equal_to(60);
+    assert_that!(left.starts_with("?: help | q: quit | f: filter")).is_true();
+    assert_that!(all.starts_with(&left)).is_true();
+
+    // help is never dropped
+    assert_that!(format_left(&state, 0).1).is_equal_to("?: help".to_string());
+  }
+}
@@ -0,0 +1,237 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::rc::Rc;
+
+use crossterm::{Terminal, TerminalCursor, ClearType};
+
+use crate::parser::{LogLevel, Message};
+use crate::renderer::types::{SourceState, dropped_lines};
+
+use super::state::{RcState, RenderState};
+
+/// Levels in the order they're counted, most severe first
+static LEVELS: &[(LogLevel, &str)] = &[
+  (LogLevel::Fatal, "fatal"),
+  (LogLevel::Error, "error"),
+  (LogLevel::Warning, "warn"),
+  (LogLevel::Info, "info"),
+  (LogLevel::Debug, "debug"),
+  (LogLevel::Plain, "plain")
+];
+
+/// Sources and message counts since the session started
+#[derive(Debug, Default)]
+pub struct Summary {
+  /// Known sources and their state, in the order they were first seen
+  sources: Vec<(String, SourceState)>,
+
+  /// Message counts by level, ignoring internal messages
+  levels: BTreeMap<LogLevel, usize>
+}
+
+impl Summary {
+  /// Counts a newly received message
+  pub fn count(&mut self, message: &Message) {
+    if message.level == Some(LogLevel::Int) {
+      return;
+    }
+
+    *self.levels.entry(message.level.unwrap_or(LogLevel::Plain)).or_insert(0) += 1;
+
+    // sources that don't report their state are assumed to be streaming
+    let source = message.reader_metadata.as_ref().and_then(|m| m.source.as_ref());
+    if let Some(source) = source {
+      if !self.sources.iter().any(|(s, _)| s == source) {
+        self.sources.push((source.clone(), SourceState::Streaming));
+      }
+    }
+  }
+
+  /// Known sources and their state, in the order they were first seen
+  pub fn sources(&self) -> &[(String, SourceState)] {
+    &self.sources
+  }
+
+  pub fn set_source(&mut self, source: &str, state: SourceState) {
+    match self.sources.iter_mut().find(|(s, _)| s == source) {
+      Some((_, current)) => *current = state,
+      None => self.sources.push((source.to_string(), state))
+    }
+  }
+
+  /// Marks every streaming source as ended
+  pub fn end(&mut self) {
+    for (_, state) in self.sources.iter_mut() {
+      if *state == SourceState::Streaming {
+        *state = SourceState::Ended;
+      }
+    }
+  }
+
+  /// Formats the summary, e.g.
+  /// `api: streaming, db: dead | 1520 buffered, 3 dropped | error 2, info 1518`
+  ///
+  /// Sources in trouble are listed first so they aren't cut off.
+  fn format(&self, buffered: usize, dropped: usize, filter: Option<&str>) -> String {
+    let mut parts = Vec::new();
+
+    if !self.sources.is_empty() {
+      let mut sources: Vec<&(String, SourceState)> = self.sources.iter().collect();
+      sources.sort_by_key(|(_, state)| match state {
+        SourceState::Dead => 0,
+        SourceState::Reconnecting => 1,
+        SourceState::Streaming | SourceState::Ended => 2
+      });
+
+      parts.push(sources.iter()
+        .map(|(source, state)| format!("{}: {}", source, state))
+        .collect::<Vec<String>>()
+        .join(", "));
+    }
+
+    parts.push(format!("{} buffered, {} dropped", buffered, dropped));
+
+    if let Some(filter) = filter {
+      parts.push(format!("filter: {}", filter));
+    }
+
+    let levels: Vec<String> = LEVELS.iter()
+      .filter_map(|(level, name)| self.levels.get(level).map(|count| format!("{} {}", name, count)))
+      .collect();
+
+    if !levels.is_empty() {
+      parts.push(levels.join(", "));
+    }
+
+    format!(" {}", parts.join(" | "))
+  }
+}
+
+/// A line above the bar summarizing source health, drops, the active filter,
+/// and message counts by level
+#[derive(Clone)]
+pub struct SummaryState {
+  /// If true, the line is shown
+  pub visible: bool,
+
+  pub summary: Rc<RefCell<Summary>>
+}
+
+impl SummaryState {
+  pub fn new() -> Self {
+    SummaryState {
+      visible: true,
+      summary: Rc::new(RefCell::new(Summary::default()))
+    }
+  }
+}
+
+/// Describes the filters applied to the focused view, if any
+fn describe_filter(state: &RenderState) -> Option<String> {
+  let mut parts: Vec<String> = state.filters.borrow().iter().map(|f| f.describe()).collect();
+  if let Some(level) = state.min_level {
+    parts.push(format!("{}+", level.to_string().to_lowercase()));
+  }
+
+  let hidden = state.sources.hidden();
+  if !hidden.is_empty() {
+    parts.push(format!("hiding {}", hidden.join(", ")));
+  }
+
+  if parts.is_empty() {
+    None
+  } else {
+    Some(parts.join(" and "))
+  }
+}
+
+/// Renders the summary line just above the bar, if it's visible
+pub fn render_line(
+  state: &RenderState, terminal: &Terminal, cursor: &TerminalCursor
+) -> Result<(), Box<dyn Error>> {
+  if !state.summary.visible || state.height < 2 {
+    return Ok(());
+  }
+
+  let line = state.summary.summary.borrow().format(
+    state.entries.borrow().len() + state.pause.pending(),
+    dropped_lines(),
+    describe_filter(state).as_deref()
+  );
+
+  let width = state.width as usize;
+  let line: String = line.chars().take(width).collect();
+  let padding = " ".repeat(width.saturating_sub(line.chars().count()));
+
+  cursor.goto(0, state.height - 2)?;
+  terminal.clear(ClearType::CurrentLine)?;
+  terminal.write(state.style.highlighted.get_base().paint(format!("{}{}", line, padding)))?;
+
+  Ok(())
+}
+
+pub mod actions {
+  use super::*;
+
+  pub fn toggle(mut state: RcState) -> RcState {
+    let state_mut = Rc::make_mut(&mut state);
+    state_mut.summary.visible = !state_mut.summary.visible;
+
+    state
+  }
+
+  pub fn set_source(state: RcState, source: &str, source_state: SourceState) -> RcState {
+    state.summary.summary.borrow_mut().set_source(source, source_state);
+
+    state
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::collections::HashMap;
+
+  use spectral::prelude::*;
+
+  use crate::parser::{MessageKind, ReaderMetadata};
+
+  fn message(level: Option<LogLevel>, source: &str) -> Message {
+    Message {
+      kind: MessageKind::Plain,
+      timestamp: None,
+      level,
+      raw: String::new(),
+      text: None,
+      metadata: HashMap::new(),
+      reader_metadata: Some(ReaderMetadata { timestamp: None, source: Some(source.to_string()) }),
+      mapped_fields: HashMap::new()
+    }
+  }
+
+  #[test]
+  fn test_format() {
+    let mut summary = Summary::default();
+    assert_that!(summary.format(0, 0, None)).is_equal_to(" 0 buffered, 0 dropped".to_string());
+
+    summary.count(&message(Some(LogLevel::Info), "api"));
+    summary.count(&message(Some(LogLevel::Error), "db"));
+    summary.count(&message(Some(LogLevel::Info), "api"));
+    summary.count(&message(Some(LogLevel::Int), "internal"));
+    summary.set_source("db", SourceState::Dead);
+
+    assert_that!(summary.format(4, 2, Some("text: foo"))).is_equal_to(
+      " db: dead, api: streaming | 4 buffered, 2 dropped | filter: text: foo | error 1, info 2"
+        .to_string()
+    );
+
+    summary.end();
+    assert_that!(summary.format(4, 2, None)).is_equal_to(
+      " db: dead, api: ended | 4 buffered, 2 dropped | error 1, info 2".to_string()
+    );
+  }
+}
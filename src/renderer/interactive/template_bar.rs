@@ -0,0 +1,355 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+use std::error::Error;
+use std::rc::Rc;
+
+use crossterm::{Terminal, TerminalCursor, KeyEvent, ClearType};
+
+use crate::filter::{ExpressionFilter, Filter, FilterMode};
+use crate::parser::{LogLevel, MessageKind};
+use crate::renderer::grouping::{TemplateGroup, TemplateGroups, template_regex};
+
+use super::facet_bar::quote;
+use super::state::{self, RcState, RenderState};
+use super::state::actions as state_actions;
+use super::bar::{self, BarType};
+use super::log;
+use super::InputAction;
+
+/// The template list overlay, grouping messages in view that differ only in
+/// IDs, addresses, and numbers
+#[derive(Clone)]
+pub struct TemplateBarState {
+  /// Templates and their counts when opened, most common first
+  groups: Rc<Vec<TemplateGroup>>,
+
+  /// The number of messages counted
+  total: usize,
+
+  /// The highlighted template in the list, by position
+  cursor: usize,
+
+  /// If true, only error and fatal messages are counted
+  errors_only: bool,
+
+  /// The query of the filter showing only one template, if any
+  only: Option<String>
+}
+
+impl TemplateBarState {
+  pub fn new() -> Self {
+    TemplateBarState {
+      groups: Rc::new(Vec::new()),
+      total: 0,
+      cursor: 0,
+      errors_only: false,
+      only: None
+    }
+  }
+}
+
+/// An expression matching messages with a template
+fn template_query(template: &str) -> String {
+  format!("message =~ {}", quote(&template_regex(template)))
+}
+
+/// Groups the messages in the current view by template
+fn count(state: &RenderState) -> (Vec<TemplateGroup>, usize) {
+  let mut groups = TemplateGroups::default();
+  let mut total = 0;
+
+  for filtered in state.filtered_entries.borrow().iter() {
+    let entry = match filtered.entry.upgrade() {
+      Some(entry) => entry,
+      None => continue
+    };
+
+    let message = &entry.message;
+    if message.kind == MessageKind::Internal {
+      continue;
+    }
+
+    let error = matches!(message.level, Some(LogLevel::Error) | Some(LogLevel::Fatal));
+    if state.templates.errors_only && !error {
+      continue;
+    }
+
+    if let Some(text) = &message.text {
+      groups.add(text, message.level, filtered.index);
+      total += 1;
+    }
+  }
+
+  (groups.sorted(), total)
+}
+
+/// The range of templates shown in the list, keeping the cursor in view
+fn visible_range(state: &RenderState, len: usize) -> (usize, usize) {
+  let rows = (state.height as usize / 2).saturating_sub(1).max(1);
+  let start = state.templates.cursor.saturating_sub(rows - 1).min(len.saturating_sub(rows));
+
+  (start, (start + rows).min(len))
+}
+
+pub fn render(
+  state: RcState, terminal: &Terminal, cursor: &TerminalCursor
+) -> Result<RcState, Box<dyn Error>> {
+  let width = state.width as usize;
+  let normal = state.style.highlighted.get_base();
+  let selected = state.style.selected.get_base();
+
+  let groups = &state.templates.groups;
+  let total = state.templates.total;
+  let count_width = groups.first().map_or(1, |group| group.count.to_string().len());
+
+  let mut lines = vec![format!(
+    " {} template{} in {} {}{}",
+    groups.len(), if groups.len() == 1 { "" } else { "s" },
+    total,
+    if state.templates.errors_only { "error" } else { "message" },
+    if total == 1 { "" } else { "s" }
+  )];
+
+  let (start, end) = visible_range(&state, groups.len());
+  for group in &groups[start..end] {
+    let level = group.level.map_or(String::new(), |l| l.to_string().to_lowercase());
+    let percent = group.count as f64 * 100.0 / total.max(1) as f64;
+    lines.push(format!(
+      " {:>w$} {:>5.1}% {:<7} {}", group.count, percent, level, group.template, w = count_width
+    ));
+  }
+
+  let top = state.height.saturating_sub(1 + lines.len() as u16);
+  for (row, line) in lines.iter().enumerate() {
+    let line: String = line.chars().take(width).collect();
+    let padding = " ".repeat(width.saturating_sub(line.chars().count()));
+
+    let style = if row > 0 && start + row - 1 == state.templates.cursor {
+      selected
+    } else {
+      normal
+    };
+
+    cursor.goto(0, top + row as u16)?;
+    terminal.clear(ClearType::CurrentLine)?;
+    terminal.write(style.paint(format!("{}{}", line, padding)))?;
+  }
+
+  let help = "templates | enter: go to latest | space: only | x: hide | e: errors | esc: close";
+  let help: String = help.chars().take(width).collect();
+  let padding = " ".repeat(width.saturating_sub(help.len()));
+
+  cursor.hide()?;
+  cursor.goto(0, state.height - 1)?;
+  terminal.clear(ClearType::CurrentLine)?;
+  terminal.write(selected.paint(format!("{}{}", help, padding)))?;
+
+  Ok(state)
+}
+
+pub fn input(mut state: RcState, key: &KeyEvent) -> (RcState, InputAction) {
+  let page = (state.height as isize / 2 - 1).max(1);
+
+  state = match key {
+    KeyEvent::Esc | KeyEvent::Char('q') => bar::actions::set_active(state, BarType::Status),
+    KeyEvent::Up => actions::move_cursor(state, -1),
+    KeyEvent::Down => actions::move_cursor(state, 1),
+    KeyEvent::PageUp => actions::move_cursor(state, -page),
+    KeyEvent::PageDown => actions::move_cursor(state, page),
+    KeyEvent::Char('\n') => actions::go_to_cursor(state),
+    KeyEvent::Char(' ') | KeyEvent::Char('+') => actions::show_only_cursor(state),
+    KeyEvent::Char('x') | KeyEvent::Char('-') => actions::hide_cursor(state),
+    KeyEvent::Char('e') => actions::toggle_errors_only(state),
+    _ => return (state, InputAction::Unhandled)
+  };
+
+  (state, InputAction::Rerender)
+}
+
+pub mod actions {
+  use super::*;
+
+  /// Lists the templates of messages in the current view, most common first
+  pub fn open(mut state: RcState) -> RcState {
+    let (groups, total) = count(&state);
+    if groups.is_empty() {
+      let what = if state.templates.errors_only { "errors" } else { "messages" };
+      return state_actions::internal(state, &format!("no {} in view to group", what));
+    }
+
+    let state_mut = Rc::make_mut(&mut state);
+    let cursor = state_mut.templates.cursor.min(groups.len() - 1);
+    state_mut.templates.groups = Rc::new(groups);
+    state_mut.templates.total = total;
+    state_mut.templates.cursor = cursor;
+
+    bar::actions::set_active(state, BarType::Templates)
+  }
+
+  pub fn move_cursor(mut state: RcState, amount: isize) -> RcState {
+    let max = state.templates.groups.len().saturating_sub(1) as isize;
+    let state_mut = Rc::make_mut(&mut state);
+    let cursor = (state_mut.templates.cursor as isize + amount).max(0).min(max);
+    state_mut.templates.cursor = cursor as usize;
+
+    state
+  }
+
+  fn template_at_cursor(state: &RenderState) -> Option<TemplateGroup> {
+    state.templates.groups.get(state.templates.cursor).cloned()
+  }
+
+  /// Selects the latest message with the highlighted template
+  pub fn go_to_cursor(state: RcState) -> RcState {
+    let group = match template_at_cursor(&state) {
+      Some(group) => group,
+      None => return state
+    };
+
+    let position = state.filtered_entries.borrow()
+      .binary_search_by_key(&group.example, |f| f.index)
+      .ok();
+
+    let state = bar::actions::set_active(state, BarType::Status);
+    match position {
+      Some(position) => log::actions::move_selection_to_index(state, position),
+      None => state_actions::internal(state, "that message is no longer in view")
+    }
+  }
+
+  /// Shows only messages with the highlighted template, replacing any
+  /// template shown only before; doing so again for the same template shows
+  /// everything again
+  pub fn show_only_cursor(mut state: RcState) -> RcState {
+    let group = match template_at_cursor(&state) {
+      Some(group) => group,
+      None => return state
+    };
+
+    let query = template_query(&group.template);
+    let previous = state.templates.only.clone();
+    if let Some(old) = &previous {
+      state.filters.borrow_mut()
+        .retain(|f| f.mode() != FilterMode::Expression || f.query() != old);
+    }
+
+    let state_mut = Rc::make_mut(&mut state);
+    state_mut.log.selection = None;
+    state_mut.templates.only = None;
+
+    if previous.as_deref() == Some(query.as_str()) {
+      return bar::actions::set_active(state::refilter(state), BarType::Status);
+    }
+
+    let filter = match ExpressionFilter::new(&query, false) {
+      Ok(filter) => filter,
+      Err(e) => return state_actions::internal(
+        state::refilter(state), &format!("can't filter on template: {}", e)
+      )
+    };
+
+    Rc::make_mut(&mut state).templates.only = Some(query);
+    let state = state_actions::add_filter(state, Box::new(filter));
+    bar::actions::set_active(state, BarType::Status)
+  }
+
+  /// Hides messages with the highlighted template, and lists the rest
+  pub fn hide_cursor(state: RcState) -> RcState {
+    let group = match template_at_cursor(&state) {
+      Some(group) => group,
+      None => return state
+    };
+
+    let filter = match ExpressionFilter::new(&template_query(&group.template), true) {
+      Ok(filter) => filter,
+      Err(e) => return state_actions::internal(state, &format!("can't filter on template: {}", e))
+    };
+
+    let state = state_actions::add_filter(state, Box::new(filter));
+    if count(&state).0.is_empty() {
+      return bar::actions::set_active(state, BarType::Status);
+    }
+
+    open(state)
+  }
+
+  /// Toggles counting only error and fatal messages
+  pub fn toggle_errors_only(mut state: RcState) -> RcState {
+    let state_mut = Rc::make_mut(&mut state);
+    state_mut.templates.errors_only = !state_mut.templates.errors_only;
+    state_mut.templates.cursor = 0;
+
+    open(state)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::sync::Arc;
+
+  use spectral::prelude::*;
+  use structopt::StructOpt;
+
+  use crate::config::Config;
+  use crate::renderer::types::MessageEntry;
+
+  #[test]
+  fn test_templates() {
+    let config = Arc::new(Config::from_iter_safe(vec![""]).unwrap());
+    let mut state = Rc::new(RenderState::new(config));
+    for (level, text) in &[
+      (LogLevel::Info, "request 1 took 12ms"),
+      (LogLevel::Error, "upstream 10.0.0.7:80 timed out"),
+      (LogLevel::Info, "request 2 took 7.5ms"),
+      (LogLevel::Error, "upstream 10.0.0.9:80 timed out"),
+      (LogLevel::Info, "request 3 took 9ms"),
+      (LogLevel::Error, "disk full")
+    ] {
+      let mut entry = MessageEntry::internal(text);
+      entry.message.kind = MessageKind::Json;
+      entry.message.level = Some(*level);
+      state = state_actions::add_entry(state, entry);
+    }
+
+    let shown = |state: &RcState| state.filtered_entries.borrow().iter()
+      .filter_map(|f| f.entry.upgrade())
+      .filter(|e| e.message.kind != MessageKind::Internal)
+      .count();
+
+    state = actions::open(state);
+    let templates: Vec<(String, usize)> = state.templates.groups.iter()
+      .map(|g| (g.template.clone(), g.count))
+      .collect();
+    assert_that!(templates).is_equal_to(vec![
+      ("request <num> took <num>ms".to_string(), 3),
+      ("upstream <ip> timed out".to_string(), 2),
+      ("disk full".to_string(), 1)
+    ]);
+
+    // the top errors
+    state = actions::toggle_errors_only(state);
+    assert_that!(state.templates.total).is_equal_to(3);
+    assert_that!(state.templates.groups[0].template.as_str())
+      .is_equal_to("upstream <ip> timed out");
+
+    state = actions::go_to_cursor(state);
+    let selected = state.log.selection
+      .map(|s| state.filtered_entries.borrow()[s.rel_index].index);
+    assert_that!(selected).is_equal_to(Some(3));
+
+    state = actions::open(state);
+    state = actions::show_only_cursor(state);
+    assert_that!(shown(&state)).is_equal_to(2);
+
+    state = actions::open(state);
+    state = actions::show_only_cursor(state);
+    assert_that!(shown(&state)).is_equal_to(6);
+    assert_that!(state.filters.borrow().is_empty()).is_true();
+
+    state = actions::toggle_errors_only(state);
+    state = actions::hide_cursor(state);
+    assert_that!(shown(&state)).is_equal_to(3);
+  }
+}
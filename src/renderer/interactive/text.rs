@@ -100,7 +100,7 @@ pub fn render(
   // before calling this render()
 
   let out_text = if let Some(styler) = &text.styler {
-    let style = styler(&state.config.style);
+    let style = styler(&state.style);
     style.paint(&text.input).to_string()
   } else {
     text.input.clone()
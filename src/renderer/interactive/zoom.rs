@@ -0,0 +1,210 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+use std::rc::Rc;
+
+use chrono::prelude::*;
+
+use crate::parser::{Message, MessageKind};
+use crate::renderer::common::message_timestamp;
+use crate::timezone;
+
+use super::state::{self, RcState};
+use super::state::actions as state_actions;
+use super::log;
+
+/// A window of time, inclusive of both ends
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Window {
+  pub start: DateTime<Utc>,
+  pub end: DateTime<Utc>
+}
+
+impl Window {
+  pub fn new(a: DateTime<Utc>, b: DateTime<Utc>) -> Self {
+    Window { start: a.min(b), end: a.max(b) }
+  }
+
+  fn contains(&self, time: DateTime<Utc>) -> bool {
+    time >= self.start && time <= self.end
+  }
+
+  /// Formats the window, e.g. `2020-11-25 21:22:10 to 21:22:40`, leaving out
+  /// the end's date if it's the same as the start's
+  pub fn describe(&self) -> String {
+    let start = timezone::to_display(self.start);
+    let end = timezone::to_display(self.end);
+
+    let end_format = if start.date_naive() == end.date_naive() {
+      "%H:%M:%S"
+    } else {
+      "%Y-%m-%d %H:%M:%S"
+    };
+
+    format!("{} to {}", start.format("%Y-%m-%d %H:%M:%S"), end.format(end_format))
+  }
+}
+
+/// The time windows the log is zoomed into, innermost last
+///
+/// While zoomed, messages outside the window are hidden as if they'd never
+/// arrived, so filters, context, search, and the histogram only see the
+/// window. Messages without a timestamp are hidden too, but internal messages
+/// are always shown.
+#[derive(Clone)]
+pub struct ZoomState {
+  windows: Vec<Window>
+}
+
+impl ZoomState {
+  pub fn new() -> Self {
+    ZoomState {
+      windows: Vec::new()
+    }
+  }
+
+  pub fn window(&self) -> Option<Window> {
+    self.windows.last().copied()
+  }
+
+  /// If true, a message is inside the current window, if any
+  pub fn pass(&self, message: &Message) -> bool {
+    let window = match self.windows.last() {
+      Some(window) => window,
+      None => return true
+    };
+
+    if message.kind == MessageKind::Internal {
+      return true;
+    }
+
+    message_timestamp(message).map_or(false, |t| window.contains(t))
+  }
+}
+
+pub mod actions {
+  use super::*;
+
+  /// Zooms into a window, within the current one if already zoomed
+  pub fn zoom_to(mut state: RcState, window: Window) -> RcState {
+    let window = match state.zoom.window() {
+      Some(outer) => Window::new(window.start.max(outer.start), window.end.min(outer.end)),
+      None => window
+    };
+
+    let state_mut = Rc::make_mut(&mut state);
+    state_mut.zoom.windows.push(window);
+    state_mut.log.selection = None;
+    state_mut.log.mark = None;
+
+    let state = state::refilter(state);
+    let depth = state.zoom.windows.len();
+    let text = if depth > 1 {
+      format!("zoomed to {} ({} levels deep)", window.describe(), depth)
+    } else {
+      format!("zoomed to {}", window.describe())
+    };
+
+    state_actions::internal(state, &text)
+  }
+
+  /// Zooms into the time spanned by the marked range
+  pub fn zoom_to_range(state: RcState) -> RcState {
+    let range = match (state.log.mark, log::selected_range(&state)) {
+      (Some(_), Some(range)) => range,
+      _ => return state_actions::internal(state, "mark a range first, then select its other end")
+    };
+
+    let timestamps: Vec<DateTime<Utc>> = {
+      let filtered_entries = state.filtered_entries.borrow();
+      range
+        .filter_map(|i| filtered_entries.get(i)?.entry.upgrade())
+        .filter(|e| e.message.kind != MessageKind::Internal)
+        .filter_map(|e| message_timestamp(&e.message))
+        .collect()
+    };
+
+    match (timestamps.iter().min(), timestamps.iter().max()) {
+      (Some(start), Some(end)) => zoom_to(state, Window::new(*start, *end)),
+      _ => state_actions::internal(state, "no timestamps in the marked range to zoom to")
+    }
+  }
+
+  /// Returns to the previous window, or the whole log
+  pub fn zoom_out(mut state: RcState) -> RcState {
+    let state_mut = Rc::make_mut(&mut state);
+    if state_mut.zoom.windows.pop().is_none() {
+      return state_actions::internal(state, "not zoomed in");
+    }
+
+    state_mut.log.selection = None;
+    state_mut.log.mark = None;
+
+    let state = state::refilter(state);
+    let text = match state.zoom.window() {
+      Some(window) => format!("zoomed out to {}", window.describe()),
+      None => "zoomed out to the whole log".to_string()
+    };
+
+    state_actions::internal(state, &text)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::sync::Arc;
+
+  use spectral::prelude::*;
+  use structopt::StructOpt;
+
+  use crate::config::Config;
+  use crate::parser::LogLevel;
+  use crate::renderer::types::MessageEntry;
+  use crate::renderer::interactive::RenderState;
+
+  fn time(second: u32) -> DateTime<Utc> {
+    format!("2020-11-25T21:22:{:02}Z", second).parse().unwrap()
+  }
+
+  fn entry(second: u32) -> MessageEntry {
+    let mut entry = MessageEntry::internal(&format!("at {}", second));
+    entry.message.kind = MessageKind::Json;
+    entry.message.level = Some(LogLevel::Info);
+    entry.message.timestamp = Some(time(second));
+    entry
+  }
+
+  fn shown(state: &RcState) -> Vec<u32> {
+    state.filtered_entries.borrow().iter()
+      .filter_map(|f| f.entry.upgrade())
+      .filter(|e| e.message.kind != MessageKind::Internal)
+      .filter_map(|e| e.message.timestamp.map(|t| t.second()))
+      .collect()
+  }
+
+  #[test]
+  fn test_zoom() {
+    let config = Arc::new(Config::from_iter_safe(vec![""]).unwrap());
+    let mut state = Rc::new(RenderState::new(config));
+    for second in &[10, 20, 30, 40] {
+      state = state_actions::add_entry(state, entry(*second));
+    }
+
+    state = actions::zoom_to(state, Window::new(time(35), time(15)));
+    assert_that!(shown(&state)).is_equal_to(vec![20, 30]);
+
+    // later messages outside the window stay hidden, and zooms nest
+    state = state_actions::add_entry(state, entry(50));
+    state = state_actions::add_entry(state, entry(25));
+    state = actions::zoom_to(state, Window::new(time(22), time(59)));
+    assert_that!(shown(&state)).is_equal_to(vec![30, 25]);
+
+    state = actions::zoom_out(state);
+    assert_that!(shown(&state)).is_equal_to(vec![20, 30, 25]);
+
+    state = actions::zoom_out(state);
+    assert_that!(shown(&state)).is_equal_to(vec![10, 20, 30, 40, 50, 25]);
+    assert_that!(state.zoom.window()).is_none();
+  }
+}
@@ -1,30 +1,33 @@
 // (C) Copyright 2019 Hewlett Packard Enterprise Development LP
 
+use std::io::{self, Write};
 use std::sync::Arc;
-use std::sync::mpsc::Receiver;
-use std::thread::{self, JoinHandle};
 
 use serde_json;
 
 use crate::config::Config;
 use crate::renderer::types::*;
+use crate::renderer::registry::Renderer;
 
-pub fn json_renderer(_: Arc<Config>, rx: Receiver<LogEntry>) -> JoinHandle<()> {
-  thread::Builder::new().name("json_renderer".to_string()).spawn(move || {
-    for entry in rx {
-      if entry.eof.is_some() {
-        break;
-      }
-
-      if let Some(message) = entry.message {
-        match serde_json::to_string(&message.message) {
-          Ok(s) => println!("{}", s),
-          Err(e) => {
-            eprintln!("error converting message to json: {:?}", e);
-            break;
-          }
-        }
-      }
+/// Writes each message as parsed, one JSON object per line
+struct JsonRenderer {
+  config: Arc<Config>
+}
+
+impl Renderer for JsonRenderer {
+  fn render(&mut self, entry: &MessageEntry) -> io::Result<()> {
+    if let Some(projection) = &self.config.project {
+      return writeln!(io::stdout(), "{}", projection.to_json(&entry.message));
     }
-  }).unwrap()
+
+    let s = serde_json::to_string(&entry.message).map_err(|e| io::Error::new(
+      io::ErrorKind::Other, format!("error converting message to json: {}", e)
+    ))?;
+
+    writeln!(io::stdout(), "{}", s)
+  }
+}
+
+pub fn json_renderer(config: Arc<Config>) -> Box<dyn Renderer> {
+  Box::new(JsonRenderer { config })
 }
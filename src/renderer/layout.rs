@@ -0,0 +1,271 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+use std::fmt;
+use std::str::FromStr;
+use std::sync::RwLock;
+
+use serde::{Deserialize, Serialize};
+use simple_error::SimpleError;
+
+use crate::classifier::{Chunk, ChunkKind, ChunkWeight};
+
+/// Widths below which high, medium, and normal weight columns are the lowest
+/// shown, i.e. `--breakpoints`
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Breakpoints {
+  pub high: usize,
+  pub medium: usize,
+  pub normal: usize
+}
+
+impl Default for Breakpoints {
+  fn default() -> Self {
+    Breakpoints { high: 60, medium: 80, normal: 100 }
+  }
+}
+
+impl FromStr for Breakpoints {
+  type Err = SimpleError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let widths = s.split(',')
+      .map(|w| w.trim().parse::<usize>())
+      .collect::<Result<Vec<_>, _>>()
+      .map_err(|_| SimpleError::new(format!("invalid breakpoints: {}", s)))?;
+
+    match widths.as_slice() {
+      &[high, medium, normal] if high <= medium && medium <= normal => {
+        Ok(Breakpoints { high, medium, normal })
+      },
+      _ => bail!("breakpoints must be three ascending widths, e.g. 60,80,100: {}", s)
+    }
+  }
+}
+
+/// How many columns the styled and interactive renderers show, i.e.
+/// `--density`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Density {
+  /// Columns are dropped only as needed to fit the terminal width
+  #[default]
+  Detailed,
+
+  /// Only the time, level, and message are shown, as on a narrow terminal
+  Compact
+}
+
+impl Density {
+  pub fn toggle(self) -> Density {
+    match self {
+      Density::Detailed => Density::Compact,
+      Density::Compact => Density::Detailed
+    }
+  }
+}
+
+impl FromStr for Density {
+  type Err = SimpleError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s.to_lowercase().as_str() {
+      "detailed" => Ok(Density::Detailed),
+      "compact" => Ok(Density::Compact),
+      _ => bail!("invalid density, expected one of: detailed, compact: {}", s)
+    }
+  }
+}
+
+impl fmt::Display for Density {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}", match self {
+      Density::Detailed => "detailed",
+      Density::Compact => "compact"
+    })
+  }
+}
+
+/// A column that may be hidden at some width
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Column {
+  Date,
+  Time,
+  Delta,
+  Level,
+  Fields,
+
+  /// the source and context shown on the right
+  Context,
+
+  Repeat
+}
+
+impl Column {
+  fn of(kind: ChunkKind) -> Option<Column> {
+    match kind {
+      ChunkKind::Date => Some(Column::Date),
+      ChunkKind::Time => Some(Column::Time),
+      ChunkKind::Delta => Some(Column::Delta),
+      ChunkKind::Level(_) => Some(Column::Level),
+      ChunkKind::Field | ChunkKind::FieldKey | ChunkKind::FieldValue => Some(Column::Fields),
+      ChunkKind::Context => Some(Column::Context),
+      ChunkKind::Repeat => Some(Column::Repeat),
+      _ => None
+    }
+  }
+}
+
+/// An explicit set of columns to hide at terminal widths up to `max_width`,
+/// from the config file's `layouts`
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct WidthLayout {
+  pub max_width: usize,
+
+  #[serde(default)]
+  pub hide: Vec<Column>
+}
+
+/// Decides which columns the styled and interactive renderers drop to fit the
+/// terminal width
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Layout {
+  pub breakpoints: Breakpoints,
+
+  /// If any match the terminal width, the narrowest is used instead of the
+  /// breakpoints
+  pub layouts: Vec<WidthLayout>,
+
+  pub density: Density
+}
+
+impl Layout {
+  fn min_weight(&self, width: usize) -> ChunkWeight {
+    if width < self.breakpoints.high {
+      ChunkWeight::High
+    } else if width < self.breakpoints.medium {
+      ChunkWeight::Medium
+    } else if width < self.breakpoints.normal {
+      ChunkWeight::Normal
+    } else {
+      ChunkWeight::Low
+    }
+  }
+
+  /// If true, a chunk should be shown at the given wrap width; nothing is
+  /// dropped if wrapping is disabled
+  pub fn shows(&self, chunk: &Chunk, wrap_width: Option<usize>) -> bool {
+    if self.density == Density::Compact && chunk.weight < ChunkWeight::Medium.value() {
+      return false;
+    }
+
+    let width = match wrap_width {
+      Some(width) => width,
+      None => return true
+    };
+
+    let layout = self.layouts.iter()
+      .filter(|l| width <= l.max_width)
+      .min_by_key(|l| l.max_width);
+
+    match layout {
+      Some(layout) => match Column::of(chunk.kind) {
+        Some(column) => !layout.hide.contains(&column),
+        None => true
+      },
+      None => chunk.weight >= self.min_weight(width).value()
+    }
+  }
+}
+
+lazy_static! {
+  static ref LAYOUT: RwLock<Layout> = RwLock::new(Layout::default());
+}
+
+/// Sets the layout used by all renderers, i.e. from `--breakpoints` and the
+/// config file
+///
+/// This is global as `styled_render()` is called from many places that don't
+/// otherwise need the config.
+pub fn set_layout(layout: Layout) {
+  if let Ok(mut current) = LAYOUT.write() {
+    *current = layout;
+  }
+}
+
+/// Changes the density of the layout used by all renderers, e.g. when toggled
+/// in the interactive renderer
+pub fn set_density(density: Density) {
+  if let Ok(mut current) = LAYOUT.write() {
+    current.density = density;
+  }
+}
+
+pub fn density() -> Density {
+  match LAYOUT.read() {
+    Ok(layout) => layout.density,
+    Err(_) => Density::default()
+  }
+}
+
+/// If true, a chunk should be shown at the given wrap width
+pub fn shows(chunk: &Chunk, wrap_width: Option<usize>) -> bool {
+  match LAYOUT.read() {
+    Ok(layout) => layout.shows(chunk, wrap_width),
+    Err(_) => true
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use spectral::prelude::*;
+
+  use crate::parser::LogLevel;
+
+  fn chunk(kind: ChunkKind, weight: ChunkWeight) -> Chunk {
+    Chunk { kind, weight: weight.value(), ..Default::default() }
+  }
+
+  #[test]
+  fn test_shows() {
+    let date = chunk(ChunkKind::Date, ChunkWeight::Normal);
+    let time = chunk(ChunkKind::Time, ChunkWeight::Medium);
+    let level = chunk(ChunkKind::Level(LogLevel::Info), ChunkWeight::High);
+
+    let layout = Layout::default();
+    assert_that!(layout.shows(&date, Some(80))).is_true();
+    assert_that!(layout.shows(&date, Some(79))).is_false();
+    assert_that!(layout.shows(&time, Some(60))).is_true();
+    assert_that!(layout.shows(&time, Some(59))).is_false();
+    assert_that!(layout.shows(&level, Some(20))).is_true();
+    assert_that!(layout.shows(&date, None)).is_true();
+
+    let layout = Layout {
+      breakpoints: "40,50,60".parse().unwrap(),
+      layouts: vec![
+        WidthLayout { max_width: 200, hide: vec![Column::Time] },
+        WidthLayout { max_width: 120, hide: vec![Column::Date, Column::Level] }
+      ],
+      density: Density::Detailed
+    };
+    assert_that!(layout.shows(&date, Some(110))).is_false();
+    assert_that!(layout.shows(&level, Some(110))).is_false();
+    assert_that!(layout.shows(&time, Some(110))).is_true();
+    assert_that!(layout.shows(&date, Some(150))).is_true();
+    assert_that!(layout.shows(&time, Some(150))).is_false();
+
+    // wider than all layouts, so back to the breakpoints
+    assert_that!(layout.shows(&date, Some(250))).is_true();
+
+    // compact drops the same columns as a narrow terminal, at any width
+    let layout = Layout { density: Density::Compact, ..Layout::default() };
+    assert_that!(layout.shows(&date, Some(200))).is_false();
+    assert_that!(layout.shows(&time, Some(200))).is_true();
+    assert_that!(layout.shows(&level, None)).is_true();
+
+    assert_that!("60,80".parse::<Breakpoints>()).is_err();
+    assert_that!("100,80,60".parse::<Breakpoints>()).is_err();
+  }
+}
@@ -0,0 +1,191 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+use std::io::{self, Write};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::mpsc::Receiver;
+use std::thread::{self, JoinHandle};
+
+use simple_error::SimpleError;
+
+use crate::config::Config;
+use crate::parser::Message;
+use crate::renderer::types::*;
+use crate::renderer::common::column_value;
+use crate::renderer::csv::DEFAULT_COLUMNS;
+use crate::renderer::plain::plain_render;
+
+/// How messages are written as Markdown
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MarkdownFormat {
+  /// A table with one column per `--columns` entry
+  Table,
+
+  /// Plain text lines in a fenced code block
+  Code
+}
+
+impl FromStr for MarkdownFormat {
+  type Err = SimpleError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s {
+      "table" => Ok(MarkdownFormat::Table),
+      "code" => Ok(MarkdownFormat::Code),
+      _ => bail!("invalid markdown format: {}", s)
+    }
+  }
+}
+
+/// Escapes a table cell, which must stay on one line
+fn escape_cell(s: &str) -> String {
+  s.replace('|', "\\|").replace("\r\n", "<br>").replace('\n', "<br>")
+}
+
+/// The columns to show, from `--columns`
+pub fn table_columns(config: &Config) -> Vec<String> {
+  if config.columns.is_empty() {
+    DEFAULT_COLUMNS.iter().map(|c| c.to_string()).collect()
+  } else {
+    config.columns.clone()
+  }
+}
+
+/// Formats messages as a GitHub-flavored Markdown table, padded so columns
+/// also line up as plain text
+pub fn markdown_table<S: AsRef<str>>(messages: &[&Message], columns: &[S]) -> Vec<String> {
+  let header: Vec<String> = columns.iter().map(|c| escape_cell(c.as_ref())).collect();
+  let rows: Vec<Vec<String>> = messages.iter()
+    .map(|m| columns.iter().map(|c| escape_cell(&column_value(m, c.as_ref()))).collect())
+    .collect();
+
+  let widths: Vec<usize> = (0..columns.len())
+    .map(|i| {
+      rows.iter()
+        .map(|row| row[i].chars().count())
+        .chain(std::iter::once(header[i].chars().count()))
+        .max()
+        .unwrap_or(0)
+        .max(3)
+    })
+    .collect();
+
+  let line = |cells: &[String]| {
+    let cells: Vec<String> = cells.iter().zip(&widths)
+      .map(|(cell, width)| format!("{:<w$}", cell, w = width))
+      .collect();
+
+    format!("| {} |", cells.join(" | "))
+  };
+
+  let separator: Vec<String> = widths.iter().map(|w| "-".repeat(*w)).collect();
+
+  let mut lines = vec![line(&header), line(&separator)];
+  lines.extend(rows.iter().map(|row| line(row)));
+  lines
+}
+
+/// The fence for a code block, longer than any backtick run in its contents
+pub fn code_fence<S: AsRef<str>>(lines: &[S]) -> String {
+  let longest = lines.iter()
+    .flat_map(|line| line.as_ref().split(|c| c != '`').map(str::len).max())
+    .max()
+    .unwrap_or(0);
+
+  "`".repeat(longest.max(2) + 1)
+}
+
+/// Formats plain text lines as a fenced code block
+pub fn markdown_code<S: AsRef<str>>(lines: &[S]) -> Vec<String> {
+  let fence = code_fence(lines);
+
+  let mut ret = vec![fence.clone()];
+  ret.extend(lines.iter().map(|l| l.as_ref().to_string()));
+  ret.push(fence);
+  ret
+}
+
+/// Writes messages as Markdown, ready to paste into an issue or chat
+///
+/// Output is written once all input has been read, so columns can be aligned.
+pub fn markdown_renderer(config: Arc<Config>, rx: Receiver<LogEntry>) -> JoinHandle<()> {
+  thread::Builder::new().name("markdown_renderer".to_string()).spawn(move || {
+    let mut entries = Vec::new();
+    for entry in rx {
+      if entry.eof.is_some() {
+        break;
+      }
+
+      if let Some(entry) = entry.message {
+        entries.push(entry);
+      }
+    }
+
+    let lines = match config.markdown_format {
+      MarkdownFormat::Table => {
+        let messages: Vec<&Message> = entries.iter().map(|e| &e.message).collect();
+        markdown_table(&messages, &table_columns(&config))
+      },
+      MarkdownFormat::Code => {
+        let lines: Vec<String> = entries.iter().flat_map(plain_render).collect();
+        markdown_code(&lines)
+      }
+    };
+
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    for line in lines {
+      if writeln!(out, "{}", line).is_err() {
+        break;
+      }
+    }
+  }).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::collections::HashMap;
+
+  use serde_json::json;
+  use spectral::prelude::*;
+
+  use crate::parser::{LogLevel, MessageKind};
+
+  fn message(level: LogLevel, text: &str) -> Message {
+    Message {
+      kind: MessageKind::Json,
+      timestamp: None,
+      level: Some(level),
+      text: Some(text.to_string()),
+      metadata: hashmap!{
+        "pod".to_string() => json!("api-1")
+      },
+      reader_metadata: None,
+      mapped_fields: HashMap::new(),
+      raw: String::new()
+    }
+  }
+
+  #[test]
+  fn test_markdown_table() {
+    let a = message(LogLevel::Info, "started");
+    let b = message(LogLevel::Warning, "a | b\nc");
+
+    assert_that!(markdown_table(&[&a, &b], &["level", "pod", "message"])).is_equal_to(vec![
+      "| level   | pod   | message     |".to_string(),
+      "| ------- | ----- | ----------- |".to_string(),
+      "| info    | api-1 | started     |".to_string(),
+      r"| warning | api-1 | a \| b<br>c |".to_string()
+    ]);
+  }
+
+  #[test]
+  fn test_markdown_code() {
+    assert_that!(markdown_code(&["hello"]))
+      .is_equal_to(vec!["```".to_string(), "hello".to_string(), "```".to_string()]);
+
+    assert_that!(markdown_code(&["see ```code```"])[0].as_str()).is_equal_to("````");
+  }
+}
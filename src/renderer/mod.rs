@@ -2,15 +2,38 @@
 
 mod types;
 mod common;
+pub mod glyphs;
+mod highlight;
+pub mod hyperlink;
+pub mod layout;
 mod json;
 mod plain;
 mod styled;
 mod raw;
+mod html;
+mod csv;
+mod normalized;
+pub mod markdown;
+mod parquet;
+mod pager;
+mod stats;
+pub mod template;
+pub mod projection;
+pub mod grouping;
 pub mod interactive;
+pub mod registry;
 
 pub use types::*;
 pub use styled::styled_renderer;
 pub use interactive::interactive_renderer;
 pub use plain::plain_renderer;
+pub(crate) use plain::plain_render;
 pub use json::json_renderer;
 pub use raw::raw_renderer;
+pub use html::html_renderer;
+pub use csv::csv_renderer;
+pub use normalized::normalized_renderer;
+pub(crate) use normalized::NormalizedRecord;
+pub use stats::stats_renderer;
+pub use markdown::markdown_renderer;
+pub use parquet::parquet_renderer;
@@ -0,0 +1,148 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::{self, Write};
+use std::sync::Arc;
+
+use chrono::SecondsFormat;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::config::{ArrayStyle, Config, FlattenConfig};
+use crate::parser::{LogLevel, Message, MessageKind, flatten_value};
+use crate::renderer::types::*;
+use crate::renderer::registry::Renderer;
+
+/// A message in the normalized output schema
+///
+/// Every key is always present (`null` if unknown), regardless of which parser
+/// produced the message. Changes to this schema should be backward compatible
+/// and documented in the README.
+#[derive(Debug, Serialize)]
+pub struct NormalizedRecord {
+  /// RFC 3339 timestamp in UTC, e.g. `2020-11-25T21:22:50.500Z`
+  pub timestamp: Option<String>,
+
+  /// One of: debug, info, warning, error, fatal
+  pub level: Option<LogLevel>,
+
+  pub message: Option<String>,
+
+  /// The detected format, e.g. `json` or `klog`
+  pub kind: MessageKind,
+
+  /// The source reported by the reader, e.g. a Kubernetes pod and container
+  pub source: Option<String>,
+
+  /// All other fields, with nested objects flattened into dotted keys
+  pub fields: BTreeMap<String, Value>
+}
+
+impl NormalizedRecord {
+  pub fn new(message: &Message) -> NormalizedRecord {
+    let flatten = FlattenConfig {
+      depth: usize::MAX,
+      separator: ".".to_string(),
+      arrays: ArrayStyle::Keep
+    };
+
+    let mut fields = HashMap::new();
+    for (key, value) in &message.metadata {
+      flatten_value(&flatten, key.clone(), value, 0, &mut fields);
+    }
+
+    NormalizedRecord {
+      timestamp: message.timestamp
+        .map(|t| t.to_rfc3339_opts(SecondsFormat::AutoSi, true)),
+      level: message.level.filter(|l| *l != LogLevel::Plain && *l != LogLevel::Int),
+      message: message.text.clone(),
+      kind: message.kind,
+      source: message.reader_metadata.as_ref().and_then(|m| m.source.clone()),
+      fields: fields.into_iter().collect()
+    }
+  }
+}
+
+/// Writes one normalized JSON object per message, for use as a
+/// log-normalization stage in scripts
+struct NormalizedRenderer;
+
+impl Renderer for NormalizedRenderer {
+  fn render(&mut self, entry: &MessageEntry) -> io::Result<()> {
+    let record = NormalizedRecord::new(&entry.message);
+    let s = serde_json::to_string(&record).map_err(|e| io::Error::new(
+      io::ErrorKind::Other, format!("error converting message to json: {}", e)
+    ))?;
+
+    writeln!(io::stdout(), "{}", s)
+  }
+}
+
+pub fn normalized_renderer(_: Arc<Config>) -> Box<dyn Renderer> {
+  Box::new(NormalizedRenderer)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use serde_json::json;
+  use spectral::prelude::*;
+
+  use crate::parser::ReaderMetadata;
+
+  #[test]
+  fn test_normalize() {
+    let message = Message {
+      kind: MessageKind::Logrus,
+      timestamp: Some("2020-11-25T21:22:50.5Z".parse().unwrap()),
+      level: Some(LogLevel::Error),
+      text: Some("request failed".to_string()),
+      metadata: hashmap!{
+        "http".to_string() => json!({"status": 503, "path": "/"}),
+        "tags".to_string() => json!(["a", "b"])
+      },
+      reader_metadata: Some(ReaderMetadata {
+        timestamp: None,
+        source: Some("api-1".to_string())
+      }),
+      mapped_fields: HashMap::new(),
+      raw: String::new()
+    };
+
+    let record = serde_json::to_value(NormalizedRecord::new(&message)).unwrap();
+    assert_that!(record).is_equal_to(json!({
+      "timestamp": "2020-11-25T21:22:50.500Z",
+      "level": "error",
+      "message": "request failed",
+      "kind": "logrus",
+      "source": "api-1",
+      "fields": {
+        "http.path": "/",
+        "http.status": 503,
+        "tags": ["a", "b"]
+      }
+    }));
+
+    let plain = Message {
+      kind: MessageKind::Plain,
+      timestamp: None,
+      level: Some(LogLevel::Plain),
+      text: Some("hello".to_string()),
+      metadata: HashMap::new(),
+      reader_metadata: None,
+      mapped_fields: HashMap::new(),
+      raw: "hello".to_string()
+    };
+
+    let record = serde_json::to_value(NormalizedRecord::new(&plain)).unwrap();
+    assert_that!(record).is_equal_to(json!({
+      "timestamp": null,
+      "level": null,
+      "message": "hello",
+      "kind": "plain",
+      "source": null,
+      "fields": {}
+    }));
+  }
+}
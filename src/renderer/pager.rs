@@ -0,0 +1,82 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+use std::env;
+use std::io::{self, LineWriter, Write};
+use std::process::{Child, ChildStdin, Command, Stdio};
+
+use crate::config::Config;
+
+/// Options passed to `less` if `$LESS` is unset: keep colors (`R`), quit if the
+/// output fits on one screen (`F`), and leave it on screen afterwards (`X`)
+static DEFAULT_LESS: &str = "FRX";
+
+/// Where a line-based renderer writes its output: stdout, or a pager's stdin
+pub struct Output {
+  pager: Option<(Child, LineWriter<ChildStdin>)>
+}
+
+fn pager_command(config: &Config) -> Option<Vec<String>> {
+  let command = config.pager.clone()
+    .or_else(|| env::var("PAGER").ok())
+    .unwrap_or_else(|| "less".to_string());
+
+  let args: Vec<String> = command.split_whitespace().map(str::to_string).collect();
+  match args.first().map(String::as_str) {
+    None | Some("cat") => None,
+    Some(_) => Some(args)
+  }
+}
+
+fn spawn_pager(args: &[String]) -> io::Result<(Child, LineWriter<ChildStdin>)> {
+  let mut command = Command::new(&args[0]);
+  command.args(&args[1..]).stdin(Stdio::piped());
+  if env::var_os("LESS").is_none() {
+    command.env("LESS", DEFAULT_LESS);
+  }
+
+  let mut child = command.spawn()?;
+  let stdin = child.stdin.take()
+    .ok_or_else(|| io::Error::new(io::ErrorKind::Other, "pager has no stdin"))?;
+
+  Ok((child, LineWriter::new(stdin)))
+}
+
+impl Output {
+  /// Opens a pager per `--paging` and `--pager`, falling back to stdout if
+  /// paging is disabled or the pager can't be started
+  pub fn open(config: &Config) -> Output {
+    let pager = if config.paging.enabled() {
+      pager_command(config).and_then(|args| spawn_pager(&args).ok())
+    } else {
+      None
+    };
+
+    Output { pager }
+  }
+}
+
+impl Write for Output {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    match &mut self.pager {
+      Some((_, stdin)) => stdin.write(buf),
+      None => io::stdout().write(buf)
+    }
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    match &mut self.pager {
+      Some((_, stdin)) => stdin.flush(),
+      None => io::stdout().flush()
+    }
+  }
+}
+
+impl Drop for Output {
+  /// Closes the pager's input and waits for the user to quit it
+  fn drop(&mut self) {
+    if let Some((mut child, stdin)) = self.pager.take() {
+      drop(stdin);
+      child.wait().ok();
+    }
+  }
+}
@@ -0,0 +1,593 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::sync::Arc;
+use std::sync::mpsc::Receiver;
+use std::thread::{self, JoinHandle};
+
+use serde_json::Value;
+
+use crate::config::Config;
+use crate::parser::MessageKind;
+use crate::renderer::types::*;
+use crate::renderer::normalized::NormalizedRecord;
+
+static MAGIC: &[u8] = b"PAR1";
+static CREATED_BY: &str = "woodchipper";
+
+/// Rows per row group, bounding the size of each page
+static ROW_GROUP_SIZE: usize = 65_536;
+
+/// Column names that fields can't use, as they're taken by canonical columns
+static CANONICAL: &[&str] = &["timestamp", "level", "message", "kind", "source", "fields"];
+
+// thrift compact protocol types
+const CT_I32: u8 = 5;
+const CT_I64: u8 = 6;
+const CT_BINARY: u8 = 8;
+const CT_LIST: u8 = 9;
+const CT_STRUCT: u8 = 12;
+
+// parquet enums
+const TYPE_BOOLEAN: i32 = 0;
+const TYPE_INT64: i32 = 2;
+const TYPE_DOUBLE: i32 = 5;
+const TYPE_BYTE_ARRAY: i32 = 6;
+const REPETITION_REQUIRED: i32 = 0;
+const REPETITION_OPTIONAL: i32 = 1;
+const CONVERTED_UTF8: i32 = 0;
+const CONVERTED_TIMESTAMP_MILLIS: i32 = 9;
+const ENCODING_PLAIN: i32 = 0;
+const ENCODING_RLE: i32 = 3;
+const CODEC_UNCOMPRESSED: i32 = 0;
+const PAGE_DATA: i32 = 0;
+
+/// A minimal writer for the thrift compact protocol, as used by Parquet
+/// metadata
+#[derive(Default)]
+struct Compact {
+  buf: Vec<u8>,
+  last_field: i16,
+  stack: Vec<i16>
+}
+
+impl Compact {
+  fn varint(&mut self, mut v: u64) {
+    while v >= 0x80 {
+      self.buf.push((v as u8) | 0x80);
+      v >>= 7;
+    }
+
+    self.buf.push(v as u8);
+  }
+
+  fn zigzag(&mut self, v: i64) {
+    self.varint(((v << 1) ^ (v >> 63)) as u64);
+  }
+
+  fn field(&mut self, ty: u8, id: i16) {
+    let delta = id - self.last_field;
+    if delta > 0 && delta <= 15 {
+      self.buf.push(((delta as u8) << 4) | ty);
+    } else {
+      self.buf.push(ty);
+      self.zigzag(i64::from(id));
+    }
+
+    self.last_field = id;
+  }
+
+  fn i32(&mut self, id: i16, v: i32) {
+    self.field(CT_I32, id);
+    self.zigzag(i64::from(v));
+  }
+
+  fn i64(&mut self, id: i16, v: i64) {
+    self.field(CT_I64, id);
+    self.zigzag(v);
+  }
+
+  fn bytes(&mut self, v: &[u8]) {
+    self.varint(v.len() as u64);
+    self.buf.extend_from_slice(v);
+  }
+
+  fn string(&mut self, id: i16, v: &str) {
+    self.field(CT_BINARY, id);
+    self.bytes(v.as_bytes());
+  }
+
+  fn list(&mut self, id: i16, elem: u8, size: usize) {
+    self.field(CT_LIST, id);
+    if size < 15 {
+      self.buf.push(((size as u8) << 4) | elem);
+    } else {
+      self.buf.push(0xf0 | elem);
+      self.varint(size as u64);
+    }
+  }
+
+  /// Begins a struct, either as field `id` or, if `None`, as a list element
+  fn begin(&mut self, id: Option<i16>) {
+    if let Some(id) = id {
+      self.field(CT_STRUCT, id);
+    }
+
+    self.stack.push(self.last_field);
+    self.last_field = 0;
+  }
+
+  fn end(&mut self) {
+    self.buf.push(0);
+    self.last_field = self.stack.pop().unwrap_or(0);
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ColumnType {
+  Boolean,
+  Int64,
+  Double,
+  Utf8,
+  TimestampMillis
+}
+
+impl ColumnType {
+  fn physical(self) -> i32 {
+    match self {
+      ColumnType::Boolean => TYPE_BOOLEAN,
+      ColumnType::Int64 | ColumnType::TimestampMillis => TYPE_INT64,
+      ColumnType::Double => TYPE_DOUBLE,
+      ColumnType::Utf8 => TYPE_BYTE_ARRAY
+    }
+  }
+
+  fn converted(self) -> Option<i32> {
+    match self {
+      ColumnType::Utf8 => Some(CONVERTED_UTF8),
+      ColumnType::TimestampMillis => Some(CONVERTED_TIMESTAMP_MILLIS),
+      _ => None
+    }
+  }
+
+  /// The narrowest type that holds every value seen for a field
+  fn infer<'a>(values: impl Iterator<Item = &'a Value>) -> ColumnType {
+    let mut ty = None;
+    for value in values {
+      let value_ty = match value {
+        Value::Null => continue,
+        Value::Bool(_) => ColumnType::Boolean,
+        Value::Number(n) if n.is_i64() => ColumnType::Int64,
+        Value::Number(_) => ColumnType::Double,
+        _ => return ColumnType::Utf8
+      };
+
+      ty = match (ty, value_ty) {
+        (None, t) => Some(t),
+        (Some(a), b) if a == b => Some(a),
+        (Some(ColumnType::Int64), ColumnType::Double)
+          | (Some(ColumnType::Double), ColumnType::Int64) => Some(ColumnType::Double),
+        _ => return ColumnType::Utf8
+      };
+    }
+
+    ty.unwrap_or(ColumnType::Utf8)
+  }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Cell {
+  Boolean(bool),
+  Int64(i64),
+  Double(f64),
+  Utf8(String)
+}
+
+/// Where a column's values come from
+#[derive(Debug, Clone, PartialEq)]
+enum Origin {
+  Timestamp,
+  Level,
+  Message,
+  Kind,
+  Source,
+
+  /// all fields, as a JSON object
+  Fields,
+
+  Field(String)
+}
+
+#[derive(Debug, Clone)]
+struct Column {
+  name: String,
+  ty: ColumnType,
+  origin: Origin,
+  required: bool
+}
+
+impl Column {
+  fn new(name: &str, ty: ColumnType, origin: Origin) -> Column {
+    Column { name: name.to_string(), ty, origin, required: false }
+  }
+
+  fn cell(&self, record: &NormalizedRecord, timestamp: Option<i64>) -> Option<Cell> {
+    let value = match &self.origin {
+      Origin::Timestamp => return timestamp.map(Cell::Int64),
+      Origin::Level => return record.level.map(|l| Cell::Utf8(l.to_string().to_lowercase())),
+      Origin::Message => return record.message.clone().map(Cell::Utf8),
+      Origin::Kind => return Some(Cell::Utf8(record.kind.to_string().to_lowercase())),
+      Origin::Source => return record.source.clone().map(Cell::Utf8),
+      Origin::Fields => return serde_json::to_string(&record.fields).ok().map(Cell::Utf8),
+      Origin::Field(name) => record.fields.get(name)?
+    };
+
+    match (self.ty, value) {
+      (_, Value::Null) => None,
+      (ColumnType::Boolean, Value::Bool(b)) => Some(Cell::Boolean(*b)),
+      (ColumnType::Int64, Value::Number(n)) => n.as_i64().map(Cell::Int64),
+      (ColumnType::Double, Value::Number(n)) => n.as_f64().map(Cell::Double),
+      (_, Value::String(s)) => Some(Cell::Utf8(s.clone())),
+      (_, value) => Some(Cell::Utf8(value.to_string()))
+    }
+  }
+}
+
+/// Picks columns: the canonical ones, then the `count` most frequent fields
+fn columns(records: &[NormalizedRecord], count: usize) -> Vec<Column> {
+  let mut columns = vec![
+    Column::new("timestamp", ColumnType::TimestampMillis, Origin::Timestamp),
+    Column::new("level", ColumnType::Utf8, Origin::Level),
+    Column::new("message", ColumnType::Utf8, Origin::Message),
+    Column { required: true, ..Column::new("kind", ColumnType::Utf8, Origin::Kind) },
+    Column::new("source", ColumnType::Utf8, Origin::Source)
+  ];
+
+  let mut frequency: HashMap<&str, usize> = HashMap::new();
+  for record in records {
+    for key in record.fields.keys() {
+      *frequency.entry(key.as_str()).or_insert(0) += 1;
+    }
+  }
+
+  let mut fields: Vec<(&str, usize)> = frequency.into_iter()
+    .filter(|(key, _)| !CANONICAL.contains(key))
+    .collect();
+  fields.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+
+  for (key, _) in fields.into_iter().take(count) {
+    let ty = ColumnType::infer(records.iter().filter_map(|r| r.fields.get(key)));
+    columns.push(Column::new(key, ty, Origin::Field(key.to_string())));
+  }
+
+  columns.push(Column {
+    required: true,
+    ..Column::new("fields", ColumnType::Utf8, Origin::Fields)
+  });
+
+  columns
+}
+
+/// Encodes definition levels (0 for null, 1 otherwise) as RLE runs, prefixed
+/// with their length
+fn encode_levels(present: &[bool]) -> Vec<u8> {
+  let mut runs = Compact::default();
+  let mut i = 0;
+  while i < present.len() {
+    let value = present[i];
+    let len = present[i..].iter().take_while(|p| **p == value).count();
+
+    runs.varint((len as u64) << 1);
+    runs.buf.push(value as u8);
+    i += len;
+  }
+
+  let mut out = (runs.buf.len() as u32).to_le_bytes().to_vec();
+  out.extend(runs.buf);
+  out
+}
+
+fn encode_values(cells: &[Cell]) -> Vec<u8> {
+  let mut out = Vec::new();
+  let mut bits = 0;
+  for cell in cells {
+    match cell {
+      Cell::Boolean(b) => {
+        if bits % 8 == 0 {
+          out.push(0);
+        }
+
+        *out.last_mut().unwrap() |= (*b as u8) << (bits % 8);
+        bits += 1;
+      },
+      Cell::Int64(v) => out.extend_from_slice(&v.to_le_bytes()),
+      Cell::Double(v) => out.extend_from_slice(&v.to_le_bytes()),
+      Cell::Utf8(s) => {
+        out.extend_from_slice(&(s.len() as u32).to_le_bytes());
+        out.extend_from_slice(s.as_bytes());
+      }
+    }
+  }
+
+  out
+}
+
+/// Metadata for a written column chunk
+struct ChunkMeta {
+  offset: usize,
+  size: usize,
+  num_values: usize
+}
+
+/// Writes a column chunk, as a single uncompressed data page
+fn write_chunk(
+  out: &mut Vec<u8>, column: &Column, rows: &[(&NormalizedRecord, Option<i64>)]
+) -> ChunkMeta {
+  let cells: Vec<Option<Cell>> = rows.iter().map(|(r, t)| column.cell(r, *t)).collect();
+
+  let mut data = Vec::new();
+  if !column.required {
+    let present: Vec<bool> = cells.iter().map(Option::is_some).collect();
+    data.extend(encode_levels(&present));
+  }
+
+  let values: Vec<Cell> = cells.into_iter().flatten().collect();
+  data.extend(encode_values(&values));
+
+  let mut header = Compact::default();
+  header.i32(1, PAGE_DATA);
+  header.i32(2, data.len() as i32);
+  header.i32(3, data.len() as i32);
+  header.begin(Some(5));
+  header.i32(1, rows.len() as i32);
+  header.i32(2, ENCODING_PLAIN);
+  header.i32(3, ENCODING_RLE);
+  header.i32(4, ENCODING_RLE);
+  header.end();
+  header.buf.push(0);
+
+  let offset = out.len();
+  out.extend(header.buf);
+  out.extend(data);
+
+  ChunkMeta { offset, size: out.len() - offset, num_values: rows.len() }
+}
+
+fn write_metadata(
+  columns: &[Column], num_rows: usize, row_groups: &[(usize, Vec<ChunkMeta>)]
+) -> Vec<u8> {
+  let mut m = Compact::default();
+  m.i32(1, 1);
+
+  m.list(2, CT_STRUCT, columns.len() + 1);
+  m.begin(None);
+  m.string(4, "schema");
+  m.i32(5, columns.len() as i32);
+  m.end();
+
+  for column in columns {
+    let repetition = if column.required { REPETITION_REQUIRED } else { REPETITION_OPTIONAL };
+
+    m.begin(None);
+    m.i32(1, column.ty.physical());
+    m.i32(3, repetition);
+    m.string(4, &column.name);
+    if let Some(converted) = column.ty.converted() {
+      m.i32(6, converted);
+    }
+    m.end();
+  }
+
+  m.i64(3, num_rows as i64);
+
+  m.list(4, CT_STRUCT, row_groups.len());
+  for (rows, chunks) in row_groups {
+    m.begin(None);
+    m.list(1, CT_STRUCT, chunks.len());
+    for (column, chunk) in columns.iter().zip(chunks) {
+      m.begin(None);
+      m.i64(2, chunk.offset as i64);
+
+      m.begin(Some(3));
+      m.i32(1, column.ty.physical());
+      m.list(2, CT_I32, 2);
+      m.zigzag(i64::from(ENCODING_PLAIN));
+      m.zigzag(i64::from(ENCODING_RLE));
+      m.list(3, CT_BINARY, 1);
+      m.bytes(column.name.as_bytes());
+      m.i32(4, CODEC_UNCOMPRESSED);
+      m.i64(5, chunk.num_values as i64);
+      m.i64(6, chunk.size as i64);
+      m.i64(7, chunk.size as i64);
+      m.i64(9, chunk.offset as i64);
+      m.end();
+
+      m.end();
+    }
+
+    m.i64(2, chunks.iter().map(|c| c.size as i64).sum());
+    m.i64(3, *rows as i64);
+    m.end();
+  }
+
+  m.string(6, CREATED_BY);
+  m.buf.push(0);
+
+  m.buf
+}
+
+/// Encodes records as a Parquet file, with typed columns for the canonical
+/// fields and the `field_count` most frequent other fields
+fn encode(records: &[NormalizedRecord], timestamps: &[Option<i64>], field_count: usize) -> Vec<u8> {
+  let columns = columns(records, field_count);
+  let rows: Vec<(&NormalizedRecord, Option<i64>)> = records.iter()
+    .zip(timestamps.iter().cloned())
+    .collect();
+
+  let mut out = MAGIC.to_vec();
+  let mut row_groups = Vec::new();
+  for group in rows.chunks(ROW_GROUP_SIZE) {
+    let chunks = columns.iter()
+      .map(|column| write_chunk(&mut out, column, group))
+      .collect();
+
+    row_groups.push((group.len(), chunks));
+  }
+
+  let metadata = write_metadata(&columns, records.len(), &row_groups);
+  out.extend_from_slice(&metadata);
+  out.extend_from_slice(&(metadata.len() as u32).to_le_bytes());
+  out.extend_from_slice(MAGIC);
+  out
+}
+
+/// Writes messages as a Parquet file, for analysis with e.g. DuckDB or pandas
+///
+/// Columns are typed based on all values seen, so output is only written once
+/// all input has been read.
+pub fn parquet_renderer(config: Arc<Config>, rx: Receiver<LogEntry>) -> JoinHandle<()> {
+  thread::Builder::new().name("parquet_renderer".to_string()).spawn(move || {
+    let mut records = Vec::new();
+    let mut timestamps = Vec::new();
+    for entry in rx {
+      if entry.eof.is_some() {
+        break;
+      }
+
+      if let Some(entry) = entry.message {
+        if entry.message.kind != MessageKind::Internal {
+          timestamps.push(entry.message.timestamp.map(|t| t.timestamp_millis()));
+          records.push(NormalizedRecord::new(&entry.message));
+        }
+      }
+    }
+
+    let bytes = encode(&records, &timestamps, config.parquet_fields);
+    if let Err(e) = io::stdout().lock().write_all(&bytes) {
+      eprintln!("error writing parquet: {}", e);
+    }
+  }).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::collections::HashMap;
+
+  use serde_json::json;
+  use spectral::prelude::*;
+
+  use crate::parser::{LogLevel, Message};
+
+  #[test]
+  fn test_compact() {
+    let mut c = Compact::default();
+    c.i32(1, -1);
+    c.i64(17, 300);
+    c.begin(Some(18));
+    c.string(1, "a");
+    c.end();
+    c.list(19, CT_I32, 1);
+    c.zigzag(2);
+
+    assert_that!(c.buf).is_equal_to(vec![
+      0x15, 0x01,
+      0x06, 0x22, 0xd8, 0x04,
+      0x1c, 0x18, 0x01, b'a', 0x00,
+      0x19, 0x15, 0x04
+    ]);
+  }
+
+  #[test]
+  fn test_metadata() {
+    let columns = vec![
+      Column { required: true, ..Column::new("kind", ColumnType::Utf8, Origin::Kind) },
+      Column::new("n", ColumnType::Int64, Origin::Field("n".to_string()))
+    ];
+    let chunks = vec![
+      ChunkMeta { offset: 4, size: 20, num_values: 2 },
+      ChunkMeta { offset: 24, size: 30, num_values: 2 }
+    ];
+
+    // FileMetaData, field by field per parquet.thrift
+    let expected: Vec<u8> = [
+      // 1: version, 2: schema (3 SchemaElements)
+      &[0x15, 0x02, 0x19, 0x3c][..],
+      // root: 4: name, 5: num_children
+      &[0x48, 0x06], b"schema", &[0x15, 0x04, 0x00],
+      // 1: type BYTE_ARRAY, 3: REQUIRED, 4: name, 6: UTF8
+      &[0x15, 0x0c, 0x25, 0x00, 0x18, 0x04], b"kind", &[0x25, 0x00, 0x00],
+      // 1: type INT64, 3: OPTIONAL, 4: name
+      &[0x15, 0x04, 0x25, 0x02, 0x18, 0x01], b"n", &[0x00],
+      // 3: num_rows, 4: row_groups (1), with 1: columns (2 ColumnChunks)
+      &[0x16, 0x04, 0x19, 0x1c, 0x19, 0x2c],
+      // 2: file_offset, 3: meta_data with 1: type, 2: encodings [PLAIN, RLE],
+      // 3: path_in_schema, 4: codec, 5: num_values, 6-7: sizes,
+      // 9: data_page_offset
+      &[0x26, 0x08, 0x1c, 0x15, 0x0c, 0x19, 0x25, 0x00, 0x06, 0x19, 0x18, 0x04], b"kind",
+      &[0x15, 0x00, 0x16, 0x04, 0x16, 0x28, 0x16, 0x28, 0x26, 0x08, 0x00, 0x00],
+      &[0x26, 0x30, 0x1c, 0x15, 0x04, 0x19, 0x25, 0x00, 0x06, 0x19, 0x18, 0x01], b"n",
+      &[0x15, 0x00, 0x16, 0x04, 0x16, 0x3c, 0x16, 0x3c, 0x26, 0x30, 0x00, 0x00],
+      // row group 2: total_byte_size, 3: num_rows
+      &[0x16, 0x64, 0x16, 0x04, 0x00],
+      // 6: created_by
+      &[0x28, 0x0b], b"woodchipper", &[0x00]
+    ].concat();
+
+    assert_that!(write_metadata(&columns, 2, &[(2, chunks)])).is_equal_to(expected);
+  }
+
+  #[test]
+  fn test_encode_levels() {
+    // 2 present, 1 null, 1 present
+    assert_that!(encode_levels(&[true, true, false, true])).is_equal_to(vec![
+      6, 0, 0, 0,
+      0x04, 1, 0x02, 0, 0x02, 1
+    ]);
+  }
+
+  #[test]
+  fn test_columns() {
+    let message = |fields: HashMap<String, Value>| {
+      NormalizedRecord::new(&Message {
+        kind: MessageKind::Json,
+        timestamp: None,
+        level: Some(LogLevel::Info),
+        text: None,
+        metadata: fields,
+        reader_metadata: None,
+        mapped_fields: HashMap::new(),
+        raw: String::new()
+      })
+    };
+
+    let records = vec![
+      message(hashmap!{ "status".to_string() => json!(200), "ok".to_string() => json!(true) }),
+      message(hashmap!{ "status".to_string() => json!(2.5), "pod".to_string() => json!("a") }),
+      message(hashmap!{ "status".to_string() => json!(404), "ok".to_string() => json!("no") })
+    ];
+
+    let columns = columns(&records, 2);
+    let typed: Vec<(&str, ColumnType)> = columns.iter()
+      .map(|c| (c.name.as_str(), c.ty))
+      .collect();
+
+    assert_that!(typed[5..].to_vec()).is_equal_to(vec![
+      ("status", ColumnType::Double),
+      ("ok", ColumnType::Utf8),
+      ("fields", ColumnType::Utf8)
+    ]);
+
+    let bytes = encode(&records, &[Some(0), None, Some(1)], 2);
+    assert_that!(bytes[..4].to_vec()).is_equal_to(MAGIC.to_vec());
+    assert_that!(bytes[bytes.len() - 4..].to_vec()).is_equal_to(MAGIC.to_vec());
+
+    // the footer length points back at the metadata, right after the last chunk
+    let mut len = [0; 4];
+    len.copy_from_slice(&bytes[bytes.len() - 8..bytes.len() - 4]);
+    let footer = bytes.len() - 8 - u32::from_le_bytes(len) as usize;
+    assert_that!(bytes[footer..footer + 2].to_vec()).is_equal_to(vec![0x15, 0x02]);
+  }
+}
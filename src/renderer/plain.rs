@@ -6,11 +6,15 @@ use std::sync::Arc;
 use std::sync::mpsc::Receiver;
 use std::thread::{self, JoinHandle};
 
-use crate::classifier::Chunk;
-use crate::config::Config;
+use chrono::{DateTime, Utc};
+
+use crate::classifier::{Chunk, classify};
+use crate::config::{AnsiMode, Config};
+use crate::parser::MessageKind;
 use crate::style::StyleProfile;
 use crate::renderer::types::*;
 use crate::renderer::common::*;
+use crate::renderer::pager::Output;
 
 lazy_static! {
   static ref DUMMY_STYLE: StyleProfile = StyleProfile::plain();
@@ -65,7 +69,8 @@ where
 
 pub fn plain_render(entry: &MessageEntry) -> Vec<String> {
   // don't bother with the right column
-  let (left, center, _) = bucketize(entry.chunks.iter());
+  let chunks = display_chunks(entry);
+  let (left, center, _) = bucketize(chunks.iter());
 
   let left_rendered = plain_render_region(left);
   let left_width = measure_chunks(&left_rendered);
@@ -86,22 +91,79 @@ pub fn plain_render(entry: &MessageEntry) -> Vec<String> {
   ret
 }
 
-pub fn plain_renderer(_: Arc<Config>, rx: Receiver<LogEntry>) -> JoinHandle<()> {
+/// Swaps a plaintext message's text for its original, colored line
+fn with_ansi(entry: MessageEntry) -> MessageEntry {
+  if entry.message.kind != MessageKind::Plain || !entry.message.raw.contains('\x1b') {
+    return entry;
+  }
+
+  let mut message = entry.message;
+  message.text = Some(message.raw.clone());
+  let chunks = classify(&message);
+
+  MessageEntry { message, chunks }
+}
+
+/// Writes one message, per `--project` or `--format` if set
+fn write_entry(
+  config: &Config, out: &mut Output, mut message: MessageEntry,
+  previous: &mut Option<DateTime<Utc>>
+) -> io::Result<()> {
+  if let Some(projection) = &config.project {
+    return writeln!(out, "{}", projection.to_plain(&message.message));
+  }
+
+  if let Some(template) = &config.format {
+    return writeln!(out, "{}", template.render(&message.message, None));
+  }
+
+  if config.expand_fields {
+    message = expand_fields(&message);
+  }
+
+  if config.delta {
+    let timestamp = message_timestamp(&message.message);
+    message = with_delta(&message, *previous);
+    *previous = timestamp.or(*previous);
+  }
+
+  plain_render(&message).iter().try_for_each(|line| writeln!(out, "{}", line))
+}
+
+pub fn plain_renderer(config: Arc<Config>, rx: Receiver<LogEntry>) -> JoinHandle<()> {
   thread::Builder::new().name("plain_renderer".to_string()).spawn(move || {
+    let mut previous = None;
+    let mut collapser = RepeatCollapser::default();
+    let mut out = Output::open(&config);
+
     for entry in rx {
       if entry.eof.is_some() {
         break;
       }
 
-      if let Some(message) = entry.message {
-        for line in plain_render(&message) {
-          // println! may fail when piped to e.g. head
-          // see also: https://github.com/rust-lang/rust/issues/24821
-          if writeln!(io::stdout(), "{}", line).is_err() {
-            break;
-          }
+      let message = match entry.message {
+        Some(message) if config.ansi == AnsiMode::Keep && config.color.allowed() => {
+          Some(with_ansi(message))
+        },
+        message => message
+      };
+
+      let message = match message {
+        Some(message) if config.collapse_repeats => collapser.push(message),
+        message => message
+      };
+
+      // writes may fail when piped to e.g. head
+      // see also: https://github.com/rust-lang/rust/issues/24821
+      if let Some(message) = message {
+        if write_entry(&config, &mut out, message, &mut previous).is_err() {
+          return;
         }
       }
     }
+
+    if let Some(message) = collapser.finish() {
+      write_entry(&config, &mut out, message, &mut previous).ok();
+    }
   }).unwrap()
 }
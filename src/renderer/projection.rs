@@ -0,0 +1,329 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+use std::iter::Peekable;
+use std::str::{Chars, FromStr};
+
+use serde_json::{Map, Value};
+use simple_error::SimpleError;
+
+use crate::parser::Message;
+
+/// Top-level keys of a record, besides `fields`
+static RECORD_KEYS: &[&str] = &["timestamp", "level", "message", "kind", "source", "raw"];
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+  Key(String),
+  Index(usize)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Term {
+  name: String,
+  path: Vec<Segment>
+}
+
+/// A jq-style selection of parts of each record, e.g.
+/// `.fields.request_id, .message`
+///
+/// Terms are separated by commas, and each is a path into the record, which
+/// has keys `timestamp`, `level`, `message`, `kind`, `source`, `raw`, and
+/// `fields`:
+///  - keys are written `.name` or `."name with spaces"`, and array elements
+///    `[0]`; `.` alone is the whole record
+///  - keys other than the above are shorthand for fields, e.g. `.pod` is
+///    `.fields.pod`
+///  - a term may be named with `name: .path`; otherwise it's named by its last
+///    key, e.g. `request_id`
+///
+/// Missing values are `null`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Projection {
+  terms: Vec<Term>
+}
+
+fn parse_quoted(chars: &mut Peekable<Chars>) -> Result<String, SimpleError> {
+  let mut s = String::new();
+  loop {
+    match chars.next() {
+      Some('"') => return Ok(s),
+      Some('\\') => match chars.next() {
+        Some(c) => s.push(c),
+        None => break
+      },
+      Some(c) => s.push(c),
+      None => break
+    }
+  }
+
+  bail!("unterminated string in projection")
+}
+
+fn parse_ident(chars: &mut Peekable<Chars>) -> String {
+  let mut s = String::new();
+  while let Some(&c) = chars.peek() {
+    if !(c.is_alphanumeric() || c == '_' || c == '-' || c == '@') {
+      break;
+    }
+
+    s.push(c);
+    chars.next();
+  }
+
+  s
+}
+
+fn skip_whitespace(chars: &mut Peekable<Chars>) {
+  while chars.peek().map_or(false, |c| c.is_whitespace()) {
+    chars.next();
+  }
+}
+
+fn parse_path(chars: &mut Peekable<Chars>) -> Result<Vec<Segment>, SimpleError> {
+  if chars.next() != Some('.') {
+    bail!("expected a path starting with . in projection");
+  }
+
+  let mut path = Vec::new();
+  let mut dotted = true;
+  loop {
+    match chars.peek() {
+      Some('"') if dotted => {
+        chars.next();
+        path.push(Segment::Key(parse_quoted(chars)?));
+      },
+      Some('[') => {
+        chars.next();
+        let index: String = chars.by_ref().take_while(|c| *c != ']').collect();
+        let index = index.trim().parse().map_err(|_| SimpleError::new(
+          format!("invalid array index in projection: [{}]", index)
+        ))?;
+
+        path.push(Segment::Index(index));
+      },
+      Some('.') if !dotted => {
+        chars.next();
+        dotted = true;
+        continue;
+      },
+      Some(_) if dotted => {
+        let key = parse_ident(chars);
+        if key.is_empty() {
+          if path.is_empty() {
+            // `.` alone
+            return Ok(path);
+          }
+
+          bail!("expected a key after . in projection");
+        }
+
+        path.push(Segment::Key(key));
+      },
+      _ if dotted && !path.is_empty() => bail!("expected a key after . in projection"),
+      _ => return Ok(path)
+    }
+
+    dotted = false;
+  }
+}
+
+fn parse_term(chars: &mut Peekable<Chars>) -> Result<Term, SimpleError> {
+  skip_whitespace(chars);
+
+  let name = match chars.peek() {
+    Some('.') => None,
+    Some('"') => {
+      chars.next();
+      Some(parse_quoted(chars)?)
+    },
+    _ => Some(parse_ident(chars))
+  };
+
+  if let Some(name) = &name {
+    skip_whitespace(chars);
+    if name.is_empty() || chars.next() != Some(':') {
+      bail!("expected a path like .message or name: .path in projection");
+    }
+
+    skip_whitespace(chars);
+  }
+
+  let path = parse_path(chars)?;
+  let name = match (name, path.last()) {
+    (Some(name), _) => name,
+    (None, Some(Segment::Key(key))) => key.clone(),
+    (None, Some(Segment::Index(_))) => {
+      bail!("name array elements in projection, e.g. first: .tags[0]")
+    },
+    (None, None) => "record".to_string()
+  };
+
+  Ok(Term { name, path })
+}
+
+impl FromStr for Projection {
+  type Err = SimpleError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let mut chars = s.chars().peekable();
+    let mut terms: Vec<Term> = Vec::new();
+
+    loop {
+      let term = parse_term(&mut chars)?;
+      if terms.iter().any(|t| t.name == term.name) {
+        bail!("duplicate key {} in projection, name one with e.g. other: .path", term.name);
+      }
+
+      terms.push(term);
+
+      skip_whitespace(&mut chars);
+      match chars.next() {
+        Some(',') => continue,
+        Some(c) => bail!("unexpected {:?} in projection", c),
+        None => break
+      }
+    }
+
+    Ok(Projection { terms })
+  }
+}
+
+/// The whole record, as `.` selects it
+fn record(message: &Message) -> Value {
+  let mut map: Map<String, Value> = RECORD_KEYS.iter()
+    .map(|key| (key.to_string(), message.field(key).unwrap_or(Value::Null)))
+    .collect();
+
+  map.insert("fields".to_string(), fields(message));
+  Value::Object(map)
+}
+
+fn fields(message: &Message) -> Value {
+  Value::Object(message.metadata.iter().map(|(k, v)| (k.clone(), v.clone())).collect())
+}
+
+fn resolve(message: &Message, path: &[Segment]) -> Value {
+  let (first, rest) = match path.split_first() {
+    Some((Segment::Key(key), rest)) => {
+      let value = match key.as_str() {
+        "fields" => Some(fields(message)),
+        key => message.field(key)
+      };
+
+      (value, rest)
+    },
+    Some((Segment::Index(_), rest)) => (None, rest),
+    None => return record(message)
+  };
+
+  let mut value = match first {
+    Some(value) => value,
+    None => return Value::Null
+  };
+
+  for segment in rest {
+    let next = match segment {
+      Segment::Key(key) => value.get(key),
+      Segment::Index(index) => value.get(index)
+    };
+
+    value = match next {
+      Some(next) => next.clone(),
+      None => return Value::Null
+    };
+  }
+
+  value
+}
+
+impl Projection {
+  /// Selects each term's value from a message, in order
+  pub fn values(&self, message: &Message) -> Vec<(&str, Value)> {
+    self.terms.iter()
+      .map(|term| (term.name.as_str(), resolve(message, &term.path)))
+      .collect()
+  }
+
+  /// Renders a message as a JSON object with the terms' keys, in order
+  pub fn to_json(&self, message: &Message) -> String {
+    let members: Vec<String> = self.values(message).iter()
+      .map(|(name, value)| format!("{}:{}", Value::String(name.to_string()), value))
+      .collect();
+
+    format!("{{{}}}", members.join(","))
+  }
+
+  /// Renders a message as tab-separated values, with strings unquoted and
+  /// missing values left empty
+  pub fn to_plain(&self, message: &Message) -> String {
+    let values: Vec<String> = self.values(message).into_iter()
+      .map(|(_, value)| match value {
+        Value::Null => String::new(),
+        Value::String(s) => s,
+        value => value.to_string()
+      })
+      .collect();
+
+    values.join("\t")
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::collections::HashMap;
+
+  use serde_json::json;
+  use spectral::prelude::*;
+
+  use crate::parser::{LogLevel, MessageKind};
+
+  fn message() -> Message {
+    Message {
+      kind: MessageKind::Json,
+      timestamp: None,
+      level: Some(LogLevel::Error),
+      text: Some("upstream timeout".to_string()),
+      metadata: hashmap!{
+        "request_id".to_string() => json!("f00"),
+        "http".to_string() => json!({"status": 504, "hops": ["lb", "api"]})
+      },
+      reader_metadata: None,
+      mapped_fields: HashMap::new(),
+      raw: String::new()
+    }
+  }
+
+  #[test]
+  fn test_project() {
+    let projection: Projection = ".fields.request_id, .message".parse().unwrap();
+    assert_that!(projection.to_json(&message()))
+      .is_equal_to(r#"{"request_id":"f00","message":"upstream timeout"}"#.to_string());
+    assert_that!(projection.to_plain(&message()))
+      .is_equal_to("f00\tupstream timeout".to_string());
+
+    let projection: Projection = r#"status: .http.status, last: .http."hops"[1], .missing, .level"#
+      .parse()
+      .unwrap();
+    assert_that!(projection.to_json(&message()))
+      .is_equal_to(r#"{"status":504,"last":"api","missing":null,"level":"error"}"#.to_string());
+    assert_that!(projection.to_plain(&message()))
+      .is_equal_to("504\tapi\t\terror".to_string());
+
+    let projection: Projection = ".".parse().unwrap();
+    let record: Value = serde_json::from_str(&projection.to_json(&message())).unwrap();
+    assert_that!(record["record"]["fields"]["http"]["status"]).is_equal_to(json!(504));
+  }
+
+  #[test]
+  fn test_invalid() {
+    assert_that!("message".parse::<Projection>()).is_err();
+    assert_that!(".message,".parse::<Projection>()).is_err();
+    assert_that!(".http.".parse::<Projection>()).is_err();
+    assert_that!(".tags[x]".parse::<Projection>()).is_err();
+    assert_that!(".tags[0]".parse::<Projection>()).is_err();
+    assert_that!(".message, .fields.message".parse::<Projection>()).is_err();
+    assert_that!(".message | length".parse::<Projection>()).is_err();
+  }
+}
@@ -1,22 +1,21 @@
 // (C) Copyright 2019 Hewlett Packard Enterprise Development LP
 
+use std::io::{self, Write};
 use std::sync::Arc;
-use std::sync::mpsc::Receiver;
-use std::thread::{self, JoinHandle};
 
 use crate::config::Config;
 use crate::renderer::types::*;
+use crate::renderer::registry::Renderer;
 
-pub fn raw_renderer(_: Arc<Config>, rx: Receiver<LogEntry>) -> JoinHandle<()> {
-  thread::Builder::new().name("raw_renderer".to_string()).spawn(move || {
-    for entry in rx {
-      if entry.eof.is_some() {
-        break;
-      }
+/// Writes each message's original line, exactly as read
+struct RawRenderer;
 
-      if let Some(message) = entry.message {
-        println!("{}", message.message.raw);
-      }
-    }
-  }).unwrap()
+impl Renderer for RawRenderer {
+  fn render(&mut self, entry: &MessageEntry) -> io::Result<()> {
+    writeln!(io::stdout(), "{}", entry.message.raw)
+  }
+}
+
+pub fn raw_renderer(_: Arc<Config>) -> Box<dyn Renderer> {
+  Box::new(RawRenderer)
 }
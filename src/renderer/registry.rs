@@ -0,0 +1,235 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+use std::io::{self, ErrorKind};
+use std::sync::{Arc, RwLock};
+use std::sync::mpsc::Receiver;
+use std::thread::{self, JoinHandle};
+
+use atty::{self, Stream};
+
+use crate::config::Config;
+use crate::renderer::types::*;
+use crate::renderer;
+
+/// A renderer that runs its own thread, receiving entries directly
+///
+/// This suits renderers that need to do more than handle entries in order,
+/// e.g. the interactive renderer, which also handles input and redraws.
+pub type RendererThread = fn(config: Arc<Config>, rx: Receiver<LogEntry>) -> JoinHandle<()>;
+
+/// Creates a `Renderer` for a session
+pub type RendererFactory = fn(config: Arc<Config>) -> Box<dyn Renderer>;
+
+/// What a renderer needs from, or does to, its output
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Capabilities {
+  /// If true, output must be a terminal, e.g. for a full-screen UI
+  pub needs_tty: bool,
+
+  /// If true, output is styled for a terminal and is replaced with the plain
+  /// renderer when redirected, unless explicitly selected
+  pub styled: bool,
+
+  /// If true, output is binary and is never written to a terminal
+  pub binary: bool
+}
+
+/// An output format, handling one entry at a time
+///
+/// Entries are passed to `render()` in order until the end of input, after
+/// which `teardown()` is called. If any method returns an error, rendering
+/// stops; broken pipes (e.g. from `| head`) are not reported.
+pub trait Renderer: Send {
+  /// Called once before any entries are rendered, e.g. to write a header
+  fn init(&mut self) -> io::Result<()> {
+    Ok(())
+  }
+
+  fn render(&mut self, entry: &MessageEntry) -> io::Result<()>;
+
+  /// Called once at the end of input, e.g. to write a footer or flush output
+  fn teardown(&mut self) -> io::Result<()> {
+    Ok(())
+  }
+}
+
+#[derive(Clone, Copy)]
+pub enum RendererKind {
+  Thread(RendererThread),
+  Factory(RendererFactory)
+}
+
+/// A registered renderer, selected with `--renderer <name>`
+#[derive(Clone, Copy)]
+pub struct RendererInfo {
+  pub name: &'static str,
+  pub capabilities: Capabilities,
+  pub kind: RendererKind
+}
+
+impl RendererInfo {
+  pub fn thread(name: &'static str, capabilities: Capabilities, f: RendererThread) -> Self {
+    RendererInfo { name, capabilities, kind: RendererKind::Thread(f) }
+  }
+
+  pub fn factory(name: &'static str, capabilities: Capabilities, f: RendererFactory) -> Self {
+    RendererInfo { name, capabilities, kind: RendererKind::Factory(f) }
+  }
+
+  /// Starts rendering entries from `rx`
+  pub fn spawn(&self, config: Arc<Config>, rx: Receiver<LogEntry>) -> JoinHandle<()> {
+    let name = self.name;
+    if self.capabilities.binary && atty::is(Stream::Stdout) {
+      return thread::spawn(move || eprintln!(
+        "error: refusing to write {} output to a terminal, redirect it to a file instead",
+        name
+      ));
+    }
+
+    let factory = match self.kind {
+      RendererKind::Thread(f) => return f(config, rx),
+      RendererKind::Factory(factory) => factory
+    };
+
+    thread::Builder::new().name(format!("{}_renderer", name)).spawn(move || {
+      let mut renderer = factory(config);
+      if let Err(e) = run(renderer.as_mut(), rx) {
+        if e.kind() != ErrorKind::BrokenPipe {
+          eprintln!("error in {} renderer: {}", name, e);
+        }
+      }
+    }).unwrap()
+  }
+}
+
+fn run(renderer: &mut dyn Renderer, rx: Receiver<LogEntry>) -> io::Result<()> {
+  renderer.init()?;
+
+  for entry in rx {
+    if let Some(message) = &entry.message {
+      renderer.render(message)?;
+    }
+
+    if entry.eof.is_some() {
+      break;
+    }
+  }
+
+  renderer.teardown()
+}
+
+fn builtin() -> Vec<RendererInfo> {
+  let none = Capabilities::default();
+  let styled = Capabilities { styled: true, ..none };
+  let tty = Capabilities { needs_tty: true, styled: true, ..none };
+  let binary = Capabilities { binary: true, ..none };
+
+  vec![
+    RendererInfo::thread("plain", none, renderer::plain_renderer),
+    RendererInfo::factory("raw", none, renderer::raw_renderer),
+    RendererInfo::factory("json", none, renderer::json_renderer),
+    RendererInfo::thread("styled", styled, renderer::styled_renderer),
+    RendererInfo::thread("interactive", tty, renderer::interactive_renderer),
+    RendererInfo::thread("html", none, renderer::html_renderer),
+    RendererInfo::thread("csv", none, renderer::csv_renderer),
+    RendererInfo::factory("normalized", none, renderer::normalized_renderer),
+    RendererInfo::thread("stats", none, renderer::stats_renderer),
+    RendererInfo::thread("markdown", none, renderer::markdown_renderer),
+    RendererInfo::thread("parquet", binary, renderer::parquet_renderer)
+  ]
+}
+
+lazy_static! {
+  static ref RENDERERS: RwLock<Vec<RendererInfo>> = RwLock::new(builtin());
+}
+
+/// Adds a renderer, replacing any existing renderer with the same name
+///
+/// Renderers must be registered before `run()` parses the config, so
+/// `--renderer` can find them. Built-in renderers are listed in `builtin()`.
+pub fn register(info: RendererInfo) {
+  if let Ok(mut renderers) = RENDERERS.write() {
+    renderers.retain(|r| r.name != info.name);
+    renderers.push(info);
+  }
+}
+
+/// Finds a registered renderer by name
+pub fn find(name: &str) -> Option<RendererInfo> {
+  RENDERERS.read().ok()?.iter().find(|r| r.name == name).cloned()
+}
+
+/// The names of all registered renderers, in registration order
+pub fn names() -> Vec<&'static str> {
+  match RENDERERS.read() {
+    Ok(renderers) => renderers.iter().map(|r| r.name).collect(),
+    Err(_) => Vec::new()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::sync::Mutex;
+  use std::sync::mpsc::channel;
+
+  use spectral::prelude::*;
+  use structopt::StructOpt;
+
+  lazy_static! {
+    static ref RENDERED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+  }
+
+  struct Recorder;
+
+  impl Renderer for Recorder {
+    fn render(&mut self, entry: &MessageEntry) -> io::Result<()> {
+      RENDERED.lock().unwrap().push(entry.message.raw.clone());
+      Ok(())
+    }
+
+    fn teardown(&mut self) -> io::Result<()> {
+      RENDERED.lock().unwrap().push("done".to_string());
+      Ok(())
+    }
+  }
+
+  fn recorder(_: Arc<Config>) -> Box<dyn Renderer> {
+    Box::new(Recorder)
+  }
+
+  #[test]
+  fn test_builtin() {
+    assert_that!(find("json").map(|r| r.name)).is_equal_to(Some("json"));
+    assert_that!(find("interactive").map(|r| r.capabilities.needs_tty)).is_equal_to(Some(true));
+    assert_that!(find("parquet").map(|r| r.capabilities.binary)).is_equal_to(Some(true));
+    assert_that!(find("missing").map(|r| r.name)).is_none();
+  }
+
+  #[test]
+  fn test_register() {
+    let styled = Capabilities { styled: true, ..Capabilities::default() };
+    register(RendererInfo::factory("test-replaced", Capabilities::default(), recorder));
+    register(RendererInfo::factory("test-replaced", styled, recorder));
+
+    assert_that!(names().iter().filter(|name| **name == "test-replaced").count()).is_equal_to(1);
+    assert_that!(find("test-replaced").map(|r| r.capabilities)).is_equal_to(Some(styled));
+  }
+
+  #[test]
+  fn test_spawn_factory() {
+    register(RendererInfo::factory("test-recorder", Capabilities::default(), recorder));
+
+    let config = Arc::new(Config::from_iter_safe(vec!["", "-r", "test-recorder"]).unwrap());
+    let (tx, rx) = channel();
+    let handle = find("test-recorder").unwrap().spawn(config, rx);
+
+    tx.send(LogEntry::internal("hello")).unwrap();
+    tx.send(LogEntry::eof()).unwrap();
+    handle.join().unwrap();
+
+    assert_that!(*RENDERED.lock().unwrap())
+      .is_equal_to(vec!["hello".to_string(), "done".to_string()]);
+  }
+}
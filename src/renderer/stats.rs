@@ -0,0 +1,313 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::io::{self, Write};
+use std::sync::Arc;
+use std::sync::mpsc::{Receiver, RecvTimeoutError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use atty::{self, Stream};
+use chrono::{DateTime, Utc};
+use crossterm::{ClearType, Crossterm};
+
+use crate::config::Config;
+use crate::parser::{LogLevel, Message, MessageKind};
+use crate::renderer::types::*;
+use crate::renderer::common::message_timestamp;
+use crate::renderer::grouping::canonicalize;
+
+lazy_static! {
+  /// The interval between redraws of the live summary
+  static ref REFRESH_INTERVAL: Duration = Duration::from_millis(500);
+}
+
+static SPARK_GLYPHS: &[char] = &['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// The number of top sources and messages shown
+static TOP_COUNT: usize = 5;
+
+/// Running totals for the stats renderer, and the interactive renderer's range
+/// summary
+#[derive(Debug, Default)]
+pub(crate) struct Stats {
+  total: usize,
+  levels: BTreeMap<LogLevel, usize>,
+  sources: HashMap<String, usize>,
+  messages: HashMap<String, usize>,
+
+  /// counts of error and fatal messages
+  errors: HashMap<String, usize>,
+
+  /// message counts per second, by timestamp
+  volume: BTreeMap<i64, usize>
+}
+
+fn top(counts: &HashMap<String, usize>, n: usize) -> Vec<(&str, usize)> {
+  let mut top: Vec<(&str, usize)> = counts.iter()
+    .map(|(k, v)| (k.as_str(), *v))
+    .collect();
+
+  top.sort_by_key(|(k, v)| (Reverse(*v), *k));
+  top.truncate(n);
+  top
+}
+
+/// The bar chart glyph for a count, scaled to the largest count
+pub(crate) fn spark_glyph(count: usize, max: usize) -> char {
+  match count {
+    0 => ' ',
+    c => {
+      let max = max.max(c);
+      SPARK_GLYPHS[(c * (SPARK_GLYPHS.len() - 1) + max - 1) / max]
+    }
+  }
+}
+
+/// Draws counts as a one-line bar chart, scaled to the largest count
+fn sparkline(counts: &[usize]) -> String {
+  let max = counts.iter().cloned().max().unwrap_or(0);
+  if max == 0 {
+    return String::new();
+  }
+
+  counts.iter().map(|&c| spark_glyph(c, max)).collect()
+}
+
+fn truncate(s: &str, width: usize) -> String {
+  if s.chars().count() > width {
+    let mut s: String = s.chars().take(width.saturating_sub(1)).collect();
+    s.push('…');
+    s
+  } else {
+    s.to_string()
+  }
+}
+
+impl Stats {
+  pub(crate) fn add(&mut self, message: &Message, received: DateTime<Utc>) {
+    if message.kind == MessageKind::Internal {
+      return;
+    }
+
+    self.total += 1;
+    *self.levels.entry(message.level.unwrap_or(LogLevel::Plain)).or_insert(0) += 1;
+
+    if let Some(source) = message.reader_metadata.as_ref().and_then(|m| m.source.as_ref()) {
+      *self.sources.entry(source.clone()).or_insert(0) += 1;
+    }
+
+    if let Some(text) = &message.text {
+      let template = canonicalize(text);
+      if matches!(message.level, Some(LogLevel::Error) | Some(LogLevel::Fatal)) {
+        *self.errors.entry(template.clone()).or_insert(0) += 1;
+      }
+
+      *self.messages.entry(template).or_insert(0) += 1;
+    }
+
+    let timestamp = message_timestamp(message).unwrap_or(received);
+    *self.volume.entry(timestamp.timestamp()).or_insert(0) += 1;
+  }
+
+  /// Buckets the per-second volume into `width` columns spanning all seen
+  /// timestamps
+  fn volume_buckets(&self, width: usize) -> Vec<usize> {
+    let (first, last) = match (self.volume.keys().next(), self.volume.keys().next_back()) {
+      (Some(first), Some(last)) => (*first, *last),
+      _ => return Vec::new()
+    };
+
+    let span = (last - first + 1) as usize;
+    let width = width.min(span).max(1);
+    let mut buckets = vec![0; width];
+    for (second, count) in &self.volume {
+      buckets[(*second - first) as usize * width / span] += count;
+    }
+
+    buckets
+  }
+
+  pub(crate) fn render(&self, elapsed: Duration, width: usize) -> Vec<String> {
+    let elapsed_secs = elapsed.as_secs_f64().max(0.001);
+    let mut lines = vec![
+      format!(
+        "records: {} in {:.1}s ({:.1}/s)",
+        self.total, elapsed_secs, self.total as f64 / elapsed_secs
+      ),
+      String::new(),
+      "levels:".to_string()
+    ];
+
+    for (level, count) in &self.levels {
+      lines.push(format!("  {:<8} {:>8}", level.to_string().to_lowercase(), count));
+    }
+
+    let value_width = width.saturating_sub(12).max(10);
+    let sections = [
+      ("top sources:", &self.sources),
+      ("top messages:", &self.messages),
+      ("top errors:", &self.errors)
+    ];
+
+    for (title, counts) in &sections {
+      if counts.is_empty() {
+        continue;
+      }
+
+      lines.push(String::new());
+      lines.push(title.to_string());
+      for (value, count) in top(counts, TOP_COUNT) {
+        lines.push(format!("  {:>8} {}", count, truncate(value, value_width)));
+      }
+    }
+
+    let buckets = self.volume_buckets(width.saturating_sub(2).max(1));
+    if !buckets.is_empty() {
+      lines.push(String::new());
+      lines.push("volume:".to_string());
+      lines.push(format!("  {}", sparkline(&buckets)));
+    }
+
+    lines
+  }
+}
+
+fn redraw(
+  screen: &Crossterm, stats: &Stats, elapsed: Duration, width: usize
+) -> Result<(), Box<dyn Error>> {
+  screen.cursor().goto(0, 0)?;
+  screen.terminal().clear(ClearType::All)?;
+
+  let stdout = io::stdout();
+  let mut out = stdout.lock();
+  for line in stats.render(elapsed, width) {
+    writeln!(out, "{}", line)?;
+  }
+
+  Ok(out.flush()?)
+}
+
+/// Summarizes messages rather than showing them: counts per level, top
+/// sources, the most common messages and errors by template, and a sparkline
+/// of volume over time
+///
+/// When writing to a terminal, the summary is redrawn as messages arrive;
+/// otherwise, it's printed once all input has been read.
+pub fn stats_renderer(config: Arc<Config>, rx: Receiver<LogEntry>) -> JoinHandle<()> {
+  thread::Builder::new().name("stats_renderer".to_string()).spawn(move || {
+    let screen = Crossterm::new();
+    let live = atty::is(Stream::Stdout);
+    let width = || match screen.terminal().terminal_size().0 as usize {
+      0 => config.fallback_width,
+      width => width
+    };
+
+    let start = Instant::now();
+    let mut stats = Stats::default();
+    let mut last_draw = Instant::now();
+
+    loop {
+      match rx.recv_timeout(*REFRESH_INTERVAL) {
+        Ok(entry) => {
+          if let Some(entry) = &entry.message {
+            stats.add(&entry.message, Utc::now());
+          }
+
+          if entry.eof.is_some() {
+            break;
+          }
+        },
+        Err(RecvTimeoutError::Timeout) => (),
+        Err(RecvTimeoutError::Disconnected) => break
+      }
+
+      if live && last_draw.elapsed() >= *REFRESH_INTERVAL {
+        if redraw(&screen, &stats, start.elapsed(), width()).is_err() {
+          return;
+        }
+
+        last_draw = Instant::now();
+      }
+    }
+
+    if live {
+      redraw(&screen, &stats, start.elapsed(), width()).ok();
+    } else {
+      let stdout = io::stdout();
+      let mut out = stdout.lock();
+      for line in stats.render(start.elapsed(), width()) {
+        if writeln!(out, "{}", line).is_err() {
+          break;
+        }
+      }
+    }
+  }).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::collections::HashMap;
+
+  use spectral::prelude::*;
+
+  use crate::parser::ReaderMetadata;
+
+  fn message(second: u32, level: LogLevel, source: &str, text: &str) -> Message {
+    Message {
+      kind: MessageKind::Json,
+      timestamp: Some(format!("2020-11-25T21:22:{:02}Z", second).parse().unwrap()),
+      level: Some(level),
+      text: Some(text.to_string()),
+      metadata: HashMap::new(),
+      reader_metadata: Some(ReaderMetadata {
+        timestamp: None,
+        source: Some(source.to_string())
+      }),
+      mapped_fields: HashMap::new(),
+      raw: String::new()
+    }
+  }
+
+  #[test]
+  fn test_sparkline() {
+    assert_that!(sparkline(&[0, 1, 4, 8])).is_equal_to(" ▂▅█".to_string());
+    assert_that!(sparkline(&[0, 0])).is_equal_to(String::new());
+  }
+
+  #[test]
+  fn test_render() {
+    let mut stats = Stats::default();
+    let now = Utc::now();
+    stats.add(&message(0, LogLevel::Info, "api", "request 1 done"), now);
+    stats.add(&message(0, LogLevel::Info, "api", "request 2 done"), now);
+    stats.add(&message(3, LogLevel::Error, "db", "connection lost"), now);
+
+    let lines = stats.render(Duration::from_secs(2), 40);
+    assert_that!(lines).is_equal_to(vec![
+      "records: 3 in 2.0s (1.5/s)".to_string(),
+      String::new(),
+      "levels:".to_string(),
+      "  info            2".to_string(),
+      "  error           1".to_string(),
+      String::new(),
+      "top sources:".to_string(),
+      "         2 api".to_string(),
+      "         1 db".to_string(),
+      String::new(),
+      "top messages:".to_string(),
+      "         2 request <num> done".to_string(),
+      "         1 connection lost".to_string(),
+      String::new(),
+      "top errors:".to_string(),
+      "         1 connection lost".to_string(),
+      String::new(),
+      "volume:".to_string(),
+      "  █  ▅".to_string()
+    ]);
+  }
+}
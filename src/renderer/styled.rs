@@ -1,14 +1,17 @@
 // (C) Copyright 2019 Hewlett Packard Enterprise Development LP
 
+use std::io::{self, Write};
 use std::sync::Arc;
 use std::sync::mpsc::Receiver;
 use std::thread::{self, JoinHandle};
 
+use chrono::{DateTime, Utc};
 use crossterm::Crossterm;
 
-use crate::config::Config;
+use crate::config::{Config, FieldSelection};
 use crate::renderer::types::*;
 use crate::renderer::common::*;
+use crate::renderer::pager::Output;
 
 /// A container for one or more wrapped lines in a message
 #[derive(Debug, Clone)]
@@ -16,22 +19,65 @@ pub struct RenderedMessage {
   lines: Vec<RenderedChunk>
 }
 
+/// Writes one message, per `--format` if set
+fn write_entry(
+  config: &Config, selection: &FieldSelection, out: &mut Output,
+  message_entry: MessageEntry, previous: &mut Option<DateTime<Utc>>,
+  term_width: Option<usize>
+) -> io::Result<()> {
+  if let Some(template) = &config.format {
+    // templates may have their own colors, so skip styling entirely
+    let profile = if config.color.enabled() { Some(&config.style.normal) } else { None };
+    return writeln!(out, "{}", template.render(&message_entry.message, profile));
+  }
+
+  let mut message_entry = truncate_fields(
+    selection, select_fields(selection, message_entry)
+  );
+  if config.expand_fields {
+    message_entry = expand_fields(&message_entry);
+  }
+
+  if config.delta {
+    let timestamp = message_timestamp(&message_entry.message);
+    message_entry = with_delta(&message_entry, *previous);
+    *previous = timestamp.or(*previous);
+  }
+
+  let profile = config.style.profile_for(&message_entry.message);
+  styled_render(&message_entry, &profile, term_width).iter()
+    .try_for_each(|line| writeln!(out, "{}", line))
+}
+
 pub fn styled_renderer(config: Arc<Config>, rx: Receiver<LogEntry>) -> JoinHandle<()> {
   thread::Builder::new().name("styled_renderer".to_string()).spawn(move || {
     let screen = Crossterm::new();
     let term = screen.terminal();
+    let term_width = || match term.terminal_size().0 as usize {
+      0 => Some(config.fallback_width),
+      width => Some(width)
+    };
 
-    let profile = &config.style.normal;
+    let selection = config.field_selection();
+
+    let mut previous = None;
+    let mut collapser = RepeatCollapser::default();
+    let mut out = Output::open(&config);
 
     for entry in rx {
-      if let Some(message_entry) = entry.message {
-        let term_width = match term.terminal_size().0 as usize {
-          0 => Some(config.fallback_width),
-          width => Some(width)
-        };
-
-        for line in styled_render(&message_entry, &profile, term_width) {
-          println!("{}", line);
+      let message = match entry.message {
+        Some(message) if config.collapse_repeats => collapser.push(message),
+        message => message
+      };
+
+      // the pager may have been closed, or stdout piped to e.g. head
+      if let Some(message) = message {
+        let result = write_entry(
+          &config, &selection, &mut out, message, &mut previous, term_width()
+        );
+
+        if result.is_err() {
+          return;
         }
       }
 
@@ -39,5 +85,9 @@ pub fn styled_renderer(config: Arc<Config>, rx: Receiver<LogEntry>) -> JoinHandl
         break;
       }
     }
+
+    if let Some(message) = collapser.finish() {
+      write_entry(&config, &selection, &mut out, message, &mut previous, term_width()).ok();
+    }
   }).unwrap()
 }
@@ -0,0 +1,306 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+use std::str::FromStr;
+
+use ansi_term::Style;
+use serde_json::Value;
+use simple_error::SimpleError;
+
+use crate::classifier::ChunkKind;
+use crate::parser::{LogLevel, Message};
+use crate::style::{StyleProfile, parse_style};
+use crate::timezone;
+
+/// Timestamp format used when a template doesn't specify one
+static DEFAULT_TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Align {
+  Left,
+  Right,
+  Center
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Placeholder {
+  name: String,
+
+  /// strftime format, for timestamps
+  time_format: Option<String>,
+
+  align: Align,
+  width: Option<usize>,
+
+  /// values longer than this are truncated
+  max_width: Option<usize>,
+
+  /// an explicit style; if unset, the styled renderer picks one by field
+  style: Option<Style>
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Segment {
+  Literal(String),
+  Field(Placeholder)
+}
+
+/// A user-defined line layout, e.g.
+/// `{timestamp:%H:%M:%S} {level:>5|bold} [{fields.pod}] {message}`
+///
+/// Placeholders are written `{name:spec|style}`, where both `spec` and `style`
+/// are optional:
+///  - `name` is anything accepted by `Message::field()`, optionally prefixed
+///    with `fields.`
+///  - `spec` is a strftime format for `timestamp`, or otherwise an alignment
+///    (`<`, `>` or `^`), a width, and a maximum width, e.g. `>5` or `<20.20`
+///  - `style` is a comma-separated list of colors and effects, e.g.
+///    `red,bold`, or `plain` for none
+///
+/// Literal braces are written `{{` and `}}`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+  segments: Vec<Segment>
+}
+
+fn parse_placeholder(s: &str) -> Result<Placeholder, SimpleError> {
+  let (s, style) = match s.find('|') {
+    Some(i) => (&s[..i], Some(parse_style(&s[i + 1..])?)),
+    None => (s, None)
+  };
+
+  let (name, spec) = match s.find(':') {
+    Some(i) => (&s[..i], Some(&s[i + 1..])),
+    None => (s, None)
+  };
+
+  let name = name.trim();
+  let name = name.strip_prefix("fields.").unwrap_or(name);
+  if name.is_empty() {
+    bail!("empty placeholder in format");
+  }
+
+  let mut placeholder = Placeholder {
+    name: name.to_string(),
+    time_format: None,
+    align: Align::Left,
+    width: None,
+    max_width: None,
+    style
+  };
+
+  let spec = match spec {
+    Some(spec) if name == "timestamp" => {
+      placeholder.time_format = Some(spec.to_string());
+      return Ok(placeholder);
+    },
+    Some(spec) => spec,
+    None => return Ok(placeholder)
+  };
+
+  let spec = if let Some(rest) = spec.strip_prefix('<') {
+    rest
+  } else if let Some(rest) = spec.strip_prefix('>') {
+    placeholder.align = Align::Right;
+    rest
+  } else if let Some(rest) = spec.strip_prefix('^') {
+    placeholder.align = Align::Center;
+    rest
+  } else {
+    spec
+  };
+
+  let mut parts = spec.splitn(2, '.');
+  let invalid = || SimpleError::new(format!("invalid format spec for {}: {}", name, spec));
+
+  match parts.next() {
+    Some("") | None => (),
+    Some(width) => placeholder.width = Some(width.parse().map_err(|_| invalid())?)
+  };
+
+  if let Some(max) = parts.next() {
+    placeholder.max_width = Some(max.parse().map_err(|_| invalid())?);
+  }
+
+  Ok(placeholder)
+}
+
+impl FromStr for Template {
+  type Err = SimpleError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let mut segments = Vec::new();
+    let mut literal = String::new();
+    let mut chars = s.chars().peekable();
+
+    while let Some(c) = chars.next() {
+      match c {
+        '{' if chars.peek() == Some(&'{') => {
+          chars.next();
+          literal.push('{');
+        },
+        '}' if chars.peek() == Some(&'}') => {
+          chars.next();
+          literal.push('}');
+        },
+        '{' => {
+          let mut inner = String::new();
+          loop {
+            match chars.next() {
+              Some('}') => break,
+              Some(c) => inner.push(c),
+              None => bail!("unclosed '{' in format")
+            }
+          }
+
+          if !literal.is_empty() {
+            segments.push(Segment::Literal(literal.split_off(0)));
+          }
+
+          segments.push(Segment::Field(parse_placeholder(&inner)?));
+        },
+        '}' => bail!("unmatched '}' in format"),
+        c => literal.push(c)
+      }
+    }
+
+    if !literal.is_empty() {
+      segments.push(Segment::Literal(literal));
+    }
+
+    Ok(Template { segments })
+  }
+}
+
+fn pad(value: &str, align: Align, width: usize) -> String {
+  match align {
+    Align::Left => format!("{:<w$}", value, w = width),
+    Align::Right => format!("{:>w$}", value, w = width),
+    Align::Center => format!("{:^w$}", value, w = width)
+  }
+}
+
+impl Placeholder {
+  fn value(&self, message: &Message) -> String {
+    if self.name == "timestamp" {
+      let timestamp = message.timestamp
+        .or_else(|| message.reader_metadata.as_ref().and_then(|m| m.timestamp));
+
+      let format = match &self.time_format {
+        Some(format) => format,
+        None => match timestamp.and_then(timezone::to_relative) {
+          Some(relative) => return relative,
+          None => DEFAULT_TIME_FORMAT
+        }
+      };
+
+      return match timestamp {
+        Some(t) => timezone::to_display(t).format(format).to_string(),
+        None => "-".to_string()
+      };
+    }
+
+    match message.field(&self.name) {
+      Some(Value::String(s)) => s,
+      Some(Value::Null) | None => String::new(),
+      Some(value) => value.to_string()
+    }
+  }
+
+  /// The style used when none is given explicitly, matching the chunk this
+  /// field would normally be displayed in
+  fn default_kind(&self, message: &Message) -> ChunkKind {
+    match self.name.as_str() {
+      "timestamp" => ChunkKind::Time,
+      "level" => ChunkKind::Level(message.level.unwrap_or(LogLevel::Plain)),
+      "message" | "text" => ChunkKind::Text,
+      "source" => ChunkKind::Context,
+      _ => ChunkKind::FieldValue
+    }
+  }
+
+  fn render(&self, message: &Message, profile: Option<&StyleProfile>) -> String {
+    let mut value = self.value(message);
+
+    if let Some(max) = self.max_width {
+      if value.chars().count() > max {
+        value = value.chars().take(max).collect();
+      }
+    }
+
+    if let Some(width) = self.width {
+      value = pad(&value, self.align, width);
+    }
+
+    match profile {
+      Some(profile) => {
+        let style = self.style.unwrap_or_else(|| *profile.get_style(&self.default_kind(message)));
+        style.paint(value).to_string()
+      },
+      None => value
+    }
+  }
+}
+
+impl Template {
+  /// Renders a message, styling fields if a profile is given
+  pub fn render(&self, message: &Message, profile: Option<&StyleProfile>) -> String {
+    self.segments.iter()
+      .map(|segment| match segment {
+        Segment::Literal(s) => s.clone(),
+        Segment::Field(placeholder) => placeholder.render(message, profile)
+      })
+      .collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::collections::HashMap;
+
+  use ansi_term::Color;
+  use serde_json::json;
+  use spectral::prelude::*;
+
+  use crate::parser::MessageKind;
+
+  fn message() -> Message {
+    Message {
+      kind: MessageKind::Json,
+      timestamp: None,
+      level: Some(LogLevel::Warning),
+      text: Some("slow request".to_string()),
+      metadata: hashmap!{
+        "pod".to_string() => json!("api-7d9f8"),
+        "status".to_string() => json!(200)
+      },
+      reader_metadata: None,
+      mapped_fields: HashMap::new(),
+      raw: String::new()
+    }
+  }
+
+  #[test]
+  fn test_render() {
+    let template: Template = "{timestamp:%H:%M} {level:>8} [{fields.pod:.3}] {{{status}}} {message}"
+      .parse()
+      .unwrap();
+
+    assert_that!(template.render(&message(), None))
+      .is_equal_to("-  warning [api] {200} slow request".to_string());
+
+    let styled: Template = "{level|red,bold} {message}".parse().unwrap();
+    assert_that!(styled.render(&message(), Some(&StyleProfile::plain())))
+      .is_equal_to(format!("{} slow request", Color::Red.bold().paint("warning")));
+  }
+
+  #[test]
+  fn test_invalid() {
+    assert_that!("{level".parse::<Template>()).is_err();
+    assert_that!("level}".parse::<Template>()).is_err();
+    assert_that!("{}".parse::<Template>()).is_err();
+    assert_that!("{level:wide}".parse::<Template>()).is_err();
+    assert_that!("{level|sparkly}".parse::<Template>()).is_err();
+  }
+}
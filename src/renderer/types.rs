@@ -2,14 +2,15 @@
 
 use std::collections::HashMap;
 use std::error::Error;
+use std::fmt;
 use std::sync::Arc;
-use std::sync::mpsc::Receiver;
-use std::thread::JoinHandle;
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 use chrono::offset::Utc;
+use serde_json::{Map, Value};
 
 use crate::config::Config;
-use crate::parser::{LogLevel, Message, MessageKind, ReaderMetadata, parse};
+use crate::parser::{LogLevel, Message, MessageKind, ReaderMetadata, parse, parse_record};
 use crate::classifier::{Chunk, classify};
 
 #[derive(Debug, Clone)]
@@ -46,17 +47,56 @@ impl MessageEntry {
 #[derive(Debug)]
 pub struct EofEntry;
 
+/// The health of a followed source, as reported by its reader
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SourceState {
+  Streaming,
+  Reconnecting,
+  Dead,
+
+  /// The end of input was reached
+  Ended
+}
+
+impl fmt::Display for SourceState {
+  fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+    write!(f, "{}", match self {
+      SourceState::Streaming => "streaming",
+      SourceState::Reconnecting => "reconnecting",
+      SourceState::Dead => "dead",
+      SourceState::Ended => "ended"
+    })
+  }
+}
+
+/// A LogEntry sent when a source's state changes
+#[derive(Debug, Clone)]
+pub struct StatusEntry {
+  pub source: String,
+  pub state: SourceState
+}
+
+/// The number of lines dropped because no parser produced a message from them
+static DROPPED: AtomicUsize = AtomicUsize::new(0);
+
+/// Returns the number of lines dropped so far
+pub fn dropped_lines() -> usize {
+  DROPPED.load(Ordering::Relaxed)
+}
+
 #[derive(Debug)]
 pub struct LogEntry {
   pub message: Option<MessageEntry>,
-  pub eof: Option<EofEntry>
+  pub eof: Option<EofEntry>,
+  pub status: Option<StatusEntry>
 }
 
 impl Default for LogEntry {
   fn default() -> LogEntry {
     LogEntry {
       message: None,
-      eof: None
+      eof: None,
+      status: None
     }
   }
 }
@@ -69,12 +109,43 @@ impl LogEntry {
     }
   }
 
+  pub fn status(source: &str, state: SourceState) -> LogEntry {
+    LogEntry {
+      status: Some(StatusEntry { source: source.to_string(), state }),
+      ..Default::default()
+    }
+  }
+
   pub fn message(
     config: Arc<Config>, line: &str, meta: Option<ReaderMetadata>
   ) -> Result<Option<LogEntry>, Box<dyn Error>> {
     let message = match parse(config, &line, meta)? {
       Some(message) => message,
-      None => return Ok(None)
+      None => {
+        DROPPED.fetch_add(1, Ordering::Relaxed);
+        return Ok(None);
+      }
+    };
+
+    let chunks = classify(&message);
+
+    Ok(Some(LogEntry {
+      message: Some(MessageEntry { message, chunks }),
+
+      ..Default::default()
+    }))
+  }
+
+  /// Creates an entry from a record decoded from a binary stream
+  pub fn record(
+    config: Arc<Config>, doc: Map<String, Value>, meta: Option<ReaderMetadata>
+  ) -> Result<Option<LogEntry>, Box<dyn Error>> {
+    let message = match parse_record(config, doc, meta)? {
+      Some(message) => message,
+      None => {
+        DROPPED.fetch_add(1, Ordering::Relaxed);
+        return Ok(None);
+      }
     };
 
     let chunks = classify(&message);
@@ -86,6 +157,17 @@ impl LogEntry {
     }))
   }
 
+  /// Creates an entry from an already parsed message, e.g. from a session
+  pub fn parsed(message: Message) -> LogEntry {
+    let chunks = classify(&message);
+
+    LogEntry {
+      message: Some(MessageEntry { message, chunks }),
+
+      ..Default::default()
+    }
+  }
+
   pub fn internal(message: &str) -> LogEntry {
     LogEntry {
       message: Some(MessageEntry::internal(message)),
@@ -94,5 +176,3 @@ impl LogEntry {
     }
   }
 }
-
-pub type Renderer = fn(config: Arc<Config>, rx: Receiver<LogEntry>) -> JoinHandle<()>;
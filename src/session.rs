@@ -0,0 +1,178 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, BufWriter, Lines, Write};
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use simple_error::{SimpleError, SimpleResult};
+
+use crate::history::SavedFilter;
+use crate::parser::{LogLevel, Message};
+
+/// The session file format version, bumped on incompatible changes
+const SESSION_VERSION: u32 = 1;
+
+/// How the interactive view looked when a session was snapshotted
+///
+/// Bookmarks, the selection, and the mark are positions in the snapshot's
+/// records, which are stored in the order they arrived.
+#[derive(Debug, Default, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionView {
+  pub version: u32,
+
+  /// The number of records following the view
+  pub records: usize,
+
+  #[serde(default)]
+  pub filters: Vec<SavedFilter>,
+
+  #[serde(default)]
+  pub min_level: Option<LogLevel>,
+
+  #[serde(default)]
+  pub context: usize,
+
+  /// Sources whose messages were hidden
+  #[serde(default)]
+  pub hidden_sources: Vec<String>,
+
+  #[serde(default)]
+  pub bookmarks: Vec<usize>,
+
+  #[serde(default)]
+  pub selection: Option<usize>,
+
+  #[serde(default)]
+  pub mark: Option<usize>
+}
+
+/// Writes a session file: the view as one line of JSON, then every record,
+/// one JSON message per line
+///
+/// Returns the number of records written.
+pub fn write<'a>(
+  path: &Path, mut view: SessionView, messages: impl Iterator<Item = &'a Message>,
+  overwrite: bool
+) -> SimpleResult<usize> {
+  let messages: Vec<&Message> = messages.collect();
+  view.version = SESSION_VERSION;
+  view.records = messages.len();
+
+  let file = OpenOptions::new()
+    .write(true)
+    .truncate(true)
+    .create(overwrite)
+    .create_new(!overwrite)
+    .open(path)
+    .map_err(|e| SimpleError::new(format!("could not write {}: {}", path.display(), e)))?;
+
+  let mut out = BufWriter::new(file);
+  let mut write_line = |line: String| writeln!(out, "{}", line).map_err(SimpleError::from);
+
+  write_line(serde_json::to_string(&view).map_err(SimpleError::from)?)?;
+  for message in &messages {
+    write_line(serde_json::to_string(message).map_err(SimpleError::from)?)?;
+  }
+
+  out.flush().map_err(SimpleError::from)?;
+
+  Ok(messages.len())
+}
+
+/// An open session file
+pub struct SessionReader {
+  pub view: SessionView,
+  lines: Lines<BufReader<File>>
+}
+
+impl SessionReader {
+  pub fn open(path: &Path) -> SimpleResult<SessionReader> {
+    let file = File::open(path).map_err(|e| SimpleError::new(
+      format!("could not open session {}: {}", path.display(), e)
+    ))?;
+
+    let mut lines = BufReader::new(file).lines();
+    let header = match lines.next() {
+      Some(line) => line.map_err(SimpleError::from)?,
+      None => bail!("{} is empty, not a session", path.display())
+    };
+
+    let view: SessionView = serde_json::from_str(&header).map_err(|e| SimpleError::new(
+      format!("{} is not a woodchipper session: {}", path.display(), e)
+    ))?;
+
+    if view.version != SESSION_VERSION {
+      bail!(
+        "{} is a version {} session, but only version {} is supported",
+        path.display(), view.version, SESSION_VERSION
+      );
+    }
+
+    Ok(SessionReader { view, lines })
+  }
+}
+
+impl Iterator for SessionReader {
+  type Item = SimpleResult<Message>;
+
+  fn next(&mut self) -> Option<Self::Item> {
+    self.lines.next().map(|line| {
+      let line = line.map_err(SimpleError::from)?;
+      serde_json::from_str(&line).map_err(SimpleError::from)
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::collections::HashMap;
+  use std::env;
+
+  use spectral::prelude::*;
+
+  use crate::filter::FilterMode;
+  use crate::parser::MessageKind;
+
+  fn message(text: &str) -> Message {
+    Message {
+      kind: MessageKind::Plain,
+      timestamp: None,
+      level: Some(LogLevel::Info),
+      raw: text.to_string(),
+      text: Some(text.to_string()),
+      metadata: HashMap::new(),
+      reader_metadata: None,
+      mapped_fields: HashMap::new()
+    }
+  }
+
+  #[test]
+  fn test_round_trip() {
+    let path = env::temp_dir().join(format!("woodchipper-test-{}.wcs", std::process::id()));
+    let view = SessionView {
+      filters: vec![SavedFilter::new(FilterMode::Expression, "level >= warn", false)],
+      min_level: Some(LogLevel::Info),
+      hidden_sources: vec!["db".to_string()],
+      bookmarks: vec![1],
+      selection: Some(0),
+      ..Default::default()
+    };
+
+    let messages = [message("first"), message("second")];
+    assert_that!(write(&path, view.clone(), messages.iter(), false)).is_ok_containing(2);
+
+    // snapshots aren't overwritten unless asked
+    assert_that!(write(&path, view.clone(), messages.iter(), false)).is_err();
+
+    let reader = SessionReader::open(&path).unwrap();
+    assert_that!(reader.view).is_equal_to(SessionView { version: 1, records: 2, ..view });
+
+    let texts: Vec<String> = reader.map(|m| m.unwrap().raw).collect();
+    assert_that!(texts).is_equal_to(vec!["first".to_string(), "second".to_string()]);
+
+    std::fs::remove_file(&path).ok();
+  }
+}
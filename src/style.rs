@@ -1,6 +1,7 @@
 // (C) Copyright 2019 Hewlett Packard Enterprise Development LP
 
-use std::collections::BTreeMap;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap};
 use std::error::Error;
 use std::io::BufReader;
 use std::fmt;
@@ -12,10 +13,49 @@ use ansi_term::{Style, Color};
 use regex::Regex;
 use serde::Deserialize;
 use serde::de::{self, Visitor, Unexpected, Deserializer};
+use serde_json::Value;
 use shellexpand;
+use simple_error::SimpleError;
 
 use crate::classifier::ChunkKind;
-use crate::parser::LogLevel;
+use crate::config::{ColorDepth, de_regex};
+use crate::parser::{LogLevel, Message};
+
+/// Parses a named ANSI color, e.g. `red`
+fn named_color(s: &str) -> Option<Color> {
+  Some(match s.to_lowercase().as_str() {
+    "black" => Color::Black,
+    "red" => Color::Red,
+    "green" => Color::Green,
+    "yellow" => Color::Yellow,
+    "blue" => Color::Blue,
+    "purple" | "magenta" => Color::Purple,
+    "cyan" => Color::Cyan,
+    "white" => Color::White,
+    _ => return None
+  })
+}
+
+/// Parses a comma-separated list of colors and effects, e.g. `red,bold`, or
+/// `plain` for none
+pub fn parse_style(s: &str) -> Result<Style, SimpleError> {
+  let mut style = Style::new();
+  for word in s.split(',').map(str::trim) {
+    style = match word {
+      "plain" => style,
+      "bold" => style.bold(),
+      "dimmed" => style.dimmed(),
+      "italic" => style.italic(),
+      "underline" => style.underline(),
+      other => match named_color(other) {
+        Some(color) => style.fg(color),
+        None => bail!("unknown style: {}", other)
+      }
+    };
+  }
+
+  Ok(style)
+}
 
 struct ColorFromStr;
 
@@ -23,13 +63,17 @@ impl<'de> Visitor<'de> for ColorFromStr {
   type Value = Color;
 
   fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
-    f.write_str("a string containing a hexidecimal RGB color")
+    f.write_str("a string containing a hexidecimal RGB color or a color name")
   }
 
   fn visit_str<E>(self, s: &str) -> Result<Self::Value, E>
   where
     E: de::Error,
   {
+    if let Some(color) = named_color(s) {
+      return Ok(color);
+    }
+
     let color_part = s.trim_start_matches('#');
     if color_part.len() != 6 {
       return Err(de::Error::invalid_length(6, &self));
@@ -61,6 +105,157 @@ where
   deserializer.deserialize_str(ColorFromStr)
 }
 
+fn de_opt_color<'de, D>(deserializer: D) -> Result<Option<Color>, D::Error>
+where
+  D: Deserializer<'de>
+{
+  de_color(deserializer).map(Some)
+}
+
+pub fn de_opt_regex<'de, D>(deserializer: D) -> Result<Option<Regex>, D::Error>
+where
+  D: Deserializer<'de>
+{
+  de_regex(deserializer).map(Some)
+}
+
+pub fn de_opt_level<'de, D>(deserializer: D) -> Result<Option<LogLevel>, D::Error>
+where
+  D: Deserializer<'de>
+{
+  let s = String::deserialize(deserializer)?;
+  s.parse().map(Some).map_err(|_| de::Error::custom(format!("invalid log level: {}", s)))
+}
+
+fn de_style<'de, D>(deserializer: D) -> Result<Style, D::Error>
+where
+  D: Deserializer<'de>
+{
+  let s = String::deserialize(deserializer)?;
+  parse_style(&s).map_err(de::Error::custom)
+}
+
+/// Formats a field value for comparison, without quoting strings
+pub fn value_string(value: &Value) -> String {
+  match value {
+    Value::String(s) => s.clone(),
+    value => value.to_string()
+  }
+}
+
+/// A field value as a number, parsing numeric strings
+pub fn value_number(value: &Value) -> Option<f64> {
+  match value {
+    Value::String(s) => s.parse().ok(),
+    value => value.as_f64()
+  }
+}
+
+/// Conditions on a message, shared by the config file's `rules`; a rule
+/// applies if all of its conditions hold
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct RuleConditions {
+  /// A field name as accepted by `Message::field()`, e.g. `status`; the
+  /// message must have this field, and any comparisons apply to its value
+  pub field: Option<String>,
+
+  /// A regex the message text must match
+  #[serde(default, deserialize_with = "de_opt_regex")]
+  pub message: Option<Regex>,
+
+  /// A regex the field value must match
+  #[serde(default, deserialize_with = "de_opt_regex")]
+  pub matches: Option<Regex>,
+
+  /// A value the field must equal; numbers and strings are compared as text,
+  /// so `500` matches both `500` and `"500"`
+  pub equals: Option<Value>,
+
+  pub gt: Option<f64>,
+  pub gte: Option<f64>,
+  pub lt: Option<f64>,
+  pub lte: Option<f64>
+}
+
+impl RuleConditions {
+  pub fn matches(&self, message: &Message) -> bool {
+    if let Some(re) = &self.message {
+      match &message.text {
+        Some(text) if re.is_match(text) => (),
+        _ => return false
+      }
+    }
+
+    let name = match &self.field {
+      Some(name) => name.strip_prefix("fields.").unwrap_or(name),
+      None => return true
+    };
+
+    let value = match message.field(name) {
+      Some(Value::Null) | None => return false,
+      Some(value) => value
+    };
+
+    if let Some(expected) = &self.equals {
+      if value_string(&value) != value_string(expected) {
+        return false;
+      }
+    }
+
+    if let Some(re) = &self.matches {
+      if !re.is_match(&value_string(&value)) {
+        return false;
+      }
+    }
+
+    let bounds = [self.gt, self.gte, self.lt, self.lte];
+    if bounds.iter().all(Option::is_none) {
+      return true;
+    }
+
+    let number = match value_number(&value) {
+      Some(number) => number,
+      None => return false
+    };
+
+    self.gt.map_or(true, |b| number > b) &&
+      self.gte.map_or(true, |b| number >= b) &&
+      self.lt.map_or(true, |b| number < b) &&
+      self.lte.map_or(true, |b| number <= b)
+  }
+}
+
+/// A rule that styles messages matching all of its conditions, from the config
+/// file's `rules` section
+#[derive(Debug, Clone, Deserialize)]
+pub struct StyleRule {
+  #[serde(flatten)]
+  pub conditions: RuleConditions,
+
+  /// Colors and effects added to the whole message, e.g. `red,bold`
+  #[serde(deserialize_with = "de_style")]
+  pub style: Style
+}
+
+impl StyleRule {
+  pub fn matches(&self, message: &Message) -> bool {
+    self.conditions.matches(message)
+  }
+}
+
+/// Adds the colors and effects of `overlay` to `style`
+fn overlay_style(style: Style, overlay: Style) -> Style {
+  Style {
+    foreground: overlay.foreground.or(style.foreground),
+    background: overlay.background.or(style.background),
+    is_bold: style.is_bold || overlay.is_bold,
+    is_dimmed: style.is_dimmed || overlay.is_dimmed,
+    is_italic: style.is_italic || overlay.is_italic,
+    is_underline: style.is_underline || overlay.is_underline,
+    ..style
+  }
+}
+
 #[derive(Debug, Deserialize)]
 #[allow(non_snake_case)]
 pub struct Base16 {
@@ -83,55 +278,299 @@ pub struct Base16 {
 }
 
 impl Base16 {
+  pub fn to_palette(&self) -> Palette {
+    Palette {
+      extends: None,
+      foreground: Some(self.base05),
+      timestamp: Some(self.base03),
+      field_key: Some(self.base0C),
+      context: Some(self.base03),
+      debug: Some(self.base0C),
+      info: Some(self.base0B),
+      warning: Some(self.base0A),
+      error: Some(self.base09),
+      fatal: Some(self.base08),
+      internal: Some(self.base0F),
+      selection: Some(self.base02),
+      highlight: Some(self.base06)
+    }
+  }
+}
+
+/// Colors for each part of the display, used to build a theme
+///
+/// User-defined palettes may be set in the config file, e.g.:
+///
+/// ```yaml
+/// themes:
+///   mine:
+///     extends: light
+///     error: "#d70000"
+///     field_key: blue
+/// ```
+///
+/// Unset colors are taken from the `extends` theme if any, or otherwise use the
+/// terminal's default color.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Palette {
+  /// A built-in theme to take unset colors from
+  pub extends: Option<String>,
+
+  #[serde(deserialize_with = "de_opt_color")] pub foreground: Option<Color>,
+  #[serde(deserialize_with = "de_opt_color")] pub timestamp: Option<Color>,
+  #[serde(deserialize_with = "de_opt_color")] pub field_key: Option<Color>,
+  #[serde(deserialize_with = "de_opt_color")] pub context: Option<Color>,
+  #[serde(deserialize_with = "de_opt_color")] pub debug: Option<Color>,
+  #[serde(deserialize_with = "de_opt_color")] pub info: Option<Color>,
+  #[serde(deserialize_with = "de_opt_color")] pub warning: Option<Color>,
+  #[serde(deserialize_with = "de_opt_color")] pub error: Option<Color>,
+  #[serde(deserialize_with = "de_opt_color")] pub fatal: Option<Color>,
+  #[serde(deserialize_with = "de_opt_color")] pub internal: Option<Color>,
+
+  /// Background of the selected line in the interactive renderer
+  #[serde(deserialize_with = "de_opt_color")] pub selection: Option<Color>,
+
+  /// Foreground of highlighted (e.g. search result) lines
+  #[serde(deserialize_with = "de_opt_color")] pub highlight: Option<Color>
+}
+
+/// Builds a built-in palette from hex colors, in the same order as `Palette`'s
+/// fields
+fn rgb_palette(colors: [u32; 12]) -> Palette {
+  let c = |i: usize| {
+    let rgb = colors[i];
+    Some(Color::RGB((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8))
+  };
+
+  Palette {
+    extends: None,
+    foreground: c(0),
+    timestamp: c(1),
+    field_key: c(2),
+    context: c(3),
+    debug: c(4),
+    info: c(5),
+    warning: c(6),
+    error: c(7),
+    fatal: c(8),
+    internal: c(9),
+    selection: c(10),
+    highlight: c(11)
+  }
+}
+
+impl Palette {
+  /// Finds a built-in palette by name
+  pub fn builtin(name: &str) -> Option<Palette> {
+    Some(rgb_palette(match name {
+      "dark" => [
+        0xc5c8c6, 0x969896, 0x8abeb7, 0x969896, 0x8abeb7, 0xb5bd68,
+        0xf0c674, 0xde935f, 0xcc6666, 0xb294bb, 0x373b41, 0xffffff
+      ],
+      "light" => [
+        0x4d4d4c, 0x8e908c, 0x3e999f, 0x8e908c, 0x3e999f, 0x718c00,
+        0xc99e00, 0xf5871f, 0xc82829, 0x8959a8, 0xd6d6d6, 0x1d1f21
+      ],
+      "solarized" => [
+        0x839496, 0x586e75, 0x2aa198, 0x586e75, 0x2aa198, 0x859900,
+        0xb58900, 0xcb4b16, 0xdc322f, 0x6c71c4, 0x073642, 0x93a1a1
+      ],
+      "dracula" => [
+        0xf8f8f2, 0x6272a4, 0x8be9fd, 0x6272a4, 0x8be9fd, 0x50fa7b,
+        0xf1fa8c, 0xffb86c, 0xff5555, 0xbd93f9, 0x44475a, 0xffffff
+      ],
+      _ => return None
+    }))
+  }
+
+  /// Fills unset colors from the `extends` theme
+  fn resolve(&self) -> Result<Palette, Box<dyn Error>> {
+    let parent = match &self.extends {
+      Some(name) => match Palette::builtin(name) {
+        Some(parent) => parent,
+        None => bail!(format!("unknown theme to extend: {}", name))
+      },
+      None => return Ok(self.clone())
+    };
+
+    Ok(Palette {
+      extends: None,
+      foreground: self.foreground.or(parent.foreground),
+      timestamp: self.timestamp.or(parent.timestamp),
+      field_key: self.field_key.or(parent.field_key),
+      context: self.context.or(parent.context),
+      debug: self.debug.or(parent.debug),
+      info: self.info.or(parent.info),
+      warning: self.warning.or(parent.warning),
+      error: self.error.or(parent.error),
+      fatal: self.fatal.or(parent.fatal),
+      internal: self.internal.or(parent.internal),
+      selection: self.selection.or(parent.selection),
+      highlight: self.highlight.or(parent.highlight)
+    })
+  }
+
   fn chunk_styles(&self, base: Style) -> BTreeMap<ChunkKind, Style> {
+    let fg = |color: Option<Color>| match color {
+      Some(color) => base.fg(color),
+      None => base
+    };
+
     btreemap!{
-      ChunkKind::Date => base.fg(self.base03),
-      ChunkKind::Time => base.fg(self.base03),
-      ChunkKind::FieldKey => base.fg(self.base0C),
-      ChunkKind::Context => base.fg(self.base03),
-
-      ChunkKind::Level(LogLevel::Debug) => base.fg(self.base0C),
-      ChunkKind::Level(LogLevel::Info) => base.fg(self.base0B),
-      ChunkKind::Level(LogLevel::Warning) => base.fg(self.base0A),
-      ChunkKind::Level(LogLevel::Error) => base.fg(self.base09),
-      ChunkKind::Level(LogLevel::Fatal) => base.fg(self.base08),
+      ChunkKind::Date => fg(self.timestamp),
+      ChunkKind::Time => fg(self.timestamp),
+      ChunkKind::Delta => fg(self.timestamp),
+      ChunkKind::FieldKey => fg(self.field_key),
+      ChunkKind::Context => fg(self.context),
+      ChunkKind::Repeat => fg(self.warning).bold(),
+      ChunkKind::Collapsed => fg(self.context),
+
+      ChunkKind::Level(LogLevel::Debug) => fg(self.debug),
+      ChunkKind::Level(LogLevel::Info) => fg(self.info),
+      ChunkKind::Level(LogLevel::Warning) => fg(self.warning),
+      ChunkKind::Level(LogLevel::Error) => fg(self.error),
+      ChunkKind::Level(LogLevel::Fatal) => fg(self.fatal),
       ChunkKind::Level(LogLevel::Plain) => base,
-      ChunkKind::Level(LogLevel::Int) => base.fg(self.base0F)
+      ChunkKind::Level(LogLevel::Int) => fg(self.internal)
     }
   }
 
-  pub fn to_profile_normal(&self) -> StyleProfile {
-    let base = Style::new().fg(self.base05);
+  fn profile(&self, base: Style, opaque: bool) -> StyleProfile {
     StyleProfile {
       base_style: base,
-      opaque: false, // TODO: make opaque configurable?
+      opaque,
+      syntax: !opaque,
       chunk_styles: self.chunk_styles(base)
     }
   }
 
-  pub fn to_profile_selected(&self) -> StyleProfile {
-    let base = Style::new().fg(self.base05).on(self.base02);
-    StyleProfile {
-      base_style: base,
-      opaque: true,
-      chunk_styles: self.chunk_styles(base)
+  pub fn to_style_config(&self) -> StyleConfig {
+    let base = match self.foreground {
+      Some(color) => Style::new().fg(color),
+      None => Style::new()
+    };
+
+    let selected = match self.selection {
+      Some(color) => base.on(color),
+      None => base.reverse()
+    };
+
+    let highlighted = match self.highlight {
+      Some(color) => Style::new().fg(color).bold(),
+      None => base.bold()
+    };
+
+    StyleConfig {
+      name: String::new(),
+      normal: self.profile(base, false),
+      selected: self.profile(selected, true),
+      highlighted: self.profile(highlighted, false),
+      theme: None,
+      rules: Vec::new()
     }
   }
+}
 
-  pub fn to_profile_highlighted(&self) -> StyleProfile {
-    let base = Style::new().fg(self.base06).bold();
-    StyleProfile {
-      base_style: base,
-      opaque: false,
-      chunk_styles: self.chunk_styles(base)
+/// The standard 16 ANSI colors, as displayed by xterm
+static ANSI_16: &[(u8, u8, u8)] = &[
+  (0, 0, 0), (205, 0, 0), (0, 205, 0), (205, 205, 0),
+  (0, 0, 238), (205, 0, 205), (0, 205, 205), (229, 229, 229),
+  (127, 127, 127), (255, 0, 0), (0, 255, 0), (255, 255, 0),
+  (92, 92, 255), (255, 0, 255), (0, 255, 255), (255, 255, 255)
+];
+
+/// Levels of each component in the 256-color palette's 6x6x6 color cube
+static CUBE_LEVELS: &[u8] = &[0, 95, 135, 175, 215, 255];
+
+fn distance(a: (u8, u8, u8), b: (u8, u8, u8)) -> u32 {
+  let d = |x: u8, y: u8| (i32::from(x) - i32::from(y)).pow(2) as u32;
+  d(a.0, b.0) + d(a.1, b.1) + d(a.2, b.2)
+}
+
+fn nearest_cube_level(v: u8) -> usize {
+  (0..CUBE_LEVELS.len())
+    .min_by_key(|i| (i32::from(CUBE_LEVELS[*i]) - i32::from(v)).abs())
+    .unwrap_or(0)
+}
+
+/// The RGB value of a color in the 256-color palette
+fn fixed_to_rgb(n: u8) -> (u8, u8, u8) {
+  match n {
+    0..=15 => ANSI_16[n as usize],
+    16..=231 => {
+      let i = n - 16;
+      let level = |v: u8| CUBE_LEVELS[v as usize];
+      (level(i / 36), level(i / 6 % 6), level(i % 6))
+    },
+    _ => {
+      let v = 8 + (n - 232) * 10;
+      (v, v, v)
     }
   }
 }
 
+fn rgb_to_256(rgb: (u8, u8, u8)) -> u8 {
+  let r = nearest_cube_level(rgb.0);
+  let g = nearest_cube_level(rgb.1);
+  let b = nearest_cube_level(rgb.2);
+  let cube = (16 + 36 * r + 6 * g + b) as u8;
+
+  // the grayscale ramp is finer than the cube's grays
+  let avg = (u32::from(rgb.0) + u32::from(rgb.1) + u32::from(rgb.2)) / 3;
+  let gray = 232 + ((avg.saturating_sub(8) / 10).min(23)) as u8;
+
+  if distance(fixed_to_rgb(gray), rgb) < distance(fixed_to_rgb(cube), rgb) {
+    gray
+  } else {
+    cube
+  }
+}
+
+fn rgb_to_16(rgb: (u8, u8, u8)) -> Color {
+  let i = (0..ANSI_16.len())
+    .min_by_key(|i| distance(ANSI_16[*i], rgb))
+    .unwrap_or(0);
+
+  match i {
+    0 => Color::Black,
+    1 => Color::Red,
+    2 => Color::Green,
+    3 => Color::Yellow,
+    4 => Color::Blue,
+    5 => Color::Purple,
+    6 => Color::Cyan,
+    7 => Color::White,
+    i => Color::Fixed(i as u8)
+  }
+}
+
+/// Approximates a color for terminals that can't display it
+fn downsample_color(color: Color, depth: ColorDepth) -> Color {
+  match (color, depth) {
+    (Color::RGB(r, g, b), ColorDepth::Ansi256) => Color::Fixed(rgb_to_256((r, g, b))),
+    (Color::RGB(r, g, b), ColorDepth::Ansi16) => rgb_to_16((r, g, b)),
+    (Color::Fixed(n), ColorDepth::Ansi16) if n > 15 => rgb_to_16(fixed_to_rgb(n)),
+    (color, _) => color
+  }
+}
+
+fn downsample_style(style: Style, depth: ColorDepth) -> Style {
+  Style {
+    foreground: style.foreground.map(|c| downsample_color(c, depth)),
+    background: style.background.map(|c| downsample_color(c, depth)),
+    ..style
+  }
+}
+
+#[derive(Clone)]
 pub struct StyleProfile {
   base_style: Style,
   opaque: bool,
 
+  /// if true, strings, numbers, etc in message text are highlighted
+  syntax: bool,
+
   chunk_styles: BTreeMap<ChunkKind, Style>
 }
 
@@ -163,6 +602,7 @@ impl StyleProfile {
     StyleProfile {
       base_style: Style::new(),
       opaque: false,
+      syntax: false,
       chunk_styles: btreemap! {}
     }
   }
@@ -173,11 +613,15 @@ impl StyleProfile {
     StyleProfile {
       base_style: base,
       opaque: false,
+      syntax: true,
       chunk_styles: btreemap!{
         ChunkKind::Date => base.fg(Color::White).dimmed(),
         ChunkKind::Time => base.fg(Color::White).dimmed(),
+        ChunkKind::Delta => base.fg(Color::White).dimmed(),
         ChunkKind::FieldKey => base.fg(Color::Cyan).dimmed(),
         ChunkKind::Context => base.fg(Color::Black).bold(),
+        ChunkKind::Repeat => base.fg(Color::Yellow).bold(),
+        ChunkKind::Collapsed => base.fg(Color::Black).bold(),
 
         ChunkKind::Level(LogLevel::Debug) => base.fg(Color::Cyan),
         ChunkKind::Level(LogLevel::Info) => base.fg(Color::Green),
@@ -196,6 +640,7 @@ impl StyleProfile {
     StyleProfile {
       base_style: base,
       opaque: true,
+      syntax: false,
       chunk_styles: btreemap!{
         ChunkKind::FieldKey => base.fg(Color::Blue).dimmed(),
 
@@ -215,11 +660,15 @@ impl StyleProfile {
     StyleProfile {
       base_style: base,
       opaque: false,
+      syntax: true,
       chunk_styles: btreemap!{
         ChunkKind::Date => base.fg(Color::White).dimmed(),
         ChunkKind::Time => base.fg(Color::White).dimmed(),
+        ChunkKind::Delta => base.fg(Color::White).dimmed(),
         ChunkKind::FieldKey => base.fg(Color::Cyan).dimmed(),
         ChunkKind::Context => base.fg(Color::Black),
+        ChunkKind::Repeat => base.fg(Color::Yellow).bold(),
+        ChunkKind::Collapsed => base.fg(Color::Black),
 
         ChunkKind::Level(LogLevel::Debug) => base.fg(Color::Cyan),
         ChunkKind::Level(LogLevel::Info) => base.fg(Color::Green),
@@ -246,6 +695,39 @@ impl StyleProfile {
       None => &self.base_style
     }
   }
+
+  /// Returns the style for a chunk kind, only if it isn't the base style
+  pub fn get_chunk_style(&self, kind: &ChunkKind) -> Option<&Style> {
+    self.chunk_styles.get(kind)
+  }
+
+  pub fn highlights_syntax(&self) -> bool {
+    self.syntax
+  }
+
+  fn downsample(&mut self, depth: ColorDepth) {
+    self.base_style = downsample_style(self.base_style, depth);
+    for style in self.chunk_styles.values_mut() {
+      *style = downsample_style(*style, depth);
+    }
+  }
+
+  /// Returns a copy of this profile with `style` added to every chunk
+  fn overlay(&self, style: Style) -> StyleProfile {
+    StyleProfile {
+      base_style: overlay_style(self.base_style, style),
+      opaque: self.opaque,
+      syntax: self.syntax,
+      chunk_styles: self.chunk_styles.iter()
+        .map(|(kind, s)| (*kind, overlay_style(*s, style)))
+        .collect()
+    }
+  }
+
+  /// Returns a dimmed copy of this profile, e.g. for context around matches
+  pub fn dimmed(&self) -> StyleProfile {
+    self.overlay(Style::new().dimmed())
+  }
 }
 
 #[derive(Copy, Clone)]
@@ -255,27 +737,107 @@ pub enum StyleProfileKind {
   //Highlighted
 }
 
-#[derive(Debug)]
+/// Built-in themes, in the order they're cycled through by the interactive
+/// renderer
+pub static BUILTIN_THEMES: &[&str] = &["default", "dark", "light", "solarized", "dracula"];
+
+#[derive(Debug, Clone)]
 pub struct StyleConfig {
+  /// The theme name given to `--style`, e.g. `dark`
+  pub name: String,
+
   pub normal: StyleProfile,
   pub selected: StyleProfile,
-  pub highlighted: StyleProfile
+  pub highlighted: StyleProfile,
+
+  /// The name of a user-defined theme to be loaded from the config file, see
+  /// `resolve()`
+  pub theme: Option<String>,
+
+  /// Conditional styles from the config file, applied to normal messages in
+  /// order
+  pub rules: Vec<StyleRule>
 }
 
 impl StyleConfig {
   pub fn default() -> Self {
     StyleConfig {
+      name: "default".to_string(),
       normal: StyleProfile::default_normal(),
       selected: StyleProfile::default_selected(),
-      highlighted: StyleProfile::default_highlighted()
+      highlighted: StyleProfile::default_highlighted(),
+      theme: None,
+      rules: Vec::new()
     }
   }
 
-  pub fn from_base16(base16: &Base16) -> Self {
+  /// Styles without colors, for when colors are disabled
+  pub fn monochrome() -> Self {
+    let base = Style::new();
+    let plain = |base: Style| StyleProfile {
+      base_style: base,
+      opaque: false,
+      syntax: false,
+      chunk_styles: btreemap!{}
+    };
+
     StyleConfig {
-      normal: base16.to_profile_normal(),
-      selected: base16.to_profile_selected(),
-      highlighted: base16.to_profile_highlighted()
+      name: "monochrome".to_string(),
+      normal: plain(base),
+      selected: StyleProfile { opaque: true, ..plain(base.reverse()) },
+      highlighted: plain(base.bold()),
+      theme: None,
+      rules: Vec::new()
+    }
+  }
+
+  pub fn from_base16(base16: &Base16) -> Self {
+    base16.to_palette().to_style_config()
+  }
+
+  /// Approximates colors the terminal can't display
+  pub fn downsample(&mut self, depth: ColorDepth) {
+    self.normal.downsample(depth);
+    self.selected.downsample(depth);
+    self.highlighted.downsample(depth);
+
+    for rule in &mut self.rules {
+      rule.style = downsample_style(rule.style, depth);
+    }
+  }
+
+  /// Disables highlighting of strings, numbers, etc in message text, i.e. for
+  /// `--no-highlight`
+  pub fn disable_syntax(&mut self) {
+    self.normal.syntax = false;
+    self.highlighted.syntax = false;
+  }
+
+  /// The profile for a normal (not selected or highlighted) message, with any
+  /// matching rules applied
+  pub fn profile_for(&self, message: &Message) -> Cow<'_, StyleProfile> {
+    let mut profile = Cow::Borrowed(&self.normal);
+    for rule in self.rules.iter().filter(|rule| rule.matches(message)) {
+      profile = Cow::Owned(profile.overlay(rule.style));
+    }
+
+    profile
+  }
+
+  /// Loads a user-defined theme named by `--style`, once the config file is
+  /// available
+  pub fn resolve(&mut self, themes: &HashMap<String, Palette>) -> Result<(), Box<dyn Error>> {
+    let name = match &self.theme {
+      Some(name) => name,
+      None => return Ok(())
+    };
+
+    match themes.get(name) {
+      Some(palette) => {
+        *self = StyleConfig { name: name.clone(), ..palette.resolve()?.to_style_config() };
+        Ok(())
+      },
+      None => bail!(format!("unsupported style profile: {}", name))
     }
   }
 
@@ -288,6 +850,25 @@ impl StyleConfig {
   }
 }
 
+/// Built-in themes and then user-defined ones, by name
+pub fn theme_names(themes: &HashMap<String, Palette>) -> Vec<String> {
+  let mut user: Vec<String> = themes.keys().cloned().collect();
+  user.sort();
+
+  BUILTIN_THEMES.iter().map(|name| name.to_string()).chain(user).collect()
+}
+
+/// The theme after `current` when cycling through `theme_names()`
+pub fn next_theme(current: &str, themes: &HashMap<String, Palette>) -> String {
+  let names = theme_names(themes);
+
+  // base16 files and the like aren't in the cycle, so start it over
+  match names.iter().position(|name| name == current) {
+    Some(i) => names[(i + 1) % names.len()].clone(),
+    None => names[0].clone()
+  }
+}
+
 fn load_base16(path: &str) -> Result<StyleConfig, Box<dyn Error>> {
   let expanded_path = shellexpand::full(path)?;
   let file = File::open(&expanded_path.to_string())?;
@@ -307,15 +888,29 @@ impl FromStr for StyleConfig {
 
     if let Some(groups) = RE.captures(s) {
       if let Some(path) = groups.get(1) {
-        load_base16(path.as_str())
+        Ok(StyleConfig { name: s.to_string(), ..load_base16(path.as_str())? })
       } else {
         bail!(format!("invalid b16: {}", s))
       }
     } else {
-      match s {
-        "default" => Ok(StyleConfig::default()),
-        _ => bail!(format!("unsupported style profile: {}", s))
+      if s == "default" {
+        return Ok(StyleConfig::default());
       }
+
+      if let Some(palette) = Palette::builtin(s) {
+        return Ok(StyleConfig { name: s.to_string(), ..palette.to_style_config() });
+      }
+
+      // may be a user-defined theme, but the config file isn't loaded yet
+      if s.chars().all(|c| c.is_alphanumeric() || c == '-' || c == '_') {
+        return Ok(StyleConfig {
+          name: s.to_string(),
+          theme: Some(s.to_string()),
+          ..StyleConfig::default()
+        });
+      }
+
+      bail!(format!("unsupported style profile: {}", s))
     }
   }
 }
@@ -331,3 +926,103 @@ pub fn styler_error(kind: StyleProfileKind) -> Styler {
     *c.get_profile(kind).get_style(&ChunkKind::Level(LogLevel::Error))
   }))
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use spectral::prelude::*;
+
+  #[test]
+  fn test_style_rules() {
+    use serde_json::json;
+
+    let rules: Vec<StyleRule> = serde_yaml::from_str(concat!(
+      "- field: status\n",
+      "  gte: 500\n",
+      "  style: red\n",
+      "- message: deadline exceeded\n",
+      "  style: bold\n"
+    )).unwrap();
+
+    let message = |text: &str, status| Message {
+      kind: crate::parser::MessageKind::Json,
+      timestamp: None,
+      level: Some(LogLevel::Info),
+      text: Some(text.to_string()),
+      metadata: hashmap!{ "status".to_string() => status },
+      reader_metadata: None,
+      mapped_fields: HashMap::new(),
+      raw: String::new()
+    };
+
+    assert_that!(rules[0].matches(&message("ok", json!(503)))).is_true();
+    assert_that!(rules[0].matches(&message("ok", json!("500")))).is_true();
+    assert_that!(rules[0].matches(&message("ok", json!(200)))).is_false();
+    assert_that!(rules[0].matches(&message("ok", json!("n/a")))).is_false();
+    assert_that!(rules[1].matches(&message("rpc: deadline exceeded", json!(200)))).is_true();
+
+    let config = StyleConfig { rules, ..StyleConfig::default() };
+    let profile = config.profile_for(&message("deadline exceeded", json!(504)));
+    let style = profile.get_style(&ChunkKind::Text);
+    assert_that!(style.foreground).is_equal_to(Some(Color::Red));
+    assert_that!(style.is_bold).is_true();
+
+    let profile = config.profile_for(&message("ok", json!(200)));
+    assert_that!(profile.get_style(&ChunkKind::Text).foreground).is_none();
+  }
+
+  #[test]
+  fn test_user_theme() {
+    let themes: HashMap<String, Palette> = serde_yaml::from_str(concat!(
+      "mine:\n",
+      "  extends: light\n",
+      "  error: '#d70000'\n",
+      "  field_key: blue\n"
+    )).unwrap();
+
+    let mut style: StyleConfig = "mine".parse().unwrap();
+    style.resolve(&themes).unwrap();
+
+    let light = Palette::builtin("light").unwrap();
+    assert_that!(*style.normal.get_style(&ChunkKind::Level(LogLevel::Error)))
+      .is_equal_to(Style::new().fg(Color::RGB(0xd7, 0, 0)));
+    assert_that!(*style.normal.get_style(&ChunkKind::FieldKey))
+      .is_equal_to(Style::new().fg(Color::Blue));
+    assert_that!(*style.normal.get_style(&ChunkKind::Date))
+      .is_equal_to(Style::new().fg(light.timestamp.unwrap()));
+
+    let mut unknown: StyleConfig = "nope".parse().unwrap();
+    assert_that!(unknown.resolve(&themes)).is_err();
+    assert_that!("dracula".parse::<StyleConfig>()).is_ok();
+  }
+
+  #[test]
+  fn test_next_theme() {
+    let themes: HashMap<String, Palette> = serde_yaml::from_str(
+      "mine:\n  extends: light\n"
+    ).unwrap();
+
+    assert_that!(next_theme("default", &themes)).is_equal_to("dark".to_string());
+    assert_that!(next_theme("dracula", &themes)).is_equal_to("mine".to_string());
+    assert_that!(next_theme("mine", &themes)).is_equal_to("default".to_string());
+    assert_that!(next_theme("dracula", &HashMap::new())).is_equal_to("default".to_string());
+    assert_that!(next_theme("b16:theme.yaml", &themes)).is_equal_to("default".to_string());
+
+    let style: StyleConfig = "solarized".parse().unwrap();
+    assert_that!(style.name).is_equal_to("solarized".to_string());
+  }
+
+  #[test]
+  fn test_downsample() {
+    let orange = Color::RGB(0xff, 0x87, 0x00);
+    assert_that!(downsample_color(orange, ColorDepth::Truecolor)).is_equal_to(orange);
+    assert_that!(downsample_color(orange, ColorDepth::Ansi256)).is_equal_to(Color::Fixed(208));
+    assert_that!(downsample_color(orange, ColorDepth::Ansi16)).is_equal_to(Color::Yellow);
+
+    let gray = Color::RGB(0x80, 0x80, 0x80);
+    assert_that!(downsample_color(gray, ColorDepth::Ansi256)).is_equal_to(Color::Fixed(244));
+    assert_that!(downsample_color(Color::Fixed(196), ColorDepth::Ansi16)).is_equal_to(Color::Fixed(9));
+    assert_that!(downsample_color(Color::Red, ColorDepth::Ansi16)).is_equal_to(Color::Red);
+  }
+}
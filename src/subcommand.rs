@@ -0,0 +1,281 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+use std::path::PathBuf;
+
+use simple_error::SimpleResult;
+use structopt::StructOpt;
+use structopt::clap::ErrorKind;
+
+use crate::config::Config;
+use crate::reader::listen::DEFAULT_ADDRESS;
+
+/// A subcommand: a reader, and how its positional arguments are used; every
+/// other flag is shared, e.g. `woodchipper k8s -n prod -r plain app=api`
+pub struct Subcommand {
+  pub name: &'static str,
+  usage: &'static str,
+  about: &'static str,
+
+  /// Flags the subcommand stands for, as if given first
+  flags: &'static [&'static str],
+
+  /// Checks the subcommand got what it needs after parsing, moving any
+  /// positional arguments into place
+  finish: fn(&Subcommand, &mut Config) -> SimpleResult<()>
+}
+
+impl Subcommand {
+  pub fn finish(&self, config: &mut Config) -> SimpleResult<()> {
+    (self.finish)(self, config)
+  }
+
+  fn usage_error(&self) -> SimpleResult<()> {
+    bail!("usage: woodchipper {} [FLAGS] {}", self.name, self.usage)
+  }
+}
+
+fn finish_tail(subcommand: &Subcommand, config: &mut Config) -> SimpleResult<()> {
+  if config.app.is_empty() {
+    return subcommand.usage_error();
+  }
+
+  Ok(())
+}
+
+fn finish_k8s(_subcommand: &Subcommand, _config: &mut Config) -> SimpleResult<()> {
+  Ok(())
+}
+
+fn finish_exec(subcommand: &Subcommand, config: &mut Config) -> SimpleResult<()> {
+  if config.app.is_empty() && config.exec.is_empty() {
+    return subcommand.usage_error();
+  }
+
+  Ok(())
+}
+
+fn finish_listen(subcommand: &Subcommand, config: &mut Config) -> SimpleResult<()> {
+  match config.app.len() {
+    0 => Ok(()),
+    1 if config.listen.is_none() => {
+      config.listen = config.app.pop();
+      Ok(())
+    },
+    _ => subcommand.usage_error()
+  }
+}
+
+fn finish_open(subcommand: &Subcommand, config: &mut Config) -> SimpleResult<()> {
+  match config.app.len() {
+    0 if config.open.is_some() => Ok(()),
+    1 if config.open.is_none() => {
+      config.open = config.app.pop().map(PathBuf::from);
+      Ok(())
+    },
+    _ => subcommand.usage_error()
+  }
+}
+
+pub static SUBCOMMANDS: &[Subcommand] = &[
+  Subcommand {
+    name: "tail",
+    usage: "FILE...",
+    about: "follows files, each shown as its own source, across log rotation",
+    flags: &["--reader=file"],
+    finish: finish_tail
+  },
+  Subcommand {
+    name: "k8s",
+    usage: "[SELECTOR]...",
+    about: "follows Kubernetes pods matching a selector, e.g. `k8s -n prod app=api`",
+    flags: &["--reader=kubernetes"],
+    finish: finish_k8s
+  },
+  Subcommand {
+    name: "exec",
+    usage: "-- COMMAND [ARGS]...",
+    about: "runs a command and reads its output; see also --exec",
+    flags: &["--reader=exec"],
+    finish: finish_exec
+  },
+  Subcommand {
+    name: "listen",
+    usage: "[ADDRESS]",
+    about: "reads lines sent over TCP or UDP, e.g. by syslog",
+    flags: &["--reader=listen"],
+    finish: finish_listen
+  },
+  Subcommand {
+    name: "open",
+    usage: "SESSION",
+    about: "reopens a session saved with `:snapshot`",
+    flags: &["--reader=session"],
+    finish: finish_open
+  }
+];
+
+lazy_static! {
+  static ref HELP: String = {
+    let mut lines = vec!["SUBCOMMANDS:".to_string()];
+    let commands = SUBCOMMANDS.iter()
+      .map(|s| (format!("{} {}", s.name, s.usage), s.about.to_string()))
+      .chain(std::iter::once((
+        "bench FILE".to_string(),
+        "measures parser throughput; see `woodchipper bench --help`".to_string()
+      )));
+
+    for (usage, about) in commands {
+      lines.push(format!("    {:<28}{}", usage, about));
+    }
+
+    lines.push(String::new());
+    lines.push(format!(
+      "Subcommands share every flag, and are shorthand for `--reader`; `listen` binds {} \
+       unless given an address.",
+      DEFAULT_ADDRESS
+    ));
+
+    lines.join("\n")
+  };
+}
+
+/// The list of subcommands shown at the end of `--help`
+pub fn help() -> &'static str {
+  &HELP
+}
+
+/// Determines if a flag takes the next argument as its value, or `None` if it
+/// isn't a flag
+fn takes_value(flag: &str) -> Option<bool> {
+  static PROBE: &str = "woodchipper-probe";
+
+  // options with defaults parse without a value, so see where a value goes
+  match Config::clap().get_matches_from_safe(["woodchipper", flag, PROBE]) {
+    Ok(matches) => Some(!matches.values_of("app").map_or(false, |mut app| app.any(|a| a == PROBE))),
+    Err(e) => match e.kind {
+      ErrorKind::InvalidValue | ErrorKind::ValueValidation => Some(true),
+      _ => None
+    }
+  }
+}
+
+/// Finds the first positional argument, skipping flags and their values, if
+/// there is one before any `--`
+pub fn first_positional(args: &[String]) -> Option<usize> {
+  let mut i = 1;
+  while let Some(arg) = args.get(i) {
+    if arg == "--" {
+      return None;
+    } else if !arg.starts_with('-') || arg == "-" {
+      return Some(i);
+    } else if arg.starts_with("--") && arg.contains('=') {
+      i += 1;
+    } else {
+      i += if takes_value(arg)? { 2 } else { 1 };
+    }
+  }
+
+  None
+}
+
+/// Replaces a subcommand, the first positional argument, with the flags it
+/// stands for, e.g. `woodchipper -r plain tail app.log` as
+/// `woodchipper -r plain --reader=file app.log`, returning it to finish once
+/// parsed
+pub fn rewrite_args(mut args: Vec<String>) -> (Option<&'static Subcommand>, Vec<String>) {
+  let found = first_positional(&args)
+    .and_then(|i| Some((i, SUBCOMMANDS.iter().find(|s| s.name == args[i])?)));
+
+  match found {
+    Some((i, subcommand)) => {
+      args.splice(i..=i, subcommand.flags.iter().map(|flag| flag.to_string()));
+      (Some(subcommand), args)
+    },
+    None => (None, args)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use spectral::prelude::*;
+
+  fn parse(items: &[&str]) -> SimpleResult<Config> {
+    let args = items.iter().map(|s| s.to_string()).collect();
+    let (subcommand, args) = rewrite_args(args);
+    let mut config = Config::from_iter_safe(args).unwrap();
+    if let Some(subcommand) = subcommand {
+      subcommand.finish(&mut config)?;
+    }
+
+    Ok(config)
+  }
+
+  #[test]
+  fn test_tail() {
+    let config = parse(&["wd", "tail", "-r", "plain", "a.log", "b.log"]).unwrap();
+    assert_that!(format!("{:?}", config.reader)).is_equal_to("File".to_string());
+    assert_that!(config.app).is_equal_to(vec!["a.log".to_string(), "b.log".to_string()]);
+    assert_that!(parse(&["wd", "tail"]).is_err()).is_true();
+  }
+
+  #[test]
+  fn test_k8s() {
+    let config = parse(&["wd", "k8s", "-n", "prod", "app=api"]).unwrap();
+    assert_that!(format!("{:?}", config.reader)).is_equal_to("Kubernetes".to_string());
+    assert_that!(config.kubernetes.namespace).is_equal_to(Some("prod".to_string()));
+  }
+
+  #[test]
+  fn test_exec() {
+    let config = parse(&["wd", "exec", "--", "make", "-j4"]).unwrap();
+    assert_that!(config.app).is_equal_to(vec!["make".to_string(), "-j4".to_string()]);
+    assert_that!(parse(&["wd", "exec"]).is_err()).is_true();
+  }
+
+  #[test]
+  fn test_listen() {
+    let config = parse(&["wd", "listen", "0.0.0.0:514"]).unwrap();
+    assert_that!(config.listen).is_equal_to(Some("0.0.0.0:514".to_string()));
+    assert_that!(parse(&["wd", "listen"]).unwrap().listen).is_none();
+  }
+
+  #[test]
+  fn test_open() {
+    let config = parse(&["wd", "open", "-r", "plain", "a.wcs"]).unwrap();
+    assert_that!(config.open).is_equal_to(Some(PathBuf::from("a.wcs")));
+    assert_that!(config.app).is_empty();
+    assert_that!(parse(&["wd", "open"]).is_err()).is_true();
+  }
+
+  #[test]
+  fn test_subcommand_position() {
+    // flags may come first
+    let config = parse(&["wd", "-r", "json", "tail", "--no-follow", "app.log"]).unwrap();
+    assert_that!(format!("{:?}", config.reader)).is_equal_to("File".to_string());
+    assert_that!(config.app).is_equal_to(vec!["app.log".to_string()]);
+
+    let config = parse(&["wd", "--no-follow", "-rjson", "--style=dark", "exec", "--", "echo"]);
+    let config = config.unwrap();
+    assert_that!(format!("{:?}", config.reader)).is_equal_to("Exec".to_string());
+    assert_that!(config.app).is_equal_to(vec!["echo".to_string()]);
+
+    // but subcommand names are only special as the first positional argument
+    let config = parse(&["wd", "-i", "exec", "--", "open"]).unwrap();
+    assert_that!(config.app).is_equal_to(vec!["open".to_string()]);
+
+    let config = parse(&["wd", "-i", "file", "a.log", "tail"]).unwrap();
+    assert_that!(config.app).is_equal_to(vec!["a.log".to_string(), "tail".to_string()]);
+  }
+
+  #[test]
+  fn test_first_positional() {
+    let args = |items: &[&str]| items.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+    assert_that!(first_positional(&args(&["wd", "-r", "plain", "--ordered", "a"])))
+      .is_equal_to(Some(4));
+    assert_that!(first_positional(&args(&["wd", "--renderer=plain", "-"]))).is_equal_to(Some(2));
+    assert_that!(first_positional(&args(&["wd", "--", "a"]))).is_none();
+    assert_that!(first_positional(&args(&["wd", "--bogus", "a"]))).is_none();
+  }
+}
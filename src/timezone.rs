@@ -0,0 +1,639 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+//! Display timezones, including named zones from the system's tz database, and
+//! relative timestamps
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+
+use chrono::prelude::*;
+use regex::Regex;
+use simple_error::{SimpleError, SimpleResult};
+
+use crate::config::Zone;
+
+/// Where the tz database is found, unless `TZDIR` is set
+static DEFAULT_TZDIR: &str = "/usr/share/zoneinfo";
+
+/// A rule for the start or end of daylight saving time in a POSIX TZ string
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum DateRule {
+  /// `Jn`: day 1-365, never counting February 29
+  Julian(u32),
+
+  /// `n`: day 0-365, counting February 29
+  Ordinal(u32),
+
+  /// `Mm.w.d`: day `d` (0 = Sunday) of week `w` (5 = last) of month `m`
+  MonthWeekDay(u32, u32, u32)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Transition {
+  rule: DateRule,
+
+  /// seconds after local midnight
+  time: i64
+}
+
+/// A rule from a POSIX TZ string, e.g. `CET-1CEST,M3.5.0,M10.5.0/3`, used for
+/// instants after the last transition in a TZif file
+#[derive(Debug, Clone, PartialEq)]
+struct PosixRule {
+  /// standard offset, in seconds east of UTC
+  std_offset: i32,
+
+  /// daylight saving offset, and when it starts and ends
+  dst: Option<(i32, Transition, Transition)>
+}
+
+/// Parses a zone name, i.e. alphabetic or `<...>`-quoted
+fn parse_name(s: &str) -> Option<&str> {
+  if let Some(rest) = s.strip_prefix('<') {
+    return rest.find('>').map(|i| &rest[i + 1..]);
+  }
+
+  let end = s.find(|c: char| !c.is_ascii_alphabetic()).unwrap_or(s.len());
+  if end < 3 {
+    None
+  } else {
+    Some(&s[end..])
+  }
+}
+
+/// Parses `[+-]hh[:mm[:ss]]` as seconds, returning the remaining input
+fn parse_hms(s: &str) -> Option<(i64, &str)> {
+  let (sign, s) = match s.chars().next()? {
+    '-' => (-1, &s[1..]),
+    '+' => (1, &s[1..]),
+    _ => (1, s)
+  };
+
+  let end = s.find(|c: char| !c.is_ascii_digit() && c != ':').unwrap_or(s.len());
+  let mut total = 0;
+  for (i, part) in s[..end].split(':').enumerate() {
+    if i > 2 {
+      return None;
+    }
+
+    let value: i64 = part.parse().ok()?;
+    total += value * [3600, 60, 1][i];
+  }
+
+  Some((sign * total, &s[end..]))
+}
+
+fn parse_transition(s: &str) -> Option<Transition> {
+  let mut parts = s.splitn(2, '/');
+  let date = parts.next()?;
+  let time = match parts.next() {
+    Some(time) => parse_hms(time)?.0,
+    None => 2 * 3600
+  };
+
+  let rule = if let Some(mwd) = date.strip_prefix('M') {
+    let fields: Vec<u32> = mwd.split('.').map(|f| f.parse().ok()).collect::<Option<_>>()?;
+    match fields.as_slice() {
+      [m @ 1..=12, w @ 1..=5, d @ 0..=6] => DateRule::MonthWeekDay(*m, *w, *d),
+      _ => return None
+    }
+  } else if let Some(n) = date.strip_prefix('J') {
+    DateRule::Julian(n.parse().ok().filter(|n| (1..=365).contains(n))?)
+  } else {
+    DateRule::Ordinal(date.parse().ok().filter(|n| *n <= 365)?)
+  };
+
+  Some(Transition { rule, time })
+}
+
+impl FromStr for PosixRule {
+  type Err = SimpleError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let invalid = || SimpleError::new(format!("invalid TZ rule: {}", s));
+
+    let rest = parse_name(s).ok_or_else(invalid)?;
+    let (std_west, rest) = parse_hms(rest).ok_or_else(invalid)?;
+    let std_offset = -std_west as i32;
+
+    if rest.is_empty() {
+      return Ok(PosixRule { std_offset, dst: None });
+    }
+
+    let rest = parse_name(rest).ok_or_else(invalid)?;
+    let (dst_offset, rest) = if rest.starts_with(',') {
+      (std_offset + 3600, rest)
+    } else {
+      let (dst_west, rest) = parse_hms(rest).ok_or_else(invalid)?;
+      (-dst_west as i32, rest)
+    };
+
+    let mut rules = rest.strip_prefix(',').ok_or_else(invalid)?.split(',');
+    let start = rules.next().and_then(parse_transition).ok_or_else(invalid)?;
+    let end = rules.next().and_then(parse_transition).ok_or_else(invalid)?;
+
+    Ok(PosixRule { std_offset, dst: Some((dst_offset, start, end)) })
+  }
+}
+
+fn is_leap(year: i32) -> bool {
+  (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+/// The date a rule falls on in the given year
+fn rule_date(rule: DateRule, year: i32) -> Option<NaiveDate> {
+  match rule {
+    DateRule::Julian(n) => {
+      let skip_leap_day = is_leap(year) && n >= 60;
+      NaiveDate::from_yo_opt(year, n + skip_leap_day as u32)
+    },
+    DateRule::Ordinal(n) => NaiveDate::from_yo_opt(year, n + 1),
+    DateRule::MonthWeekDay(month, week, weekday) => {
+      let first = NaiveDate::from_ymd_opt(year, month, 1)?;
+      let first_weekday = first.weekday().num_days_from_sunday();
+      let mut day = 1 + (weekday + 7 - first_weekday) % 7 + (week - 1) * 7;
+
+      // week 5 means the last such day, which may be in the 4th week
+      while NaiveDate::from_ymd_opt(year, month, day).is_none() {
+        day -= 7;
+      }
+
+      NaiveDate::from_ymd_opt(year, month, day)
+    }
+  }
+}
+
+impl PosixRule {
+  /// The UTC instant a transition occurs in some year, given the offset in
+  /// effect before it
+  fn transition_at(transition: Transition, year: i32, offset: i32) -> Option<i64> {
+    let date = rule_date(transition.rule, year)?;
+    let midnight = date.and_hms_opt(0, 0, 0)?.timestamp();
+    Some(midnight + transition.time - i64::from(offset))
+  }
+
+  fn offset_at(&self, timestamp: i64) -> i32 {
+    let (dst_offset, start, end) = match self.dst {
+      Some(dst) => dst,
+      None => return self.std_offset
+    };
+
+    let year = match Utc.timestamp_opt(timestamp + i64::from(self.std_offset), 0).single() {
+      Some(local) => local.year(),
+      None => return self.std_offset
+    };
+
+    let start = PosixRule::transition_at(start, year, self.std_offset);
+    let end = PosixRule::transition_at(end, year, dst_offset);
+    let in_dst = match (start, end) {
+      (Some(start), Some(end)) if start <= end => timestamp >= start && timestamp < end,
+
+      // southern hemisphere, daylight saving time spans the new year
+      (Some(start), Some(end)) => timestamp >= start || timestamp < end,
+      _ => false
+    };
+
+    if in_dst {
+      dst_offset
+    } else {
+      self.std_offset
+    }
+  }
+}
+
+/// A zone loaded from a TZif file, e.g. `/usr/share/zoneinfo/Europe/Berlin`
+#[derive(Debug, Clone, PartialEq)]
+pub struct TzFile {
+  pub name: String,
+
+  /// UTC instants at which the offset changes, in order
+  transitions: Vec<i64>,
+
+  /// the index into `offsets` in effect from each transition on
+  transition_types: Vec<usize>,
+
+  /// offsets, in seconds east of UTC
+  offsets: Vec<i32>,
+
+  /// the rule for instants after the last transition
+  rule: Option<PosixRule>
+}
+
+struct Cursor<'a> {
+  data: &'a [u8]
+}
+
+impl<'a> Cursor<'a> {
+  fn take(&mut self, len: usize) -> SimpleResult<&'a [u8]> {
+    if self.data.len() < len {
+      bail!("truncated TZif file");
+    }
+
+    let (taken, rest) = self.data.split_at(len);
+    self.data = rest;
+    Ok(taken)
+  }
+
+  fn int(&mut self, len: usize) -> SimpleResult<i64> {
+    let bytes = self.take(len)?;
+    let value = bytes.iter().fold(0u64, |acc, b| (acc << 8) | u64::from(*b));
+    let shift = 64 - len * 8;
+
+    // sign extend from the encoded width
+    Ok(((value << shift) as i64) >> shift)
+  }
+}
+
+/// Reads a TZif header, returning the version and the counts of each type of
+/// record that follow
+fn read_header(cursor: &mut Cursor, name: &str) -> SimpleResult<(u8, [usize; 6])> {
+  if cursor.take(4)? != b"TZif" {
+    bail!("not a TZif file: {}", name);
+  }
+
+  let version = cursor.take(16)?[0];
+  let mut counts = [0usize; 6];
+  for count in counts.iter_mut() {
+    *count = cursor.int(4)? as usize;
+  }
+
+  Ok((version, counts))
+}
+
+impl TzFile {
+  /// Parses a TZif file (RFC 8536)
+  pub fn parse(name: &str, data: &[u8]) -> SimpleResult<TzFile> {
+    let mut cursor = Cursor { data };
+    let (version, counts) = read_header(&mut cursor, name)?;
+
+    // version 2+ files repeat the data with 64-bit times, so skip the first
+    let (time_size, counts) = if version >= b'2' {
+      let [isut, isstd, leap, time, kind, chars] = counts;
+      cursor.take(time * 5 + kind * 6 + chars + leap * 8 + isstd + isut)?;
+      (8, read_header(&mut cursor, name)?.1)
+    } else {
+      (4, counts)
+    };
+
+    let [isut, isstd, leap, time, kind, chars] = counts;
+
+    let transitions = (0..time)
+      .map(|_| cursor.int(time_size))
+      .collect::<SimpleResult<Vec<_>>>()?;
+
+    let transition_types = cursor.take(time)?.iter().map(|i| *i as usize).collect::<Vec<_>>();
+
+    let mut offsets = Vec::with_capacity(kind);
+    for _ in 0..kind {
+      offsets.push(cursor.int(4)? as i32);
+      cursor.take(2)?;
+    }
+
+    if offsets.is_empty() || transition_types.iter().any(|i| *i >= offsets.len()) {
+      bail!("invalid TZif file: {}", name);
+    }
+
+    cursor.take(chars + leap * (time_size + 4) + isstd + isut)?;
+
+    let rule = if time_size == 8 {
+      String::from_utf8_lossy(cursor.data)
+        .trim()
+        .parse::<PosixRule>()
+        .ok()
+    } else {
+      None
+    };
+
+    Ok(TzFile { name: name.to_string(), transitions, transition_types, offsets, rule })
+  }
+
+  /// Loads a zone by name from the tz database, e.g. `Europe/Berlin`
+  pub fn load(name: &str) -> SimpleResult<TzFile> {
+    if name.split('/').any(|part| part.is_empty() || part == "..") {
+      bail!("invalid timezone: {}", name);
+    }
+
+    let dir = env::var_os("TZDIR")
+      .map(PathBuf::from)
+      .unwrap_or_else(|| Path::new(DEFAULT_TZDIR).to_path_buf());
+
+    let data = fs::read(dir.join(name)).map_err(|e| SimpleError::new(
+      format!("unknown timezone {}: {}", name, e)
+    ))?;
+
+    TzFile::parse(name, &data)
+  }
+
+  fn offset_at(&self, timestamp: i64) -> i32 {
+    let i = match self.transitions.binary_search(&timestamp) {
+      Ok(i) => i,
+      Err(0) => return match (&self.rule, self.transitions.is_empty()) {
+        (Some(rule), true) => rule.offset_at(timestamp),
+        _ => self.offsets[0]
+      },
+      Err(i) => i - 1
+    };
+
+    if i == self.transitions.len() - 1 {
+      if let Some(rule) = &self.rule {
+        return rule.offset_at(timestamp);
+      }
+    }
+
+    self.offsets[self.transition_types[i]]
+  }
+}
+
+/// The timezone in which timestamps are displayed
+#[derive(Debug, Clone)]
+pub enum DisplayZone {
+  Zone(Zone),
+  Named(Arc<TzFile>)
+}
+
+impl DisplayZone {
+  pub fn convert(&self, timestamp: DateTime<Utc>) -> DateTime<FixedOffset> {
+    let offset = match self {
+      DisplayZone::Zone(Zone::Local) => *timestamp.with_timezone(&Local).offset(),
+      DisplayZone::Zone(Zone::Utc) => FixedOffset::east_opt(0).unwrap(),
+      DisplayZone::Zone(Zone::Fixed(offset)) => *offset,
+      DisplayZone::Named(tz) => FixedOffset::east_opt(tz.offset_at(timestamp.timestamp()))
+        .unwrap_or_else(|| FixedOffset::east_opt(0).unwrap())
+    };
+
+    timestamp.with_timezone(&offset)
+  }
+}
+
+impl Default for DisplayZone {
+  fn default() -> Self {
+    DisplayZone::Zone(Zone::Local)
+  }
+}
+
+impl FromStr for DisplayZone {
+  type Err = SimpleError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s.parse::<Zone>() {
+      Ok(zone) => Ok(DisplayZone::Zone(zone)),
+      Err(_) => TzFile::load(s).map(|tz| DisplayZone::Named(Arc::new(tz)))
+    }
+  }
+}
+
+/// A reference point for displaying timestamps as offsets rather than as
+/// dates and times
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RelativeTime {
+  /// the time of display, e.g. `-00:02:13` for two minutes ago
+  Now,
+
+  /// the first timestamp seen in this session, e.g. `+00:02:13`
+  First
+}
+
+impl RelativeTime {
+  /// Cycles through absolute and relative display: absolute, now, first
+  pub fn next(mode: Option<RelativeTime>) -> Option<RelativeTime> {
+    match mode {
+      None => Some(RelativeTime::Now),
+      Some(RelativeTime::Now) => Some(RelativeTime::First),
+      Some(RelativeTime::First) => None
+    }
+  }
+}
+
+impl FromStr for RelativeTime {
+  type Err = SimpleError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    match s.to_lowercase().as_str() {
+      "now" => Ok(RelativeTime::Now),
+      "first" => Ok(RelativeTime::First),
+      _ => bail!("invalid relative time, expected now or first: {}", s)
+    }
+  }
+}
+
+lazy_static! {
+  static ref DISPLAY_ZONE: RwLock<DisplayZone> = RwLock::new(DisplayZone::default());
+  static ref RELATIVE_TIME: RwLock<Option<RelativeTime>> = RwLock::new(None);
+  static ref FIRST_TIMESTAMP: RwLock<Option<DateTime<Utc>>> = RwLock::new(None);
+}
+
+/// Sets the timezone used by all renderers, i.e. from `--timezone`
+///
+/// This is global as timestamps are formatted during classification, which
+/// doesn't otherwise need the config.
+pub fn set_display_zone(zone: DisplayZone) {
+  if let Ok(mut display_zone) = DISPLAY_ZONE.write() {
+    *display_zone = zone;
+  }
+}
+
+/// Converts a timestamp into the display timezone
+pub fn to_display(timestamp: DateTime<Utc>) -> DateTime<FixedOffset> {
+  match DISPLAY_ZONE.read() {
+    Ok(zone) => zone.convert(timestamp),
+    Err(_) => timestamp.with_timezone(&FixedOffset::east_opt(0).unwrap())
+  }
+}
+
+/// Sets whether timestamps are displayed relative to some reference point,
+/// i.e. from `--relative-time` or toggled in the interactive renderer
+pub fn set_relative_time(mode: Option<RelativeTime>) {
+  if let Ok(mut relative_time) = RELATIVE_TIME.write() {
+    *relative_time = mode;
+  }
+}
+
+pub fn relative_time() -> Option<RelativeTime> {
+  RELATIVE_TIME.read().map(|mode| *mode).unwrap_or(None)
+}
+
+/// Records a message timestamp, keeping the first as the reference for
+/// `RelativeTime::First`
+pub fn note_timestamp(timestamp: DateTime<Utc>) {
+  if FIRST_TIMESTAMP.read().map(|first| first.is_some()).unwrap_or(true) {
+    return;
+  }
+
+  if let Ok(mut first) = FIRST_TIMESTAMP.write() {
+    first.get_or_insert(timestamp);
+  }
+}
+
+/// Formats the offset of `timestamp` from `reference` as `[+-]hh:mm:ss`, with
+/// any whole days prefixed, e.g. `-2d 03:00:00`
+pub fn format_offset(timestamp: DateTime<Utc>, reference: DateTime<Utc>) -> String {
+  let offset = timestamp.signed_duration_since(reference).num_seconds();
+  let sign = if offset < 0 { '-' } else { '+' };
+  let offset = offset.abs();
+
+  let time = format!(
+    "{:02}:{:02}:{:02}", offset % 86_400 / 3600, offset % 3600 / 60, offset % 60
+  );
+
+  match offset / 86_400 {
+    0 => format!("{}{}", sign, time),
+    days => format!("{}{}d {}", sign, days, time)
+  }
+}
+
+/// Formats a timestamp relative to the current reference point, if relative
+/// display is enabled
+pub fn to_relative(timestamp: DateTime<Utc>) -> Option<String> {
+  let reference = match relative_time()? {
+    RelativeTime::Now => Utc::now(),
+    RelativeTime::First => FIRST_TIMESTAMP.read().ok().and_then(|first| *first)?
+  };
+
+  Some(format_offset(timestamp, reference))
+}
+
+/// Parses a time to jump to, e.g. `14:32`, `2023-05-01T14:32:10Z` or `-10m`
+///
+/// Offsets (`[+-]` followed by any of `<n>d`, `h`, `m`, `s` or `ms`, e.g.
+/// `-1h30m`) are from `reference`. Times without an offset are in the
+/// reference's timezone, and times without a date are on its date.
+pub fn parse_target(input: &str, reference: DateTime<FixedOffset>) -> SimpleResult<DateTime<Utc>> {
+  lazy_static! {
+    static ref OFFSET_RE: Regex = Regex::new(r"^([+-])\s*((?:\d+(?:ms|d|h|m|s))+)$").unwrap();
+    static ref PART_RE: Regex = Regex::new(r"(\d+)(ms|d|h|m|s)").unwrap();
+  }
+
+  let input = input.trim();
+
+  if let Some(caps) = OFFSET_RE.captures(input) {
+    let mut millis: i64 = 0;
+    for part in PART_RE.captures_iter(&caps[2]) {
+      let n: i64 = part[1].parse().map_err(SimpleError::from)?;
+      let unit = match &part[2] {
+        "d" => 86_400_000,
+        "h" => 3_600_000,
+        "m" => 60_000,
+        "s" => 1000,
+        _ => 1
+      };
+
+      millis = n.checked_mul(unit)
+        .and_then(|m| millis.checked_add(m))
+        .ok_or_else(|| SimpleError::new("offset is too large"))?;
+    }
+
+    let offset = chrono::Duration::milliseconds(if &caps[1] == "-" { -millis } else { millis });
+    return reference.checked_add_signed(offset)
+      .map(|t| t.with_timezone(&Utc))
+      .ok_or_else(|| SimpleError::new("offset is too large"));
+  }
+
+  if let Ok(timestamp) = DateTime::parse_from_rfc3339(input) {
+    return Ok(timestamp.with_timezone(&Utc));
+  }
+
+  let datetime = ["%Y-%m-%dT%H:%M:%S%.f", "%Y-%m-%d %H:%M:%S%.f", "%Y-%m-%dT%H:%M", "%Y-%m-%d %H:%M"]
+    .iter()
+    .find_map(|format| NaiveDateTime::parse_from_str(input, format).ok())
+    .or_else(|| ["%H:%M:%S%.f", "%H:%M"].iter()
+      .find_map(|format| NaiveTime::parse_from_str(input, format).ok())
+      .map(|time| reference.date_naive().and_time(time)));
+
+  match datetime {
+    Some(naive) => reference.offset().from_local_datetime(&naive)
+      .single()
+      .map(|t| t.with_timezone(&Utc))
+      .ok_or_else(|| SimpleError::new("invalid time")),
+    None => bail!("invalid time: {}, expected e.g. 14:32, 2023-05-01T14:32:10Z or -10m", input)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use spectral::prelude::*;
+
+  fn offset(rule: &PosixRule, s: &str) -> i32 {
+    rule.offset_at(s.parse::<DateTime<Utc>>().unwrap().timestamp())
+  }
+
+  #[test]
+  fn test_posix_rule() {
+    let berlin: PosixRule = "CET-1CEST,M3.5.0,M10.5.0/3".parse().unwrap();
+    assert_that!(offset(&berlin, "2030-01-15T12:00:00Z")).is_equal_to(3600);
+    assert_that!(offset(&berlin, "2030-03-31T00:59:59Z")).is_equal_to(3600);
+    assert_that!(offset(&berlin, "2030-03-31T01:00:00Z")).is_equal_to(7200);
+    assert_that!(offset(&berlin, "2030-10-27T00:59:59Z")).is_equal_to(7200);
+    assert_that!(offset(&berlin, "2030-10-27T01:00:00Z")).is_equal_to(3600);
+
+    let sydney: PosixRule = "AEST-10AEDT,M10.1.0,M4.1.0/3".parse().unwrap();
+    assert_that!(offset(&sydney, "2030-01-15T00:00:00Z")).is_equal_to(11 * 3600);
+    assert_that!(offset(&sydney, "2030-07-15T00:00:00Z")).is_equal_to(10 * 3600);
+
+    let tehran: PosixRule = "<+0330>-3:30".parse().unwrap();
+    assert_that!(offset(&tehran, "2030-07-15T00:00:00Z")).is_equal_to(12600);
+
+    assert_that!("nonsense".parse::<PosixRule>()).is_err();
+  }
+
+  #[test]
+  fn test_tzif() {
+    // a version 2 file with a single transition, to +01:00 at 1970-01-02
+    let mut data = Vec::new();
+    let header = |data: &mut Vec<u8>, time: u32, kind: u32, chars: u32| {
+      data.extend(b"TZif2");
+      data.extend(&[0u8; 15]);
+      for count in &[0, 0, 0, time, kind, chars] {
+        data.extend(&count.to_be_bytes());
+      }
+    };
+
+    header(&mut data, 0, 1, 4);
+    data.extend(&[0, 0, 0, 0, 0, 0]);
+    data.extend(b"UTC\0");
+
+    header(&mut data, 1, 2, 8);
+    data.extend(&86400i64.to_be_bytes());
+    data.push(1);
+    data.extend(&[0, 0, 0, 0, 0, 0]);
+    data.extend(&[0, 0, 0x0e, 0x10, 0, 4]);
+    data.extend(b"UTC\0CET\0");
+    data.extend(b"\nCET-1\n");
+
+    let tz = TzFile::parse("Test/Zone", &data).unwrap();
+    assert_that!(tz.offset_at(0)).is_equal_to(0);
+    assert_that!(tz.offset_at(86400)).is_equal_to(3600);
+    assert_that!(tz.offset_at(10_000_000)).is_equal_to(3600);
+
+    assert_that!(TzFile::parse("Bad", b"nope")).is_err();
+    assert_that!(TzFile::load("../etc/passwd")).is_err();
+  }
+
+  #[test]
+  fn test_format_offset() {
+    let reference: DateTime<Utc> = "2020-11-25T12:00:00Z".parse().unwrap();
+    let at = |s: &str| format_offset(s.parse().unwrap(), reference);
+
+    assert_that!(at("2020-11-25T11:57:47Z")).is_equal_to("-00:02:13".to_string());
+    assert_that!(at("2020-11-25T12:00:00Z")).is_equal_to("+00:00:00".to_string());
+    assert_that!(at("2020-11-27T15:04:05Z")).is_equal_to("+2d 03:04:05".to_string());
+  }
+
+  #[test]
+  fn test_parse_target() {
+    let reference = DateTime::parse_from_rfc3339("2023-05-01T14:40:00+02:00").unwrap();
+    let parse = |s| parse_target(s, reference).map(|t| t.to_rfc3339());
+
+    assert_that!(parse("-10m")).is_equal_to(Ok("2023-05-01T12:30:00+00:00".to_string()));
+    assert_that!(parse("+1h30m")).is_equal_to(Ok("2023-05-01T14:10:00+00:00".to_string()));
+    assert_that!(parse("14:32")).is_equal_to(Ok("2023-05-01T12:32:00+00:00".to_string()));
+    assert_that!(parse("2023-05-01T14:32:10Z")).is_equal_to(Ok("2023-05-01T14:32:10+00:00".to_string()));
+    assert_that!(parse("2023-04-30 23:00")).is_equal_to(Ok("2023-04-30T21:00:00+00:00".to_string()));
+    assert_that!(parse("yesterday")).is_err();
+    assert_that!(parse("-10y")).is_err();
+  }
+}
@@ -0,0 +1,652 @@
+// (C) Copyright 2020 Hewlett Packard Enterprise Development LP
+
+//! Reads TOML config files into the same values as the equivalent YAML
+//!
+//! Only what config files need is supported: tables, arrays of tables, inline
+//! tables, arrays, strings, integers, floats, and booleans. Dates and times
+//! are read as strings.
+
+use std::collections::HashSet;
+
+use serde_yaml::{Mapping, Number, Value};
+use simple_error::SimpleResult;
+
+struct TomlParser {
+  chars: Vec<char>,
+  pos: usize,
+  line: usize,
+
+  /// `[table]` headers seen so far, which may only appear once
+  tables: HashSet<Vec<String>>
+}
+
+/// Finds the table at `path` below `root`, creating any missing tables; arrays
+/// of tables resolve to their last element
+fn table_at<'a>(root: &'a mut Mapping, path: &[String]) -> Result<&'a mut Mapping, String> {
+  let mut table = root;
+  for key in path {
+    let value = table.entry(Value::from(key.as_str()))
+      .or_insert_with(|| Value::Mapping(Mapping::new()));
+
+    table = match value {
+      Value::Mapping(mapping) => mapping,
+      Value::Sequence(items) => match items.last_mut() {
+        Some(Value::Mapping(mapping)) => mapping,
+        _ => return Err(format!("{} is not a table", key))
+      },
+      _ => return Err(format!("{} is not a table", key))
+    };
+  }
+
+  Ok(table)
+}
+
+/// Sets a (possibly dotted) key in `table`, which must not already be set
+fn insert(table: &mut Mapping, key: &[String], value: Value) -> Result<(), String> {
+  let (last, parents) = match key.split_last() {
+    Some(split) => split,
+    None => return Err("empty key".to_string())
+  };
+
+  let table = table_at(table, parents)?;
+  let last = Value::from(last.as_str());
+  if table.contains_key(&last) {
+    return Err(format!("duplicate key {}", key.join(".")));
+  }
+
+  table.insert(last, value);
+  Ok(())
+}
+
+fn is_bare_key(c: char) -> bool {
+  c.is_ascii_alphanumeric() || c == '_' || c == '-'
+}
+
+/// Parses a number, or a date or time (as a string), from a bare value
+fn parse_scalar(token: &str) -> Option<Value> {
+  let digits = token.replace('_', "");
+  let (sign, unsigned) = match digits.strip_prefix('-') {
+    Some(rest) => (-1, rest),
+    None => (1, digits.strip_prefix('+').unwrap_or(&digits))
+  };
+
+  let radix = match unsigned.get(..2) {
+    Some("0x") => Some(16),
+    Some("0o") => Some(8),
+    Some("0b") => Some(2),
+    _ => None
+  };
+
+  if let Some(radix) = radix {
+    return i64::from_str_radix(&unsigned[2..], radix).ok().map(|n| Value::from(sign * n));
+  }
+
+  if let Ok(n) = digits.parse::<i64>() {
+    return Some(Value::from(n));
+  }
+
+  match unsigned {
+    "inf" => return Some(Value::Number(Number::from(f64::INFINITY * sign as f64))),
+    "nan" => return Some(Value::Number(Number::from(f64::NAN))),
+    _ => ()
+  }
+
+  let is_float = unsigned.starts_with(|c: char| c.is_ascii_digit())
+    && unsigned.chars().all(|c| c.is_ascii_digit() || "+-.eE".contains(c));
+  if is_float {
+    if let Ok(n) = digits.parse::<f64>() {
+      return Some(Value::Number(Number::from(n)));
+    }
+  }
+
+  // dates and times, e.g. 1979-05-27T07:32:00Z or 07:32:00
+  let is_datetime = token.starts_with(|c: char| c.is_ascii_digit())
+    && token.contains(['-', ':'])
+    && token.chars().all(|c| c.is_ascii_alphanumeric() || "-:.+ ".contains(c));
+  if is_datetime {
+    return Some(Value::from(token));
+  }
+
+  None
+}
+
+impl TomlParser {
+  fn peek(&self) -> Option<char> {
+    self.chars.get(self.pos).cloned()
+  }
+
+  fn peek_at(&self, offset: usize) -> Option<char> {
+    self.chars.get(self.pos + offset).cloned()
+  }
+
+  fn starts_with(&self, s: &str) -> bool {
+    s.chars().enumerate().all(|(i, c)| self.peek_at(i) == Some(c))
+  }
+
+  fn bump(&mut self) -> Option<char> {
+    let c = self.peek()?;
+    self.pos += 1;
+    if c == '\n' {
+      self.line += 1;
+    }
+
+    Some(c)
+  }
+
+  fn error<T>(&self, message: &str) -> SimpleResult<T> {
+    bail!("line {}: {}", self.line, message)
+  }
+
+  fn expect(&mut self, c: char) -> SimpleResult<()> {
+    match self.peek() {
+      Some(found) if found == c => {
+        self.bump();
+        Ok(())
+      },
+      Some(found) => self.error(&format!("expected '{}', found '{}'", c, found)),
+      None => self.error(&format!("expected '{}', found end of file", c))
+    }
+  }
+
+  /// Skips spaces and tabs, but not newlines
+  fn skip_spaces(&mut self) {
+    while let Some(' ') | Some('\t') = self.peek() {
+      self.bump();
+    }
+  }
+
+  fn skip_comment(&mut self) {
+    if self.peek() == Some('#') {
+      while !matches!(self.peek(), None | Some('\n')) {
+        self.bump();
+      }
+    }
+  }
+
+  /// Skips whitespace, newlines, and comments, e.g. between array items
+  fn skip_blank(&mut self) {
+    loop {
+      self.skip_spaces();
+      self.skip_comment();
+
+      match self.peek() {
+        Some('\n') | Some('\r') => {
+          self.bump();
+        },
+        _ => return
+      }
+    }
+  }
+
+  /// Checks nothing but a comment follows on the current line
+  fn end_line(&mut self) -> SimpleResult<()> {
+    self.skip_spaces();
+    self.skip_comment();
+
+    match self.peek() {
+      None => Ok(()),
+      Some('\r') if self.peek_at(1) == Some('\n') => {
+        self.bump();
+        self.bump();
+        Ok(())
+      },
+      Some('\n') => {
+        self.bump();
+        Ok(())
+      },
+      Some(c) => self.error(&format!("expected the end of the line, found '{}'", c))
+    }
+  }
+
+  /// Parses a key, which may be dotted, e.g. `rules."style".color`
+  fn parse_key(&mut self) -> SimpleResult<Vec<String>> {
+    let mut key = Vec::new();
+
+    loop {
+      self.skip_spaces();
+      let part = match self.peek() {
+        Some('"') => self.parse_basic_string()?,
+        Some('\'') => self.parse_literal_string()?,
+        Some(c) if is_bare_key(c) => {
+          let mut part = String::new();
+          while let Some(c) = self.peek().filter(|c| is_bare_key(*c)) {
+            part.push(c);
+            self.bump();
+          }
+
+          part
+        },
+        _ => return self.error("expected a key")
+      };
+
+      key.push(part);
+      self.skip_spaces();
+
+      if self.peek() == Some('.') {
+        self.bump();
+      } else {
+        return Ok(key);
+      }
+    }
+  }
+
+  fn parse_escape(&mut self) -> SimpleResult<char> {
+    let c = match self.bump() {
+      Some('b') => '\u{8}',
+      Some('t') => '\t',
+      Some('n') => '\n',
+      Some('f') => '\u{c}',
+      Some('r') => '\r',
+      Some('e') => '\u{1b}',
+      Some('"') => '"',
+      Some('\\') => '\\',
+      Some(u @ 'u') | Some(u @ 'U') => {
+        let len = if u == 'u' { 4 } else { 8 };
+        let hex: String = (0..len).filter_map(|_| self.bump()).collect();
+
+        match u32::from_str_radix(&hex, 16).ok().and_then(std::char::from_u32) {
+          Some(c) => c,
+          None => return self.error(&format!("invalid unicode escape \\{}{}", u, hex))
+        }
+      },
+      Some(c) => return self.error(&format!("invalid escape \\{}", c)),
+      None => return self.error("unterminated string")
+    };
+
+    Ok(c)
+  }
+
+  fn parse_basic_string(&mut self) -> SimpleResult<String> {
+    self.expect('"')?;
+
+    let mut s = String::new();
+    loop {
+      if self.peek() == Some('\n') {
+        return self.error("unterminated string");
+      }
+
+      match self.bump() {
+        Some('"') => return Ok(s),
+        Some('\\') => s.push(self.parse_escape()?),
+        None => return self.error("unterminated string"),
+        Some(c) => s.push(c)
+      }
+    }
+  }
+
+  fn parse_literal_string(&mut self) -> SimpleResult<String> {
+    self.expect('\'')?;
+
+    let mut s = String::new();
+    loop {
+      if self.peek() == Some('\n') {
+        return self.error("unterminated string");
+      }
+
+      match self.bump() {
+        Some('\'') => return Ok(s),
+        None => return self.error("unterminated string"),
+        Some(c) => s.push(c)
+      }
+    }
+  }
+
+  /// Parses a `"""` or `'''` string, which may span lines
+  fn parse_multiline_string(&mut self, quote: char) -> SimpleResult<String> {
+    for _ in 0..3 {
+      self.expect(quote)?;
+    }
+
+    // a newline right after the opening quotes isn't included
+    if self.starts_with("\r\n") {
+      self.bump();
+    }
+    if self.peek() == Some('\n') {
+      self.bump();
+    }
+
+    let mut s = String::new();
+    loop {
+      match self.peek() {
+        Some(c) if c == quote => {
+          // up to two quotes may directly precede the closing ones
+          let mut quotes = 0;
+          while self.peek() == Some(quote) && quotes < 5 {
+            self.bump();
+            quotes += 1;
+          }
+
+          if quotes >= 3 {
+            s.extend(std::iter::repeat(quote).take(quotes - 3));
+            return Ok(s);
+          }
+
+          s.extend(std::iter::repeat(quote).take(quotes));
+        },
+        Some('\\') if quote == '"' => {
+          self.bump();
+
+          // a backslash at the end of a line trims the following whitespace
+          let rest = &self.chars[self.pos..];
+          let blank = rest.iter().take_while(|c| **c == ' ' || **c == '\t').count();
+          if matches!(rest.get(blank), Some('\n') | Some('\r')) {
+            while let Some(' ') | Some('\t') | Some('\n') | Some('\r') = self.peek() {
+              self.bump();
+            }
+          } else {
+            s.push(self.parse_escape()?);
+          }
+        },
+        Some(_) => s.extend(self.bump()),
+        None => return self.error("unterminated string")
+      }
+    }
+  }
+
+  fn parse_array(&mut self) -> SimpleResult<Value> {
+    self.expect('[')?;
+
+    let mut items = Vec::new();
+    loop {
+      self.skip_blank();
+      if self.peek() == Some(']') {
+        self.bump();
+        return Ok(Value::Sequence(items));
+      }
+
+      items.push(self.parse_value()?);
+      self.skip_blank();
+
+      match self.peek() {
+        Some(',') => {
+          self.bump();
+        },
+        Some(']') => (),
+        _ => return self.error("expected ',' or ']' in array")
+      }
+    }
+  }
+
+  fn parse_inline_table(&mut self) -> SimpleResult<Value> {
+    self.expect('{')?;
+
+    let mut table = Mapping::new();
+    loop {
+      self.skip_blank();
+      if self.peek() == Some('}') {
+        self.bump();
+        return Ok(Value::Mapping(table));
+      }
+
+      let key = self.parse_key()?;
+      self.expect('=')?;
+      self.skip_spaces();
+
+      let value = self.parse_value()?;
+      if let Err(e) = insert(&mut table, &key, value) {
+        return self.error(&e);
+      }
+
+      self.skip_blank();
+      match self.peek() {
+        Some(',') => {
+          self.bump();
+        },
+        Some('}') => (),
+        _ => return self.error("expected ',' or '}' in inline table")
+      }
+    }
+  }
+
+  fn parse_value(&mut self) -> SimpleResult<Value> {
+    if self.starts_with("\"\"\"") {
+      return self.parse_multiline_string('"').map(Value::from);
+    } else if self.starts_with("'''") {
+      return self.parse_multiline_string('\'').map(Value::from);
+    }
+
+    match self.peek() {
+      Some('"') => return self.parse_basic_string().map(Value::from),
+      Some('\'') => return self.parse_literal_string().map(Value::from),
+      Some('[') => return self.parse_array(),
+      Some('{') => return self.parse_inline_table(),
+      _ => ()
+    }
+
+    let mut token = String::new();
+    while let Some(c) = self.peek() {
+      // a space may separate a date and time, e.g. `1979-05-27 07:32:00`
+      let datetime_space = c == ' '
+        && token.len() == 10
+        && token.matches('-').count() == 2
+        && self.peek_at(1).map_or(false, |c| c.is_ascii_digit());
+
+      if c.is_whitespace() && !datetime_space || ",]}#".contains(c) {
+        break;
+      }
+
+      token.push(c);
+      self.bump();
+    }
+
+    match token.as_str() {
+      "true" => return Ok(Value::Bool(true)),
+      "false" => return Ok(Value::Bool(false)),
+      "" => return self.error("expected a value"),
+      _ => ()
+    }
+
+    match parse_scalar(&token) {
+      Some(value) => Ok(value),
+      None => self.error(&format!("invalid value: {}", token))
+    }
+  }
+
+  /// Parses a `[table]` or `[[array]]` header, returning the table's path
+  fn parse_header(&mut self, root: &mut Mapping) -> SimpleResult<Vec<String>> {
+    self.expect('[')?;
+    let array = self.peek() == Some('[');
+    if array {
+      self.bump();
+    }
+
+    let path = self.parse_key()?;
+    self.expect(']')?;
+    if array {
+      self.expect(']')?;
+    }
+
+    let result = match path.split_last() {
+      Some((last, parents)) if array => table_at(root, parents).and_then(|table| {
+        let items = table.entry(Value::from(last.as_str()))
+          .or_insert_with(|| Value::Sequence(Vec::new()));
+
+        match items {
+          Value::Sequence(items) => {
+            items.push(Value::Mapping(Mapping::new()));
+            Ok(())
+          },
+          _ => Err(format!("{} is not an array of tables", path.join(".")))
+        }
+      }),
+      _ if !self.tables.insert(path.clone()) => {
+        Err(format!("duplicate table [{}]", path.join(".")))
+      },
+      _ => table_at(root, &path).map(|_| ())
+    };
+
+    match result {
+      Ok(()) => Ok(path),
+      Err(e) => self.error(&e)
+    }
+  }
+
+  fn parse(&mut self) -> SimpleResult<Value> {
+    let mut root = Mapping::new();
+    let mut table = Vec::new();
+
+    loop {
+      self.skip_blank();
+
+      match self.peek() {
+        None => return Ok(Value::Mapping(root)),
+        Some('[') => table = self.parse_header(&mut root)?,
+        Some(_) => {
+          let key = self.parse_key()?;
+          self.expect('=')?;
+          self.skip_spaces();
+
+          let value = self.parse_value()?;
+          let result = table_at(&mut root, &table)
+            .and_then(|table| insert(table, &key, value));
+
+          if let Err(e) = result {
+            return self.error(&e);
+          }
+        }
+      }
+
+      self.end_line()?;
+    }
+  }
+}
+
+/// Parses a TOML document into the equivalent YAML value
+pub fn parse(s: &str) -> SimpleResult<Value> {
+  TomlParser {
+    chars: s.chars().collect(),
+    pos: 0,
+    line: 1,
+    tables: HashSet::new()
+  }.parse()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use spectral::prelude::*;
+
+  fn yaml(s: &str) -> Value {
+    serde_yaml::from_str(s).unwrap()
+  }
+
+  #[test]
+  fn test_tables() {
+    let toml = concat!(
+      "# comments are ignored\n",
+      "title = \"logs\" # here too\n",
+      "\n",
+      "[fields]\n",
+      "show = [\"pod\", \"status\"]\n",
+      "max_width = 40\n",
+      "\n",
+      "[flatten]\n",
+      "depth.max = 2\n",
+      "\"quoted key\" = true\n",
+      "\n",
+      "[profiles.prod-api]\n",
+      "namespace = 'prod'\n"
+    );
+
+    assert_that!(parse(toml)).is_ok_containing(yaml(concat!(
+      "title: logs\n",
+      "fields: {show: [pod, status], max_width: 40}\n",
+      "flatten: {depth: {max: 2}, quoted key: true}\n",
+      "profiles: {prod-api: {namespace: prod}}\n"
+    )));
+  }
+
+  #[test]
+  fn test_arrays_of_tables() {
+    let toml = concat!(
+      "[[rules.style]]\n",
+      "field = \"status\"\n",
+      "gte = 500\n",
+      "\n",
+      "[[rules.style]]\n",
+      "message = \"timeout\"\n",
+      "\n",
+      "[rules.style.when]\n",
+      "level = \"error\"\n"
+    );
+
+    assert_that!(parse(toml)).is_ok_containing(yaml(concat!(
+      "rules:\n",
+      "  style:\n",
+      "    - {field: status, gte: 500}\n",
+      "    - {message: timeout, when: {level: error}}\n"
+    )));
+  }
+
+  #[test]
+  fn test_values() {
+    let toml = concat!(
+      "ints = [1, +2, -3, 1_000, 0xff, 0o17, 0b101]\n",
+      "floats = [1.5, -0.25, 5e2]\n",
+      "bools = [true, false]\n",
+      "mixed = [\"WRN\", 30,]\n",
+      "nested = [[1, 2], []]\n",
+      "inline = { style = \"red\", when.level = \"error\" }\n",
+      "multiline = [\n",
+      "  \"a\", # first\n",
+      "  \"b\",\n",
+      "]\n",
+      "date = 1979-05-27T07:32:00Z\n",
+      "spaced = 1979-05-27 07:32:00\n",
+      "time = 07:32:00\n"
+    );
+
+    assert_that!(parse(toml)).is_ok_containing(yaml(concat!(
+      "ints: [1, 2, -3, 1000, 255, 15, 5]\n",
+      "floats: [1.5, -0.25, 500.0]\n",
+      "bools: [true, false]\n",
+      "mixed: [WRN, 30]\n",
+      "nested: [[1, 2], []]\n",
+      "inline: {style: red, when: {level: error}}\n",
+      "multiline: [a, b]\n",
+      "date: '1979-05-27T07:32:00Z'\n",
+      "spaced: '1979-05-27 07:32:00'\n",
+      "time: '07:32:00'\n"
+    )));
+  }
+
+  #[test]
+  fn test_strings() {
+    let toml = concat!(
+      "basic = \"tab\\there \\\"quoted\\\" \\u00e9\"\n",
+      "literal = '^\\d+ \\w+$'\n",
+      "multi = \"\"\"\n",
+      "first\n",
+      "second \\\n",
+      "    continued\"\"\"\n",
+      "quotes = \"\"\"a \"\"quoted\"\" b\"\"\"\"\n",
+      "raw = '''\n",
+      "C:\\path\n",
+      "'''\n"
+    );
+
+    let value = parse(toml).unwrap();
+    assert_that!(value["basic"].as_str()).is_equal_to(Some("tab\there \"quoted\" é"));
+    assert_that!(value["literal"].as_str()).is_equal_to(Some("^\\d+ \\w+$"));
+    assert_that!(value["multi"].as_str()).is_equal_to(Some("first\nsecond continued"));
+    assert_that!(value["quotes"].as_str()).is_equal_to(Some("a \"\"quoted\"\" b\""));
+    assert_that!(value["raw"].as_str()).is_equal_to(Some("C:\\path\n"));
+  }
+
+  #[test]
+  fn test_errors() {
+    let error = |toml: &str| parse(toml).unwrap_err().as_str().to_string();
+
+    assert_that!(error("a = 1\na = 2\n")).is_equal_to("line 2: duplicate key a".to_string());
+    assert_that!(error("[a]\n[a]\n")).is_equal_to("line 2: duplicate table [a]".to_string());
+    assert_that!(error("a = 1\n[a.b]\n")).is_equal_to("line 2: a is not a table".to_string());
+    assert_that!(error("a = \"open\n")).is_equal_to("line 1: unterminated string".to_string());
+    assert_that!(error("a = 1 b = 2\n"))
+      .is_equal_to("line 1: expected the end of the line, found 'b'".to_string());
+    assert_that!(error("a = nope\n")).is_equal_to("line 1: invalid value: nope".to_string());
+    assert_that!(error("a\n")).is_equal_to("line 1: expected '=', found '\n'".to_string());
+    assert_that!(error("a = [1 2]\n"))
+      .is_equal_to("line 1: expected ',' or ']' in array".to_string());
+  }
+}